@@ -0,0 +1,389 @@
+//! Comparison of many file pairs listed in a manifest, started with `json-diff batch`
+//!
+//! Each manifest line is `left<TAB>right[<TAB>profile]`; blank lines and lines
+//! starting with `#` are skipped, and relative paths are taken from the manifest's
+//! directory. Pairs that fail to compare are reported without stopping the batch;
+//! pairs with a file that is not valid JSON are reported as unparseable, with the
+//! parse error of each such side.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use serde::Serialize;
+use json_diff_core::{compare_files, read_file_limited, CompareOptions, DiffResult, JsonDiffError, OutputStyle};
+
+use crate::summary::{Summary, DEFAULT_SUMMARY_DEPTH, DEFAULT_SUMMARY_TOP};
+use crate::{load_profile, parse_size, source_date_epoch, OutputFormat};
+
+#[derive(Parser, Debug)]
+#[command(name = "json-diff batch", about = "Compare the file pairs listed in a manifest")]
+pub struct BatchArgs {
+    /// Manifest with one `left<TAB>right[<TAB>profile]` pair per line
+    pub manifest: PathBuf,
+
+    /// Profile for the pairs whose line names none
+    #[arg(short, long)]
+    pub profile: Option<PathBuf>,
+
+    /// Number of pairs compared at the same time
+    #[arg(short, long, default_value_t = 1)]
+    pub jobs: usize,
+
+    /// Output format of the combined report (text or json)
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Output file for the combined report (stdout if not specified)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Use symbols instead of readable text for diff types
+    #[arg(short = 'S', long)]
+    pub symbols: bool,
+
+    /// Leave the timestamp out of the reports
+    #[arg(long)]
+    pub no_timestamp: bool,
+
+    /// Print only the summary of the differences of all pairs together, see
+    /// `json-diff --summary-only`, and the number of pairs per outcome
+    #[arg(long)]
+    pub summary_only: bool,
+
+    /// Steps below the root the paths of --summary-only are cut to
+    #[arg(long, value_name = "DEPTH", default_value_t = DEFAULT_SUMMARY_DEPTH, requires = "summary_only")]
+    pub summary_depth: usize,
+
+    /// Number of paths --summary-only lists
+    #[arg(long, value_name = "COUNT", default_value_t = DEFAULT_SUMMARY_TOP, requires = "summary_only")]
+    pub summary_top: usize,
+
+    /// Compare the pairs with a file that is not valid JSON line by line, see
+    /// `json-diff --text-fallback`, rather than reporting them as unparseable
+    #[arg(long)]
+    pub text_fallback: bool,
+
+    /// Largest input file that will be read, e.g. 500MB or 2GiB; a pair with a larger
+    /// file is reported as failed
+    #[arg(long, value_name = "SIZE", default_value = "1GiB", value_parser = parse_size)]
+    pub max_input_size: u64,
+}
+
+/// A pair of the manifest
+#[derive(Debug, Clone, PartialEq)]
+struct Pair {
+    /// 1-based line of the manifest
+    line: usize,
+    left: PathBuf,
+    right: PathBuf,
+    profile: Option<PathBuf>,
+}
+
+/// How the comparison of a pair ended
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum PairStatus {
+    Equal { result: DiffResult },
+    Differing { result: DiffResult },
+    /// A file of the pair is not valid JSON; the parse error of each such side
+    Unparseable {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        left_error: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        right_error: Option<String>,
+    },
+    Error { error: String },
+}
+
+/// A compared pair, as it appears in the combined report
+#[derive(Debug, Serialize)]
+pub struct PairReport {
+    pub line: usize,
+    pub left: PathBuf,
+    pub right: PathBuf,
+    #[serde(flatten)]
+    pub status: PairStatus,
+}
+
+/// Number of pairs per outcome
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct BatchSummary {
+    pub pairs: usize,
+    pub equal: usize,
+    pub differing: usize,
+    pub unparseable: usize,
+    pub errored: usize,
+}
+
+impl BatchSummary {
+    /// Exit code of the batch: 0 if every pair is equal, 1 if some differ, 2 if some
+    /// could not be parsed or failed
+    pub fn exit_code(&self) -> i32 {
+        if self.errored > 0 || self.unparseable > 0 {
+            2
+        } else if self.differing > 0 {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct BatchReport<'a> {
+    pairs: &'a [PairReport],
+    summary: &'a BatchSummary,
+}
+
+/// The report of `--summary-only`
+#[derive(Serialize)]
+struct BatchSummaryReport<'a> {
+    summary: &'a BatchSummary,
+    differences: &'a Summary,
+}
+
+/// Compare every pair of the manifest named in `args` and write the combined report
+pub fn batch(args: BatchArgs) -> Result<BatchSummary> {
+    if !matches!(args.format, OutputFormat::Text | OutputFormat::Json) {
+        bail!("batch only supports --format text and --format json");
+    }
+
+    let content = fs::read_to_string(&args.manifest)
+        .with_context(|| format!("Failed to read manifest {}", args.manifest.display()))?;
+    let base = args.manifest.parent().unwrap_or(Path::new(""));
+    let pairs = parse_manifest(&content, base);
+
+    let timestamp = source_date_epoch()?;
+    let default_options = CompareOptions {
+        timestamp,
+        text_fallback: args.text_fallback,
+        max_input_bytes: Some(args.max_input_size),
+        ..CompareOptions::default()
+    };
+
+    // Each profile is loaded once, however many pairs use it
+    let mut profiles: HashMap<PathBuf, Result<CompareOptions, String>> = HashMap::new();
+    for profile in pairs.iter().filter_map(|pair| pair.as_ref().ok()?.profile.as_ref()).chain(args.profile.as_ref()) {
+        profiles.entry(profile.clone()).or_insert_with(|| {
+            let mut options = load_profile(profile)
+                .map_err(|e| format!("Failed to load profile {}: {}", profile.display(), e))?;
+            options.timestamp = timestamp;
+            options.text_fallback |= args.text_fallback;
+            options.max_input_bytes = Some(args.max_input_size);
+            Ok(options)
+        });
+    }
+
+    let compare = |pair: &Pair| -> PairStatus {
+        let options = match pair.profile.as_ref().or(args.profile.as_ref()) {
+            Some(profile) => match &profiles[profile] {
+                Ok(options) => options,
+                Err(error) => return PairStatus::Error { error: error.clone() },
+            },
+            None => &default_options,
+        };
+        match compare_files(&pair.left, &pair.right, options) {
+            Ok(mut result) => {
+                if args.no_timestamp {
+                    result.timestamp = None;
+                }
+                if has_differences(&result) {
+                    PairStatus::Differing { result }
+                } else {
+                    PairStatus::Equal { result }
+                }
+            }
+            Err(JsonDiffError::Unparseable { left, right }) => PairStatus::Unparseable { left_error: left, right_error: right },
+            Err(e) => PairStatus::Error { error: e.to_string() },
+        }
+    };
+
+    let reports = run_jobs(&pairs, args.jobs, |pair| match pair {
+        Ok(pair) => PairReport {
+            line: pair.line,
+            left: pair.left.clone(),
+            right: pair.right.clone(),
+            status: compare(pair),
+        },
+        Err((line, error)) => PairReport {
+            line: *line,
+            left: PathBuf::new(),
+            right: PathBuf::new(),
+            status: PairStatus::Error { error: error.clone() },
+        },
+    });
+
+    let mut summary = BatchSummary { pairs: reports.len(), ..BatchSummary::default() };
+    for report in &reports {
+        match report.status {
+            PairStatus::Equal { .. } => summary.equal += 1,
+            PairStatus::Differing { .. } => summary.differing += 1,
+            PairStatus::Unparseable { .. } => summary.unparseable += 1,
+            PairStatus::Error { .. } => summary.errored += 1,
+        }
+    }
+
+    let style = if args.symbols { OutputStyle::Symbols } else { OutputStyle::Readable };
+    let text = match (args.format, args.summary_only) {
+        (OutputFormat::Json, false) => serde_json::to_string_pretty(&BatchReport { pairs: &reports, summary: &summary })
+            .context("Failed to serialize batch report")?,
+        (_, false) => format_text(&reports, &summary, style),
+        (format, true) => {
+            let differences = summarize(&reports, &args)?;
+            match format {
+                OutputFormat::Json => serde_json::to_string_pretty(&BatchSummaryReport { summary: &summary, differences: &differences })
+                    .context("Failed to serialize batch summary")?,
+                _ => format!("{}\n\n{}", differences.format_text(), format_summary(&summary)),
+            }
+        }
+    };
+    match &args.output {
+        Some(output_path) => fs::write(output_path, text).context("Failed to write batch report to file")?,
+        None => println!("{}", text),
+    }
+
+    Ok(summary)
+}
+
+/// The pairs of a manifest, or the line and problem of each malformed line
+fn parse_manifest(content: &str, base: &Path) -> Vec<Result<Pair, (usize, String)>> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(index, line)| {
+            let line_number = index + 1;
+            let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+            if !(2..=3).contains(&fields.len()) || fields.iter().any(|field| field.is_empty()) {
+                return Err((line_number, format!("Manifest line {}: expected left<TAB>right[<TAB>profile]", line_number)));
+            }
+            Ok(Pair {
+                line: line_number,
+                left: base.join(fields[0]),
+                right: base.join(fields[1]),
+                profile: fields.get(2).map(|profile| base.join(profile)),
+            })
+        })
+        .collect()
+}
+
+/// Run `job` on every item with up to `jobs` threads, keeping the items' order
+fn run_jobs<T: Sync, R: Send>(items: &[T], jobs: usize, job: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new((0..items.len()).map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else { break };
+                let result = job(item);
+                results.lock().unwrap_or_else(|e| e.into_inner())[index] = Some(result);
+            });
+        }
+    });
+
+    results.into_inner().unwrap_or_else(|e| e.into_inner()).into_iter().flatten().collect()
+}
+
+/// Whether a result holds anything besides ignored and tolerated entries
+fn has_differences(result: &DiffResult) -> bool {
+    result.iter().any(|entry| entry.diff_type.is_difference())
+}
+
+/// The combined text report: each pair under a heading, then the summary table
+fn format_text(reports: &[PairReport], summary: &BatchSummary, style: OutputStyle) -> String {
+    let mut text = String::new();
+    // Such as that of a pair compared line by line
+    let warnings = |result: &DiffResult| result.warnings.iter().map(|warning| format!("  warning: {}\n", warning)).collect::<String>();
+    for report in reports {
+        let pair = format!("{} <-> {}", report.left.display(), report.right.display());
+        match &report.status {
+            PairStatus::Equal { result } => text.push_str(&format!("=== Line {}: {}: equal\n{}\n", report.line, pair, warnings(result))),
+            PairStatus::Differing { result } => {
+                let count = result.iter().filter(|entry| entry.diff_type.is_difference()).count();
+                let plural = if count == 1 { "" } else { "s" };
+                text.push_str(&format!("=== Line {}: {}: {} difference{}\n", report.line, pair, count, plural));
+                text.push_str(&warnings(result));
+                text.push_str(&result.format_with(style));
+                text.push_str("\n\n");
+            }
+            PairStatus::Unparseable { left_error, right_error } => {
+                text.push_str(&format!("=== Line {}: {}: unparseable\n", report.line, pair));
+                for (side, error) in [("left", left_error), ("right", right_error)] {
+                    if let Some(error) = error {
+                        text.push_str(&format!("  {}: {}\n", side, error));
+                    }
+                }
+                text.push('\n');
+            }
+            PairStatus::Error { error } if report.left.as_os_str().is_empty() => {
+                text.push_str(&format!("=== Line {}: error: {}\n\n", report.line, error));
+            }
+            PairStatus::Error { error } => {
+                text.push_str(&format!("=== Line {}: {}: error: {}\n\n", report.line, pair, error));
+            }
+        }
+    }
+
+    text.push_str(&format_summary(summary));
+    text
+}
+
+/// The number of pairs per outcome, as a table
+fn format_summary(summary: &BatchSummary) -> String {
+    format!(
+        "SUMMARY\n  Pairs:       {}\n  Equal:       {}\n  Differing:   {}\n  Unparseable: {}\n  Errored:     {}",
+        summary.pairs, summary.equal, summary.differing, summary.unparseable, summary.errored
+    )
+}
+
+/// The summary of the differences of every compared pair together, over the lines of
+/// all their files
+fn summarize(reports: &[PairReport], args: &BatchArgs) -> Result<Summary> {
+    let mut results = Vec::new();
+    let (mut left_lines, mut right_lines) = (0, 0);
+    for report in reports {
+        if let PairStatus::Equal { result } | PairStatus::Differing { result } = &report.status {
+            left_lines += read_file_limited(&report.left, None)?.lines().count();
+            right_lines += read_file_limited(&report.right, None)?.lines().count();
+            results.push(result.clone());
+        }
+    }
+    Ok(Summary::new(&DiffResult::merge(results), (left_lines, right_lines), args.summary_depth, args.summary_top))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = "# nightly pairs\na.json\tb.json\n\n/abs/c.json\td.json\tp.toml\nonly-one.json\n";
+        let pairs = parse_manifest(manifest, Path::new("jobs"));
+
+        assert_eq!(pairs.len(), 3);
+        assert_eq!(pairs[0], Ok(Pair { line: 2, left: "jobs/a.json".into(), right: "jobs/b.json".into(), profile: None }));
+        assert_eq!(pairs[1], Ok(Pair {
+            line: 4,
+            left: "/abs/c.json".into(),
+            right: "jobs/d.json".into(),
+            profile: Some("jobs/p.toml".into()),
+        }));
+        assert_eq!(pairs[2], Err((5, "Manifest line 5: expected left<TAB>right[<TAB>profile]".to_string())));
+    }
+
+    #[test]
+    fn test_run_jobs_keeps_order() {
+        let items: Vec<u64> = (0..50).collect();
+        let results = run_jobs(&items, 4, |n| {
+            std::thread::sleep(std::time::Duration::from_millis(50 - n));
+            n * 2
+        });
+        assert_eq!(results, items.iter().map(|n| n * 2).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,65 @@
+//! On-disk cache of comparison results
+//!
+//! Results are stored as JSON under `<dir>/v<FORMAT_VERSION>/`, keyed by the
+//! checksums of both inputs and the fingerprint of the comparison options, so a
+//! report format upgrade starts from an empty cache.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use json_diff_core::{sha256_hex, CompareOptions, DiffResult, FORMAT_VERSION};
+
+pub struct ResultCache {
+    dir: PathBuf,
+}
+
+impl ResultCache {
+    pub fn new(root: &Path) -> Self {
+        Self { dir: root.join(format!("v{}", FORMAT_VERSION)) }
+    }
+
+    /// Remove every cached result, of all format versions
+    pub fn clear(root: &Path) -> Result<()> {
+        if root.exists() {
+            fs::remove_dir_all(root)
+                .with_context(|| format!("Failed to clear cache directory {}", root.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Cache key of a comparison of the given inputs with the given options
+    pub fn key(left_content: &str, right_content: &str, options: &CompareOptions) -> String {
+        let input = format!(
+            "{}\0{}\0{}",
+            sha256_hex(left_content.as_bytes()),
+            sha256_hex(right_content.as_bytes()),
+            options.fingerprint()
+        );
+        sha256_hex(input.as_bytes())
+    }
+
+    /// Load a cached result; unreadable entries count as misses
+    pub fn load(&self, key: &str) -> Option<DiffResult> {
+        let content = fs::read_to_string(self.entry_path(key)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn store(&self, key: &str, result: &DiffResult) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create cache directory {}", self.dir.display()))?;
+
+        // Write then rename, so concurrent runs never read a partial entry
+        let path = self.entry_path(key);
+        let partial = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        fs::write(&partial, serde_json::to_vec(result)?)
+            .with_context(|| format!("Failed to write cache entry {}", partial.display()))?;
+        fs::rename(&partial, &path)
+            .with_context(|| format!("Failed to write cache entry {}", path.display()))?;
+
+        Ok(())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
@@ -0,0 +1,314 @@
+//! Validation of a profile without comparing anything, started with `json-diff check-profile`
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use serde_json::Value;
+use json_diff_core::{value_paths, JsonPath};
+
+use crate::{PathRule, PreprocessRule, Profile};
+
+/// Matches listed per rule in the `--against` report before the rest are counted
+const MAX_LISTED_MATCHES: usize = 10;
+
+/// Sections whose rules cannot apply to ignored paths
+const SHADOWED_BY_IGNORE: [&str; 6] = ["unordered", "set", "tolerance", "decode", "numeric_strings", "allowed_transition"];
+
+/// Sections where one rule per path is enough
+const SINGLE_RULE_SECTIONS: [&str; 7] = ["ignore", "unordered", "set", "tolerance", "parse_embedded_json", "decode", "numeric_strings"];
+
+#[derive(Parser, Debug)]
+#[command(name = "json-diff check-profile", about = "Validate a profile without comparing files")]
+pub struct CheckProfileArgs {
+    /// Profile to validate
+    pub profile: PathBuf,
+
+    /// JSON document to list the paths each rule would match in
+    #[arg(long)]
+    pub against: Option<PathBuf>,
+}
+
+/// Problems found in a profile and, if a document was given, what its rules match
+#[derive(Debug, Default)]
+pub struct ProfileReport {
+    /// Problems that make the profile unusable or contradictory
+    pub errors: Vec<String>,
+    /// Rules that are redundant, overlap others, or match nothing
+    pub warnings: Vec<String>,
+    /// Each rule with the paths of the document it matches
+    pub matches: Vec<(String, Vec<String>)>,
+    /// Number of path rules in the profile
+    pub rules: usize,
+}
+
+/// A path rule of the profile and where it was written
+struct Rule<'a> {
+    section: &'static str,
+    index: usize,
+    rule: &'a PathRule,
+    /// What the rule sets for its paths, which duplicates should agree on
+    setting: Option<String>,
+    path: Option<JsonPath>,
+}
+
+impl Rule<'_> {
+    /// The rule as it is named in the report, e.g. ``ignore[2] `$.id` ``
+    fn name(&self) -> String {
+        let text = match self.rule {
+            PathRule::Path(path) => path.clone(),
+            PathRule::Pattern { pattern, .. } => format!("pattern '{}'", pattern),
+        };
+        format!("{}[{}] `{}`", self.section, self.index, text)
+    }
+
+    /// Whether both rules are written the same way
+    fn same_text(&self, other: &Rule) -> bool {
+        match (self.rule, other.rule) {
+            (PathRule::Path(a), PathRule::Path(b)) => a == b,
+            (
+                PathRule::Pattern { pattern: a, anchored: a_anchored, case_insensitive: a_case },
+                PathRule::Pattern { pattern: b, anchored: b_anchored, case_insensitive: b_case },
+            ) => a == b && a_anchored == b_anchored && a_case == b_case,
+            _ => false,
+        }
+    }
+
+    /// Whether every path `other` matches is also matched by this rule, as far as can be told
+    fn covers(&self, other: &Rule) -> bool {
+        if self.same_text(other) {
+            return true;
+        }
+        match (&self.path, &other.path, other.rule) {
+            (Some(path), Some(other_path), PathRule::Path(text)) => other_path.is_concrete() && path.matches(text),
+            _ => false,
+        }
+    }
+}
+
+impl PreprocessRule {
+    fn path(&self) -> &PathRule {
+        match self {
+            PreprocessRule::Remove { path, .. }
+            | PreprocessRule::SortArray { path, .. }
+            | PreprocessRule::Round { path, .. }
+            | PreprocessRule::Lowercase { path, .. }
+            | PreprocessRule::LowercaseKeys { path, .. } => path,
+        }
+    }
+}
+
+/// Check the profile in `content`, listing what its rules match in `against` if given
+pub fn check_profile_str(content: &str, against: Option<&Value>) -> ProfileReport {
+    let mut report = ProfileReport::default();
+    let profile: Profile = match toml::from_str(content) {
+        Ok(profile) => profile,
+        Err(e) => {
+            report.errors.push(format!("Failed to parse profile: {}", e));
+            return report;
+        }
+    };
+
+    let rules = collect_rules(&profile);
+    report.rules = rules.len();
+    for rule in &rules {
+        if let Err(e) = rule.rule.to_path() {
+            report.errors.push(format!("{}: {}", rule.name(), e));
+        }
+    }
+
+    for (j, later) in rules.iter().enumerate() {
+        for earlier in &rules[..j] {
+            if earlier.section == later.section && SINGLE_RULE_SECTIONS.contains(&later.section) {
+                check_duplicate(earlier, later, &mut report);
+            }
+        }
+        if SHADOWED_BY_IGNORE.contains(&later.section) {
+            for ignore in rules.iter().filter(|rule| rule.section == "ignore") {
+                check_ignored(ignore, later, &mut report);
+            }
+        }
+    }
+
+    if let Some(document) = against {
+        let paths = value_paths(document);
+        for rule in &rules {
+            let Some(path) = &rule.path else { continue };
+            let matched: Vec<String> = paths.iter().filter(|p| path.matches_in(p, document)).cloned().collect();
+            if matched.is_empty() {
+                report.warnings.push(format!("{}: matches nothing in the document", rule.name()));
+            }
+            report.matches.push((rule.name(), matched));
+        }
+    }
+
+    report
+}
+
+/// The path rules of every section, in the order they are listed in the profile
+fn collect_rules(profile: &Profile) -> Vec<Rule<'_>> {
+    fn plain(list: &[PathRule]) -> Vec<(&PathRule, Option<String>)> {
+        list.iter().map(|rule| (rule, None)).collect()
+    }
+
+    let sections = [
+        ("ignore", plain(&profile.ignore)),
+        ("unordered", plain(&profile.unordered)),
+        ("set", plain(&profile.set)),
+        ("tolerance", profile.tolerance.iter().map(|rule| (&rule.path, Some(format!("within {}", rule.within)))).collect()),
+        ("parse_embedded_json", plain(&profile.parse_embedded_json)),
+        ("decode", profile.decode.iter().map(|rule| (&rule.path, Some(format!("codec {}", rule.codec)))).collect()),
+        ("numeric_strings", plain(&profile.numeric_strings)),
+        ("allowed_transition", profile.allowed_transition.iter().map(|rule| (&rule.path, None)).collect()),
+        ("preprocess", profile.preprocess.iter().map(|rule| (rule.path(), None)).collect()),
+    ];
+
+    let mut rules = Vec::new();
+    for (section, list) in sections {
+        for (index, (rule, setting)) in list.into_iter().enumerate() {
+            // Invalid rules are reported as errors and take no part in later checks
+            let path = rule.to_path().ok();
+            rules.push(Rule { section, index, rule, setting, path });
+        }
+    }
+
+    rules
+}
+
+/// Report `later` if an earlier rule of its section already covers its paths
+fn check_duplicate(earlier: &Rule, later: &Rule, report: &mut ProfileReport) {
+    let same_setting = earlier.setting == later.setting;
+    if earlier.same_text(later) {
+        if same_setting {
+            report.warnings.push(format!("{}: duplicate of {}", later.name(), earlier.name()));
+        } else {
+            report.errors.push(format!(
+                "{}: conflicts with {} ({} vs {})",
+                later.name(), earlier.name(),
+                later.setting.as_deref().unwrap_or_default(), earlier.setting.as_deref().unwrap_or_default()
+            ));
+        }
+    } else if earlier.covers(later) || later.covers(earlier) {
+        let (narrow, wide) = if earlier.covers(later) { (later, earlier) } else { (earlier, later) };
+        if same_setting {
+            report.warnings.push(format!("{}: already covered by {}", narrow.name(), wide.name()));
+        } else {
+            report.warnings.push(format!("{}: overlaps {} with a different setting", narrow.name(), wide.name()));
+        }
+    }
+}
+
+/// Report `rule` if an ignore rule keeps it from applying to some or all of its paths
+fn check_ignored(ignore: &Rule, rule: &Rule, report: &mut ProfileReport) {
+    if ignore.covers(rule) {
+        report.errors.push(format!("{}: never applies, its paths are ignored by {}", rule.name(), ignore.name()));
+    } else if rule.covers(ignore) {
+        report.warnings.push(format!("{}: does not apply where {} ignores", rule.name(), ignore.name()));
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for error in &self.errors {
+            writeln!(f, "error: {}", error)?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "warning: {}", warning)?;
+        }
+
+        if !self.matches.is_empty() {
+            writeln!(f, "Matches:")?;
+            for (rule, paths) in &self.matches {
+                let listed = paths.iter().take(MAX_LISTED_MATCHES).map(String::as_str).collect::<Vec<_>>().join(", ");
+                match paths.len() {
+                    0 => writeln!(f, "  {}: nothing", rule)?,
+                    n if n > MAX_LISTED_MATCHES => writeln!(f, "  {}: {} and {} more", rule, listed, n - MAX_LISTED_MATCHES)?,
+                    _ => writeln!(f, "  {}: {}", rule, listed)?,
+                }
+            }
+        }
+
+        write!(f, "{} rules, {} errors, {} warnings", self.rules, self.errors.len(), self.warnings.len())
+    }
+}
+
+/// Print the report on the profile named in `args`, failing if it has errors
+pub fn check_profile(args: CheckProfileArgs) -> Result<()> {
+    let content = fs::read_to_string(&args.profile)
+        .with_context(|| format!("Failed to read profile {}", args.profile.display()))?;
+    let against = match &args.against {
+        Some(path) => {
+            let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            Some(serde_json::from_str::<Value>(&text).with_context(|| format!("Failed to parse {}", path.display()))?)
+        }
+        None => None,
+    };
+
+    let report = check_profile_str(&content, against.as_ref());
+    println!("{}", report);
+    if !report.errors.is_empty() {
+        bail!("{} is not a valid profile", args.profile.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_valid_profile() {
+        let report = check_profile_str(
+            "ignore = [\"$.id\", { pattern = '\\$\\.meta\\..*' }]\nunordered = [\"$.tags\"]\n\n[[tolerance]]\npath = \"$.price\"\nwithin = 0.01\n",
+            None,
+        );
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        assert_eq!(report.to_string(), "4 rules, 0 errors, 0 warnings");
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let report = check_profile_str("ignore = [{ pattern = '(unclosed' }, \"id\"]", None);
+        assert_eq!(report.errors.len(), 2);
+        assert!(report.errors[0].starts_with("ignore[0] `pattern '(unclosed'`: "), "{}", report.errors[0]);
+        assert!(report.errors[1].starts_with("ignore[1] `id`: Invalid JSON path"), "{}", report.errors[1]);
+
+        let report = check_profile_str("ignore = 3", None);
+        assert!(report.errors[0].starts_with("Failed to parse profile"));
+    }
+
+    #[test]
+    fn test_conflicts_and_duplicates() {
+        let report = check_profile_str(
+            "ignore = [\"$.items\", \"$.a.*\", \"$.a.b\", \"$.a.*\"]\nunordered = [\"$.items\", \"$.list[*]\"]\n\n\
+             [[tolerance]]\npath = \"$.p\"\nwithin = 0.1\n\n[[tolerance]]\npath = \"$.p\"\nwithin = 0.5\n",
+            None,
+        );
+        assert_eq!(report.errors, vec![
+            "unordered[0] `$.items`: never applies, its paths are ignored by ignore[0] `$.items`",
+            "tolerance[1] `$.p`: conflicts with tolerance[0] `$.p` (within 0.5 vs within 0.1)",
+        ]);
+        assert_eq!(report.warnings, vec![
+            "ignore[2] `$.a.b`: already covered by ignore[1] `$.a.*`",
+            "ignore[3] `$.a.*`: duplicate of ignore[1] `$.a.*`",
+            "ignore[2] `$.a.b`: already covered by ignore[3] `$.a.*`",
+        ]);
+    }
+
+    #[test]
+    fn test_matches_against_document() {
+        let document = json!({"id": 1, "items": [{"ts": 1}, {"ts": 2}], "name": "x"});
+        let report = check_profile_str("ignore = [\"$.items[*].ts\", \"$.missing\"]\nunordered = [\"$.items\"]", Some(&document));
+
+        assert_eq!(report.matches, vec![
+            ("ignore[0] `$.items[*].ts`".to_string(), vec!["$.items[0].ts".to_string(), "$.items[1].ts".to_string()]),
+            ("ignore[1] `$.missing`".to_string(), vec![]),
+            ("unordered[0] `$.items`".to_string(), vec!["$.items".to_string()]),
+        ]);
+        assert_eq!(report.warnings, vec!["ignore[1] `$.missing`: matches nothing in the document"]);
+        assert!(report.to_string().contains("  ignore[1] `$.missing`: nothing\n"));
+    }
+}
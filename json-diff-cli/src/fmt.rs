@@ -0,0 +1,112 @@
+//! Formatting of one JSON file in canonical form, started with `json-diff fmt`
+//!
+//! The file is written as [`format_canonical`] writes values, the same code that
+//! serializes values for fingerprints. `--check` only tells whether the file is
+//! already written that way, for pre-commit hooks.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use anyhow::{Context, Result};
+use clap::Parser;
+use serde_json::Value;
+use json_diff_core::{format_canonical, CanonicalOptions};
+
+#[derive(Parser, Debug)]
+#[command(name = "json-diff fmt", about = "Write a JSON file in canonical form")]
+pub struct FmtArgs {
+    /// JSON file to format (stdin if `-`)
+    #[arg(default_value = "-")]
+    pub input: PathBuf,
+
+    /// Output file (stdout if not specified)
+    #[arg(short, long, conflicts_with = "check")]
+    pub output: Option<PathBuf>,
+
+    /// Sort object keys (without the key-order feature they are always sorted, as the
+    /// parser does not keep their order)
+    #[arg(long)]
+    pub sort_keys: bool,
+
+    /// Spaces per level of nesting; 0 writes the value on one line
+    #[arg(long, default_value_t = 2)]
+    pub indent: usize,
+
+    /// End the output with a newline
+    #[arg(long)]
+    pub trailing_newline: bool,
+
+    /// Write nothing, and fail with exit code 1 if the file is not in canonical form
+    #[arg(long)]
+    pub check: bool,
+}
+
+impl FmtArgs {
+    fn canonical_options(&self) -> CanonicalOptions {
+        CanonicalOptions { sort_keys: self.sort_keys, indent: (self.indent > 0).then_some(self.indent) }
+    }
+}
+
+/// Exit code of `json-diff fmt --check` on a file that is not in canonical form
+pub const NOT_CANONICAL_EXIT_CODE: i32 = 1;
+
+/// `text` in canonical form, as `args` choose it
+pub fn format_text(text: &str, args: &FmtArgs) -> Result<String> {
+    let value: Value = serde_json::from_str(text).context("Failed to parse JSON")?;
+    let mut formatted = format_canonical(&value, &args.canonical_options());
+    if args.trailing_newline {
+        formatted.push('\n');
+    }
+    Ok(formatted)
+}
+
+/// Format the input, or with `--check` tell whether it is in canonical form already
+///
+/// Returns whether the input was canonical, which is always true without `--check`.
+pub fn fmt(args: FmtArgs) -> Result<bool> {
+    let is_stdin = args.input.as_os_str() == "-";
+    let text = if is_stdin {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text).context("Failed to read stdin")?;
+        text
+    } else {
+        fs::read_to_string(&args.input).with_context(|| format!("Failed to read {}", args.input.display()))?
+    };
+    let name = if is_stdin { "stdin".to_string() } else { args.input.display().to_string() };
+    let formatted = format_text(&text, &args).with_context(|| format!("Failed to format {}", name))?;
+
+    if args.check {
+        let canonical = formatted == text;
+        if !canonical {
+            eprintln!("{} is not in canonical form", name);
+        }
+        return Ok(canonical);
+    }
+
+    match &args.output {
+        Some(path) => fs::write(path, &formatted).with_context(|| format!("Failed to write {}", path.display()))?,
+        None => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(formatted.as_bytes()).and_then(|_| stdout.flush()).context("Failed to write the output")?;
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(extra: &[&str]) -> FmtArgs {
+        FmtArgs::parse_from(["json-diff fmt"].iter().chain(extra))
+    }
+
+    #[test]
+    fn test_format_text() {
+        let text = r#"{"b": [1, 2], "a": {"c": null}}"#;
+        assert_eq!(format_text(text, &args(&["--sort-keys"])).unwrap(), "{\n  \"a\": {\n    \"c\": null\n  },\n  \"b\": [\n    1,\n    2\n  ]\n}");
+        assert_eq!(format_text(text, &args(&["--sort-keys", "--indent", "0", "--trailing-newline"])).unwrap(), "{\"a\":{\"c\":null},\"b\":[1,2]}\n");
+        assert_eq!(format_text("[]", &args(&["--indent", "4"])).unwrap(), "[]");
+        assert!(format!("{:#}", format_text("{", &args(&[])).unwrap_err()).starts_with("Failed to parse JSON"));
+    }
+}
@@ -1,12 +1,112 @@
 //! CLI interface for the JSON diff tool
 
-use std::path::PathBuf;
+pub mod batch;
+mod cache;
+pub mod check_profile;
+pub mod fmt;
+mod output;
+pub mod renorm;
+pub mod self_test;
+mod summary;
+#[cfg(feature = "serve")]
+pub mod serve;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
 use anyhow::{Result, Context};
-use clap::Parser;
-use serde::Deserialize;
-use json_diff_core::{compare_files, CompareOptions, JsonDiffError, JsonPath, DiffResult};
-use json_diff_display;
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use cache::ResultCache;
+use json_diff_core::{compare_array_streams, compare_files, compare_strings, compare_tables, merge_patch, StreamOptions, FORMAT_VERSION, format_html, read_file_limited, check_input_file, AllowedTransition, Annotations, OutputStyle, CompareOptions, DiffClassification, DiffResult, DiffType, IndexStyle, PathMapping, HtmlOptions, HtmlSources, JsonDiffError, JsonPath, MissingFilePolicy, RegexOpts, Transform, TransformSide, rules_from_schema, http_capture_rules, HttpCaptureOptions, Codec, NumberLocale, RuleStats, RuleUsage, sha256_hex, GENERATOR, presets, PRESETS, Query, ValueRenderOptions, DEFAULT_MAX_VALUE_CHARS, Timings};
+use json_diff_display::{format_side_by_side, SideBySideOptions};
+use summary::Summary;
+
+/// Command line of the `json-diff` binary: the two files to compare, or a subcommand
+///
+/// A file named like a subcommand is compared by naming it after `--`, or as `./fmt`.
+#[derive(Parser, Debug)]
+#[command(
+    name = "json-diff",
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Show what each --preset sets
+    #[arg(long, exclusive = true)]
+    pub list_presets: bool,
+
+    #[command(flatten)]
+    pub args: Option<Args>,
+}
+
+/// The subcommands of `json-diff`
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    Batch(batch::BatchArgs),
+    CheckProfile(check_profile::CheckProfileArgs),
+    Fmt(fmt::FmtArgs),
+    Renorm(renorm::RenormArgs),
+    SelfTest(self_test::SelfTestArgs),
+    #[cfg(feature = "serve")]
+    Serve(serve::ServeArgs),
+}
+
+impl Command {
+    /// Run the subcommand, returning the code the process exits with
+    pub fn run(self) -> Result<i32> {
+        match self {
+            Command::Batch(args) => batch::batch(args).map(|summary| summary.exit_code()),
+            Command::CheckProfile(args) => check_profile::check_profile(args).map(|()| 0),
+            Command::Fmt(args) => fmt::fmt(args).map(|canonical| if canonical { 0 } else { fmt::NOT_CANONICAL_EXIT_CODE }),
+            Command::Renorm(args) => renorm::renorm(args).map(|()| 0),
+            Command::SelfTest(args) => self_test::self_test(args).map(|passed| if passed { 0 } else { self_test::SELF_TEST_FAILED_EXIT_CODE }),
+            #[cfg(feature = "serve")]
+            Command::Serve(args) => serve::serve(args).map(|()| 0),
+        }
+    }
+}
+
+/// Exit code of a subcommand that failed; 1 is left to the outcomes they report, such as
+/// differing pairs or a file not in canonical form
+pub const COMMAND_ERROR_EXIT_CODE: i32 = 2;
+
+/// Run the command line, printing the error it fails with, and return the code the
+/// process exits with
+pub fn run_cli(cli: Cli) -> i32 {
+    let outcome = match (cli.command, cli.args) {
+        (Some(command), _) => command.run().map_err(|e| (e, COMMAND_ERROR_EXIT_CODE)),
+        (None, Some(args)) => {
+            let mode = args.exit_code;
+            run(args).map_err(|e| {
+                let code = mode.error_code(&e);
+                (e, code)
+            })
+        }
+        // Without files or a subcommand only --list-presets parses
+        (None, None) => {
+            print!("{}", format_presets());
+            Ok(0)
+        }
+    };
+    match outcome {
+        Ok(code) => code,
+        Err((e, code)) => {
+            eprintln!("Error: {:#}", e);
+            code
+        }
+    }
+}
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -17,124 +117,1560 @@ pub struct Args {
     /// Second JSON file to compare
     pub file2: PathBuf,
 
-    /// Profile with comparison rules
+    /// Profile with comparison rules; `-` reads it from stdin
     #[arg(short, long)]
     pub profile: Option<PathBuf>,
 
+    /// Format of the --profile file
+    #[arg(long, value_enum, default_value_t = ProfileFormat::Toml)]
+    pub profile_format: ProfileFormat,
+
+    /// Profile given as a TOML snippet, e.g. 'ignore = ["$.ts"]'
+    #[arg(long, value_name = "TOML", conflicts_with = "profile")]
+    pub profile_inline: Option<String>,
+
+    /// Start from the options of a preset, which the profile and flags then override;
+    /// `json-diff --list-presets` shows what each one sets
+    #[arg(long, value_name = "NAME", value_parser = clap::builder::PossibleValuesParser::new(PRESETS.iter().map(|preset| preset.name)))]
+    pub preset: Option<String>,
+
+    /// Compare numbers by value, so 1 equals 1.0 (overrides the preset and profile)
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    pub numeric_equivalence: Option<bool>,
+
+    /// Record how much changed numbers moved, shown as `10.99 -> 12.99 (+2.00, +18.2%)`
+    /// and written to the JSON output (overrides the preset and profile)
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    pub numeric_deltas: Option<bool>,
+
+    /// Mark changed strings at least this alike (0 to 1), such as a typo, as near matches,
+    /// shown with `(≈)`; alike is one minus the edit distance over the longer length
+    /// (overrides the profile)
+    #[arg(long, value_name = "THRESHOLD", value_parser = parse_similarity)]
+    pub near_match: Option<f64>,
+
+    /// Only mark near matches at this path (repeatable); every path when not given
+    #[arg(long, value_name = "PATH", requires = "near_match")]
+    pub near_match_path: Vec<String>,
+
+    /// Report values accepted by tolerance and normalization rules as tolerated
+    /// (overrides the preset and profile)
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    pub report_tolerated: Option<bool>,
+
+    /// Report the changed elements of arrays rather than whole arrays (overrides the
+    /// preset and profile)
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    pub identify_array_item_changes: Option<bool>,
+
     /// Output file for diff result (stdout if not specified)
     #[arg(short, long)]
     pub output: Option<PathBuf>,
 
+    /// With --interactive, fail with exit code 3 instead of writing the report when there
+    /// is no terminal
+    #[arg(long, requires = "interactive")]
+    pub require_tty: bool,
+
+    /// With --interactive, write only the entries shown in the display when it is
+    /// left, in the order they are listed, to --output
+    #[arg(long, requires_all = ["interactive", "output"])]
+    pub output_view: bool,
+
     /// Display diff result in interactive terminal UI
     #[arg(short, long)]
     pub interactive: bool,
 
     /// Use symbols instead of readable text for diff types
-    #[arg(short = 'S', long)]
+    #[arg(short = 'S', long, overrides_with = "readable")]
     pub symbols: bool,
+
+    /// Use readable text for diff types (the default; overrides an earlier --symbols)
+    #[arg(long, overrides_with = "symbols")]
+    pub readable: bool,
+
+    /// JSON Schema whose x-diff annotations add ignore/tolerance rules
+    #[arg(long)]
+    pub schema: Option<PathBuf>,
+
+    /// Compare the files as HTTP captures ({"status", "headers", "body"}): header names
+    /// case-insensitively, volatile headers (date, request ids) ignored, JSON string bodies parsed
+    #[arg(long)]
+    pub http_capture: bool,
+
+    /// Accept the NaN, Infinity and -Infinity tokens some producers write; each is equal
+    /// only to itself
+    #[arg(long)]
+    pub lenient: bool,
+
+    /// Compare the files line by line, with a warning, when either is not valid JSON,
+    /// rather than failing; entries are at paths like <line 42>
+    #[arg(long)]
+    pub text_fallback: bool,
+
+    /// Record on each entry of the JSON output the line, column and byte range of its
+    /// key and value in each file
+    #[arg(long, conflicts_with = "stream_array")]
+    pub include_spans: bool,
+
+    /// Name the elements of arrays matched by key by that key in reported paths, as in
+    /// $.products[id=P1].price: the elements of unordered arrays, matched by "id", and of
+    /// streams matched by --stream-key
+    #[arg(long)]
+    pub keyed_paths: bool,
+
+    /// Compare a file that does not exist as an empty object, so every key of the other
+    /// is added or removed, instead of failing; the report marks that side `(missing)`
+    #[arg(long, conflicts_with_all = ["cache_dir", "stream_array", "table", "verify"])]
+    pub missing_as_empty: bool,
+
+    /// Compare only the subtree at this path of the first file (e.g. "$.data.result");
+    /// reported paths are relative to it
+    #[arg(long, value_name = "PATH", conflicts_with = "stream_array")]
+    pub left_root: Option<String>,
+
+    /// Compare only the subtree at this path of the second file (e.g. "$.payload")
+    #[arg(long, value_name = "PATH", conflicts_with = "stream_array")]
+    pub right_root: Option<String>,
+
+    /// Report only differences at paths this one matches, or beneath them (repeatable),
+    /// e.g. '$.spec.containers[*].image'; everything else is left out, even added and
+    /// removed values, and ignore rules still apply inside
+    #[arg(long, value_name = "PATH")]
+    pub select: Vec<String>,
+
+    /// Output format of the diff result
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Expected SHA-256 hashes of the two files; fail before comparing if they differ
+    #[arg(long, num_args = 2, value_names = ["LEFT_HASH", "RIGHT_HASH"])]
+    pub verify: Option<Vec<String>>,
+
+    /// Show only the first COUNT entries of the text report, followed by a notice with
+    /// the counts; a report printed to a terminal shows at most 10,000 unless --all
+    #[arg(long, value_name = "COUNT", conflicts_with = "all")]
+    pub limit: Option<usize>,
+
+    /// Show every entry of the text report, however many there are
+    #[arg(long)]
+    pub all: bool,
+
+    /// Show the report through $PAGER, else `less -R`, when printing to a terminal;
+    /// nothing is left out
+    #[arg(long, conflicts_with = "output")]
+    pub page: bool,
+
+    /// Print only the counts per type, the share of unchanged lines and the paths
+    /// holding the most differences, not the entries (text or json)
+    #[arg(long, conflicts_with_all = ["interactive", "compare_with", "stream_array", "table"])]
+    pub summary_only: bool,
+
+    /// Steps below the root the paths of --summary-only are cut to, e.g. 1 counts
+    /// $.items[3].price toward $.items
+    #[arg(long, value_name = "DEPTH", default_value_t = summary::DEFAULT_SUMMARY_DEPTH, requires = "summary_only")]
+    pub summary_depth: usize,
+
+    /// Number of paths --summary-only lists
+    #[arg(long, value_name = "COUNT", default_value_t = summary::DEFAULT_SUMMARY_TOP, requires = "summary_only")]
+    pub summary_top: usize,
+
+    /// Omit the generation timestamp from the output
+    #[arg(long)]
+    pub no_timestamp: bool,
+
+    /// Record the host the comparison ran on in the report
+    #[arg(long, conflicts_with = "no_metadata")]
+    pub hostname: bool,
+
+    /// Leave everything but the entries and warnings out of the report: file paths,
+    /// checksums, sizes, timestamp and generator
+    #[arg(long)]
+    pub no_metadata: bool,
+
+    /// Earlier JSON report (from `--format json`) to compare the result against,
+    /// printing new, resolved and persisting entries
+    #[arg(long, value_name = "PREVIOUS_REPORT", conflicts_with = "interactive")]
+    pub compare_with: Option<PathBuf>,
+
+    /// Largest input file that will be read, e.g. 500MB or 2GiB
+    #[arg(long, value_name = "SIZE", default_value = "1GiB", value_parser = parse_size)]
+    pub max_input_size: u64,
+
+    /// Stop with exit code 2 after visiting this many values across both documents
+    #[arg(long, value_name = "COUNT")]
+    pub max_nodes: Option<u64>,
+
+    /// Stop with exit code 2 once the comparison finds more than this many differences
+    #[arg(long, value_name = "COUNT")]
+    pub max_entries: Option<usize>,
+
+    /// Stop with exit code 2 when the comparison runs longer than this, e.g. 500ms, 30s or 2m
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub max_duration: Option<Duration>,
+
+    /// Embed both source files in the HTML report, with entries linking to their lines
+    #[arg(long)]
+    pub html_embed_sources: bool,
+
+    /// Largest source embedded in full in the HTML report; larger ones are embedded
+    /// as snippets around each difference
+    #[arg(long, value_name = "SIZE", default_value = "1MiB", value_parser = parse_size)]
+    pub html_max_embed_bytes: u64,
+
+    /// Directory caching results by input checksums and options; unchanged pairs are not re-compared
+    #[arg(long, value_name = "DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Wipe the cache directory before comparing
+    #[arg(long, requires = "cache_dir")]
+    pub cache_clear: bool,
+
+    /// Keep the timestamp stored with a cached result instead of refreshing it
+    #[arg(long, requires = "cache_dir")]
+    pub cache_preserve_timestamp: bool,
+
+    /// Compare two top-level arrays element by element without loading the whole files
+    #[arg(long, conflicts_with_all = ["interactive", "cache_dir", "compare_with"])]
+    pub stream_array: bool,
+
+    /// Match streamed array elements by the value of this key instead of by position
+    #[arg(long, value_name = "KEY", requires = "stream_array")]
+    pub stream_key: Option<String>,
+
+    /// Most streamed elements held while waiting for their match by key
+    #[arg(long, value_name = "COUNT", default_value_t = 100_000, requires = "stream_key")]
+    pub stream_buffer: usize,
+
+    /// Compare two arrays of records as tables, matching rows by --key and reporting
+    /// added, removed and changed rows; rules apply to each row as a document
+    #[arg(long, requires = "key", conflicts_with_all = ["interactive", "cache_dir", "compare_with", "stream_array"])]
+    pub table: bool,
+
+    /// Field holding the primary key of each row, with --table
+    #[arg(long, value_name = "FIELD", requires = "table")]
+    pub key: Option<String>,
+
+    /// Print how long reading, parsing, line mapping, comparing, sorting and writing the
+    /// report took, on stderr; the JSON output and the facts hold the comparison phases
+    #[arg(long, conflicts_with_all = ["stream_array", "table"])]
+    pub timings: bool,
+
+    /// Report how often each ignore rule matched and the time spent matching it, on stderr
+    #[arg(long, conflicts_with_all = ["cache_dir", "stream_array"])]
+    pub explain_rules: bool,
+
+    /// Fail with exit code 2 when an ignore or unordered rule matches no path of either
+    /// file, naming those rules; rules matching paths whose values are equal are fine
+    #[arg(long, conflicts_with_all = ["stream_array", "table"])]
+    pub strict_rules: bool,
+
+    /// Keep entries under the same parent together, removed before added before modified
+    #[arg(long, conflicts_with = "stream_array")]
+    pub group_related: bool,
+
+    /// Order of the entries: by line, or largest numeric change first (which records
+    /// the deltas, as --numeric-deltas does)
+    #[arg(long, value_enum, value_name = "ORDER", conflicts_with_all = ["group_related", "stream_array"])]
+    pub sort: Option<SortOrder>,
+
+    /// Leave the terminal title alone in the interactive display, which otherwise shows
+    /// the entry counts in it (`window_title = false` in the profile does the same)
+    #[arg(long)]
+    pub no_title: bool,
+
+    /// Report only the entries this query selects, e.g.
+    /// 'type:removed AND path:$.config AND value~"secret"'; the interactive display starts on it
+    #[arg(long, value_name = "QUERY", conflicts_with = "stream_array")]
+    pub query: Option<String>,
+
+    /// Characters of a value shown in the text output before it is truncated; 0 shows values in full
+    #[arg(long, value_name = "CHARS", default_value_t = DEFAULT_MAX_VALUE_CHARS)]
+    pub max_value_chars: usize,
+
+    /// Show objects, arrays and long strings in the text output as a preview of their
+    /// shape, e.g. {name, tags[3], details{…}}, within --max-value-chars
+    #[arg(long)]
+    pub preview_values: bool,
+
+    /// Line up the types, paths, lines and values of the text output in columns, paths
+    /// wider than MAX_WIDTH (60 by default) cut in the middle with …
+    #[arg(long, value_name = "MAX_WIDTH", num_args = 0..=1, default_missing_value = "60", conflicts_with_all = ["stream_array", "table"])]
+    pub align: Option<usize>,
+
+    /// Width of the output: of the side-by-side rendering (160 by default), and of the
+    /// text output, whose entry lines are wrapped under their values past it (not by
+    /// default, nor with 0)
+    #[arg(long, value_name = "COLUMNS")]
+    pub width: Option<usize>,
+
+    /// Unchanged lines shown around each changed line in the side-by-side rendering
+    #[arg(long, value_name = "LINES", default_value_t = 0)]
+    pub context: usize,
+
+    /// When to colorize changed lines in the side-by-side rendering
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// Triage annotations, loaded at start and saved when the interactive display exits
+    /// (default: next to --output, or next to the first file, as <file>.annotations.json)
+    #[arg(long, value_name = "FILE")]
+    pub annotations: Option<PathBuf>,
+
+    /// Write facts about the run to this file as JSON for CI steps: entry counts,
+    /// duration, input sizes and checksums, exit code, classification and options fingerprint
+    #[arg(long, value_name = "FILE", conflicts_with = "stream_array")]
+    pub report_facts: Option<PathBuf>,
+
+    /// What the exit code tells: standard exits 0 whatever the differences; extended
+    /// exits 0 when there are no entries, 3 when every entry is ignored or tolerated,
+    /// 1 when the files differ and 2 on any error
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = ExitCodeMode::Standard)]
+    pub exit_code: ExitCodeMode,
+}
+
+impl Args {
+    /// How diff types are written; the last of --symbols and --readable wins
+    pub fn output_style(&self) -> OutputStyle {
+        if self.symbols { OutputStyle::Symbols } else { OutputStyle::Readable }
+    }
+
+    /// How values are rendered in the text output
+    pub fn value_render_options(&self) -> ValueRenderOptions {
+        ValueRenderOptions { max_value_chars: self.max_value_chars, preview: self.preview_values, align: self.align, width: self.width.unwrap_or(0) }
+    }
+}
+
+/// When to emit ANSI colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Only when writing to a terminal and NO_COLOR is unset
+    Auto,
+    Always,
+    Never,
+}
+
+/// What the exit code of a comparison tells, see `--exit-code`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExitCodeMode {
+    /// 0 whatever the differences; failures have their own codes
+    Standard,
+    /// 0 identical, 3 only ignored or tolerated differences, 1 differences, 2 failures
+    Extended,
+}
+
+impl ExitCodeMode {
+    /// Exit code of a run whose comparison came out as `classification`
+    pub fn success_code(self, classification: DiffClassification) -> i32 {
+        match (self, classification) {
+            (ExitCodeMode::Standard, _) | (ExitCodeMode::Extended, DiffClassification::Identical) => 0,
+            (ExitCodeMode::Extended, DiffClassification::OnlyIgnored) => ONLY_IGNORED_EXIT_CODE,
+            (ExitCodeMode::Extended, DiffClassification::Different) => DIFFERENT_EXIT_CODE,
+        }
+    }
+
+    /// Exit code of a run that failed with `err`
+    pub fn error_code(self, err: &anyhow::Error) -> i32 {
+        match self {
+            ExitCodeMode::Standard => exit_code(err),
+            ExitCodeMode::Extended => EXTENDED_ERROR_EXIT_CODE,
+        }
+    }
+}
+
+/// Output format of the diff result
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Line-oriented DIFF-JSON text report
+    Text,
+    /// Serialized DiffResult as JSON
+    Json,
+    /// Standalone HTML report
+    Html,
+    /// Both files in two columns, with changed lines marked in the gutter
+    SideBySide,
+    /// JSON Merge Patch (RFC 7386) turning the first file into the second
+    MergePatch,
+}
+
+/// Order of the entries in the report and the display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    /// By line, as the entries appear in the files
+    Line,
+    /// By how much a number changed, either way, largest first
+    Delta,
+}
+
+/// Format of a profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProfileFormat {
+    Toml,
+    /// The keys of the TOML profile as a JSON object
+    Json,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Profile {
     #[serde(default)]
-    pub ignore: Vec<String>,
+    pub ignore: Vec<PathRule>,
+
+    #[serde(default)]
+    pub unordered: Vec<PathRule>,
 
+    /// Arrays of primitives compared as sets
     #[serde(default)]
-    pub unordered: Vec<String>,
+    pub set: Vec<PathRule>,
 
     #[serde(default)]
-    pub show_nested_differences: bool,
+    pub show_nested_differences: Option<bool>,
 
     #[serde(default)]
     pub identify_array_item_changes: Option<bool>,
+
+    #[serde(default)]
+    pub detect_array_moves: Option<bool>,
+
+    #[serde(default)]
+    pub tag_array_item_changes: Option<bool>,
+
+    #[serde(default)]
+    pub tag_nested_array_item_changes: Option<bool>,
+
+    #[serde(default)]
+    pub report_tolerated: Option<bool>,
+
+    /// Needs the key-order feature
+    #[serde(default)]
+    pub report_key_order: Option<bool>,
+
+    #[serde(default)]
+    pub normalize_entries: Option<bool>,
+
+    #[serde(default)]
+    pub max_value_size: Option<usize>,
+
+    #[serde(default)]
+    pub preprocess: Vec<PreprocessRule>,
+
+    #[serde(default)]
+    pub tolerance: Vec<ToleranceRule>,
+
+    #[serde(default)]
+    pub parse_embedded_json: Vec<PathRule>,
+
+    #[serde(default)]
+    pub auto_parse_embedded_json: Option<bool>,
+
+    #[serde(default)]
+    pub decode: Vec<DecodeRule>,
+
+    #[serde(default)]
+    pub numeric_strings: Vec<PathRule>,
+
+    #[serde(default)]
+    pub all_numeric_strings: Option<bool>,
+
+    #[serde(default)]
+    pub number_locale: Option<NumberLocale>,
+
+    #[serde(default)]
+    pub numeric_equivalence: Option<bool>,
+
+    #[serde(default)]
+    pub numeric_deltas: Option<bool>,
+
+    /// Whether files that are not valid JSON are compared line by line
+    #[serde(default)]
+    pub text_fallback: Option<bool>,
+
+    /// Changed strings alike enough to be marked as near matches
+    #[serde(default)]
+    pub near_match: Option<NearMatchRule>,
+
+    #[serde(default)]
+    pub allowed_transition: Vec<AllowedTransitionRule>,
+
+    /// Values compared across differently named paths
+    #[serde(default)]
+    pub map: Vec<MapRule>,
+
+    /// Whether the interactive display shows the entry counts in the terminal title
+    #[serde(default)]
+    pub window_title: Option<bool>,
+
+    /// Whether the list of the interactive display shows paths in full, scrolled
+    /// sideways, rather than shortened to fit its width
+    #[serde(default)]
+    pub full_paths: Option<bool>,
+
+    /// Whether the split view marks where the focused entry would be in the pane without it
+    #[serde(default)]
+    pub phantom_rows: Option<bool>,
+}
+
+/// A path in the profile: a JSON path, or a table with a regex `pattern`
+///
+/// Patterns must match the whole path unless `anchored = false`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum PathRule {
+    Path(String),
+    Pattern {
+        pattern: String,
+        #[serde(default = "default_anchored")]
+        anchored: bool,
+        #[serde(default)]
+        case_insensitive: bool,
+    },
+}
+
+fn default_anchored() -> bool {
+    true
+}
+
+impl PathRule {
+    fn to_path(&self) -> Result<JsonPath, JsonDiffError> {
+        match self {
+            PathRule::Path(path) => JsonPath::new(path),
+            PathRule::Pattern { pattern, anchored, case_insensitive } => JsonPath::with_regex_opts(
+                pattern,
+                pattern,
+                RegexOpts { anchored: *anchored, case_insensitive: *case_insensitive },
+            ),
+        }
+    }
 }
 
-pub fn run(args: Args) -> Result<()> {
+/// An entry of the `decode` list in the profile
+#[derive(Debug, Deserialize)]
+pub struct DecodeRule {
+    pub path: PathRule,
+    pub codec: Codec,
+}
+
+/// A `[[tolerance]]` table in the profile
+#[derive(Debug, Deserialize)]
+pub struct ToleranceRule {
+    pub path: PathRule,
+    pub within: f64,
+}
+
+/// The `[near_match]` table in the profile
+#[derive(Debug, Deserialize)]
+pub struct NearMatchRule {
+    /// Similarity from 0 to 1 at or above which changed strings are near matches
+    pub threshold: f64,
+    /// Paths to mark near matches at; every path if empty
+    #[serde(default)]
+    pub paths: Vec<PathRule>,
+}
+
+/// An `[[allowed_transition]]` table in the profile
+#[derive(Debug, Deserialize)]
+pub struct AllowedTransitionRule {
+    pub path: PathRule,
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+}
+
+/// A `[[map]]` table in the profile
+#[derive(Debug, Deserialize)]
+pub struct MapRule {
+    pub left: String,
+    pub right: String,
+}
+
+/// A `[[preprocess]]` table in the profile
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum PreprocessRule {
+    Remove {
+        path: PathRule,
+        #[serde(default)]
+        side: TransformSide,
+    },
+    SortArray {
+        path: PathRule,
+        #[serde(default)]
+        by_key: Option<String>,
+        #[serde(default)]
+        side: TransformSide,
+    },
+    Round {
+        path: PathRule,
+        decimals: u32,
+        #[serde(default)]
+        side: TransformSide,
+    },
+    Lowercase {
+        path: PathRule,
+        #[serde(default)]
+        side: TransformSide,
+    },
+    LowercaseKeys {
+        path: PathRule,
+        #[serde(default)]
+        side: TransformSide,
+    },
+}
+
+impl PreprocessRule {
+    fn to_transform(&self) -> Result<Transform, JsonDiffError> {
+        let transform = match self {
+            PreprocessRule::Remove { path, side } => Transform::remove(path.to_path()?).on(*side),
+            PreprocessRule::SortArray { path, by_key, side } => {
+                Transform::sort_array(path.to_path()?, by_key.as_deref()).on(*side)
+            }
+            PreprocessRule::Round { path, decimals, side } => Transform::round(path.to_path()?, *decimals).on(*side),
+            PreprocessRule::Lowercase { path, side } => Transform::lowercase(path.to_path()?).on(*side),
+            PreprocessRule::LowercaseKeys { path, side } => Transform::lowercase_keys(path.to_path()?).on(*side),
+        };
+        Ok(transform)
+    }
+}
+
+/// Compare the files as `args` ask and write the report, returning the exit code of
+/// the run, see [`ExitCodeMode::success_code`]
+pub fn run(args: Args) -> Result<i32> {
+    check_inputs(&args)?;
+
+    // The preset is the base the profile and the flags are applied on
+    let base = match &args.preset {
+        Some(name) => presets::preset(name)
+            .map(|preset| (preset.options)())
+            .with_context(|| format!("Unknown preset {}", name))?,
+        None => CompareOptions::default(),
+    };
+
     // Load profile if specified
-    let options = if let Some(profile_path) = args.profile {
-        load_profile(&profile_path)?
-    } else {
-        CompareOptions::default()
+    let profile = match (&args.profile, &args.profile_inline) {
+        (Some(profile_path), _) => {
+            let content = if is_stdin(profile_path) {
+                io::read_to_string(io::stdin()).context("Failed to read profile from stdin")?
+            } else {
+                fs::read_to_string(profile_path)
+                    .with_context(|| format!("Failed to read profile {}", profile_path.display()))?
+            };
+            Some(parse_profile(&content, args.profile_format)?)
+        }
+        (None, Some(inline)) => Some(parse_profile(inline, ProfileFormat::Toml)?),
+        (None, None) => None,
+    };
+    let mut options = match &profile {
+        Some(profile) => profile.to_options_over(base)?,
+        None => base,
     };
+    if let Some(numeric_equivalence) = args.numeric_equivalence {
+        options.numeric_equivalence = numeric_equivalence;
+    }
+    if let Some(numeric_deltas) = args.numeric_deltas {
+        options.numeric_deltas = numeric_deltas;
+    }
+    if let Some(threshold) = args.near_match {
+        let paths = args.near_match_path.iter().map(|path| JsonPath::new(path)).collect::<Result<_, _>>()?;
+        options.string_similarity = Some((threshold, paths));
+    }
+    // Sorting by delta needs the deltas
+    if args.sort == Some(SortOrder::Delta) {
+        options.numeric_deltas = true;
+    }
+    if let Some(report_tolerated) = args.report_tolerated {
+        options.report_tolerated = report_tolerated;
+    }
+    if let Some(identify_array_item_changes) = args.identify_array_item_changes {
+        options.identify_array_item_changes = identify_array_item_changes;
+    }
+    // A merge patch replaces arrays whole, so it needs their values rather than their elements
+    if args.format == OutputFormat::MergePatch {
+        options.identify_array_item_changes = false;
+    }
+
+    // Add rules annotated in the schema, if given
+    if let Some(schema_path) = &args.schema {
+        let content = fs::read_to_string(schema_path)
+            .with_context(|| format!("Failed to read schema {}", schema_path.display()))?;
+        let schema: serde_json::Value = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse schema {}", schema_path.display()))?;
+        options.extend_rules(rules_from_schema(&schema)?);
+    }
+
+    if args.http_capture {
+        options.extend_rules(http_capture_rules(&HttpCaptureOptions::default())?);
+    }
+    options.lenient = args.lenient;
+    if args.text_fallback {
+        options.text_fallback = true;
+    }
+    options.record_spans = args.include_spans;
+    options.collect_timings = args.timings;
+    if args.missing_as_empty {
+        options.missing_file_policy = MissingFilePolicy::TreatAsEmptyObject;
+    }
+    if args.keyed_paths {
+        options.path_index_style = IndexStyle::Keyed;
+    }
+    options.explain_rules = args.explain_rules;
+    options.left_root = args.left_root.as_deref().map(JsonPath::new).transpose()?;
+    options.right_root = args.right_root.as_deref().map(JsonPath::new).transpose()?;
+    for path in &args.select {
+        options.select_paths.push(JsonPath::new(path)?);
+    }
+
+    options.max_input_bytes = Some(args.max_input_size);
+    options.max_nodes = args.max_nodes;
+    options.max_entries = args.max_entries;
+    options.max_duration = args.max_duration;
+
+    // Honor SOURCE_DATE_EPOCH so reruns produce byte-identical reports
+    if let Some(timestamp) = source_date_epoch()? {
+        options.timestamp = Some(timestamp);
+    }
+
+    if let Some(expected) = &args.verify {
+        verify_hash(&args.file1, &expected[0], "Left")?;
+        verify_hash(&args.file2, &expected[1], "Right")?;
+    }
+
+    // Neither mode has a result to classify
+    if args.exit_code == ExitCodeMode::Extended && (args.stream_array || (args.table && args.key.is_some())) {
+        anyhow::bail!("--exit-code extended is not supported with --stream-array or --table");
+    }
+    if args.stream_array {
+        return run_stream(&args, &options).map(|()| 0);
+    }
+    if let Some(key) = args.key.as_deref().filter(|_| args.table) {
+        return run_table(&args, key, &options).map(|()| 0);
+    }
+    // A malformed query fails before the comparison
+    if let Some(query) = &args.query {
+        Query::parse(query)?;
+    }
 
     // Compare files
-    let result = compare_files(&args.file1, &args.file2, &options)
-        .context("Failed to compare JSON files")?;
+    let start = Instant::now();
+    let mut result = match &args.cache_dir {
+        Some(cache_dir) => compare_cached(&args, cache_dir, &options)?,
+        None => compare_files(&args.file1, &args.file2, &options)
+            .context("Failed to compare JSON files")?,
+    };
+    let duration = start.elapsed();
+    let classification = result.classification();
+    // Rules matching nothing fail the run, so they are checked before the facts record its code
+    let rules_checked = match args.strict_rules {
+        true => check_rules(&args, &options, &result),
+        false => Ok(()),
+    };
+    let code = match &rules_checked {
+        Ok(()) => args.exit_code.success_code(classification),
+        Err(e) => args.exit_code.error_code(e),
+    };
 
-    if args.interactive {
-        // Use the interactive display module (readable format is default, symbols if requested)
-        json_diff_display::run_display_with_options(result, !args.symbols)
-            .context("Failed to run interactive display")?;
-    } else {
-        // Output the result as text (readable format is default, symbols if requested)
-        let diff_text = if args.symbols {
-            result.to_string()
-        } else {
-            format_diff_readable(&result)
+    if let Some(facts_path) = &args.report_facts {
+        let facts = serde_json::to_string_pretty(&RunFacts::new(&result, &options, duration, code))
+            .context("Failed to serialize run facts")?;
+        fs::write(facts_path, facts)
+            .with_context(|| format!("Failed to write run facts to {}", facts_path.display()))?;
+    }
+
+    if args.no_timestamp {
+        result.timestamp = None;
+    }
+    if args.hostname {
+        result.hostname = hostname();
+    }
+
+    for warning in &result.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    if args.explain_rules {
+        eprint!("{}", format_rule_stats(&result.rule_stats));
+    }
+    rules_checked?;
+
+    if args.group_related {
+        result.group_related();
+    }
+    if args.sort == Some(SortOrder::Delta) {
+        result.sort_by_delta();
+    }
+    let interactive = interactive_session(&args)?;
+    if let Some(query) = args.query.as_ref().filter(|_| !interactive) {
+        result = result.query(query)?;
+    }
+
+    let annotations_path = annotations_path(&args);
+    if interactive || args.annotations.is_some() {
+        result.annotations = Annotations::load(&annotations_path)
+            .with_context(|| format!("Failed to load annotations {}", annotations_path.display()))?;
+    }
+
+    if interactive {
+        let mut app = json_diff_display::App::with_input_limit(result, options.max_input_bytes);
+        app.set_output_style(args.output_style());
+        // Ignore rules can only be written back to a profile file
+        app.set_profile_path(args.profile.clone().filter(|path| !is_stdin(path)));
+        if args.group_related {
+            app.set_sort_mode(json_diff_display::SortMode::Related);
+        }
+        if args.sort == Some(SortOrder::Delta) {
+            app.set_sort_mode(json_diff_display::SortMode::Delta);
+        }
+        app.set_window_title(!args.no_title && profile.as_ref().and_then(|profile| profile.window_title).unwrap_or(true));
+        app.set_full_paths(profile.as_ref().and_then(|profile| profile.full_paths).unwrap_or(false));
+        app.set_phantom_rows(profile.as_ref().and_then(|profile| profile.phantom_rows).unwrap_or(true));
+        if let Some(query) = &args.query {
+            app.set_query(query)?;
+        }
+        // The app is kept, so the report can be written even if the display fails
+        let shown = json_diff_display::run_app_mut(&mut app, json_diff_display::DEFAULT_TICK_RATE)
+            .context("Failed to run interactive display");
+        let view = args.output_view.then(|| app.view_result());
+        result = app.into_result();
+        save_annotations(&annotations_path, &mut result)?;
+
+        // Without an output file there is nothing to export
+        let Some(output_path) = &args.output else { return shown.map(|()| outcome(&args, classification, code)) };
+        let written = write_report(&args, &options, view.unwrap_or(result));
+        return after_display(shown, written, output_path).map(|()| outcome(&args, classification, code));
+    }
+
+    let (timings, start) = (result.timings, Instant::now());
+    write_report(&args, &options, result)?;
+    if args.timings {
+        match timings {
+            Some(mut timings) => {
+                timings.add_format(start.elapsed());
+                eprintln!("Timings: {}", timings);
+            }
+            None => eprintln!("Timings: none, the result was taken from the cache"),
+        }
+    }
+    Ok(outcome(&args, classification, code))
+}
+
+/// Exit code of the run, told on stderr with --exit-code extended
+fn outcome(args: &Args, classification: DiffClassification, code: i32) -> i32 {
+    if args.exit_code == ExitCodeMode::Extended {
+        let text = match classification {
+            DiffClassification::Identical => "identical",
+            DiffClassification::OnlyIgnored => "only ignored or tolerated differences",
+            DiffClassification::Different => "different",
         };
+        eprintln!("Outcome: {} (exit code {})", text, code);
+    }
+    code
+}
+
+/// Exit code of a run that failed because --require-tty found no terminal
+pub const NO_TERMINAL_EXIT_CODE: i32 = 3;
+
+/// Exit code of a run stopped by --max-nodes, --max-entries or --max-duration
+pub const LIMIT_EXIT_CODE: i32 = 2;
+
+/// Exit code with --exit-code extended of a comparison that found differences
+pub const DIFFERENT_EXIT_CODE: i32 = 1;
+
+/// Exit code with --exit-code extended of a comparison that found only ignored or
+/// tolerated differences
+pub const ONLY_IGNORED_EXIT_CODE: i32 = 3;
+
+/// Exit code with --exit-code extended of any run that failed
+pub const EXTENDED_ERROR_EXIT_CODE: i32 = 2;
+
+/// Exit code of a run whose rules name paths neither file has, with --strict-rules
+pub const STRICT_RULES_EXIT_CODE: i32 = 2;
+
+/// Error of --strict-rules: the rules matching no path of either file
+#[derive(Debug, Clone, PartialEq)]
+pub struct AbsentRules(pub Vec<RuleUsage>);
+
+impl std::fmt::Display for AbsentRules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.len() {
+            1 => write!(f, "1 rule matches no path of either file:")?,
+            count => write!(f, "{} rules match no path of either file:", count)?,
+        }
+        for usage in &self.0 {
+            write!(f, "\n  {} `{}`", usage.kind, usage.rule)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AbsentRules {}
 
-        if let Some(output_path) = args.output {
-            fs::write(&output_path, diff_text)
-                .context("Failed to write diff result to file")?;
-        } else {
-            println!("{}", diff_text);
+/// Exit code of a run that failed with `err`
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    if err.downcast_ref::<json_diff_display::NoTerminal>().is_some() {
+        NO_TERMINAL_EXIT_CODE
+    } else if err.downcast_ref::<AbsentRules>().is_some() {
+        STRICT_RULES_EXIT_CODE
+    } else if matches!(err.downcast_ref::<JsonDiffError>(), Some(JsonDiffError::LimitExceeded { .. })) {
+        LIMIT_EXIT_CODE
+    } else {
+        1
+    }
+}
+
+/// Whether the run shows the interactive display: without a terminal --interactive
+/// falls back to the plain report, or fails with --require-tty
+fn interactive_session(args: &Args) -> Result<bool> {
+    if !args.interactive || json_diff_display::terminal_available() {
+        return Ok(args.interactive);
+    }
+    if args.require_tty {
+        return Err(json_diff_display::NoTerminal.into());
+    }
+    eprintln!("Warning: no terminal for --interactive; writing the report instead");
+    Ok(false)
+}
+
+/// Combine the outcomes of the interactive display and of writing its report to
+/// `output_path`, telling on stderr what happened to the report
+fn after_display(shown: Result<()>, written: Result<()>, output_path: &Path) -> Result<()> {
+    match (shown, written) {
+        (shown, Ok(())) => {
+            eprintln!("Wrote the report to {}", output_path.display());
+            shown
+        }
+        (Ok(()), Err(err)) => Err(err),
+        (Err(err), Err(write_err)) => {
+            eprintln!("Error: {:#}", write_err);
+            Err(err)
         }
     }
+}
+
+/// Format the result as the arguments ask and write it to the output file or stdout
+fn write_report(args: &Args, options: &CompareOptions, mut result: DiffResult) -> Result<()> {
+    if args.no_metadata {
+        result.strip_metadata();
+    }
+
+    // A text report of an enormous diff is cut short, unless kept whole in a file or a pager
+    let to_terminal = args.output.is_none() && !args.page && io::stdout().is_terminal();
+    let cap = match (args.format, &args.compare_with) {
+        (OutputFormat::Text, None) if !args.summary_only => output::entry_cap(result.entries().len(), args.limit, args.all || args.page, to_terminal),
+        _ => None,
+    };
+    let notice = cap.map(|shown| output::truncate(&mut result, shown, args.limit.is_some()));
+
+    // Output the result in the requested format
+    let diff_text = if args.summary_only {
+        let lines = (
+            read_source(&args.file1, result.left_missing, options)?.lines().count(),
+            read_source(&args.file2, result.right_missing, options)?.lines().count(),
+        );
+        let summary = Summary::new(&result, lines, args.summary_depth, args.summary_top);
+        match args.format {
+            OutputFormat::Json => serde_json::to_string_pretty(&summary).context("Failed to serialize summary")?,
+            OutputFormat::Text => summary.format_text(),
+            _ => anyhow::bail!("--summary-only only supports --format text and --format json"),
+        }
+    } else if let Some(previous_path) = &args.compare_with {
+        let buckets = result.diff_against(&load_report(previous_path)?);
+        match args.format {
+            OutputFormat::Json => serde_json::to_string_pretty(&buckets)
+                .context("Failed to serialize report comparison")?,
+            OutputFormat::Html | OutputFormat::SideBySide | OutputFormat::MergePatch => anyhow::bail!(
+                "--format {} is not supported with --compare-with",
+                args.format.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default()
+            ),
+            OutputFormat::Text => buckets.format_with_options(args.output_style(), &args.value_render_options()),
+        }
+    } else {
+        match args.format {
+            OutputFormat::Json => serde_json::to_string_pretty(&result)
+                .context("Failed to serialize diff result")?,
+            OutputFormat::Html => {
+                let html_options = HtmlOptions {
+                    embed_sources: args.html_embed_sources,
+                    max_embed_bytes: usize::try_from(args.html_max_embed_bytes).unwrap_or(usize::MAX),
+                    ..HtmlOptions::default()
+                };
+                if args.html_embed_sources {
+                    let left = read_source(&args.file1, result.left_missing, options)?;
+                    let right = read_source(&args.file2, result.right_missing, options)?;
+                    format_html(&result, Some(HtmlSources { left: &left, right: &right }), &html_options)
+                } else {
+                    format_html(&result, None, &html_options)
+                }
+            }
+            OutputFormat::SideBySide => {
+                let left = read_source(&args.file1, result.left_missing, options)?;
+                let right = read_source(&args.file2, result.right_missing, options)?;
+                let side_by_side_options = SideBySideOptions {
+                    width: args.width.unwrap_or(SideBySideOptions::default().width),
+                    context: args.context,
+                    color: use_color(args.color, args.output.is_none()),
+                };
+                format_side_by_side(&result, &left, &right, &side_by_side_options)
+            }
+            OutputFormat::MergePatch => {
+                let (patch, warnings) = merge_patch::from_result(&result);
+                for warning in &warnings {
+                    eprintln!("Warning: {}", warning);
+                }
+                serde_json::to_string_pretty(&patch).context("Failed to serialize merge patch")?
+            }
+            OutputFormat::Text => result.format_with_options(args.output_style(), &args.value_render_options()),
+        }
+    };
+    let diff_text = match notice {
+        Some(notice) => format!("{}\n{}", diff_text, notice),
+        None => diff_text,
+    };
+
+    if let Some(output_path) = &args.output {
+        fs::write(output_path, diff_text)
+            .context("Failed to write diff result to file")?;
+    } else {
+        output::print(&diff_text, args.page)?;
+    }
 
     Ok(())
 }
 
-fn format_diff_readable(result: &DiffResult) -> String {
-    let mut output = String::new();
+/// The text of a compared file, shown beside the report; nothing for a missing one
+fn read_source(path: &Path, missing: bool, options: &CompareOptions) -> Result<String> {
+    match missing {
+        true => Ok(String::new()),
+        false => Ok(read_file_limited(path, options.max_input_bytes)?),
+    }
+}
 
-    output.push_str("DIFF-JSON v1\n");
+/// Facts about a comparison run, written with `--report-facts`
+#[derive(Debug, Serialize)]
+struct RunFacts {
+    /// Entries per type, every type included
+    counts: BTreeMap<String, usize>,
+    total: usize,
+    /// Wall-clock time of the comparison, in milliseconds
+    duration_ms: f64,
+    left_size: Option<u64>,
+    right_size: Option<u64>,
+    left_sha256: Option<String>,
+    right_sha256: Option<String>,
+    /// Exit code of the process; a run that writes its facts has compared both files
+    exit_code: i32,
+    /// Whether the files are identical, differ only in ignored or tolerated ways, or differ
+    classification: DiffClassification,
+    /// See [`CompareOptions::fingerprint`]
+    options_fingerprint: String,
+    /// Phases of the comparison, with --timings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timings: Option<Timings>,
+}
 
-    if let Some(left) = &result.left_file {
-        output.push_str(&format!("LEFT: {}\n", left.display()));
+impl RunFacts {
+    fn new(result: &DiffResult, options: &CompareOptions, duration: Duration, exit_code: i32) -> Self {
+        Self {
+            counts: DiffType::ALL.into_iter().map(|diff_type| (format!("{:?}", diff_type), result.count(diff_type))).collect(),
+            total: result.entries().len(),
+            duration_ms: duration.as_secs_f64() * 1000.0,
+            left_size: result.left_size,
+            right_size: result.right_size,
+            left_sha256: result.left_sha256.clone(),
+            right_sha256: result.right_sha256.clone(),
+            exit_code,
+            classification: result.classification(),
+            options_fingerprint: options.fingerprint(),
+            timings: result.timings,
+        }
+    }
+}
+
+/// Fail with [`AbsentRules`] when a rule matches no path of either file
+fn check_rules(args: &Args, options: &CompareOptions, result: &DiffResult) -> Result<()> {
+    let left = read_source(&args.file1, result.left_missing, options)?;
+    let right = read_source(&args.file2, result.right_missing, options)?;
+    let usage = options.rule_usage(&left, &right, result).context("--strict-rules needs both files to be JSON")?;
+    let absent: Vec<RuleUsage> = usage.into_iter().filter(RuleUsage::is_absent).collect();
+    match absent.is_empty() {
+        true => Ok(()),
+        false => Err(AbsentRules(absent).into()),
+    }
+}
+
+/// The ignore rules by time spent matching them, slowest first
+fn format_rule_stats(stats: &[RuleStats]) -> String {
+    let mut stats: Vec<&RuleStats> = stats.iter().collect();
+    stats.sort_by_key(|stats| std::cmp::Reverse(stats.nanos));
+
+    let width = stats.iter().map(|stats| stats.rule.len()).max().unwrap_or(0).max("Ignore rule".len());
+    let mut text = format!("{:<width$}  {:>8}  {:>8}  {:>10}\n", "Ignore rule", "Matched", "Tested", "Time");
+    for stats in stats {
+        text.push_str(&format!(
+            "{:<width$}  {:>8}  {:>8}  {:>8.3}ms\n",
+            stats.rule, stats.matched, stats.tested, stats.nanos as f64 / 1_000_000.0
+        ));
+    }
+    text
+}
+
+/// Where annotations are kept: the --annotations file, or a sidecar of the report or the left input
+fn annotations_path(args: &Args) -> PathBuf {
+    args.annotations.clone().unwrap_or_else(|| {
+        let mut sidecar = args.output.as_ref().unwrap_or(&args.file1).as_os_str().to_owned();
+        sidecar.push(".annotations.json");
+        PathBuf::from(sidecar)
+    })
+}
+
+/// Save the annotations made in the display, on top of any saved by others meanwhile
+fn save_annotations(path: &Path, result: &mut DiffResult) -> Result<()> {
+    if result.annotations.is_empty() && !path.exists() {
+        return Ok(());
     }
 
-    if let Some(right) = &result.right_file {
-        output.push_str(&format!("RIGHT: {}\n", right.display()));
+    let mut annotations = Annotations::load(path)
+        .with_context(|| format!("Failed to load annotations {}", path.display()))?;
+    annotations.merge(std::mem::take(&mut result.annotations));
+    annotations.save(path)
+        .with_context(|| format!("Failed to save annotations {}", path.display()))?;
+    result.annotations = annotations;
+    Ok(())
+}
+
+/// Parse a byte size such as `1024`, `500MB` or `2GiB` (decimal `KB`/`MB`/`GB`, binary `K`/`KiB` etc.)
+pub(crate) fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let split = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+    let (digits, unit) = input.split_at(split);
+
+    let value: u64 = digits.parse().map_err(|_| format!("invalid size: {}", input))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        other => return Err(format!("unknown size unit: {}", other)),
+    };
+
+    value.checked_mul(multiplier).ok_or_else(|| format!("size is too large: {}", input))
+}
+
+/// Parse a similarity threshold from 0 to 1
+pub(crate) fn parse_similarity(input: &str) -> Result<f64, String> {
+    let threshold: f64 = input.trim().parse().map_err(|_| format!("invalid threshold: {}", input))?;
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(format!("threshold must be from 0 to 1: {}", input));
     }
+    Ok(threshold)
+}
 
-    output.push_str(&format!("TIMESTAMP: {}\n\n", result.timestamp.to_rfc3339()));
+/// Parse a duration such as `500ms`, `30s` or `2m`; a bare number is in seconds
+pub(crate) fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+    let (digits, unit) = input.split_at(split);
 
-    for entry in &result.entries {
-        output.push_str(&format!("{}\n", entry.format_readable()));
+    let value: u64 = digits.parse().map_err(|_| format!("invalid duration: {}", input))?;
+    match unit.trim() {
+        "ms" => Ok(Duration::from_millis(value)),
+        "" | "s" => Ok(Duration::from_secs(value)),
+        "m" => value.checked_mul(60).map(Duration::from_secs).ok_or_else(|| format!("duration is too long: {}", input)),
+        other => Err(format!("unknown duration unit: {}", other)),
     }
+}
 
-    output
+/// The presets and the settings each of them changes from the defaults, for `--list-presets`
+pub fn format_presets() -> String {
+    let defaults = preset_settings(&CompareOptions::default());
+    let mut text = String::new();
+    for preset in PRESETS {
+        text.push_str(&format!("{}: {}\n", preset.name, preset.description));
+        for (setting, value) in preset_settings(&(preset.options)()) {
+            if !defaults.contains(&(setting, value.clone())) {
+                text.push_str(&format!("  {} = {}\n", setting, value));
+            }
+        }
+    }
+    text
 }
 
-fn load_profile(path: &PathBuf) -> Result<CompareOptions, JsonDiffError> {
+/// The settings presets set, under their profile keys
+fn preset_settings(options: &CompareOptions) -> Vec<(&'static str, String)> {
+    let paths = |paths: &[JsonPath]| format!("{:?}", paths.iter().map(ToString::to_string).collect::<Vec<_>>());
+    vec![
+        ("ignore", paths(&options.ignore_paths)),
+        ("unordered", paths(&options.unordered_arrays)),
+        ("set", paths(&options.set_arrays)),
+        ("identify_array_item_changes", options.identify_array_item_changes.to_string()),
+        ("detect_array_moves", options.detect_array_moves.to_string()),
+        ("tag_array_item_changes", options.tag_array_item_changes.to_string()),
+        ("tag_nested_array_item_changes", options.tag_nested_array_item_changes.to_string()),
+        ("report_tolerated", options.report_tolerated.to_string()),
+        ("max_value_size", options.max_value_size.map_or_else(|| "none".to_string(), |size| size.to_string())),
+        ("numeric_equivalence", options.numeric_equivalence.to_string()),
+    ]
+}
+
+/// Name of this host, from `HOSTNAME` or `COMPUTERNAME`, else `/etc/hostname`
+fn hostname() -> Option<String> {
+    let from_env = ["HOSTNAME", "COMPUTERNAME"].into_iter().find_map(|name| std::env::var(name).ok());
+    from_env
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Read the timestamp from the `SOURCE_DATE_EPOCH` environment variable, if set
+pub(crate) fn source_date_epoch() -> Result<Option<DateTime<Utc>>> {
+    let Ok(value) = std::env::var("SOURCE_DATE_EPOCH") else {
+        return Ok(None);
+    };
+
+    let seconds: i64 = value.trim().parse()
+        .with_context(|| format!("SOURCE_DATE_EPOCH is not a number of seconds: {}", value))?;
+    let timestamp = DateTime::from_timestamp(seconds, 0)
+        .with_context(|| format!("SOURCE_DATE_EPOCH is out of range: {}", value))?;
+
+    Ok(Some(timestamp))
+}
+
+/// Compare two top-level arrays element by element, writing entries as they are found
+fn run_stream(args: &Args, options: &CompareOptions) -> Result<()> {
+    if args.format != OutputFormat::Text {
+        anyhow::bail!("--stream-array only supports --format text");
+    }
+
+    let open = |path: &PathBuf| fs::File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()));
+    let (left, right) = (open(&args.file1)?, open(&args.file2)?);
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(output_path) => Box::new(io::BufWriter::new(fs::File::create(output_path)
+            .context("Failed to write diff result to file")?)),
+        None => Box::new(io::BufWriter::new(io::stdout().lock())),
+    };
+
+    writeln!(out, "DIFF-JSON v{}", FORMAT_VERSION)?;
+    if !args.no_metadata {
+        writeln!(out, "LEFT: {}", args.file1.display())?;
+        writeln!(out, "RIGHT: {}", args.file2.display())?;
+        if !args.no_timestamp {
+            writeln!(out, "TIMESTAMP: {}", options.timestamp.unwrap_or_else(Utc::now).to_rfc3339())?;
+        }
+        writeln!(out, "GENERATOR: {}", GENERATOR)?;
+        if let Some(hostname) = hostname().filter(|_| args.hostname) {
+            writeln!(out, "HOSTNAME: {}", hostname)?;
+        }
+    }
+    writeln!(out)?;
+
+    let stream_options = StreamOptions {
+        key: args.stream_key.clone(),
+        max_buffered: args.stream_buffer,
+    };
+
+    let mut write_error = None;
+    let summary = compare_array_streams(left, right, options, &stream_options, |entry| {
+        let line = entry.format_with_options(args.output_style(), &args.value_render_options());
+        if write_error.is_none() {
+            write_error = writeln!(out, "{}", line).err();
+        }
+    }).context("Failed to compare JSON files")?;
+
+    if let Some(err) = write_error {
+        return Err(err).context("Failed to write diff result");
+    }
+    out.flush()?;
+
+    for warning in &summary.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    Ok(())
+}
+
+/// Compare the files as tables keyed by `key` and write the per-row report
+fn run_table(args: &Args, key: &str, options: &CompareOptions) -> Result<()> {
+    if !matches!(args.format, OutputFormat::Text | OutputFormat::Json) {
+        anyhow::bail!("--table only supports --format text and --format json");
+    }
+
+    let left = read_file_limited(&args.file1, options.max_input_bytes)?;
+    let right = read_file_limited(&args.file2, options.max_input_bytes)?;
+    let table = compare_tables(&left, &right, key, options).context("Failed to compare JSON files")?;
+    for warning in &table.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    let text = match args.format {
+        OutputFormat::Json => serde_json::to_string_pretty(&table).context("Failed to serialize table diff")?,
+        _ => table.format_with_options(&args.value_render_options()),
+    };
+    match &args.output {
+        Some(output_path) => fs::write(output_path, text).context("Failed to write diff result to file")?,
+        None => println!("{}", text),
+    }
+
+    Ok(())
+}
+
+/// Compare the files, reusing a cached result when both inputs and the options are unchanged
+fn compare_cached(args: &Args, cache_dir: &Path, options: &CompareOptions) -> Result<DiffResult> {
+    if args.cache_clear {
+        ResultCache::clear(cache_dir)?;
+    }
+    let cache = ResultCache::new(cache_dir);
+
+    let left_content = read_file_limited(&args.file1, options.max_input_bytes)?;
+    let right_content = read_file_limited(&args.file2, options.max_input_bytes)?;
+    let key = ResultCache::key(&left_content, &right_content, options);
+
+    let mut result = match cache.load(&key) {
+        Some(mut cached) => {
+            if !args.cache_preserve_timestamp {
+                cached.timestamp = Some(options.timestamp.unwrap_or_else(Utc::now));
+            }
+            // Nothing was compared this time
+            cached.timings = None;
+            cached
+        }
+        None => {
+            let result = compare_strings(&left_content, &right_content, options)
+                .context("Failed to compare JSON files")?;
+            // The JSON form holds non-finite numbers as strings, which would read back as such
+            if !options.lenient {
+                cache.store(&key, &result)?;
+            }
+            result
+        }
+    };
+
+    // The same contents may live at different paths
+    result.set_files(&args.file1, &args.file2);
+
+    Ok(result)
+}
+
+/// Whether to colorize output going to stdout (`to_stdout`) or to a file
+fn use_color(choice: ColorChoice, to_stdout: bool) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => to_stdout
+            && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            && io::stdout().is_terminal(),
+    }
+}
+
+/// Load a report previously written with `--format json`
+pub(crate) fn load_report(path: &PathBuf) -> Result<DiffResult> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read report {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse report {} (expected --format json output)", path.display()))
+}
+
+/// Fail early, naming the argument, when an input is missing, a directory or unreadable
+fn check_inputs(args: &Args) -> Result<()> {
+    if args.profile.as_deref().is_some_and(is_stdin) && (is_stdin(&args.file1) || is_stdin(&args.file2)) {
+        anyhow::bail!("Only one input can be read from stdin; --profile - cannot be combined with `-` as a file to compare");
+    }
+    if args.file1.is_dir() && args.file2.is_dir() {
+        anyhow::bail!(
+            "Both {} and {} are directories; json-diff compares two files, \
+             use `json-diff batch` with a manifest of file pairs to compare directories",
+            args.file1.display(), args.file2.display()
+        );
+    }
+    // With --missing-as-empty the comparison decides what to do with a missing file
+    let check = |path: &Path, argument: &'static str| match check_input_file(path) {
+        Err(JsonDiffError::FileNotFound { .. }) if args.missing_as_empty && !is_stdin(path) => Ok(()),
+        checked => checked.context(argument),
+    };
+    check(&args.file1, "First argument")?;
+    check(&args.file2, "Second argument")?;
+    Ok(())
+}
+
+/// Check that a file's SHA-256 hash matches the expected one
+fn verify_hash(path: &PathBuf, expected: &str, side: &str) -> Result<()> {
+    let content = fs::read(path)
+        .with_context(|| format!("Failed to read {} for verification", path.display()))?;
+    let actual = sha256_hex(&content);
+
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        anyhow::bail!(
+            "{} file {} does not match the expected hash: expected {}, got {}",
+            side, path.display(), expected, actual
+        );
+    }
+
+    Ok(())
+}
+
+pub(crate) fn load_profile(path: &PathBuf) -> Result<CompareOptions, JsonDiffError> {
+    load_profile_over(path, CompareOptions::default())
+}
+
+/// Load a profile, applying it on top of `base` (see [`Profile::to_options_over`])
+pub(crate) fn load_profile_over(path: &PathBuf, base: CompareOptions) -> Result<CompareOptions, JsonDiffError> {
     let content = fs::read_to_string(path)?;
-    let profile: Profile = toml::from_str(&content)
-        .map_err(|e| JsonDiffError::IoError(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            format!("Failed to parse profile: {}", e)
-        )))?;
+    parse_profile_over(&content, ProfileFormat::Toml, base)
+}
 
-    let mut options = CompareOptions::default();
+/// Parse a profile in `format`, applying it on top of `base`
+pub(crate) fn parse_profile_over(content: &str, format: ProfileFormat, base: CompareOptions) -> Result<CompareOptions, JsonDiffError> {
+    parse_profile(content, format)?.to_options_over(base)
+}
 
-    // Parse ignore paths
-    for path_str in &profile.ignore {
-        let path = JsonPath::new(path_str)?;
-        options.ignore_paths.push(path);
+/// Parse a profile in `format`
+fn parse_profile(content: &str, format: ProfileFormat) -> Result<Profile, JsonDiffError> {
+    match format {
+        ProfileFormat::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+        ProfileFormat::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
     }
+    .map_err(JsonDiffError::InvalidProfile)
+}
 
-    // Parse unordered array paths
-    for path_str in &profile.unordered {
-        let path = JsonPath::new(path_str)?;
-        options.unordered_arrays.push(path);
+/// Whether `path` is `-`, standing for stdin
+fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+impl Profile {
+    /// The comparison options described by the profile
+    pub fn to_options(&self) -> Result<CompareOptions, JsonDiffError> {
+        self.to_options_over(CompareOptions::default())
     }
 
-    // Set show_nested_differences option
-    options.show_nested_differences = profile.show_nested_differences;
+    /// The options of `base`, such as a preset, with the profile's settings replacing
+    /// its settings and the profile's rules added to its rules
+    pub fn to_options_over(&self, base: CompareOptions) -> Result<CompareOptions, JsonDiffError> {
+        let mut options = base;
+        // A setting the profile leaves out keeps the base's value
+        let set = |setting: &mut bool, value: Option<bool>| {
+            if let Some(value) = value {
+                *setting = value;
+            }
+        };
 
-    // Set identify_array_item_changes option if provided
-    if let Some(identify_array_item_changes) = profile.identify_array_item_changes {
-        options.identify_array_item_changes = identify_array_item_changes;
+        // Parse ignore paths
+        for path in &self.ignore {
+            options.ignore_paths.push(path.to_path()?);
+        }
+
+        // Parse unordered array paths
+        for path in &self.unordered {
+            options.unordered_arrays.push(path.to_path()?);
+        }
+
+        // Parse set array paths
+        for path in &self.set {
+            options.set_arrays.push(path.to_path()?);
+        }
+
+        set(&mut options.show_nested_differences, self.show_nested_differences);
+        set(&mut options.identify_array_item_changes, self.identify_array_item_changes);
+        set(&mut options.detect_array_moves, self.detect_array_moves);
+        set(&mut options.tag_array_item_changes, self.tag_array_item_changes);
+        set(&mut options.tag_nested_array_item_changes, self.tag_nested_array_item_changes);
+        set(&mut options.report_tolerated, self.report_tolerated);
+        set(&mut options.report_key_order, self.report_key_order);
+        set(&mut options.normalize_entries, self.normalize_entries);
+        if self.max_value_size.is_some() {
+            options.max_value_size = self.max_value_size;
+        }
+
+        // Parse numeric tolerances
+        for rule in &self.tolerance {
+            options.tolerances.push((rule.path.to_path()?, rule.within));
+        }
+
+        // Parse paths of strings holding embedded JSON documents
+        for path in &self.parse_embedded_json {
+            options.parse_embedded_json.push(path.to_path()?);
+        }
+        set(&mut options.auto_parse_embedded_json, self.auto_parse_embedded_json);
+
+        // Parse decoders for encoded string values
+        for rule in &self.decode {
+            options.decoders.push((rule.path.to_path()?, rule.codec));
+        }
+
+        // Parse numeric string comparison settings
+        for path in &self.numeric_strings {
+            options.numeric_strings.push(path.to_path()?);
+        }
+        set(&mut options.all_numeric_strings, self.all_numeric_strings);
+        options.number_locale = self.number_locale.unwrap_or(options.number_locale);
+        set(&mut options.numeric_equivalence, self.numeric_equivalence);
+        set(&mut options.numeric_deltas, self.numeric_deltas);
+        set(&mut options.text_fallback, self.text_fallback);
+        if let Some(rule) = &self.near_match {
+            parse_similarity(&rule.threshold.to_string()).map_err(|reason| JsonDiffError::InvalidProfile(format!("invalid near_match: {}", reason)))?;
+            let paths = rule.paths.iter().map(PathRule::to_path).collect::<Result<_, _>>()?;
+            options.string_similarity = Some((rule.threshold, paths));
+        }
+
+        // Parse expected value transitions
+        for rule in &self.allowed_transition {
+            options.allowed_transitions.push(AllowedTransition {
+                path: rule.path.to_path()?,
+                from: rule.from.clone(),
+                to: rule.to.clone(),
+            });
+        }
+
+        // Parse mappings between differently named paths
+        for rule in &self.map {
+            let mapping = PathMapping { left: JsonPath::new(&rule.left)?, right: JsonPath::new(&rule.right)? };
+            mapping.validate()?;
+            options.path_mappings.push(mapping);
+        }
+
+        // Parse preprocessing transforms
+        for rule in &self.preprocess {
+            options.preprocess.push(rule.to_transform()?);
+        }
+
+        Ok(options)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(options)
+    #[test]
+    fn test_after_display() {
+        let path = Path::new("report.txt");
+        let failed = |message: &str| Err(anyhow::anyhow!(message.to_string()));
+
+        assert!(after_display(Ok(()), Ok(()), path).is_ok());
+        // A display failure is still reported once the report is written
+        let err = after_display(failed("no terminal"), Ok(()), path).unwrap_err();
+        assert_eq!(err.to_string(), "no terminal");
+        let err = after_display(Ok(()), failed("disk full"), path).unwrap_err();
+        assert_eq!(err.to_string(), "disk full");
+        // With both failing, the display's error is returned and the write's printed
+        let err = after_display(failed("no terminal"), failed("disk full"), path).unwrap_err();
+        assert_eq!(err.to_string(), "no terminal");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Err("unknown duration unit: h".to_string()));
+        assert!(parse_duration("ms").is_err());
+    }
+
+    #[test]
+    fn test_exit_code_of_limits() {
+        let limit = anyhow::Error::from(JsonDiffError::LimitExceeded { limit: "max_nodes", value: 10 })
+            .context("Failed to compare JSON files");
+        assert_eq!(exit_code(&limit), LIMIT_EXIT_CODE);
+        assert_eq!(exit_code(&anyhow::anyhow!("other")), 1);
+    }
 }
@@ -0,0 +1,112 @@
+//! Writing the report to stdout: cut short on a terminal when it is enormous, or
+//! through a pager with `--page`
+
+use std::io::{self, ErrorKind, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+use json_diff_core::{DiffResult, DiffType};
+
+/// Most entries a text report printed to a terminal shows, unless `--all` or `--limit`
+/// say otherwise
+pub const DEFAULT_TERMINAL_LIMIT: usize = 10_000;
+
+/// How many of `total` entries to show, or `None` for all of them
+///
+/// An explicit `limit` applies wherever the report goes; without one, only a report
+/// printed to a terminal is cut, at [`DEFAULT_TERMINAL_LIMIT`]. `all` shows everything.
+pub(crate) fn entry_cap(total: usize, limit: Option<usize>, all: bool, to_terminal: bool) -> Option<usize> {
+    if all {
+        return None;
+    }
+    limit.or(to_terminal.then_some(DEFAULT_TERMINAL_LIMIT)).filter(|limit| total > *limit)
+}
+
+/// Keep the first `shown` entries of `result`, returning the notice to print after them;
+/// `explicit` says the limit was given with `--limit`, rather than the terminal's
+pub(crate) fn truncate(result: &mut DiffResult, shown: usize, explicit: bool) -> String {
+    let counts: Vec<String> = DiffType::ALL.into_iter()
+        .map(|diff_type| (result.count(diff_type.clone()), diff_type))
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, diff_type)| format!("{} {}", count, diff_type.readable_text().to_lowercase()))
+        .collect();
+    let notice = format!(
+        "Showing the first {} of {} entries ({}); {} to see everything",
+        shown,
+        result.entries().len(),
+        counts.join(", "),
+        if explicit { "drop --limit" } else { "use --all or --output" }
+    );
+    result.entries.truncate(shown);
+    notice
+}
+
+/// Print `text` to stdout, with `page` through `$PAGER` (else `less -R`) when stdout is
+/// a terminal; without a pager to start, the text is printed as it is
+pub(crate) fn print(text: &str, page: bool) -> Result<()> {
+    if page && io::stdout().is_terminal() {
+        if let Some(mut pager) = spawn_pager() {
+            // Closed once written, so the pager sees the end of the text
+            if let Some(mut stdin) = pager.stdin.take() {
+                match stdin.write_all(text.as_bytes()).and_then(|_| stdin.write_all(b"\n")) {
+                    // The pager quitting before reading everything is not an error
+                    Err(err) if err.kind() != ErrorKind::BrokenPipe => return Err(err).context("Failed to write to the pager"),
+                    _ => {}
+                }
+            }
+            pager.wait().context("Failed to wait for the pager")?;
+            return Ok(());
+        }
+    }
+    let mut stdout = io::stdout().lock();
+    match writeln!(stdout, "{}", text).and_then(|_| stdout.flush()) {
+        // Nor is a reader such as `head` closing stdout early
+        Err(err) if err.kind() != ErrorKind::BrokenPipe => Err(err).context("Failed to write to stdout"),
+        _ => Ok(()),
+    }
+}
+
+/// The pager `$PAGER` names, with its arguments, or `less -R`, which shows colors as they are
+fn pager_command() -> Vec<String> {
+    let from_env = std::env::var("PAGER").ok().map(|pager| pager.split_whitespace().map(str::to_string).collect::<Vec<_>>());
+    from_env.filter(|words| !words.is_empty()).unwrap_or_else(|| vec!["less".to_string(), "-R".to_string()])
+}
+
+fn spawn_pager() -> Option<std::process::Child> {
+    let command = pager_command();
+    Command::new(&command[0]).args(&command[1..]).stdin(Stdio::piped()).spawn().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use json_diff_core::DiffEntry;
+    use serde_json::json;
+
+    #[test]
+    fn test_entry_cap() {
+        // A terminal is cut at the default, elsewhere nothing is
+        assert_eq!(entry_cap(DEFAULT_TERMINAL_LIMIT + 1, None, false, true), Some(DEFAULT_TERMINAL_LIMIT));
+        assert_eq!(entry_cap(DEFAULT_TERMINAL_LIMIT, None, false, true), None);
+        assert_eq!(entry_cap(1_400_000, None, false, false), None);
+        // An explicit limit applies anywhere, --all overrides both
+        assert_eq!(entry_cap(50, Some(10), false, false), Some(10));
+        assert_eq!(entry_cap(5, Some(10), false, true), None);
+        assert_eq!(entry_cap(1_400_000, None, true, true), None);
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut result = DiffResult::builder().build().unwrap();
+        result.entries = vec![
+            DiffEntry::added("$.a", json!(1)),
+            DiffEntry::removed("$.b", json!(2)),
+            DiffEntry::added("$.c", json!(3)),
+        ];
+        let notice = truncate(&mut result.clone(), 2, true);
+        assert_eq!(notice, "Showing the first 2 of 3 entries (2 added, 1 removed); drop --limit to see everything");
+        let notice = truncate(&mut result, 1, false);
+        assert_eq!(notice, "Showing the first 1 of 3 entries (2 added, 1 removed); use --all or --output to see everything");
+        assert_eq!(result.entries().len(), 1);
+    }
+}
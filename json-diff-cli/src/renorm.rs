@@ -0,0 +1,63 @@
+//! Rules applied anew to a saved report, started with `json-diff renorm`
+//!
+//! The report is one written with `--format json`; its modifications are compared again
+//! under the profile, see [`json_diff_core::DiffResult::renormalize`], so a newly added
+//! ignore rule or tolerance takes effect without the files that were compared.
+
+use std::fs;
+use std::path::PathBuf;
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use json_diff_core::OutputStyle;
+
+use crate::{load_profile, load_report, OutputFormat};
+
+#[derive(Parser, Debug)]
+#[command(name = "json-diff renorm", about = "Apply a profile's rules to a saved report")]
+pub struct RenormArgs {
+    /// Report written with `--format json`
+    pub report: PathBuf,
+
+    /// Profile whose rules the modifications are compared again under
+    #[arg(short, long)]
+    pub profile: PathBuf,
+
+    /// Output format of the report (text or json)
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Output file (stdout if not specified)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Use symbols instead of readable text for diff types
+    #[arg(short = 'S', long)]
+    pub symbols: bool,
+}
+
+/// Load the report, apply the profile to it and write it
+pub fn renorm(args: RenormArgs) -> Result<()> {
+    if !matches!(args.format, OutputFormat::Text | OutputFormat::Json) {
+        bail!("renorm only supports --format text and --format json");
+    }
+
+    let mut result = load_report(&args.report)?;
+    let options = load_profile(&args.profile)
+        .with_context(|| format!("Failed to load profile {}", args.profile.display()))?;
+    let warned = result.warnings.len();
+    result.renormalize(&options).context("Failed to compare the report's values again")?;
+    for warning in &result.warnings[warned..] {
+        eprintln!("Warning: {}", warning);
+    }
+
+    let text = match args.format {
+        OutputFormat::Json => serde_json::to_string_pretty(&result).context("Failed to serialize diff result")?,
+        _ => result.format_with(if args.symbols { OutputStyle::Symbols } else { OutputStyle::Readable }),
+    };
+    match &args.output {
+        Some(output_path) => fs::write(output_path, text).context("Failed to write diff result to file")?,
+        None => println!("{}", text),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,111 @@
+//! Comparisons of embedded fixtures against their expected reports, started with
+//! `json-diff self-test`, to check a binary works where it is deployed without any files
+//!
+//! Each case of [`cases::CASES`] is compared with its profile and written as the readable
+//! report without metadata, which must equal the expected one byte for byte; a mismatch
+//! names the first line that differs. New features append their cases there.
+
+mod cases;
+
+use anyhow::Result;
+use clap::Parser;
+use json_diff_core::{quick, CompareOptions, GENERATOR};
+
+use crate::{parse_profile_over, ProfileFormat};
+pub(crate) use cases::{Case, CASES};
+
+#[derive(Parser, Debug)]
+#[command(name = "json-diff self-test", about = "Compare embedded fixtures and check the reports")]
+pub struct SelfTestArgs {
+    /// Run only the cases whose name contains this text
+    #[arg(long, value_name = "TEXT")]
+    pub case: Option<String>,
+}
+
+/// Exit code of `json-diff self-test` when a case fails
+pub const SELF_TEST_FAILED_EXIT_CODE: i32 = 1;
+
+/// Run the cases `args` select, printing PASS or FAIL for each and then the counts
+///
+/// Returns whether every case passed.
+pub fn self_test(args: SelfTestArgs) -> Result<bool> {
+    let cases: Vec<&Case> = CASES.iter()
+        .filter(|case| args.case.as_ref().is_none_or(|text| case.name.contains(text.as_str())))
+        .collect();
+    if cases.is_empty() {
+        anyhow::bail!("No case matches {:?}", args.case.unwrap_or_default());
+    }
+
+    println!("{} self-test on {} {}", GENERATOR, std::env::consts::OS, std::env::consts::ARCH);
+    let mut failed = 0;
+    for case in &cases {
+        match run_case(case) {
+            Ok(()) => println!("PASS {}", case.name),
+            Err(problem) => {
+                failed += 1;
+                println!("FAIL {}: {}", case.name, problem);
+            }
+        }
+    }
+    println!("{} cases: {} passed, {} failed", cases.len(), cases.len() - failed, failed);
+    Ok(failed == 0)
+}
+
+/// Compare the case's documents and check the report, or tell what went wrong
+pub(crate) fn run_case(case: &Case) -> Result<(), String> {
+    let options = parse_profile_over(case.profile, ProfileFormat::Toml, CompareOptions::default())
+        .map_err(|e| format!("invalid profile: {}", e))?;
+    let report = quick::diff_strings_to_string_with_options(case.left, case.right, &options)
+        .map_err(|e| format!("comparison failed: {}", e))?;
+    if report == case.expected {
+        return Ok(());
+    }
+
+    let (mut expected, mut actual) = (case.expected.lines(), report.lines());
+    for line in 1.. {
+        match (expected.next(), actual.next()) {
+            (Some(expected), Some(actual)) if expected == actual => continue,
+            (None, None) => break,
+            (expected, actual) => return Err(format!(
+                "line {}: expected {:?}, got {:?}",
+                line,
+                expected.unwrap_or("<end>"),
+                actual.unwrap_or("<end>")
+            )),
+        }
+    }
+    // Only the line endings differ
+    Err(format!("expected {:?}, got {:?}", case.expected, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cases_pass() {
+        for case in CASES {
+            assert_eq!(run_case(case), Ok(()), "{}", case.name);
+        }
+        assert!(CASES.iter().any(|case| case.profile.is_empty()) && CASES.iter().any(|case| !case.profile.is_empty()));
+    }
+
+    #[test]
+    fn test_corrupted_expectation_fails() {
+        let case = CASES.iter().find(|case| case.name == "nested_objects").unwrap();
+        let corrupted = case.expected.replace("2 -> 4", "2 -> 5");
+        let problem = run_case(&Case { expected: &corrupted, ..*case }).unwrap_err();
+        assert!(problem.starts_with("line 3: expected \"[MODIFIED] $.service.limits.cpu (L4:L4): 2 -> 5\""), "{}", problem);
+
+        let truncated = case.expected.lines().take(3).collect::<Vec<_>>().join("\n");
+        let problem = run_case(&Case { expected: &truncated, ..*case }).unwrap_err();
+        assert!(problem.starts_with("line 4: expected \"<end>\", got \"[ADDED]"), "{}", problem);
+
+        // Line endings alone are told apart too
+        let crlf = case.expected.replace('\n', "\r\n");
+        assert!(run_case(&Case { expected: &crlf, ..*case }).unwrap_err().starts_with("expected \"DIFF-JSON v1\\r\\n"));
+
+        let broken = Case { profile: "ignore = [", ..*case };
+        assert!(run_case(&broken).unwrap_err().starts_with("invalid profile"));
+    }
+}
@@ -0,0 +1,121 @@
+//! The cases of `json-diff self-test`: documents, a profile and the expected report
+
+/// A comparison of two documents and the report it must give
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Case<'a> {
+    pub name: &'a str,
+    pub left: &'a str,
+    pub right: &'a str,
+    /// Profile in TOML; empty for the default options
+    pub profile: &'a str,
+    /// The readable report without metadata, see [`json_diff_core::quick`]
+    pub expected: &'a str,
+}
+
+pub(crate) static CASES: &[Case<'static>] = &[
+    Case {
+        name: "nested_objects",
+        left: include_str!("fixtures/nested.left.json"),
+        right: include_str!("fixtures/nested.right.json"),
+        profile: "",
+        expected: concat!(
+            "DIFF-JSON v1\n\n",
+            "[MODIFIED] $.service.limits.cpu (L4:L4): 2 -> 4\n",
+            "[ADDED] $.service.limits.pids (L4:L4): 100\n",
+            "[MODIFIED] $.service.debug (L5:L5): false -> null\n",
+        ),
+    },
+    Case {
+        name: "arrays",
+        left: include_str!("fixtures/arrays.left.json"),
+        right: include_str!("fixtures/arrays.right.json"),
+        profile: "",
+        expected: concat!(
+            "DIFF-JSON v1\n\n",
+            "[MODIFIED] $.ports[1] (L2:L2): 443 -> 8443\n",
+            "[REMOVED] $.ports[2] (L2:L2): 8080\n",
+            "[MODIFIED] $.matrix[1][1] (L3:L3): 4 -> 5\n",
+            "[ADDED] $.matrix[2] (L3:L3): [6]\n",
+        ),
+    },
+    Case {
+        name: "unordered_arrays",
+        left: include_str!("fixtures/unordered.left.json"),
+        right: include_str!("fixtures/unordered.right.json"),
+        profile: "unordered = [\"$.tags\", \"$.users\"]\nshow_nested_differences = true",
+        expected: concat!(
+            "DIFF-JSON v1\n\n",
+            "[ARRAY_REORDERED] $.tags (L2:L2): [REORDERED]\n",
+            "[ARRAY_REORDERED] $.users (L3:L3): [REORDERED]\n",
+            "[MODIFIED] $.users[0].name (right: $.users[1].name) (L3:L3): \"ann\" -> \"anne\"\n",
+        ),
+    },
+    Case {
+        name: "ignored_paths",
+        left: include_str!("fixtures/ignore.left.json"),
+        right: include_str!("fixtures/ignore.right.json"),
+        profile: r#"ignore = ["$.meta.generated_at", { pattern = '\$\.meta\.ho.*' }]"#,
+        expected: concat!(
+            "DIFF-JSON v1\n\n",
+            "[IGNORED] $.meta.generated_at (L3:L3): [IGNORED]\n",
+            "[IGNORED] $.meta.host (L3:L3): [IGNORED]\n",
+            "[MODIFIED] $.total (L4:L4): 10 -> 11\n",
+        ),
+    },
+    Case {
+        name: "unicode_keys",
+        left: include_str!("fixtures/unicode.left.json"),
+        right: include_str!("fixtures/unicode.right.json"),
+        profile: "",
+        expected: concat!(
+            "DIFF-JSON v1\n\n",
+            "[MODIFIED] $.名前 (L2:L2): \"太郎\" -> \"花子\"\n",
+            "[MODIFIED] $.emoji 🎉 (L4:L4): 1 -> 2\n",
+            "[ADDED] $.ωmega (L4:L5): true\n",
+            "[REMOVED] $.Ωmega (L5:L4): true\n",
+        ),
+    },
+    Case {
+        name: "big_numbers",
+        left: include_str!("fixtures/big_numbers.left.json"),
+        right: include_str!("fixtures/big_numbers.right.json"),
+        profile: "",
+        expected: concat!(
+            "DIFF-JSON v1\n\n",
+            "[MODIFIED] $.id (L2:L2): 9007199254740993 -> 9007199254740992\n",
+            "[MODIFIED] $.max (L3:L3): 18446744073709551615 -> 18446744073709551614\n",
+            "[MODIFIED] $.min (L4:L4): -9223372036854775808 -> -9223372036854775807\n",
+            "[MODIFIED] $.tiny (L5:L5): 1e-300 -> 2e-300\n",
+        ),
+    },
+    // Written inline, so a checkout converting line endings cannot change them
+    Case {
+        name: "crlf_line_endings",
+        left: "{\r\n  \"a\": 1,\r\n  \"b\": \"x\\r\\ny\"\r\n}\r\n",
+        right: "{\r\n  \"a\": 2,\r\n  \"b\": \"x\\ny\"\r\n}\r\n",
+        profile: "",
+        expected: concat!(
+            "DIFF-JSON v1\n\n",
+            "[MODIFIED] $.a (L2:L2): 1 -> 2\n",
+            "[MODIFIED] $.b (L3:L3): \"x\\r\\ny\" -> \"x\\ny\"\n",
+        ),
+    },
+    // Numbers in strings read with a decimal comma, whatever the system's locale
+    Case {
+        name: "number_locale",
+        left: r#"{"price": "1.234,50", "rate": "0,5"}"#,
+        right: r#"{"price": "1234,5", "rate": "0,75"}"#,
+        profile: "all_numeric_strings = true\nnumber_locale = \"comma\"",
+        expected: concat!(
+            "DIFF-JSON v1\n\n",
+            "[MODIFIED] $.rate (L1:L1): \"0,5\" -> \"0,75\"\n",
+        ),
+    },
+    Case {
+        name: "equal_documents",
+        left: include_str!("fixtures/nested.left.json"),
+        right: include_str!("fixtures/nested.left.json"),
+        profile: "",
+        expected: "DIFF-JSON v1\n\n",
+    },
+];
@@ -0,0 +1,287 @@
+//! HTTP server exposing comparisons, started with `json-diff serve`
+//!
+//! - `POST /compare` takes `{"left": <json>, "right": <json>, "options": {...}}` and
+//!   answers with the serialized `DiffResult`
+//! - `POST /compare/text` takes the same request and answers with the readable report
+//!
+//! `options` has the shape of a profile written as JSON, e.g.
+//! `{"ignore": ["$.id"], "tolerance": [{"path": "$.price", "within": 0.01}]}`.
+//! Failures are answered with `{"error": "<message>"}` and a 4xx status, and
+//! comparisons stopped by the server's `--max-duration` or `--max-nodes` with a 503.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use json_diff_core::{compare_json, JsonDiffError};
+
+use crate::{parse_duration, parse_size, Profile};
+
+/// Largest request head (request line and headers) read before giving up
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+#[derive(Parser, Debug)]
+#[command(name = "json-diff serve", about = "Serve comparisons over HTTP")]
+pub struct ServeArgs {
+    /// Port to listen on; 0 picks a free one
+    #[arg(long, default_value_t = 8080)]
+    pub port: u16,
+
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    pub host: String,
+
+    /// Number of requests handled at the same time
+    #[arg(long, default_value_t = 4)]
+    pub workers: usize,
+
+    /// Seconds a client may take to send a request or read the response
+    #[arg(long, default_value_t = 30)]
+    pub timeout: u64,
+
+    /// Largest request body accepted (e.g. 16MiB)
+    #[arg(long, value_parser = parse_size, default_value = "16MiB")]
+    pub max_request_size: u64,
+
+    /// Longest a comparison may run, e.g. 500ms or 10s
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "10s")]
+    pub max_duration: Duration,
+
+    /// Most values a comparison may visit across both documents
+    #[arg(long, value_name = "COUNT", default_value_t = 10_000_000)]
+    pub max_nodes: u64,
+}
+
+/// Work one comparison may do before it is stopped, whatever options the client sends
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    max_duration: Duration,
+    max_nodes: u64,
+}
+
+/// Body of a comparison request
+#[derive(Debug, Deserialize)]
+struct CompareRequest {
+    left: Value,
+    right: Value,
+    #[serde(default)]
+    options: Option<Profile>,
+}
+
+/// A response to send: status, content type and body
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: &impl serde::Serialize) -> Self {
+        Self { status, content_type: "application/json", body: serde_json::to_string(body).unwrap_or_default() }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self::json(status, &json!({ "error": message.into() }))
+    }
+}
+
+/// Listen on the configured address and serve requests until the process ends
+///
+/// The bound address is printed first, so callers that asked for port 0 learn it.
+pub fn serve(args: ServeArgs) -> Result<()> {
+    let listener = TcpListener::bind((args.host.as_str(), args.port))
+        .with_context(|| format!("Failed to listen on {}:{}", args.host, args.port))?;
+    println!("Listening on http://{}", listener.local_addr()?);
+    std::io::stdout().flush()?;
+
+    // Each worker accepts connections from the shared listener in turn
+    let workers: Vec<_> = (0..args.workers.max(1))
+        .map(|_| {
+            let listener = listener.try_clone()?;
+            let timeout = Duration::from_secs(args.timeout.max(1));
+            let max_body = args.max_request_size;
+            let limits = Limits { max_duration: args.max_duration, max_nodes: args.max_nodes };
+            Ok(std::thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    // A client that misbehaves only loses its own connection
+                    let _ = handle_connection(stream, timeout, max_body, limits);
+                }
+            }))
+        })
+        .collect::<std::io::Result<_>>()?;
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream, timeout: Duration, max_body: u64, limits: Limits) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let response = match read_request(&mut reader, max_body) {
+        Ok((method, path, body)) => route(&method, &path, &body, limits),
+        Err(response) => response,
+    };
+    write_response(stream, &response)
+}
+
+/// Read the method, path and body of a request, or the error response to send instead
+fn read_request(reader: &mut impl BufRead, max_body: u64) -> Result<(String, String, Vec<u8>), Response> {
+    let mut head = Vec::new();
+    loop {
+        let mut line = Vec::new();
+        let read = reader.by_ref().take((MAX_HEAD_BYTES + 1 - head.len().min(MAX_HEAD_BYTES)) as u64)
+            .read_until(b'\n', &mut line)
+            .map_err(|e| Response::error(408, format!("Failed to read request: {}", e)))?;
+        head.extend_from_slice(&line);
+        if head.len() > MAX_HEAD_BYTES {
+            return Err(Response::error(431, "Request headers are too large"));
+        }
+        if read == 0 || line == b"\r\n" || line == b"\n" {
+            break;
+        }
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(path)) = (request_line.next(), request_line.next()) else {
+        return Err(Response::error(400, "Malformed request line"));
+    };
+
+    let content_length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .map(|(_, value)| value.trim().parse::<u64>());
+    let length = match content_length {
+        Some(Ok(length)) => length,
+        Some(Err(_)) => return Err(Response::error(400, "Invalid Content-Length")),
+        None if method == "POST" => return Err(Response::error(411, "Content-Length is required")),
+        None => 0,
+    };
+    if length > max_body {
+        return Err(Response::error(413, format!("Request body exceeds the limit of {} bytes", max_body)));
+    }
+
+    let mut body = vec![0; length as usize];
+    reader.read_exact(&mut body)
+        .map_err(|e| Response::error(408, format!("Failed to read request body: {}", e)))?;
+
+    Ok((method.to_string(), path.to_string(), body))
+}
+
+fn route(method: &str, path: &str, body: &[u8], limits: Limits) -> Response {
+    let as_text = match path {
+        "/compare" => false,
+        "/compare/text" => true,
+        _ => return Response::error(404, format!("No such endpoint: {}", path)),
+    };
+    if method != "POST" {
+        return Response::error(405, format!("{} expects POST", path));
+    }
+
+    let request: CompareRequest = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => return Response::error(400, format!("Invalid request: {}", e)),
+    };
+    let mut options = match request.options.as_ref().map(Profile::to_options).transpose() {
+        Ok(options) => options.unwrap_or_default(),
+        Err(e) => return Response::error(400, format!("Invalid options: {}", e)),
+    };
+    options.max_duration = Some(limits.max_duration);
+    options.max_nodes = Some(limits.max_nodes);
+
+    match compare_json(&request.left, &request.right, &options) {
+        Ok(result) if as_text => Response { status: 200, content_type: "text/plain; charset=utf-8", body: result.format_readable() },
+        Ok(result) => Response::json(200, &result),
+        Err(e @ JsonDiffError::LimitExceeded { .. }) => Response::error(503, e.to_string()),
+        Err(e) => Response::error(422, format!("Comparison failed: {}", e)),
+    }
+}
+
+fn write_response(mut stream: TcpStream, response: &Response) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status, reason, response.content_type, response.body.len(), response.body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(text: &str, max_body: u64) -> Result<(String, String, Vec<u8>), Response> {
+        read_request(&mut BufReader::new(text.as_bytes()), max_body)
+    }
+
+    #[test]
+    fn test_read_request() {
+        let (method, path, body) = request("POST /compare HTTP/1.1\r\ncontent-length: 4\r\n\r\nbody", 10).ok().unwrap();
+        assert_eq!((method.as_str(), path.as_str(), body.as_slice()), ("POST", "/compare", b"body".as_slice()));
+
+        assert_eq!(request("POST /compare HTTP/1.1\r\nContent-Length: 11\r\n\r\n", 10).err().unwrap().status, 413);
+        assert_eq!(request("POST /compare HTTP/1.1\r\n\r\n", 10).err().unwrap().status, 411);
+        assert_eq!(request("\r\n", 10).err().unwrap().status, 400);
+        let huge = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_HEAD_BYTES));
+        assert_eq!(request(&huge, 10).err().unwrap().status, 431);
+    }
+
+    const LIMITS: Limits = Limits { max_duration: Duration::from_secs(10), max_nodes: 10_000 };
+
+    fn route(method: &str, path: &str, body: &[u8]) -> Response {
+        super::route(method, path, body, LIMITS)
+    }
+
+    #[test]
+    fn test_route() {
+        let body = br#"{"left": {"id": 1, "a": 1}, "right": {"id": 2, "a": 2}, "options": {"ignore": ["$.id"]}}"#;
+        let response = route("POST", "/compare/text", body);
+        assert_eq!(response.status, 200);
+        assert!(response.body.contains("[MODIFIED] $.a: 1 -> 2"));
+        assert!(response.body.contains("[IGNORED] $.id"));
+
+        assert_eq!(route("GET", "/compare", body).status, 405);
+        assert_eq!(route("POST", "/other", body).status, 404);
+        let response = route("POST", "/compare", br#"{"left": 1}"#);
+        assert_eq!(response.status, 400);
+        assert!(response.body.contains("missing field `right`"));
+        let response = route("POST", "/compare", br#"{"left": 1, "right": 2, "options": {"ignore": ["id"]}}"#);
+        assert_eq!(response.status, 400);
+        assert!(response.body.starts_with(r#"{"error":"Invalid options: Invalid JSON path"#));
+    }
+
+    #[test]
+    fn test_route_limits() {
+        // Arrays whose moves would weigh every pair of elements, as a client may ask
+        let (left, right): (Vec<u32>, Vec<u32>) = ((0..500).collect(), (0..500).rev().collect());
+        let body = json!({"left": left, "right": right, "options": {"detect_array_moves": true}}).to_string();
+        let response = route("POST", "/compare", body.as_bytes());
+        assert_eq!(response.status, 503);
+        assert_eq!(response.body, r#"{"error":"Comparison stopped: visited more than 10000 values (max_nodes)"}"#);
+
+        let limits = Limits { max_duration: Duration::ZERO, max_nodes: u64::MAX };
+        let body = json!({"left": vec![0; 5000], "right": vec![0; 5000]}).to_string();
+        assert_eq!(super::route("POST", "/compare", body.as_bytes(), limits).status, 503);
+    }
+}
@@ -0,0 +1,99 @@
+//! The summary `--summary-only` prints instead of the entries: counts per type, the share
+//! of unchanged lines and the paths holding the most differences
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use unicode_width::UnicodeWidthStr;
+use json_diff_core::{escape_control, DiffResult, DiffType, PathCount};
+
+/// Steps below the root the top paths are cut to, by default
+pub const DEFAULT_SUMMARY_DEPTH: usize = 1;
+
+/// Number of top paths listed, by default
+pub const DEFAULT_SUMMARY_TOP: usize = 10;
+
+/// Counts of a result and where its differences are
+#[derive(Debug, Serialize)]
+pub(crate) struct Summary {
+    /// Entries per type, every type included
+    counts: BTreeMap<String, usize>,
+    total: usize,
+    /// Entries besides ignored and tolerated ones
+    differences: usize,
+    /// Percentage of the lines of the inputs no difference points at
+    similarity: f64,
+    /// Steps below the root the top paths are cut to
+    depth: usize,
+    top_paths: Vec<PathCount>,
+}
+
+impl Summary {
+    /// The summary of `result`, whose inputs have `left_lines` and `right_lines` lines
+    pub(crate) fn new(result: &DiffResult, (left_lines, right_lines): (usize, usize), depth: usize, top: usize) -> Self {
+        Self {
+            counts: DiffType::ALL.into_iter().map(|diff_type| (format!("{:?}", diff_type), result.count(diff_type))).collect(),
+            total: result.entries().len(),
+            differences: result.iter().filter(|entry| entry.diff_type.is_difference()).count(),
+            similarity: result.line_similarity(left_lines, right_lines),
+            depth,
+            top_paths: result.top_paths(depth, top),
+        }
+    }
+
+    /// The summary as tables: entries by type, then the top paths
+    pub(crate) fn format_text(&self) -> String {
+        let mut text = String::from("Entries by type\n");
+        for diff_type in DiffType::ALL {
+            let count = self.counts.get(&format!("{:?}", diff_type)).copied().unwrap_or(0);
+            text.push_str(&format!("  {:<20} {}\n", diff_type.readable_text(), count));
+        }
+        text.push_str(&format!("  Total: {}, of which differences: {}\n", self.total, self.differences));
+        text.push_str(&format!("  Similarity: {:.1}% of lines unchanged\n\n", self.similarity));
+
+        if self.top_paths.is_empty() {
+            text.push_str(&format!("Top paths at depth {}: none", self.depth));
+            return text;
+        }
+        let paths: Vec<_> = self.top_paths.iter().map(|top| escape_control(&top.path)).collect();
+        // Padded by terminal columns, which `{:<width$}` does not count
+        let width = paths.iter().map(|path| path.width()).max().unwrap_or(0).max("Path".len());
+        let pad = |path: &str| format!("{}{}", path, " ".repeat(width - path.width()));
+        text.push_str(&format!("Top paths at depth {}\n", self.depth));
+        text.push_str(&format!("  {}  {:>6}  Mostly", pad("Path"), "Count"));
+        for (top, path) in self.top_paths.iter().zip(&paths) {
+            text.push_str(&format!("\n  {}  {:>6}  {}", pad(path), top.count, top.dominant_type.readable_text()));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use json_diff_core::DiffEntry;
+    use serde_json::json;
+
+    #[test]
+    fn test_format_text() {
+        let mut result = DiffResult::builder().build().unwrap();
+        result.entries = vec![
+            DiffEntry::added("$.items[1]", json!(1)).with_lines(Some(3), Some(4)),
+            DiffEntry::modified("$.items[0]", json!(1), json!(2)).with_lines(Some(2), Some(2)),
+            DiffEntry::modified("$.name", json!("a"), json!("b")).with_lines(Some(1), Some(1)),
+            DiffEntry::ignored("$.ts"),
+        ];
+        let text = Summary::new(&result, (5, 5), 1, 1).format_text();
+        assert!(text.contains("  MODIFIED             2\n"), "{}", text);
+        assert!(text.contains("  Total: 4, of which differences: 3\n  Similarity: 50.0% of lines unchanged\n"), "{}", text);
+        assert!(text.ends_with("Top paths at depth 1\n  Path      Count  Mostly\n  $.items       2  ADDED"), "{}", text);
+
+        // Wide characters take two columns
+        result.entries.push(DiffEntry::added("$.名前", json!(1)).with_lines(None, Some(5)));
+        let text = Summary::new(&result, (5, 5), 1, 3).format_text();
+        assert!(text.ends_with("  $.name        1  MODIFIED\n  $.名前        1  ADDED"), "{}", text);
+
+        let text = Summary::new(&DiffResult::builder().build().unwrap(), (0, 0), 2, 10).format_text();
+        assert!(text.ends_with("Similarity: 100.0% of lines unchanged\n\nTop paths at depth 2: none"), "{}", text);
+    }
+}
@@ -0,0 +1,150 @@
+//! Benchmarks of the comparison pipeline
+//!
+//! Run with `cargo bench -p json-diff-core`. Set `JSON_DIFF_BENCH_SMOKE=1` for a quick
+//! run with a reduced sample size and smaller inputs, e.g. to check that the
+//! benchmarks still work in CI. Baseline numbers are recorded in `docs/benchmarks.md`.
+
+mod workloads;
+
+use std::time::Duration;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serde_json::{json, Value};
+use json_diff_core::{compare_files, compare_json, compare_strings, CompareOptions, JsonPath, RegexOpts};
+
+fn is_smoke_run() -> bool {
+    std::env::var_os("JSON_DIFF_BENCH_SMOKE").is_some()
+}
+
+fn workload_size() -> usize {
+    if is_smoke_run() { 100 } else { 1_000 }
+}
+
+/// Traversal alone, on already parsed documents
+fn bench_traversal(c: &mut Criterion) {
+    let mut group = c.benchmark_group("traversal");
+
+    for workload in workloads::all(workload_size()) {
+        group.bench_function(workload.name, |b| {
+            b.iter(|| compare_json(&workload.left, &workload.right, &workload.options).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+/// Reading, parsing, line mapping and traversal of files on disk
+fn bench_end_to_end(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let mut group = c.benchmark_group("compare_files");
+    // Line mapping dominates these and takes seconds per iteration at full size
+    group.sample_size(10);
+
+    for workload in workloads::all(workload_size()) {
+        let left_path = dir.path().join(format!("{}-left.json", workload.name));
+        let right_path = dir.path().join(format!("{}-right.json", workload.name));
+        std::fs::write(&left_path, serde_json::to_string_pretty(&workload.left).unwrap()).unwrap();
+        std::fs::write(&right_path, serde_json::to_string_pretty(&workload.right).unwrap()).unwrap();
+
+        group.bench_function(workload.name, |b| {
+            b.iter(|| compare_files(&left_path, &right_path, &workload.options).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+/// Line mapping is sensitive to layout: one long line versus one line per value
+fn bench_line_map(c: &mut Criterion) {
+    let mut group = c.benchmark_group("line_map");
+    group.sample_size(10);
+
+    for workload in workloads::all(workload_size()).into_iter().filter(|w| w.name == "ordered_array") {
+        for (layout, left, right) in [
+            ("pretty", serde_json::to_string_pretty(&workload.left).unwrap(), serde_json::to_string_pretty(&workload.right).unwrap()),
+            ("minified", serde_json::to_string(&workload.left).unwrap(), serde_json::to_string(&workload.right).unwrap()),
+        ] {
+            group.throughput(Throughput::Bytes((left.len() + right.len()) as u64));
+            group.bench_with_input(BenchmarkId::new(workload.name, layout), &(left, right), |b, (left, right)| {
+                b.iter(|| compare_strings(left, right, &workload.options).unwrap())
+            });
+        }
+    }
+
+    group.finish();
+}
+
+/// Many regex ignore rules on a document with many paths, where rule matching dominates
+fn bench_ignore_rules(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ignore_rules");
+    group.sample_size(10);
+
+    // About 100k paths (10k in a smoke run): nine per record
+    let records = if is_smoke_run() { 1_100 } else { 11_000 };
+    let document = |changed: bool| -> Value {
+        let items: Vec<Value> = (0..records)
+            .map(|i| json!({"id": i, "name": format!("item-{}", i), "price": if changed && i % 97 == 13 { -1 } else { 1 }, "tags": ["a", "b", "c"], "active": true}))
+            .collect();
+        json!({ "items": items })
+    };
+    let (left, right) = (document(false), document(true));
+
+    for rules in [1, 50] {
+        // Only the last rule matches anything
+        let mut options = CompareOptions::default();
+        for i in 1..rules {
+            let pattern = format!(r"\$\.items\[\d+\]\.(meta|extra){}\..*", i);
+            options.ignore_paths.push(JsonPath::with_regex_opts("$", &pattern, RegexOpts::default()).unwrap());
+        }
+        options.ignore_paths.push(JsonPath::with_regex_opts("$", r"\$\.items\[\d+\]\.tags", RegexOpts::default()).unwrap());
+
+        group.bench_function(BenchmarkId::new("regex", rules), |b| {
+            b.iter(|| compare_json(&left, &right, &options).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+/// Line mapping of one object with 50k keys (5k in a smoke run), a fifth of them
+/// only in the right document, which are anchored among their siblings in the left one
+fn bench_wide_line_map(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wide_line_map");
+    group.sample_size(10);
+
+    let keys = if is_smoke_run() { 5_000 } else { 50_000 };
+    let document = |with_new: bool| -> String {
+        let mut object = serde_json::Map::new();
+        for i in 0..keys {
+            if i % 5 != 0 || with_new {
+                object.insert(format!("key_{:06}", i), json!(i));
+            }
+        }
+        serde_json::to_string_pretty(&Value::Object(object)).unwrap()
+    };
+    let (left, right) = (document(false), document(true));
+
+    group.throughput(Throughput::Bytes((left.len() + right.len()) as u64));
+    group.bench_function(BenchmarkId::new("keys", keys), |b| {
+        b.iter(|| compare_strings(&left, &right, &CompareOptions::default()).unwrap())
+    });
+
+    group.finish();
+}
+
+fn config() -> Criterion {
+    if is_smoke_run() {
+        Criterion::default()
+            .sample_size(10)
+            .warm_up_time(Duration::from_millis(100))
+            .measurement_time(Duration::from_millis(200))
+    } else {
+        Criterion::default()
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = config();
+    targets = bench_traversal, bench_end_to_end, bench_line_map, bench_ignore_rules, bench_wide_line_map
+}
+criterion_main!(benches);
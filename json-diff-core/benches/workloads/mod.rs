@@ -0,0 +1,120 @@
+//! Deterministic workload generators shared by the benchmarks and the `gen_fixture` example
+
+use serde_json::{json, Map, Value};
+use json_diff_core::{CompareOptions, JsonPath};
+
+/// serde_json refuses to parse documents nested deeper than 128 levels
+const MAX_DEPTH: usize = 100;
+
+/// A pair of documents to compare, with the options they are meant to be compared with
+pub struct Workload {
+    pub name: &'static str,
+    pub left: Value,
+    pub right: Value,
+    // Only the benchmarks compare; the fixture generator just writes the documents
+    #[allow(dead_code)]
+    pub options: CompareOptions,
+}
+
+/// Every workload, scaled by `size` (number of keys or array elements)
+pub fn all(size: usize) -> Vec<Workload> {
+    vec![
+        deep_nesting(size),
+        wide_object(size),
+        ordered_array(size),
+        unordered_array(size),
+    ]
+}
+
+/// Small deterministic pseudo-random generator, so fixtures are identical across runs
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+}
+
+fn record(id: usize, rng: &mut Lcg) -> Value {
+    json!({
+        "id": id,
+        "name": format!("item-{}", id),
+        "price": (rng.next() % 10_000) as f64 / 100.0,
+        "tags": ["a", "b", "c"],
+        "active": rng.next().is_multiple_of(2),
+    })
+}
+
+/// Change roughly one element in a hundred, at deterministic positions
+fn planted(index: usize) -> bool {
+    index % 97 == 13
+}
+
+fn deep_nesting(size: usize) -> Workload {
+    let depth = size.clamp(1, MAX_DEPTH);
+    let nest = |leaf: Value| (0..depth).fold(leaf, |inner, level| json!({ format!("level{}", level): inner, "sibling": level }));
+
+    Workload {
+        name: "deep_nesting",
+        left: nest(json!({"value": 1})),
+        right: nest(json!({"value": 2})),
+        options: CompareOptions::default(),
+    }
+}
+
+fn wide_object(size: usize) -> Workload {
+    let mut rng = Lcg(1);
+    let mut left = Map::new();
+    let mut right = Map::new();
+
+    for i in 0..size {
+        let value = Value::from(rng.next() % 1000);
+        left.insert(format!("key{}", i), value.clone());
+        right.insert(format!("key{}", i), if planted(i) { Value::from("changed") } else { value });
+    }
+
+    Workload {
+        name: "wide_object",
+        left: Value::Object(left),
+        right: Value::Object(right),
+        options: CompareOptions::default(),
+    }
+}
+
+fn ordered_array(size: usize) -> Workload {
+    let mut rng = Lcg(2);
+    let left: Vec<Value> = (0..size).map(|i| record(i, &mut rng)).collect();
+    let right: Vec<Value> = left.iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let mut item = item.clone();
+            if planted(i) {
+                item["price"] = json!(-1.0);
+            }
+            item
+        })
+        .collect();
+
+    Workload {
+        name: "ordered_array",
+        left: json!({ "items": left }),
+        right: json!({ "items": right }),
+        options: CompareOptions::default(),
+    }
+}
+
+fn unordered_array(size: usize) -> Workload {
+    let mut workload = ordered_array(size);
+    workload.name = "unordered_array";
+
+    // Same records, rotated, so every element must be matched by id
+    if let Some(items) = workload.right["items"].as_array_mut() {
+        let shift = items.len() / 3;
+        items.rotate_left(shift);
+    }
+    workload.options.unordered_arrays.push(JsonPath::new("$.items").expect("valid path"));
+    // Without nested differences the arrays are only checked for equality
+    workload.options.show_nested_differences = true;
+    workload
+}
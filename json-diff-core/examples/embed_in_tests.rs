@@ -0,0 +1,75 @@
+//! Checks on two documents inside a crate's own tests: whether they are equivalent
+//! under a set of rules, and what text their differences give
+//!
+//! ```text
+//! cargo test -p json-diff-core --example embed_in_tests
+//! ```
+//!
+//! [`quick::assert_equivalent`] fails a test with the readable report when the documents
+//! differ beyond what the options ignore or tolerate, so the failure says what changed.
+//! [`quick::diff_to_string`] gives the same text on every run and host, fit to be stored
+//! and compared against as a snapshot.
+
+use serde_json::{json, Value};
+use json_diff_core::{quick, CompareOptions, JsonPath};
+
+/// The response a service under test might return, stamped with the time it was made
+fn render_order(id: u64, generated_at: &str) -> Value {
+    json!({
+        "id": id,
+        "generated_at": generated_at,
+        "lines": [{"sku": "A-1", "qty": 2}, {"sku": "B-7", "qty": 1}],
+        "total": 29.97,
+    })
+}
+
+/// The options the tests compare responses under: the stamp is ignored
+fn response_options() -> CompareOptions {
+    CompareOptions { ignore_paths: vec![JsonPath::new("$.generated_at").unwrap()], ..CompareOptions::default() }
+}
+
+fn main() {
+    let expected = render_order(7, "2024-01-01T00:00:00Z");
+    let actual = render_order(7, "2024-06-30T12:00:00Z");
+    quick::assert_equivalent(&expected, &actual, &response_options());
+
+    let changed = json!({"id": 7, "generated_at": "now", "lines": [], "total": 0});
+    // Caught here to show the message, which a failing test prints instead
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let failure = std::panic::catch_unwind(|| quick::assert_equivalent(&expected, &changed, &response_options()));
+    std::panic::set_hook(hook);
+    let message = failure.unwrap_err().downcast::<String>().unwrap();
+    println!("{}", message);
+    assert!(message.starts_with("documents differ:\nDIFF-JSON v1\n"));
+    assert!(message.contains("[MODIFIED] $.total: 29.97 -> 0\n"));
+}
+
+#[test]
+fn runs() {
+    main();
+}
+
+#[test]
+fn ignored_stamp_is_equivalent() {
+    quick::assert_equivalent(&render_order(1, "a"), &render_order(1, "b"), &response_options());
+}
+
+#[test]
+#[should_panic(expected = "[MODIFIED] $.id: 1 -> 2")]
+fn other_order_is_not() {
+    quick::assert_equivalent(&render_order(1, "a"), &render_order(2, "a"), &response_options());
+}
+
+#[test]
+fn report_matches_snapshot() {
+    let text = quick::diff_to_string(&render_order(1, "a"), &render_order(1, "b"));
+    assert_eq!(text, "DIFF-JSON v1\n\n[MODIFIED] $.generated_at: \"a\" -> \"b\"\n");
+}
+
+#[test]
+fn result_holds_its_invariants() {
+    let result = json_diff_core::compare_json(&render_order(1, "a"), &json!({"id": 1, "lines": [1]}), &response_options()).unwrap();
+    result.assert_invariants();
+    assert_eq!(result.classification(), json_diff_core::DiffClassification::Different);
+}
@@ -0,0 +1,37 @@
+//! Write the benchmark workloads as JSON fixture files
+//!
+//! ```text
+//! cargo run --example gen_fixture -- <size> [out_dir]
+//! ```
+//!
+//! Each workload is written as `<name>-left.json` and `<name>-right.json` (pretty-printed)
+//! plus `<name>-left.min.json` and `<name>-right.min.json` (minified). The output only
+//! depends on `size`.
+
+#[path = "../benches/workloads/mod.rs"]
+mod workloads;
+
+use std::path::PathBuf;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let size: usize = match args.next() {
+        Some(size) => size.parse()?,
+        None => return Err("usage: gen_fixture <size> [out_dir]".into()),
+    };
+    let out_dir = PathBuf::from(args.next().unwrap_or_else(|| "bench-fixtures".to_string()));
+    std::fs::create_dir_all(&out_dir)?;
+
+    for workload in workloads::all(size) {
+        for (side, value) in [("left", &workload.left), ("right", &workload.right)] {
+            let pretty = out_dir.join(format!("{}-{}.json", workload.name, side));
+            let minified = out_dir.join(format!("{}-{}.min.json", workload.name, side));
+            std::fs::write(&pretty, serde_json::to_string_pretty(value)?)?;
+            std::fs::write(&minified, serde_json::to_string(value)?)?;
+            println!("{}", pretty.display());
+            println!("{}", minified.display());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+//! Comparison options built in code rather than read from a profile
+//!
+//! ```text
+//! cargo run -p json-diff-core --example programmatic_options
+//! ```
+//!
+//! Options are a struct with a default for every field, so a comparison sets only the
+//! fields it needs; [`CompareOptions::extend_rules`] layers the rules of another set on
+//! top, as a profile over a preset does. Paths take `*` for any key or index.
+
+use json_diff_core::{compare_json, CompareOptions, DiffEntry, DiffType, JsonDiffError, JsonPath};
+use serde_json::json;
+
+fn main() -> Result<(), JsonDiffError> {
+    let left = json!({
+        "order": 1042,
+        "items": [
+            {"sku": "A-1", "price": 9.99, "updated_at": "2024-05-01T10:00:00Z"},
+            {"sku": "B-2", "price": 25.0, "updated_at": "2024-05-01T10:00:00Z"},
+        ],
+        "total": 34.99,
+    });
+    let right = json!({
+        "order": 1042,
+        "items": [
+            {"sku": "A-1", "price": 9.991, "updated_at": "2024-05-02T08:30:00Z"},
+            {"sku": "B-2", "price": 27.5, "updated_at": "2024-05-02T08:30:00Z"},
+        ],
+        "total": 37.491,
+    });
+
+    // Timestamps change on every export; prices may drift by a fraction of a cent
+    let mut options = CompareOptions {
+        ignore_paths: vec![JsonPath::new("$.items[*].updated_at")?],
+        tolerances: vec![(JsonPath::new("$.items[*].price")?, 0.005)],
+        report_tolerated: true,
+        ..CompareOptions::default()
+    };
+    // Rules kept elsewhere, e.g. shared by several comparisons
+    options.extend_rules(CompareOptions { tolerances: vec![(JsonPath::new("$.total")?, 0.01)], ..CompareOptions::default() });
+
+    let result = compare_json(&left, &right, &options)?;
+    for entry in result.iter() {
+        println!("{}", entry);
+    }
+
+    assert_eq!(result.count(DiffType::Ignored), 2);
+    assert_eq!(result.iter_type(DiffType::Tolerated).map(|entry| entry.path.as_str()).collect::<Vec<_>>(), ["$.items[0].price"]);
+    // Only the second price and the total moved more than their tolerance
+    let differences: Vec<&DiffEntry> = result.iter().filter(|entry| entry.diff_type.is_difference()).collect();
+    assert_eq!(differences, [
+        &DiffEntry::modified("$.items[1].price", json!(25.0), json!(27.5)),
+        &DiffEntry::modified("$.total", json!(34.99), json!(37.491)),
+    ]);
+    assert_eq!(
+        result.iter_type(DiffType::Tolerated).next(),
+        Some(&DiffEntry::tolerated("$.items[0].price", json!(9.99), json!(9.991), "tolerance 0.005")),
+    );
+    Ok(())
+}
+
+#[test]
+fn runs() {
+    main().unwrap();
+}
@@ -0,0 +1,54 @@
+//! Differences of two huge arrays handed over one at a time, stopping at the first few
+//!
+//! ```text
+//! cargo run -p json-diff-core --features streaming --example stream_entries
+//! ```
+//!
+//! [`compare_array_streams_until`] reads the arrays from any [`std::io::Read`], such as
+//! files or sockets, holding one element per side at a time, and calls back with each
+//! entry. Returning `ControlFlow::Break` stops it there, leaving the rest unread: a
+//! check that only needs to know whether the arrays differ stops at the first entry.
+
+use std::ops::ControlFlow;
+
+use json_diff_core::{compare_array_streams, compare_array_streams_until, CompareOptions, JsonDiffError, StreamOptions};
+
+/// A JSON array of `count` records, the ones at `changed` with another status
+fn records(count: usize, changed: &[usize]) -> String {
+    let records: Vec<String> = (0..count)
+        .map(|id| format!(r#"{{"id": {}, "status": "{}"}}"#, id, if changed.contains(&id) { "failed" } else { "ok" }))
+        .collect();
+    format!("[\n{}\n]\n", records.join(",\n"))
+}
+
+fn main() -> Result<(), JsonDiffError> {
+    let left = records(50_000, &[]);
+    let right = records(50_000, &[120, 7_000, 42_000]);
+    let (options, stream_options) = (CompareOptions::default(), StreamOptions::default());
+
+    // Stop after the first two differences
+    let mut first = Vec::new();
+    let summary = compare_array_streams_until(left.as_bytes(), right.as_bytes(), &options, &stream_options, |entry| {
+        println!("{}", entry);
+        first.push(entry);
+        if first.len() == 2 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+    })?;
+    assert!(summary.stopped);
+    assert_eq!(first.iter().map(|entry| entry.path.as_str()).collect::<Vec<_>>(), ["$[120].status", "$[7000].status"]);
+    // Each entry has the line its element starts on, after the `[` line
+    assert_eq!(first[0].left_line, Some(122));
+    assert_eq!(summary.left_elements, 7_001);
+    println!("stopped after {} of 50000 elements", summary.left_elements);
+
+    // Counting every difference reads both arrays to the end
+    let mut count = 0;
+    let summary = compare_array_streams(left.as_bytes(), right.as_bytes(), &options, &stream_options, |_| count += 1)?;
+    assert!(!summary.stopped);
+    assert_eq!((count, summary.entries, summary.right_elements), (3, 3, 50_000));
+    Ok(())
+}
+
+#[test]
+fn runs() {
+    main().unwrap();
+}
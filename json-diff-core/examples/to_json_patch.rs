@@ -0,0 +1,69 @@
+//! A JSON Patch and a JSON Merge Patch built from the differences of two documents and
+//! applied to the left one, giving the right one back
+//!
+//! ```text
+//! cargo run -p json-diff-core --example to_json_patch
+//! ```
+//!
+//! The JSON Patch (RFC 6902) says each change, and with the left document at hand
+//! replaces a mostly changed object whole. The merge patch (RFC 7386) is shorter but
+//! cannot say everything, such as setting a value to `null` or changing part of an array,
+//! and [`merge_patch::from_result`] warns about what it leaves out.
+
+use serde_json::json;
+use json_diff_core::{apply_json_patch, compare_json, merge_patch, CompareOptions, JsonDiffError, PatchOptions};
+
+fn main() -> Result<(), JsonDiffError> {
+    let left = json!({
+        "name": "api",
+        "image": "registry.example.com/api:1.4",
+        "labels": {"team": "core", "tier": "backend", "region": "eu-west"},
+        "replicas": 2,
+        "ports": [80, 443],
+        "limits": {"cpu": 1, "memory": "1Gi"},
+        "debug": true,
+    });
+    let right = json!({
+        "name": "api",
+        "image": "registry.example.com/api:1.4",
+        "labels": {"team": "core", "tier": "backend", "region": "eu-west"},
+        "replicas": 3,
+        "ports": [80, 443, 8080],
+        "limits": {"cpu": 2, "memory": "2Gi"},
+    });
+    let result = compare_json(&left, &right, &CompareOptions::default())?;
+
+    let patch = result.to_json_patch_optimized(PatchOptions { left: Some(&left), ..PatchOptions::default() });
+    println!("{}", serde_json::to_string_pretty(&patch).unwrap());
+    // Every leaf of the limits changed, so they are replaced in one operation
+    assert!(patch.as_array().unwrap().contains(&json!({"op": "replace", "path": "/limits", "value": {"cpu": 2, "memory": "2Gi"}})));
+    let mut patched = left.clone();
+    apply_json_patch(&mut patched, &patch)?;
+    assert_eq!(patched, right);
+
+    // A patch that does not fit the document is an error, leaving it unchanged
+    let mut other = json!({"name": "web"});
+    assert!(matches!(apply_json_patch(&mut other, &patch), Err(JsonDiffError::InvalidPatch(_))));
+    assert_eq!(other, json!({"name": "web"}));
+
+    // The merge patch removes `debug` with a `null` but cannot append to the ports: an
+    // array changed element by element is left out, with a warning saying so
+    let (merge, warnings) = merge_patch::from_result(&result);
+    println!("{}", serde_json::to_string_pretty(&merge).unwrap());
+    assert_eq!(merge["debug"], json!(null));
+    assert_eq!(warnings.len(), 1);
+    println!("warning: {}", warnings[0]);
+    let merged = merge_patch::apply(&left, &merge);
+    assert_eq!((&merged["limits"], &merged["ports"]), (&right["limits"], &left["ports"]));
+
+    // Compared without element changes, the ports are one entry with the whole new array
+    let options = CompareOptions { identify_array_item_changes: false, ..CompareOptions::default() };
+    let merge = compare_json(&left, &right, &options)?.to_merge_patch();
+    assert_eq!(merge_patch::apply(&left, &merge), right);
+    Ok(())
+}
+
+#[test]
+fn runs() {
+    main().unwrap();
+}
@@ -0,0 +1,162 @@
+//! Entries of the text formats laid out in columns, see [`ValueRenderOptions::align`]
+//!
+//! The type, the path, the lines and the values of each entry start at the same
+//! column on every line. Widths are measured in terminal columns, so keys in scripts
+//! taking two columns per character, such as CJK, line up with ASCII ones. A path wider
+//! than the limit is cut in the middle with `…`, keeping its last segment.
+
+use std::fmt;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::diff::{escape_control, DiffEntry, OutputStyle, ValueRenderOptions};
+use crate::path::parent_path;
+
+/// Widths of the columns of a set of entries
+pub(crate) struct Columns {
+    kind: usize,
+    path: usize,
+    lines: usize,
+    max_path: usize,
+}
+
+impl Columns {
+    /// Columns wide enough for each of `entries`, the path one at most `max_path` wide
+    pub(crate) fn measure<'a>(
+        entries: impl IntoIterator<Item = &'a DiffEntry>,
+        style: OutputStyle,
+        options: &ValueRenderOptions,
+        max_path: usize,
+    ) -> Self {
+        let mut columns = Self { kind: 0, path: 0, lines: 0, max_path };
+        for entry in entries {
+            let cells = columns.cells(entry, style, options);
+            columns.kind = columns.kind.max(cells[0].width());
+            columns.path = columns.path.max(cells[1].width());
+            columns.lines = columns.lines.max(cells[2].width());
+        }
+        columns.path = columns.path.min(max_path);
+        columns
+    }
+
+    /// Write `entry` with its cells padded to the columns, without a line break
+    pub(crate) fn write(&self, f: &mut impl fmt::Write, entry: &DiffEntry, style: OutputStyle, options: &ValueRenderOptions) -> fmt::Result {
+        let [kind, path, lines, values] = self.cells(entry, style, options);
+        let mut line = String::new();
+        for (cell, width) in [(kind, self.kind), (path, self.path), (lines, self.lines)] {
+            // A column no entry fills is left out
+            if width == 0 {
+                continue;
+            }
+            line.push_str(&cell);
+            line.push_str(&" ".repeat(width.saturating_sub(cell.width()) + 1));
+        }
+        line.push_str(&values);
+        f.write_str(line.trim_end())
+    }
+
+    /// The type, path, lines and values of `entry`, the path cut to the widest allowed
+    fn cells(&self, entry: &DiffEntry, style: OutputStyle, options: &ValueRenderOptions) -> [String; 4] {
+        let (kind, suffix, lines, values) = entry.text_cells(style, options);
+        let path = format!("{}{}", truncate_middle(&escape_control(&entry.path), self.max_path), suffix);
+        [kind, path, lines, values]
+    }
+}
+
+/// `path` cut in the middle with `…` to at most `width` columns, keeping its last
+/// segment, or as much of the end of it as fits
+pub(crate) fn truncate_middle(path: &str, width: usize) -> String {
+    if path.width() <= width {
+        return path.to_string();
+    }
+    let leaf = &path[parent_path(path).len()..];
+    let Some(budget) = width.checked_sub(leaf.width() + 1).filter(|budget| *budget > 0) else {
+        let mut tail = take_width(leaf.graphemes(true).rev(), width.saturating_sub(1));
+        tail.reverse();
+        return format!("…{}", tail.concat());
+    };
+    format!("{}…{}", take_width(path.graphemes(true), budget).concat(), leaf)
+}
+
+/// The grapheme clusters of `graphemes`, in order, that fit in `width` columns
+///
+/// Whole clusters are taken, so a base character keeps its combining marks.
+fn take_width<'a>(graphemes: impl Iterator<Item = &'a str>, width: usize) -> Vec<&'a str> {
+    let mut taken = Vec::new();
+    let mut used = 0;
+    for grapheme in graphemes {
+        if used + grapheme.width() > width {
+            break;
+        }
+        used += grapheme.width();
+        taken.push(grapheme);
+    }
+    taken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::DiffResult;
+    use serde_json::json;
+
+    fn aligned(entries: Vec<DiffEntry>, max_path: usize) -> Vec<String> {
+        let result = DiffResult { entries, ..DiffResult::builder().build().unwrap() };
+        let options = ValueRenderOptions { align: Some(max_path), ..ValueRenderOptions::default() };
+        let text = result.format_with_options(OutputStyle::Readable, &options);
+        text.lines().skip_while(|line| !line.starts_with('[')).map(str::to_string).collect()
+    }
+
+    /// Terminal column at which `needle` starts on `line`
+    fn column(line: &str, needle: &str) -> usize {
+        line[..line.find(needle).unwrap_or_else(|| panic!("{:?} not in {:?}", needle, line))].width()
+    }
+
+    #[test]
+    fn test_columns_line_up() {
+        let lines = aligned(
+            vec![
+                DiffEntry::removed("$.a", json!(1)).with_lines(Some(2), None),
+                DiffEntry::modified("$.config.database.host", json!("x"), json!("y")).with_lines(Some(10), Some(12)),
+                DiffEntry::added("$.用户.名字", json!(true)).with_lines(None, Some(3)),
+            ],
+            60,
+        );
+        assert_eq!(lines[0], "[REMOVED]  $.a                    (L2)      1");
+        assert_eq!(lines[1], "[MODIFIED] $.config.database.host (L10:L12) \"x\" -> \"y\"");
+        // The CJK keys take two columns a character
+        assert_eq!(lines[2], "[ADDED]    $.用户.名字            (L3)      true");
+        for (needle_a, needle_b) in [("$.a", "$.用户"), ("(L2)", "(L3)"), ("1", "true")] {
+            assert_eq!(column(&lines[0], needle_a), column(&lines[2], needle_b), "{:?}", lines);
+        }
+        assert_eq!(column(&lines[1], "\"x\""), column(&lines[2], "true"));
+    }
+
+    #[test]
+    fn test_long_paths_are_cut() {
+        let lines = aligned(
+            vec![
+                DiffEntry::modified("$.metrics.requests.by_region.eu_west.count", json!(1), json!(2)),
+                DiffEntry::ignored("$.id"),
+            ],
+            20,
+        );
+        assert_eq!(lines[0], "[MODIFIED] $.metrics.req….count 1 -> 2");
+        assert_eq!(lines[1], "[IGNORED]  $.id                 [IGNORED]");
+    }
+
+    #[test]
+    fn test_truncate_middle() {
+        assert_eq!(truncate_middle("$.a.b", 10), "$.a.b");
+        assert_eq!(truncate_middle("$.aaaa.bbbb.leaf", 10), "$.aa….leaf");
+        assert_eq!(truncate_middle("$.list[12]", 8), "$.l…[12]");
+        // A leaf too wide for the limit keeps its end
+        assert_eq!(truncate_middle("$.a.very_long_leaf", 6), "…_leaf");
+        // Wide characters are not split
+        assert_eq!(truncate_middle("$.数据数据数据.值", 9), "$.数….值");
+        // Nor are letters and their combining marks
+        assert_eq!(truncate_middle("$.cafe\u{301}cafe\u{301}.x", 9), "$.cafe\u{301}….x");
+        assert_eq!(truncate_middle("$.e\u{301}te\u{301}", 3), "…te\u{301}");
+    }
+}
@@ -0,0 +1,174 @@
+//! Triage notes attached to diff entries
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::diff::DiffEntry;
+use crate::error::JsonDiffError;
+
+/// Verdict of a reviewer on a difference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriageStatus {
+    /// The difference is intended
+    Expected,
+    /// The difference is a defect
+    Bug,
+}
+
+impl TriageStatus {
+    /// The status after `status` when cycling through them, `None` after the last
+    pub fn cycle(status: Option<TriageStatus>) -> Option<TriageStatus> {
+        match status {
+            None => Some(TriageStatus::Expected),
+            Some(TriageStatus::Expected) => Some(TriageStatus::Bug),
+            Some(TriageStatus::Bug) => None,
+        }
+    }
+}
+
+impl fmt::Display for TriageStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TriageStatus::Expected => write!(f, "expected"),
+            TriageStatus::Bug => write!(f, "bug"),
+        }
+    }
+}
+
+/// Status and note of one entry
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<TriageStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl Annotation {
+    /// Whether the annotation carries neither a status nor a note
+    pub fn is_empty(&self) -> bool {
+        self.status.is_none() && self.note.is_none()
+    }
+}
+
+/// Annotations keyed by entry fingerprint, so they survive re-running the comparison
+/// and reordering of the entries
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Annotations {
+    entries: BTreeMap<String, Annotation>,
+}
+
+impl Annotations {
+    /// Read annotations saved with [`Annotations::save`]; a missing file holds none
+    pub fn load(path: &Path) -> Result<Self, JsonDiffError> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write the annotations as JSON, leaving out empty ones
+    pub fn save(&self, path: &Path) -> Result<(), JsonDiffError> {
+        let mut saved = self.clone();
+        saved.entries.retain(|_, annotation| !annotation.is_empty());
+        std::fs::write(path, serde_json::to_string_pretty(&saved)?)?;
+        Ok(())
+    }
+
+    /// Add the annotations of `other`, which replace ours for the same entry
+    ///
+    /// Empty annotations in `other` clear ours, so an annotation removed in one copy
+    /// stays removed after merging it over an older copy.
+    pub fn merge(&mut self, other: Annotations) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn get(&self, entry: &DiffEntry) -> Option<&Annotation> {
+        self.entries.get(&entry.fingerprint()).filter(|annotation| !annotation.is_empty())
+    }
+
+    /// Move the entry to the next triage status
+    pub fn cycle_status(&mut self, entry: &DiffEntry) {
+        let annotation = self.entries.entry(entry.fingerprint()).or_default();
+        annotation.status = TriageStatus::cycle(annotation.status);
+    }
+
+    /// Set the note of an entry; a blank note removes it
+    pub fn set_note(&mut self, entry: &DiffEntry, note: &str) {
+        let note = note.trim();
+        self.entries.entry(entry.fingerprint()).or_default().note =
+            if note.is_empty() { None } else { Some(note.to_string()) };
+    }
+
+    /// Whether no entry is annotated
+    pub fn is_empty(&self) -> bool {
+        self.entries.values().all(Annotation::is_empty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(path: &str, line: usize) -> DiffEntry {
+        DiffEntry::modified(path, json!({"a": 1, "b": 2}), json!(3)).with_lines(Some(line), Some(line))
+    }
+
+    #[test]
+    fn test_annotations_follow_fingerprints() {
+        let mut annotations = Annotations::default();
+        annotations.cycle_status(&entry("$.x", 1));
+        annotations.set_note(&entry("$.y", 2), "  tracked in #12 ");
+
+        // The same changes found at other lines, e.g. after entries were reordered
+        let moved_x = DiffEntry { old_value: Some(json!({"b": 2, "a": 1})), ..entry("$.x", 40) };
+        assert_eq!(annotations.get(&moved_x).unwrap().status, Some(TriageStatus::Expected));
+        assert_eq!(annotations.get(&entry("$.y", 7)).unwrap().note.as_deref(), Some("tracked in #12"));
+        assert!(annotations.get(&entry("$.z", 1)).is_none());
+
+        annotations.cycle_status(&moved_x);
+        assert_eq!(annotations.get(&moved_x).unwrap().status, Some(TriageStatus::Bug));
+        annotations.cycle_status(&moved_x);
+        assert!(annotations.get(&moved_x).is_none());
+    }
+
+    #[test]
+    fn test_save_load_and_merge() {
+        let path = std::env::temp_dir().join(format!("json-diff-annotations-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert!(Annotations::load(&path).unwrap().is_empty());
+
+        let mut saved = Annotations::default();
+        saved.cycle_status(&entry("$.x", 1));
+        saved.set_note(&entry("$.y", 2), "flaky");
+        // Cleared again, so it is not written
+        saved.set_note(&entry("$.z", 3), "gone");
+        saved.set_note(&entry("$.z", 3), "");
+        saved.save(&path).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("\"expected\""));
+        assert!(!content.contains("gone"));
+        let mut loaded = Annotations::load(&path).unwrap();
+        assert_eq!(loaded.entries.len(), 2);
+
+        // A teammate marked $.x as a bug and removed the note on $.y
+        let mut theirs = Annotations::default();
+        theirs.cycle_status(&entry("$.x", 1));
+        theirs.cycle_status(&entry("$.x", 1));
+        theirs.set_note(&entry("$.y", 2), "");
+        theirs.set_note(&entry("$.w", 4), "new");
+        loaded.merge(theirs);
+
+        assert_eq!(loaded.get(&entry("$.x", 1)).unwrap().status, Some(TriageStatus::Bug));
+        assert!(loaded.get(&entry("$.y", 2)).is_none());
+        assert_eq!(loaded.get(&entry("$.w", 4)).unwrap().note.as_deref(), Some("new"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,113 @@
+//! Canonical JSON text: one way of writing a value, whatever the text it was read from
+//!
+//! Fingerprints of entries and options hash the compact form with sorted keys, and
+//! `json-diff fmt` writes files in the form its options choose, so files formatted by
+//! it serialize as they are fingerprinted.
+
+use serde_json::Value;
+
+/// How [`format_canonical`] writes a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanonicalOptions {
+    /// Whether object keys are sorted; otherwise they keep their order, which is only
+    /// the order of the input with the `key-order` feature, and sorted without it
+    pub sort_keys: bool,
+    /// Spaces per level of nesting, each member and element on its own line; `None`
+    /// writes the value on one line without spaces
+    pub indent: Option<usize>,
+}
+
+impl CanonicalOptions {
+    /// One line with sorted keys, the form fingerprints hash
+    pub const COMPACT: Self = Self { sort_keys: true, indent: None };
+}
+
+impl Default for CanonicalOptions {
+    fn default() -> Self {
+        Self { sort_keys: true, indent: Some(2) }
+    }
+}
+
+/// The canonical text of `value`, without a trailing newline
+pub fn format_canonical(value: &Value, options: &CanonicalOptions) -> String {
+    let mut out = String::new();
+    write_canonical(value, options, 0, &mut out);
+    out
+}
+
+fn write_canonical(value: &Value, options: &CanonicalOptions, depth: usize, out: &mut String) {
+    let newline = |out: &mut String, depth: usize| {
+        if let Some(indent) = options.indent {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent * depth));
+        }
+    };
+
+    match value {
+        Value::Object(obj) if !obj.is_empty() => {
+            let mut keys: Vec<&String> = obj.keys().collect();
+            if options.sort_keys {
+                keys.sort();
+            }
+
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, depth + 1);
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push_str(if options.indent.is_some() { ": " } else { ":" });
+                write_canonical(&obj[key], options, depth + 1, out);
+            }
+            newline(out, depth);
+            out.push('}');
+        }
+        Value::Array(arr) if !arr.is_empty() => {
+            out.push('[');
+            for (i, item) in arr.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, depth + 1);
+                write_canonical(item, options, depth + 1, out);
+            }
+            newline(out, depth);
+            out.push(']');
+        }
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_compact() {
+        let value = json!({"b": [1, {"d": null, "c": "x"}], "a": {}, "e": []});
+        assert_eq!(format_canonical(&value, &CanonicalOptions::COMPACT), r#"{"a":{},"b":[1,{"c":"x","d":null}],"e":[]}"#);
+    }
+
+    #[test]
+    fn test_indented() {
+        let value = json!({"b": [1, {"c": "x"}], "a": {}, "k\"ey": 1.5});
+        let expected = "{\n  \"a\": {},\n  \"b\": [\n    1,\n    {\n      \"c\": \"x\"\n    }\n  ],\n  \"k\\\"ey\": 1.5\n}";
+        assert_eq!(format_canonical(&value, &CanonicalOptions::default()), expected);
+
+        let four = format_canonical(&json!({"a": [true]}), &CanonicalOptions { indent: Some(4), ..CanonicalOptions::default() });
+        assert_eq!(four, "{\n    \"a\": [\n        true\n    ]\n}");
+        // Scalars are written alone
+        assert_eq!(format_canonical(&json!("text"), &CanonicalOptions::default()), "\"text\"");
+    }
+
+    #[cfg(feature = "key-order")]
+    #[test]
+    fn test_unsorted_keys_keep_their_order() {
+        let value: Value = serde_json::from_str(r#"{"b": 1, "a": {"d": 2, "c": 3}}"#).unwrap();
+        let options = CanonicalOptions { sort_keys: false, indent: None };
+        assert_eq!(format_canonical(&value, &options), r#"{"b":1,"a":{"d":2,"c":3}}"#);
+        assert_eq!(format_canonical(&value, &CanonicalOptions::COMPACT), r#"{"a":{"c":3,"d":2},"b":1}"#);
+    }
+}
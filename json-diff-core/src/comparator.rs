@@ -0,0 +1,179 @@
+//! Pluggable value comparators for domain-specific equality rules
+//!
+//! A comparator is registered against a [`JsonPath`](crate::JsonPath) in
+//! [`CompareOptions::custom_comparators`](crate::CompareOptions) and is consulted
+//! before the built-in comparison logic for every node whose path matches,
+//! whether the node is a scalar or a whole object/array subtree.
+//!
+//! # Example
+//!
+//! Treat two geo-coordinates as equal when they are within 10 meters of each other:
+//!
+//! ```
+//! use std::sync::Arc;
+//! use serde_json::{json, Value};
+//! use json_diff_core::{compare_json, CompareOptions, ComparatorVerdict, JsonPath, ValueComparator};
+//!
+//! #[derive(Debug)]
+//! struct GeoComparator {
+//!     max_distance_meters: f64,
+//! }
+//!
+//! impl ValueComparator for GeoComparator {
+//!     fn compare(&self, _path: &str, left: &Value, right: &Value) -> ComparatorVerdict {
+//!         let coords = |v: &Value| Some((v.get("lat")?.as_f64()?, v.get("lon")?.as_f64()?));
+//!         let (Some((lat1, lon1)), Some((lat2, lon2))) = (coords(left), coords(right)) else {
+//!             return ComparatorVerdict::Different;
+//!         };
+//!
+//!         // Equirectangular approximation, good enough for short distances
+//!         let x = (lon2 - lon1).to_radians() * ((lat1 + lat2) / 2.0).to_radians().cos();
+//!         let y = (lat2 - lat1).to_radians();
+//!         let distance = (x * x + y * y).sqrt() * 6_371_000.0;
+//!
+//!         if distance <= self.max_distance_meters {
+//!             ComparatorVerdict::Equal
+//!         } else {
+//!             ComparatorVerdict::Different
+//!         }
+//!     }
+//! }
+//!
+//! let mut options = CompareOptions::default();
+//! options.custom_comparators.push((
+//!     JsonPath::new("$.location").unwrap(),
+//!     Arc::new(GeoComparator { max_distance_meters: 10.0 }),
+//! ));
+//!
+//! let left = json!({"location": {"lat": 52.520008, "lon": 13.404954}});
+//! let near = json!({"location": {"lat": 52.520010, "lon": 13.404960}});
+//! let far = json!({"location": {"lat": 52.530000, "lon": 13.404954}});
+//!
+//! assert!(compare_json(&left, &near, &options).unwrap().entries.is_empty());
+//! assert_eq!(compare_json(&left, &far, &options).unwrap().entries.len(), 1);
+//! ```
+
+use std::fmt;
+use serde_json::Value;
+
+use crate::diff::DiffEntry;
+
+/// Outcome of a custom comparison
+#[derive(Debug, Clone, PartialEq)]
+pub enum ComparatorVerdict {
+    /// The values are considered equal, no entry is produced
+    Equal,
+    /// The values differ, a default Modified entry is produced for the node
+    Different,
+    /// The values differ, the given entry is produced instead of the default one
+    Replace(Box<DiffEntry>),
+}
+
+/// A user-supplied comparison rule for values at matching paths
+pub trait ValueComparator: fmt::Debug + Send + Sync {
+    /// Compare the left and right values found at `path`
+    fn compare(&self, path: &str, left: &Value, right: &Value) -> ComparatorVerdict;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use serde_json::json;
+    use crate::{compare_json, CompareOptions, DiffType, JsonDiffError, JsonPath};
+
+    #[derive(Debug)]
+    struct AlwaysEqual;
+
+    impl ValueComparator for AlwaysEqual {
+        fn compare(&self, _path: &str, _left: &Value, _right: &Value) -> ComparatorVerdict {
+            ComparatorVerdict::Equal
+        }
+    }
+
+    #[derive(Debug)]
+    struct CustomEntry;
+
+    impl ValueComparator for CustomEntry {
+        fn compare(&self, path: &str, left: &Value, right: &Value) -> ComparatorVerdict {
+            let entry = DiffEntry::modified(format!("{}.custom", path), left.clone(), right.clone());
+            ComparatorVerdict::Replace(Box::new(DiffEntry { diff_type: DiffType::ArrayItemChanged, ..entry }))
+        }
+    }
+
+    #[test]
+    fn test_comparator_forces_equality() {
+        let left = json!({"name": "John", "tags": ["a", "b"]});
+        let right = json!({"name": "Jane", "tags": ["c"]});
+
+        let mut options = CompareOptions::default();
+        options.custom_comparators.push((JsonPath::new("$.name").unwrap(), Arc::new(AlwaysEqual)));
+        options.custom_comparators.push((JsonPath::new("$.tags").unwrap(), Arc::new(AlwaysEqual)));
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert!(result.entries.is_empty());
+    }
+
+    #[test]
+    fn test_comparator_emits_custom_entry() {
+        let left = json!({"user": {"name": "John"}, "age": 30});
+        let right = json!({"user": {"name": "John"}, "age": 31});
+
+        let mut options = CompareOptions::default();
+        options.custom_comparators.push((JsonPath::new("$.user").unwrap(), Arc::new(CustomEntry)));
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.entries.len(), 2);
+
+        let custom = result.entries.iter().find(|e| e.path == "$.user.custom").unwrap();
+        assert_eq!(custom.diff_type, DiffType::ArrayItemChanged);
+        assert_eq!(custom.old_value, Some(json!({"name": "John"})));
+
+        // Paths without a comparator still use the built-in logic
+        assert!(result.entries.iter().any(|e| e.path == "$.age" && e.diff_type == DiffType::Modified));
+    }
+
+    #[test]
+    fn test_comparator_repeating_an_entry_fails() {
+        #[derive(Debug)]
+        struct Misplaced;
+
+        impl ValueComparator for Misplaced {
+            fn compare(&self, _path: &str, left: &Value, right: &Value) -> ComparatorVerdict {
+                ComparatorVerdict::Replace(Box::new(DiffEntry::modified("$.b", left.clone(), right.clone())))
+            }
+        }
+
+        let left = json!({"a": 1, "b": 1});
+        let right = json!({"a": 2, "b": 2});
+        let mut options = CompareOptions { strict_invariants: true, ..CompareOptions::default() };
+        options.custom_comparators.push((JsonPath::new("$.a").unwrap(), Arc::new(Misplaced)));
+
+        let error = compare_json(&left, &right, &options).unwrap_err();
+        assert!(matches!(error, JsonDiffError::DuplicateEntry { ref path, diff_type: "MODIFIED" } if path == "$.b"), "{}", error);
+    }
+
+    #[test]
+    fn test_comparator_different_uses_default_entry() {
+        #[derive(Debug)]
+        struct AlwaysDifferent;
+
+        impl ValueComparator for AlwaysDifferent {
+            fn compare(&self, _path: &str, _left: &Value, _right: &Value) -> ComparatorVerdict {
+                ComparatorVerdict::Different
+            }
+        }
+
+        let left = json!({"point": {"x": 1, "y": 2}});
+        let right = json!({"point": {"x": 1, "y": 2}});
+
+        let mut options = CompareOptions::default();
+        options.custom_comparators.push((JsonPath::new("$.point").unwrap(), Arc::new(AlwaysDifferent)));
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].diff_type, DiffType::Modified);
+        assert_eq!(result.entries[0].path, "$.point");
+        assert_eq!(result.entries[0].new_value, Some(json!({"x": 1, "y": 2})));
+    }
+}
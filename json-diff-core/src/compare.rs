@@ -1,64 +1,820 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::path::Path;
-use std::fs::File;
-use std::io::Read;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+#[cfg(feature = "timestamps")]
 use chrono::Utc;
+use serde::{Serialize, Deserialize};
 use serde_json::{Value, Map};
 
-use crate::diff::{DiffEntry, DiffType, DiffResult};
-use crate::path::JsonPath;
+use crate::annotation::Annotations;
+use crate::checksum::sha256_hex;
+use crate::comparator::{ComparatorVerdict, ValueComparator};
+use crate::decode::{hex_preview, Codec};
+use crate::canonical::{format_canonical, CanonicalOptions};
+use crate::diff::{ArraySummary, DiffEntry, DiffType, DiffResult, Timestamp, Timings, GENERATOR};
+use crate::path::{index_segments, keyed_segment, parent_path, rebase_path, value_paths, Filter, JsonPath};
 use crate::error::JsonDiffError;
+use crate::input::{check_input_file, read_file_limited};
+use crate::lenient::substitute_non_finite;
+use crate::sniff::explain_parse_error;
+use crate::textdiff;
+use crate::line_map::LineMap;
+use crate::rule_set::{RuleSet, RuleUsage};
+use crate::transform::{apply_transforms, Transform};
+
+/// Path suffix marking the root of a JSON document embedded in a string value
+const EMBEDDED_JSON_MARKER: &str = "(json)";
+
+/// Maximum nesting of embedded JSON documents that will be parsed
+const MAX_EMBEDDED_JSON_DEPTH: usize = 8;
+
+/// Field the elements of unordered arrays are matched by, when they are objects holding it
+const ELEMENT_KEY: &str = "id";
 
 /// Options for JSON comparison
-#[derive(Debug, Clone)]
+///
+/// Options serialize with paths as their text (regex paths as their compiled pattern)
+/// and deserialize with every field optional; custom comparators are left out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CompareOptions {
     /// Paths to ignore during comparison
     pub ignore_paths: Vec<JsonPath>,
+    /// Paths the comparison is limited to: with any, only entries at a path one of them
+    /// matches, or beneath one, are reported, see [`CompareOptions::is_selected`]; ignore
+    /// rules still apply inside them
+    pub select_paths: Vec<JsonPath>,
     /// Paths to arrays that should be compared without considering order
     pub unordered_arrays: Vec<JsonPath>,
+    /// Paths to arrays of primitives compared as sets: order and duplicates do not
+    /// matter, and each value only on one side is reported as added or removed
+    pub set_arrays: Vec<JsonPath>,
     /// Whether to show nested differences in unordered arrays
     pub show_nested_differences: bool,
     /// Whether to identify specific different items in arrays rather than marking whole arrays as different
     pub identify_array_item_changes: bool,
+    /// Whether elements of ordered arrays found unchanged at another index are reported as moved
+    pub detect_array_moves: bool,
+    /// Whether a changed value whose path ends in an array index, like `$.items[1]`, is
+    /// reported as `ArrayItemChanged` rather than `Modified`
+    pub tag_array_item_changes: bool,
+    /// With `tag_array_item_changes`, whether values anywhere beneath an array element,
+    /// like `$.items[1].value`, are tagged as well
+    pub tag_nested_array_item_changes: bool,
+    /// Whether values that only compare equal thanks to a tolerance or normalization
+    /// (numeric tolerances, numeric strings, `numeric_equivalence`, decoders) are reported
+    /// as `Tolerated` entries naming the rule, rather than left out
+    pub report_tolerated: bool,
+    /// Whether to time how the ignore rules are matched, reported in `DiffResult::rule_stats`;
+    /// every rule is then tried on every path, which slows the comparison
+    pub explain_rules: bool,
+    /// Largest JSON text, in bytes, of the values of an array compared as a whole;
+    /// larger ones are left out of the entry and described by their length
+    pub max_value_size: Option<usize>,
+    /// Custom comparators consulted before the built-in logic for matching paths
+    #[serde(skip)]
+    pub custom_comparators: Vec<(JsonPath, Arc<dyn ValueComparator>)>,
+    /// Transforms applied to the documents before comparison
+    pub preprocess: Vec<Transform>,
+    /// Absolute tolerances within which numbers at matching paths are considered equal
+    pub tolerances: Vec<(JsonPath, f64)>,
+    /// Paths of string values holding JSON documents that should be parsed and compared structurally
+    pub parse_embedded_json: Vec<JsonPath>,
+    /// Whether to parse any string that looks like a JSON object or array
+    pub auto_parse_embedded_json: bool,
+    /// Encodings to decode string values at matching paths with before comparison
+    pub decoders: Vec<(JsonPath, Codec)>,
+    /// Paths whose string values should be compared as numbers
+    pub numeric_strings: Vec<JsonPath>,
+    /// Whether to compare numeric-looking strings as numbers at every path
+    pub all_numeric_strings: bool,
+    /// Separator conventions used when parsing numeric strings
+    pub number_locale: NumberLocale,
+    /// Whether numbers are compared by value, so `1` equals `1.0` and `-0` equals `0`
+    pub numeric_equivalence: bool,
+    /// Whether documents given as text may hold the `NaN`, `Infinity` and `-Infinity` tokens
+    pub lenient: bool,
+    /// Whether an object with the same keys in another order gets a `KeyOrderChanged`
+    /// entry; needs the `key-order` feature, without which parsing forgets key order
+    pub report_key_order: bool,
+    /// Subtree of the left document to compare instead of the whole; entries' paths are
+    /// relative to it, their lines still those of the whole document
+    pub left_root: Option<JsonPath>,
+    /// Subtree of the right document to compare instead of the whole
+    pub right_root: Option<JsonPath>,
+    /// Timestamp recorded on the result instead of the current time
+    pub timestamp: Option<Timestamp>,
+    /// Largest input, in bytes, that `compare_files` will read
+    pub max_input_bytes: Option<u64>,
+    /// Most values the comparison may visit, counting each document's separately;
+    /// past it the comparison fails with `JsonDiffError::LimitExceeded`
+    pub max_nodes: Option<u64>,
+    /// Most entries the comparison may report before it fails with
+    /// `JsonDiffError::LimitExceeded`, rather than stopping short
+    pub max_entries: Option<usize>,
+    /// Longest the comparison may run before it fails with `JsonDiffError::LimitExceeded`;
+    /// checked every few hundred values, so it may overrun a little
+    pub max_duration: Option<Duration>,
+    /// Value changes at matching paths that are expected and reported as ignored
+    pub allowed_transitions: Vec<AllowedTransition>,
+    /// Whether the result is brought to canonical form, see [`DiffResult::normalize`]
+    pub normalize_entries: bool,
+    /// Whether entries compared from text record the region of their key and value in
+    /// each file, see [`DiffResult::locate`]
+    pub record_spans: bool,
+    /// Whether the result records how long each phase of the comparison took, see
+    /// [`DiffResult::timings`]
+    pub collect_timings: bool,
+    /// Values compared across differently named paths, such as a field renamed between
+    /// two versions of an API; see [`PathMapping`]
+    pub path_mappings: Vec<PathMapping>,
+    /// How entries name the elements of arrays matched by key
+    pub path_index_style: IndexStyle,
+    /// Whether modifications between two numbers record how much the value changed,
+    /// see [`DiffEntry::delta`]
+    pub numeric_deltas: bool,
+    /// Similarity from 0 to 1 at or above which a modification between two strings at the
+    /// given paths, or at any path if there are none, is a near match, such as a typo;
+    /// see [`DiffEntry::near_match`]. Similarity is one minus the edit distance over the
+    /// length of the longer string
+    pub string_similarity: Option<(f64, Vec<JsonPath>)>,
+    /// What [`compare_files`] compares a file that does not exist as, if not an error
+    pub missing_file_policy: MissingFilePolicy,
+    /// Whether a comparison reporting a second entry of the same type at a path fails
+    /// with [`JsonDiffError::DuplicateEntry`], rather than leaving the repeat out; such a
+    /// repeat is a bug, so debug builds always fail on it
+    pub strict_invariants: bool,
+    /// Whether documents given as text that are not both valid JSON are compared line by
+    /// line, with a warning, rather than failing with [`JsonDiffError::Unparseable`]; the
+    /// entries are at paths like `<line 42>`
+    pub text_fallback: bool,
+}
+
+/// What [`compare_files`] does when one of the two files does not exist
+///
+/// A substituted side is marked missing on the result, and its entries have no lines,
+/// checksum or size on that side. Both files missing is still an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingFilePolicy {
+    /// Fail with `JsonDiffError::FileNotFound`
+    #[default]
+    Error,
+    /// Compare it as `{}`, so every top-level key of the other file is added or removed
+    TreatAsEmptyObject,
+    /// Compare it as `null`, so the other document as a whole is one modification
+    TreatAsNull,
+}
+
+impl MissingFilePolicy {
+    /// The JSON text a missing file is compared as, if it is not an error
+    fn substitute(self) -> Option<&'static str> {
+        match self {
+            MissingFilePolicy::Error => None,
+            MissingFilePolicy::TreatAsEmptyObject => Some("{}"),
+            MissingFilePolicy::TreatAsNull => Some("null"),
+        }
+    }
+}
+
+/// A value at `left` in the left document compared against the one at `right` in the
+/// right document, instead of each being reported as removed and added
+///
+/// Entries are reported at the left path and name the right one in `mapped_to`. Both
+/// paths end in a key and may hold `[*]`, which pair elements index for index; a value
+/// on one side only is reported as removed or added at the left path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathMapping {
+    pub left: JsonPath,
+    pub right: JsonPath,
+}
+
+impl PathMapping {
+    /// Check that both paths can name the ends of a mapping
+    pub fn validate(&self) -> Result<(), JsonDiffError> {
+        let invalid = |reason: &str| JsonDiffError::InvalidMapping {
+            left: self.left.to_string(),
+            right: self.right.to_string(),
+            reason: reason.to_string(),
+        };
+        if let Some(problem) = self.left.mapping_problem().or_else(|| self.right.mapping_problem()) {
+            return Err(invalid(problem));
+        }
+        if self.left.wildcard_count() != self.right.wildcard_count() {
+            return Err(invalid("both paths must have the same number of [*]"));
+        }
+        Ok(())
+    }
+}
+
+/// An expected change of an enum-like value, e.g. `"PENDING"` to `"PROCESSING"`
+///
+/// Values are compared as strings: string values by their content, other values
+/// by their JSON text. `"*"` in `from` or `to` matches any value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllowedTransition {
+    pub path: JsonPath,
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+}
+
+impl AllowedTransition {
+    /// Whether changing `old` into `new` at `path` is covered by this rule
+    pub fn allows(&self, path: &str, old: &Value, new: &Value) -> bool {
+        let in_set = |set: &[String], value: &Value| {
+            let text = match value {
+                Value::String(s) => Cow::Borrowed(s.as_str()),
+                other => Cow::Owned(other.to_string()),
+            };
+            set.iter().any(|allowed| allowed == "*" || *allowed == text)
+        };
+
+        self.path.matches(path) && in_set(&self.from, old) && in_set(&self.to, new)
+    }
+}
+
+impl std::fmt::Display for AllowedTransition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "allowed transition {:?} -> {:?}", self.from, self.to)
+    }
+}
+
+/// How the paths of entries name the elements of arrays matched by key: unordered arrays,
+/// whose elements are matched by `id`, and streams matched by a stream key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexStyle {
+    /// `$.products[2].price`, by the index of the element in the left document, or in
+    /// the right one for an added element
+    #[default]
+    Numeric,
+    /// `$.products[id=P1].price`, by the key of the element, which does not change when
+    /// elements are reordered; `DiffEntry::index_path` keeps the numeric path. Elements
+    /// without a string or number key, or sharing it with another, keep their index
+    Keyed,
+}
+
+/// Decimal and thousands separator conventions for numeric strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberLocale {
+    /// `1,234.5` — decimal point, comma thousands separators
+    #[default]
+    Point,
+    /// `1.234,5` — decimal comma, point thousands separators
+    Comma,
+}
+
+impl NumberLocale {
+    /// Parse a numeric string, stripping thousands separators and normalizing the decimal mark
+    pub fn parse(&self, input: &str) -> Option<f64> {
+        let (thousands, decimal) = match self {
+            NumberLocale::Point => (',', '.'),
+            NumberLocale::Comma => ('.', ','),
+        };
+
+        let normalized: String = input.trim()
+            .chars()
+            .filter(|c| *c != thousands && *c != '_' && *c != '\'' && !c.is_whitespace())
+            .map(|c| if c == decimal { '.' } else { c })
+            .collect();
+
+        // Reject what f64 parsing would otherwise accept, like "inf" or "NaN"
+        let is_plain_number = !normalized.is_empty()
+            && normalized.chars().any(|c| c.is_ascii_digit())
+            && normalized.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E'));
+        if !is_plain_number {
+            return None;
+        }
+
+        normalized.parse().ok()
+    }
 }
 
 impl Default for CompareOptions {
     fn default() -> Self {
         Self {
             ignore_paths: Vec::new(),
+            select_paths: Vec::new(),
             unordered_arrays: Vec::new(),
+            set_arrays: Vec::new(),
             show_nested_differences: false,
             identify_array_item_changes: true,
+            detect_array_moves: false,
+            tag_array_item_changes: false,
+            tag_nested_array_item_changes: false,
+            report_tolerated: false,
+            explain_rules: false,
+            max_value_size: None,
+            custom_comparators: Vec::new(),
+            preprocess: Vec::new(),
+            tolerances: Vec::new(),
+            parse_embedded_json: Vec::new(),
+            auto_parse_embedded_json: false,
+            decoders: Vec::new(),
+            numeric_strings: Vec::new(),
+            all_numeric_strings: false,
+            number_locale: NumberLocale::Point,
+            numeric_equivalence: false,
+            lenient: false,
+            report_key_order: false,
+            left_root: None,
+            right_root: None,
+            timestamp: None,
+            max_input_bytes: None,
+            max_nodes: None,
+            max_entries: None,
+            max_duration: None,
+            allowed_transitions: Vec::new(),
+            normalize_entries: false,
+            record_spans: false,
+            collect_timings: false,
+            path_mappings: Vec::new(),
+            path_index_style: IndexStyle::Numeric,
+            numeric_deltas: false,
+            string_similarity: None,
+            missing_file_policy: MissingFilePolicy::Error,
+            strict_invariants: false,
+            text_fallback: false,
+        }
+    }
+}
+
+impl CompareOptions {
+    /// Append the rules of another set of options (e.g. one generated from a schema)
+    /// to this one, keeping this one's flags
+    pub fn extend_rules(&mut self, overlay: CompareOptions) {
+        self.ignore_paths.extend(overlay.ignore_paths);
+        self.select_paths.extend(overlay.select_paths);
+        self.unordered_arrays.extend(overlay.unordered_arrays);
+        self.set_arrays.extend(overlay.set_arrays);
+        self.custom_comparators.extend(overlay.custom_comparators);
+        self.preprocess.extend(overlay.preprocess);
+        self.tolerances.extend(overlay.tolerances);
+        self.parse_embedded_json.extend(overlay.parse_embedded_json);
+        self.decoders.extend(overlay.decoders);
+        self.numeric_strings.extend(overlay.numeric_strings);
+        self.allowed_transitions.extend(overlay.allowed_transitions);
+        self.path_mappings.extend(overlay.path_mappings);
+    }
+
+    /// Whether an entry at `path` is reported under `select_paths`: always without any,
+    /// else when one of them matches the path or one of its ancestors
+    pub fn is_selected(&self, path: &str) -> bool {
+        if self.select_paths.is_empty() {
+            return true;
+        }
+        let mut path = path;
+        while !path.is_empty() {
+            if self.select_paths.iter().any(|selection| selection.matches(path)) {
+                return true;
+            }
+            path = parent_path(path);
+        }
+        false
+    }
+
+    /// How the ignore and unordered rules apply to two documents given as text, as they
+    /// were compared into `result`: the entries each rule covers, and the paths of either
+    /// document it matches
+    ///
+    /// A rule covering no entry may just have matched paths whose values are equal; one
+    /// matching no path at all names something neither document has, as a rule written
+    /// for an older schema does. Paths are taken beneath `left_root` and `right_root`,
+    /// and a side the result marks missing is the document that stood in for it.
+    pub fn rule_usage(&self, left_content: &str, right_content: &str, result: &DiffResult) -> Result<Vec<RuleUsage>, JsonDiffError> {
+        let parse = |content: &str, missing: bool| {
+            let content = match (missing, self.missing_file_policy.substitute()) {
+                (true, Some(substitute)) => substitute,
+                _ => content,
+            };
+            let text = if self.lenient { substitute_non_finite(content) } else { Cow::Borrowed(content) };
+            serde_json::from_str::<Value>(&text).map_err(|e| explain_parse_error(content, &e))
+        };
+        let (left, right) = match (parse(left_content, result.left_missing), parse(right_content, result.right_missing)) {
+            (Ok(left), Ok(right)) => (left, right),
+            (left, right) => return Err(JsonDiffError::Unparseable { left: left.err(), right: right.err() }),
+        };
+        let (left, _) = select_root(&left, self.left_root.as_ref(), "left")?;
+        let (right, _) = select_root(&right, self.right_root.as_ref(), "right")?;
+        let paths = [(left, value_paths(left)), (right, value_paths(right))];
+
+        let rules = self.ignore_paths.iter().map(|rule| ("ignore", rule))
+            .chain(self.unordered_arrays.iter().map(|rule| ("unordered", rule)));
+        Ok(rules.map(|(kind, rule)| {
+            // An entry is covered by a rule matching its path or one of its ancestors
+            let covers = |mut path: &str| {
+                while !path.is_empty() {
+                    if paths.iter().any(|(document, _)| rule.matches_in(path, document)) {
+                        return true;
+                    }
+                    path = parent_path(path);
+                }
+                false
+            };
+            RuleUsage {
+                kind: kind.to_string(),
+                rule: rule.to_string(),
+                entries: result.iter().filter(|entry| covers(&entry.path)).count(),
+                paths: paths.iter()
+                    .map(|(document, paths)| paths.iter().filter(|path| rule.matches_in(path, document)).count())
+                    .sum(),
+            }
+        }).collect())
+    }
+
+    /// The options as JSON with object keys sorted, the same for equal options however
+    /// they were built or ordered when read; custom comparators are left out
+    pub fn canonical_json(&self) -> String {
+        // Every option has a JSON form, so serializing cannot fail
+        format_canonical(&serde_json::to_value(self).unwrap_or_default(), &CanonicalOptions::COMPACT)
+    }
+
+    /// Hash of every option that affects the entries of a comparison
+    ///
+    /// It hashes the canonical JSON, leaving out the options that only affect metadata,
+    /// checks or whether the comparison finishes (`timestamp`, `explain_rules`,
+    /// `collect_timings`, `strict_invariants` and the limits `max_input_bytes`,
+    /// `max_nodes`, `max_entries`, `max_duration`). Custom comparators, which have no
+    /// JSON form, are identified by their path and `Debug` output.
+    pub fn fingerprint(&self) -> String {
+        let relevant = CompareOptions {
+            timestamp: None,
+            max_input_bytes: None,
+            max_nodes: None,
+            max_entries: None,
+            max_duration: None,
+            explain_rules: false,
+            collect_timings: false,
+            strict_invariants: false,
+            custom_comparators: Vec::new(),
+            ..self.clone()
+        };
+        let mut input = relevant.canonical_json();
+        for (path, comparator) in &self.custom_comparators {
+            input.push('\0');
+            input.push_str(&format!("{} {:?}", path, comparator));
+        }
+        sha256_hex(input.as_bytes())
+    }
+}
+
+/// Inputs and accumulated output of a single comparison run
+struct CompareContext<'a> {
+    options: &'a CompareOptions,
+    left_line_map: &'a HashMap<String, usize>,
+    right_line_map: &'a HashMap<String, usize>,
+    /// Positions of both documents, when compared from their text
+    positions: Option<(&'a LineMap, &'a LineMap)>,
+    ignore: RuleSet,
+    /// Filter segments of the ignore rules, such as `[?id="u-42"]`
+    filters: Vec<&'a Filter>,
+    /// Paths of the array elements being compared, innermost last, with the filters each passes
+    elements: Vec<(String, Vec<&'a Filter>)>,
+    entries: Vec<DiffEntry>,
+    warnings: Vec<String>,
+    /// Insertion lines of the children of each parent, in the left document (`true`)
+    /// or the right one, filled as entries are anchored
+    anchors: RefCell<HashMap<(bool, String), InsertionLines>>,
+    budget: Budget,
+    /// Path and type of each entry kept by [`CompareContext::check_emitted`]
+    emitted: HashSet<(String, DiffType)>,
+    /// Paths reported ignored so far, see [`CompareContext::push_ignored`]
+    ignored: HashSet<String>,
+    /// Number of entries checked
+    checked: usize,
+}
+
+/// The work a comparison has done, against the limits of its options
+struct Budget {
+    max_nodes: Option<u64>,
+    max_entries: Option<usize>,
+    max_duration: Option<Duration>,
+    start: Instant,
+    nodes: u64,
+    /// Checks since the clock was last read
+    unclocked: u32,
+}
+
+/// Checks between two readings of the clock for `max_duration`
+const CHECKS_PER_CLOCK: u32 = 256;
+
+impl Budget {
+    fn new(options: &CompareOptions) -> Self {
+        Self {
+            max_nodes: options.max_nodes,
+            max_entries: options.max_entries,
+            max_duration: options.max_duration,
+            start: Instant::now(),
+            nodes: 0,
+            unclocked: 0,
+        }
+    }
+
+    /// Count `nodes` more visited values, with `entries` reported so far, failing once
+    /// a limit is exceeded
+    fn spend(&mut self, nodes: u64, entries: usize) -> Result<(), JsonDiffError> {
+        self.nodes = self.nodes.saturating_add(nodes);
+        if let Some(max) = self.max_nodes.filter(|&max| self.nodes > max) {
+            return Err(JsonDiffError::LimitExceeded { limit: "max_nodes", value: max });
         }
+        if let Some(max) = self.max_entries.filter(|&max| entries > max) {
+            return Err(JsonDiffError::LimitExceeded { limit: "max_entries", value: max as u64 });
+        }
+        self.unclocked += 1;
+        if self.unclocked >= CHECKS_PER_CLOCK {
+            self.unclocked = 0;
+            if let Some(max) = self.max_duration.filter(|&max| self.start.elapsed() > max) {
+                return Err(JsonDiffError::LimitExceeded {
+                    limit: "max_duration",
+                    value: u64::try_from(max.as_millis()).unwrap_or(u64::MAX),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Insertion lines of the children of one parent, see [`LineMap::insertion_lines`]
+type InsertionLines = Vec<Option<usize>>;
+
+impl CompareContext<'_> {
+    /// Record that `old` became `new` at `path`, unless an allowed transition covers it
+    fn push_modified(&mut self, path: &str, old: Value, new: Value) {
+        let rule = self.options.allowed_transitions.iter().find(|rule| rule.allows(path, &old, &new));
+        let diff_type = match rule {
+            Some(_) => DiffType::Ignored,
+            None if self.is_array_item(path) => DiffType::ArrayItemChanged,
+            None => DiffType::Modified,
+        };
+
+        let entry = DiffEntry { diff_type, reason: rule.map(|rule| rule.to_string()), ..DiffEntry::modified(path, old, new) }
+            .with_lines(find_line_for_path(path, self.left_line_map), find_line_for_path(path, self.right_line_map));
+        match entry.diff_type {
+            DiffType::Ignored => self.push_ignored(entry),
+            _ => self.entries.push(entry),
+        }
+    }
+
+    /// Record an ignored entry, unless its path was reported ignored already: rules can
+    /// reach the same ignored value more than once, e.g. two mappings from one left path.
+    /// Every ignored entry is pushed here.
+    fn push_ignored(&mut self, entry: DiffEntry) {
+        if self.ignored.insert(entry.path.clone()) {
+            self.entries.push(entry);
+        }
+    }
+
+    /// An ignored entry at `path`, on its lines in both documents
+    fn ignored_entry(&self, path: &str) -> DiffEntry {
+        DiffEntry::ignored(path).with_lines(find_line_for_path(path, self.left_line_map), find_line_for_path(path, self.right_line_map))
+    }
+
+    /// Check the entries reported since the last check: one outside the selected paths
+    /// is left out, and so is a repeat of an earlier entry's path and type, which fails
+    /// the comparison instead with `strict_invariants` and in debug builds
+    fn check_emitted(&mut self) -> Result<(), JsonDiffError> {
+        let strict = self.options.strict_invariants || cfg!(debug_assertions);
+        let mut index = self.checked;
+        while let Some(entry) = self.entries.get(index) {
+            if !self.options.is_selected(&entry.path) {
+                self.entries.remove(index);
+            } else if self.emitted.insert((entry.path.clone(), entry.diff_type.clone())) {
+                index += 1;
+            } else if strict {
+                return Err(JsonDiffError::DuplicateEntry { path: entry.path.clone(), diff_type: entry.diff_type.readable_text() });
+            } else {
+                self.entries.remove(index);
+            }
+        }
+        self.checked = index;
+        Ok(())
+    }
+
+    /// Whether an ignore rule matches `path`, resolving filters against the elements being compared
+    fn is_ignored(&mut self, path: &str) -> bool {
+        let elements = &self.elements;
+        self.ignore.is_match(path, &|element_path, filter| {
+            elements.iter().rev()
+                .find(|(path, _)| path == element_path)
+                .is_some_and(|(_, passed)| passed.contains(&filter))
+        })
+    }
+
+    /// Record that `old` and `new` at `path` differ but `rule` accepts them, if such values are reported
+    fn push_tolerated(&mut self, path: &str, old: &Value, new: &Value, rule: String) {
+        if !self.options.report_tolerated {
+            return;
+        }
+
+        let entry = DiffEntry::tolerated(path, old.clone(), new.clone(), rule)
+            .with_lines(find_line_for_path(path, self.left_line_map), find_line_for_path(path, self.right_line_map));
+        self.entries.push(entry);
+    }
+
+    /// Whether a change at `path` is tagged as a change of an array item
+    fn is_array_item(&self, path: &str) -> bool {
+        if !self.options.tag_array_item_changes {
+            return false;
+        }
+        let (in_element, ends_with_index) = index_segments(path);
+        ends_with_index || (in_element && self.options.tag_nested_array_item_changes)
+    }
+
+    /// Record that the array at `path` differs as a whole, leaving out values over `max_value_size`
+    fn push_modified_array(&mut self, path: &str, old: &[Value], new: &[Value]) {
+        self.push_modified(path, Value::Array(old.to_vec()), Value::Array(new.to_vec()));
+
+        let limit = self.options.max_value_size;
+        let Some(entry) = self.entries.last_mut() else { return };
+        entry.array_summary = Some(ArraySummary::new(old, new));
+        if let Some(limit) = limit {
+            for value in [&mut entry.old_value, &mut entry.new_value] {
+                if value.as_ref().is_some_and(|v| serde_json::to_vec(v).map_or(0, |text| text.len()) > limit) {
+                    *value = None;
+                }
+            }
+        }
+    }
+
+    /// Note on the entries from `start` on, found beneath `left_prefix`, that their right
+    /// values lie beneath `right_prefix`, and look their right lines up there
+    fn relocate_right(&mut self, start: usize, left_prefix: &str, right_prefix: &str) {
+        let relocated: Vec<(usize, String, Option<usize>)> = self.entries[start..]
+            .iter()
+            .enumerate()
+            .filter_map(|(offset, entry)| {
+                let rest = entry.right_path.as_deref().unwrap_or(entry.indexed_path()).strip_prefix(left_prefix)?;
+                let right_path = format!("{}{}", right_prefix, rest);
+                let line = match entry.diff_type {
+                    DiffType::Removed => self.right_anchor(&right_path),
+                    _ => find_line_for_path(&right_path, self.right_line_map),
+                };
+                Some((start + offset, right_path, line))
+            })
+            .collect();
+        for (index, right_path, line) in relocated {
+            let entry = &mut self.entries[index];
+            entry.right_line = line.or(entry.right_line);
+            entry.right_path = Some(right_path);
+        }
+    }
+
+    /// Name the element at `element_path` by `segment` in the paths of the entries from
+    /// `start` on, keeping their numeric paths in `index_path`
+    fn key_element(&mut self, start: usize, element_path: &str, segment: Option<&String>) {
+        let Some(segment) = segment.filter(|_| self.options.path_index_style == IndexStyle::Keyed) else { return };
+        for entry in &mut self.entries[start..] {
+            entry.name_element(element_path, segment);
+        }
+    }
+
+    /// Left line of an entry only present in the right document: where it would be inserted
+    fn left_anchor(&self, path: &str) -> Option<usize> {
+        self.positions
+            .and_then(|(left, right)| self.insertion_line(true, path, left, right))
+            .or_else(|| find_line_for_path(path, self.left_line_map))
+    }
+
+    /// Right line of an entry only present in the left document: where it would be inserted
+    fn right_anchor(&self, path: &str) -> Option<usize> {
+        self.positions
+            .and_then(|(left, right)| self.insertion_line(false, path, right, left))
+            .or_else(|| find_line_for_path(path, self.right_line_map))
+    }
+
+    /// Line of `here` after which `path`, only in `other`, would be inserted; the lines of
+    /// all its siblings are found at once, so anchoring the members of a wide object is linear
+    fn insertion_line(&self, is_left: bool, path: &str, here: &LineMap, other: &LineMap) -> Option<usize> {
+        let (parent, index) = other.sibling_index(path)?;
+        let mut anchors = self.anchors.borrow_mut();
+        let lines = anchors.entry((is_left, parent.to_string())).or_insert_with(|| here.insertion_lines(parent, other));
+        lines.get(index).copied().flatten()
     }
 }
 
 /// Compare two JSON files and generate a diff result
-pub fn compare_files<P: AsRef<Path>>(
-    left_path: P,
-    right_path: P,
+///
+/// The paths may be of different types, e.g. a `PathBuf` and a `&str`. Besides what
+/// [`compare_strings`] records, the result holds both paths and labels them with their
+/// file names. A missing file is compared as [`CompareOptions::missing_file_policy`] says.
+pub fn compare_files<L: AsRef<Path>, R: AsRef<Path>>(
+    left_path: L,
+    right_path: R,
     options: &CompareOptions
 ) -> Result<DiffResult, JsonDiffError> {
-    let mut left_file = File::open(left_path.as_ref())?;
-    let mut right_file = File::open(right_path.as_ref())?;
+    let (left_path, right_path) = (left_path.as_ref(), right_path.as_ref());
+    let start = Instant::now();
+    let (left_content, left_missing) = read_input(left_path, options)?;
+    let (right_content, right_missing) = read_input(right_path, options)?;
+    if left_missing && right_missing {
+        // Nothing to compare; fail as without the policy
+        check_input_file(left_path)?;
+    }
+    let read = start.elapsed();
+
+    let mut result = compare_strings(&left_content, &right_content, options)?;
+    if let Some(timings) = &mut result.timings {
+        timings.read = read;
+        timings.total = start.elapsed();
+    }
+    // The substituted text is not a file any line or checksum could point into
+    if left_missing {
+        (result.left_missing, result.left_sha256, result.left_size) = (true, None, None);
+        result.entries.iter_mut().for_each(|entry| (entry.left_line, entry.left_span) = (None, None));
+    }
+    if right_missing {
+        (result.right_missing, result.right_sha256, result.right_size) = (true, None, None);
+        result.entries.iter_mut().for_each(|entry| (entry.right_line, entry.right_span) = (None, None));
+    }
+    result.set_files(left_path, right_path);
 
-    let mut left_content = String::new();
-    let mut right_content = String::new();
+    Ok(result)
+}
 
-    left_file.read_to_string(&mut left_content)?;
-    right_file.read_to_string(&mut right_content)?;
+/// The text of an input of [`compare_files`], and whether it is missing and `options`
+/// substitute it
+fn read_input(path: &Path, options: &CompareOptions) -> Result<(String, bool), JsonDiffError> {
+    match (read_file_limited(path, options.max_input_bytes), options.missing_file_policy.substitute()) {
+        (Err(JsonDiffError::FileNotFound { .. }), Some(substitute)) => Ok((substitute.to_string(), true)),
+        (read, _) => read.map(|content| (content, false)),
+    }
+}
 
-    let left_json: Value = serde_json::from_str(&left_content)?;
-    let right_json: Value = serde_json::from_str(&right_content)?;
+/// Compare two JSON documents given as text and generate a diff result
+///
+/// Line numbers, checksums and sizes of both inputs are recorded on the result.
+pub fn compare_strings(
+    left_content: &str,
+    right_content: &str,
+    options: &CompareOptions
+) -> Result<DiffResult, JsonDiffError> {
+    let start = Instant::now();
+    // Lenient documents are read with their non-finite tokens replaced; no line changes
+    let (left_text, right_text) = if options.lenient {
+        (substitute_non_finite(left_content), substitute_non_finite(right_content))
+    } else {
+        (Cow::Borrowed(left_content), Cow::Borrowed(right_content))
+    };
+    // Both sides are parsed so a failure names every unparseable one
+    let (left_json, right_json): (Value, Value) = match (serde_json::from_str(&left_text), serde_json::from_str(&right_text)) {
+        (Ok(left), Ok(right)) => (left, right),
+        (left, right) => {
+            let error = JsonDiffError::Unparseable {
+                left: left.err().map(|e| explain_parse_error(left_content, &e)),
+                right: right.err().map(|e| explain_parse_error(right_content, &e)),
+            };
+            return match options.text_fallback {
+                true => compare_lines(left_content, right_content, options, &error),
+                false => Err(error),
+            };
+        }
+    };
 
-    // Build line number mappings
-    let left_line_map = build_line_number_map(&left_content, &left_json);
-    let right_line_map = build_line_number_map(&right_content, &right_json);
+    let parse = start.elapsed();
+
+    // The scanner reads non-finite tokens as any other scalar, so positions are those of the original text
+    let left_positions = LineMap::scan(left_content);
+    let right_positions = LineMap::scan(right_content);
+    let line_maps = (left_positions.line_map(), right_positions.line_map());
+    let line_map = start.elapsed() - parse;
+
+    let mut result = compare_json_with_positions(
+        &left_json,
+        &right_json,
+        options,
+        (&line_maps.0, &line_maps.1),
+        Some((&left_positions, &right_positions)),
+    )?;
+    if let Some(timings) = &mut result.timings {
+        (timings.parse, timings.line_map) = (parse, line_map);
+        timings.total = start.elapsed();
+    }
+    result.left_sha256 = Some(sha256_hex(left_content.as_bytes()));
+    result.right_sha256 = Some(sha256_hex(right_content.as_bytes()));
+    result.left_size = Some(left_content.len() as u64);
+    result.right_size = Some(right_content.len() as u64);
 
-    let mut result = compare_json_with_lines(&left_json, &right_json, options, &left_line_map, &right_line_map)?;
-    result.left_file = Some(left_path.as_ref().to_path_buf());
-    result.right_file = Some(right_path.as_ref().to_path_buf());
+    Ok(result)
+}
 
+/// The line diff [`CompareOptions::text_fallback`] reports for texts failing to parse with
+/// `error`, with checksums and sizes as [`compare_strings`] records them
+fn compare_lines(left_content: &str, right_content: &str, options: &CompareOptions, error: &JsonDiffError) -> Result<DiffResult, JsonDiffError> {
+    let mut builder = DiffResult::builder()
+        .entries(textdiff::line_entries(left_content, right_content))
+        .warning(format!("{}; structural comparison was not possible, so the texts were compared line by line", error));
+    if let Some(timestamp) = generation_time(options) {
+        builder = builder.timestamp(timestamp);
+    }
+    let mut result = builder.build()?;
+    result.left_sha256 = Some(sha256_hex(left_content.as_bytes()));
+    result.right_sha256 = Some(sha256_hex(right_content.as_bytes()));
+    result.left_size = Some(left_content.len() as u64);
+    result.right_size = Some(right_content.len() as u64);
     Ok(result)
 }
 
@@ -80,117 +836,304 @@ pub fn compare_json_with_lines(
     left_line_map: &HashMap<String, usize>,
     right_line_map: &HashMap<String, usize>,
 ) -> Result<DiffResult, JsonDiffError> {
-    let mut entries = Vec::new();
+    compare_json_with_positions(left, right, options, (left_line_map, right_line_map), None)
+}
+
+fn compare_json_with_positions(
+    left: &Value,
+    right: &Value,
+    options: &CompareOptions,
+    (left_line_map, right_line_map): (&HashMap<String, usize>, &HashMap<String, usize>),
+    positions: Option<(&LineMap, &LineMap)>,
+) -> Result<DiffResult, JsonDiffError> {
+    let start = Instant::now();
+    // Compare the selected subtrees, with paths relative to them but lines of the whole documents
+    let (left, left_root) = select_root(left, options.left_root.as_ref(), "left")?;
+    let (right, right_root) = select_root(right, options.right_root.as_ref(), "right")?;
+    let rebase_lines = |map: &HashMap<String, usize>, root: &Option<String>| {
+        root.as_ref().map(|root| {
+            map.iter().filter_map(|(path, line)| Some((rebase_path(path, root)?, *line))).collect::<HashMap<_, _>>()
+        })
+    };
+    let (left_lines, right_lines) = (rebase_lines(left_line_map, &left_root), rebase_lines(right_line_map, &right_root));
+    let rebase_positions = |map, root: &Option<String>| match root {
+        Some(root) => Cow::Owned(LineMap::rebase(map, root)),
+        None => Cow::Borrowed(map),
+    };
+    let positions = positions.map(|(left, right)| (rebase_positions(left, &left_root), rebase_positions(right, &right_root)));
+
+    let mut ctx = CompareContext {
+        options,
+        left_line_map: left_lines.as_ref().unwrap_or(left_line_map),
+        right_line_map: right_lines.as_ref().unwrap_or(right_line_map),
+        positions: positions.as_ref().map(|(left, right)| (left.as_ref(), right.as_ref())),
+        ignore: RuleSet::new(&options.ignore_paths, options.explain_rules),
+        filters: options.ignore_paths.iter().flat_map(JsonPath::filters).collect(),
+        elements: Vec::new(),
+        entries: Vec::new(),
+        warnings: Vec::new(),
+        anchors: RefCell::default(),
+        budget: Budget::new(options),
+        emitted: HashSet::new(),
+        ignored: HashSet::new(),
+        checked: 0,
+    };
+    if options.report_key_order && !cfg!(feature = "key-order") {
+        ctx.warnings.push("report_key_order needs json-diff built with the key-order feature; key order was not compared".to_string());
+    }
+
+    // Line maps describe the original documents, so paths inside transformed
+    // regions fall back to their nearest surviving ancestor on lookup
+    let (left, right) = if options.preprocess.is_empty() {
+        (Cow::Borrowed(left), Cow::Borrowed(right))
+    } else {
+        let mut left = left.clone();
+        let mut right = right.clone();
+        ctx.warnings.extend(apply_transforms(&options.preprocess, &mut left, true));
+        ctx.warnings.extend(apply_transforms(&options.preprocess, &mut right, false));
+        (Cow::Owned(left), Cow::Owned(right))
+    };
+
+    // Mapped values are taken out of the documents and compared on their own
+    let (left, right, mapped) = if options.path_mappings.is_empty() {
+        (left, right, Vec::new())
+    } else {
+        let (mut left, mut right) = (left.into_owned(), right.into_owned());
+        let mapped = take_mapped(&options.path_mappings, &mut left, &mut right)?;
+        (Cow::Owned(left), Cow::Owned(right), mapped)
+    };
+
+    compare_values_with_lines(&left, &right, "$", &mut ctx)?;
+    for pair in mapped {
+        compare_mapped(pair, &mut ctx)?;
+    }
+    ctx.check_emitted()?;
+    ctx.budget.spend(0, ctx.entries.len())?;
+
+    let CompareContext { mut entries, warnings, ignore, .. } = ctx;
+    if let Some((left_positions, right_positions)) = positions.as_ref().filter(|_| options.record_spans) {
+        for entry in &mut entries {
+            let path = entry.indexed_path();
+            let left_span = left_positions.span_of(path);
+            let right_span = match (entry.moved_to, entry.mapped_to.as_ref().or(entry.right_path.as_ref())) {
+                (Some(index), _) => right_positions.span_of(&format!("{}[{}]", parent_path(path), index)),
+                (None, Some(right_path)) => right_positions.span_of(right_path),
+                (None, None) => right_positions.span_of(path),
+            };
+            (entry.left_span, entry.right_span) = (left_span, right_span);
+        }
+    }
+
+    if options.numeric_deltas {
+        entries.iter_mut().for_each(DiffEntry::record_delta);
+    }
+    record_near_matches(&mut entries, options);
 
-    compare_values_with_lines(left, right, "$", &mut entries, options, left_line_map, right_line_map)?;
+    let compared = start.elapsed();
 
     // Sort entries by line number (based on left file's line numbers)
     sort_entries_by_line_number(&mut entries);
 
-    let result = DiffResult {
+    let mut result = DiffResult {
         left_file: None,
         right_file: None,
-        timestamp: Utc::now(),
+        timestamp: generation_time(options),
         entries,
+        warnings,
+        left_sha256: None,
+        right_sha256: None,
+        left_size: None,
+        right_size: None,
+        sources: Vec::new(),
+        annotations: Annotations::default(),
+        left_label: None,
+        right_label: None,
+        rule_stats: ignore.into_stats(),
+        generator: GENERATOR.to_string(),
+        hostname: None,
+        timings: None,
+        left_missing: false,
+        right_missing: false,
+        selections: options.select_paths.iter().map(JsonPath::to_string).collect(),
     };
+    if options.normalize_entries {
+        result.normalize();
+    }
+    if options.collect_timings {
+        let total = start.elapsed();
+        result.timings = Some(Timings { compare: compared, sort: total - compared, total, ..Timings::default() });
+    }
 
     Ok(result)
 }
 
-/// Sort diff entries by line number (based on left file's line numbers)
-/// Entries without line numbers are placed at the end
-fn sort_entries_by_line_number(entries: &mut Vec<DiffEntry>) {
-    entries.sort_by_key(|entry| {
-        // Use left_line as primary sort key, fall back to right_line if left_line is None
-        // Entries without any line numbers go to the end (using usize::MAX)
-        match (entry.left_line, entry.right_line) {
-            (Some(left_line), _) => left_line,
-            (None, Some(right_line)) => right_line,
-            (None, None) => usize::MAX,
-        }
-    });
+/// Values of the two documents paired by a mapping rule, at most one of them absent
+struct MappedPair {
+    left_path: String,
+    right_path: String,
+    left: Option<Value>,
+    right: Option<Value>,
 }
 
-/// Build a mapping from JSON paths to line numbers
-fn build_line_number_map(content: &str, json: &Value) -> HashMap<String, usize> {
-    let mut line_map = HashMap::new();
+/// Take the values paired by `mappings` out of both documents
+///
+/// Pairs are found from the left values, then from the right values no left value
+/// took, so each value is compared once.
+fn take_mapped(mappings: &[PathMapping], left: &mut Value, right: &mut Value) -> Result<Vec<MappedPair>, JsonDiffError> {
+    // Objects emptied by taking a value out are taken as well, so they are not reported
+    let take = |document: &mut Value, path: &str| {
+        let taken = JsonPath::new(path).ok()?.take(document);
+        let mut parent = parent_path(path);
+        while taken.is_some() && parent.len() > 1 {
+            let Ok(parent_json_path) = JsonPath::new(parent) else { break };
+            if !parent_json_path.select(document).is_some_and(|(_, value)| value.as_object().is_some_and(Map::is_empty)) {
+                break;
+            }
+            parent_json_path.take(document);
+            parent = parent_path(parent);
+        }
+        taken
+    };
+    let mut pairs = Vec::new();
+
+    for mapping in mappings {
+        mapping.validate()?;
+        let found: Vec<(String, Vec<usize>)> = mapping.left.select_all(left).into_iter().map(|(path, indices, _)| (path, indices)).collect();
+        for (left_path, indices) in found {
+            // Paths are concrete and were validated, so they resolve
+            let Some(right_path) = mapping.right.with_indices(&indices) else { continue };
+            let (left_value, right_value) = (take(left, &left_path), take(right, &right_path));
+            pairs.push(MappedPair { left_path, right_path, left: left_value, right: right_value });
+        }
 
-    // Build a mapping by parsing the JSON structure and tracking line numbers
-    build_path_line_mapping(json, "$", content, &mut line_map);
+        let found: Vec<(String, Vec<usize>)> = mapping.right.select_all(right).into_iter().map(|(path, indices, _)| (path, indices)).collect();
+        for (right_path, indices) in found {
+            let Some(left_path) = mapping.left.with_indices(&indices) else { continue };
+            let right_value = take(right, &right_path);
+            pairs.push(MappedPair { left_path, right_path, left: None, right: right_value });
+        }
+    }
 
-    line_map
+    Ok(pairs)
 }
 
-/// Recursively build path to line number mapping
-fn build_path_line_mapping(
-    value: &Value,
-    current_path: &str,
-    content: &str,
-    line_map: &mut HashMap<String, usize>,
-) {
-    match value {
-        Value::Object(obj) => {
-            for (key, val) in obj {
-                let field_path = if current_path == "$" {
-                    format!("$.{}", key)
-                } else {
-                    format!("{}.{}", current_path, key)
-                };
-
-                // Find the line number for this field
-                if let Some(line_num) = find_field_line_number(content, key) {
-                    line_map.insert(field_path.clone(), line_num);
-                }
+/// Compare the values of a mapping rule, reporting entries at the left path with the right path noted
+fn compare_mapped(pair: MappedPair, ctx: &mut CompareContext<'_>) -> Result<(), JsonDiffError> {
+    let MappedPair { left_path, right_path, left, right } = pair;
+    let start = ctx.entries.len();
 
-                // Recursively process nested values
-                build_path_line_mapping(val, &field_path, content, line_map);
-            }
+    let lines = (find_line_for_path(&left_path, ctx.left_line_map), find_line_for_path(&right_path, ctx.right_line_map));
+    match (left, right) {
+        (Some(left), Some(right)) => compare_values_with_lines(&left, &right, &left_path, ctx)?,
+        (Some(_), None) | (None, Some(_)) if ctx.is_ignored(&left_path) => {
+            ctx.push_ignored(DiffEntry::ignored(&left_path).with_lines(lines.0, lines.1));
         }
-        Value::Array(arr) => {
-            for (index, val) in arr.iter().enumerate() {
-                let array_path = format!("{}[{}]", current_path, index);
-
-                // For arrays, we'll use the line number of the parent field
-                // A more sophisticated approach would track the exact array element line
-                if let Some(parent_line) = line_map.get(current_path) {
-                    line_map.insert(array_path.clone(), *parent_line);
-                }
+        (Some(left), None) => ctx.entries.push(DiffEntry::removed(&left_path, left).with_lines(lines.0, lines.1)),
+        (None, Some(right)) => ctx.entries.push(DiffEntry::added(&left_path, right).with_lines(lines.0, lines.1)),
+        (None, None) => {}
+    }
 
-                // Recursively process array elements
-                build_path_line_mapping(val, &array_path, content, line_map);
-            }
-        }
-        _ => {
-            // For primitive values, the line number is already set by the parent object
-        }
+    // Entries beneath the mapped value lie beneath the right path in the right document
+    for entry in &mut ctx.entries[start..] {
+        let Some(right_entry_path) = entry.indexed_path().strip_prefix(&left_path).map(|rest| format!("{}{}", right_path, rest)) else { continue };
+        entry.right_line = find_line_for_path(&right_entry_path, ctx.right_line_map).or(entry.right_line);
+        entry.mapped_to = Some(right_entry_path);
     }
+
+    Ok(())
 }
 
-/// Find the line number where a specific field is defined
-fn find_field_line_number(content: &str, field_name: &str) -> Option<usize> {
-    let mut found_lines = Vec::new();
+/// When a result is generated: the options' timestamp, else now; without the
+/// `timestamps` feature there is no clock to read, so only the options' timestamp
+fn generation_time(options: &CompareOptions) -> Option<Timestamp> {
+    #[cfg(feature = "timestamps")]
+    return Some(options.timestamp.unwrap_or_else(Utc::now));
+    #[cfg(not(feature = "timestamps"))]
+    return options.timestamp.clone();
+}
 
-    for (line_num, line) in content.lines().enumerate() {
-        let line_num = line_num + 1; // 1-based line numbers
+/// The subtree of `value` at `root`, if given, with the root's path as entries report it
+fn select_root<'a>(value: &'a Value, root: Option<&JsonPath>, side: &'static str) -> Result<(&'a Value, Option<String>), JsonDiffError> {
+    let Some(root) = root else { return Ok((value, None)) };
+    let invalid = |reason: &str| JsonDiffError::InvalidRoot { side, path: root.to_string(), reason: reason.to_string() };
 
-        // Look for the field name as a quoted string followed by a colon
-        let pattern = format!("\"{}\"", field_name);
-        if let Some(start) = line.find(&pattern) {
-            // Check if this is followed by a colon (with possible whitespace)
-            let after_quote = start + pattern.len();
-            if line[after_quote..].trim_start().starts_with(':') {
-                found_lines.push(line_num);
-            }
-        }
+    if !root.is_concrete() {
+        return Err(invalid("a root must name a single value, without wildcards or regex"));
+    }
+    let (path, subtree) = root.select(value).ok_or_else(|| invalid("no value at this path"))?;
+    Ok((subtree, Some(path)))
+}
+
+/// Compare two values found at `path` within larger documents, e.g. array elements of
+/// a stream or the values of a saved entry, without line information; the filters of
+/// the ignore rules are resolved against them
+///
+/// `ignore` holds the ignore rules of `options`, so they are compiled once for many calls.
+pub(crate) fn compare_values_at(
+    left: &Value,
+    right: &Value,
+    path: &str,
+    options: &CompareOptions,
+    ignore: &mut RuleSet,
+) -> Result<(Vec<DiffEntry>, Vec<String>), JsonDiffError> {
+    let empty_map = HashMap::new();
+    let mut ctx = CompareContext {
+        options,
+        left_line_map: &empty_map,
+        right_line_map: &empty_map,
+        positions: None,
+        ignore: std::mem::replace(ignore, RuleSet::new(&[], false)),
+        filters: options.ignore_paths.iter().flat_map(JsonPath::filters).collect(),
+        elements: Vec::new(),
+        entries: Vec::new(),
+        warnings: Vec::new(),
+        anchors: RefCell::default(),
+        budget: Budget::new(options),
+        emitted: HashSet::new(),
+        ignored: HashSet::new(),
+        checked: 0,
+    };
+
+    let compared = compare_elements(left, right, path, &mut ctx);
+    *ignore = ctx.ignore;
+    compared?;
+    if options.numeric_deltas {
+        ctx.entries.iter_mut().for_each(DiffEntry::record_delta);
     }
+    record_near_matches(&mut ctx.entries, options);
+
+    Ok((ctx.entries, ctx.warnings))
+}
 
-    // Return the first occurrence for now
-    // A more sophisticated approach would track context to find the right occurrence
-    found_lines.first().copied()
+/// Mark the modifications between alike strings, see [`CompareOptions::string_similarity`]
+fn record_near_matches(entries: &mut [DiffEntry], options: &CompareOptions) {
+    let Some((threshold, paths)) = &options.string_similarity else { return };
+    entries.iter_mut()
+        .filter(|entry| paths.is_empty() || paths.iter().any(|path| path.matches(entry.indexed_path())))
+        .for_each(|entry| entry.record_near_match(*threshold));
+}
+
+/// Sort diff entries by line number (based on left file's line numbers)
+/// Entries without line numbers are placed at the end
+fn sort_entries_by_line_number(entries: &mut [DiffEntry]) {
+    entries.sort_by_key(|entry| {
+        // Use left_line as primary sort key, fall back to right_line if left_line is None
+        // Entries without any line numbers go to the end (using usize::MAX)
+        match (entry.left_line, entry.right_line) {
+            (Some(left_line), _) => left_line,
+            (None, Some(right_line)) => right_line,
+            (None, None) => usize::MAX,
+        }
+    });
 }
 
 /// Find line number for a given path, with fallback strategies
 fn find_line_for_path(path: &str, line_map: &HashMap<String, usize>) -> Option<usize> {
+    // Paths inside an embedded document all live on the line of the string holding it
+    if let Some(pos) = path.find(EMBEDDED_JSON_MARKER) {
+        return find_line_for_path(&path[..pos], line_map);
+    }
+
     // Try exact match first
     if let Some(line) = line_map.get(path) {
         return Some(*line);
@@ -208,7 +1151,7 @@ fn find_line_for_path(path: &str, line_map: &HashMap<String, usize>) -> Option<u
     }
 
     // Try to find by field name only (last component of the path)
-    if let Some(field_name) = path.split('.').last() {
+    if let Some(field_name) = path.split('.').next_back() {
         // Remove array indices if present
         let clean_field = field_name.split('[').next().unwrap_or(field_name);
 
@@ -225,96 +1168,304 @@ fn find_line_for_path(path: &str, line_map: &HashMap<String, usize>) -> Option<u
 
 
 
+/// Compare two values at `path`, then check the entries reported for them, see
+/// [`CompareContext::check_emitted`]
 fn compare_values_with_lines(
     left: &Value,
     right: &Value,
     path: &str,
-    entries: &mut Vec<DiffEntry>,
-    options: &CompareOptions,
-    left_line_map: &HashMap<String, usize>,
-    right_line_map: &HashMap<String, usize>,
+    ctx: &mut CompareContext<'_>,
+) -> Result<(), JsonDiffError> {
+    compare_value_pair(left, right, path, ctx)?;
+    ctx.check_emitted()
+}
+
+/// Compare two values at `path`, see [`compare_values_with_lines`]
+fn compare_value_pair(
+    left: &Value,
+    right: &Value,
+    path: &str,
+    ctx: &mut CompareContext<'_>,
 ) -> Result<(), JsonDiffError> {
+    ctx.budget.spend(2, ctx.entries.len())?;
+
     // Check if this path should be ignored
-    if options.ignore_paths.iter().any(|p| p.matches(path)) {
-        entries.push(DiffEntry {
-            diff_type: DiffType::Ignored,
-            path: path.to_string(),
-            old_value: None,
-            new_value: None,
-            left_line: find_line_for_path(path, left_line_map),
-            right_line: find_line_for_path(path, right_line_map),
-        });
+    if ctx.is_ignored(path) {
+        let entry = ctx.ignored_entry(path);
+        ctx.push_ignored(entry);
+        return Ok(());
+    }
+
+    // Let a matching custom comparator decide before the built-in logic
+    if let Some((_, comparator)) = ctx.options.custom_comparators.iter().find(|(p, _)| p.matches(path)) {
+        match comparator.compare(path, left, right) {
+            ComparatorVerdict::Equal => {}
+            ComparatorVerdict::Different => {
+                ctx.push_modified(path, left.clone(), right.clone());
+            }
+            ComparatorVerdict::Replace(entry) => ctx.entries.push(*entry),
+        }
+        return Ok(());
+    }
+
+    // Decode encoded strings before comparing them
+    if let (Value::String(left_str), Value::String(right_str)) = (left, right) {
+        if let Some((_, codec)) = ctx.options.decoders.iter().find(|(p, _)| p.matches(path)) {
+            if left_str != right_str {
+                compare_decoded(left_str, right_str, *codec, path, ctx)?;
+            }
+            return Ok(());
+        }
+    }
+
+    // Compare JSON documents embedded in strings structurally
+    if let Some((left_doc, right_doc)) = parse_embedded_pair(left, right, path, ctx.options) {
+        let embedded_path = format!("{}{}", path, EMBEDDED_JSON_MARKER);
+        return compare_values_with_lines(&left_doc, &right_doc, &embedded_path, ctx);
+    }
+
+    // Compare numeric strings (and strings against numbers) by their numeric value
+    if let Some(equal) = compare_as_numbers(left, right, path, ctx) {
+        if !equal {
+            ctx.push_modified(path, left.clone(), right.clone());
+        }
         return Ok(());
     }
 
     match (left, right) {
         (Value::Object(left_obj), Value::Object(right_obj)) => {
-            compare_objects_with_lines(left_obj, right_obj, path, entries, options, left_line_map, right_line_map)?;
+            compare_objects_with_lines(left_obj, right_obj, path, ctx)?;
         }
         (Value::Array(left_arr), Value::Array(right_arr)) => {
+            let set = ctx.options.set_arrays.iter().any(|p| p.matches(path));
+            let primitive = |arr: &[Value]| arr.iter().all(|item| !item.is_object() && !item.is_array());
+            if set && primitive(left_arr) && primitive(right_arr) {
+                compare_sets(left_arr, right_arr, path, ctx);
+                return Ok(());
+            }
+            if set {
+                ctx.warnings.push(format!(
+                    "Array at {} holds objects or arrays, so it cannot be compared as a set; comparing it as unordered",
+                    path
+                ));
+            }
+
             // Check if this array should be compared without order
-            let unordered = options.unordered_arrays.iter().any(|p| p.matches(path));
-            compare_arrays_with_lines(left_arr, right_arr, path, entries, options, unordered, left_line_map, right_line_map)?;
+            let unordered = set || ctx.options.unordered_arrays.iter().any(|p| p.matches(path));
+            compare_arrays_with_lines(left_arr, right_arr, path, unordered, ctx)?;
         }
         _ if left == right => {
             // Values are equal, no diff needed
         }
-        _ => {
-            // Values are different
-            let left_line = find_line_for_path(path, left_line_map);
-            let right_line = find_line_for_path(path, right_line_map);
-
-            entries.push(DiffEntry {
-                diff_type: DiffType::Modified,
-                path: path.to_string(),
-                old_value: Some(left.clone()),
-                new_value: Some(right.clone()),
-                left_line,
-                right_line,
-            });
+        (Value::Number(l), Value::Number(r)) if ctx.options.numeric_equivalence && numbers_equivalent(l, r) => {
+            // Same value written differently, e.g. `1` and `1.0`
+            ctx.push_tolerated(path, left, right, "numeric_equivalence".to_string());
         }
+        _ => match number_tolerance(path, left, right, ctx.options) {
+            // Numbers are close enough according to a tolerance rule
+            Some(tolerance) => ctx.push_tolerated(path, left, right, format!("tolerance {}", tolerance)),
+            // Values are different
+            None => ctx.push_modified(path, left.clone(), right.clone()),
+        },
     }
 
     Ok(())
 }
 
 
-
-fn compare_objects_with_lines(
-    left: &Map<String, Value>,
-    right: &Map<String, Value>,
+/// Compare two encoded strings by their decoded content
+///
+/// Values that fail to decode are compared raw, with a warning. Decoded UTF-8 text is
+/// compared as strings (and may be parsed as embedded JSON); other bytes are compared
+/// as binary and reported with hex previews.
+fn compare_decoded(
+    left: &str,
+    right: &str,
+    codec: Codec,
     path: &str,
-    entries: &mut Vec<DiffEntry>,
-    options: &CompareOptions,
-    left_line_map: &HashMap<String, usize>,
-    right_line_map: &HashMap<String, usize>,
+    ctx: &mut CompareContext<'_>,
 ) -> Result<(), JsonDiffError> {
-    // Find keys that exist in left but not in right
-    for key in left.keys() {
-        if !right.contains_key(key) {
-            let key_path = format!("{}.{}", path, key);
-
-            // Check if this path should be ignored
-            if options.ignore_paths.iter().any(|p| p.matches(&key_path)) {
-                entries.push(DiffEntry {
-                    diff_type: DiffType::Ignored,
-                    path: key_path.clone(),
-                    old_value: None,
-                    new_value: None,
-                    left_line: find_line_for_path(&key_path, left_line_map),
-                    right_line: find_line_for_path(&key_path, right_line_map),
-                });
+    let mut decode = |value: &str, side: &str| match codec.decode(value) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            ctx.warnings.push(format!(
+                "Invalid {} value at {} in the {} document ({}); comparing the raw value",
+                codec, path, side, e
+            ));
+            value.as_bytes().to_vec()
+        }
+    };
+    let left_bytes = decode(left, "left");
+    let right_bytes = decode(right, "right");
+
+    let (old_value, new_value) = match (String::from_utf8(left_bytes), String::from_utf8(right_bytes)) {
+        (Ok(left_text), Ok(right_text)) => {
+            let (left_text, right_text) = (Value::String(left_text), Value::String(right_text));
+            if let Some((left_doc, right_doc)) = parse_embedded_pair(&left_text, &right_text, path, ctx.options) {
+                let embedded_path = format!("{}{}", path, EMBEDDED_JSON_MARKER);
+                return compare_values_with_lines(&left_doc, &right_doc, &embedded_path, ctx);
+            }
+            if left_text == right_text {
+                ctx.push_tolerated(path, &Value::from(left), &Value::from(right), format!("decoded {}", codec));
+                return Ok(());
+            }
+            (left_text, right_text)
+        }
+        (left_result, right_result) => {
+            let left_bytes = left_result.map(String::into_bytes).unwrap_or_else(|e| e.into_bytes());
+            let right_bytes = right_result.map(String::into_bytes).unwrap_or_else(|e| e.into_bytes());
+            if left_bytes == right_bytes {
+                ctx.push_tolerated(path, &Value::from(left), &Value::from(right), format!("decoded {}", codec));
+                return Ok(());
+            }
+            (Value::String(hex_preview(&left_bytes)), Value::String(hex_preview(&right_bytes)))
+        }
+    };
+
+    ctx.push_modified(path, old_value, new_value);
+
+    Ok(())
+}
+
+/// Parse both strings as JSON when the path is configured for embedded documents,
+/// returning `None` to fall back to plain string comparison
+fn parse_embedded_pair(left: &Value, right: &Value, path: &str, options: &CompareOptions) -> Option<(Value, Value)> {
+    let (Value::String(left_str), Value::String(right_str)) = (left, right) else {
+        return None;
+    };
+
+    if left_str == right_str || path.matches(EMBEDDED_JSON_MARKER).count() >= MAX_EMBEDDED_JSON_DEPTH {
+        return None;
+    }
+
+    let looks_like_json = |s: &str| matches!(s.trim_start().chars().next(), Some('{') | Some('['));
+    let configured = options.parse_embedded_json.iter().any(|p| p.matches(path))
+        || (options.auto_parse_embedded_json && looks_like_json(left_str) && looks_like_json(right_str));
+    if !configured {
+        return None;
+    }
+
+    let left_doc = serde_json::from_str(left_str).ok()?;
+    let right_doc = serde_json::from_str(right_str).ok()?;
+    Some((left_doc, right_doc))
+}
+
+/// Compare values numerically when at least one side is a string at a numeric-string path
+///
+/// Returns `None` when the values should go through the normal comparison instead,
+/// warning if a string at an explicitly configured path could not be parsed.
+fn compare_as_numbers(left: &Value, right: &Value, path: &str, ctx: &mut CompareContext<'_>) -> Option<bool> {
+    if !matches!(left, Value::String(_)) && !matches!(right, Value::String(_)) {
+        return None;
+    }
+
+    let configured = ctx.options.numeric_strings.iter().any(|p| p.matches(path));
+    if !configured && !ctx.options.all_numeric_strings {
+        return None;
+    }
+
+    let locale = ctx.options.number_locale;
+    let as_number = |value: &Value| match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => locale.parse(s),
+        _ => None,
+    };
+
+    match (as_number(left), as_number(right)) {
+        (Some(l), Some(r)) if l == r => {
+            if left != right {
+                ctx.push_tolerated(path, left, right, "numeric string".to_string());
+            }
+            Some(true)
+        }
+        (Some(l), Some(r)) => match tolerance_for(path, l, r, ctx.options) {
+            Some(tolerance) => {
+                ctx.push_tolerated(path, left, right, format!("tolerance {}", tolerance));
+                Some(true)
+            }
+            None => Some(false),
+        },
+        (l, _) => {
+            if configured {
+                let side = if l.is_none() { ("left", left) } else { ("right", right) };
+                if let Value::String(text) = side.1 {
+                    ctx.warnings.push(format!(
+                        "Could not parse {:?} at {} in the {} document as a number; comparing as a string",
+                        text, path, side.0
+                    ));
+                }
+            }
+            None
+        }
+    }
+}
+
+/// Whether two numbers have the same value, comparing integers exactly and others as `f64`
+///
+/// `-0` and `0` are equal, as they are for `f64`.
+fn numbers_equivalent(left: &serde_json::Number, right: &serde_json::Number) -> bool {
+    if let (Some(l), Some(r)) = (left.as_i64(), right.as_i64()) {
+        return l == r;
+    }
+    if let (Some(l), Some(r)) = (left.as_u64(), right.as_u64()) {
+        return l == r;
+    }
+    matches!((left.as_f64(), right.as_f64()), (Some(l), Some(r)) if l == r)
+}
+
+/// The configured tolerance for the path within which two numbers are, if both values are numbers
+fn number_tolerance(path: &str, left: &Value, right: &Value, options: &CompareOptions) -> Option<f64> {
+    match (left.as_f64(), right.as_f64()) {
+        (Some(l), Some(r)) if left.is_number() && right.is_number() => tolerance_for(path, l, r, options),
+        _ => None,
+    }
+}
+
+/// The first tolerance for the path within which two numbers are
+fn tolerance_for(path: &str, l: f64, r: f64, options: &CompareOptions) -> Option<f64> {
+    options.tolerances.iter()
+        .filter(|(p, _)| p.matches(path))
+        .map(|(_, tolerance)| *tolerance)
+        .find(|tolerance| (l - r).abs() <= *tolerance)
+}
+
+/// Record that two objects hold the same keys in another order; objects whose keys
+/// differ get entries for those keys instead
+fn push_key_order_change(left: &Map<String, Value>, right: &Map<String, Value>, path: &str, ctx: &mut CompareContext<'_>) {
+    let same_keys = left.len() == right.len() && left.keys().all(|key| right.contains_key(key));
+    if !same_keys || left.keys().eq(right.keys()) {
+        return;
+    }
+
+    let keys = |object: &Map<String, Value>| Value::Array(object.keys().cloned().map(Value::String).collect());
+    ctx.entries.push(DiffEntry { diff_type: DiffType::KeyOrderChanged, ..DiffEntry::modified(path, keys(left), keys(right)) }
+        .with_lines(find_line_for_path(path, ctx.left_line_map), find_line_for_path(path, ctx.right_line_map)));
+}
+
+fn compare_objects_with_lines(
+    left: &Map<String, Value>,
+    right: &Map<String, Value>,
+    path: &str,
+    ctx: &mut CompareContext<'_>,
+) -> Result<(), JsonDiffError> {
+    if cfg!(feature = "key-order") && ctx.options.report_key_order {
+        push_key_order_change(left, right, path, ctx);
+    }
+
+    // Find keys that exist in left but not in right
+    for key in left.keys() {
+        if !right.contains_key(key) {
+            let key_path = format!("{}.{}", path, key);
+
+            // Check if this path should be ignored
+            if ctx.is_ignored(&key_path) {
+                let entry = ctx.ignored_entry(&key_path);
+                ctx.push_ignored(entry);
                 continue;
             }
 
-            entries.push(DiffEntry {
-                diff_type: DiffType::Removed,
-                path: key_path.clone(),
-                old_value: Some(left[key].clone()),
-                new_value: None,
-                left_line: find_line_for_path(&key_path, left_line_map),
-                right_line: find_line_for_path(&key_path, right_line_map),
-            });
+            ctx.entries.push(DiffEntry::removed(key_path.clone(), left[key].clone())
+                .with_lines(find_line_for_path(&key_path, ctx.left_line_map), ctx.right_anchor(&key_path)));
         }
     }
 
@@ -323,29 +1474,17 @@ fn compare_objects_with_lines(
         let key_path = format!("{}.{}", path, key);
 
         // Check if this path should be ignored
-        if options.ignore_paths.iter().any(|p| p.matches(&key_path)) {
-            entries.push(DiffEntry {
-                diff_type: DiffType::Ignored,
-                path: key_path.clone(),
-                old_value: None,
-                new_value: None,
-                left_line: find_line_for_path(&key_path, left_line_map),
-                right_line: find_line_for_path(&key_path, right_line_map),
-            });
+        if ctx.is_ignored(&key_path) {
+            let entry = ctx.ignored_entry(&key_path);
+            ctx.push_ignored(entry);
             continue;
         }
 
         if !left.contains_key(key) {
-            entries.push(DiffEntry {
-                diff_type: DiffType::Added,
-                path: key_path.clone(),
-                old_value: None,
-                new_value: Some(right[key].clone()),
-                left_line: find_line_for_path(&key_path, left_line_map),
-                right_line: find_line_for_path(&key_path, right_line_map),
-            });
+            ctx.entries.push(DiffEntry::added(key_path.clone(), right[key].clone())
+                .with_lines(ctx.left_anchor(&key_path), find_line_for_path(&key_path, ctx.right_line_map)));
         } else {
-            compare_values_with_lines(&left[key], &right[key], &key_path, entries, options, left_line_map, right_line_map)?;
+            compare_values_with_lines(&left[key], &right[key], &key_path, ctx)?;
         }
     }
 
@@ -353,16 +1492,42 @@ fn compare_objects_with_lines(
 }
 
 
+/// Compare two arrays of primitives as sets
+///
+/// Each value only in the left array is reported once as removed, each value only in
+/// the right array once as added, at the index where it first appears on its side.
+fn compare_sets(left: &[Value], right: &[Value], path: &str, ctx: &mut CompareContext<'_>) {
+    // Primitives are equal exactly when their JSON text is
+    let members = |array: &[Value]| array.iter().map(Value::to_string).collect::<HashSet<String>>();
+    let (left_members, right_members) = (members(left), members(right));
+
+    let mut reported = HashSet::new();
+    for (index, item) in left.iter().enumerate() {
+        let text = item.to_string();
+        if !right_members.contains(&text) && reported.insert(text) {
+            let item_path = format!("{}[{}]", path, index);
+            ctx.entries.push(DiffEntry::removed(item_path.clone(), item.clone())
+                .with_lines(find_line_for_path(&item_path, ctx.left_line_map), ctx.right_anchor(&item_path)));
+        }
+    }
+
+    reported.clear();
+    for (index, item) in right.iter().enumerate() {
+        let text = item.to_string();
+        if !left_members.contains(&text) && reported.insert(text) {
+            let item_path = format!("{}[{}]", path, index);
+            ctx.entries.push(DiffEntry::added(item_path.clone(), item.clone())
+                .with_lines(ctx.left_anchor(&item_path), find_line_for_path(&item_path, ctx.right_line_map)));
+        }
+    }
+}
 
 fn compare_arrays_with_lines(
     left: &[Value],
     right: &[Value],
     path: &str,
-    entries: &mut Vec<DiffEntry>,
-    options: &CompareOptions,
     unordered: bool,
-    left_line_map: &HashMap<String, usize>,
-    right_line_map: &HashMap<String, usize>,
+    ctx: &mut CompareContext<'_>,
 ) -> Result<(), JsonDiffError> {
     if unordered {
         // For unordered comparison, we check if the arrays have the same elements
@@ -370,35 +1535,33 @@ fn compare_arrays_with_lines(
 
         // First, mark the array as reordered if the arrays are different
         if left != right {
-            entries.push(DiffEntry {
-                diff_type: DiffType::ArrayReordered,
-                path: path.to_string(),
-                old_value: None,
-                new_value: None,
-                left_line: find_line_for_path(path, left_line_map),
-                right_line: find_line_for_path(path, right_line_map),
-            });
+            ctx.entries.push(DiffEntry::new(DiffType::ArrayReordered, path)
+                .with_lines(find_line_for_path(path, ctx.left_line_map), find_line_for_path(path, ctx.right_line_map)));
 
             // If show_nested_differences is enabled, we also want to show the specific differences
-            if options.show_nested_differences {
+            if ctx.options.show_nested_differences {
                 // We need to match items between the arrays to find corresponding elements
                 // This is a simplified approach that works for arrays with unique elements
                 // A more sophisticated approach would be needed for complex cases
 
                 // First, try to match items by their "id" field if they are objects
                 let mut matched_indices: Vec<Option<usize>> = vec![None; left.len()];
+                let (left_segments, right_segments) = match ctx.options.path_index_style {
+                    IndexStyle::Keyed => (element_segments(left), element_segments(right)),
+                    IndexStyle::Numeric => (Vec::new(), Vec::new()),
+                };
 
                 // Try to match items by their content
                 for (i, left_item) in left.iter().enumerate() {
                     for (j, right_item) in right.iter().enumerate() {
                         // Skip already matched items
-                        if matched_indices.iter().any(|idx| idx.map_or(false, |idx_val| idx_val == j)) {
+                        if matched_indices.contains(&Some(j)) {
                             continue;
                         }
 
                         // Try to match by id field if objects
                         if let (Value::Object(left_obj), Value::Object(right_obj)) = (left_item, right_item) {
-                            if let (Some(left_id), Some(right_id)) = (left_obj.get("id"), right_obj.get("id")) {
+                            if let (Some(left_id), Some(right_id)) = (left_obj.get(ELEMENT_KEY), right_obj.get(ELEMENT_KEY)) {
                                 if left_id == right_id {
                                     matched_indices[i] = Some(j);
                                     break;
@@ -421,19 +1584,22 @@ fn compare_arrays_with_lines(
                         if left_item != right_item {
                             // Items are matched but different, compare their contents
                             let item_path = format!("{}[{}]", path, i);
-                            compare_values_with_lines(left_item, right_item, &item_path, entries, options, left_line_map, right_line_map)?;
+                            let start = ctx.entries.len();
+                            compare_elements(left_item, right_item, &item_path, ctx)?;
+                            if i != j {
+                                ctx.relocate_right(start, &item_path, &format!("{}[{}]", path, j));
+                            }
+                            // Both sides must name the element alike
+                            let segment = left_segments.get(i).zip(right_segments.get(j))
+                                .and_then(|(left, right)| left.as_ref().filter(|_| left == right));
+                            ctx.key_element(start, &item_path, segment);
                         }
                     } else {
                         // Item in left not found in right
                         let item_path = format!("{}[{}]", path, i);
-                        entries.push(DiffEntry {
-                            diff_type: DiffType::Removed,
-                            path: item_path.clone(),
-                            old_value: Some(left_item.clone()),
-                            new_value: None,
-                            left_line: find_line_for_path(&item_path, left_line_map),
-                            right_line: find_line_for_path(&item_path, right_line_map),
-                        });
+                        ctx.entries.push(DiffEntry::removed(item_path.clone(), left_item.clone())
+                            .with_lines(find_line_for_path(&item_path, ctx.left_line_map), ctx.right_anchor(&item_path)));
+                        ctx.key_element(ctx.entries.len() - 1, &item_path, left_segments.get(i).and_then(Option::as_ref));
                     }
                 }
 
@@ -446,76 +1612,235 @@ fn compare_arrays_with_lines(
                     if !matched_right_indices.contains(&j) {
                         // Item in right not found in left
                         let item_path = format!("{}[{}]", path, j);
-                        entries.push(DiffEntry {
-                            diff_type: DiffType::Added,
-                            path: item_path.clone(),
-                            old_value: None,
-                            new_value: Some(right_item.clone()),
-                            left_line: find_line_for_path(&item_path, left_line_map),
-                            right_line: find_line_for_path(&item_path, right_line_map),
-                        });
+                        ctx.entries.push(DiffEntry::added(item_path.clone(), right_item.clone())
+                            .with_lines(ctx.left_anchor(&item_path), find_line_for_path(&item_path, ctx.right_line_map)));
+                        ctx.key_element(ctx.entries.len() - 1, &item_path, right_segments.get(j).and_then(Option::as_ref));
                     }
                 }
             }
         }
     } else {
         // For ordered comparison, we compare elements at the same indices
-        if options.identify_array_item_changes {
+        if ctx.options.identify_array_item_changes && ctx.options.detect_array_moves {
+            compare_ordered_with_moves(left, right, path, ctx)?;
+        } else if ctx.options.identify_array_item_changes {
             // Compare common elements
             let min_len = left.len().min(right.len());
 
             for i in 0..min_len {
                 let item_path = format!("{}[{}]", path, i);
-                compare_values_with_lines(&left[i], &right[i], &item_path, entries, options, left_line_map, right_line_map)?;
+                compare_elements(&left[i], &right[i], &item_path, ctx)?;
             }
 
             // Handle extra elements in left
-            for i in min_len..left.len() {
+            for (i, item) in left.iter().enumerate().skip(min_len) {
                 let item_path = format!("{}[{}]", path, i);
-                entries.push(DiffEntry {
-                    diff_type: DiffType::Removed,
-                    path: item_path.clone(),
-                    old_value: Some(left[i].clone()),
-                    new_value: None,
-                    left_line: find_line_for_path(&item_path, left_line_map),
-                    right_line: find_line_for_path(&item_path, right_line_map),
-                });
+                ctx.entries.push(DiffEntry::removed(item_path.clone(), item.clone())
+                    .with_lines(find_line_for_path(&item_path, ctx.left_line_map), ctx.right_anchor(&item_path)));
             }
 
             // Handle extra elements in right
-            for i in min_len..right.len() {
+            for (i, item) in right.iter().enumerate().skip(min_len) {
                 let item_path = format!("{}[{}]", path, i);
-                entries.push(DiffEntry {
-                    diff_type: DiffType::Added,
-                    path: item_path.clone(),
-                    old_value: None,
-                    new_value: Some(right[i].clone()),
-                    left_line: find_line_for_path(&item_path, left_line_map),
-                    right_line: find_line_for_path(&item_path, right_line_map),
-                });
+                ctx.entries.push(DiffEntry::added(item_path.clone(), item.clone())
+                    .with_lines(ctx.left_anchor(&item_path), find_line_for_path(&item_path, ctx.right_line_map)));
             }
         } else {
             // Mark the whole array as modified if there are any differences
             if left != right {
-                entries.push(DiffEntry {
-                    diff_type: DiffType::Modified,
-                    path: path.to_string(),
-                    old_value: Some(Value::Array(left.to_vec())),
-                    new_value: Some(Value::Array(right.to_vec())),
-                    left_line: find_line_for_path(path, left_line_map),
-                    right_line: find_line_for_path(path, right_line_map),
-                });
+                ctx.push_modified_array(path, left, right);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The segment naming each element by its key, as `IndexStyle::Keyed` reports it; `None`
+/// for elements without a key that can be written in a path, or holding the same key
+/// as another element
+fn element_segments(items: &[Value]) -> Vec<Option<String>> {
+    let segments: Vec<Option<String>> = items.iter()
+        .map(|item| item.get(ELEMENT_KEY).and_then(|id| keyed_segment(ELEMENT_KEY, id)))
+        .collect();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for segment in segments.iter().flatten() {
+        *counts.entry(segment).or_default() += 1;
+    }
+    segments.iter().map(|segment| segment.clone().filter(|segment| counts[segment.as_str()] == 1)).collect()
+}
+
+/// Compare two array elements found at `path`
+///
+/// The filters of the ignore rules are resolved against both elements: `[?id="u-42"]`
+/// stands for `path` when either element has that `id`, wherever each one is.
+fn compare_elements(left: &Value, right: &Value, path: &str, ctx: &mut CompareContext<'_>) -> Result<(), JsonDiffError> {
+    if ctx.filters.is_empty() {
+        return compare_values_with_lines(left, right, path, ctx);
+    }
+
+    let passed = ctx.filters.iter().copied().filter(|filter| filter.holds(left) || filter.holds(right)).collect();
+    ctx.elements.push((path.to_string(), passed));
+    let compared = compare_values_with_lines(left, right, path, ctx);
+    ctx.elements.pop();
+    compared
+}
+
+/// Most cells the table of common subsequence lengths of two arrays may have; past it
+/// their elements are compared by index, without looking for moves
+const MAX_MOVE_TABLE_CELLS: usize = 16_000_000;
+
+/// Compare ordered arrays, reporting elements that only changed their index as moved
+///
+/// Elements on the longest common subsequence of both arrays are unchanged. Of the
+/// rest, a left element equal to a right one is a move; what remains is compared
+/// positionally when both sides have an element at the index, and is removed or
+/// added otherwise. Arrays whose differing middles are too long for the subsequence,
+/// see [`MAX_MOVE_TABLE_CELLS`], are compared positionally with a warning.
+fn compare_ordered_with_moves(
+    left: &[Value],
+    right: &[Value],
+    path: &str,
+    ctx: &mut CompareContext<'_>,
+) -> Result<(), JsonDiffError> {
+    let (left_ids, right_ids) = element_ids(left, right);
+    let aligned = unaligned_indices(&left_ids, &right_ids, &mut ctx.budget)?;
+    let searched = aligned.is_some();
+    let (mut left_rest, mut right_rest) = aligned.unwrap_or_else(|| {
+        ctx.warnings.push(format!(
+            "Arrays at {} are too long to look for moved elements ({} and {} elements); comparing them by index",
+            path, left.len(), right.len()
+        ));
+        ((0..left.len()).collect(), (0..right.len()).collect())
+    });
+
+    let mut moves = Vec::new();
+    if searched {
+        // An element off the subsequence crosses it, so one equal to its counterpart
+        // moved even when both are at the same index
+        left_rest.retain(|&i| {
+            match right_rest.iter().position(|&j| left_ids[i] == right_ids[j]) {
+                Some(position) => {
+                    moves.push((i, right_rest.remove(position)));
+                    false
+                }
+                None => true,
             }
+        });
+    }
+
+    for (i, j) in moves {
+        let item_path = format!("{}[{}]", path, i);
+        let moved = DiffEntry { diff_type: DiffType::Moved, moved_to: Some(j), ..DiffEntry::removed(item_path.clone(), left[i].clone()) };
+        ctx.entries.push(moved.with_lines(
+            find_line_for_path(&item_path, ctx.left_line_map),
+            find_line_for_path(&format!("{}[{}]", path, j), ctx.right_line_map),
+        ));
+    }
+
+    for &i in &left_rest {
+        let item_path = format!("{}[{}]", path, i);
+        if right_rest.contains(&i) {
+            compare_elements(&left[i], &right[i], &item_path, ctx)?;
+        } else {
+            ctx.entries.push(DiffEntry::removed(item_path.clone(), left[i].clone())
+                .with_lines(find_line_for_path(&item_path, ctx.left_line_map), ctx.right_anchor(&item_path)));
         }
     }
 
+    for &j in right_rest.iter().filter(|j| !left_rest.contains(j)) {
+        let item_path = format!("{}[{}]", path, j);
+        ctx.entries.push(DiffEntry::added(item_path.clone(), right[j].clone())
+            .with_lines(ctx.left_anchor(&item_path), find_line_for_path(&item_path, ctx.right_line_map)));
+    }
+
     Ok(())
 }
 
+/// The elements of both arrays numbered so that equal elements, and only they, share
+/// a number
+fn element_ids(left: &[Value], right: &[Value]) -> (Vec<u32>, Vec<u32>) {
+    let mut ids: HashMap<String, u32> = HashMap::new();
+    let mut id_of = |value: &Value| {
+        let next = ids.len() as u32;
+        *ids.entry(format_canonical(value, &CanonicalOptions::COMPACT)).or_insert(next)
+    };
+    let left_ids = left.iter().map(&mut id_of).collect();
+    (left_ids, right.iter().map(id_of).collect())
+}
+
+/// Indices of the left and the right array, see [`unaligned_indices`]
+type Unaligned = (Vec<usize>, Vec<usize>);
+
+/// Indices of both arrays outside their longest common subsequence, the arrays given
+/// by [`element_ids`]; `None` when the table it takes is too large
+///
+/// The elements both arrays start and end with are set aside first. Each pair of the
+/// other elements weighed counts as two visited values of `budget`.
+fn unaligned_indices(left: &[u32], right: &[u32], budget: &mut Budget) -> Result<Option<Unaligned>, JsonDiffError> {
+    let prefix = left.iter().zip(right).take_while(|(l, r)| l == r).count();
+    let suffix = left[prefix..].iter().rev()
+        .zip(right[prefix..].iter().rev())
+        .take_while(|(l, r)| l == r)
+        .count();
+    let (left_middle, right_middle) = (&left[prefix..left.len() - suffix], &right[prefix..right.len() - suffix]);
+    let (n, m) = (left_middle.len(), right_middle.len());
+    if (n + 1).saturating_mul(m + 1) > MAX_MOVE_TABLE_CELLS {
+        return Ok(None);
+    }
+
+    // Length of the common subsequence of left_middle[i..] and right_middle[j..], at i * (m + 1) + j
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        budget.spend(2 * m as u64, 0)?;
+        for j in (0..m).rev() {
+            lengths[i * (m + 1) + j] = if left_middle[i] == right_middle[j] {
+                lengths[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let (mut left_rest, mut right_rest) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if left_middle[i] == right_middle[j] {
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1] {
+            left_rest.push(prefix + i);
+            i += 1;
+        } else {
+            right_rest.push(prefix + j);
+            j += 1;
+        }
+    }
+    left_rest.extend(prefix + i..prefix + n);
+    right_rest.extend(prefix + j..prefix + m);
+
+    Ok(Some((left_rest, right_rest)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
+    use crate::path::RegexOpts;
+    use crate::line_map::SourceSpan;
+
+    // The comparisons of these tests, whose results must hold `DiffResult::assert_invariants`
+    fn compare_json(left: &Value, right: &Value, options: &CompareOptions) -> Result<DiffResult, JsonDiffError> {
+        super::compare_json(left, right, options).inspect(DiffResult::assert_invariants)
+    }
+
+    fn compare_strings(left: &str, right: &str, options: &CompareOptions) -> Result<DiffResult, JsonDiffError> {
+        super::compare_strings(left, right, options).inspect(DiffResult::assert_invariants)
+    }
+
+    fn compare_files(left: impl AsRef<Path>, right: impl AsRef<Path>, options: &CompareOptions) -> Result<DiffResult, JsonDiffError> {
+        super::compare_files(left, right, options).inspect(DiffResult::assert_invariants)
+    }
 
     #[test]
     fn test_compare_equal_values() {
@@ -563,6 +1888,25 @@ mod tests {
         assert_eq!(result.entries[0].path, "$.age");
     }
 
+    #[test]
+    fn test_added_and_removed_anchor_lines() {
+        let left = "{\n  \"a\": 1,\n  \"user\": {\n    \"name\": \"x\"\n  },\n  \"gone\": true\n}";
+        let right = "{\n  \"first\": 0,\n  \"a\": 1,\n  \"user\": {\n    \"name\": \"x\",\n    \"age\": 3\n  }\n}";
+
+        let result = compare_strings(left, right, &CompareOptions::default()).unwrap();
+        let lines = |path: &str| {
+            let entry = result.iter().find(|e| e.path == path).unwrap();
+            (entry.left_line, entry.right_line)
+        };
+
+        // Added before every common sibling: anchored to the parent's opening line
+        assert_eq!(lines("$.first"), (Some(1), Some(2)));
+        // Added after the last common sibling of a nested object
+        assert_eq!(lines("$.user.age"), (Some(4), Some(6)));
+        // Removed after "user", whose value ends on line 7 of the right document
+        assert_eq!(lines("$.gone"), (Some(6), Some(7)));
+    }
+
     #[test]
     fn test_ignore_path() {
         let left = json!({"name": "John", "timestamp": "2023-01-01"});
@@ -585,7 +1929,7 @@ mod tests {
         // With ordered comparison
         let options = CompareOptions::default();
         let result = compare_json(&left, &right, &options).unwrap();
-        assert!(result.entries.len() > 0); // Should have differences
+        assert!(!result.entries.is_empty()); // Should have differences
 
         // With unordered comparison
         let mut unordered_options = CompareOptions::default();
@@ -597,6 +1941,159 @@ mod tests {
         assert_eq!(result.entries[0].path, "$.items");
     }
 
+    fn set_options(path: &str) -> CompareOptions {
+        CompareOptions { set_arrays: vec![JsonPath::new(path).unwrap()], ..CompareOptions::default() }
+    }
+
+    #[test]
+    fn test_set_array_added_and_removed() {
+        let left = json!({"tags": ["a", "b", 1, null]});
+        let right = json!({"tags": [true, null, "a", 2]});
+
+        let result = compare_json(&left, &right, &set_options("$.tags")).unwrap();
+        let entries: Vec<(DiffType, &str, Option<&Value>, Option<&Value>)> = result.entries.iter()
+            .map(|entry| (entry.diff_type.clone(), entry.path.as_str(), entry.old_value.as_ref(), entry.new_value.as_ref()))
+            .collect();
+        assert_eq!(entries, [
+            (DiffType::Removed, "$.tags[1]", Some(&json!("b")), None),
+            (DiffType::Removed, "$.tags[2]", Some(&json!(1)), None),
+            (DiffType::Added, "$.tags[0]", None, Some(&json!(true))),
+            (DiffType::Added, "$.tags[3]", None, Some(&json!(2))),
+        ]);
+
+        // The same values in another order are an equal set
+        let right = json!({"tags": [null, 1, "b", "a"]});
+        let result = compare_json(&left, &right, &set_options("$.tags")).unwrap();
+        assert!(result.entries.is_empty(), "{:?}", result.entries);
+    }
+
+    #[test]
+    fn test_set_array_collapses_duplicates() {
+        let left = json!({"tags": ["a", "b", "b", "a"]});
+        let right = json!({"tags": ["a", "a", "c", "c", "c"]});
+
+        let result = compare_json(&left, &right, &set_options("$.tags")).unwrap();
+        assert_eq!(result.entries.len(), 2, "{:?}", result.entries);
+        assert_eq!(result.entries[0].diff_type, DiffType::Removed);
+        assert_eq!(result.entries[0].path, "$.tags[1]");
+        assert_eq!(result.entries[1].diff_type, DiffType::Added);
+        assert_eq!(result.entries[1].path, "$.tags[2]");
+
+        let result = compare_json(&json!({"tags": ["a", "a"]}), &json!({"tags": ["a"]}), &set_options("$.tags")).unwrap();
+        assert!(result.entries.is_empty(), "{:?}", result.entries);
+    }
+
+    #[test]
+    fn test_set_array_with_objects_falls_back_to_unordered() {
+        let left = json!({"items": [{"id": 1}, {"id": 2}]});
+        let right = json!({"items": [{"id": 2}, {"id": 1}]});
+
+        let result = compare_json(&left, &right, &set_options("$.items")).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].diff_type, DiffType::ArrayReordered);
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].contains("$.items"), "{}", result.warnings[0]);
+    }
+
+    #[cfg(feature = "key-order")]
+    #[test]
+    fn test_key_order_changed() {
+        let options = CompareOptions { report_key_order: true, ..CompareOptions::default() };
+        let left: Value = serde_json::from_str(r#"{"a": 1, "inner": {"x": 1, "y": 2}}"#).unwrap();
+        let right: Value = serde_json::from_str(r#"{"inner": {"y": 2, "x": 1}, "a": 1}"#).unwrap();
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.entries.len(), 2, "{:?}", result.entries);
+        let root = result.entries.iter().find(|entry| entry.path == "$").unwrap();
+        assert_eq!(root.diff_type, DiffType::KeyOrderChanged);
+        assert_eq!(root.old_value, Some(json!(["a", "inner"])));
+        assert_eq!(root.new_value, Some(json!(["inner", "a"])));
+        assert!(result.entries.iter().any(|entry| entry.path == "$.inner" && entry.diff_type == DiffType::KeyOrderChanged));
+        assert!(result.warnings.is_empty());
+
+        // Off by default
+        assert!(compare_json(&left, &right, &CompareOptions::default()).unwrap().entries.is_empty());
+    }
+
+    #[cfg(feature = "key-order")]
+    #[test]
+    fn test_key_order_ignored_when_keys_differ() {
+        let options = CompareOptions { report_key_order: true, ..CompareOptions::default() };
+        let left: Value = serde_json::from_str(r#"{"a": 1, "b": 2}"#).unwrap();
+        let right: Value = serde_json::from_str(r#"{"c": 3, "a": 1}"#).unwrap();
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        let types: Vec<DiffType> = result.entries.iter().map(|entry| entry.diff_type.clone()).collect();
+        assert_eq!(types, [DiffType::Removed, DiffType::Added]);
+    }
+
+    #[cfg(not(feature = "key-order"))]
+    #[test]
+    fn test_key_order_needs_feature() {
+        let options = CompareOptions { report_key_order: true, ..CompareOptions::default() };
+        let result = compare_json(&json!({"a": 1, "b": 2}), &json!({"b": 2, "a": 1}), &options).unwrap();
+        assert!(result.entries.is_empty());
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].contains("key-order feature"), "{}", result.warnings[0]);
+    }
+
+    #[test]
+    fn test_compared_entries_validate() {
+        let options = CompareOptions {
+            ignore_paths: vec![JsonPath::new("$.id").unwrap()],
+            unordered_arrays: vec![JsonPath::new("$.tags").unwrap()],
+            tolerances: vec![(JsonPath::new("$.price").unwrap(), 0.1)],
+            detect_array_moves: true,
+            report_tolerated: true,
+            identify_array_item_changes: false,
+            max_value_size: Some(4),
+            ..CompareOptions::default()
+        };
+        let left = json!({"id": 1, "gone": true, "price": 1.0, "tags": ["a", "b"], "list": [1, 2, 3], "name": "x"});
+        let right = json!({"id": 2, "new": null, "price": 1.05, "tags": ["b", "a"], "list": [3, 1, 2], "name": "y"});
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert!(result.entries.len() >= 6, "{:?}", result.entries);
+        for entry in &result.entries {
+            entry.validate().unwrap_or_else(|e| panic!("{}: {:?}", e, entry));
+        }
+    }
+
+    #[test]
+    fn test_entry_spans() {
+        let left = "{\n  \"name\": \"a\",\n  \"config\": {\"retries\": 3},\n  \"tags\": [\"x\", {\"id\": 1}]\n}";
+        let right = "{\"name\": \"b\", \"config\": {\"retries\": 3, \"debug\": {\"level\": 2}}, \"tags\": [\"x\", 5]}";
+        let options = CompareOptions { record_spans: true, ..CompareOptions::default() };
+        let result = compare_strings(left, right, &options).unwrap();
+        let locate = |path: &str| result.locate(result.entries.iter().position(|entry| entry.path == path).unwrap());
+        let text = |document: &'static str, span: Option<SourceSpan>| {
+            let span = span.unwrap();
+            &document[span.byte_start..span.byte_end]
+        };
+        // Object members span their key too, so they parse as an object of one member
+        let member = |text: &str| serde_json::from_str::<Value>(&format!("{{{}}}", text)).unwrap();
+
+        // A scalar value
+        let (left_span, right_span) = locate("$.name");
+        assert_eq!(member(text(left, left_span)), json!({"name": "a"}));
+        assert_eq!(member(text(right, right_span)), json!({"name": "b"}));
+        assert_eq!(left_span.map(|span| (span.line, span.col)), Some((2, 3)));
+
+        // A nested object value, on the right only
+        let (left_span, right_span) = locate("$.config.debug");
+        assert_eq!(left_span, None);
+        assert_eq!(member(text(right, right_span)), json!({"debug": {"level": 2}}));
+
+        // An array element spans its value
+        let (left_span, right_span) = locate("$.tags[1]");
+        assert_eq!(serde_json::from_str::<Value>(text(left, left_span)).unwrap(), json!({"id": 1}));
+        assert_eq!(text(right, right_span), "5");
+
+        assert_eq!(result.locate(result.entries.len()), (None, None));
+        let result = compare_strings(left, right, &CompareOptions::default()).unwrap();
+        assert_eq!(result.locate(0), (None, None));
+    }
+
     #[test]
     fn test_unordered_array_with_nested_differences() {
         let left = json!({
@@ -677,6 +2174,112 @@ mod tests {
         assert!(nested_diff, "Should detect the nested theme change");
     }
 
+    #[test]
+    fn test_unordered_match_right_path() {
+        let left = r#"{
+  "users": [
+    {
+      "id": 1,
+      "settings": {
+        "theme": "dark"
+      }
+    },
+    {
+      "id": 2,
+      "settings": {
+        "theme": "light"
+      }
+    }
+  ]
+}"#;
+        let right = r#"{
+  "users": [
+    {
+      "id": 2,
+      "settings": {
+        "theme": "light"
+      }
+    },
+    {
+      "id": 1,
+      "settings": {
+        "theme": "light"
+      }
+    }
+  ]
+}"#;
+        let mut options = CompareOptions::default();
+        options.unordered_arrays.push(JsonPath::new("$.users").unwrap());
+        options.show_nested_differences = true;
+
+        let result = compare_strings(left, right, &options).unwrap();
+        let theme = result.entries.iter().find(|e| e.diff_type == DiffType::Modified).unwrap();
+        assert_eq!(theme.path, "$.users[0].settings.theme");
+        assert_eq!(theme.right_path.as_deref(), Some("$.users[1].settings.theme"));
+        assert_eq!((theme.left_line, theme.right_line), (Some(6), Some(12)));
+        assert!(theme.format_readable().contains("(right: $.users[1].settings.theme)"), "{}", theme.format_readable());
+
+        // Elements matched at their own index keep no right path
+        let result = compare_strings(left, &left.replace("dark", "blue"), &options).unwrap();
+        let theme = result.entries.iter().find(|e| e.diff_type == DiffType::Modified).unwrap();
+        assert_eq!(theme.path, "$.users[0].settings.theme");
+        assert_eq!(theme.right_path, None);
+    }
+
+    #[test]
+    fn test_keyed_paths() {
+        let left = json!({"products": [
+            {"id": "P1", "price": 10, "tags": [{"id": 1, "v": "a"}]},
+            {"id": "P2", "price": 20},
+            {"id": "a b]", "price": 30},
+            {"id": "gone", "price": 40}
+        ]});
+        let right = json!({"products": [
+            {"id": "a b]", "price": 31},
+            {"id": "P2", "price": 20},
+            {"id": "P1", "price": 11, "tags": [{"id": 1, "v": "b"}]},
+            {"id": "new", "price": 50}
+        ]});
+        let mut options = CompareOptions::default();
+        options.unordered_arrays.push(JsonPath::new("$.products").unwrap());
+        options.unordered_arrays.push(JsonPath::new("$.products[*].tags").unwrap());
+        options.show_nested_differences = true;
+        options.path_index_style = IndexStyle::Keyed;
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        let paths: Vec<(&str, Option<&str>)> = result.entries.iter()
+            .map(|e| (e.path.as_str(), e.index_path.as_deref()))
+            .collect();
+        assert_eq!(paths, vec![
+            ("$.products", None),
+            ("$.products[id=P1].price", Some("$.products[0].price")),
+            ("$.products[id=P1].tags", Some("$.products[0].tags")),
+            ("$.products[id=P1].tags[id=1].v", Some("$.products[0].tags[0].v")),
+            (r#"$.products[id="a b]"].price"#, Some("$.products[2].price")),
+            ("$.products[id=gone]", Some("$.products[3]")),
+            ("$.products[id=new]", Some("$.products[3]")),
+        ]);
+        // The right side keeps its numeric path
+        assert_eq!(result.entries[3].right_path.as_deref(), Some("$.products[2].tags[0].v"));
+        assert!(result.entries.iter().all(|e| e.validate().is_ok()));
+
+        // Ignore rules name elements by key, wherever they are
+        options.ignore_paths.push(JsonPath::new(r#"$.products[id="a b]"].price"#).unwrap());
+        options.ignore_paths.push(JsonPath::new("$.products[id=P1].tags").unwrap());
+        let result = compare_json(&left, &right, &options).unwrap();
+        let ignored: Vec<&str> = result.entries.iter().filter(|e| e.diff_type == DiffType::Ignored).map(|e| e.path.as_str()).collect();
+        assert_eq!(ignored, vec!["$.products[id=P1].tags", r#"$.products[id="a b]"].price"#]);
+        assert_eq!(result.entries.len(), 6);
+
+        // Elements sharing their key, or without one, keep their index
+        let left = json!([{"id": 1, "v": 1}, {"id": 1, "v": 2}, {"v": 3}]);
+        let right = json!([{"id": 1, "v": 1}, {"id": 1, "v": 5}, {"v": 4}]);
+        let mut options = CompareOptions { show_nested_differences: true, path_index_style: IndexStyle::Keyed, ..CompareOptions::default() };
+        options.unordered_arrays.push(JsonPath::new("$").unwrap());
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert!(result.entries.iter().all(|e| e.index_path.is_none()), "{:?}", result.entries);
+    }
+
     #[test]
     fn test_array_item_changes() {
         let left = json!({
@@ -713,8 +2316,10 @@ mod tests {
         assert!(!result.entries.iter().any(|e| e.path == "$.items[4]"));
 
         // With identify_array_item_changes = false
-        let mut whole_array_options = CompareOptions::default();
-        whole_array_options.identify_array_item_changes = false;
+        let whole_array_options = CompareOptions {
+            identify_array_item_changes: false,
+            ..CompareOptions::default()
+        };
 
         let result = compare_json(&left, &right, &whole_array_options).unwrap();
 
@@ -724,6 +2329,546 @@ mod tests {
         assert_eq!(result.entries[0].diff_type, DiffType::Modified);
     }
 
+    #[test]
+    fn test_tag_array_item_changes() {
+        let left = json!({"items": [{"value": "a"}, {"value": "b"}], "name": "n", "tags": ["x", "y"]});
+        let right = json!({"items": [{"value": "a"}, {"value": "c"}], "name": "m", "tags": ["x", "z"]});
+        let types = |options: &CompareOptions| -> Vec<(String, DiffType)> {
+            compare_json(&left, &right, options).unwrap().iter().map(|e| (e.path.clone(), e.diff_type.clone())).collect()
+        };
+
+        // Off by default
+        assert!(types(&CompareOptions::default()).iter().all(|(_, t)| *t == DiffType::Modified));
+
+        let mut options = CompareOptions { tag_array_item_changes: true, ..CompareOptions::default() };
+        assert_eq!(types(&options), vec![
+            ("$.items[1].value".to_string(), DiffType::Modified),
+            ("$.name".to_string(), DiffType::Modified),
+            ("$.tags[1]".to_string(), DiffType::ArrayItemChanged),
+        ]);
+
+        options.tag_nested_array_item_changes = true;
+        assert_eq!(types(&options), vec![
+            ("$.items[1].value".to_string(), DiffType::ArrayItemChanged),
+            ("$.name".to_string(), DiffType::Modified),
+            ("$.tags[1]".to_string(), DiffType::ArrayItemChanged),
+        ]);
+    }
+
+    #[test]
+    fn test_ignore_filtered_element_at_different_indices() {
+        // u-42 moved from index 1 to index 2; both logins changed
+        let left = json!({"users": [
+            {"id": "u-1", "lastLogin": "a"}, {"id": "u-42", "lastLogin": "b"}, {"id": "u-7", "lastLogin": "c"},
+        ]});
+        let right = json!({"users": [
+            {"id": "u-1", "lastLogin": "a"}, {"id": "u-7", "lastLogin": "c"}, {"id": "u-42", "lastLogin": "x"},
+        ]});
+        let mut options = CompareOptions::default();
+        options.ignore_paths.push(JsonPath::new(r#"$.users[?id="u-42"].lastLogin"#).unwrap());
+
+        // Positionally, u-42 is at index 1 on the left and at index 2 on the right
+        let result = compare_json(&left, &right, &options).unwrap();
+        let entries: Vec<(&str, DiffType)> = result.iter().map(|e| (e.path.as_str(), e.diff_type.clone())).collect();
+        assert_eq!(entries, vec![
+            ("$.users[1].id", DiffType::Modified),
+            ("$.users[1].lastLogin", DiffType::Ignored),
+            ("$.users[2].id", DiffType::Modified),
+            ("$.users[2].lastLogin", DiffType::Ignored),
+        ]);
+
+        // Matched by id, the element's own login is ignored and nothing else differs
+        options.unordered_arrays.push(JsonPath::new("$.users").unwrap());
+        options.show_nested_differences = true;
+        let result = compare_json(&left, &right, &options).unwrap();
+        let entries: Vec<(&str, DiffType)> = result.iter().map(|e| (e.path.as_str(), e.diff_type.clone())).collect();
+        assert_eq!(entries, vec![("$.users", DiffType::ArrayReordered), ("$.users[1].lastLogin", DiffType::Ignored)]);
+
+        // A number filter, on the element itself
+        let options = CompareOptions { ignore_paths: vec![JsonPath::new("$.users[?rank=2]").unwrap()], ..CompareOptions::default() };
+        let left = json!({"users": [{"rank": 1, "v": 1}, {"rank": 2, "v": 1}]});
+        let right = json!({"users": [{"rank": 1, "v": 1}, {"rank": 2.0, "v": 2}]});
+        let result = compare_json(&left, &right, &options).unwrap();
+        let entries: Vec<(&str, DiffType)> = result.iter().map(|e| (e.path.as_str(), e.diff_type.clone())).collect();
+        assert_eq!(entries, vec![("$.users[1]", DiffType::Ignored)]);
+    }
+
+    #[test]
+    fn test_explain_rules() {
+        let left = json!({"a": {"id": 1, "at": 1}, "b": [{"id": 1}], "c": 1});
+        let right = json!({"a": {"id": 2, "at": 2}, "b": [{"id": 2}], "c": 2});
+        let mut options = CompareOptions::default();
+        options.ignore_paths.push(JsonPath::with_regex_opts("$", r"\$\.a\.(id|at)", RegexOpts::default()).unwrap());
+        options.ignore_paths.push(JsonPath::new("$..id").unwrap());
+        options.ignore_paths.push(JsonPath::with_regex_opts("$", r"\$\.never", RegexOpts::default()).unwrap());
+
+        let plain = compare_json(&left, &right, &options).unwrap();
+        assert!(plain.rule_stats.is_empty());
+
+        options.explain_rules = true;
+        let explained = compare_json(&left, &right, &options).unwrap();
+        let entries = |result: &DiffResult| result.iter().map(|e| (e.path.clone(), e.diff_type.clone())).collect::<Vec<_>>();
+        assert_eq!(entries(&explained), entries(&plain));
+
+        // `$.a.id` is matched by the first rule only; each path is counted once
+        let matched: Vec<(&str, usize)> = explained.rule_stats.iter().map(|s| (s.rule.as_str(), s.matched)).collect();
+        assert_eq!(matched, vec![(r"^(?:\$\.a\.(id|at))$", 2), ("$..id", 2), (r"^(?:\$\.never)$", 0)]);
+        assert!(explained.rule_stats.iter().all(|s| s.tested == explained.rule_stats[0].tested));
+        assert_eq!(options.fingerprint(), CompareOptions { explain_rules: false, ..options.clone() }.fingerprint());
+    }
+
+    #[test]
+    fn test_group_related_replaced_subtree() {
+        let left = "{\n  \"config\": {\n    \"old_a\": 1,\n    \"old_b\": 2\n  },\n  \"name\": \"x\",\n  \"items\": [\n    {\"id\": 1}\n  ],\n  \"z\": 1\n}";
+        let right = "{\n  \"name\": \"y\",\n  \"config\": {\n    \"new_a\": 1,\n    \"new_b\": 2\n  },\n  \"items\": [\n    {\"id\": 2}\n  ],\n  \"z\": 2\n}";
+        let mut result = compare_strings(left, right, &CompareOptions::default()).unwrap();
+        let paths = |result: &DiffResult| result.iter().map(|e| e.path.clone()).collect::<Vec<_>>();
+
+        // Line order interleaves the replaced members
+        assert_eq!(paths(&result), vec![
+            "$.config.new_a", "$.config.new_b", "$.config.old_a", "$.config.old_b", "$.name", "$.items[0].id", "$.z",
+        ]);
+
+        result.group_related();
+        assert_eq!(paths(&result), vec![
+            "$.config.old_a", "$.config.old_b", "$.config.new_a", "$.config.new_b", "$.name", "$.z", "$.items[0].id",
+        ]);
+    }
+
+    #[test]
+    fn test_select_paths() {
+        let left = json!({
+            "metadata": {"name": "api", "labels": {"tier": "web"}},
+            "spec": {"replicas": 2, "containers": [
+                {"name": "app", "image": "app:1.0", "ports": [80]},
+                {"name": "sidecar", "image": "proxy:2.0"}
+            ]}
+        });
+        let right = json!({
+            "metadata": {"name": "api-v2"},
+            "spec": {"replicas": 3, "containers": [
+                {"name": "app", "image": "app:1.1", "ports": [80, 443]},
+                {"name": "sidecar", "image": "proxy:2.1"},
+                {"name": "logger", "image": "log:1.0"}
+            ]}
+        });
+        let paths = |options: &CompareOptions| -> Vec<(String, DiffType)> {
+            compare_json(&left, &right, options).unwrap().entries.into_iter().map(|e| (e.path, e.diff_type)).collect()
+        };
+
+        // Only the images of containers on both sides; the added container is beside the selection
+        let mut options = CompareOptions { select_paths: vec![JsonPath::new("$.spec.containers[*].image").unwrap()], ..CompareOptions::default() };
+        assert_eq!(paths(&options), [
+            ("$.spec.containers[0].image".to_string(), DiffType::Modified),
+            ("$.spec.containers[1].image".to_string(), DiffType::Modified),
+        ]);
+
+        // Ignore rules win inside the selection, and descendants of a selected path are kept
+        options.ignore_paths.push(JsonPath::new("$.spec.containers[1].image").unwrap());
+        options.select_paths.push(JsonPath::new("$.metadata").unwrap());
+        assert_eq!(paths(&options), [
+            ("$.metadata.labels".to_string(), DiffType::Removed),
+            ("$.metadata.name".to_string(), DiffType::Modified),
+            ("$.spec.containers[0].image".to_string(), DiffType::Modified),
+            ("$.spec.containers[1].image".to_string(), DiffType::Ignored),
+        ]);
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.selections, ["$.spec.containers[*].image", "$.metadata"]);
+        assert!(result.format_readable().contains("SELECT: $.spec.containers[*].image\nSELECT: $.metadata\n"));
+
+        assert_eq!(paths(&CompareOptions::default()).len(), 7);
+        assert!(CompareOptions::default().is_selected("$.anything"));
+    }
+
+    #[test]
+    fn test_string_similarity() {
+        let left = json!({"city": "Amsterdam", "count": 1, "name": "Jonh Smith", "note": "x".repeat(5000)});
+        let right = json!({"city": "Rotterdam", "count": 2, "name": "John Smith", "note": "y".repeat(5000)});
+        let near = |options: &CompareOptions| -> Vec<String> {
+            let result = compare_json(&left, &right, options).unwrap();
+            assert!(result.iter().all(|entry| entry.diff_type == DiffType::Modified));
+            result.iter().filter(|entry| entry.near_match).map(|entry| entry.path.clone()).collect()
+        };
+
+        // Without the option nothing is a near match
+        assert!(near(&CompareOptions::default()).is_empty());
+
+        // A typo is; "Amsterdam" to "Rotterdam" is 5 edits in 9. The long notes are past
+        // the limit of the edit distance and taken as rewritten
+        let options = CompareOptions { string_similarity: Some((0.8, Vec::new())), ..CompareOptions::default() };
+        assert_eq!(near(&options), ["$.name"]);
+        let options = CompareOptions { string_similarity: Some((0.4, Vec::new())), ..CompareOptions::default() };
+        assert_eq!(near(&options), ["$.city", "$.name"]);
+
+        // Only at the given paths
+        let options = CompareOptions { string_similarity: Some((0.4, vec![JsonPath::new("$.city").unwrap()])), ..CompareOptions::default() };
+        assert_eq!(near(&options), ["$.city"]);
+    }
+
+    #[test]
+    fn test_missing_file_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let (present, missing) = (dir.path().join("present.json"), dir.path().join("missing.json"));
+        std::fs::write(&present, "{\n  \"a\": 1,\n  \"b\": [true]\n}\n").unwrap();
+        let compare = |policy: MissingFilePolicy, left: &Path, right: &Path| {
+            compare_files(left, right, &CompareOptions { missing_file_policy: policy, ..CompareOptions::default() })
+        };
+        let summary = |result: &DiffResult| -> Vec<String> { result.iter().map(DiffEntry::format_readable).collect() };
+
+        // The default fails
+        assert!(matches!(compare(MissingFilePolicy::Error, &missing, &present), Err(JsonDiffError::FileNotFound { .. })));
+        assert!(matches!(compare(MissingFilePolicy::Error, &present, &missing), Err(JsonDiffError::FileNotFound { .. })));
+
+        // An empty object has none of the keys
+        let result = compare(MissingFilePolicy::TreatAsEmptyObject, &missing, &present).unwrap();
+        assert_eq!(summary(&result), ["[ADDED] $.a (L2): 1", "[ADDED] $.b (L3): [true]"]);
+        assert!(result.left_missing && !result.right_missing);
+        assert_eq!((result.left_sha256.as_ref(), result.left_size), (None, None));
+        assert!(result.format_readable().contains(&format!("LEFT: {} (missing)\n", missing.display())));
+        let result = compare(MissingFilePolicy::TreatAsEmptyObject, &present, &missing).unwrap();
+        assert_eq!(summary(&result), ["[REMOVED] $.a (L2): 1", "[REMOVED] $.b (L3): [true]"]);
+        assert!(result.right_missing && !result.left_missing);
+        assert_eq!(serde_json::to_value(&result).unwrap()["right_missing"], true);
+
+        // Null is one modification of the whole document
+        let result = compare(MissingFilePolicy::TreatAsNull, &missing, &present).unwrap();
+        assert_eq!(summary(&result), ["[MODIFIED] $ (L1): null -> {\"a\":1,\"b\":[true]}"]);
+        let result = compare(MissingFilePolicy::TreatAsNull, &present, &missing).unwrap();
+        assert_eq!(summary(&result), ["[MODIFIED] $ (L1): {\"a\":1,\"b\":[true]} -> null"]);
+        assert!(result.right_missing);
+
+        // Nothing to compare with both missing, and other errors are not covered
+        assert!(matches!(compare(MissingFilePolicy::TreatAsEmptyObject, &missing, &missing), Err(JsonDiffError::FileNotFound { .. })));
+        assert!(matches!(compare(MissingFilePolicy::TreatAsNull, dir.path(), &present), Err(JsonDiffError::IsDirectory { .. })));
+    }
+
+    #[test]
+    fn test_timings() {
+        let dir = std::env::temp_dir().join(format!("json-diff-timings-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (left, right) = (dir.join("left.json"), dir.join("right.json"));
+        let items = |offset: u64| Value::Array((0..5000).map(|i| json!({"id": i, "value": i * offset, "tags": ["a", "b"]})).collect());
+        std::fs::write(&left, serde_json::to_string_pretty(&items(1)).unwrap()).unwrap();
+        std::fs::write(&right, serde_json::to_string_pretty(&items(2)).unwrap()).unwrap();
+
+        let without = compare_files(&left, &right, &CompareOptions::default()).unwrap();
+        let options = CompareOptions { collect_timings: true, ..CompareOptions::default() };
+        let result = compare_files(&left, &right, &options).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(without.timings.is_none());
+
+        let timings = result.timings.unwrap();
+        let phases = [timings.read, timings.parse, timings.line_map, timings.compare, timings.sort];
+        assert!(phases.iter().all(|phase| !phase.is_zero()), "{:?}", timings);
+        assert_eq!(timings.format, None);
+        // Little besides the phases is timed, such as checksumming the inputs
+        let sum: Duration = phases.iter().sum();
+        assert!(sum <= timings.total && timings.total - sum < timings.total / 4, "{:?}", timings);
+
+        let json = serde_json::to_value(timings).unwrap();
+        assert!((json["total_ms"].as_f64().unwrap() - timings.total.as_secs_f64() * 1000.0).abs() < 1e-6);
+        assert!(json.get("format_ms").is_none());
+        assert_eq!(serde_json::from_value::<Timings>(json).unwrap().total.as_micros(), timings.total.as_micros());
+        // Timings are not part of what the comparison does
+        assert_eq!(options.fingerprint(), CompareOptions::default().fingerprint());
+    }
+
+    #[test]
+    fn test_compare_files_mixed_path_types() {
+        let dir = std::env::temp_dir().join(format!("json-diff-compare-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (left, right) = (dir.join("left.json"), dir.join("right.json"));
+        std::fs::write(&left, r#"{"a": 1}"#).unwrap();
+        std::fs::write(&right, r#"{"a": 2, "b": 3}"#).unwrap();
+        let right_str = right.to_str().unwrap();
+        let (left_string, right_string) = (left.display().to_string(), right.display().to_string());
+        let options = CompareOptions::default();
+
+        let results = [
+            compare_files(left.clone(), right_str, &options).unwrap(),
+            compare_files(left_string, &right, &options).unwrap(),
+            compare_files(left.as_path(), right_string, &options).unwrap(),
+        ];
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        for result in &results {
+            assert_eq!(result.entries.len(), 2);
+            assert_eq!(result.left_file.as_deref(), Some(left.as_path()));
+            assert_eq!(result.right_file.as_deref(), Some(right.as_path()));
+            assert_eq!((result.left_size, result.right_size), (Some(8), Some(16)));
+            assert_eq!(result.left_label.as_deref(), Some("left.json"));
+            assert_eq!(result.right_label.as_deref(), Some("right.json"));
+        }
+    }
+
+    #[test]
+    fn test_whole_array_values_capped() {
+        let options = CompareOptions {
+            identify_array_item_changes: false,
+            max_value_size: Some(64),
+            ..CompareOptions::default()
+        };
+
+        // Small arrays keep their values
+        let result = compare_json(&json!({"items": [1, 2]}), &json!({"items": [1, 3]}), &options).unwrap();
+        let entry = &result.entries[0];
+        assert_eq!(entry.to_string(), "~ $.items: [1,2] -> [1,3]");
+        assert_eq!(entry.array_summary, Some(ArraySummary { old_len: 2, new_len: 2, differing_indices: vec![1] }));
+
+        // Large ones are described by their length
+        let old: Vec<Value> = (0..500).map(Value::from).collect();
+        let mut new = old.clone();
+        new[3] = json!(-1);
+        new.extend((0..12).map(Value::from));
+        let result = compare_json(&json!({"items": old}), &json!({"items": new}), &options).unwrap();
+        let entry = &result.entries[0];
+        assert_eq!(entry.to_string(), "~ $.items: <array, 500 items> -> <array, 512 items>");
+        assert_eq!(entry.old_value, None);
+        let summary = entry.array_summary.as_ref().unwrap();
+        assert_eq!(summary.differing_indices.len(), ArraySummary::MAX_INDICES);
+        assert_eq!(summary.differing_indices[..2], [3, 500]);
+
+        // Without a limit the values are kept whatever their size
+        let unlimited = CompareOptions { max_value_size: None, ..options };
+        let result = compare_json(&json!({"items": old}), &json!({"items": new}), &unlimited).unwrap();
+        assert_eq!(result.entries[0].old_value, Some(json!(old)));
+    }
+
+    #[test]
+    fn test_array_moves() {
+        let options = CompareOptions { detect_array_moves: true, ..CompareOptions::default() };
+        let moves = |left: Value, right: Value| -> Vec<(String, Option<usize>)> {
+            let result = compare_json(&left, &right, &options).unwrap();
+            assert!(result.entries.iter().all(|e| e.diff_type == DiffType::Moved), "{:?}", result.entries);
+            result.entries.iter().map(|e| (e.path.clone(), e.moved_to)).collect()
+        };
+
+        // A single element shifted to the end
+        let shifted = moves(json!({"items": [1, 2, 3, 4, 5]}), json!({"items": [2, 3, 4, 5, 1]}));
+        assert_eq!(shifted, vec![("$.items[0]".to_string(), Some(4))]);
+
+        // Swapping neighbours moves one of them
+        let swapped = moves(json!([{"id": 1}, {"id": 2}, {"id": 3}]), json!([{"id": 2}, {"id": 1}, {"id": 3}]));
+        assert_eq!(swapped.len(), 1);
+        let moved = compare_json(&json!(["a", "b"]), &json!(["b", "a"]), &options).unwrap();
+        assert_eq!(moved.entries[0].to_string(), "> $[0] → [1]");
+
+        // Without the option the shift is four modifications and an addition
+        let result = compare_json(&json!([1, 2, 3, 4, 5]), &json!([2, 3, 4, 5, 1]), &CompareOptions::default()).unwrap();
+        assert!(result.entries.iter().all(|e| e.diff_type == DiffType::Modified));
+    }
+
+    #[test]
+    fn test_array_moves_rebuild_the_right_array() {
+        let options = CompareOptions { detect_array_moves: true, ..CompareOptions::default() };
+        // The right array from the left one and the entries alone: moved, added and
+        // modified elements at their indices, the others in their left order in the slots left
+        let rebuild = |left: &[Value], right_len: usize, entries: &[DiffEntry]| -> Vec<Value> {
+            let mut placed: Vec<Option<Value>> = vec![None; right_len];
+            let mut gone = HashSet::new();
+            let index = |path: &str| path.trim_end_matches(']').rsplit('[').next().unwrap().parse::<usize>().unwrap();
+            for entry in entries {
+                match entry.diff_type {
+                    DiffType::Moved => {
+                        gone.insert(index(&entry.path));
+                        placed[entry.moved_to.unwrap()] = entry.old_value.clone();
+                    }
+                    DiffType::Added => placed[index(&entry.path)] = entry.new_value.clone(),
+                    DiffType::Modified => {
+                        gone.insert(index(&entry.path));
+                        placed[index(&entry.path)] = entry.new_value.clone();
+                    }
+                    DiffType::Removed => {
+                        gone.insert(index(&entry.path));
+                    }
+                    _ => panic!("unexpected entry {:?}", entry),
+                }
+            }
+            let mut kept = left.iter().enumerate().filter(|(i, _)| !gone.contains(i)).map(|(_, value)| value.clone());
+            placed.into_iter().map(|slot| slot.or_else(|| kept.next())).collect::<Option<_>>().unwrap()
+        };
+
+        let cases = [
+            (json!([0, 1, 2]), json!([2, 1, 0])),
+            (json!([1, 3, 2, {"v": 1}, {"v": 0}]), json!([1, {"v": 0}, {"v": 2}, {"v": 1}, 0])),
+            (json!(["a", "b", "c", "d"]), json!(["d", "c", "b", "a"])),
+            (json!([1, 2, 1, 2]), json!([2, 1, 2, 1])),
+        ];
+        for (left, right) in cases {
+            let result = compare_json(&left, &right, &options).unwrap();
+            let (left, right) = (left.as_array().unwrap(), right.as_array().unwrap());
+            assert_eq!(&rebuild(left, right.len(), &result.entries), right, "{:?}", result.entries);
+        }
+
+        // Every element the reversal moves is reported
+        let result = compare_json(&json!({"a": [0, 1, 2]}), &json!({"a": [2, 1, 0]}), &options).unwrap();
+        assert_eq!(result.entries.len(), 2);
+        assert!(result.entries.iter().all(|e| e.diff_type == DiffType::Moved));
+    }
+
+    #[test]
+    fn test_array_moves_too_long_to_align() {
+        let options = CompareOptions { detect_array_moves: true, ..CompareOptions::default() };
+        // The shared start and end are set aside, so only the middle takes a table
+        let mut right: Vec<i64> = (0..20_000).collect();
+        right[10_000..10_010].rotate_left(1);
+        let result = compare_json(&json!((0..20_000).collect::<Vec<i64>>()), &json!(right), &options).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!((result.entries[0].path.as_str(), result.entries[0].moved_to), ("$[10000]", Some(10_009)));
+        assert!(result.warnings.is_empty());
+
+        // Past the table limit the elements are compared by index instead
+        let (left, right) = (json!((0..5_000).collect::<Vec<i64>>()), json!((0..5_000).rev().collect::<Vec<i64>>()));
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.entries.len(), 5_000);
+        assert!(result.entries.iter().all(|e| e.diff_type == DiffType::Modified));
+        assert_eq!(result.warnings, ["Arrays at $ are too long to look for moved elements (5000 and 5000 elements); comparing them by index"]);
+    }
+
+    #[test]
+    fn test_array_moved_and_modified_not_paired() {
+        let options = CompareOptions { detect_array_moves: true, ..CompareOptions::default() };
+        let left = json!({"items": [{"id": 1, "v": "a"}, {"id": 2}, {"id": 3}]});
+        let right = json!({"items": [{"id": 2}, {"id": 3}, {"id": 1, "v": "b"}]});
+        let result = compare_json(&left, &right, &options).unwrap();
+
+        let summary: Vec<_> = result.entries.iter().map(|e| (e.diff_type.clone(), e.path.as_str())).collect();
+        assert_eq!(summary, vec![(DiffType::Removed, "$.items[0]"), (DiffType::Added, "$.items[2]")]);
+
+        // Elements changed in place are still compared field by field
+        let result = compare_json(&json!([{"id": 1, "v": "a"}, 2]), &json!([{"id": 1, "v": "b"}, 2]), &options).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!((result.entries[0].diff_type.clone(), result.entries[0].path.as_str()), (DiffType::Modified, "$[0].v"));
+    }
+
+    #[test]
+    fn test_asymmetric_roots() {
+        let left = "{\n  \"data\": {\n    \"result\": {\n      \"id\": 1,\n      \"name\": \"a\"\n    }\n  }\n}";
+        let right = "{\n  \"payload\": {\n    \"id\": 1,\n    \"name\": \"b\",\n    \"extra\": true\n  }\n}";
+
+        let options = CompareOptions {
+            left_root: Some(JsonPath::new("$.data.result").unwrap()),
+            right_root: Some(JsonPath::new("$.payload").unwrap()),
+            ignore_paths: vec![JsonPath::new("$.extra").unwrap()],
+            ..CompareOptions::default()
+        };
+        let result = compare_strings(left, right, &options).unwrap();
+
+        // Paths are relative to the roots, lines those of the original documents
+        let entries: Vec<_> = result.iter().map(|e| (e.path.as_str(), e.left_line, e.right_line)).collect();
+        // The ignored member only on the right falls back to the left root's line
+        assert_eq!(entries, vec![("$.extra", Some(3), Some(5)), ("$.name", Some(5), Some(4))]);
+    }
+
+    fn mapping(left: &str, right: &str) -> CompareOptions {
+        CompareOptions {
+            path_mappings: vec![PathMapping { left: JsonPath::new(left).unwrap(), right: JsonPath::new(right).unwrap() }],
+            ..CompareOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_path_mapping_rename() {
+        let left = "{\n  \"id\": 1,\n  \"total_cents\": 1200\n}";
+        let right = "{\n  \"id\": 1,\n  \"amount\": {\n    \"cents\": 1250\n  }\n}";
+
+        let result = compare_strings(left, right, &mapping("$.total_cents", "$.amount.cents")).unwrap();
+        assert_eq!(result.entries.len(), 1, "{:?}", result.entries);
+        let entry = &result.entries[0];
+        assert_eq!((entry.diff_type.clone(), entry.path.as_str()), (DiffType::Modified, "$.total_cents"));
+        assert_eq!(entry.mapped_to.as_deref(), Some("$.amount.cents"));
+        assert_eq!((entry.left_line, entry.right_line), (Some(3), Some(4)));
+        assert_eq!(entry.to_string(), "~ $.total_cents => $.amount.cents (L3:L4): 1200 -> 1250");
+
+        // Equal mapped values leave nothing, and the emptied object is not reported either
+        let right = r#"{"id": 1, "amount": {"cents": 1200}}"#;
+        let result = compare_strings(left, right, &mapping("$.total_cents", "$.amount.cents")).unwrap();
+        assert!(result.entries.is_empty(), "{:?}", result.entries);
+    }
+
+    #[test]
+    fn test_path_mapping_nested() {
+        let left = json!({"items": [{"sku": "a", "price": {"net": 10}}, {"sku": "b", "price": {"net": 20}}]});
+        let right = json!({"items": [{"sku": "a", "cost": {"net": 10}}, {"sku": "b", "cost": {"net": 25, "tax": 5}}]});
+
+        let result = compare_json(&left, &right, &mapping("$.items[*].price", "$.items[*].cost")).unwrap();
+        let entries: Vec<_> = result.iter().map(|e| (e.diff_type.clone(), e.path.as_str(), e.mapped_to.as_deref())).collect();
+        assert_eq!(entries, vec![
+            (DiffType::Modified, "$.items[1].price.net", Some("$.items[1].cost.net")),
+            (DiffType::Added, "$.items[1].price.tax", Some("$.items[1].cost.tax")),
+        ]);
+    }
+
+    #[test]
+    fn test_path_mapping_absent_right() {
+        let left = json!({"total_cents": 1200, "currency": "EUR"});
+        let right = json!({"currency": "EUR"});
+
+        let result = compare_json(&left, &right, &mapping("$.total_cents", "$.amount.cents")).unwrap();
+        let entries: Vec<_> = result.iter().map(|e| (e.diff_type.clone(), e.path.as_str(), e.mapped_to.as_deref())).collect();
+        assert_eq!(entries, vec![(DiffType::Removed, "$.total_cents", Some("$.amount.cents"))]);
+
+        // Only on the right, the value is added at the left path
+        let result = compare_json(&right, &json!({"amount": {"cents": 5}, "currency": "EUR"}), &mapping("$.total_cents", "$.amount.cents")).unwrap();
+        let entries: Vec<_> = result.iter().map(|e| (e.diff_type.clone(), e.path.as_str())).collect();
+        assert_eq!(entries, vec![(DiffType::Added, "$.total_cents")]);
+    }
+
+    #[test]
+    fn test_ignored_path_reported_once() {
+        let path = |path: &str| JsonPath::new(path).unwrap();
+        // Both mappings reach the ignored left value, which is reported once, not failing
+        // the comparison as a repeated entry
+        let options = CompareOptions {
+            ignore_paths: vec![path("$.total")],
+            path_mappings: vec![
+                PathMapping { left: path("$.total"), right: path("$.amount") },
+                PathMapping { left: path("$.total"), right: path("$.sum") },
+            ],
+            ..CompareOptions::default()
+        };
+        let result = compare_json(&json!({"total": 1}), &json!({"amount": 2, "sum": 3}), &options).unwrap();
+        let entries: Vec<_> = result.iter().map(|e| (e.diff_type.clone(), e.path.as_str())).collect();
+        assert_eq!(entries, vec![(DiffType::Ignored, "$.total")]);
+        result.assert_invariants();
+
+        // A subtree matched by overlapping rules, its members missing on either side or
+        // changed, is reported once at its root
+        let options = CompareOptions {
+            ignore_paths: vec![path("$.meta"), path("$.meta.*"), path("$.*.ts"), path("$.items[*].ts")],
+            unordered_arrays: vec![path("$.items")],
+            show_nested_differences: true,
+            ..CompareOptions::default()
+        };
+        let left = json!({"items": [{"id": 1, "ts": 1}, {"id": 2, "ts": 1}], "meta": {"host": "a", "ts": 1}});
+        let right = json!({"items": [{"id": 2, "ts": 2}, {"id": 1, "ts": 3}], "meta": {"region": "eu", "ts": 2}});
+        let result = compare_json(&left, &right, &options).unwrap();
+        let ignored: Vec<&str> = result.iter_type(DiffType::Ignored).map(|e| e.path.as_str()).collect();
+        assert_eq!(ignored, ["$.items[0].ts", "$.items[1].ts", "$.meta"]);
+        result.assert_invariants();
+    }
+
+    #[test]
+    fn test_invalid_path_mapping() {
+        let err = compare_json(&json!({}), &json!({}), &mapping("$.items[*].id", "$.id")).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid mapping $.items[*].id -> $.id: both paths must have the same number of [*]");
+        assert!(compare_json(&json!({}), &json!({}), &mapping("$.items[0]", "$.first")).is_err());
+    }
+
+    #[test]
+    fn test_missing_root() {
+        let options = CompareOptions {
+            right_root: Some(JsonPath::new("$.payload").unwrap()),
+            ..CompareOptions::default()
+        };
+        let error = compare_json(&json!({"payload": 1}), &json!({"data": 1}), &options).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid right root $.payload: no value at this path");
+
+        let options = CompareOptions { left_root: Some(JsonPath::new("$.items[*]").unwrap()), ..CompareOptions::default() };
+        let error = compare_json(&json!({"items": [1]}), &json!({}), &options).unwrap_err();
+        assert!(error.to_string().starts_with("Invalid left root $.items[*]: a root must name a single value"));
+    }
+
     #[test]
     fn test_nested_objects() {
         let left = json!({
@@ -753,4 +2898,539 @@ mod tests {
         assert_eq!(result.entries[0].diff_type, DiffType::Modified);
         assert_eq!(result.entries[0].path, "$.user.address.city");
     }
+
+    #[test]
+    fn test_numeric_tolerance() {
+        let left = json!({"price": 10.00, "count": 5});
+        let right = json!({"price": 10.004, "count": 6});
+
+        let mut options = CompareOptions::default();
+        options.tolerances.push((JsonPath::new("$.price").unwrap(), 0.01));
+        options.tolerances.push((JsonPath::new("$.count").unwrap(), 0.5));
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].path, "$.count");
+    }
+
+    #[test]
+    fn test_report_tolerated() {
+        let left = json!({"amount": "1,50", "count": 5, "id": "aGk=", "price": 10.0, "ratio": 1});
+        let right = json!({"amount": 1.5, "count": 6, "id": "aGk", "price": 10.004, "ratio": 1.0});
+        let mut options = CompareOptions {
+            numeric_equivalence: true,
+            number_locale: NumberLocale::Comma,
+            ..CompareOptions::default()
+        };
+        options.tolerances.push((JsonPath::new("$.price").unwrap(), 0.01));
+        options.numeric_strings.push(JsonPath::new("$.amount").unwrap());
+        options.decoders.push((JsonPath::new("$.id").unwrap(), Codec::Base64));
+
+        // Off by default: only the real difference is reported
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.iter().map(|e| e.path.as_str()).collect::<Vec<_>>(), vec!["$.count"]);
+
+        options.report_tolerated = true;
+        let result = compare_json(&left, &right, &options).unwrap();
+        let entries: Vec<(&str, DiffType, Option<&str>)> = result.iter()
+            .map(|e| (e.path.as_str(), e.diff_type.clone(), e.reason.as_deref()))
+            .collect();
+        assert_eq!(entries, vec![
+            ("$.amount", DiffType::Tolerated, Some("numeric string")),
+            ("$.count", DiffType::Modified, None),
+            ("$.id", DiffType::Tolerated, Some("decoded base64")),
+            ("$.price", DiffType::Tolerated, Some("tolerance 0.01")),
+            ("$.ratio", DiffType::Tolerated, Some("numeric_equivalence")),
+        ]);
+
+        let price = result.iter().find(|e| e.path == "$.price").unwrap();
+        assert_eq!(price.old_value, Some(json!(10.0)));
+        assert_eq!(price.new_value, Some(json!(10.004)));
+        assert_eq!(price.to_string(), "≈ $.price: 10.0 -> 10.004 (tolerance 0.01)");
+        assert!(!price.diff_type.is_difference());
+    }
+
+    #[test]
+    fn test_allowed_transitions() {
+        let mut options = CompareOptions::default();
+        options.allowed_transitions.push(AllowedTransition {
+            path: JsonPath::new("$.orders[*].status").unwrap(),
+            from: vec!["PENDING".to_string()],
+            to: vec!["PROCESSING".to_string(), "QUEUED".to_string()],
+        });
+        options.allowed_transitions.push(AllowedTransition {
+            path: JsonPath::new("$.retries").unwrap(),
+            from: vec!["*".to_string()],
+            to: vec!["0".to_string()],
+        });
+
+        let left = json!({"orders": [{"status": "PENDING"}, {"status": "PENDING"}, {"status": "PROCESSING"}], "retries": 3});
+        let right = json!({"orders": [{"status": "QUEUED"}, {"status": "FAILED"}, {"status": "PENDING"}], "retries": 0});
+        let result = compare_json(&left, &right, &options).unwrap();
+        let entry = |path: &str| result.iter().find(|e| e.path == path).unwrap();
+
+        // Allowed: the values are kept and the rule is recorded
+        let allowed = entry("$.orders[0].status");
+        assert_eq!(allowed.diff_type, DiffType::Ignored);
+        assert_eq!(allowed.new_value, Some(json!("QUEUED")));
+        assert_eq!(allowed.reason.as_deref(), Some(r#"allowed transition ["PENDING"] -> ["PROCESSING", "QUEUED"]"#));
+        // Disallowed target
+        assert_eq!(entry("$.orders[1].status").diff_type, DiffType::Modified);
+        // Reverse direction is not allowed
+        assert_eq!(entry("$.orders[2].status").diff_type, DiffType::Modified);
+        assert_eq!(entry("$.orders[2].status").reason, None);
+        // Any value to a number, compared by its JSON text
+        assert_eq!(entry("$.retries").diff_type, DiffType::Ignored);
+    }
+
+    #[test]
+    fn test_embedded_json() {
+        let left = json!({"payload": "{\"user\": {\"id\": 1, \"name\": \"John\"}}"});
+        let right = json!({"payload": "{\"user\": {\"id\": 2, \"name\": \"John\"}}"});
+
+        let mut options = CompareOptions::default();
+        options.parse_embedded_json.push(JsonPath::new("$.payload").unwrap());
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].diff_type, DiffType::Modified);
+        assert_eq!(result.entries[0].path, "$.payload(json).user.id");
+        assert_eq!(result.entries[0].old_value, Some(json!(1)));
+
+        // Without the rule the whole string is reported
+        let result = compare_json(&left, &right, &CompareOptions::default()).unwrap();
+        assert_eq!(result.entries[0].path, "$.payload");
+    }
+
+    #[test]
+    fn test_embedded_json_not_json() {
+        let left = json!({"payload": "not json"});
+        let right = json!({"payload": "{\"a\": 1}"});
+
+        let mut options = CompareOptions::default();
+        options.parse_embedded_json.push(JsonPath::new("$.payload").unwrap());
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].path, "$.payload");
+        assert_eq!(result.entries[0].old_value, Some(json!("not json")));
+    }
+
+    #[test]
+    fn test_nested_embedded_json_auto() {
+        let inner_left = json!({"audit": "{\"by\": \"alice\"}"}).to_string();
+        let inner_right = json!({"audit": "{\"by\": \"bob\"}"}).to_string();
+        let left = json!({"blob": inner_left});
+        let right = json!({"blob": inner_right});
+
+        let options = CompareOptions {
+            auto_parse_embedded_json: true,
+            ..CompareOptions::default()
+        };
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].path, "$.blob(json).audit(json).by");
+    }
+
+    #[test]
+    fn test_decoders() {
+        let mut options = CompareOptions::default();
+        options.decoders.push((JsonPath::new("$.blob").unwrap(), Codec::Base64));
+        options.decoders.push((JsonPath::new("$.query").unwrap(), Codec::Url));
+
+        // Equal after decoding: padding differs, and one side stores the raw query
+        let left = json!({"blob": "aGVsbG8=", "query": "a%20b%26c"});
+        let right = json!({"blob": "aGVsbG8", "query": "a b&c"});
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert!(result.entries.is_empty());
+        assert!(result.warnings.is_empty());
+
+        // Different after decoding reports the decoded text
+        let right = json!({"blob": "aGVsbG8gd29ybGQ=", "query": "a b&c"});
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].old_value, Some(json!("hello")));
+        assert_eq!(result.entries[0].new_value, Some(json!("hello world")));
+
+        // Binary content is reported as hex previews
+        let left = json!({"blob": "AP8="});
+        let right = json!({"blob": "AP4="});
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.entries[0].old_value, Some(json!("hex:00ff (2 bytes)")));
+        assert_eq!(result.entries[0].new_value, Some(json!("hex:00fe (2 bytes)")));
+    }
+
+    #[test]
+    fn test_decoder_invalid_base64() {
+        let mut options = CompareOptions::default();
+        options.decoders.push((JsonPath::new("$.blob").unwrap(), Codec::Base64));
+
+        let left = json!({"blob": "aGVsbG8="});
+        let right = json!({"blob": "not base64!"});
+        let result = compare_json(&left, &right, &options).unwrap();
+
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].contains("Invalid base64 value at $.blob in the right document"));
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].old_value, Some(json!("hello")));
+        assert_eq!(result.entries[0].new_value, Some(json!("not base64!")));
+    }
+
+    #[test]
+    fn test_decoded_embedded_json() {
+        let mut options = CompareOptions::default();
+        options.decoders.push((JsonPath::new("$.blob").unwrap(), Codec::Base64));
+        options.parse_embedded_json.push(JsonPath::new("$.blob").unwrap());
+
+        // {"a":1} and {"a":2}
+        let left = json!({"blob": "eyJhIjoxfQ=="});
+        let right = json!({"blob": "eyJhIjoyfQ=="});
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].path, "$.blob(json).a");
+    }
+
+    #[test]
+    fn test_number_locale_parse() {
+        assert_eq!(NumberLocale::Point.parse("1,234.50"), Some(1234.5));
+        assert_eq!(NumberLocale::Point.parse(" -1 234 567.5 "), Some(-1234567.5));
+        assert_eq!(NumberLocale::Comma.parse("1.234,50"), Some(1234.5));
+        assert_eq!(NumberLocale::Comma.parse("0,5"), Some(0.5));
+        assert_eq!(NumberLocale::Point.parse("NaN"), None);
+        assert_eq!(NumberLocale::Point.parse("12 apples"), None);
+    }
+
+    #[test]
+    fn test_numeric_strings() {
+        let left = json!({"a": "1,234.50", "b": "1234.5", "c": "10.00", "d": "7"});
+        let right = json!({"a": 1234.5, "b": "1,234.5", "c": "10.004", "d": "8"});
+
+        let mut options = CompareOptions::default();
+        options.numeric_strings.push(JsonPath::with_regex("$", r"^\$\.[a-d]$").unwrap());
+        options.tolerances.push((JsonPath::new("$.c").unwrap(), 0.01));
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].path, "$.d");
+        assert_eq!(result.entries[0].old_value, Some(json!("7")));
+
+        // Without the rule the strings differ
+        let result = compare_json(&left, &right, &CompareOptions::default()).unwrap();
+        assert_eq!(result.entries.len(), 4);
+    }
+
+    #[test]
+    fn test_numeric_equivalence() {
+        let left = r#"{"zero": -0, "float_zero": -0.0, "one": 1, "big": 9007199254740993}"#;
+        let right = r#"{"zero": 0, "float_zero": 0, "one": 1.0, "big": 9007199254740992}"#;
+
+        let result = compare_strings(left, right, &CompareOptions::default()).unwrap();
+        assert_eq!(result.entries.len(), 4);
+
+        let options = CompareOptions { numeric_equivalence: true, ..CompareOptions::default() };
+        let result = compare_strings(left, right, &options).unwrap();
+        // Integers too large for f64 are still told apart
+        let paths: Vec<&str> = result.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["$.big"]);
+    }
+
+    #[test]
+    fn test_lenient_non_finite_numbers() {
+        let left = "{\n  \"a\": NaN,\n  \"b\": NaN,\n  \"c\": -Infinity\n}";
+        let right = "{\n  \"a\": NaN,\n  \"b\": 1.5,\n  \"c\": -Infinity\n}";
+
+        assert!(compare_strings(left, right, &CompareOptions::default()).is_err());
+
+        let options = CompareOptions { lenient: true, ..CompareOptions::default() };
+        let result = compare_strings(left, right, &options).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].diff_type, DiffType::Modified);
+        assert_eq!(result.entries[0].to_string(), "~ $.b (L3:L3): NaN -> 1.5");
+    }
+
+    #[test]
+    fn test_unparseable_names_each_side() {
+        let options = CompareOptions::default();
+        match compare_strings("{\"a\": 1}", "{\"a\": ", &options) {
+            Err(JsonDiffError::Unparseable { left: None, right: Some(error) }) => assert!(error.contains("EOF"), "{}", error),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let error = compare_strings("[1,", "nope", &options).unwrap_err();
+        let message = error.to_string();
+        assert!(message.starts_with("Failed to parse JSON: left: "), "{}", message);
+        assert!(message.contains("; right: expected ident"), "{}", message);
+    }
+
+    #[test]
+    fn test_rule_usage() {
+        let left = r#"{"id": 1, "tags": ["a", "b"], "meta": {"ts": 1}, "items": [{"sku": "x"}]}"#;
+        let right = r#"{"id": 2, "tags": ["a", "b"], "meta": {"ts": 2}, "items": [{"sku": "y"}]}"#;
+        let options = CompareOptions {
+            ignore_paths: ["$.meta.ts", "$.legacy.id", "$.items[*].sku"].map(|path| JsonPath::new(path).unwrap()).to_vec(),
+            unordered_arrays: vec![JsonPath::new("$.tags").unwrap(), JsonPath::new("$.labels").unwrap()],
+            ..CompareOptions::default()
+        };
+        let result = compare_strings(left, right, &options).unwrap();
+        let usage = options.rule_usage(left, right, &result).unwrap();
+        let usage: Vec<(&str, &str, usize, usize)> = usage.iter()
+            .map(|usage| (if usage.is_absent() { "absent" } else { "present" }, usage.kind.as_str(), usage.entries, usage.paths))
+            .collect();
+        assert_eq!(usage, [
+            ("present", "ignore", 1, 2),
+            ("absent", "ignore", 0, 0),
+            ("present", "ignore", 1, 2),
+            // Equal arrays, so nothing to report, but there
+            ("present", "unordered", 0, 2),
+            ("absent", "unordered", 0, 0),
+        ]);
+
+        // Beneath the roots, and with a missing side as the document standing in for it
+        let rooted = CompareOptions { left_root: Some(JsonPath::new("$.meta").unwrap()), right_root: Some(JsonPath::new("$.meta").unwrap()), ..options.clone() };
+        let result = compare_strings(left, right, &rooted).unwrap();
+        assert_eq!(rooted.rule_usage(left, right, &result).unwrap()[0].paths, 0);
+        let missing = CompareOptions { missing_file_policy: MissingFilePolicy::TreatAsEmptyObject, ..options.clone() };
+        let mut result = compare_strings(left, "{}", &missing).unwrap();
+        result.right_missing = true;
+        assert_eq!(missing.rule_usage(left, "", &result).unwrap()[0].paths, 1);
+        assert!(matches!(options.rule_usage(left, "{", &result), Err(JsonDiffError::Unparseable { left: None, right: Some(_) })));
+    }
+
+    #[test]
+    fn test_text_fallback() {
+        let (left, right) = ("{\n  \"a\": 1,\n  \"b\": 2\n}", "{\n  \"a\": 1,\n  \"b\": 3,\n}");
+        let options = CompareOptions { text_fallback: true, ..CompareOptions::default() };
+        let result = compare_strings(left, right, &options).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].to_string(), "~ <line 3> (L3:L3): \"  \\\"b\\\": 2\" -> \"  \\\"b\\\": 3,\"");
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].starts_with("Failed to parse JSON: right: "), "{}", result.warnings[0]);
+        assert!(result.warnings[0].ends_with("structural comparison was not possible, so the texts were compared line by line"));
+        assert_eq!((result.left_size, result.right_sha256.is_some()), (Some(left.len() as u64), true));
+        assert_eq!(result.classification(), crate::DiffClassification::Different);
+
+        // Valid documents are still compared structurally
+        let result = compare_strings(left, left.replace('2', "3").as_str(), &options).unwrap();
+        assert_eq!((result.entries[0].path.as_str(), result.warnings.len()), ("$.b", 0));
+    }
+
+    #[test]
+    fn test_numeric_strings_comma_locale() {
+        let left = json!({"amount": "1.234,5"});
+        let right = json!({"amount": "1234,50"});
+
+        let options = CompareOptions {
+            all_numeric_strings: true,
+            number_locale: NumberLocale::Comma,
+            ..CompareOptions::default()
+        };
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert!(result.entries.is_empty());
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_numeric_strings_non_numeric() {
+        let left = json!({"amount": "n/a"});
+        let right = json!({"amount": "12"});
+
+        let mut options = CompareOptions::default();
+        options.numeric_strings.push(JsonPath::new("$.amount").unwrap());
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].diff_type, DiffType::Modified);
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].contains("\"n/a\" at $.amount in the left document"));
+    }
+
+    #[cfg(feature = "timestamps")]
+    #[test]
+    fn test_explicit_timestamp() {
+        let left = json!({"a": 1});
+        let right = json!({"a": 2});
+
+        let timestamp = chrono::DateTime::parse_from_rfc3339("2023-07-21T14:30:00Z").unwrap().with_timezone(&Utc);
+        let options = CompareOptions {
+            timestamp: Some(timestamp),
+            ..CompareOptions::default()
+        };
+
+        let first = compare_json(&left, &right, &options).unwrap();
+        let second = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(first.timestamp, Some(timestamp));
+        assert_eq!(first.format_readable(), second.format_readable());
+        assert!(first.format_readable().contains("TIMESTAMP: 2023-07-21T14:30:00+00:00"));
+    }
+
+    #[cfg(not(feature = "timestamps"))]
+    #[test]
+    fn test_timestamp_without_clock() {
+        let result = compare_json(&json!({"a": 1}), &json!({"a": 2}), &CompareOptions::default()).unwrap();
+        assert_eq!(result.timestamp, None);
+        assert!(!result.format_readable().contains("TIMESTAMP"));
+
+        let options = CompareOptions { timestamp: Some("2023-07-21T14:30:00Z".to_string()), ..CompareOptions::default() };
+        let result = compare_json(&json!({"a": 1}), &json!({"a": 2}), &options).unwrap();
+        assert!(result.format_readable().contains("TIMESTAMP: 2023-07-21T14:30:00Z"));
+        assert_eq!(serde_json::to_value(&result).unwrap()["timestamp"], "2023-07-21T14:30:00Z");
+    }
+
+    #[cfg(feature = "timestamps")]
+    #[test]
+    fn test_omitted_timestamp() {
+        let mut result = compare_json(&json!({"a": 1}), &json!({"a": 1}), &CompareOptions::default()).unwrap();
+        assert!(result.timestamp.is_some());
+
+        result.timestamp = None;
+        assert!(!result.format_readable().contains("TIMESTAMP"));
+        assert_eq!(serde_json::to_value(&result).unwrap()["timestamp"], Value::Null);
+    }
+
+    #[test]
+    fn test_options_fingerprint() {
+        let base = CompareOptions::default();
+        let mut ignoring = CompareOptions::default();
+        ignoring.ignore_paths.push(JsonPath::new("$.id").unwrap());
+        let limited = CompareOptions {
+            max_input_bytes: Some(10),
+            timestamp: serde_json::from_value(json!("2023-07-21T14:30:00Z")).unwrap(),
+            ..CompareOptions::default()
+        };
+
+        assert_eq!(base.fingerprint(), CompareOptions::default().fingerprint());
+        assert_eq!(base.fingerprint(), limited.fingerprint());
+        assert_ne!(base.fingerprint(), ignoring.fingerprint());
+    }
+
+    fn serializable_options() -> CompareOptions {
+        CompareOptions {
+            ignore_paths: vec![
+                JsonPath::new("$.meta.id").unwrap(),
+                JsonPath::new("$.items[*].*_at").unwrap(),
+                JsonPath::with_regex_opts("$", r"\$\.Trace\..*", RegexOpts { case_insensitive: true, ..RegexOpts::default() }).unwrap(),
+                JsonPath::with_regex("$", "token").unwrap(),
+            ],
+            unordered_arrays: vec![JsonPath::new("$.tags").unwrap()],
+            tolerances: vec![(JsonPath::new("$..price").unwrap(), 0.01)],
+            preprocess: vec![Transform::sort_array(JsonPath::new("$.items").unwrap(), Some("id"))],
+            decoders: vec![(JsonPath::new("$.blob").unwrap(), Codec::Base64)],
+            allowed_transitions: vec![AllowedTransition {
+                path: JsonPath::new("$.status").unwrap(),
+                from: vec!["PENDING".to_string()],
+                to: vec!["*".to_string()],
+            }],
+            number_locale: NumberLocale::Comma,
+            numeric_equivalence: true,
+            max_value_size: Some(64),
+            ..CompareOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_options_round_trip() {
+        let options = serializable_options();
+        let json = serde_json::to_value(&options).unwrap();
+        assert_eq!(json["ignore_paths"][0], json!("$.meta.id"));
+        assert_eq!(json["ignore_paths"][2], json!({"path": "$", "regex": r"^(?:\$\.Trace\..*)$", "case_insensitive": true}));
+        assert_eq!(json["ignore_paths"][3], json!({"path": "$", "regex": "token"}));
+
+        let parsed: CompareOptions = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), json);
+        assert_eq!(parsed.fingerprint(), options.fingerprint());
+
+        // The recompiled paths match what the originals do
+        for path in ["$.meta.id", "$.items[2].created_at", "$.trace.span", "$.TRACE.span", "$.auth.token", "$.other"] {
+            let originals: Vec<bool> = options.ignore_paths.iter().map(|rule| rule.matches(path)).collect();
+            let recompiled: Vec<bool> = parsed.ignore_paths.iter().map(|rule| rule.matches(path)).collect();
+            assert_eq!(originals, recompiled, "{}", path);
+        }
+
+        // Every field may be left out
+        let empty: CompareOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(empty.fingerprint(), CompareOptions::default().fingerprint());
+        assert!(empty.identify_array_item_changes);
+    }
+
+    #[test]
+    fn test_options_invalid_paths_fail_to_deserialize() {
+        let error = serde_json::from_str::<CompareOptions>(r#"{"ignore_paths": ["$.a["]}"#).unwrap_err();
+        assert!(error.to_string().contains("Invalid JSON path"), "{}", error);
+        let error = serde_json::from_str::<CompareOptions>(r#"{"ignore_paths": [{"path": "$", "regex": "("}]}"#).unwrap_err();
+        assert!(error.to_string().contains("Invalid regex pattern"), "{}", error);
+    }
+
+    #[test]
+    fn test_options_fingerprint_ignores_field_order() {
+        let json = serde_json::to_string(&serializable_options()).unwrap();
+        let mut fields: Vec<(String, Value)> = serde_json::from_str::<Map<String, Value>>(&json).unwrap().into_iter().collect();
+        fields.reverse();
+        // Written by hand in reverse order, as another tool might
+        let reversed = format!("{{{}}}", fields.iter()
+            .map(|(key, value)| format!("{}:{}", Value::String(key.clone()), value))
+            .collect::<Vec<_>>()
+            .join(","));
+        assert!(!reversed.starts_with(&json[..10]));
+
+        let parsed: CompareOptions = serde_json::from_str(&reversed).unwrap();
+        assert_eq!(parsed.canonical_json(), serializable_options().canonical_json());
+        assert_eq!(parsed.fingerprint(), serializable_options().fingerprint());
+        assert_ne!(parsed.fingerprint(), CompareOptions { numeric_equivalence: false, ..parsed.clone() }.fingerprint());
+    }
+
+    fn limit_of(error: JsonDiffError) -> (&'static str, u64) {
+        match error {
+            JsonDiffError::LimitExceeded { limit, value } => (limit, value),
+            other => panic!("expected a limit error, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_max_nodes() {
+        // An array of arrays, as a hostile producer might send
+        let nested = |fill: i64| Value::Array((0..300).map(|_| Value::Array(vec![json!(fill); 300])).collect());
+        let options = CompareOptions { max_nodes: Some(10_000), ..CompareOptions::default() };
+        let error = compare_json(&nested(0), &nested(1), &options).unwrap_err();
+        assert_eq!(error.to_string(), "Comparison stopped: visited more than 10000 values (max_nodes)");
+        assert_eq!(limit_of(error), ("max_nodes", 10_000));
+
+        // The subsequence behind move detection weighs every pair of elements
+        let options = CompareOptions { detect_array_moves: true, ..options };
+        let (left, right) = (json!((0..200).collect::<Vec<_>>()), json!((0..200).rev().collect::<Vec<_>>()));
+        assert_eq!(limit_of(compare_json(&left, &right, &options).unwrap_err()), ("max_nodes", 10_000));
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let options = CompareOptions { max_entries: Some(100), ..CompareOptions::default() };
+        let error = compare_json(&json!(vec![0; 5000]), &json!(vec![1; 5000]), &options).unwrap_err();
+        assert_eq!(limit_of(error), ("max_entries", 100));
+
+        // Exactly at the limit is fine
+        let result = compare_json(&json!(vec![0; 100]), &json!(vec![1; 100]), &options).unwrap();
+        assert_eq!(result.entries.len(), 100);
+    }
+
+    #[test]
+    fn test_max_duration() {
+        let options = CompareOptions { max_duration: Some(Duration::ZERO), ..CompareOptions::default() };
+        let error = compare_json(&json!(vec![0; 5000]), &json!(vec![0; 5000]), &options).unwrap_err();
+        assert_eq!(error.to_string(), "Comparison stopped: ran longer than 0 ms (max_duration)");
+    }
+
+    #[test]
+    fn test_limits_leave_normal_documents_alone() {
+        let options = CompareOptions {
+            max_nodes: Some(10_000),
+            max_entries: Some(100),
+            max_duration: Some(Duration::from_secs(10)),
+            ..CompareOptions::default()
+        };
+        let left = json!({"users": [{"id": 1, "name": "a", "tags": ["x"]}, {"id": 2, "name": "b"}], "version": 1});
+        let right = json!({"users": [{"id": 1, "name": "c", "tags": ["x", "y"]}, {"id": 2, "name": "b"}], "version": 2});
+        assert_eq!(compare_json(&left, &right, &options).unwrap().entries.len(), 3);
+        assert_eq!(options.fingerprint(), CompareOptions::default().fingerprint());
+    }
 }
@@ -0,0 +1,100 @@
+//! Short answers to common tasks, each checked by `cargo test`
+//!
+//! The longer versions are the examples in `json-diff-core/examples`, which run with
+//! `cargo run -p json-diff-core --example <name>`.
+//!
+//! # Ignore fields anywhere in an array
+//!
+//! `*` stands for any key or index in a path, so one rule covers every element.
+//!
+//! ```
+//! use serde_json::json;
+//! use json_diff_core::{compare_json, CompareOptions, DiffType, JsonPath};
+//!
+//! let options = CompareOptions {
+//!     ignore_paths: vec![JsonPath::new("$.items[*].updated_at")?],
+//!     ..CompareOptions::default()
+//! };
+//! let left = json!({"items": [{"id": 1, "updated_at": "mon"}, {"id": 2, "updated_at": "mon"}]});
+//! let right = json!({"items": [{"id": 1, "updated_at": "tue"}, {"id": 2, "updated_at": "tue"}]});
+//! let result = compare_json(&left, &right, &options)?;
+//! assert_eq!(result.count(DiffType::Ignored), 2);
+//! assert!(result.iter().all(|entry| !entry.diff_type.is_difference()));
+//! # Ok::<(), json_diff_core::JsonDiffError>(())
+//! ```
+//!
+//! # Accept numbers within a tolerance
+//!
+//! A number at a path given a tolerance equals any within it; `report_tolerated` keeps
+//! an entry saying so.
+//!
+//! ```
+//! use serde_json::json;
+//! use json_diff_core::{compare_json, CompareOptions, DiffType, JsonPath};
+//!
+//! let options = CompareOptions {
+//!     tolerances: vec![(JsonPath::new("$.latency_ms")?, 0.5)],
+//!     report_tolerated: true,
+//!     ..CompareOptions::default()
+//! };
+//! let result = compare_json(&json!({"latency_ms": 12.1}), &json!({"latency_ms": 12.4}), &options)?;
+//! assert_eq!(result.count(DiffType::Tolerated), 1);
+//! assert_eq!(result.classification(), json_diff_core::DiffClassification::OnlyIgnored);
+//! # Ok::<(), json_diff_core::JsonDiffError>(())
+//! ```
+//!
+//! # Turn the differences into a JSON Patch
+//!
+//! [`DiffResult::to_json_patch_optimized`](crate::DiffResult::to_json_patch_optimized)
+//! writes the patch and [`apply_json_patch`](crate::apply_json_patch) applies it.
+//!
+//! ```
+//! use serde_json::json;
+//! use json_diff_core::{apply_json_patch, compare_json, CompareOptions, PatchOptions};
+//!
+//! let (left, right) = (json!({"a": 1, "b": [1, 2]}), json!({"a": 2, "b": [1, 2, 3]}));
+//! let patch = compare_json(&left, &right, &CompareOptions::default())?
+//!     .to_json_patch_optimized(PatchOptions { left: Some(&left), ..PatchOptions::default() });
+//! let mut patched = left.clone();
+//! apply_json_patch(&mut patched, &patch)?;
+//! assert_eq!(patched, right);
+//! # Ok::<(), json_diff_core::JsonDiffError>(())
+//! ```
+//!
+//! # Stop at the first difference of two large arrays
+//!
+//! With the `streaming` feature, `compare_array_streams_until` reads the arrays one
+//! element at a time and stops when the callback breaks.
+//!
+//! ```
+//! # #[cfg(feature = "streaming")]
+//! # {
+//! use std::ops::ControlFlow;
+//! use json_diff_core::{compare_array_streams_until, CompareOptions, StreamOptions};
+//!
+//! let (left, right) = ("[1, 2, 3, 4]", "[1, 5, 3, 6]");
+//! let mut first = None;
+//! let summary = compare_array_streams_until(left.as_bytes(), right.as_bytes(), &CompareOptions::default(), &StreamOptions::default(), |entry| {
+//!     first = Some(entry.path);
+//!     ControlFlow::Break(())
+//! })?;
+//! assert!(summary.stopped);
+//! assert_eq!(first.as_deref(), Some("$[1]"));
+//! # }
+//! # Ok::<(), json_diff_core::JsonDiffError>(())
+//! ```
+//!
+//! # Check documents in a test
+//!
+//! [`quick::assert_equivalent`](crate::quick::assert_equivalent) passes when nothing but
+//! ignored or tolerated values differ, and otherwise panics with the readable report.
+//!
+//! ```
+//! use serde_json::json;
+//! use json_diff_core::{quick, CompareOptions, JsonPath};
+//!
+//! let options = CompareOptions { ignore_paths: vec![JsonPath::new("$.request_id")?], ..CompareOptions::default() };
+//! quick::assert_equivalent(&json!({"request_id": "a", "ok": true}), &json!({"request_id": "b", "ok": true}), &options);
+//! assert_eq!(quick::diff_to_string(&json!({"ok": true}), &json!({"ok": false})), "DIFF-JSON v1\n\n[MODIFIED] $.ok: true -> false\n");
+//! # Ok::<(), json_diff_core::JsonDiffError>(())
+//! ```
@@ -0,0 +1,110 @@
+use std::fmt;
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, Engine, GeneralPurpose, GeneralPurposeConfig};
+use serde::{Serialize, Deserialize};
+
+/// Number of bytes shown in the hex preview of binary values
+const HEX_PREVIEW_BYTES: usize = 16;
+
+/// Accept both padded and unpadded input, since padding is a common source of noise
+const INDIFFERENT: GeneralPurposeConfig = GeneralPurposeConfig::new()
+    .with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const STANDARD: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, INDIFFERENT);
+const URL_SAFE: GeneralPurpose = GeneralPurpose::new(&alphabet::URL_SAFE, INDIFFERENT);
+
+/// Encoding applied to string values that should be decoded before comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    /// Base64 with the standard or URL-safe alphabet, padded or not
+    Base64,
+    /// Percent-encoding, with `+` decoded as a space
+    Url,
+}
+
+impl Codec {
+    /// Decode a string value into raw bytes
+    pub fn decode(&self, input: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Codec::Base64 => STANDARD.decode(input)
+                .or_else(|_| URL_SAFE.decode(input))
+                .map_err(|e| e.to_string()),
+            Codec::Url => percent_decode(input),
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Codec::Base64 => write!(f, "base64"),
+            Codec::Url => write!(f, "url"),
+        }
+    }
+}
+
+fn percent_decode(input: &str) -> Result<Vec<u8>, String> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = bytes.get(i + 1..i + 3)
+                    .and_then(|h| std::str::from_utf8(h).ok())
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                    .ok_or_else(|| format!("invalid percent escape at offset {}", i))?;
+                decoded.push(hex);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            b => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    Ok(decoded)
+}
+
+/// Render binary data as a short hex preview, e.g. `hex:00ff10… (32 bytes)`
+pub(crate) fn hex_preview(bytes: &[u8]) -> String {
+    let hex: String = bytes.iter()
+        .take(HEX_PREVIEW_BYTES)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let ellipsis = if bytes.len() > HEX_PREVIEW_BYTES { "…" } else { "" };
+    format!("hex:{}{} ({} bytes)", hex, ellipsis, bytes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_padding_and_alphabet() {
+        assert_eq!(Codec::Base64.decode("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(Codec::Base64.decode("aGVsbG8").unwrap(), b"hello");
+        assert_eq!(Codec::Base64.decode("_-8").unwrap(), vec![0xff, 0xef]);
+        assert!(Codec::Base64.decode("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_url_decode() {
+        assert_eq!(Codec::Url.decode("a%20b+c%3Dd").unwrap(), b"a b c=d");
+        assert_eq!(Codec::Url.decode("plain").unwrap(), b"plain");
+        assert!(Codec::Url.decode("bad%2").is_err());
+        assert!(Codec::Url.decode("bad%zz").is_err());
+    }
+
+    #[test]
+    fn test_hex_preview() {
+        assert_eq!(hex_preview(&[0x00, 0xff]), "hex:00ff (2 bytes)");
+        assert_eq!(hex_preview(&[0xab; 20]), format!("hex:{}… (20 bytes)", "ab".repeat(16)));
+    }
+}
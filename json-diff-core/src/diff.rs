@@ -1,10 +1,51 @@
 use serde::{Serialize, Deserialize};
+#[cfg(feature = "timestamps")]
 use chrono::{DateTime, Utc};
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use std::fmt;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::align::Columns;
+use crate::wrap::wrap;
+use crate::canonical::{format_canonical, CanonicalOptions};
+use crate::checksum::sha256_hex;
+use crate::annotation::Annotations;
+use crate::lenient::{format_value, without_sentinels};
+use crate::path::{ancestor_at_depth, parent_path};
+use crate::compare::{compare_values_at, CompareOptions};
+use crate::rule_set::{RuleSet, RuleStats};
+use crate::error::JsonDiffError;
+use crate::line_map::SourceSpan;
+use crate::query::Query;
+use crate::patch::PatchOptions;
+
+/// Version of the report format, written in the `DIFF-JSON v<N>` header line
+pub const FORMAT_VERSION: u32 = 1;
+
+/// When a result was generated: a `chrono` time with the `timestamps` feature, its
+/// RFC 3339 text without it
+#[cfg(feature = "timestamps")]
+pub type Timestamp = DateTime<Utc>;
+#[cfg(not(feature = "timestamps"))]
+pub type Timestamp = String;
+
+/// RFC 3339 text of a timestamp, as the formats write it
+pub(crate) fn timestamp_text(timestamp: &Timestamp) -> String {
+    #[cfg(feature = "timestamps")]
+    return timestamp.to_rfc3339();
+    #[cfg(not(feature = "timestamps"))]
+    return timestamp.clone();
+}
+
+/// Name and version of the tool, recorded as the generator of the results it produces
+pub const GENERATOR: &str = concat!("json-diff ", env!("CARGO_PKG_VERSION"));
 
 /// Types of differences that can be detected
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DiffType {
     Added,
     Removed,
@@ -12,9 +53,25 @@ pub enum DiffType {
     ArrayItemChanged,
     ArrayReordered,
     Ignored,
+    Moved,
+    Tolerated,
+    KeyOrderChanged,
 }
 
 impl DiffType {
+    /// Every type, in the order they are listed in summaries
+    pub const ALL: [DiffType; 9] = [
+        DiffType::Added,
+        DiffType::Removed,
+        DiffType::Modified,
+        DiffType::ArrayItemChanged,
+        DiffType::ArrayReordered,
+        DiffType::Ignored,
+        DiffType::Moved,
+        DiffType::Tolerated,
+        DiffType::KeyOrderChanged,
+    ];
+
     /// Get the symbol representation of the diff type
     pub fn symbol(&self) -> &'static str {
         match self {
@@ -24,6 +81,9 @@ impl DiffType {
             DiffType::ArrayItemChanged => "!",
             DiffType::ArrayReordered => "*",
             DiffType::Ignored => "?",
+            DiffType::Moved => ">",
+            DiffType::Tolerated => "≈",
+            DiffType::KeyOrderChanged => "^",
         }
     }
 
@@ -36,6 +96,17 @@ impl DiffType {
             DiffType::ArrayItemChanged => "ARRAY_ITEM_CHANGED",
             DiffType::ArrayReordered => "ARRAY_REORDERED",
             DiffType::Ignored => "IGNORED",
+            DiffType::Moved => "MOVED",
+            DiffType::Tolerated => "TOLERATED",
+            DiffType::KeyOrderChanged => "KEY_ORDER_CHANGED",
+        }
+    }
+
+    /// Text of the diff type in the given style
+    pub fn text(&self, style: OutputStyle) -> &'static str {
+        match style {
+            OutputStyle::Readable => self.readable_text(),
+            OutputStyle::Symbols => self.symbol(),
         }
     }
 
@@ -45,11 +116,19 @@ impl DiffType {
             DiffType::Added => "Property exists in target but not in source",
             DiffType::Removed => "Property exists in source but not in target",
             DiffType::Modified => "Property exists in both but with different values",
-            DiffType::ArrayItemChanged => "Array item has changed",
+            DiffType::ArrayItemChanged => "Value of an array element has changed (with tag_array_item_changes)",
             DiffType::ArrayReordered => "Array elements are reordered",
             DiffType::Ignored => "Property was ignored based on configuration",
+            DiffType::Moved => "Array element is unchanged but at another index",
+            DiffType::Tolerated => "Values differ but a tolerance or normalization rule accepts them (with report_tolerated)",
+            DiffType::KeyOrderChanged => "Object has the same keys in another order (with report_key_order)",
         }
     }
+
+    /// Whether entries of this type tell the documents apart; ignored and tolerated ones do not
+    pub fn is_difference(&self) -> bool {
+        !matches!(self, DiffType::Ignored | DiffType::Tolerated)
+    }
 }
 
 impl fmt::Display for DiffType {
@@ -58,6 +137,205 @@ impl fmt::Display for DiffType {
     }
 }
 
+/// How diff types are written in text output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputStyle {
+    /// Descriptive text such as `[MODIFIED]`
+    #[default]
+    Readable,
+    /// Compact symbols such as `~`
+    Symbols,
+}
+
+impl OutputStyle {
+    /// The style after this one when cycling through them
+    pub fn next(self) -> Self {
+        match self {
+            OutputStyle::Readable => OutputStyle::Symbols,
+            OutputStyle::Symbols => OutputStyle::Readable,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OutputStyle::Readable => "Readable",
+            OutputStyle::Symbols => "Symbols",
+        }
+    }
+}
+
+/// `text` with its control characters written as escapes, so it stays on one line and
+/// sends no terminal escape sequences: `\n`, `\r` and `\t` as such, the others as `\u{1b}`
+///
+/// The text formats write paths, values and reasons through it; keys may hold anything.
+pub fn escape_control(text: &str) -> Cow<'_, str> {
+    if !text.contains(char::is_control) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Placeholder for the value of a side that has none
+pub(crate) const ABSENT: &str = "<absent>";
+
+/// Characters of a value shown in the text formats before it is truncated, by default
+pub const DEFAULT_MAX_VALUE_CHARS: usize = 512;
+
+/// How values are rendered in the text formats
+///
+/// The JSON output always holds the values in full.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValueRenderOptions {
+    /// Characters of a value shown before the rest is replaced by `…(+N chars)`; 0 shows every value in full
+    pub max_value_chars: usize,
+    /// Whether objects, arrays and long strings are shown as a preview of their shape
+    /// within `max_value_chars`, see [`preview_value`]
+    pub preview: bool,
+    /// Widest path column when the entries of a result are laid out in columns, see
+    /// [`DiffResult::format_with_options`]; `None` leaves them unaligned
+    pub align: Option<usize>,
+    /// Columns past which entry lines are wrapped, continuing under their values, see the
+    /// `wrap` module; 0 leaves them whole
+    pub width: usize,
+}
+
+impl Default for ValueRenderOptions {
+    fn default() -> Self {
+        Self { max_value_chars: DEFAULT_MAX_VALUE_CHARS, preview: false, align: None, width: 0 }
+    }
+}
+
+impl ValueRenderOptions {
+    /// A value's text as these options render it
+    pub fn render(&self, value: &serde_json::Value) -> String {
+        if self.preview {
+            let budget = if self.max_value_chars == 0 { usize::MAX } else { self.max_value_chars };
+            preview_value(value, budget)
+        } else {
+            self.truncate(format_value(value, false))
+        }
+    }
+
+    /// A value's text, cut at `max_value_chars` with the number of characters left out
+    ///
+    /// Characters are grapheme clusters, so an accent written as a combining mark or an
+    /// emoji joined from several code points is kept whole.
+    pub fn truncate(&self, text: String) -> String {
+        if self.max_value_chars == 0 {
+            return text;
+        }
+        match text.grapheme_indices(true).nth(self.max_value_chars) {
+            Some((end, _)) => {
+                let hidden = text[end..].graphemes(true).count();
+                format!("{}…(+{} chars)", &text[..end], hidden)
+            }
+            None => text,
+        }
+    }
+}
+
+/// A preview of the shape of `value` in about `budget` characters, counted as
+/// [`ValueRenderOptions::truncate`] counts them
+///
+/// Objects list their keys, with the length of arrays and `{…}` for objects they
+/// hold: `{name, price, tags[3], details{…}}`. Arrays give their length and their
+/// elements, or only the first one's shape if the elements are objects or arrays:
+/// `[5 items: {id, name}, …]`. Strings longer than the budget are cut and followed by
+/// their length: `"lorem ip…" (446 chars)`. What does not fit is replaced by `…`; the
+/// shortest forms, such as `{…}`, are kept even if they exceed the budget.
+pub fn preview_value(value: &serde_json::Value, budget: usize) -> String {
+    use serde_json::Value;
+
+    let len = |text: &str| text.graphemes(true).count();
+    match value {
+        Value::Object(members) if !members.is_empty() => {
+            let members: Vec<String> = members.iter()
+                .map(|(key, value)| {
+                    let plain = !key.is_empty() && !key.contains(|c: char| c.is_whitespace() || c.is_control() || ",:{}[]\"".contains(c));
+                    let key = if plain { key.clone() } else { serde_json::to_string(key).unwrap_or_default() };
+                    match value {
+                        Value::Array(items) => format!("{}[{}]", key, items.len()),
+                        Value::Object(nested) if !nested.is_empty() => format!("{}{{…}}", key),
+                        Value::Object(_) => format!("{}{{}}", key),
+                        _ => key,
+                    }
+                })
+                .collect();
+            let mut text = String::from("{");
+            for (index, member) in members.iter().enumerate() {
+                let separator = if index == 0 { "" } else { ", " };
+                // Room for `}` after the last member, for `, …}` after the others
+                let tail = if index + 1 == members.len() { 1 } else { 4 };
+                if len(&text) + separator.len() + len(member) + tail > budget {
+                    text.push_str(separator);
+                    text.push('…');
+                    break;
+                }
+                text.push_str(separator);
+                text.push_str(member);
+            }
+            text.push('}');
+            text
+        }
+        Value::Array(items) if !items.is_empty() => {
+            let mut text = format!("[{} item{}: ", items.len(), if items.len() == 1 { "" } else { "s" });
+            // Elements holding structure usually share it, so the first one stands for all
+            let structured = items[0].is_object() || items[0].is_array();
+            let shown = if structured { 1 } else { items.len() };
+            for (index, item) in items.iter().enumerate() {
+                let separator = if index == 0 { "" } else { ", " };
+                if index == shown {
+                    text.push_str(separator);
+                    text.push('…');
+                    break;
+                }
+                let tail = if index + 1 == items.len() { 1 } else { 4 };
+                let room = budget.saturating_sub(len(&text) + separator.len() + tail);
+                let element = preview_value(item, room);
+                if len(&element) > room {
+                    text.push_str(separator);
+                    text.push('…');
+                    break;
+                }
+                text.push_str(separator);
+                text.push_str(&element);
+            }
+            text.push(']');
+            text
+        }
+        Value::String(content) => {
+            let full = format_value(value, false);
+            if len(&full) <= budget || !full.starts_with('"') {
+                return full;
+            }
+            let graphemes: Vec<&str> = content.graphemes(true).collect();
+            let suffix = format!("…\" ({} chars)", graphemes.len());
+            // Escapes take more than one character, so characters are dropped until the text fits
+            let room = budget.saturating_sub(len(&suffix));
+            let mut kept = room.saturating_sub(1).min(graphemes.len());
+            loop {
+                let quoted = serde_json::to_string(&graphemes[..kept].concat()).unwrap_or_default();
+                let open = &quoted[..quoted.len() - 1];
+                if len(open) <= room || kept == 0 {
+                    return format!("{}{}", open, suffix);
+                }
+                kept -= 1;
+            }
+        }
+        _ => format_value(value, false),
+    }
+}
+
 /// A single difference entry
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DiffEntry {
@@ -66,95 +344,561 @@ pub struct DiffEntry {
     /// JSON path to the difference
     pub path: String,
     /// Original value (if applicable)
+    ///
+    /// `None` means there is no value, while `Some(Value::Null)` is a JSON `null`;
+    /// in JSON the field is left out or `null` respectively.
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "written_value", deserialize_with = "present_value")]
     pub old_value: Option<serde_json::Value>,
     /// New value (if applicable)
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "written_value", deserialize_with = "present_value")]
     pub new_value: Option<serde_json::Value>,
     /// Line number in the left/source file (if applicable)
     pub left_line: Option<usize>,
     /// Line number in the right/target file (if applicable)
     pub right_line: Option<usize>,
+    /// Document the entry came from, in results merged from several comparisons
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<EntrySource>,
+    /// Rule that turned what would have been a difference into an ignored or tolerated entry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Index in the right array a moved element ended up at; `path` holds the left one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moved_to: Option<usize>,
+    /// Lengths of arrays compared as a whole, which stand in for values left out as too large
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub array_summary: Option<ArraySummary>,
+    /// Region of the entry's key and value in the left file, when spans were recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left_span: Option<SourceSpan>,
+    /// Region of the entry's key and value in the right file, when spans were recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right_span: Option<SourceSpan>,
+    /// Path of the right value the entry compared against, when a mapping rule paired
+    /// differently named paths; `path` holds the left one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mapped_to: Option<String>,
+    /// Path of the right value the entry compared against, when that value sits at
+    /// another index than `path` says: elements of an unordered array matched across
+    /// indices, or of a stream matched by key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right_path: Option<String>,
+    /// `path` with the indices of the elements it names by key, when entries are reported
+    /// with [`IndexStyle::Keyed`](crate::IndexStyle): the left indices, or the right ones
+    /// for an added element
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_path: Option<String>,
+    /// New minus old value of a modification between two numbers, recorded with
+    /// [`CompareOptions::numeric_deltas`](crate::CompareOptions::numeric_deltas)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta: Option<f64>,
+    /// `delta` as a percentage of the old value; left out when the old value is 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta_pct: Option<f64>,
+    /// Whether the entry is a modification between two strings alike enough to be the
+    /// same text with a typo, see
+    /// [`CompareOptions::string_similarity`](crate::CompareOptions::string_similarity)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub near_match: bool,
+}
+
+/// Shape of two arrays compared as a whole
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArraySummary {
+    pub old_len: usize,
+    pub new_len: usize,
+    /// The first indices at which the arrays differ, at most [`ArraySummary::MAX_INDICES`]
+    pub differing_indices: Vec<usize>,
+}
+
+impl ArraySummary {
+    /// Number of differing indices kept as a hint
+    pub const MAX_INDICES: usize = 10;
+
+    pub fn new(old: &[serde_json::Value], new: &[serde_json::Value]) -> Self {
+        let differing_indices = (0..old.len().max(new.len()))
+            .filter(|&i| old.get(i) != new.get(i))
+            .take(Self::MAX_INDICES)
+            .collect();
+        Self { old_len: old.len(), new_len: new.len(), differing_indices }
+    }
+}
+
+/// Serialize a value field, with the non-finite numbers of lenient documents as the
+/// strings of their tokens
+fn written_value<S: serde::Serializer>(value: &Option<serde_json::Value>, serializer: S) -> Result<S::Ok, S::Error> {
+    value.as_ref().map(without_sentinels).serialize(serializer)
+}
+
+/// Deserialize a value field that is present, keeping an explicit `null` as `Some(Value::Null)`
+fn present_value<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<serde_json::Value>, D::Error> {
+    serde_json::Value::deserialize(deserializer).map(Some)
+}
+
+/// Origin of an entry in a result merged from several comparisons
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntrySource {
+    /// A pair of compared files
+    FilePair { left: PathBuf, right: PathBuf },
+    /// A record identified by a key, e.g. within a multi-document input
+    Record { key: String },
+}
+
+impl fmt::Display for EntrySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntrySource::FilePair { left, right } => write!(f, "{} <-> {}", left.display(), right.display()),
+            EntrySource::Record { key } => write!(f, "record {}", key),
+        }
+    }
+}
+
+/// Summary of one of the comparisons merged into a result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceInfo {
+    /// The compared documents
+    pub source: EntrySource,
+    /// SHA-256 digest of the left input (if known)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left_sha256: Option<String>,
+    /// SHA-256 digest of the right input (if known)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right_sha256: Option<String>,
+    /// Number of entries contributed by this source
+    pub entry_count: usize,
+}
+
+/// A path holding many differences, see [`DiffResult::top_paths`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PathCount {
+    pub path: String,
+    /// Number of differences at or below the path
+    pub count: usize,
+    /// The type most of them have
+    pub dominant_type: DiffType,
 }
 
 impl DiffEntry {
-    /// Format the diff entry with readable text instead of symbols
-    pub fn format_readable(&self) -> String {
-        let mut result = String::new();
+    /// An entry of the given type at `path`, without values or lines
+    pub fn new(diff_type: DiffType, path: impl Into<String>) -> Self {
+        Self {
+            diff_type,
+            path: path.into(),
+            old_value: None,
+            new_value: None,
+            left_line: None,
+            right_line: None,
+            source: None,
+            reason: None,
+            moved_to: None,
+            array_summary: None,
+            left_span: None,
+            right_span: None,
+            mapped_to: None,
+            right_path: None,
+            index_path: None,
+            delta: None,
+            delta_pct: None,
+            near_match: false,
+        }
+    }
 
-        // Add readable diff type
-        result.push_str(&format!("[{}] {}", self.diff_type.readable_text(), self.path));
+    /// `value` only exists in the right document
+    pub fn added(path: impl Into<String>, value: serde_json::Value) -> Self {
+        Self { new_value: Some(value), ..Self::new(DiffType::Added, path) }
+    }
 
-        // Add line number information if available
-        match (self.left_line, self.right_line) {
-            (Some(left), Some(right)) => result.push_str(&format!(" (L{}:L{})", left, right)),
-            (Some(left), None) => result.push_str(&format!(" (L{})", left)),
-            (None, Some(right)) => result.push_str(&format!(" (L{})", right)),
-            (None, None) => {},
+    /// `value` only exists in the left document
+    pub fn removed(path: impl Into<String>, value: serde_json::Value) -> Self {
+        Self { old_value: Some(value), ..Self::new(DiffType::Removed, path) }
+    }
+
+    /// `old` became `new`
+    pub fn modified(path: impl Into<String>, old: serde_json::Value, new: serde_json::Value) -> Self {
+        Self { old_value: Some(old), new_value: Some(new), ..Self::new(DiffType::Modified, path) }
+    }
+
+    /// `old` and `new` only compare equal under `rule`, such as `tolerance 0.01`
+    pub fn tolerated(path: impl Into<String>, old: serde_json::Value, new: serde_json::Value, rule: impl Into<String>) -> Self {
+        Self { old_value: Some(old), new_value: Some(new), reason: Some(rule.into()), ..Self::new(DiffType::Tolerated, path) }
+    }
+
+    /// Whatever is at `path` was not compared
+    pub fn ignored(path: impl Into<String>) -> Self {
+        Self::new(DiffType::Ignored, path)
+    }
+
+    /// The entry with its lines in the left and right files
+    pub fn with_lines(self, left_line: Option<usize>, right_line: Option<usize>) -> Self {
+        Self { left_line, right_line, ..self }
+    }
+
+    /// The path with numeric indices: `index_path` if the entry names elements by key, else `path`
+    pub fn indexed_path(&self) -> &str {
+        self.index_path.as_deref().unwrap_or(&self.path)
+    }
+
+    /// Record `delta` and `delta_pct` if the entry is a modification between two numbers
+    pub(crate) fn record_delta(&mut self) {
+        if self.diff_type != DiffType::Modified {
+            return;
         }
+        let number = |value: &Option<serde_json::Value>| value.as_ref().and_then(serde_json::Value::as_f64);
+        let (Some(old), Some(new)) = (number(&self.old_value), number(&self.new_value)) else { return };
+        let delta = new - old;
+        self.delta = Some(delta);
+        self.delta_pct = (old != 0.0).then(|| delta / old.abs() * 100.0);
+    }
+
+    /// Record `near_match` if the entry is a modification between two strings at least
+    /// `threshold` alike
+    pub(crate) fn record_near_match(&mut self, threshold: f64) {
+        if self.diff_type != DiffType::Modified {
+            return;
+        }
+        let old = self.old_value.as_ref().and_then(serde_json::Value::as_str);
+        let new = self.new_value.as_ref().and_then(serde_json::Value::as_str);
+        if let (Some(old), Some(new)) = (old, new) {
+            self.near_match = crate::similarity::similarity(old, new) >= threshold;
+        }
+    }
 
-        result.push_str(": ");
+    /// Name the array element at `element_path` by the keyed `segment`, such as
+    /// `[id=P1]`, if the entry lies beneath it; the numeric path is kept in `index_path`
+    pub(crate) fn name_element(&mut self, element_path: &str, segment: &str) {
+        let Some(rest) = self.path.strip_prefix(element_path).filter(|rest| rest.is_empty() || rest.starts_with(['.', '[', '('])) else {
+            return;
+        };
+        let path = format!("{}{}{}", crate::path::parent_path(element_path), segment, rest);
+        let index_path = std::mem::replace(&mut self.path, path);
+        self.index_path.get_or_insert(index_path);
+    }
 
-        // Add value information
-        match self.diff_type {
-            DiffType::Added => {
-                result.push_str(&serde_json::to_string(&self.new_value).unwrap_or_default());
-            }
-            DiffType::Removed => {
-                result.push_str(&serde_json::to_string(&self.old_value).unwrap_or_default());
+    /// Check that the entry's fields fit its type, as the formats and the display expect:
+    /// an added entry has a new value and no old one, a modification both values (or an
+    /// array summary standing in for them), a moved entry the index it moved to
+    pub fn validate(&self) -> Result<(), JsonDiffError> {
+        let invalid = |reason: &str| Err(JsonDiffError::InvalidEntry { path: self.path.clone(), reason: reason.to_string() });
+        let type_name = self.diff_type.readable_text();
+
+        // Line diffs of texts that are not JSON name lines instead
+        if !self.path.starts_with('$') && !crate::textdiff::is_line_path(&self.path) {
+            return invalid("the path must start with $ or be a <line N> of a line diff");
+        }
+        if self.moved_to.is_some() && self.diff_type != DiffType::Moved {
+            return invalid(&format!("{} entries have no moved_to index", type_name));
+        }
+
+        let summarized = self.array_summary.is_some();
+        let (needs_old, needs_new, forbids_old, forbids_new) = match self.diff_type {
+            DiffType::Added => (false, !summarized, true, false),
+            DiffType::Removed => (!summarized, false, false, true),
+            DiffType::Modified | DiffType::ArrayItemChanged | DiffType::Tolerated | DiffType::KeyOrderChanged => {
+                (!summarized, !summarized, false, false)
             }
-            DiffType::Modified | DiffType::ArrayItemChanged => {
-                result.push_str(&format!("{} -> {}",
-                    serde_json::to_string(&self.old_value).unwrap_or_default(),
-                    serde_json::to_string(&self.new_value).unwrap_or_default()
-                ));
+            DiffType::Moved => {
+                if self.moved_to.is_none() {
+                    return invalid("MOVED entries need the index they moved to");
+                }
+                (false, false, false, true)
             }
-            DiffType::ArrayReordered => {
-                result.push_str("[REORDERED]");
+            DiffType::ArrayReordered | DiffType::Ignored => (false, false, false, false),
+        };
+        match (&self.old_value, &self.new_value) {
+            (None, _) if needs_old => invalid(&format!("{} entries need their old value", type_name)),
+            (_, None) if needs_new => invalid(&format!("{} entries need their new value", type_name)),
+            (Some(_), _) if forbids_old => invalid(&format!("{} entries have no old value", type_name)),
+            (_, Some(_)) if forbids_new => invalid(&format!("{} entries have no new value", type_name)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Stable identifier of the entry, independent of line numbers and object key order
+    ///
+    /// Two entries describing the same change at the same path share a fingerprint,
+    /// which makes entries comparable across reports.
+    pub fn fingerprint(&self) -> String {
+        let mut input = String::new();
+        input.push_str(self.diff_type.readable_text());
+        input.push('\0');
+        input.push_str(&self.path);
+        // Reports from before absent values were left out of the JSON have null in
+        // their place, so only values a type always has keep null apart from absent
+        let values = match self.diff_type {
+            DiffType::Modified | DiffType::ArrayItemChanged | DiffType::Tolerated | DiffType::KeyOrderChanged => {
+                [self.old_value.as_ref(), self.new_value.as_ref()]
             }
-            DiffType::Ignored => {
-                result.push_str("[IGNORED]");
+            DiffType::Added => [None, self.new_value.as_ref()],
+            DiffType::Removed | DiffType::Moved => [self.old_value.as_ref(), None],
+            DiffType::ArrayReordered | DiffType::Ignored => [
+                self.old_value.as_ref().filter(|value| !value.is_null()),
+                self.new_value.as_ref().filter(|value| !value.is_null()),
+            ],
+        };
+        for value in values {
+            input.push('\0');
+            if let Some(value) = value {
+                input.push_str(&format_canonical(value, &CanonicalOptions::COMPACT));
             }
         }
+        if let Some(index) = self.moved_to {
+            input.push('\0');
+            input.push_str(&index.to_string());
+        }
+        for right_path in [&self.mapped_to, &self.right_path].into_iter().flatten() {
+            input.push('\0');
+            input.push_str(right_path);
+        }
+        // Values left out as too large are told apart by the shape of the arrays
+        if let Some(summary) = self.array_summary.as_ref().filter(|_| self.old_value.is_none() || self.new_value.is_none()) {
+            input.push('\0');
+            input.push_str(&format!("{:?}", summary));
+        }
+        // The same change in two merged documents is two different entries
+        if let Some(source) = &self.source {
+            input.push('\0');
+            input.push_str(&source.to_string());
+        }
+
+        // 64 bits are plenty to tell the entries of two reports apart
+        sha256_hex(input.as_bytes())[..16].to_string()
+    }
+
+    /// Format the diff entry in the given style
+    pub fn format_with(&self, style: OutputStyle) -> String {
+        self.format_with_options(style, &ValueRenderOptions::default())
+    }
 
+    /// Format the diff entry in the given style, rendering values as `options` say
+    ///
+    /// A single entry is never aligned; `options.align` only lays out the entries of a result.
+    pub fn format_with_options(&self, style: OutputStyle, options: &ValueRenderOptions) -> String {
+        let mut result = String::new();
+        // Writing to a String cannot fail
+        let _ = self.write_line(&mut result, style, options, None);
         result
     }
-}
 
-impl fmt::Display for DiffEntry {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Write the diff type and path
-        write!(f, "{} {}", self.diff_type, self.path)?;
+    /// Write the entry as a line of a report, laid out in `columns` if given and wrapped
+    /// at `options.width`
+    pub(crate) fn write_line(&self, f: &mut impl fmt::Write, style: OutputStyle, options: &ValueRenderOptions, columns: Option<&Columns>) -> fmt::Result {
+        let mut line = String::new();
+        match columns {
+            Some(columns) => columns.write(&mut line, self, style, options)?,
+            None => self.write_styled(&mut line, style, options)?,
+        }
+        if options.width == 0 {
+            return f.write_str(&line);
+        }
 
-        // Add line number information if available
-        match (self.left_line, self.right_line) {
-            (Some(left), Some(right)) => write!(f, " (L{}:L{})", left, right)?,
-            (Some(left), None) => write!(f, " (L{})", left)?,
-            (None, Some(right)) => write!(f, " (L{})", right)?,
-            (None, None) => {},
+        // The values end the line; continuation lines start under them
+        let (_, _, _, values) = self.text_cells(style, options);
+        let value_start = line.strip_suffix(values.as_str()).map_or(0, str::len);
+        let arrow = match self.diff_type {
+            DiffType::Modified | DiffType::ArrayItemChanged | DiffType::KeyOrderChanged | DiffType::Tolerated => {
+                let old = match &self.old_value {
+                    Some(value) => options.render(value),
+                    None => self.missing_value_text(true),
+                };
+                Some(value_start + escape_control(&old).len() + 1).filter(|&arrow| line.get(arrow..).is_some_and(|rest| rest.starts_with("->")))
+            }
+            _ => None,
+        };
+        f.write_str(&wrap(&line, options.width, line[..value_start].width(), arrow))
+    }
+
+    /// Placeholder for a side of a modification without a value
+    ///
+    /// Arrays left out as too large are described by their length, other sides are `<absent>`.
+    pub(crate) fn missing_value_text(&self, is_old: bool) -> String {
+        match &self.array_summary {
+            Some(summary) => format!("<array, {} items>", if is_old { summary.old_len } else { summary.new_len }),
+            None => ABSENT.to_string(),
         }
+    }
 
-        write!(f, ": ")?;
+    /// Write the `: <values>` part of the entry
+    ///
+    /// A side without a value is left out for added and removed entries and shown as
+    /// `<absent>` for modifications, so it cannot be mistaken for a JSON `null`.
+    fn write_values(&self, f: &mut impl fmt::Write, options: &ValueRenderOptions) -> fmt::Result {
+        let value_text = |value: &Option<serde_json::Value>, is_old: bool| match value {
+            Some(value) => options.render(value),
+            None => self.missing_value_text(is_old),
+        };
 
         match self.diff_type {
-            DiffType::Added => {
-                write!(f, "{}", serde_json::to_string(&self.new_value).unwrap_or_default())
-            }
-            DiffType::Removed => {
-                write!(f, "{}", serde_json::to_string(&self.old_value).unwrap_or_default())
+            DiffType::Added | DiffType::Removed => {
+                let value = if self.diff_type == DiffType::Added { &self.new_value } else { &self.old_value };
+                match value {
+                    Some(_) => write!(f, ": {}", value_text(value, self.diff_type == DiffType::Removed)),
+                    // Nothing to show, rather than a misleading `null`
+                    None => Ok(()),
+                }
             }
-            DiffType::Modified | DiffType::ArrayItemChanged => {
-                write!(f, "{} -> {}",
-                    serde_json::to_string(&self.old_value).unwrap_or_default(),
-                    serde_json::to_string(&self.new_value).unwrap_or_default()
-                )
+            DiffType::Modified | DiffType::ArrayItemChanged | DiffType::KeyOrderChanged => {
+                write!(f, ": {} -> {}", value_text(&self.old_value, true), value_text(&self.new_value, false))?;
+                match (self.delta, self.delta_pct) {
+                    (Some(delta), Some(pct)) => write!(f, " ({:+.2}, {:+.1}%)", delta, pct),
+                    (Some(delta), None) => write!(f, " ({:+.2})", delta),
+                    _ if self.near_match => write!(f, " (≈)"),
+                    _ => Ok(()),
+                }
             }
-            DiffType::ArrayReordered => {
-                write!(f, "[REORDERED]")
-            }
-            DiffType::Ignored => {
-                write!(f, "[IGNORED]")
+            DiffType::ArrayReordered => write!(f, ": [REORDERED]"),
+            // The element itself is unchanged, only where it went is of interest
+            DiffType::Moved => match self.moved_to {
+                Some(index) => write!(f, " → [{}]", index),
+                None => Ok(()),
+            },
+            DiffType::Ignored => match &self.reason {
+                Some(reason) => write!(f, ": [IGNORED] ({})", reason),
+                None => write!(f, ": [IGNORED]"),
+            },
+            DiffType::Tolerated => {
+                write!(f, ": {} -> {}", value_text(&self.old_value, true), value_text(&self.new_value, false))?;
+                match &self.reason {
+                    Some(reason) => write!(f, " ({})", reason),
+                    None => Ok(()),
+                }
             }
         }
     }
+
+    /// Format the diff entry with readable text instead of symbols
+    pub fn format_readable(&self) -> String {
+        self.format_with_options(OutputStyle::Readable, &ValueRenderOptions::default())
+    }
+
+    fn write_styled(&self, f: &mut impl fmt::Write, style: OutputStyle, options: &ValueRenderOptions) -> fmt::Result {
+        match style {
+            OutputStyle::Readable => self.write_readable(f, options),
+            OutputStyle::Symbols => self.write_symbols(f, options),
+        }
+    }
+
+    fn write_readable(&self, f: &mut impl fmt::Write, options: &ValueRenderOptions) -> fmt::Result {
+        let mut line = format!("[{}] {}", self.diff_type.readable_text(), self.path);
+        self.write_rest(&mut line, options)?;
+        f.write_str(&escape_control(&line))
+    }
+
+    fn write_symbols(&self, f: &mut impl fmt::Write, options: &ValueRenderOptions) -> fmt::Result {
+        let mut line = format!("{} {}", self.diff_type, self.path);
+        self.write_rest(&mut line, options)?;
+        f.write_str(&escape_control(&line))
+    }
+
+    /// Write what follows the path of the entry
+    fn write_rest(&self, f: &mut impl fmt::Write, options: &ValueRenderOptions) -> fmt::Result {
+        self.write_mapped_to(f)?;
+        self.write_right_path(f)?;
+        self.write_lines(f)?;
+        self.write_values(f, options)
+    }
+
+    /// The type, the text following the path, the lines and the values of the entry,
+    /// each without the space before it, for laying entries out in columns
+    pub(crate) fn text_cells(&self, style: OutputStyle, options: &ValueRenderOptions) -> (String, String, String, String) {
+        let kind = match style {
+            OutputStyle::Readable => format!("[{}]", self.diff_type.readable_text()),
+            OutputStyle::Symbols => self.diff_type.symbol().to_string(),
+        };
+        // Writing to a String cannot fail
+        let (mut suffix, mut lines, mut values) = (String::new(), String::new(), String::new());
+        let _ = self.write_mapped_to(&mut suffix).and_then(|_| self.write_right_path(&mut suffix));
+        let _ = self.write_lines(&mut lines);
+        let _ = self.write_values(&mut values, options);
+        let values = values.strip_prefix(": ").or_else(|| values.strip_prefix(' ')).unwrap_or(&values);
+        (kind, escape_control(&suffix).into_owned(), lines.trim_start().to_string(), escape_control(values).into_owned())
+    }
+
+    /// Write the right path of a mapped entry, if it has one
+    fn write_mapped_to(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        match &self.mapped_to {
+            Some(right_path) => write!(f, " => {}", right_path),
+            None => Ok(()),
+        }
+    }
+
+    /// Write where the entry's right value sits, if elsewhere than its path says
+    fn write_right_path(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        match &self.right_path {
+            Some(right_path) => write!(f, " (right: {})", right_path),
+            None => Ok(()),
+        }
+    }
+
+    /// Write the line numbers, if available
+    fn write_lines(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        match (self.left_line, self.right_line) {
+            (Some(left), Some(right)) => write!(f, " (L{}:L{})", left, right),
+            (Some(left), None) => write!(f, " (L{})", left),
+            (None, Some(right)) => write!(f, " (L{})", right),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for DiffEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_symbols(f, &ValueRenderOptions::default())
+    }
+}
+
+/// Reorder already sorted items so entries with the same parent path form a block
+///
+/// Each block takes the place of its first entry, and within a block removed entries
+/// come before added ones, then modified ones, then the rest; ties keep their order.
+/// This way a replaced subtree reads as one change. `entry` gives the entry an item,
+/// such as an index into the entries, stands for.
+pub fn group_related<'a, T>(items: &mut Vec<T>, entry: impl Fn(&T) -> &'a DiffEntry) {
+    let mut groups: HashMap<String, usize> = HashMap::new();
+    let keys: Vec<(usize, usize)> = items.iter()
+        .map(|item| {
+            let entry = entry(item);
+            let next = groups.len();
+            let group = *groups.entry(parent_path(&entry.path).to_string()).or_insert(next);
+            let rank = match entry.diff_type {
+                DiffType::Removed => 0,
+                DiffType::Added => 1,
+                DiffType::Modified => 2,
+                _ => 3,
+            };
+            (group, rank)
+        })
+        .collect();
+
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    order.sort_by_key(|&index| keys[index]);
+
+    let mut slots: Vec<Option<T>> = std::mem::take(items).into_iter().map(Some).collect();
+    items.extend(order.into_iter().filter_map(|index| slots[index].take()));
+}
+
+/// Put the items whose entries have the largest numeric delta, either way, first
+///
+/// Items without a delta follow in their order; ties keep theirs too. `entry` gives the
+/// entry an item stands for, as with [`group_related`].
+pub fn sort_by_delta<'a, T>(items: &mut [T], entry: impl Fn(&T) -> &'a DiffEntry) {
+    items.sort_by(|a, b| {
+        let magnitude = |item| entry(item).delta.map(f64::abs);
+        // `None` sorts before any number, so comparing b with a puts it last
+        magnitude(b).partial_cmp(&magnitude(a)).unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+/// Which of three outcomes a result is, see [`DiffResult::classification`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffClassification {
+    /// No entries at all
+    Identical,
+    /// Only ignored and tolerated entries: the documents differ in ways the rules accept
+    OnlyIgnored,
+    /// At least one entry telling the documents apart
+    Different,
 }
 
 /// Complete diff result between two JSON documents
@@ -164,31 +908,1503 @@ pub struct DiffResult {
     pub left_file: Option<PathBuf>,
     /// Path to the right (modified) file
     pub right_file: Option<PathBuf>,
-    /// When the diff was generated
-    pub timestamp: DateTime<Utc>,
+    /// When the diff was generated (None when omitted for reproducible output)
+    pub timestamp: Option<Timestamp>,
     /// List of differences
+    ///
+    /// Prefer the accessors ([`DiffResult::iter`], [`DiffResult::entries`],
+    /// [`DiffResult::into_entries`]); direct field access may go away in a future release.
     pub entries: Vec<DiffEntry>,
+    /// Non-fatal problems noticed during the comparison
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// SHA-256 digest of the left input (if compared from text)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left_sha256: Option<String>,
+    /// SHA-256 digest of the right input (if compared from text)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right_sha256: Option<String>,
+    /// Size of the left input in bytes (if compared from text)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left_size: Option<u64>,
+    /// Size of the right input in bytes (if compared from text)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right_size: Option<u64>,
+    /// Comparisons merged into this result, in entry order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<SourceInfo>,
+    /// Reviewer triage of the entries, exported from the interactive display
+    #[serde(default, skip_serializing_if = "Annotations::is_empty")]
+    pub annotations: Annotations,
+    /// Short name of the left input for titles, by default its file name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left_label: Option<String>,
+    /// Short name of the right input for titles, by default its file name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right_label: Option<String>,
+    /// How the ignore rules were matched, when collected with `CompareOptions::explain_rules`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rule_stats: Vec<RuleStats>,
+    /// Tool and version that produced the result, such as `json-diff 0.1.0`; empty in
+    /// reports from before it was recorded
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub generator: String,
+    /// Host the comparison ran on, when asked for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Time each phase of the comparison took, when collected with
+    /// [`CompareOptions::collect_timings`](crate::CompareOptions::collect_timings)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timings: Option<Timings>,
+    /// Whether the left file did not exist and was compared as the document
+    /// [`CompareOptions::missing_file_policy`](crate::CompareOptions::missing_file_policy) stands in with
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub left_missing: bool,
+    /// Whether the right file did not exist, as `left_missing`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub right_missing: bool,
+    /// Paths the comparison was limited to, see
+    /// [`CompareOptions::select_paths`](crate::CompareOptions::select_paths)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub selections: Vec<String>,
 }
 
-impl fmt::Display for DiffResult {
+/// Time each phase of a comparison took, measured on the monotonic clock
+///
+/// A phase the comparison did not go through, such as reading files for
+/// [`compare_strings`](crate::compare_strings), took zero. `total` is measured on its
+/// own, so it is about the sum of the phases. In JSON every phase is in milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Timings {
+    /// Reading the input files
+    #[serde(rename = "read_ms", with = "millis")]
+    pub read: Duration,
+    /// Parsing the JSON text
+    #[serde(rename = "parse_ms", with = "millis")]
+    pub parse: Duration,
+    /// Scanning the text for the lines and spans of the values
+    #[serde(rename = "line_map_ms", with = "millis")]
+    pub line_map: Duration,
+    /// Preprocessing and comparing the documents
+    #[serde(rename = "compare_ms", with = "millis")]
+    pub compare: Duration,
+    /// Sorting and normalizing the entries
+    #[serde(rename = "sort_ms", with = "millis")]
+    pub sort: Duration,
+    /// Writing the report, which only the caller that writes it knows
+    #[serde(rename = "format_ms", default, skip_serializing_if = "Option::is_none", with = "optional_millis")]
+    pub format: Option<Duration>,
+    #[serde(rename = "total_ms", with = "millis")]
+    pub total: Duration,
+}
+
+impl Timings {
+    /// Record the time writing the report took, counting it into the total
+    pub fn add_format(&mut self, format: Duration) {
+        self.format = Some(format);
+        self.total += format;
+    }
+}
+
+impl fmt::Display for Timings {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "DIFF-JSON v1")?;
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        write!(
+            f,
+            "read {:.1}ms, parse {:.1}ms, line map {:.1}ms, compare {:.1}ms, sort {:.1}ms",
+            ms(self.read), ms(self.parse), ms(self.line_map), ms(self.compare), ms(self.sort)
+        )?;
+        if let Some(format) = self.format {
+            write!(f, ", format {:.1}ms", ms(format))?;
+        }
+        write!(f, ", total {:.1}ms", ms(self.total))
+    }
+}
+
+/// A duration as fractional milliseconds
+mod millis {
+    use std::time::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let ms = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(ms / 1000.0).map_err(serde::de::Error::custom)
+    }
+}
+
+/// An optional duration as fractional milliseconds
+mod optional_millis {
+    use std::time::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => super::millis::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        let ms = Option::<f64>::deserialize(deserializer)?;
+        ms.map(|ms| Duration::try_from_secs_f64(ms / 1000.0).map_err(serde::de::Error::custom)).transpose()
+    }
+}
+
+/// Assembles a [`DiffResult`] from entries made elsewhere, e.g. converted from another
+/// tool's output, checking each of them on [`build`](DiffResultBuilder::build)
+#[derive(Debug, Clone, Default)]
+pub struct DiffResultBuilder {
+    left_file: Option<PathBuf>,
+    right_file: Option<PathBuf>,
+    left_label: Option<String>,
+    right_label: Option<String>,
+    timestamp: Option<Timestamp>,
+    entries: Vec<DiffEntry>,
+    warnings: Vec<String>,
+}
+
+impl DiffResultBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The compared files, labelled with their file names unless labels are given
+    pub fn files(mut self, left: impl Into<PathBuf>, right: impl Into<PathBuf>) -> Self {
+        self.left_file = Some(left.into());
+        self.right_file = Some(right.into());
+        self
+    }
+
+    /// Names shown for the two sides instead of the file names
+    pub fn labels(mut self, left: impl Into<String>, right: impl Into<String>) -> Self {
+        self.left_label = Some(left.into());
+        self.right_label = Some(right.into());
+        self
+    }
+
+    pub fn timestamp(mut self, timestamp: Timestamp) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn entry(mut self, entry: DiffEntry) -> Self {
+        self.entries.push(entry);
+        self
+    }
+
+    pub fn entries(mut self, entries: impl IntoIterator<Item = DiffEntry>) -> Self {
+        self.entries.extend(entries);
+        self
+    }
+
+    pub fn warning(mut self, warning: impl Into<String>) -> Self {
+        self.warnings.push(warning.into());
+        self
+    }
+
+    /// The result, or the problem of the first entry that fails [`DiffEntry::validate`]
+    pub fn build(self) -> Result<DiffResult, JsonDiffError> {
+        for entry in &self.entries {
+            entry.validate()?;
+        }
+
+        let label = |label: Option<String>, file: &Option<PathBuf>| label.or_else(|| file_label(file.as_deref()?));
+        Ok(DiffResult {
+            left_label: label(self.left_label, &self.left_file),
+            right_label: label(self.right_label, &self.right_file),
+            left_file: self.left_file,
+            right_file: self.right_file,
+            timestamp: self.timestamp,
+            entries: self.entries,
+            warnings: self.warnings,
+            left_sha256: None,
+            right_sha256: None,
+            left_size: None,
+            right_size: None,
+            sources: Vec::new(),
+            annotations: Annotations::default(),
+            rule_stats: Vec::new(),
+            generator: GENERATOR.to_string(),
+            hostname: None,
+            timings: None,
+            left_missing: false,
+            right_missing: false,
+            selections: Vec::new(),
+        })
+    }
+}
+
+/// The label of a side compared from `path`, its file name
+fn file_label(path: &Path) -> Option<String> {
+    path.file_name().map(|name| name.to_string_lossy().into_owned())
+}
+
+impl DiffResult {
+    /// Start assembling a result, see [`DiffResultBuilder`]
+    pub fn builder() -> DiffResultBuilder {
+        DiffResultBuilder::new()
+    }
+
+    /// The differences, in report order
+    pub fn entries(&self) -> &[DiffEntry] {
+        &self.entries
+    }
+
+    /// Iterate over the differences without cloning them
+    pub fn iter(&self) -> std::slice::Iter<'_, DiffEntry> {
+        self.entries.iter()
+    }
+
+    /// Regions of the entry at `entry_index` in the left and right files
+    ///
+    /// Spans are only known for results compared from text with
+    /// [`CompareOptions::record_spans`](crate::CompareOptions::record_spans) set; a side
+    /// is `None` when the entry's path does not exist there, and both are when there is
+    /// no such entry.
+    pub fn locate(&self, entry_index: usize) -> (Option<SourceSpan>, Option<SourceSpan>) {
+        self.entries.get(entry_index).map_or((None, None), |entry| (entry.left_span, entry.right_span))
+    }
+
+    /// The result with only the entries `query` selects, see [`Query`]
+    pub fn query(&self, query: &str) -> Result<DiffResult, JsonDiffError> {
+        let query = Query::parse(query)?;
+        let mut result = self.clone();
+        result.entries.retain(|entry| query.matches(entry));
+        Ok(result)
+    }
+
+    /// The differences as a JSON Patch (RFC 6902) that turns the left document into
+    /// the right one, with array operations ordered to apply cleanly and mostly changed
+    /// subtrees replaced whole, see [`PatchOptions`]
+    pub fn to_json_patch_optimized(&self, opts: PatchOptions<'_>) -> serde_json::Value {
+        crate::patch::json_patch(self, opts)
+    }
+
+    /// The differences as a JSON Merge Patch (RFC 7386), see [`merge_patch`](crate::merge_patch)
+    /// for what it leaves out and [`merge_patch::from_result`](crate::merge_patch::from_result)
+    /// for the warnings
+    pub fn to_merge_patch(&self) -> serde_json::Value {
+        crate::merge_patch::from_result(self).0
+    }
+
+    /// Iterate over the differences of one type
+    pub fn iter_type(&self, diff_type: DiffType) -> impl Iterator<Item = &DiffEntry> {
+        self.entries.iter().filter(move |entry| entry.diff_type == diff_type)
+    }
+
+    /// Number of differences of one type
+    pub fn count(&self, diff_type: DiffType) -> usize {
+        self.iter_type(diff_type).count()
+    }
+
+    /// Whether the documents are identical, differ only where the rules ignore or
+    /// tolerate it, or really differ; see [`DiffType::is_difference`]
+    pub fn classification(&self) -> DiffClassification {
+        if self.entries.iter().any(|entry| entry.diff_type.is_difference()) {
+            DiffClassification::Different
+        } else if self.entries.is_empty() {
+            DiffClassification::Identical
+        } else {
+            DiffClassification::OnlyIgnored
+        }
+    }
+
+    /// The `limit` paths `depth` steps below the root holding the most differences, most first
+    ///
+    /// Each difference counts toward its path cut to `depth` steps, e.g. `$.items` for
+    /// `$.items[3].price` at depth 1; shallower ones count toward their own path. Ignored
+    /// and tolerated entries are left out. Paths with as many differences are in path
+    /// order, and a path's dominant type is the first of [`DiffType::ALL`] among its most
+    /// frequent ones.
+    pub fn top_paths(&self, depth: usize, limit: usize) -> Vec<PathCount> {
+        let mut counts: HashMap<&str, [usize; DiffType::ALL.len()]> = HashMap::new();
+        for entry in self.iter().filter(|entry| entry.diff_type.is_difference()) {
+            let position = DiffType::ALL.iter().position(|diff_type| *diff_type == entry.diff_type).unwrap_or(0);
+            counts.entry(ancestor_at_depth(&entry.path, depth)).or_default()[position] += 1;
+        }
+
+        let mut paths: Vec<PathCount> = counts.into_iter()
+            .map(|(path, by_type)| {
+                let most = by_type.iter().max().copied().unwrap_or(0);
+                PathCount {
+                    path: path.to_string(),
+                    count: by_type.iter().sum(),
+                    dominant_type: DiffType::ALL[by_type.iter().position(|count| *count == most).unwrap_or(0)].clone(),
+                }
+            })
+            .collect();
+        paths.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.path.cmp(&b.path)));
+        paths.truncate(limit);
+        paths
+    }
+
+    /// Percentage of the lines of both inputs that no difference points at, given how
+    /// many lines each has; all of them if they have none
+    ///
+    /// An added entry's line on the left, and a removed one's on the right, is where it
+    /// would be inserted and does not count as changed. Lines of merged results are told
+    /// apart by their source, so the counts are the lines of all compared inputs.
+    pub fn line_similarity(&self, left_lines: usize, right_lines: usize) -> f64 {
+        let total = left_lines + right_lines;
+        if total == 0 {
+            return 100.0;
+        }
+        let mut changed: [HashSet<(Option<&EntrySource>, usize)>; 2] = Default::default();
+        for entry in self.iter().filter(|entry| entry.diff_type.is_difference()) {
+            if let Some(line) = entry.left_line.filter(|_| entry.diff_type != DiffType::Added) {
+                changed[0].insert((entry.source.as_ref(), line));
+            }
+            if let Some(line) = entry.right_line.filter(|_| entry.diff_type != DiffType::Removed) {
+                changed[1].insert((entry.source.as_ref(), line));
+            }
+        }
+        let changed = changed[0].len() + changed[1].len();
+        100.0 * total.saturating_sub(changed) as f64 / total as f64
+    }
+
+    /// Keep entries with the same parent path together, see [`group_related`]
+    pub fn group_related(&mut self) {
+        let mut order: Vec<usize> = (0..self.entries.len()).collect();
+        group_related(&mut order, |&index| &self.entries[index]);
+
+        let mut slots: Vec<Option<DiffEntry>> = std::mem::take(&mut self.entries).into_iter().map(Some).collect();
+        self.entries = order.into_iter().filter_map(|index| slots[index].take()).collect();
+    }
+
+    /// List the entries with the largest numeric delta first, see [`sort_by_delta`]
+    pub fn sort_by_delta(&mut self) {
+        let mut order: Vec<usize> = (0..self.entries.len()).collect();
+        sort_by_delta(&mut order, |&index| &self.entries[index]);
+
+        let mut slots: Vec<Option<DiffEntry>> = std::mem::take(&mut self.entries).into_iter().map(Some).collect();
+        self.entries = order.into_iter().filter_map(|index| slots[index].take()).collect();
+    }
+
+    /// Bring the entries to one canonical form, for results assembled from several
+    /// comparisons or by other tools
+    ///
+    /// A removed and an added entry at the same path, from the same source, become one
+    /// modification with both values; an entry repeating another exactly is dropped; the
+    /// rest are sorted by line (left, else right, entries without lines last), then by
+    /// path and type, so results holding the same entries list them alike.
+    pub fn normalize(&mut self) {
+        let mut seen = HashSet::new();
+        let mut entries: Vec<DiffEntry> = std::mem::take(&mut self.entries)
+            .into_iter()
+            .filter(|entry| {
+                let key = (entry.fingerprint(), entry.left_line, entry.right_line, entry.source.clone(), entry.reason.clone(), entry.moved_to);
+                seen.insert(key)
+            })
+            .collect();
+
+        // Each removed entry takes the first added one at its path
+        let mut added: HashMap<(String, Option<EntrySource>), Vec<usize>> = HashMap::new();
+        for (index, entry) in entries.iter().enumerate().rev() {
+            if entry.diff_type == DiffType::Added {
+                added.entry((entry.path.clone(), entry.source.clone())).or_default().push(index);
+            }
+        }
+        let mut merged = vec![false; entries.len()];
+        for index in 0..entries.len() {
+            if entries[index].diff_type != DiffType::Removed {
+                continue;
+            }
+            let key = (entries[index].path.clone(), entries[index].source.clone());
+            let Some(added_index) = added.get_mut(&key).and_then(Vec::pop) else { continue };
+            let (new_value, right_line) = (entries[added_index].new_value.clone(), entries[added_index].right_line);
+            let right_span = entries[added_index].right_span;
+            let removed = &mut entries[index];
+            removed.diff_type = DiffType::Modified;
+            removed.new_value = new_value;
+            removed.right_line = right_line;
+            removed.right_span = right_span;
+            merged[added_index] = true;
+        }
+        let mut merged = merged.into_iter();
+        entries.retain(|_| !merged.next().unwrap_or(false));
+
+        let type_rank = |diff_type: &DiffType| DiffType::ALL.iter().position(|t| t == diff_type);
+        entries.sort_by(|a, b| {
+            let line = |entry: &DiffEntry| entry.left_line.or(entry.right_line).unwrap_or(usize::MAX);
+            line(a).cmp(&line(b))
+                .then_with(|| a.path.cmp(&b.path))
+                .then_with(|| type_rank(&a.diff_type).cmp(&type_rank(&b.diff_type)))
+        });
+        self.entries = entries;
+    }
+
+    /// Apply `options` anew to the modifications, for rules added after the comparison,
+    /// e.g. to an archived report whose files are gone
+    ///
+    /// The old and new values of each modification are compared again at its path. One
+    /// the options now call equal is dropped, or replaced by the ignored or tolerated
+    /// entries the comparison reports, with the lines and source of the modification;
+    /// one still differing is kept as it was. Added and removed entries are not touched,
+    /// nor are modifications whose values were left out of the result, which get a
+    /// warning instead. The entry counts of the sources are recounted.
+    pub fn renormalize(&mut self, options: &CompareOptions) -> Result<(), JsonDiffError> {
+        let mut ignore = RuleSet::new(&options.ignore_paths, false);
+        let mut entries = Vec::with_capacity(self.entries.len());
+        let mut warnings = Vec::new();
+        for entry in &self.entries {
+            if entry.diff_type != DiffType::Modified {
+                entries.push(entry.clone());
+                continue;
+            }
+            let (Some(old), Some(new), None) = (&entry.old_value, &entry.new_value, &entry.array_summary) else {
+                warnings.push(format!("{}: values left out of the report, not compared again", entry.path));
+                entries.push(entry.clone());
+                continue;
+            };
+
+            let (replacements, _) = compare_values_at(old, new, &entry.path, options, &mut ignore)?;
+            if replacements.iter().any(|replacement| replacement.diff_type.is_difference()) {
+                entries.push(entry.clone());
+                continue;
+            }
+            // Paths the modification's right value or keyed path stand for, below it
+            let rebase = |base: &Option<String>, path: &str| {
+                let rest = path.strip_prefix(entry.path.as_str())?;
+                base.as_ref().map(|base| format!("{}{}", base, rest))
+            };
+            entries.extend(replacements.into_iter().map(|replacement| {
+                let at_entry = replacement.path == entry.path;
+                DiffEntry {
+                    left_line: entry.left_line,
+                    right_line: entry.right_line,
+                    source: entry.source.clone(),
+                    left_span: entry.left_span.filter(|_| at_entry),
+                    right_span: entry.right_span.filter(|_| at_entry),
+                    mapped_to: rebase(&entry.mapped_to, &replacement.path),
+                    right_path: rebase(&entry.right_path, &replacement.path),
+                    index_path: rebase(&entry.index_path, &replacement.path),
+                    ..replacement
+                }
+            }));
+        }
+
+        self.entries = entries;
+        self.warnings.extend(warnings);
+        for info in &mut self.sources {
+            info.entry_count = self.entries.iter().filter(|entry| entry.source.as_ref() == Some(&info.source)).count();
+        }
+        Ok(())
+    }
+
+    /// When the result was generated, as RFC 3339 text
+    pub fn timestamp_rfc3339(&self) -> Option<String> {
+        self.timestamp.as_ref().map(timestamp_text)
+    }
+
+    /// Take ownership of the differences
+    pub fn into_entries(self) -> Vec<DiffEntry> {
+        self.entries
+    }
+
+    /// Combine several results into one, tagging each entry with the comparison it came from
+    ///
+    /// Results with both file paths are identified by them, others by their position
+    /// in `results`. Results that were themselves merged keep their entries' sources.
+    pub fn merge(results: Vec<DiffResult>) -> DiffResult {
+        let mut merged = DiffResult {
+            left_file: None,
+            right_file: None,
+            timestamp: results.iter().filter_map(|r| r.timestamp.as_ref()).max().cloned(),
+            entries: Vec::new(),
+            warnings: Vec::new(),
+            left_sha256: None,
+            right_sha256: None,
+            left_size: None,
+            right_size: None,
+            sources: Vec::new(),
+            annotations: Annotations::default(),
+            left_label: None,
+            right_label: None,
+            rule_stats: Vec::new(),
+            generator: GENERATOR.to_string(),
+            hostname: None,
+            timings: None,
+            left_missing: false,
+            right_missing: false,
+            selections: Vec::new(),
+        };
+
+        for (index, result) in results.into_iter().enumerate() {
+            for selection in &result.selections {
+                if !merged.selections.contains(selection) {
+                    merged.selections.push(selection.clone());
+                }
+            }
+            if !result.sources.is_empty() {
+                merged.sources.extend(result.sources);
+                merged.warnings.extend(result.warnings);
+                merged.entries.extend(result.entries);
+                continue;
+            }
+
+            let source = match (&result.left_file, &result.right_file) {
+                (Some(left), Some(right)) => EntrySource::FilePair { left: left.clone(), right: right.clone() },
+                _ => EntrySource::Record { key: index.to_string() },
+            };
+
+            merged.warnings.extend(result.warnings.iter().map(|w| format!("{}: {}", source, w)));
+            merged.sources.push(SourceInfo {
+                source: source.clone(),
+                left_sha256: result.left_sha256,
+                right_sha256: result.right_sha256,
+                entry_count: result.entries.len(),
+            });
+            merged.entries.extend(result.entries.into_iter().map(|mut entry| {
+                entry.source.get_or_insert_with(|| source.clone());
+                entry
+            }));
+        }
+
+        merged
+    }
+
+    /// Record `left` and `right` as the compared files, each labelled with its file name
+    pub fn set_files(&mut self, left: &Path, right: &Path) {
+        self.left_file = Some(left.to_path_buf());
+        self.right_file = Some(right.to_path_buf());
+        self.left_label = file_label(left);
+        self.right_label = file_label(right);
+    }
+
+    /// Remove what the result records about the inputs and the run, keeping the
+    /// entries, warnings and annotations
+    ///
+    /// File paths, labels, checksums, sizes, the timestamp, the generator and the
+    /// hostname are cleared, for reports shared where they would say too much.
+    pub fn strip_metadata(&mut self) {
+        self.left_file = None;
+        self.right_file = None;
+        self.left_label = None;
+        self.right_label = None;
+        self.left_sha256 = None;
+        self.right_sha256 = None;
+        self.left_size = None;
+        self.right_size = None;
+        self.timestamp = None;
+        self.generator.clear();
+        self.hostname = None;
+    }
+
+    /// Check what every comparison's result must hold, panicking with each breach
+    ///
+    /// No two entries of one source share a path and type; nothing else of the source is
+    /// reported at or beneath an ignored path; every entry's fields fit its type, see
+    /// [`DiffEntry::validate`]; and when the result names files that can be read, every
+    /// line is within them. Meant for tests of the comparison features.
+    ///
+    /// # Panics
+    ///
+    /// If any of these does not hold.
+    pub fn assert_invariants(&self) {
+        let mut problems = Vec::new();
+        let mut seen = HashSet::new();
+        let line_count = |file: &Option<PathBuf>, missing: bool| {
+            let text = file.as_ref().filter(|_| !missing).and_then(|file| std::fs::read_to_string(file).ok())?;
+            Some(text.lines().count().max(1))
+        };
+        let (left_lines, right_lines) = (line_count(&self.left_file, self.left_missing), line_count(&self.right_file, self.right_missing));
+        let ignored: HashSet<(&Option<EntrySource>, &str)> = self.iter_type(DiffType::Ignored).map(|entry| (&entry.source, entry.path.as_str())).collect();
+
+        for entry in &self.entries {
+            let name = format!("{} {}", entry.diff_type.readable_text(), entry.path);
+            if !seen.insert((&entry.source, &entry.path, &entry.diff_type)) {
+                problems.push(format!("{}: reported twice", name));
+            }
+            let mut ancestor = if entry.diff_type == DiffType::Ignored { parent_path(&entry.path) } else { &entry.path };
+            while !ancestor.is_empty() {
+                if ignored.contains(&(&entry.source, ancestor)) {
+                    problems.push(format!("{}: reported at or beneath the ignored {}", name, ancestor));
+                    break;
+                }
+                ancestor = parent_path(ancestor);
+            }
+            if let Err(e) = entry.validate() {
+                problems.push(e.to_string());
+            }
+            for (side, line, count) in [("left", entry.left_line, left_lines), ("right", entry.right_line, right_lines)] {
+                if let (Some(line), Some(count)) = (line, count) {
+                    if line == 0 || line > count {
+                        problems.push(format!("{}: {} line {} outside the file's {} lines", name, side, line, count));
+                    }
+                }
+            }
+        }
+
+        assert!(problems.is_empty(), "Result breaks its invariants:\n{}", problems.join("\n"));
+    }
+
+    /// Compare this result against an earlier one, bucketing entries by fingerprint
+    pub fn diff_against(&self, previous: &DiffResult) -> DiffOfDiffs {
+        let current: HashSet<String> = self.iter().map(DiffEntry::fingerprint).collect();
+        let before: HashSet<String> = previous.iter().map(DiffEntry::fingerprint).collect();
+
+        let (persisting, new) = self.iter()
+            .cloned()
+            .partition(|entry| before.contains(&entry.fingerprint()));
+        let resolved = previous.iter()
+            .filter(|entry| !current.contains(&entry.fingerprint()))
+            .cloned()
+            .collect();
+
+        DiffOfDiffs { new, resolved, persisting }
+    }
+
+    /// Format the diff result in the given style
+    pub fn format_with(&self, style: OutputStyle) -> String {
+        self.format_with_options(style, &ValueRenderOptions::default())
+    }
 
+    /// Format the diff result in the given style, rendering values as `options` say
+    ///
+    /// With `options.align` the entries are laid out in columns, see the `align` module.
+    pub fn format_with_options(&self, style: OutputStyle, options: &ValueRenderOptions) -> String {
+        let mut output = String::new();
+        // Writing to a String cannot fail
+        let _ = self.write_entries(&mut output, style, options);
+        output
+    }
+
+    /// Format the diff result with readable text instead of symbols
+    pub fn format_readable(&self) -> String {
+        self.format_with(OutputStyle::Readable)
+    }
+
+    fn write_entries(&self, f: &mut impl fmt::Write, style: OutputStyle, options: &ValueRenderOptions) -> fmt::Result {
+        self.write_header(f)?;
+
+        let columns = options.align.map(|max_path| Columns::measure(self, style, options, max_path));
+        let mut current_source = None;
+        for entry in self {
+            self.write_source_heading(f, entry, &mut current_source)?;
+            entry.write_line(f, style, options, columns.as_ref())?;
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a `SOURCE:` heading when the entry starts a new group of merged entries
+    fn write_source_heading<'a>(
+        &self,
+        f: &mut impl fmt::Write,
+        entry: &'a DiffEntry,
+        current: &mut Option<&'a EntrySource>,
+    ) -> fmt::Result {
+        let Some(source) = entry.source.as_ref() else {
+            return Ok(());
+        };
+        if *current == Some(source) {
+            return Ok(());
+        }
+        if current.is_some() {
+            writeln!(f)?;
+        }
+        *current = Some(source);
+
+        let count = self.sources.iter()
+            .find(|info| &info.source == source)
+            .map(|info| info.entry_count);
+        match count {
+            Some(count) => writeln!(f, "SOURCE: {} ({} entries)", source, count),
+            None => writeln!(f, "SOURCE: {}", source),
+        }
+    }
+
+    /// Write the header lines shared by all text formats
+    fn write_header(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        writeln!(f, "DIFF-JSON v{}", FORMAT_VERSION)?;
+
+        let missing = |missing: bool| if missing { " (missing)" } else { "" };
         if let Some(left) = &self.left_file {
-            writeln!(f, "LEFT: {}", left.display())?;
+            writeln!(f, "LEFT: {}{}", escape_control(&left.display().to_string()), missing(self.left_missing))?;
         }
 
         if let Some(right) = &self.right_file {
-            writeln!(f, "RIGHT: {}", right.display())?;
+            writeln!(f, "RIGHT: {}{}", escape_control(&right.display().to_string()), missing(self.right_missing))?;
         }
 
-        writeln!(f, "TIMESTAMP: {}", self.timestamp.to_rfc3339())?;
-        writeln!(f)?;
+        if let Some(hash) = &self.left_sha256 {
+            writeln!(f, "LEFT-SHA256: {}", hash)?;
+        }
 
-        for entry in &self.entries {
-            writeln!(f, "{}", entry)?;
+        if let Some(hash) = &self.right_sha256 {
+            writeln!(f, "RIGHT-SHA256: {}", hash)?;
+        }
+
+        if let Some(timestamp) = self.timestamp_rfc3339() {
+            writeln!(f, "TIMESTAMP: {}", timestamp)?;
+        }
+
+        if !self.generator.is_empty() {
+            writeln!(f, "GENERATOR: {}", self.generator)?;
+        }
+
+        if let Some(hostname) = &self.hostname {
+            writeln!(f, "HOSTNAME: {}", hostname)?;
+        }
+
+        for selection in &self.selections {
+            writeln!(f, "SELECT: {}", escape_control(selection))?;
+        }
+
+        writeln!(f)
+    }
+}
+
+impl<'a> IntoIterator for &'a DiffResult {
+    type Item = &'a DiffEntry;
+    type IntoIter = std::slice::Iter<'a, DiffEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for DiffResult {
+    type Item = DiffEntry;
+    type IntoIter = std::vec::IntoIter<DiffEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl Extend<DiffEntry> for DiffResult {
+    fn extend<T: IntoIterator<Item = DiffEntry>>(&mut self, iter: T) {
+        self.entries.extend(iter);
+    }
+}
+
+impl fmt::Display for DiffResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_entries(f, OutputStyle::Symbols, &ValueRenderOptions::default())
+    }
+}
+
+/// Entries of a diff result bucketed against an earlier result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffOfDiffs {
+    /// Entries only present in the current result
+    pub new: Vec<DiffEntry>,
+    /// Entries only present in the previous result
+    pub resolved: Vec<DiffEntry>,
+    /// Entries present in both results
+    pub persisting: Vec<DiffEntry>,
+}
+
+impl DiffOfDiffs {
+    /// Format the buckets in the given style
+    pub fn format_with(&self, style: OutputStyle) -> String {
+        self.format_with_options(style, &ValueRenderOptions::default())
+    }
+
+    /// Format the buckets in the given style, rendering values as `options` say
+    pub fn format_with_options(&self, style: OutputStyle, options: &ValueRenderOptions) -> String {
+        let mut output = String::new();
+        // Writing to a String cannot fail
+        let _ = self.write_buckets(&mut output, style, options);
+        output
+    }
+
+    /// Format the buckets with readable text instead of symbols
+    pub fn format_readable(&self) -> String {
+        self.format_with(OutputStyle::Readable)
+    }
+
+    fn write_buckets(&self, f: &mut impl fmt::Write, style: OutputStyle, options: &ValueRenderOptions) -> fmt::Result {
+        // The buckets share their columns
+        let all = self.buckets().into_iter().flat_map(|(_, entries)| entries);
+        let columns = options.align.map(|max_path| Columns::measure(all, style, options, max_path));
+        for (title, entries) in self.buckets() {
+            writeln!(f, "{} ({}):", title, entries.len())?;
+            for entry in entries {
+                entry.write_line(f, style, options, columns.as_ref())?;
+                writeln!(f)?;
+            }
         }
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn buckets(&self) -> [(&'static str, &[DiffEntry]); 3] {
+        [
+            ("NEW", &self.new),
+            ("RESOLVED", &self.resolved),
+            ("PERSISTING", &self.persisting),
+        ]
+    }
+}
+
+impl fmt::Display for DiffOfDiffs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_buckets(f, OutputStyle::Symbols, &ValueRenderOptions::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(diff_type: DiffType, path: &str, old: Option<serde_json::Value>, new: Option<serde_json::Value>, line: usize) -> DiffEntry {
+        DiffEntry { old_value: old, new_value: new, ..DiffEntry::new(diff_type, path) }.with_lines(Some(line), Some(line))
+    }
+
+    fn result(entries: Vec<DiffEntry>) -> DiffResult {
+        DiffResult {
+            left_file: None,
+            right_file: None,
+            timestamp: None,
+            entries,
+            warnings: Vec::new(),
+            left_sha256: None,
+            right_sha256: None,
+            left_size: None,
+            right_size: None,
+            sources: Vec::new(),
+            annotations: Annotations::default(),
+            left_label: None,
+            right_label: None,
+            rule_stats: Vec::new(),
+            generator: String::new(),
+            hostname: None,
+            timings: None,
+            left_missing: false,
+            right_missing: false,
+            selections: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        let a = entry(DiffType::Modified, "$.user", Some(json!({"a": 1, "b": 2})), Some(json!(null)), 3);
+        let b = entry(DiffType::Modified, "$.user", Some(json!({"b": 2, "a": 1})), Some(json!(null)), 40);
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_eq!(a.fingerprint().len(), 16);
+
+        let other_type = entry(DiffType::ArrayItemChanged, "$.user", a.old_value.clone(), a.new_value.clone(), 3);
+        let other_value = entry(DiffType::Modified, "$.user", Some(json!({"a": 1})), Some(json!(null)), 3);
+        let missing_value = entry(DiffType::Modified, "$.user", Some(json!({"a": 1, "b": 2})), None, 3);
+        assert_ne!(a.fingerprint(), other_type.fingerprint());
+        assert_ne!(a.fingerprint(), other_value.fingerprint());
+        assert_ne!(a.fingerprint(), missing_value.fingerprint());
+
+        let removed = entry(DiffType::Removed, "$.user", Some(json!(1)), None, 3);
+        let from_old_report = entry(DiffType::Removed, "$.user", Some(json!(1)), Some(json!(null)), 3);
+        assert_eq!(removed.fingerprint(), from_old_report.fingerprint());
+        let ignored = entry(DiffType::Ignored, "$.user", None, None, 3);
+        let from_old_report = entry(DiffType::Ignored, "$.user", Some(json!(null)), Some(json!(null)), 3);
+        assert_eq!(ignored.fingerprint(), from_old_report.fingerprint());
+    }
+
+    #[test]
+    fn test_format_with_style() {
+        let result = result(vec![entry(DiffType::Removed, "$.age", Some(json!(30)), None, 3)]);
+        assert_eq!(result.format_with(OutputStyle::Readable), result.format_readable());
+        assert_eq!(result.format_with(OutputStyle::Symbols), result.to_string());
+        assert_eq!(DiffType::Removed.text(OutputStyle::Symbols), "-");
+        assert_eq!(OutputStyle::default(), OutputStyle::Readable);
+        assert_eq!(OutputStyle::Readable.next(), OutputStyle::Symbols);
+        assert_eq!(OutputStyle::Symbols.next().label(), "Readable");
+    }
+
+    #[test]
+    fn test_absent_and_null_values_render_differently() {
+        let modified = |old, new| entry(DiffType::Modified, "$.a", old, new, 1);
+        let cases = [
+            (modified(None, None), "~ $.a (L1:L1): <absent> -> <absent>"),
+            (modified(Some(json!(null)), Some(json!(1))), "~ $.a (L1:L1): null -> 1"),
+            (modified(Some(json!(1)), Some(json!(null))), "~ $.a (L1:L1): 1 -> null"),
+            (modified(Some(json!(null)), Some(json!(null))), "~ $.a (L1:L1): null -> null"),
+            (entry(DiffType::Removed, "$.a", None, None, 1), "- $.a (L1:L1)"),
+            (entry(DiffType::Removed, "$.a", Some(json!(null)), None, 1), "- $.a (L1:L1): null"),
+            (entry(DiffType::Added, "$.a", None, None, 1), "+ $.a (L1:L1)"),
+            (entry(DiffType::Added, "$.a", None, Some(json!(null)), 1), "+ $.a (L1:L1): null"),
+        ];
+
+        for (entry, expected) in &cases {
+            assert_eq!(&entry.to_string(), expected);
+            let readable = format!("[{}]{}", entry.diff_type.readable_text(), &expected[1..]);
+            assert_eq!(entry.format_readable(), readable);
+
+            // JSON leaves an absent value out and keeps a null one
+            let json = serde_json::to_value(entry).unwrap();
+            assert_eq!(json.get("old_value").cloned(), entry.old_value);
+            assert_eq!(json.get("new_value").cloned(), entry.new_value);
+            let parsed: DiffEntry = serde_json::from_value(json).unwrap();
+            assert_eq!(&parsed, entry);
+        }
+    }
+
+    #[test]
+    fn test_long_values_are_truncated() {
+        let long = "x".repeat(600);
+        let modified = entry(DiffType::Modified, "$.blob", Some(json!(long)), Some(json!("short")), 1);
+
+        // The JSON text of the string is 602 characters with its quotes
+        let expected = format!("[MODIFIED] $.blob (L1:L1): \"{}…(+90 chars) -> \"short\"", "x".repeat(511));
+        assert_eq!(modified.format_readable(), expected);
+        assert!(modified.to_string().ends_with("…(+90 chars) -> \"short\""));
+        assert!(result(vec![modified.clone()]).format_readable().contains("…(+90 chars)"));
+
+        let unlimited = ValueRenderOptions { max_value_chars: 0, ..ValueRenderOptions::default() };
+        let full = modified.format_with_options(OutputStyle::Readable, &unlimited);
+        assert_eq!(full, format!("[MODIFIED] $.blob (L1:L1): \"{}\" -> \"short\"", long));
+
+        // Characters are counted, not bytes
+        let tight = ValueRenderOptions { max_value_chars: 3, ..ValueRenderOptions::default() };
+        assert_eq!(tight.truncate("\"é€x\"".to_string()), "\"é€…(+2 chars)");
+        assert_eq!(tight.truncate("123".to_string()), "123");
+        // A character is what is shown as one: combining marks, flags and joined emoji are not split
+        assert_eq!(tight.truncate("\"e\u{301}e\u{301}s".to_string()), "\"e\u{301}e\u{301}…(+1 chars)");
+        assert_eq!(tight.truncate("\"🇯🇵🇫🇷\"".to_string()), "\"🇯🇵🇫🇷…(+1 chars)");
+        assert_eq!(tight.truncate("\"👨‍👩‍👧‍👦!\"".to_string()), "\"👨‍👩‍👧‍👦!…(+1 chars)");
+
+        // The JSON output keeps the value in full
+        let json = serde_json::to_value(&modified).unwrap();
+        assert_eq!(json["old_value"], json!(long));
+    }
+
+    #[test]
+    fn test_wrapped_entries() {
+        let options = ValueRenderOptions { width: 60, ..ValueRenderOptions::default() };
+        let modified = entry(DiffType::Modified, "$.title", Some(json!("the old title")), Some(json!("a new and longer title")), 3);
+        assert_eq!(
+            modified.format_with_options(OutputStyle::Readable, &options),
+            "[MODIFIED] $.title (L3:L3): \"the old title\"\n                            -> \"a new and longer title\""
+        );
+        // An arrow inside a value is not the one between the values
+        let arrows = entry(DiffType::Modified, "$.a", Some(json!("x -> y")), Some(json!("a longer value than fits")), 1);
+        assert_eq!(
+            arrows.format_with_options(OutputStyle::Readable, &options),
+            "[MODIFIED] $.a (L1:L1): \"x -> y\"\n                        -> \"a longer value than fits\""
+        );
+
+        // Aligned entries continue under their value column
+        let aligned = ValueRenderOptions { align: Some(20), ..options };
+        let text = result(vec![modified, entry(DiffType::Added, "$.tag", None, Some(json!("short")), 4)]).format_with_options(OutputStyle::Symbols, &aligned);
+        let lines: Vec<&str> = text.lines().skip_while(|line| !line.starts_with('~')).collect();
+        assert_eq!(lines, [
+            "~ $.title (L3:L3) \"the old title\"",
+            "                  -> \"a new and longer title\"",
+            "+ $.tag   (L4:L4) \"short\"",
+        ]);
+        // Width 0 leaves them whole
+        assert_eq!(result(vec![arrows.clone()]).format_with(OutputStyle::Readable).lines().last(), Some(arrows.format_readable().as_str()));
+    }
+
+    #[test]
+    fn test_preview_value() {
+        let product = json!({
+            "details": {"watts": 40}, "extras": {}, "name": "lamp", "price": 12.5, "tags": ["a", "b", "c"],
+        });
+        assert_eq!(preview_value(&product, 80), "{details{…}, extras{}, name, price, tags[3]}");
+        let items = json!([{"id": 1, "name": "a"}, {"id": 2, "name": "b"}, {"id": 3}]);
+        assert_eq!(preview_value(&items, 80), "[3 items: {id, name}, …]");
+        assert_eq!(preview_value(&json!([[1, 2], [3]]), 80), "[2 items: [2 items: 1, 2], …]");
+        assert_eq!(preview_value(&json!([1, "two", null]), 80), "[3 items: 1, \"two\", null]");
+        assert_eq!(preview_value(&json!([{"only": true}]), 80), "[1 item: {only}]");
+        assert_eq!(preview_value(&json!({}), 80), "{}");
+        assert_eq!(preview_value(&json!([]), 80), "[]");
+        assert_eq!(preview_value(&json!(3.5), 1), "3.5");
+
+        // Keys are listed in the object's order, so a value previews alike every time
+        assert_eq!(preview_value(&product, 80), preview_value(&product.clone(), 80));
+    }
+
+    #[test]
+    fn test_preview_budget() {
+        let product = json!({"details": {"watts": 40}, "name": "lamp", "price": 12.5, "tags": ["a", "b", "c"]});
+        assert_eq!(preview_value(&product, 24), "{details{…}, name, …}");
+        assert_eq!(preview_value(&product, 3), "{…}");
+        assert_eq!(preview_value(&json!([1, 2, 3, 4, 5, 6]), 18), "[6 items: 1, 2, …]");
+        assert_eq!(preview_value(&json!([{"id": 1}]), 12), "[1 item: …]");
+        assert_eq!(preview_value(&json!("こんにちは世界、これは長い説明文です。"), 20), "\"こんにちは世…\" (19 chars)");
+        assert_eq!(preview_value(&json!(["cafe\u{301}"; 5].join(" ")), 20), "\"cafe\u{301} c…\" (24 chars)");
+        for budget in 5..40 {
+            let preview = preview_value(&product, budget);
+            assert!(preview.chars().count() <= budget, "{} > {}", preview, budget);
+        }
+
+        // Strings keep as much as fits and give their length
+        let text = json!("lorem ipsum dolor sit amet");
+        assert_eq!(preview_value(&text, 30), "\"lorem ipsum dolor sit amet\"");
+        assert_eq!(preview_value(&text, 20), "\"lorem …\" (26 chars)");
+        assert_eq!(preview_value(&json!("line\nbreak and more"), 20), "\"line\\n…\" (19 chars)");
+    }
+
+    #[test]
+    fn test_preview_non_ascii_keys() {
+        let value = json!({"": 2, "a b": 1, "é": {"x": 1}, "タグ": [1, 2], "名前": "猫"});
+        assert_eq!(preview_value(&value, 80), "{\"\", \"a b\", é{…}, タグ[2], 名前}");
+        // Characters are counted, not bytes
+        assert_eq!(preview_value(&json!({"タグ": 1, "名前": 2}), 8), "{タグ, 名前}");
+        assert_eq!(preview_value(&json!({"タグ": 1, "名前": 2}), 7), "{タグ, …}");
+        assert_eq!(preview_value(&json!("猫".repeat(30)), 20), format!("\"{}…\" (30 chars)", "猫".repeat(6)));
+
+        let entry = DiffEntry::added("$.product", json!({"tags": [1], "名前": "猫"}));
+        let options = ValueRenderOptions { max_value_chars: 80, preview: true, ..ValueRenderOptions::default() };
+        assert_eq!(entry.format_with_options(OutputStyle::Readable, &options), "[ADDED] $.product: {tags[1], 名前}");
+    }
+
+    #[test]
+    fn test_generator_metadata() {
+        // Reports from before the generator was recorded still load
+        let old: DiffResult = serde_json::from_value(json!({
+            "left_file": "a.json", "right_file": "b.json", "timestamp": null, "entries": []
+        })).unwrap();
+        assert_eq!(old.generator, "");
+        assert_eq!(old.hostname, None);
+        assert_eq!(old.format_readable(), "DIFF-JSON v1\nLEFT: a.json\nRIGHT: b.json\n\n");
+
+        let mut result = DiffResult { generator: GENERATOR.to_string(), hostname: Some("build-7".to_string()), ..old };
+        assert_eq!(GENERATOR, format!("json-diff {}", env!("CARGO_PKG_VERSION")));
+        assert!(result.format_readable().ends_with(&format!("GENERATOR: {}\nHOSTNAME: build-7\n\n", GENERATOR)));
+
+        result.strip_metadata();
+        assert_eq!(result.format_readable(), "DIFF-JSON v1\n\n");
+    }
+
+    #[test]
+    fn test_diff_against_overlapping() {
+        let kept = entry(DiffType::Modified, "$.name", Some(json!("John")), Some(json!("Jane")), 2);
+        let fixed = entry(DiffType::Removed, "$.age", Some(json!(30)), None, 3);
+        let added = entry(DiffType::Added, "$.email", None, Some(json!("j@example.com")), 4);
+
+        // The persisting entry moved down a few lines, which must not make it new
+        let mut moved = kept.clone();
+        moved.left_line = Some(12);
+        moved.right_line = Some(14);
+
+        let previous = result(vec![kept, fixed.clone()]);
+        let current = result(vec![moved.clone(), added.clone()]);
+
+        let buckets = current.diff_against(&previous);
+        assert_eq!(buckets.new, vec![added]);
+        assert_eq!(buckets.resolved, vec![fixed]);
+        assert_eq!(buckets.persisting, vec![moved]);
+        assert!(buckets.format_readable().starts_with("NEW (1):\n[ADDED] $.email"));
+    }
+
+    #[test]
+    fn test_diff_against_disjoint() {
+        let previous = result(vec![entry(DiffType::Removed, "$.a", Some(json!(1)), None, 1)]);
+        let current = result(vec![entry(DiffType::Removed, "$.a", Some(json!(2)), None, 1)]);
+
+        let buckets = current.diff_against(&previous);
+        assert_eq!(buckets.new.len(), 1);
+        assert_eq!(buckets.resolved.len(), 1);
+        assert!(buckets.persisting.is_empty());
+    }
+
+    #[test]
+    fn test_entry_iterators() {
+        let mut result = result(vec![
+            entry(DiffType::Added, "$.a", None, Some(json!(1)), 1),
+            entry(DiffType::Removed, "$.b", Some(json!(2)), None, 2),
+        ]);
+        result.extend([entry(DiffType::Added, "$.c", None, Some(json!(3)), 3)]);
+
+        assert_eq!(result.iter().count(), 3);
+        assert_eq!(result.entries().len(), 3);
+
+        let added: Vec<&str> = result.iter_type(DiffType::Added).map(|e| e.path.as_str()).collect();
+        assert_eq!(added, vec!["$.a", "$.c"]);
+        assert_eq!(result.count(DiffType::Added), 2);
+        assert_eq!(result.count(DiffType::Ignored), 0);
+
+        let mut paths = Vec::new();
+        for entry in &result {
+            paths.push(entry.path.clone());
+        }
+        assert_eq!(paths, vec!["$.a", "$.b", "$.c"]);
+
+        let owned = result.clone().into_entries();
+        assert_eq!(owned.len(), 3);
+        assert_eq!(result.into_iter().last().unwrap().path, "$.c");
+    }
+
+    #[test]
+    fn test_merge_groups_by_source() {
+        let mut first = result(vec![
+            entry(DiffType::Modified, "$.a", Some(json!(1)), Some(json!(2)), 1),
+            entry(DiffType::Removed, "$.b", Some(json!(2)), None, 2),
+        ]);
+        first.left_file = Some(PathBuf::from("a1.json"));
+        first.right_file = Some(PathBuf::from("a2.json"));
+        first.warnings.push("something odd".to_string());
+
+        let second = result(vec![entry(DiffType::Added, "$.c", None, Some(json!(3)), 1)]);
+
+        let merged = DiffResult::merge(vec![first, second]);
+        assert_eq!(merged.entries().len(), 3);
+        assert_eq!(merged.sources.len(), 2);
+        assert_eq!(merged.sources[0].entry_count, 2);
+        assert_eq!(merged.sources[1].source, EntrySource::Record { key: "1".to_string() });
+        assert_eq!(merged.entries()[2].source, Some(EntrySource::Record { key: "1".to_string() }));
+        assert_eq!(merged.warnings, vec!["a1.json <-> a2.json: something odd"]);
+
+        assert_eq!(merged.format_readable(), concat!(
+            "DIFF-JSON v1\n",
+            "GENERATOR: json-diff ", env!("CARGO_PKG_VERSION"), "\n",
+            "\n",
+            "SOURCE: a1.json <-> a2.json (2 entries)\n",
+            "[MODIFIED] $.a (L1:L1): 1 -> 2\n",
+            "[REMOVED] $.b (L2:L2): 2\n",
+            "\n",
+            "SOURCE: record 1 (1 entries)\n",
+            "[ADDED] $.c (L1:L1): 3\n",
+        ));
+
+        // Merging a merged result keeps the original sources
+        let remerged = DiffResult::merge(vec![merged.clone()]);
+        assert_eq!(remerged.sources, merged.sources);
+        assert_eq!(remerged.entries(), merged.entries());
+
+        let json = serde_json::to_value(&merged).unwrap();
+        assert_eq!(json["entries"][0]["source"]["file_pair"]["left"], "a1.json");
+        assert_eq!(json["sources"][1]["source"]["record"]["key"], "1");
+    }
+
+    #[test]
+    fn test_entry_constructors() {
+        let added = DiffEntry::added("$.a", json!(1)).with_lines(None, Some(3));
+        assert_eq!((added.diff_type.clone(), added.old_value.clone(), added.new_value.clone()), (DiffType::Added, None, Some(json!(1))));
+        assert_eq!((added.left_line, added.right_line), (None, Some(3)));
+
+        let removed = DiffEntry::removed("$.b", json!(null));
+        assert_eq!((removed.old_value.clone(), removed.new_value.clone()), (Some(json!(null)), None));
+
+        let modified = DiffEntry::modified("$.c", json!("x"), json!("y"));
+        assert_eq!(modified.to_string(), "~ $.c: \"x\" -> \"y\"");
+
+        let ignored = DiffEntry::ignored("$.d");
+        assert_eq!((ignored.diff_type.clone(), ignored.old_value.clone()), (DiffType::Ignored, None));
+
+        for entry in [&added, &removed, &modified, &ignored] {
+            entry.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_control_characters_are_escaped() {
+        assert_eq!(escape_control("plain"), "plain");
+        assert_eq!(escape_control("a\nb\tc\r\u{1b}[31m"), "a\\nb\\tc\\r\\u{1b}[31m");
+
+        let mut result = DiffResult::builder().build().unwrap();
+        result.entries = vec![
+            DiffEntry::added("$['line\nbreak']", json!("x\ny")),
+            DiffEntry::modified("$['tab\there']", json!(1), json!(2)),
+            DiffEntry::removed("$['\u{1b}[31mred']", json!(true)),
+        ];
+        for text in [result.format_readable(), result.format_with(OutputStyle::Symbols)] {
+            assert!(!text.contains('\u{1b}') && !text.contains('\t'));
+            assert_eq!(text.lines().filter(|line| line.contains("$[")).count(), 3);
+        }
+        assert!(result.format_readable().contains("[ADDED] $['line\\nbreak']: \"x\\ny\""));
+        assert_eq!(result.entries()[1].to_string(), "~ $['tab\\there']: 1 -> 2");
+
+        // The JSON output holds the path as it is, escaped the way JSON escapes it
+        let json = serde_json::to_string(&result).unwrap();
+        assert!(json.contains(r#""$['line\nbreak']""#) && json.contains(r#""$['\u001b[31mred']""#));
+    }
+
+    #[test]
+    fn test_top_paths() {
+        let mut result = DiffResult::builder().build().unwrap();
+        result.entries = vec![
+            DiffEntry::modified("$.items[0].price", json!(1), json!(2)),
+            DiffEntry::added("$.items[1]", json!({})),
+            DiffEntry::added("$.items[2]", json!({})),
+            DiffEntry::removed("$.config.a", json!(1)),
+            DiffEntry::modified("$.config.b", json!(1), json!(2)),
+            DiffEntry::modified("$.name", json!("a"), json!("b")),
+            DiffEntry::ignored("$.meta.ts"),
+            DiffEntry::ignored("$.meta.id"),
+            DiffEntry::ignored("$.meta.host"),
+        ];
+
+        let top = |depth, limit| -> Vec<(String, usize, DiffType)> {
+            result.top_paths(depth, limit).into_iter().map(|p| (p.path, p.count, p.dominant_type)).collect()
+        };
+        // Most first, ties by path, the first type of a tie dominant; ignored left out
+        assert_eq!(top(1, 10), [
+            ("$.items".to_string(), 3, DiffType::Added),
+            ("$.config".to_string(), 2, DiffType::Removed),
+            ("$.name".to_string(), 1, DiffType::Modified),
+        ]);
+        assert_eq!(top(1, 2).len(), 2);
+        assert_eq!(top(2, 1), [("$.config.a".to_string(), 1, DiffType::Removed)]);
+        assert_eq!(top(0, 10), [("$".to_string(), 6, DiffType::Modified)]);
+        assert!(top(1, 0).is_empty());
+    }
+
+    #[test]
+    fn test_classification() {
+        let mut result = DiffResult::builder().build().unwrap();
+        assert_eq!(result.classification(), DiffClassification::Identical);
+        result.entries = vec![DiffEntry::ignored("$.ts"), DiffEntry::tolerated("$.price", json!(1.0), json!(1.01), "tolerance 0.1")];
+        assert_eq!(result.classification(), DiffClassification::OnlyIgnored);
+        result.entries.push(DiffEntry::removed("$.id", json!(1)));
+        assert_eq!(result.classification(), DiffClassification::Different);
+        assert_eq!(serde_json::to_value(DiffClassification::OnlyIgnored).unwrap(), json!("only_ignored"));
+    }
+
+    #[test]
+    fn test_line_similarity() {
+        let mut result = DiffResult::builder().build().unwrap();
+        assert_eq!(result.line_similarity(0, 0), 100.0);
+        result.entries = vec![
+            DiffEntry::modified("$.a", json!(1), json!(2)).with_lines(Some(2), Some(2)),
+            // Its left line is only where it would go
+            DiffEntry::added("$.b", json!(1)).with_lines(Some(2), Some(3)),
+            DiffEntry::ignored("$.c").with_lines(Some(4), Some(5)),
+        ];
+        assert_eq!(result.line_similarity(5, 5), 70.0);
+
+        // The same lines of another source are other lines
+        let merged = DiffResult::merge(vec![result.clone(), result]);
+        assert_eq!(merged.line_similarity(10, 10), 70.0);
+    }
+
+    #[test]
+    fn test_numeric_delta() {
+        let delta = |old: serde_json::Value, new: serde_json::Value| {
+            let mut entry = DiffEntry::modified("$.price", old, new);
+            entry.record_delta();
+            entry
+        };
+
+        let up = delta(json!(10.99), json!(12.99));
+        assert!((up.delta.unwrap() - 2.0).abs() < 1e-9 && (up.delta_pct.unwrap() - 18.198).abs() < 1e-3, "{:?}", up);
+        assert_eq!(up.format_readable(), "[MODIFIED] $.price: 10.99 -> 12.99 (+2.00, +18.2%)");
+
+        let down = delta(json!(-40), json!(-50));
+        assert_eq!((down.delta, down.delta_pct), (Some(-10.0), Some(-25.0)));
+        assert_eq!(down.to_string(), "~ $.price: -40 -> -50 (-10.00, -25.0%)");
+
+        // No percentage of a zero
+        let from_zero = delta(json!(0), json!(3));
+        assert_eq!((from_zero.delta, from_zero.delta_pct), (Some(3.0), None));
+        assert_eq!(from_zero.to_string(), "~ $.price: 0 -> 3 (+3.00)");
+
+        for (old, new) in [(json!("10"), json!(12)), (json!(1), json!(null)), (json!([1]), json!([2]))] {
+            let entry = delta(old, new);
+            assert_eq!((entry.delta, entry.delta_pct), (None, None), "{}", entry);
+        }
+        let mut added = DiffEntry::added("$.price", json!(5));
+        added.record_delta();
+        assert_eq!(added.delta, None);
+
+        let mut result = result(vec![from_zero, delta(json!("a"), json!("b")), down, up]);
+        result.sort_by_delta();
+        let deltas: Vec<Option<f64>> = result.entries.iter().map(|entry| entry.delta).collect();
+        assert_eq!(deltas[..3], [Some(-10.0), Some(3.0), Some(2.0)]);
+        assert_eq!(deltas[3], None);
+        let json = serde_json::to_value(&result.entries[1]).unwrap();
+        assert_eq!((json["delta"].as_f64(), json.get("delta_pct")), (Some(3.0), None));
+    }
+
+    #[test]
+    fn test_near_match() {
+        let mut typo = DiffEntry::modified("$.name", json!("Jonh"), json!("John"));
+        typo.record_near_match(0.5);
+        assert!(typo.near_match);
+        assert_eq!(typo.format_readable(), "[MODIFIED] $.name: \"Jonh\" -> \"John\" (≈)");
+        assert_eq!(serde_json::to_value(&typo).unwrap()["near_match"], true);
+
+        let mut rewrite = DiffEntry::modified("$.name", json!("Jonh"), json!("Mary"));
+        rewrite.record_near_match(0.5);
+        assert!(!rewrite.near_match);
+        assert_eq!(rewrite.to_string(), "~ $.name: \"Jonh\" -> \"Mary\"");
+        assert!(serde_json::to_value(&rewrite).unwrap().get("near_match").is_none());
+
+        // Only strings are compared
+        let mut number = DiffEntry::modified("$.n", json!(1), json!(1.0001));
+        number.record_near_match(0.0);
+        assert!(!number.near_match);
+    }
+
+    #[test]
+    fn test_validate_rejects_inconsistent_entries() {
+        let reason = |entry: DiffEntry| match entry.validate() {
+            Err(JsonDiffError::InvalidEntry { reason, .. }) => reason,
+            other => panic!("{:?}", other),
+        };
+
+        let mut modified = DiffEntry::modified("$.a", json!(1), json!(2));
+        modified.new_value = None;
+        assert_eq!(reason(modified.clone()), "MODIFIED entries need their new value");
+        // Arrays left out as too large are described by their summary instead
+        modified.old_value = None;
+        modified.array_summary = Some(ArraySummary::new(&[json!(1)], &[json!(2)]));
+        modified.validate().unwrap();
+
+        let mut added = DiffEntry::added("$.a", json!(1));
+        added.old_value = Some(json!(0));
+        assert_eq!(reason(added), "ADDED entries have no old value");
+
+        let moved = entry(DiffType::Moved, "$.items[0]", Some(json!(1)), None, 1);
+        assert_eq!(reason(moved), "MOVED entries need the index they moved to");
+        assert_eq!(reason(DiffEntry::ignored("items")), "the path must start with $ or be a <line N> of a line diff");
+        assert_eq!(reason(DiffEntry::ignored("<line 0>")), "the path must start with $ or be a <line N> of a line diff");
+        assert!(DiffEntry::removed("<line 3>", json!("x")).validate().is_ok());
+    }
+
+    #[test]
+    fn test_result_builder() {
+        let timestamp = || serde_json::from_value::<Timestamp>(json!("2024-01-01T00:00:00Z")).unwrap();
+        let result = DiffResult::builder()
+            .files("before/a.json", "after/a.json")
+            .timestamp(timestamp())
+            .entry(DiffEntry::added("$.a", json!(1)))
+            .entries([DiffEntry::ignored("$.b")])
+            .warning("converted")
+            .build()
+            .unwrap();
+        assert_eq!(result.left_label.as_deref(), Some("a.json"));
+        assert_eq!(result.right_file.as_deref(), Some(std::path::Path::new("after/a.json")));
+        assert_eq!(result.timestamp, Some(timestamp()));
+        assert_eq!(result.entries().len(), 2);
+        assert_eq!(result.warnings, ["converted"]);
+        assert_eq!(result.generator, GENERATOR);
+
+        let labelled = DiffResult::builder().files("a.json", "b.json").labels("old", "new").build().unwrap();
+        assert_eq!((labelled.left_label.as_deref(), labelled.right_label.as_deref()), (Some("old"), Some("new")));
+
+        let mut broken = DiffEntry::removed("$.c", json!(1));
+        broken.new_value = Some(json!(2));
+        let error = DiffResult::builder().entry(broken).build().unwrap_err();
+        assert_eq!(error.to_string(), "Invalid entry at $.c: REMOVED entries have no new value");
+    }
+
+    #[test]
+    fn test_normalize() {
+        let source = |key: &str| Some(EntrySource::Record { key: key.to_string() });
+        let mut from_other_source = DiffEntry::added("$.b", json!(2)).with_lines(None, Some(2));
+        from_other_source.source = source("2");
+        let mut removed_b = DiffEntry::removed("$.b", json!(1)).with_lines(Some(2), None);
+        removed_b.source = source("1");
+        let mut added_b = DiffEntry::added("$.b", json!(3)).with_lines(None, Some(4));
+        added_b.source = source("1");
+
+        let mut result = result(vec![
+            entry(DiffType::Modified, "$.z", Some(json!(1)), Some(json!(2)), 9),
+            added_b,
+            DiffEntry::added("$.a", json!("new")).with_lines(None, Some(1)),
+            from_other_source.clone(),
+            DiffEntry::ignored("$.id"),
+            removed_b,
+            DiffEntry::removed("$.a", json!("old")).with_lines(Some(1), None),
+            entry(DiffType::Modified, "$.z", Some(json!(1)), Some(json!(2)), 9),
+            DiffEntry::ignored("$.id"),
+        ]);
+        result.normalize();
+
+        let mut modified_b = DiffEntry::modified("$.b", json!(1), json!(3)).with_lines(Some(2), Some(4));
+        modified_b.source = source("1");
+        assert_eq!(result.entries, [
+            DiffEntry::modified("$.a", json!("old"), json!("new")).with_lines(Some(1), Some(1)),
+            from_other_source,
+            modified_b,
+            entry(DiffType::Modified, "$.z", Some(json!(1)), Some(json!(2)), 9),
+            DiffEntry::ignored("$.id"),
+        ]);
+
+        // Normalizing again changes nothing
+        let normalized = result.entries.clone();
+        result.normalize();
+        assert_eq!(result.entries, normalized);
+    }
+
+    #[test]
+    fn test_assert_invariants() {
+        let dir = tempfile::tempdir().unwrap();
+        let left = dir.path().join("left.json");
+        std::fs::write(&left, "{\n  \"a\": 1\n}").unwrap();
+        let mut result = result(vec![
+            DiffEntry::modified("$.a", json!(1), json!(2)).with_lines(Some(2), Some(2)),
+            DiffEntry::added("$.b", json!(1)).with_lines(Some(3), Some(9)),
+        ]);
+        result.left_file = Some(left);
+        result.assert_invariants();
+
+        // Every breach is named, not only the first
+        result.entries[0].new_value = None;
+        result.entries[1].left_line = Some(4);
+        result.entries.push(DiffEntry::added("$.b", json!(2)));
+        let problem = std::panic::catch_unwind(|| result.assert_invariants()).unwrap_err();
+        let problem = problem.downcast_ref::<String>().unwrap();
+        assert!(problem.contains("Invalid entry at $.a: MODIFIED entries need their new value"), "{}", problem);
+        assert!(problem.contains("ADDED $.b: left line 4 outside the file's 3 lines"), "{}", problem);
+        assert!(problem.contains("ADDED $.b: reported twice"), "{}", problem);
+
+        // An ignored value is reported once, with nothing at or beneath it
+        let mut ignored = self::result(vec![
+            DiffEntry::ignored("$.meta"),
+            DiffEntry::ignored("$.meta"),
+            DiffEntry::ignored("$.meta.ts"),
+            DiffEntry::modified("$.meta", json!(1), json!(2)),
+            DiffEntry::modified("$.metadata", json!(1), json!(2)),
+        ]);
+        let problem = std::panic::catch_unwind(|| ignored.assert_invariants()).unwrap_err();
+        let problem = problem.downcast_ref::<String>().unwrap();
+        assert!(problem.contains("IGNORED $.meta: reported twice"), "{}", problem);
+        assert!(problem.contains("IGNORED $.meta.ts: reported at or beneath the ignored $.meta"), "{}", problem);
+        assert!(problem.contains("MODIFIED $.meta: reported at or beneath the ignored $.meta"), "{}", problem);
+        assert!(!problem.contains("$.metadata"), "{}", problem);
+        ignored.entries.drain(1..4);
+        ignored.assert_invariants();
+
+        // The same entry from two sources is not a repeat
+        let mut merged = DiffResult::merge(vec![result.clone(), result]);
+        merged.entries.retain(|entry| entry.path == "$.b" && entry.new_value == Some(json!(1)));
+        merged.assert_invariants();
+    }
+
+    #[test]
+    fn test_renormalize() {
+        let left = r#"{"ids": [1, 2], "meta": {"ts": 1}, "name": "a", "old": 1, "price": 10.0}"#;
+        let right = r#"{"ids": [1, 3], "meta": {"ts": 2}, "name": "b", "new": 1, "price": 10.004}"#;
+        let options = CompareOptions { identify_array_item_changes: false, ..CompareOptions::default() };
+        let saved = serde_json::to_string(&crate::compare_strings(left, right, &options).unwrap()).unwrap();
+        let mut result: DiffResult = serde_json::from_str(&saved).unwrap();
+        result.sources.push(SourceInfo { source: EntrySource::Record { key: "0".to_string() }, left_sha256: None, right_sha256: None, entry_count: 9 });
+        let before = result.entries.clone();
+
+        let new_rules = CompareOptions {
+            tolerances: vec![(crate::JsonPath::new("$.price").unwrap(), 0.01)],
+            ignore_paths: vec![crate::JsonPath::new("$.meta.ts").unwrap()],
+            ..CompareOptions::default()
+        };
+        result.renormalize(&new_rules).unwrap();
+        let paths: Vec<_> = result.iter().map(|entry| (entry.path.as_str(), entry.diff_type.clone())).collect();
+        assert_eq!(paths, [
+            ("$.old", DiffType::Removed),
+            ("$.ids", DiffType::Modified),
+            ("$.meta.ts", DiffType::Ignored),
+            ("$.name", DiffType::Modified),
+            ("$.new", DiffType::Added),
+        ]);
+        let ignored = result.iter().find(|entry| entry.diff_type == DiffType::Ignored).unwrap();
+        assert_eq!(ignored.left_line, before.iter().find(|entry| entry.path == "$.meta.ts").unwrap().left_line);
+        assert_eq!(result.warnings, ["$.ids: values left out of the report, not compared again"]);
+        assert_eq!(result.sources[0].entry_count, 0);
+
+        // Reported tolerated values are kept, with the rule that accepts them
+        let mut result: DiffResult = serde_json::from_str(&saved).unwrap();
+        result.renormalize(&CompareOptions { report_tolerated: true, ..new_rules }).unwrap();
+        let price = result.iter().find(|entry| entry.path == "$.price").unwrap();
+        assert_eq!((price.diff_type.clone(), price.reason.as_deref()), (DiffType::Tolerated, Some("tolerance 0.01")));
+        assert_eq!(price.old_value, Some(json!(10.0)));
+    }
+}
@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -5,12 +6,125 @@ pub enum JsonDiffError {
     #[error("Failed to parse JSON: {0}")]
     JsonParseError(#[from] serde_json::Error),
     
+    /// One or both documents of a comparison are not valid JSON
+    #[error("Failed to parse JSON: {}", unparseable_sides(.left, .right))]
+    Unparseable {
+        /// Why the left document could not be parsed, if it could not
+        left: Option<String>,
+        right: Option<String>,
+    },
+
     #[error("Failed to read file: {0}")]
     IoError(#[from] std::io::Error),
     
-    #[error("Invalid JSON path: {0}")]
-    InvalidPath(String),
+    #[error("Invalid JSON path {path:?} at byte {position}: {reason}")]
+    InvalidPath {
+        path: String,
+        /// Byte offset of the problem in `path`
+        position: usize,
+        reason: String,
+    },
     
+    #[error("Invalid query {query:?} at byte {position}: {reason}")]
+    InvalidQuery {
+        query: String,
+        /// Byte offset of the problem in `query`
+        position: usize,
+        reason: String,
+    },
+
     #[error("Invalid regex pattern: {0}")]
     RegexError(#[from] regex::Error),
-}
\ No newline at end of file
+
+    #[error("Invalid {side} root {path}: {reason}")]
+    InvalidRoot {
+        /// `left` or `right`
+        side: &'static str,
+        path: String,
+        reason: String,
+    },
+
+    #[error("Invalid mapping {left} -> {right}: {reason}")]
+    InvalidMapping {
+        left: String,
+        right: String,
+        reason: String,
+    },
+
+    #[error("Invalid entry at {path}: {reason}")]
+    InvalidEntry {
+        path: String,
+        reason: String,
+    },
+
+    #[error("Invalid schema: {0}")]
+    InvalidSchema(String),
+
+    #[error("Invalid table: {0}")]
+    InvalidTable(String),
+
+    /// A profile that is not valid TOML or JSON, or whose rules do not fit together
+    #[error("Failed to parse profile: {0}")]
+    InvalidProfile(String),
+
+    #[error("Streaming comparison failed: {0}")]
+    StreamError(String),
+
+    #[error("Failed to apply JSON patch: {0}")]
+    InvalidPatch(String),
+
+    #[error("No such file {}{}", path.display(), suggestion.as_ref().map(|s| format!("; did you mean {}?", s.display())).unwrap_or_default())]
+    FileNotFound {
+        path: PathBuf,
+        /// A similarly named file in the same directory
+        suggestion: Option<PathBuf>,
+    },
+
+    #[error("{} is a directory, not a JSON file", path.display())]
+    IsDirectory { path: PathBuf },
+
+    #[error("Permission denied reading {}", path.display())]
+    PermissionDenied { path: PathBuf },
+
+    /// The comparison stopped because it went past one of the limits of its options
+    #[error("Comparison stopped: {}", exceeded_limit(limit, *.value))]
+    LimitExceeded {
+        /// The option, `max_nodes`, `max_entries` or `max_duration`
+        limit: &'static str,
+        /// The limit, in milliseconds for `max_duration`
+        value: u64,
+    },
+
+    /// A comparison reported two entries of the same type at one path, which is a bug;
+    /// only with `CompareOptions::strict_invariants` and in debug builds
+    #[error("Internal error: {diff_type} entry at {path} reported twice")]
+    DuplicateEntry {
+        path: String,
+        diff_type: &'static str,
+    },
+
+    #[error("Input {} is too large ({size} bytes, limit is {limit} bytes); raise the limit with --max-input-size", path.display())]
+    InputTooLarge {
+        path: PathBuf,
+        /// Size of the input, or the bytes read before giving up for streams of unknown length
+        size: u64,
+        limit: u64,
+    },
+}
+
+fn exceeded_limit(limit: &str, value: u64) -> String {
+    match limit {
+        "max_nodes" => format!("visited more than {} values (max_nodes)", value),
+        "max_entries" => format!("found more than {} differences (max_entries)", value),
+        "max_duration" => format!("ran longer than {} ms (max_duration)", value),
+        _ => format!("{} of {} exceeded", limit, value),
+    }
+}
+
+fn unparseable_sides(left: &Option<String>, right: &Option<String>) -> String {
+    let sides: Vec<String> = [("left", left), ("right", right)]
+        .into_iter()
+        .filter_map(|(side, error)| Some(format!("{}: {}", side, error.as_ref()?)))
+        .collect();
+    sides.join("; ")
+}
@@ -0,0 +1,331 @@
+//! Standalone HTML report of a diff result
+//!
+//! Each entry row links to the corresponding line in the left and right sources.
+//! When sources are embedded, they are rendered in collapsible panes with every
+//! line anchored (`#left-L12`, `#right-L14`) and the lines touched by an entry
+//! highlighted. A source larger than [`HtmlOptions::max_embed_bytes`] is embedded
+//! as snippets of [`HtmlOptions::context_lines`] lines around each difference instead.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::diff::{escape_control, DiffEntry, DiffResult, DiffType};
+use crate::lenient::format_value;
+
+/// Options for the HTML report
+#[derive(Debug, Clone)]
+pub struct HtmlOptions {
+    /// Whether to embed the source files in the report
+    pub embed_sources: bool,
+    /// Largest source embedded in full; larger sources are embedded as snippets
+    pub max_embed_bytes: usize,
+    /// Lines of context shown around each difference in snippets
+    pub context_lines: usize,
+}
+
+impl Default for HtmlOptions {
+    fn default() -> Self {
+        Self {
+            embed_sources: false,
+            max_embed_bytes: 1 << 20,
+            context_lines: 3,
+        }
+    }
+}
+
+/// The two sources of a comparison, as text
+#[derive(Debug, Clone, Copy)]
+pub struct HtmlSources<'a> {
+    pub left: &'a str,
+    pub right: &'a str,
+}
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; width: 100%; }
+th, td { border: 1px solid #ddd; padding: 4px 8px; text-align: left; vertical-align: top; }
+td.value { font-family: monospace; white-space: pre-wrap; }
+tr.added { background: #e6ffed; }
+tr.removed { background: #ffeef0; }
+tr.modified, tr.array_item_changed { background: #fff5b1; }
+tr.ignored { color: #888; }
+tr.tolerated { color: #57606a; background: #f1f8ff; }
+pre { margin: 0; }
+pre span.line { display: block; }
+pre span.line.diff { background: #fff5b1; }
+pre span.line:target { background: #ffd33d; }
+pre span.gap { display: block; color: #888; }
+span.lineno { display: inline-block; width: 5em; color: #888; user-select: none; }
+";
+
+/// Render a diff result as a standalone HTML document
+///
+/// Sources are only embedded when `options.embed_sources` is set and `sources` is given.
+pub fn format_html(result: &DiffResult, sources: Option<HtmlSources<'_>>, options: &HtmlOptions) -> String {
+    let mut html = String::new();
+
+    // Writing to a String cannot fail
+    let _ = write_document(&mut html, result, sources.filter(|_| options.embed_sources), options);
+
+    html
+}
+
+fn write_document(
+    html: &mut String,
+    result: &DiffResult,
+    sources: Option<HtmlSources<'_>>,
+    options: &HtmlOptions,
+) -> std::fmt::Result {
+    writeln!(html, "<!DOCTYPE html>")?;
+    writeln!(html, "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>JSON Diff Report</title>")?;
+    writeln!(html, "<style>\n{}</style>\n</head>\n<body>", STYLE)?;
+    writeln!(html, "<h1>JSON Diff Report</h1>")?;
+
+    writeln!(html, "<table class=\"header\">")?;
+    let file_name = |path: &Option<std::path::PathBuf>| path.as_ref().map(|p| p.display().to_string());
+    for (label, value) in [
+        ("Left", file_name(&result.left_file)),
+        ("Right", file_name(&result.right_file)),
+        ("Left SHA-256", result.left_sha256.clone()),
+        ("Right SHA-256", result.right_sha256.clone()),
+        ("Timestamp", result.timestamp_rfc3339()),
+        ("Generator", Some(result.generator.clone()).filter(|generator| !generator.is_empty())),
+        ("Hostname", result.hostname.clone()),
+    ] {
+        if let Some(value) = value {
+            writeln!(html, "<tr><th>{}</th><td>{}</td></tr>", label, escape(&escape_control(&value)))?;
+        }
+    }
+    writeln!(html, "</table>")?;
+
+    writeln!(html, "<h2>Differences ({})</h2>", result.entries().len())?;
+    writeln!(html, "<table class=\"entries\">")?;
+    writeln!(html, "<tr><th>Type</th><th>Path</th><th>Left</th><th>Right</th><th>Old value</th><th>New value</th></tr>")?;
+    for (index, entry) in result.iter().enumerate() {
+        write_entry_row(html, index, entry)?;
+    }
+    writeln!(html, "</table>")?;
+
+    if let Some(sources) = sources {
+        writeln!(html, "<h2>Sources</h2>")?;
+        let left_lines: BTreeSet<usize> = result.iter().filter_map(|e| e.left_line).collect();
+        let right_lines: BTreeSet<usize> = result.iter().filter_map(|e| e.right_line).collect();
+        write_source(html, "left", "Left", sources.left, &left_lines, options)?;
+        write_source(html, "right", "Right", sources.right, &right_lines, options)?;
+    }
+
+    writeln!(html, "</body>\n</html>")
+}
+
+fn write_entry_row(html: &mut String, index: usize, entry: &DiffEntry) -> std::fmt::Result {
+    let line_link = |side: &str, line: Option<usize>| match line {
+        Some(line) => format!("<a href=\"#{}-L{}\">L{}</a>", side, line, line),
+        None => String::new(),
+    };
+    // A modification with a side missing says so; added and removed entries leave the missing side blank
+    let has_both_sides = matches!(entry.diff_type, DiffType::Modified | DiffType::ArrayItemChanged | DiffType::Tolerated | DiffType::KeyOrderChanged);
+    let value = |value: &Option<serde_json::Value>, is_old: bool| match value {
+        Some(v) => escape(&format_value(v, false)),
+        None if has_both_sides => escape(&entry.missing_value_text(is_old)),
+        None => String::new(),
+    };
+    let kind = match entry.near_match {
+        true => format!("{} <span title=\"near match\">≈</span>", entry.diff_type.readable_text()),
+        false => entry.diff_type.readable_text().to_string(),
+    };
+
+    writeln!(
+        html,
+        "<tr id=\"entry-{}\" class=\"{}\"><td>{}</td><td><a href=\"#entry-{}\">{}</a></td><td>{}</td><td>{}</td><td class=\"value\">{}</td><td class=\"value\">{}</td></tr>",
+        index,
+        entry.diff_type.readable_text().to_lowercase(),
+        kind,
+        index,
+        escape(&escape_control(&entry.path)),
+        line_link("left", entry.left_line),
+        line_link("right", entry.right_line),
+        value(&entry.old_value, true),
+        value(&entry.new_value, false),
+    )
+}
+
+fn write_source(
+    html: &mut String,
+    side: &str,
+    title: &str,
+    source: &str,
+    diff_lines: &BTreeSet<usize>,
+    options: &HtmlOptions,
+) -> std::fmt::Result {
+    let lines: Vec<&str> = source.lines().collect();
+    let is_snippet = source.len() > options.max_embed_bytes;
+    let mode = if is_snippet { "snippet" } else { "full" };
+
+    writeln!(html, "<details open class=\"source {}\">", mode)?;
+    if is_snippet {
+        writeln!(html, "<summary>{} (context around differences, source exceeds {} bytes)</summary>", title, options.max_embed_bytes)?;
+    } else {
+        writeln!(html, "<summary>{}</summary>", title)?;
+    }
+    writeln!(html, "<pre>")?;
+
+    let ranges = if is_snippet {
+        snippet_ranges(diff_lines, options.context_lines, lines.len())
+    } else {
+        vec![(1, lines.len())]
+    };
+
+    for (i, (start, end)) in ranges.iter().enumerate() {
+        if is_snippet && (i > 0 || *start > 1) {
+            writeln!(html, "<span class=\"gap\">…</span>")?;
+        }
+        for number in *start..=*end {
+            let class = if diff_lines.contains(&number) { "line diff" } else { "line" };
+            writeln!(
+                html,
+                "<span id=\"{}-L{}\" class=\"{}\"><span class=\"lineno\">{}</span>{}</span>",
+                side, number, class, number, escape(lines[number - 1])
+            )?;
+        }
+    }
+    if is_snippet && ranges.last().is_some_and(|(_, end)| *end < lines.len()) {
+        writeln!(html, "<span class=\"gap\">…</span>")?;
+    }
+
+    writeln!(html, "</pre>\n</details>")
+}
+
+/// Merge the context windows around the given 1-based lines into inclusive ranges
+fn snippet_ranges(diff_lines: &BTreeSet<usize>, context: usize, line_count: usize) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+
+    for &line in diff_lines.iter().filter(|l| (1..=line_count).contains(*l)) {
+        let start = line.saturating_sub(context).max(1);
+        let end = (line + context).min(line_count);
+
+        match ranges.last_mut() {
+            Some((_, last_end)) if start <= *last_end + 1 => *last_end = (*last_end).max(end),
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compare_strings, CompareOptions};
+
+    fn document(values: &[(&str, i32)]) -> String {
+        let fields: Vec<String> = values.iter()
+            .map(|(key, value)| format!("  \"{}\": {}", key, value))
+            .collect();
+        format!("{{\n{}\n}}\n", fields.join(",\n"))
+    }
+
+    fn sources() -> (String, String) {
+        let keys: Vec<String> = (0..40).map(|i| format!("field{}", i)).collect();
+        let left: Vec<(&str, i32)> = keys.iter().map(|k| (k.as_str(), 1)).collect();
+        let mut right = left.clone();
+        right[2].1 = 2;
+        right[30].1 = 2;
+        (document(&left), document(&right))
+    }
+
+    #[test]
+    fn test_entry_anchors() {
+        let (left, right) = sources();
+        let result = compare_strings(&left, &right, &CompareOptions::default()).unwrap();
+        assert_eq!(result.entries.len(), 2);
+
+        let options = HtmlOptions { embed_sources: true, ..HtmlOptions::default() };
+        let html = format_html(&result, Some(HtmlSources { left: &left, right: &right }), &options);
+
+        assert!(html.contains("class=\"source full\""));
+        for entry in &result.entries {
+            for (side, line) in [("left", entry.left_line.unwrap()), ("right", entry.right_line.unwrap())] {
+                assert!(html.contains(&format!("href=\"#{}-L{}\"", side, line)));
+                assert!(html.contains(&format!("id=\"{}-L{}\" class=\"line diff\"", side, line)));
+            }
+        }
+        assert!(html.contains("id=\"left-L20\" class=\"line\""));
+    }
+
+    #[test]
+    fn test_cap_switches_to_snippets() {
+        let (left, right) = sources();
+        let result = compare_strings(&left, &right, &CompareOptions::default()).unwrap();
+
+        let options = HtmlOptions { embed_sources: true, max_embed_bytes: 100, context_lines: 2 };
+        let html = format_html(&result, Some(HtmlSources { left: &left, right: &right }), &options);
+
+        assert!(html.contains("class=\"source snippet\""));
+        assert!(!html.contains("class=\"source full\""));
+        // field2 is on line 4 and field30 on line 32; line 20 lies outside both windows
+        assert!(html.contains("id=\"left-L4\" class=\"line diff\""));
+        assert!(html.contains("id=\"left-L6\" class=\"line\""));
+        assert!(html.contains("id=\"right-L32\" class=\"line diff\""));
+        assert!(!html.contains("id=\"left-L20\""));
+        assert!(html.contains("<span class=\"gap\">…</span>"));
+    }
+
+    #[test]
+    fn test_sources_not_embedded_by_default() {
+        let (left, right) = sources();
+        let result = compare_strings(&left, &right, &CompareOptions::default()).unwrap();
+
+        let html = format_html(&result, Some(HtmlSources { left: &left, right: &right }), &HtmlOptions::default());
+        assert!(html.contains("href=\"#left-L4\""));
+        assert!(!html.contains("<details"));
+    }
+
+    #[test]
+    fn test_snippet_ranges_merge() {
+        let lines: BTreeSet<usize> = [1, 4, 20].into_iter().collect();
+        assert_eq!(snippet_ranges(&lines, 2, 21), vec![(1, 6), (18, 21)]);
+    }
+
+    #[test]
+    fn test_absent_values() {
+        let result = compare_strings(r#"{"a": null, "b": 1}"#, r#"{"a": 1, "c": null}"#, &CompareOptions::default()).unwrap();
+        let html = format_html(&result, None, &HtmlOptions::default());
+        assert!(html.contains("<td class=\"value\">null</td><td class=\"value\">1</td>"));
+        // The removed entry has no new value and the added one no old value
+        assert!(html.contains("<td class=\"value\">1</td><td class=\"value\"></td>"));
+        assert!(html.contains("<td class=\"value\"></td><td class=\"value\">null</td>"));
+
+        let mut modified = result.iter().find(|e| e.diff_type == DiffType::Modified).unwrap().clone();
+        modified.new_value = None;
+        let html = format_html(&DiffResult { entries: vec![modified], ..result }, None, &HtmlOptions::default());
+        assert!(html.contains("<td class=\"value\">null</td><td class=\"value\">&lt;absent&gt;</td>"));
+    }
+
+    #[test]
+    fn test_control_characters_in_paths() {
+        let result = compare_strings("{\"\\u001b[31mred\": 1}", "{\"\\u001b[31mred\": 2}", &CompareOptions::default()).unwrap();
+        let html = format_html(&result, None, &HtmlOptions::default());
+        assert!(!html.contains('\u{1b}'));
+        assert!(html.contains("<a href=\"#entry-0\">$.\\u{1b}[31mred</a>"), "{}", html);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(r#"<a href="x">&'"#), "&lt;a href=&quot;x&quot;&gt;&amp;&#39;");
+    }
+}
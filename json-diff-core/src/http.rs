@@ -0,0 +1,121 @@
+//! Comparison of captured HTTP responses
+//!
+//! A capture is a JSON document holding a response's status, headers and body:
+//!
+//! ```json
+//! { "status": 200, "headers": { "Content-Type": "application/json" }, "body": { "id": 1 } }
+//! ```
+//!
+//! Header names are compared case-insensitively and reported in lowercase, e.g.
+//! `$.headers.content-type`; headers that change on every request are ignored.
+
+use serde_json::Value;
+
+use crate::compare::{compare_json, CompareOptions};
+use crate::diff::DiffResult;
+use crate::error::JsonDiffError;
+use crate::path::{push_key, JsonPath};
+use crate::transform::Transform;
+
+/// Where a capture keeps its parts and how they are compared
+#[derive(Debug, Clone)]
+pub struct HttpCaptureOptions {
+    /// Field holding the headers object
+    pub headers_field: String,
+    /// Field holding the body
+    pub body_field: String,
+    /// Headers ignored in the comparison, matched case-insensitively
+    pub ignored_headers: Vec<String>,
+    /// Whether a body given as a string holding JSON is parsed and compared structurally
+    pub parse_json_body: bool,
+}
+
+impl Default for HttpCaptureOptions {
+    fn default() -> Self {
+        Self {
+            headers_field: "headers".to_string(),
+            body_field: "body".to_string(),
+            ignored_headers: vec!["date".to_string(), "request-id".to_string(), "x-request-id".to_string()],
+            parse_json_body: true,
+        }
+    }
+}
+
+/// Build a `CompareOptions` overlay comparing documents as HTTP captures
+///
+/// The returned options only carry rules; apply them on top of a base with
+/// [`CompareOptions::extend_rules`].
+pub fn http_capture_rules(http: &HttpCaptureOptions) -> Result<CompareOptions, JsonDiffError> {
+    let field_path = |field: &str| {
+        let mut path = "$".to_string();
+        push_key(&mut path, field);
+        path
+    };
+    let headers = field_path(&http.headers_field);
+
+    let mut overlay = CompareOptions::default();
+    overlay.preprocess.push(Transform::lowercase_keys(JsonPath::new(&headers)?));
+    for header in &http.ignored_headers {
+        let mut path = headers.clone();
+        push_key(&mut path, &header.to_lowercase());
+        overlay.ignore_paths.push(JsonPath::new(&path)?);
+    }
+    if http.parse_json_body {
+        overlay.parse_embedded_json.push(JsonPath::new(&field_path(&http.body_field))?);
+    }
+
+    Ok(overlay)
+}
+
+/// Compare two HTTP captures, applying the capture rules on top of `options`
+pub fn compare_http_captures(
+    left: &Value,
+    right: &Value,
+    options: &CompareOptions,
+    http: &HttpCaptureOptions,
+) -> Result<DiffResult, JsonDiffError> {
+    let mut options = options.clone();
+    options.extend_rules(http_capture_rules(http)?);
+    compare_json(left, right, &options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::DiffType;
+
+    fn capture(headers: Value, body: Value) -> Value {
+        json!({"status": 200, "headers": headers, "body": body})
+    }
+
+    #[test]
+    fn test_headers_compared_case_insensitively() {
+        let left = capture(json!({"Content-Type": "application/json", "Date": "Mon"}), json!({"id": 1}));
+        let right = capture(json!({"content-type": "application/json", "date": "Tue"}), json!({"id": 1}));
+
+        let result = compare_http_captures(&left, &right, &CompareOptions::default(), &HttpCaptureOptions::default()).unwrap();
+
+        // Only the ignored date header is left; it changes on every request
+        let summary: Vec<_> = result.iter().map(|e| (e.diff_type.clone(), e.path.as_str())).collect();
+        assert_eq!(summary, vec![(DiffType::Ignored, "$.headers.date")]);
+    }
+
+    #[test]
+    fn test_header_and_body_differences() {
+        let left = capture(json!({"Content-Type": "text/plain", "X-Request-Id": "a"}), json!("{\"id\": 1, \"name\": \"x\"}"));
+        let right = capture(json!({"Content-Type": "application/json", "X-Request-Id": "b"}), json!("{\"name\": \"x\", \"id\": 2}"));
+
+        let result = compare_http_captures(&left, &right, &CompareOptions::default(), &HttpCaptureOptions::default()).unwrap();
+        let mut modified: Vec<&str> = result.iter_type(DiffType::Modified).map(|e| e.path.as_str()).collect();
+        modified.sort();
+        assert_eq!(modified, vec!["$.body(json).id", "$.headers.content-type"]);
+
+        // Without parsing, the body strings differ as a whole
+        let http = HttpCaptureOptions { parse_json_body: false, ignored_headers: Vec::new(), ..HttpCaptureOptions::default() };
+        let result = compare_http_captures(&left, &right, &CompareOptions::default(), &http).unwrap();
+        let mut modified: Vec<&str> = result.iter_type(DiffType::Modified).map(|e| e.path.as_str()).collect();
+        modified.sort();
+        assert_eq!(modified, vec!["$.body", "$.headers.content-type", "$.headers.x-request-id"]);
+    }
+}
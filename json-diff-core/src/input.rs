@@ -0,0 +1,131 @@
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+use crate::error::JsonDiffError;
+use crate::similarity::edit_distance;
+
+/// Read a file into a string, refusing files larger than `limit` bytes
+///
+/// The file length is checked before anything is read; the read itself is
+/// bounded as well, for files whose length is not known up front (pipes, devices).
+pub fn read_file_limited(path: &Path, limit: Option<u64>) -> Result<String, JsonDiffError> {
+    check_input_file(path)?;
+    let file = File::open(path).map_err(|e| open_error(path, e))?;
+
+    if let Some(limit) = limit {
+        let size = file.metadata()?.len();
+        if size > limit {
+            return Err(JsonDiffError::InputTooLarge { path: path.to_path_buf(), size, limit });
+        }
+    }
+
+    read_limited(file, path, limit)
+}
+
+/// Fail with an error naming `path` when it is missing, a directory or unreadable
+///
+/// A missing file is reported with a similarly named file of the same directory,
+/// if there is one.
+pub fn check_input_file(path: &Path) -> Result<(), JsonDiffError> {
+    let metadata = fs::metadata(path).map_err(|e| open_error(path, e))?;
+    if metadata.is_dir() {
+        return Err(JsonDiffError::IsDirectory { path: path.to_path_buf() });
+    }
+    File::open(path).map_err(|e| open_error(path, e))?;
+    Ok(())
+}
+
+fn open_error(path: &Path, error: std::io::Error) -> JsonDiffError {
+    match error.kind() {
+        ErrorKind::NotFound => JsonDiffError::FileNotFound { path: path.to_path_buf(), suggestion: similar_file(path) },
+        ErrorKind::PermissionDenied => JsonDiffError::PermissionDenied { path: path.to_path_buf() },
+        _ => JsonDiffError::IoError(error),
+    }
+}
+
+/// The file next to `path` whose name is closest to its name, within two edits
+fn similar_file(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let candidates = fs::read_dir(dir).ok()?.filter_map(|entry| {
+        let entry = entry.ok()?;
+        let candidate = entry.file_name().into_string().ok()?;
+        let distance = edit_distance(name, &candidate);
+        (distance <= 2 && entry.file_type().ok()?.is_file()).then_some((distance, candidate))
+    });
+    let (_, candidate) = candidates.min()?;
+    Some(path.with_file_name(candidate))
+}
+
+/// Read a stream into a string, failing once more than `limit` bytes have been read
+///
+/// `source` only names the input in the error.
+pub fn read_limited<R: Read>(reader: R, source: &Path, limit: Option<u64>) -> Result<String, JsonDiffError> {
+    let mut content = String::new();
+
+    match limit {
+        Some(limit) => {
+            // Read one byte past the limit to tell "exactly at" from "over"
+            let read = reader.take(limit.saturating_add(1)).read_to_string(&mut content)? as u64;
+            if read > limit {
+                return Err(JsonDiffError::InputTooLarge { path: source.to_path_buf(), size: read, limit });
+            }
+        }
+        None => {
+            let mut reader = reader;
+            reader.read_to_string(&mut content)?;
+        }
+    }
+
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_limited_stream() {
+        let source = Path::new("<stdin>");
+
+        assert_eq!(read_limited(Cursor::new("12345"), source, Some(5)).unwrap(), "12345");
+        assert_eq!(read_limited(Cursor::new("12345"), source, None).unwrap(), "12345");
+
+        let err = read_limited(Cursor::new("123456789"), source, Some(5)).unwrap_err();
+        assert!(matches!(err, JsonDiffError::InputTooLarge { size: 6, limit: 5, .. }));
+    }
+
+    #[test]
+    fn test_read_file_limited() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.json");
+        std::fs::write(&path, r#"{"data": "0123456789"}"#).unwrap();
+
+        assert!(read_file_limited(&path, Some(1024)).is_ok());
+
+        let err = read_file_limited(&path, Some(8)).unwrap_err();
+        assert!(matches!(err, JsonDiffError::InputTooLarge { size: 22, limit: 8, .. }));
+        assert!(err.to_string().contains("--max-input-size"));
+    }
+
+    #[test]
+    fn test_check_input_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("config.json"), "{}").unwrap();
+
+        let err = check_input_file(&dir.path().join("confg.json")).unwrap_err();
+        assert!(matches!(&err, JsonDiffError::FileNotFound { suggestion: Some(s), .. } if s.ends_with("config.json")));
+        assert!(err.to_string().contains("; did you mean"));
+
+        let err = check_input_file(&dir.path().join("other.json")).unwrap_err();
+        assert!(matches!(err, JsonDiffError::FileNotFound { suggestion: None, .. }));
+
+        let err = read_file_limited(dir.path(), None).unwrap_err();
+        assert!(matches!(err, JsonDiffError::IsDirectory { .. }));
+    }
+}
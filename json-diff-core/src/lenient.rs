@@ -0,0 +1,131 @@
+//! The non-finite number tokens `NaN`, `Infinity` and `-Infinity`
+//!
+//! JSON has no such numbers, but lenient producers write them anyway. With
+//! `CompareOptions::lenient` each token outside of strings is read as a sentinel
+//! string, so it is equal to the same token only and differs from every number.
+//! Values are written back with the tokens in place of the sentinels.
+
+use std::borrow::Cow;
+use serde_json::Value;
+
+/// Tokens accepted in lenient mode, longest first so `-Infinity` is not read as `-`
+const TOKENS: [&str; 3] = ["-Infinity", "Infinity", "NaN"];
+
+/// Start of a sentinel string; a Unicode noncharacter, so real text will not hold it
+const SENTINEL_PREFIX: char = '\u{FDD0}';
+
+/// Replace every non-finite token outside of strings by its sentinel string
+pub(crate) fn substitute_non_finite(text: &str) -> Cow<'_, str> {
+    if !TOKENS.iter().any(|token| text.contains(token)) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut result = String::with_capacity(text.len() + 16);
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if let Some(token) = TOKENS.iter().find(|token| starts_with_token(rest, token)) {
+            result.push('"');
+            result.push(SENTINEL_PREFIX);
+            result.push_str(token);
+            result.push('"');
+            rest = &rest[token.len()..];
+            continue;
+        }
+        result.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    Cow::Owned(result)
+}
+
+/// Whether `text` starts with `token` as a whole word
+fn starts_with_token(text: &str, token: &str) -> bool {
+    text.strip_prefix(token)
+        .is_some_and(|after| !after.starts_with(|c: char| c.is_alphanumeric() || c == '_'))
+}
+
+/// The JSON text of a value, with sentinels written back as the tokens they stand for
+///
+/// Only documents read in lenient mode can hold sentinels; for others this is plain
+/// serialization.
+pub fn format_value(value: &Value, pretty: bool) -> String {
+    let text = if pretty { serde_json::to_string_pretty(value) } else { serde_json::to_string(value) };
+    let text = text.unwrap_or_default();
+    if !text.contains(SENTINEL_PREFIX) {
+        return text;
+    }
+
+    TOKENS.iter().fold(text, |text, token| text.replace(&format!("\"{}{}\"", SENTINEL_PREFIX, token), token))
+}
+
+/// The token a sentinel string stands for, `None` for other strings
+fn token_of(text: &str) -> Option<&'static str> {
+    let token = text.strip_prefix(SENTINEL_PREFIX)?;
+    TOKENS.iter().copied().find(|&t| t == token)
+}
+
+/// `value` with every sentinel replaced by the string of its token, e.g. `"NaN"`, for
+/// the JSON form of a report, which cannot hold the tokens themselves
+pub(crate) fn without_sentinels(value: &Value) -> Cow<'_, Value> {
+    match value {
+        Value::String(text) => match token_of(text) {
+            Some(token) => Cow::Owned(Value::String(token.to_string())),
+            None => Cow::Borrowed(value),
+        },
+        Value::Array(items) => {
+            let items: Vec<_> = items.iter().map(without_sentinels).collect();
+            if items.iter().all(|item| matches!(item, Cow::Borrowed(_))) {
+                return Cow::Borrowed(value);
+            }
+            Cow::Owned(Value::Array(items.into_iter().map(Cow::into_owned).collect()))
+        }
+        Value::Object(map) => {
+            let items: Vec<_> = map.iter().map(|(key, item)| (key, without_sentinels(item))).collect();
+            if items.iter().all(|(_, item)| matches!(item, Cow::Borrowed(_))) {
+                return Cow::Borrowed(value);
+            }
+            Cow::Owned(Value::Object(items.into_iter().map(|(key, item)| (key.clone(), item.into_owned())).collect()))
+        }
+        _ => Cow::Borrowed(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substitute_non_finite() {
+        let text = r#"{"NaNa": 1, "a": NaN, "b": [Infinity, -Infinity], "c": "NaN is fine here"}"#;
+        let value: Value = serde_json::from_str(&substitute_non_finite(text)).unwrap();
+
+        assert_eq!(value["a"], Value::String(format!("{}NaN", SENTINEL_PREFIX)));
+        assert_eq!(value["c"], "NaN is fine here");
+        assert_eq!(
+            format_value(&value, false),
+            r#"{"NaNa":1,"a":NaN,"b":[Infinity,-Infinity],"c":"NaN is fine here"}"#
+        );
+
+        // Nothing to replace
+        assert!(matches!(substitute_non_finite(r#"{"a": 1}"#), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_without_sentinels() {
+        let value: Value = serde_json::from_str(&substitute_non_finite(r#"{"a": [1, -Infinity], "b": "x"}"#)).unwrap();
+        assert_eq!(without_sentinels(&value).into_owned(), serde_json::json!({"a": [1, "-Infinity"], "b": "x"}));
+        assert!(matches!(without_sentinels(&serde_json::json!({"a": ["NaN"]})), Cow::Borrowed(_)));
+    }
+}
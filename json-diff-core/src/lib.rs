@@ -1,11 +1,70 @@
 //! JSON comparison library that generates diffs in a text-based format
+//!
+//! The [`quick`] functions are the shortest way in: two documents in, the readable text
+//! of their differences out, the same on every run.
+//!
+//! ```
+//! let text = json_diff_core::quick::diff_strings_to_string(r#"{"port": 8080}"#, r#"{"port": 9090}"#)?;
+//! assert_eq!(text, "DIFF-JSON v1\n\n[MODIFIED] $.port (L1:L1): 8080 -> 9090\n");
+//! # Ok::<(), json_diff_core::JsonDiffError>(())
+//! ```
+//!
+//! For the entries themselves, compare with [`compare_json`], [`compare_strings`] or
+//! [`compare_files`] and [`CompareOptions`], and format the [`DiffResult`] as needed.
+//! The [`cookbook`] shows how to do common tasks with them.
 
 mod diff;
+mod align;
+mod canonical;
 mod compare;
 mod path;
 mod error;
+mod comparator;
+mod transform;
+mod schema_rules;
+mod decode;
+mod checksum;
+mod input;
+mod html;
+pub mod line_map;
+mod annotation;
+mod http;
+mod lenient;
+mod rule_set;
+mod sniff;
+mod similarity;
+mod query;
+mod table;
+mod patch;
+mod textdiff;
+mod wrap;
+pub mod presets;
+pub mod quick;
+pub mod merge_patch;
+pub mod cookbook;
+#[cfg(feature = "streaming")]
+mod stream;
 
-pub use diff::{DiffEntry, DiffType, DiffResult};
-pub use compare::{compare_json, compare_files, CompareOptions};
+pub use diff::{escape_control, group_related, preview_value, sort_by_delta, FORMAT_VERSION, GENERATOR, DEFAULT_MAX_VALUE_CHARS, ArraySummary, DiffClassification, DiffEntry, DiffType, OutputStyle, DiffResult, DiffResultBuilder, DiffOfDiffs, EntrySource, PathCount, SourceInfo, Timestamp, Timings, ValueRenderOptions};
+pub use compare::{compare_json, compare_files, compare_strings, AllowedTransition, CompareOptions, IndexStyle, MissingFilePolicy, NumberLocale, PathMapping};
 pub use error::JsonDiffError;
-pub use path::JsonPath;
\ No newline at end of file
+pub use path::{parent_path, value_paths, JsonPath, RegexOpts};
+pub use comparator::{ComparatorVerdict, ValueComparator};
+pub use transform::{Transform, TransformKind, TransformSide};
+pub use schema_rules::rules_from_schema;
+pub use decode::Codec;
+pub use canonical::{format_canonical, CanonicalOptions};
+pub use checksum::sha256_hex;
+pub use input::{check_input_file, read_file_limited, read_limited};
+pub use annotation::{Annotation, Annotations, TriageStatus};
+pub use html::{format_html, HtmlOptions, HtmlSources};
+pub use line_map::{LineMap, SourceSpan};
+pub use http::{compare_http_captures, http_capture_rules, HttpCaptureOptions};
+pub use lenient::format_value;
+pub use rule_set::{RuleStats, RuleUsage};
+pub use presets::{Preset, PRESETS};
+pub use query::Query;
+pub use patch::{apply_json_patch, PatchOptions};
+pub use table::{compare_tables, RowDiff, RowStatus, TableDiff, TableSummary};
+#[cfg(feature = "streaming")]
+pub use stream::{compare_array_streams, compare_array_streams_until, StreamOptions, StreamSummary};
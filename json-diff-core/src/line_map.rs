@@ -0,0 +1,485 @@
+//! Source positions of every node of a JSON document
+//!
+//! The map is built by scanning the document text once, so every path gets the
+//! line it actually appears on, along with the order of its children. The order
+//! is needed to anchor an entry that exists in only one document to the place it
+//! would occupy in the other.
+//!
+//! Paths are written as comparison entries report them: `$` for the document,
+//! `.key` for object members (`["key"]` if the key holds characters such as `.` or
+//! `[`), and `[3]` for array elements, e.g. `$.users[0].name`. A key that occurs
+//! more than once in the same object keeps the position of its last occurrence, as
+//! its value does when the document is parsed.
+
+use std::collections::{HashMap, HashSet};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+
+use crate::error::JsonDiffError;
+use crate::path::{push_key, rebase_path};
+
+/// Where a node appears in the document text (all lines are 1-based)
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Position {
+    /// Line of the key for object members, of the value's first character otherwise
+    pub line: usize,
+    /// 1-based byte column of the same character as `line`
+    pub column: usize,
+    /// 0-based byte offset of the same character as `line`
+    pub offset: usize,
+    /// Line of the value's first character, e.g. the opening brace
+    pub value_line: usize,
+    /// Line of the value's last character
+    pub end_line: usize,
+    /// 0-based byte offset just past the value's last character
+    pub end_offset: usize,
+    /// Path of the containing object or array
+    pub parent: Option<String>,
+    /// Paths of the members or elements, in document order
+    pub children: Vec<String>,
+    /// Index of the node among its parent's children
+    pub index: usize,
+}
+
+/// The region of a document a node occupies: its key and value for object members,
+/// its value otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceSpan {
+    /// 1-based line of the region's first character
+    pub line: usize,
+    /// 1-based byte column of the region's first character
+    pub col: usize,
+    /// 0-based byte offset of the region's first character
+    pub byte_start: usize,
+    /// 0-based byte offset just past the region's last character
+    pub byte_end: usize,
+}
+
+/// Positions of the nodes of one document, by path
+#[derive(Debug, Clone, Default)]
+pub struct LineMap {
+    nodes: HashMap<String, Position>,
+}
+
+/// Map every path of a JSON document to where it appears, failing if it is not valid JSON
+pub fn build(content: &str) -> Result<LineMap, JsonDiffError> {
+    serde_json::from_str::<IgnoredAny>(content)?;
+    Ok(LineMap::scan(content))
+}
+
+impl LineMap {
+    /// Scan a JSON document; text that is not valid JSON yields a partial map
+    pub(crate) fn scan(content: &str) -> Self {
+        let mut scanner = Scanner { bytes: content.as_bytes(), pos: 0, line: 1, line_start: 0, map: LineMap::default() };
+        scanner.skip_whitespace();
+        let start = scanner.location();
+        scanner.value("$".to_string(), start, None, 0);
+        scanner.map
+    }
+
+    /// Line of the node at `path`: of the key for object members, of the value otherwise
+    pub fn line_of(&self, path: &str) -> Option<usize> {
+        self.nodes.get(path).map(|position| position.line)
+    }
+
+    /// 1-based byte column of the character [`LineMap::line_of`] refers to
+    pub fn col_of(&self, path: &str) -> Option<usize> {
+        self.nodes.get(path).map(|position| position.column)
+    }
+
+    /// 0-based byte offset in the document of the character [`LineMap::line_of`] refers to
+    pub fn offset_of(&self, path: &str) -> Option<usize> {
+        self.nodes.get(path).map(|position| position.offset)
+    }
+
+    /// Region of the node at `path`, from its key (for object members) to the end of its value
+    pub fn span_of(&self, path: &str) -> Option<SourceSpan> {
+        self.nodes.get(path).map(|position| SourceSpan {
+            line: position.line,
+            col: position.column,
+            byte_start: position.offset,
+            byte_end: position.end_offset,
+        })
+    }
+
+    /// Every mapped path, in document order
+    pub fn paths(&self) -> Vec<&str> {
+        let mut paths = Vec::with_capacity(self.nodes.len());
+        let mut pending = vec!["$"];
+        while let Some(path) = pending.pop() {
+            let Some((path, position)) = self.nodes.get_key_value(path) else { continue };
+            paths.push(path.as_str());
+            pending.extend(position.children.iter().rev().map(String::as_str));
+        }
+        paths
+    }
+
+    /// Path to line mapping, as used for the entries' line numbers
+    pub(crate) fn line_map(&self) -> HashMap<String, usize> {
+        self.nodes.iter().map(|(path, position)| (path.clone(), position.line)).collect()
+    }
+
+    /// The nodes within `root`, with their paths made relative to it
+    pub(crate) fn rebase(&self, root: &str) -> Self {
+        let nodes = self.nodes.iter()
+            .filter_map(|(path, position)| {
+                let path = rebase_path(path, root)?;
+                let position = Position {
+                    parent: position.parent.as_deref().and_then(|parent| rebase_path(parent, root)),
+                    children: position.children.iter().filter_map(|child| rebase_path(child, root)).collect(),
+                    ..position.clone()
+                };
+                Some((path, position))
+            })
+            .collect();
+        Self { nodes }
+    }
+
+    /// Lines after which each child of `parent` in `other` would be inserted in this
+    /// document, in `other`'s order, for the children that only exist in `other`
+    ///
+    /// That is the last line of the nearest preceding sibling (in `other`'s order)
+    /// that exists here too, or the opening line of the parent if there is none. The
+    /// children are walked once, so an object's lines are found in linear time.
+    pub(crate) fn insertion_lines(&self, parent: &str, other: &LineMap) -> Vec<Option<usize>> {
+        let Some(siblings) = other.nodes.get(parent).map(|position| &position.children) else { return Vec::new() };
+        let mut anchor = self.nodes.get(parent).map(|parent| parent.value_line);
+        siblings.iter()
+            .map(|sibling| {
+                let line = anchor;
+                if let Some(here) = self.nodes.get(sibling) {
+                    anchor = Some(here.end_line);
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// Line of this document after which `path`, a node of `other` missing here, would be
+    /// inserted, see [`LineMap::insertion_lines`]; if its parent is missing too, the line
+    /// the parent would follow
+    pub fn insertion_line(&self, path: &str, other: &LineMap) -> Option<usize> {
+        let (parent, index) = other.sibling_index(path)?;
+        match self.nodes.contains_key(parent) {
+            true => self.insertion_lines(parent, other).get(index).copied().flatten(),
+            false => self.insertion_line(parent, other),
+        }
+    }
+
+    /// Index of the node at `path` among its parent's children, with the parent's path
+    pub(crate) fn sibling_index(&self, path: &str) -> Option<(&str, usize)> {
+        let position = self.nodes.get(path)?;
+        Some((position.parent.as_deref()?, position.index))
+    }
+}
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    line: usize,
+    /// Offset of the first byte of the current line
+    line_start: usize,
+    map: LineMap,
+}
+
+/// Line, column and offset of a character, as recorded in a `Position`
+#[derive(Clone, Copy)]
+struct Location {
+    line: usize,
+    column: usize,
+    offset: usize,
+}
+
+impl Scanner<'_> {
+    /// Scan the value starting at the current position and record it under `path`
+    fn value(&mut self, path: String, start: Location, parent: Option<String>, index: usize) {
+        let value_line = self.line;
+        let mut children = Vec::new();
+        let mut seen = HashSet::new();
+
+        match self.peek() {
+            Some(b'{') => {
+                self.bump();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b'"') => {
+                            let key_start = self.location();
+                            let Some(key) = self.string() else { break };
+                            self.skip_whitespace();
+                            if self.peek() != Some(b':') {
+                                break;
+                            }
+                            self.bump();
+                            self.skip_whitespace();
+
+                            let mut child = path.clone();
+                            push_key(&mut child, &key);
+                            // A repeated key is listed once, where it first appeared
+                            let child_index = match seen.insert(child.clone()) {
+                                true => {
+                                    children.push(child.clone());
+                                    children.len() - 1
+                                }
+                                false => children.iter().position(|c| *c == child).unwrap_or_default(),
+                            };
+                            self.value(child, key_start, Some(path.clone()), child_index);
+                        }
+                        Some(b',') => self.bump(),
+                        Some(b'}') => {
+                            self.bump();
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.bump();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.bump(),
+                        Some(b']') => {
+                            self.bump();
+                            break;
+                        }
+                        Some(_) => {
+                            let child = format!("{}[{}]", path, children.len());
+                            children.push(child.clone());
+                            let element_start = self.location();
+                            let before = self.pos;
+                            self.value(child, element_start, Some(path.clone()), children.len() - 1);
+                            if self.pos == before {
+                                break;
+                            }
+                        }
+                        None => break,
+                    }
+                }
+            }
+            Some(b'"') => {
+                self.string();
+            }
+            Some(_) => {
+                while let Some(byte) = self.peek() {
+                    if matches!(byte, b',' | b'}' | b']') || byte.is_ascii_whitespace() {
+                        break;
+                    }
+                    self.bump();
+                }
+            }
+            None => return,
+        }
+
+        // The last character consumed belongs to the value
+        let end_line = if self.pos > 0 && self.bytes[self.pos - 1] == b'\n' { self.line - 1 } else { self.line };
+        let Location { line, column, offset } = start;
+        let end_offset = self.pos;
+        self.map.nodes.insert(path, Position { line, column, offset, value_line, end_line, end_offset, parent, children, index });
+    }
+
+    /// Scan a string literal and return its decoded content
+    fn string(&mut self) -> Option<String> {
+        let start = self.pos;
+        self.bump();
+        let mut escaped = false;
+
+        while let Some(byte) = self.peek() {
+            self.bump();
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => return serde_json::from_slice(&self.bytes[start..self.pos]).ok(),
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|b| b.is_ascii_whitespace()) {
+            self.bump();
+        }
+    }
+
+    fn location(&self) -> Location {
+        Location { line: self.line, column: self.pos - self.line_start + 1, offset: self.pos }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) {
+        if self.peek() == Some(b'\n') {
+            self.line += 1;
+            self.line_start = self.pos + 1;
+        }
+        self.pos += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"{
+  "user": {
+    "name": "John",
+    "tags": [
+      "a",
+      {"deep": "b"}
+    ]
+  },
+  "name": "top level"
+}"#;
+
+    #[test]
+    fn test_positions() {
+        let map = LineMap::scan(DOCUMENT);
+
+        let lines = map.line_map();
+        assert_eq!(lines["$"], 1);
+        assert_eq!(lines["$.user"], 2);
+        assert_eq!(lines["$.user.name"], 3);
+        assert_eq!(lines["$.name"], 9);
+        assert_eq!(lines["$.user.tags[0]"], 5);
+        assert_eq!(lines["$.user.tags[1].deep"], 6);
+
+        let user = &map.nodes["$.user"];
+        assert_eq!(user.end_line, 8);
+        assert_eq!(user.children, vec!["$.user.name", "$.user.tags"]);
+        assert_eq!(map.nodes["$.user.tags"].end_line, 7);
+        assert_eq!(map.nodes["$"].end_line, 10);
+    }
+
+    #[test]
+    fn test_insertion_line() {
+        let left = LineMap::scan("{\n  \"a\": 1,\n  \"c\": {\n    \"x\": 1\n  }\n}");
+        let right = LineMap::scan("{\n  \"first\": 0,\n  \"a\": 1,\n  \"b\": 2,\n  \"c\": {\n    \"x\": 1\n  },\n  \"d\": 4\n}");
+        let insertion_line = |path: &str| left.insertion_line(path, &right);
+
+        // Before every existing sibling: right after the opening brace
+        assert_eq!(insertion_line("$.first"), Some(1));
+        // After "a"
+        assert_eq!(insertion_line("$.b"), Some(2));
+        // After the last line of "c"
+        assert_eq!(insertion_line("$.d"), Some(5));
+        assert_eq!(insertion_line("$.missing"), None);
+        assert_eq!(left.insertion_lines("$", &right), vec![Some(1), Some(1), Some(2), Some(2), Some(5)]);
+
+        // Array elements follow the element before them; a node whose parent is missing
+        // too goes where the parent would
+        let left = LineMap::scan("{\n  \"list\": [\n    1,\n    2\n  ]\n}");
+        let right = LineMap::scan("{\n  \"list\": [1, 2, 3],\n  \"new\": {\"deep\": {\"x\": 1}}\n}");
+        assert_eq!(left.insertion_line("$.list[2]", &right), Some(4));
+        assert_eq!(left.insertion_line("$.new.deep.x", &right), Some(5));
+        assert_eq!(left.insertion_line("$", &right), None);
+    }
+
+    #[test]
+    fn test_wide_object_with_repeated_keys() {
+        let keys: Vec<String> = (0..2_000).map(|i| format!("\"k{}\": {}", i % 1_500, i)).collect();
+        let map = LineMap::scan(&format!("{{\n{}\n}}", keys.join(",\n")));
+
+        // The last occurrence wins, at the index of the first one
+        assert_eq!(map.line_of("$.k10"), Some(1_512));
+        assert_eq!(map.sibling_index("$.k10"), Some(("$", 10)));
+        assert_eq!(map.sibling_index("$.k1499"), Some(("$", 1_499)));
+        assert_eq!(map.nodes["$"].children.len(), 1_500);
+    }
+
+    #[test]
+    fn test_rebase() {
+        let map = LineMap::scan(DOCUMENT).rebase("$.user");
+
+        let lines = map.line_map();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines["$"], 2);
+        assert_eq!(lines["$.tags[1].deep"], 6);
+        assert_eq!(map.nodes["$"].parent, None);
+        assert_eq!(map.nodes["$"].children, vec!["$.name", "$.tags"]);
+        assert_eq!(map.nodes["$.tags"].parent.as_deref(), Some("$"));
+    }
+
+    #[test]
+    fn test_escaped_keys_and_minified() {
+        let map = LineMap::scan(r#"{"a\"b": [1, 2.5e3, true, null], "c": "x,y]"}"#);
+        let lines = map.line_map();
+        // Keys are quoted as in the entries' paths
+        assert!(lines.contains_key(r#"$["a\"b"]"#));
+        assert!(lines.contains_key(r#"$["a\"b"][3]"#));
+        assert!(lines.contains_key("$.c"));
+        assert_eq!(map.nodes[r#"$["a\"b"]"#].children.len(), 4);
+    }
+
+    #[test]
+    fn test_multibyte_keys() {
+        let document = "{\"名前\": \"太郎\",\n \"🎉 party\": {\"cafe\u{301}\": [\"]\", \"👨\u{200d}👩\"]}}";
+        let map = build(document).unwrap();
+        assert_eq!(map.line_of("$.名前"), Some(1));
+        assert_eq!(map.line_of("$.🎉 party.cafe\u{301}[1]"), Some(2));
+        // Columns and offsets count bytes, so they slice the document
+        let offset = map.offset_of("$.🎉 party").unwrap();
+        assert_eq!(map.col_of("$.🎉 party"), Some(2));
+        assert!(document[offset..].starts_with("\"🎉 party\""));
+        let span = map.span_of("$.🎉 party.cafe\u{301}[1]").unwrap();
+        assert_eq!(&document[span.byte_start..span.byte_end], "\"👨\u{200d}👩\"");
+    }
+
+    #[test]
+    fn test_public_lookups() {
+        let map = build(DOCUMENT).unwrap();
+
+        // The same key at different depths
+        assert_eq!(map.line_of("$.name"), Some(9));
+        assert_eq!(map.line_of("$.user.name"), Some(3));
+        assert_eq!((map.col_of("$.user.name"), map.offset_of("$.user.name")), (Some(5), Some(18)));
+        assert_eq!(&DOCUMENT[18..24], "\"name\"");
+
+        // Array elements point at their value
+        assert_eq!((map.line_of("$.user.tags[1]"), map.col_of("$.user.tags[1]")), (Some(6), Some(7)));
+        assert_eq!((map.line_of("$.user.tags[1].deep"), map.col_of("$.user.tags[1].deep")), (Some(6), Some(8)));
+        assert_eq!(map.line_of("$.user.missing"), None);
+
+        assert_eq!(map.paths(), vec![
+            "$", "$.user", "$.user.name", "$.user.tags", "$.user.tags[0]", "$.user.tags[1]",
+            "$.user.tags[1].deep", "$.name",
+        ]);
+
+        assert!(build("{\"a\": ").is_err());
+    }
+
+    #[test]
+    fn test_spans() {
+        let map = build(DOCUMENT).unwrap();
+        let text = |path: &str| {
+            let span = map.span_of(path).unwrap();
+            &DOCUMENT[span.byte_start..span.byte_end]
+        };
+
+        // Members span their key and value, so they parse as an object of one member
+        let member = |path: &str| serde_json::from_str::<serde_json::Value>(&format!("{{{}}}", text(path))).unwrap();
+        assert_eq!(member("$.user.name"), serde_json::json!({"name": "John"}));
+        assert_eq!(member("$.user.tags")["tags"][1], serde_json::json!({"deep": "b"}));
+        // Elements span their value
+        assert_eq!(serde_json::from_str::<serde_json::Value>(text("$.user.tags[1]")).unwrap(), serde_json::json!({"deep": "b"}));
+        assert_eq!(text("$"), DOCUMENT);
+
+        let span = map.span_of("$.user.tags[1]").unwrap();
+        assert_eq!((span.line, span.col), (6, 7));
+        assert_eq!(map.span_of("$.user.missing"), None);
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let map = build("{\n  \"a\": 1,\n  \"b\": {\"a\": 2},\n  \"a\": 3\n}").unwrap();
+
+        // The last occurrence wins, as when the document is parsed
+        assert_eq!(map.line_of("$.a"), Some(4));
+        assert_eq!(map.line_of("$.b.a"), Some(3));
+        assert_eq!(map.paths(), vec!["$", "$.a", "$.b", "$.b.a"]);
+    }
+}
@@ -0,0 +1,236 @@
+//! JSON Merge Patch (RFC 7386) documents made from the entries of a result
+//!
+//! A merge patch is an object holding the new value of each changed or added key,
+//! and `null` for each removed one. It has no way to touch single elements, so an
+//! array is written whole, and only when the result holds its new value: a result
+//! compared with `identify_array_item_changes` off reports a changed array as one
+//! entry with both values. Element changes of any other array are left out with a
+//! warning. A key whose new value is `null` is written as `null` too, which applying
+//! the patch reads as removing the key; that is warned about as well.
+//!
+//! Ignored and tolerated values, key order changes and entries compared across a
+//! path mapping are not patched.
+
+use std::collections::BTreeSet;
+
+use serde_json::{Map, Value};
+
+use crate::diff::{DiffEntry, DiffResult, DiffType};
+use crate::path::{JsonPath, Step};
+
+/// The merge patch of `result` and the problems found making it, see the
+/// [module documentation](self)
+pub fn from_result(result: &DiffResult) -> (Value, Vec<String>) {
+    let mut patch = Value::Object(Map::new());
+    let mut warnings = Vec::new();
+    let mut partial_arrays = BTreeSet::new();
+
+    for entry in &result.entries {
+        let patched = matches!(
+            entry.diff_type,
+            DiffType::Added | DiffType::Removed | DiffType::Modified | DiffType::ArrayItemChanged | DiffType::Moved
+        );
+        if !patched || entry.mapped_to.is_some() {
+            continue;
+        }
+        let Some(steps) = JsonPath::new(entry.indexed_path()).ok().and_then(|path| path.steps()) else {
+            warnings.push(format!("{} is left out of the merge patch: its path is not made of keys and indices", entry.path));
+            continue;
+        };
+
+        if let Some(index) = steps.iter().position(|step| matches!(step, Step::Index(_))) {
+            partial_arrays.insert(path_of(&steps[..index]));
+            continue;
+        }
+        let value = match &entry.diff_type {
+            DiffType::Removed => Value::Null,
+            _ => match &entry.new_value {
+                Some(value) => value.clone(),
+                None => {
+                    warnings.push(format!("{} is left out of the merge patch: the result does not hold its new value", entry.path));
+                    continue;
+                }
+            },
+        };
+        if entry.diff_type != DiffType::Removed {
+            note_value(entry, &value, &mut warnings);
+        }
+        set(&mut patch, &steps, value);
+    }
+
+    for path in partial_arrays {
+        warnings.push(format!(
+            "{} is left out of the merge patch: its elements changed, and a merge patch can only replace the \
+             whole array, which the result does not hold",
+            path
+        ));
+    }
+
+    (patch, warnings)
+}
+
+/// Warn about a new value the merge patch cannot carry as it is
+fn note_value(entry: &DiffEntry, value: &Value, warnings: &mut Vec<String>) {
+    if value.is_null() {
+        warnings.push(format!("{} becomes null, which the merge patch writes as removing the key", entry.path));
+    } else if holds_null_member(value) {
+        warnings.push(format!("{} holds null members, which applying the merge patch leaves out", entry.path));
+    } else if value.is_array() {
+        warnings.push(format!("{} is replaced as a whole array", entry.path));
+    }
+}
+
+/// Whether `value` is an object with a `null` member, at any depth of objects
+fn holds_null_member(value: &Value) -> bool {
+    value.as_object().is_some_and(|map| map.values().any(|member| member.is_null() || holds_null_member(member)))
+}
+
+/// The path of the keys in `steps`, as entries report it
+fn path_of(steps: &[Step]) -> String {
+    let mut path = "$".to_string();
+    for step in steps {
+        match step {
+            Step::Key(key) => crate::path::push_key(&mut path, key),
+            Step::Index(index) => path.push_str(&format!("[{}]", index)),
+        }
+    }
+    path
+}
+
+/// Set the member of `patch` at the keys `steps` to `value`, adding objects on the way
+fn set(patch: &mut Value, steps: &[Step], value: Value) {
+    let Some((Step::Key(last), parents)) = steps.split_last() else {
+        *patch = value;
+        return;
+    };
+    let mut target = patch;
+    for step in parents {
+        let Step::Key(key) = step else { return };
+        let Value::Object(map) = target else { return };
+        target = map.entry(key.clone()).or_insert_with(|| Value::Object(Map::new()));
+    }
+    if let Value::Object(map) = target {
+        map.insert(last.clone(), value);
+    }
+}
+
+/// Apply the merge patch `patch` to `base` as RFC 7386 describes
+///
+/// Members of an object patch are merged into the object they name, `null` members
+/// remove their key, and any other patch replaces the value it is applied to.
+pub fn apply(base: &Value, patch: &Value) -> Value {
+    let Value::Object(members) = patch else { return patch.clone() };
+    let mut target = match base {
+        Value::Object(map) => map.clone(),
+        _ => Map::new(),
+    };
+    for (key, member) in members {
+        if member.is_null() {
+            // Rebuilt rather than removed from, which would move the last key into its place
+            target = std::mem::take(&mut target).into_iter().filter(|(other, _)| other != key).collect();
+        } else {
+            let merged = apply(target.get(key).unwrap_or(&Value::Null), member);
+            target.insert(key.clone(), merged);
+        }
+    }
+    Value::Object(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compare::{compare_json, CompareOptions};
+    use serde_json::json;
+
+    fn whole_arrays() -> CompareOptions {
+        CompareOptions { identify_array_item_changes: false, ..CompareOptions::default() }
+    }
+
+    fn merge_patch(left: &Value, right: &Value, options: &CompareOptions) -> (Value, Vec<String>) {
+        from_result(&compare_json(left, right, options).unwrap())
+    }
+
+    #[test]
+    fn test_nested_additions_and_removals() {
+        let left = json!({"a": 1, "b": {"c": 2, "d": {"e": 3}}, "gone": true});
+        let right = json!({"a": 1, "b": {"c": 5, "d": {"e": 3, "f": {"g": 4}}}, "new": "x"});
+        let (patch, warnings) = merge_patch(&left, &right, &CompareOptions::default());
+        assert_eq!(patch, json!({"b": {"c": 5, "d": {"f": {"g": 4}}}, "gone": null, "new": "x"}));
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(apply(&left, &patch), right);
+    }
+
+    #[test]
+    fn test_arrays_are_replaced_whole() {
+        let left = json!({"items": [1, 2, 3], "nested": {"tags": ["a"]}});
+        let right = json!({"items": [1, 3], "nested": {"tags": ["a", "b"]}});
+        let (patch, warnings) = merge_patch(&left, &right, &whole_arrays());
+        assert_eq!(patch, json!({"items": [1, 3], "nested": {"tags": ["a", "b"]}}));
+        assert_eq!(warnings, vec!["$.items is replaced as a whole array", "$.nested.tags is replaced as a whole array"]);
+        assert_eq!(apply(&left, &patch), right);
+
+        // Element changes alone do not tell the new array
+        let (patch, warnings) = merge_patch(&left, &right, &CompareOptions::default());
+        assert_eq!(patch, json!({}));
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("$.items is left out of the merge patch: its elements changed"), "{:?}", warnings);
+    }
+
+    #[test]
+    fn test_null_values() {
+        // A key set to null is written as null, so the patch removes it
+        let left = json!({"a": 1, "b": {"c": 2}});
+        let right = json!({"a": null, "b": {"c": 2, "d": null}});
+        let (patch, warnings) = merge_patch(&left, &right, &CompareOptions::default());
+        assert_eq!(patch, json!({"a": null, "b": {"d": null}}));
+        assert_eq!(
+            warnings,
+            vec![
+                "$.a becomes null, which the merge patch writes as removing the key",
+                "$.b.d becomes null, which the merge patch writes as removing the key",
+            ]
+        );
+        assert_eq!(apply(&left, &patch), json!({"b": {"c": 2}}));
+
+        // Nulls inside a whole array are kept
+        let left = json!({"items": [1]});
+        let right = json!({"items": [null, {"x": null}]});
+        let (patch, _) = merge_patch(&left, &right, &whole_arrays());
+        assert_eq!(apply(&left, &patch), right);
+    }
+
+    #[test]
+    fn test_root_changes() {
+        let (patch, _) = merge_patch(&json!({"a": 1}), &json!([1, 2]), &CompareOptions::default());
+        assert_eq!(patch, json!([1, 2]));
+        assert_eq!(apply(&json!({"a": 1}), &patch), json!([1, 2]));
+
+        let (patch, _) = merge_patch(&json!("text"), &json!({"a": 1}), &CompareOptions::default());
+        assert_eq!(apply(&json!("text"), &patch), json!({"a": 1}));
+    }
+
+    #[test]
+    fn test_apply() {
+        // The examples of RFC 7386, appendix A
+        let cases = [
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "b"}), json!({"b": "c"}), json!({"a": "b", "b": "c"})),
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (json!({"a": "b", "b": "c"}), json!({"a": null}), json!({"b": "c"})),
+            (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "c"}), json!({"a": ["b"]}), json!({"a": ["b"]})),
+            (json!({"a": {"b": "c"}}), json!({"a": {"b": "d", "c": null}}), json!({"a": {"b": "d"}})),
+            (json!({"a": [{"b": "c"}]}), json!({"a": [1]}), json!({"a": [1]})),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a": "b"}), json!(["c"]), json!(["c"])),
+            (json!({"a": "foo"}), json!(null), json!(null)),
+            (json!({"a": "foo"}), json!("bar"), json!("bar")),
+            (json!({"e": null}), json!({"a": 1}), json!({"e": null, "a": 1})),
+            (json!([1, 2]), json!({"a": "b", "c": null}), json!({"a": "b"})),
+            (json!({}), json!({"a": {"bb": {"ccc": null}}}), json!({"a": {"bb": {}}})),
+        ];
+        for (base, patch, expected) in cases {
+            assert_eq!(apply(&base, &patch), expected, "{} with {}", base, patch);
+        }
+    }
+}
@@ -0,0 +1,478 @@
+//! JSON Patch (RFC 6902) documents made from the entries of a result
+//!
+//! The operations are ordered so that each path is valid when it is applied: changes
+//! inside an array come before the array's elements shift, removed elements are taken
+//! out from the highest index down, and elements are then added at their index in the
+//! right document, lowest first. An element reported as moved is removed and added
+//! again. A subtree where most leaves changed can be replaced as a whole instead.
+//!
+//! Only differences are patched: ignored and tolerated values keep their left value,
+//! and the keys of a changed key order their left order. Entries compared across a
+//! path mapping, entries whose path does not read back as keys and indices, such as
+//! those inside embedded documents, and changes whose values were left out of the
+//! result are skipped. Arrays compared without order come out equal to the
+//! right one under the same comparison rather than identical to it, with added
+//! elements at the end of an unordered array.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde_json::{json, Value};
+
+use crate::diff::{DiffEntry, DiffResult, DiffType};
+use crate::error::JsonDiffError;
+use crate::path::{JsonPath, Step};
+
+/// How [`DiffResult::to_json_patch_optimized`] builds a patch
+#[derive(Debug, Clone, Copy)]
+pub struct PatchOptions<'a> {
+    /// Share of the leaves of an object or array, from 0.0 to 1.0, that must have
+    /// changed for it to be replaced in one operation; `None` never replaces a subtree
+    pub replace_threshold: Option<f64>,
+    /// The left document, which replacing a subtree needs to count its leaves and
+    /// build its new value; without it no subtree is replaced
+    pub left: Option<&'a Value>,
+}
+
+impl Default for PatchOptions<'_> {
+    fn default() -> Self {
+        Self { replace_threshold: Some(0.5), left: None }
+    }
+}
+
+/// The changes at a path, and those below it
+#[derive(Default)]
+struct Node<'r> {
+    entry: Option<&'r DiffEntry>,
+    children: Vec<(Step, Node<'r>)>,
+    index: HashMap<Step, usize>,
+    /// Left indices of the removed elements
+    removed: Vec<usize>,
+    /// Right indices and values of the added elements
+    added: Vec<(usize, &'r Value)>,
+    /// Left index, right index and value of the moved elements
+    moved: Vec<(usize, usize, &'r Value)>,
+    /// Number of differences at and below the path
+    changes: usize,
+}
+
+impl<'r> Node<'r> {
+    fn child(&mut self, step: Step) -> &mut Node<'r> {
+        let position = *self.index.entry(step.clone()).or_insert_with(|| {
+            self.children.push((step, Node::default()));
+            self.children.len() - 1
+        });
+        &mut self.children[position].1
+    }
+
+    fn insert(&mut self, steps: &[Step], entry: &'r DiffEntry) {
+        self.changes += 1;
+        match steps {
+            [] => self.entry = Some(entry),
+            [Step::Index(index)] if matches!(entry.diff_type, DiffType::Added | DiffType::Removed | DiffType::Moved) => {
+                let value = entry.new_value.as_ref().or(entry.old_value.as_ref());
+                match (&entry.diff_type, value, entry.moved_to) {
+                    (DiffType::Removed, _, _) => self.removed.push(*index),
+                    (DiffType::Added, Some(value), _) => self.added.push((*index, value)),
+                    (DiffType::Moved, Some(value), Some(to)) => self.moved.push((*index, to, value)),
+                    _ => self.changes -= 1,
+                }
+            }
+            [step, rest @ ..] => self.child(step.clone()).insert(rest, entry),
+        }
+    }
+}
+
+/// The patch of `result`, see [`DiffResult::to_json_patch_optimized`]
+pub(crate) fn json_patch(result: &DiffResult, options: PatchOptions<'_>) -> Value {
+    let mut root = Node::default();
+    for entry in &result.entries {
+        let patched = matches!(
+            entry.diff_type,
+            DiffType::Added | DiffType::Removed | DiffType::Modified | DiffType::ArrayItemChanged | DiffType::Moved | DiffType::ArrayReordered
+        );
+        if !patched || entry.mapped_to.is_some() {
+            continue;
+        }
+        if let Some(steps) = JsonPath::new(entry.indexed_path()).ok().and_then(|path| path.steps()) {
+            root.insert(&steps, entry);
+        }
+    }
+
+    let mut operations = Vec::new();
+    emit(&root, "", options.left, options, &mut operations);
+    Value::Array(operations)
+}
+
+/// Append the operations of `node`, at `pointer` in the left document, to `operations`
+fn emit(node: &Node<'_>, pointer: &str, left: Option<&Value>, options: PatchOptions<'_>, operations: &mut Vec<Value>) {
+    let mut own = Vec::new();
+    emit_changes(node, left, options, &mut own);
+
+    let replacement = match (options.replace_threshold, left) {
+        (Some(threshold), Some(left)) if own.len() > 1 && node.entry.is_none() => {
+            let ratio = node.changes as f64 / leaf_count(left).max(1) as f64;
+            let mut value = left.clone();
+            (ratio > threshold && apply_patch(&mut value, &own).is_ok()).then_some(value)
+        }
+        _ => None,
+    };
+
+    match replacement {
+        Some(value) => operations.push(json!({"op": "replace", "path": pointer, "value": value})),
+        None => operations.extend(own.into_iter().map(|operation| anchored(operation, pointer))),
+    }
+}
+
+/// The operations of `node` with pointers relative to it
+fn emit_changes(node: &Node<'_>, left: Option<&Value>, options: PatchOptions<'_>, operations: &mut Vec<Value>) {
+    let mut unordered = false;
+    if let Some(entry) = node.entry {
+        match (&entry.diff_type, &entry.new_value) {
+            (DiffType::Added, Some(value)) => operations.push(json!({"op": "add", "path": "", "value": value})),
+            (DiffType::Removed, _) => operations.push(json!({"op": "remove", "path": ""})),
+            (DiffType::Modified | DiffType::ArrayItemChanged, Some(value)) => {
+                operations.push(json!({"op": "replace", "path": "", "value": value}))
+            }
+            (DiffType::ArrayReordered, _) => unordered = true,
+            _ => {}
+        }
+    }
+
+    // Children are addressed in left indices, so they go before the elements shift
+    for (step, child) in &node.children {
+        let (token, below) = match step {
+            Step::Key(key) => (escape(key), left.and_then(|left| left.get(key))),
+            Step::Index(index) => (index.to_string(), left.and_then(|left| left.get(index))),
+        };
+        emit(child, &format!("/{}", token), below, options, operations);
+    }
+
+    let mut gone: Vec<usize> = node.removed.iter().copied().chain(node.moved.iter().map(|&(from, _, _)| from)).collect();
+    gone.sort_unstable_by(|a, b| b.cmp(a));
+    gone.dedup();
+    operations.extend(gone.iter().map(|index| json!({"op": "remove", "path": format!("/{}", index)})));
+
+    if unordered {
+        let mut added = node.added.clone();
+        added.sort_by_key(|&(index, _)| index);
+        operations.extend(added.into_iter().map(|(_, value)| json!({"op": "add", "path": "/-", "value": value})));
+    } else {
+        let compared = node.children.iter().filter_map(|(step, _)| match step {
+            Step::Index(index) => Some(*index),
+            Step::Key(_) => None,
+        });
+        arrange(node, &gone, compared.collect(), operations);
+    }
+}
+
+/// Add the new elements of an array whose removed elements are gone, moving the kept
+/// ones where they differ in order
+///
+/// Kept elements compared at an index stay at it; the others keep their order and fill
+/// the indices left.
+fn arrange(node: &Node<'_>, gone: &[usize], compared: BTreeSet<usize>, operations: &mut Vec<Value>) {
+    let inserted: BTreeMap<usize, &Value> =
+        node.added.iter().copied().chain(node.moved.iter().map(|&(_, to, value)| (to, value))).collect();
+    let Some(last) = inserted.keys().chain(&compared).max().copied() else { return };
+
+    let gone: BTreeSet<usize> = gone.iter().copied().collect();
+    let mentioned = gone.iter().chain(&compared).max().map_or(0, |max| max + 1);
+    // Left indices of the elements, `None` for the added ones; the elements past the
+    // last index mentioned are appended when needed
+    let mut current: Vec<Option<usize>> = (0..mentioned).filter(|i| !gone.contains(i)).map(Some).collect();
+    let mut unseen = mentioned;
+    let mut plain = (0..).filter(|i| !gone.contains(i) && !compared.contains(i));
+
+    for j in 0..=last {
+        if let Some(value) = inserted.get(&j) {
+            operations.push(json!({"op": "add", "path": format!("/{}", j), "value": value}));
+            current.insert(j.min(current.len()), None);
+            continue;
+        }
+        let wanted = if compared.contains(&j) { j } else { plain.next().unwrap_or(j) };
+        while wanted >= unseen {
+            current.push(Some(unseen));
+            unseen += 1;
+        }
+        if current.get(j) == Some(&Some(wanted)) {
+            continue;
+        }
+        if let Some(from) = current.iter().position(|&element| element == Some(wanted)) {
+            operations.push(json!({"op": "move", "from": format!("/{}", from), "path": format!("/{}", j)}));
+            current.remove(from);
+            current.insert(j.min(current.len()), Some(wanted));
+        }
+    }
+}
+
+/// `operation` with its pointers under `pointer`
+fn anchored(mut operation: Value, pointer: &str) -> Value {
+    for key in ["path", "from"] {
+        if let Some(Value::String(relative)) = operation.get_mut(key) {
+            relative.insert_str(0, pointer);
+        }
+    }
+    operation
+}
+
+/// A key as a JSON Pointer reference token
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Number of scalars and empty objects and arrays in `value`
+fn leaf_count(value: &Value) -> usize {
+    match value {
+        Value::Object(map) if !map.is_empty() => map.values().map(leaf_count).sum(),
+        Value::Array(items) if !items.is_empty() => items.iter().map(leaf_count).sum(),
+        _ => 1,
+    }
+}
+
+/// Apply a JSON Patch, such as one of [`DiffResult::to_json_patch_optimized`], to `document`
+///
+/// Its patches only hold `add`, `remove`, `replace` and `move` operations; any other,
+/// or a path missing from `document`, fails with [`JsonDiffError::InvalidPatch`] and
+/// leaves `document` patched up to that operation.
+pub fn apply_json_patch(document: &mut Value, patch: &Value) -> Result<(), JsonDiffError> {
+    let operations = patch.as_array().ok_or_else(|| JsonDiffError::InvalidPatch("not an array of operations".to_string()))?;
+    apply_patch(document, operations).map_err(JsonDiffError::InvalidPatch)
+}
+
+/// Apply the `add`, `remove`, `replace` and `move` operations of `patch` to `document`
+pub(crate) fn apply_patch(document: &mut Value, patch: &[Value]) -> Result<(), String> {
+    for operation in patch {
+        let path = operation["path"].as_str().ok_or_else(|| format!("no path in {}", operation))?;
+        match operation["op"].as_str() {
+            Some("add") => add(document, path, operation["value"].clone())?,
+            Some("remove") => {
+                take(document, path)?;
+            }
+            Some("replace") => {
+                *document.pointer_mut(path).ok_or_else(|| format!("nothing at {}", path))? = operation["value"].clone();
+            }
+            Some("move") => {
+                let from = operation["from"].as_str().ok_or_else(|| format!("no from in {}", operation))?;
+                let value = take(document, from)?;
+                add(document, path, value)?;
+            }
+            _ => return Err(format!("unsupported operation {}", operation)),
+        }
+    }
+    Ok(())
+}
+
+/// The container at the parent of `path` and the last reference token of `path`
+fn parent<'v>(document: &'v mut Value, path: &str) -> Result<(&'v mut Value, String), String> {
+    let (parent, token) = path.rsplit_once('/').ok_or_else(|| format!("invalid pointer {}", path))?;
+    let container = document.pointer_mut(parent).ok_or_else(|| format!("nothing at {}", parent))?;
+    Ok((container, token.replace("~1", "/").replace("~0", "~")))
+}
+
+fn add(document: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    if path.is_empty() {
+        *document = value;
+        return Ok(());
+    }
+    match parent(document, path)? {
+        (Value::Object(map), key) => {
+            map.insert(key, value);
+        }
+        (Value::Array(items), token) if token == "-" => items.push(value),
+        (Value::Array(items), token) => match token.parse::<usize>() {
+            Ok(index) if index <= items.len() => items.insert(index, value),
+            _ => return Err(format!("no index {} to add at in {}", token, path)),
+        },
+        _ => return Err(format!("no container to add {} in", path)),
+    }
+    Ok(())
+}
+
+fn take(document: &mut Value, path: &str) -> Result<Value, String> {
+    match parent(document, path)? {
+        (Value::Object(map), key) => {
+            let taken = map.get(&key).cloned().ok_or_else(|| format!("nothing at {}", path))?;
+            // Rebuilt rather than removed from, which would move the last key into its place
+            *map = std::mem::take(map).into_iter().filter(|(other, _)| *other != key).collect();
+            Ok(taken)
+        }
+        (Value::Array(items), token) => match token.parse::<usize>() {
+            Ok(index) if index < items.len() => Ok(items.remove(index)),
+            _ => Err(format!("nothing at {}", path)),
+        },
+        _ => Err(format!("nothing at {}", path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compare::{compare_json, CompareOptions};
+
+    fn patch(left: &Value, right: &Value, options: &CompareOptions, threshold: Option<f64>) -> Vec<Value> {
+        let result = compare_json(left, right, options).unwrap();
+        let patch = result.to_json_patch_optimized(PatchOptions { replace_threshold: threshold, left: Some(left) });
+        patch.as_array().unwrap().clone()
+    }
+
+    /// Apply the patch of `left` and `right`, with and without replacing subtrees
+    fn patched(left: &Value, right: &Value, options: &CompareOptions) -> Vec<Value> {
+        let mut documents = Vec::new();
+        for threshold in [None, Some(0.5), Some(0.0)] {
+            let patch = patch(left, right, options, threshold);
+            let mut document = left.clone();
+            apply_patch(&mut document, &patch).unwrap_or_else(|e| panic!("{} in {:?}", e, patch));
+            documents.push(document);
+        }
+        documents
+    }
+
+    fn assert_patches(left: Value, right: Value, options: &CompareOptions) {
+        for document in patched(&left, &right, options) {
+            assert_eq!(document, right, "from {}", left);
+        }
+    }
+
+    #[test]
+    fn test_apply_json_patch() {
+        let (left, right) = (json!({"a": [1, 2, 3], "b": {"c": true}}), json!({"a": [3, 1], "b": {}, "d": null}));
+        let result = compare_json(&left, &right, &with_moves()).unwrap();
+        let mut document = left.clone();
+        apply_json_patch(&mut document, &result.to_json_patch_optimized(PatchOptions::default())).unwrap();
+        assert_eq!(document, right);
+
+        let error = apply_json_patch(&mut document, &json!([{"op": "test", "path": "/a", "value": 1}])).unwrap_err();
+        assert_eq!(error.to_string(), r#"Failed to apply JSON patch: unsupported operation {"op":"test","path":"/a","value":1}"#);
+        assert!(matches!(apply_json_patch(&mut document, &json!({"op": "add"})), Err(JsonDiffError::InvalidPatch(_))));
+        assert!(apply_json_patch(&mut document, &json!([{"op": "remove", "path": "/missing"}])).is_err());
+    }
+
+    fn with_moves() -> CompareOptions {
+        CompareOptions { detect_array_moves: true, ..CompareOptions::default() }
+    }
+
+    #[test]
+    fn test_patch_corpus() {
+        let cases = [
+            (json!({"a": 1, "b": {"c": [1, 2]}}), json!({"a": 2, "b": {"c": [1, 2, 3]}, "d": null})),
+            (json!({"a": {"x": 1, "y": 2, "z": 3}, "k": 0}), json!({"a": {"x": 4, "y": 5, "z": 6}, "k": 0})),
+            (json!([1, 2, 3, 4, 5]), json!([1, 9])),
+            (json!([1]), json!([1, 2, 3, [4]])),
+            (json!([{"id": 1, "tags": ["a", "b", "c"]}, {"id": 2}]), json!([{"id": 1, "tags": ["b"]}, {"id": 3}, {"id": 4}])),
+            (json!({"a/b": {"~c": 1}}), json!({"a/b": {"~c": 2, "d e": 3}})),
+            (json!(1), json!({"a": 1})),
+            (json!({"a": [1]}), json!([{"a": 1}])),
+            (json!({"a": [[1, 2], [3]]}), json!({"a": [[2], [3, 4], []]})),
+            (json!({}), json!({"deep": {"er": [1, {"est": true}]}})),
+        ];
+        for (left, right) in cases.clone() {
+            assert_patches(left, right, &CompareOptions::default());
+        }
+        for (left, right) in cases {
+            assert_patches(left, right, &with_moves());
+        }
+    }
+
+    #[test]
+    fn test_patch_interleaved_array_changes() {
+        let cases = [
+            (json!([1, 2, 3, 4, 5]), json!([0, 2, 9, 4, 6, 5, 7])),
+            (json!(["a", "b", "c", "d"]), json!(["d", "a", "b", "c"])),
+            (json!(["x", "y"]), json!(["p", "q", "x"])),
+            (json!([1, 2, 3, 4, 5, 6, 7, 8]), json!([8, 0, 2, 4, 6, 1, 10])),
+            (json!([{"n": [1, 2]}, 7, 8]), json!([0, {"n": [2, 3]}, 8, 7])),
+            (json!({"a": [1, 2, 3], "b": [3, 2, 1]}), json!({"a": [3, 1], "b": [2, 3, 4, 1]})),
+        ];
+        for (left, right) in cases {
+            assert_patches(left, right, &with_moves());
+        }
+    }
+
+    #[test]
+    fn test_patch_corpus_sweep() {
+        // Small arrays over few values, so that moves, repeats and shifts mix
+        let mut seed: u64 = 7;
+        let mut next = move |bound: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % bound
+        };
+        for _ in 0..300 {
+            let mut array = || -> Value { (0..next(6)).map(|_| json!({"v": next(4)})).collect() };
+            let (left, right) = (json!({"items": array(), "n": 1}), json!({"items": array(), "n": 1}));
+            assert_patches(left.clone(), right.clone(), &CompareOptions::default());
+            assert_patches(left, right, &with_moves());
+        }
+    }
+
+    #[test]
+    fn test_removed_elements_go_highest_first_and_added_ones_lowest_first() {
+        let removes = patch(&json!([1, 2, 3, 4]), &json!([1]), &CompareOptions::default(), None);
+        assert_eq!(
+            removes,
+            vec![json!({"op": "remove", "path": "/3"}), json!({"op": "remove", "path": "/2"}), json!({"op": "remove", "path": "/1"})]
+        );
+
+        let adds = patch(&json!([1, 3, 5]), &json!([0, 1, 2, 3, 4, 5]), &with_moves(), None);
+        assert_eq!(
+            adds,
+            vec![
+                json!({"op": "add", "path": "/0", "value": 0}),
+                json!({"op": "add", "path": "/2", "value": 2}),
+                json!({"op": "add", "path": "/4", "value": 4}),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mostly_changed_subtree_is_replaced() {
+        let left = json!({"a": {"x": 1, "y": 2, "z": 3}, "b": {"x": 1, "y": 2, "z": 3}});
+        let right = json!({"a": {"x": 4, "y": 5, "z": 3}, "b": {"x": 1, "y": 2, "z": 4}});
+        assert_eq!(
+            patch(&left, &right, &CompareOptions::default(), Some(0.5)),
+            vec![
+                json!({"op": "replace", "path": "/a", "value": {"x": 4, "y": 5, "z": 3}}),
+                json!({"op": "replace", "path": "/b/z", "value": 4}),
+            ]
+        );
+        assert_eq!(patch(&left, &right, &CompareOptions::default(), Some(0.7)).len(), 3);
+        assert_eq!(patch(&left, &right, &CompareOptions::default(), Some(0.0)), vec![json!({"op": "replace", "path": "", "value": right})]);
+
+        // Without the left document nothing can be replaced as a whole
+        let result = compare_json(&left, &right, &CompareOptions::default()).unwrap();
+        assert_eq!(result.to_json_patch_optimized(PatchOptions::default()).as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_root_and_escaped_keys() {
+        assert_eq!(
+            patch(&json!([1]), &json!("one"), &CompareOptions::default(), None),
+            vec![json!({"op": "replace", "path": "", "value": "one"})]
+        );
+        assert_eq!(
+            patch(&json!({"a/b": {"~c": 1}}), &json!({"a/b": {"~c": 2}}), &CompareOptions::default(), None),
+            vec![json!({"op": "replace", "path": "/a~1b/~0c", "value": 2})]
+        );
+    }
+
+    #[test]
+    fn test_unordered_arrays_come_out_equal() {
+        let options = CompareOptions {
+            unordered_arrays: vec![JsonPath::new("$.items").unwrap()],
+            show_nested_differences: true,
+            ..CompareOptions::default()
+        };
+        let left = json!({"items": [{"id": 1, "n": 1}, {"id": 2}, {"id": 3}]});
+        let right = json!({"items": [{"id": 4}, {"id": 3}, {"id": 1, "n": 2}]});
+        for document in patched(&left, &right, &options) {
+            let result = compare_json(&document, &right, &options).unwrap();
+            assert!(result.iter().all(|entry| entry.diff_type == DiffType::ArrayReordered), "{}", document);
+        }
+    }
+
+    #[test]
+    fn test_unpatched_entries() {
+        let options = CompareOptions { ignore_paths: vec![JsonPath::new("$.ts").unwrap()], ..CompareOptions::default() };
+        let patch = patch(&json!({"ts": 1, "a": 1}), &json!({"ts": 2, "a": 2}), &options, None);
+        assert_eq!(patch, vec![json!({"op": "replace", "path": "/a", "value": 2})]);
+    }
+}
@@ -1,39 +1,842 @@
-use regex::Regex;
+use std::fmt;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
 use crate::error::JsonDiffError;
 
 /// Represents a JSON path for matching elements
+///
+/// Paths are written as `$` followed by segments:
+///
+/// - `.key` or `["quoted key"]` - an object property (`\"` and `\\` escape inside quotes)
+/// - `.*` - any property; `*` inside a key, as in `.*_time`, matches any characters
+/// - `[3]` - an array index, `[*]` any index
+/// - `[?id="u-42"]`, `[?rank=3]` - the array element whose `id` (or `rank`) is that string
+///   or number, at whatever index it is (see [`JsonPath::matches`])
+/// - `[id=P1]`, `[id="a b"]`, `[rank=3]` - the same, as entries name the elements of
+///   arrays matched by key (see `IndexStyle::Keyed`); a value without quotes is a number
+///   if it reads as one, and a string otherwise
+/// - `(json)` - the document embedded in a string (see `parse_embedded_json`)
+/// - `..key` - `key` at any depth; `.**` - any number of segments, including none
 #[derive(Debug, Clone)]
 pub struct JsonPath {
     path: String,
-    regex: Option<Regex>,
+    matcher: Matcher,
 }
 
+#[derive(Debug, Clone)]
+enum Matcher {
+    Segments(Vec<Segment>),
+    Regex { regex: Regex, case_insensitive: bool },
+}
+
+/// How a regex path pattern is matched, for [`JsonPath::with_regex_opts`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegexOpts {
+    /// The pattern must match the whole path rather than any part of it
+    pub anchored: bool,
+    /// Letters match regardless of case
+    pub case_insensitive: bool,
+}
+
+impl Default for RegexOpts {
+    fn default() -> Self {
+        Self { anchored: true, case_insensitive: false }
+    }
+}
+
+/// A parsed path segment
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    /// A key with `*` wildcards
+    KeyPattern(String),
+    Index(usize),
+    AnyIndex,
+    /// The element whose key has the value
+    Filter(Filter),
+    Embedded,
+    /// Any number of segments
+    Descendants,
+}
+
+/// An array element filter: the element is an object whose `key` has `value`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Filter {
+    pub(crate) key: String,
+    /// A string or a number
+    pub(crate) value: Value,
+}
+
+impl Filter {
+    /// Whether `element` passes the filter; numbers are equal by value, so `3` is `3.0`
+    pub(crate) fn holds(&self, element: &Value) -> bool {
+        element.get(&self.key).is_some_and(|actual| same_value(actual, &self.value))
+    }
+}
+
+fn same_value(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Number(actual), Value::Number(expected)) => actual.as_f64() == expected.as_f64(),
+        (actual, expected) => actual == expected,
+    }
+}
+
+/// Marker of a document embedded in a string value
+const EMBEDDED: &str = "(json)";
+
 impl JsonPath {
-    /// Create a new JSON path
+    /// Create a new JSON path, failing with the position of the problem if it is malformed
     pub fn new(path: &str) -> Result<Self, JsonDiffError> {
         Ok(Self {
             path: path.to_string(),
-            regex: None,
+            matcher: Matcher::Segments(Parser { path, pos: 0 }.parse()?),
         })
     }
-    
+
     /// Create a JSON path with regex pattern matching
+    ///
+    /// The pattern matches anywhere in a path unless it is anchored with `^` and `$`
+    /// itself, so `id` also matches `$.order.id.history`; prefer
+    /// [`JsonPath::with_regex_opts`], which anchors by default.
     pub fn with_regex(path: &str, pattern: &str) -> Result<Self, JsonDiffError> {
-        let regex = Regex::new(pattern).map_err(JsonDiffError::from)?;
-        
+        Self::with_regex_opts(path, pattern, RegexOpts { anchored: false, case_insensitive: false })
+    }
+
+    /// Create a JSON path matching a regex pattern as described by `opts`
+    pub fn with_regex_opts(path: &str, pattern: &str, opts: RegexOpts) -> Result<Self, JsonDiffError> {
+        let pattern = if opts.anchored { format!("^(?:{})$", pattern) } else { pattern.to_string() };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(opts.case_insensitive)
+            .build()
+            .map_err(JsonDiffError::from)?;
+
         Ok(Self {
             path: path.to_string(),
-            regex: Some(regex),
+            matcher: Matcher::Regex { regex, case_insensitive: opts.case_insensitive },
         })
     }
-    
+
     /// Check if this path matches the given path string
+    ///
+    /// A path string does not tell which elements its indices stand for, so a filter
+    /// segment such as `[?id="u-42"]` never matches here; the comparison resolves
+    /// filters against the elements themselves.
     pub fn matches(&self, path: &str) -> bool {
-        if let Some(regex) = &self.regex {
-            regex.is_match(path)
+        self.matches_with(path, &|_, _| false)
+    }
+
+    /// Check if this path matches the given path string, with `holds` telling whether
+    /// the element at an element path, such as `$.users[3]`, passes a filter
+    pub(crate) fn matches_with(&self, path: &str, holds: &dyn Fn(&str, &Filter) -> bool) -> bool {
+        match &self.matcher {
+            Matcher::Regex { regex, .. } => regex.is_match(path),
+            Matcher::Segments(segments) => path
+                .strip_prefix('$')
+                .is_some_and(|rest| matches_segments(segments, rest, &FilterContext { path, holds })),
+        }
+    }
+
+    /// Check if this path matches the given path string, with filters resolved against `document`
+    pub fn matches_in(&self, path: &str, document: &Value) -> bool {
+        self.matches_with(path, &|element_path, filter| {
+            JsonPath::new(element_path).ok()
+                .and_then(|element_path| element_path.select(document))
+                .is_some_and(|(_, element)| filter.holds(element))
+        })
+    }
+
+    /// The filter segments of the path
+    pub(crate) fn filters(&self) -> impl Iterator<Item = &Filter> {
+        let segments = match &self.matcher {
+            Matcher::Segments(segments) => segments.as_slice(),
+            Matcher::Regex { .. } => &[],
+        };
+        segments.iter().filter_map(|segment| match segment {
+            Segment::Filter(filter) => Some(filter),
+            _ => None,
+        })
+    }
+
+    /// Whether the path names a single location: no wildcards, descendants or regex
+    pub fn is_concrete(&self) -> bool {
+        match &self.matcher {
+            Matcher::Regex { .. } => false,
+            Matcher::Segments(segments) => segments
+                .iter()
+                .all(|segment| matches!(segment, Segment::Key(_) | Segment::Index(_) | Segment::Embedded)),
+        }
+    }
+}
+
+impl JsonPath {
+    /// The pattern of a regex path, with its flags inline so it can join a `RegexSet`
+    pub(crate) fn set_pattern(&self) -> Option<String> {
+        match &self.matcher {
+            Matcher::Regex { regex, case_insensitive: true } => Some(format!("(?i:{})", regex.as_str())),
+            Matcher::Regex { regex, case_insensitive: false } => Some(regex.as_str().to_string()),
+            Matcher::Segments(_) => None,
+        }
+    }
+
+    /// The value a concrete path names in `value`, with the path as entries report it
+    ///
+    /// Returns `None` if the path is not concrete, crosses into an embedded document,
+    /// or names nothing.
+    pub(crate) fn select<'a>(&self, value: &'a Value) -> Option<(String, &'a Value)> {
+        let Matcher::Segments(segments) = &self.matcher else { return None };
+        let mut path = "$".to_string();
+        let mut value = value;
+        for segment in segments {
+            value = match (segment, value) {
+                (Segment::Key(key), Value::Object(map)) => {
+                    push_key(&mut path, key);
+                    map.get(key)?
+                }
+                (Segment::Index(index), Value::Array(items)) => {
+                    path.push_str(&format!("[{}]", index));
+                    items.get(*index)?
+                }
+                _ => return None,
+            };
+        }
+        Some((path, value))
+    }
+
+    /// The keys and indices of a path made of nothing else, as the path of an entry is
+    pub(crate) fn steps(&self) -> Option<Vec<Step>> {
+        let Matcher::Segments(segments) = &self.matcher else { return None };
+        segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Key(key) => Some(Step::Key(key.clone())),
+                Segment::Index(index) => Some(Step::Index(*index)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A key or an index of a concrete path, see [`JsonPath::steps`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Step {
+    Key(String),
+    Index(usize),
+}
+
+impl JsonPath {
+    /// Why the path cannot name the two ends of a mapping, if it cannot: mappings take
+    /// keys, indices and `[*]`, and end in a key
+    pub(crate) fn mapping_problem(&self) -> Option<&'static str> {
+        let Matcher::Segments(segments) = &self.matcher else {
+            return Some("a mapping cannot use a regex path");
+        };
+        if !segments.iter().all(|segment| matches!(segment, Segment::Key(_) | Segment::Index(_) | Segment::AnyIndex)) {
+            return Some("a mapping path may only hold keys, indices and [*]");
+        }
+        if !matches!(segments.last(), Some(Segment::Key(_))) {
+            return Some("a mapping path must end in a key");
+        }
+        None
+    }
+
+    /// Remove the value a concrete path ending in a key names in `value` and return it
+    pub(crate) fn take(&self, value: &mut Value) -> Option<Value> {
+        let Matcher::Segments(segments) = &self.matcher else { return None };
+        let (Segment::Key(last), parents) = segments.split_last()? else { return None };
+        let mut value = value;
+        for segment in parents {
+            value = match (segment, value) {
+                (Segment::Key(key), Value::Object(map)) => map.get_mut(key)?,
+                (Segment::Index(index), Value::Array(items)) => items.get_mut(*index)?,
+                _ => return None,
+            };
+        }
+        // Rebuilt rather than removed from, which would move the last key into its place
+        let map = value.as_object_mut()?;
+        let taken = map.get(last)?.clone();
+        *map = std::mem::take(map).into_iter().filter(|(key, _)| key != last).collect();
+        Some(taken)
+    }
+
+    /// Number of `[*]` segments
+    pub(crate) fn wildcard_count(&self) -> usize {
+        match &self.matcher {
+            Matcher::Segments(segments) => segments.iter().filter(|segment| **segment == Segment::AnyIndex).count(),
+            Matcher::Regex { .. } => 0,
+        }
+    }
+
+    /// The values the path names in `value`, each with its path and the indices its
+    /// `[*]` segments stood for, in document order
+    ///
+    /// Only keys, indices and `[*]` are followed; other paths select nothing.
+    pub(crate) fn select_all<'a>(&self, value: &'a Value) -> Vec<(String, Vec<usize>, &'a Value)> {
+        fn walk<'a>(segments: &[Segment], value: &'a Value, path: String, indices: Vec<usize>, found: &mut Vec<(String, Vec<usize>, &'a Value)>) {
+            let Some((segment, rest)) = segments.split_first() else {
+                found.push((path, indices, value));
+                return;
+            };
+            match (segment, value) {
+                (Segment::Key(key), Value::Object(map)) => {
+                    if let Some(child) = map.get(key) {
+                        let mut path = path;
+                        push_key(&mut path, key);
+                        walk(rest, child, path, indices, found);
+                    }
+                }
+                (Segment::Index(index), Value::Array(items)) => {
+                    if let Some(child) = items.get(*index) {
+                        walk(rest, child, format!("{}[{}]", path, index), indices, found);
+                    }
+                }
+                (Segment::AnyIndex, Value::Array(items)) => {
+                    for (index, child) in items.iter().enumerate() {
+                        let mut indices = indices.clone();
+                        indices.push(index);
+                        walk(rest, child, format!("{}[{}]", path, index), indices, found);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut found = Vec::new();
+        if let Matcher::Segments(segments) = &self.matcher {
+            walk(segments, value, "$".to_string(), Vec::new(), &mut found);
+        }
+        found
+    }
+
+    /// The concrete path, as entries report it, with the `[*]` segments standing for
+    /// `indices` in order; `None` if the path has other patterns or too few indices are given
+    pub(crate) fn with_indices(&self, indices: &[usize]) -> Option<String> {
+        let Matcher::Segments(segments) = &self.matcher else { return None };
+        let mut indices = indices.iter();
+        let mut path = "$".to_string();
+        for segment in segments {
+            match segment {
+                Segment::Key(key) => push_key(&mut path, key),
+                Segment::Index(index) => path.push_str(&format!("[{}]", index)),
+                Segment::AnyIndex => path.push_str(&format!("[{}]", indices.next()?)),
+                _ => return None,
+            }
+        }
+        Some(path)
+    }
+}
+
+/// Whether a concrete path has an array index segment, and whether its last segment is one
+///
+/// Brackets inside quoted keys such as `["a[1]"]` are part of the key.
+pub(crate) fn index_segments(path: &str) -> (bool, bool) {
+    let bytes = path.as_bytes();
+    let (mut any, mut last) = (false, false);
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'[' if bytes.get(i + 1) == Some(&b'"') => {
+                // Skip the quoted key up to its unescaped closing quote and bracket
+                i += 2;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i += 2;
+                last = false;
+            }
+            b'[' => {
+                let after = element_segment(&path[i..]).map(|(_, after)| after);
+                any |= after.is_some();
+                last = after.is_some();
+                i = after.map_or(i + 1, |after| path.len() - after.len());
+            }
+            _ => {
+                last = false;
+                i += 1;
+            }
+        }
+    }
+
+    (any, last)
+}
+
+/// The path of the object or array holding the value at `path`, `""` for the root
+pub fn parent_path(path: &str) -> &str {
+    let bytes = path.as_bytes();
+    let mut last_start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'[' if bytes.get(i + 1) == Some(&b'"') => {
+                last_start = i;
+                i += 2;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i += 2;
+            }
+            // A key value in quotes may hold delimiters
+            b'[' => {
+                last_start = i;
+                i = element_segment(&path[i..]).map_or(i + 1, |(_, after)| path.len() - after.len());
+            }
+            b'.' | b'(' => {
+                last_start = i;
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+
+    &path[..last_start.min(path.len())]
+}
+
+/// `path` cut to its first `depth` steps below the root, e.g. `$.a` for `$.a.b[0]` at
+/// depth 1; a path with fewer steps is returned whole
+pub(crate) fn ancestor_at_depth(path: &str, depth: usize) -> &str {
+    let mut ancestors = vec![path];
+    while let Some(parent) = ancestors.last().map(|path| parent_path(path)).filter(|parent| !parent.is_empty()) {
+        ancestors.push(parent);
+    }
+    // The last is the root, so the others are the steps below it
+    ancestors[(ancestors.len() - 1).saturating_sub(depth)]
+}
+
+/// `path` relative to `root`, starting at `$`, if it lies within `root`
+pub(crate) fn rebase_path(path: &str, root: &str) -> Option<String> {
+    let rest = path.strip_prefix(root)?;
+    (rest.is_empty() || rest.starts_with(['.', '[', '('])).then(|| format!("${}", rest))
+}
+
+/// The paths of every value in a document, as they are reported and matched, root first
+pub fn value_paths(value: &Value) -> Vec<String> {
+    fn walk(value: &Value, path: &mut String, paths: &mut Vec<String>) {
+        paths.push(path.clone());
+        let len = path.len();
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map {
+                    push_key(path, key);
+                    walk(child, path, paths);
+                    path.truncate(len);
+                }
+            }
+            Value::Array(items) => {
+                for (i, child) in items.iter().enumerate() {
+                    path.push_str(&format!("[{}]", i));
+                    walk(child, path, paths);
+                    path.truncate(len);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut paths = Vec::new();
+    walk(value, &mut "$".to_string(), &mut paths);
+    paths
+}
+
+/// Append `.key` to a path, quoting the key if it would not parse back as written
+pub(crate) fn push_key(path: &mut String, key: &str) {
+    let plain = !key.is_empty() && !key.contains(['.', '[', ']', '(', ')', '*', '"', '\\']);
+    if plain {
+        path.push('.');
+        path.push_str(key);
+    } else {
+        path.push_str("[\"");
+        path.push_str(&key.replace('\\', "\\\\").replace('"', "\\\""));
+        path.push_str("\"]");
+    }
+}
+
+/// The segment naming an array element by the value of its `key`, as entries under
+/// arrays matched by key are reported, e.g. `[id=P1]` or `[id="P 1"]`
+///
+/// Returns `None` if `key` or `value` cannot be written so that the segment parses back:
+/// the key must need no quotes, and the value must be a string or a number. Strings
+/// that would read as numbers, such as `"3"`, are quoted.
+pub(crate) fn keyed_segment(key: &str, value: &Value) -> Option<String> {
+    let plain_key = key.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && !key.contains(|c: char| is_delimiter(c) || matches!(c, '"' | '\\' | '*' | '=' | '?'));
+    if !plain_key {
+        return None;
+    }
+    let value = match value {
+        Value::Number(number) => number.to_string(),
+        Value::String(text) => {
+            let bare = !text.is_empty()
+                && text.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+                && serde_json::from_str::<serde_json::Number>(text).is_err();
+            if bare { text.clone() } else { Value::String(text.clone()).to_string() }
+        }
+        _ => return None,
+    };
+    Some(format!("[{}={}]", key, value))
+}
+
+struct Parser<'a> {
+    path: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(mut self) -> Result<Vec<Segment>, JsonDiffError> {
+        if !self.path.starts_with('$') {
+            return Err(self.error(0, "a path must start with '$'"));
+        }
+        self.pos = 1;
+
+        let mut segments = Vec::new();
+        while let Some(c) = self.peek() {
+            let segment = match c {
+                '.' if self.rest().starts_with("..") => {
+                    self.pos += 2;
+                    segments.push(Segment::Descendants);
+                    match self.peek() {
+                        Some('[') => self.bracket()?,
+                        Some(c) if !is_delimiter(c) => self.key()?,
+                        _ => return Err(self.error(self.pos, "expected a key or '[' after '..'")),
+                    }
+                }
+                '.' => {
+                    self.pos += 1;
+                    if self.rest().starts_with("**") && self.rest()[2..].chars().next().is_none_or(is_delimiter) {
+                        self.pos += 2;
+                        Segment::Descendants
+                    } else {
+                        self.key()?
+                    }
+                }
+                '[' => self.bracket()?,
+                '(' if self.rest().starts_with(EMBEDDED) => {
+                    self.pos += EMBEDDED.len();
+                    Segment::Embedded
+                }
+                '(' => return Err(self.error(self.pos, "only '(json)' may follow a segment in parentheses")),
+                _ if self.pos == 1 => {
+                    return Err(self.error(self.pos, format!("expected '.' or '[' after '$', found '{}'", c)));
+                }
+                _ => return Err(self.error(self.pos, format!("expected '.', '[' or the end of the path, found '{}'", c))),
+            };
+            segments.push(segment);
+        }
+
+        Ok(segments)
+    }
+
+    /// An unquoted key, up to the next delimiter
+    fn key(&mut self) -> Result<Segment, JsonDiffError> {
+        let start = self.pos;
+        let len = self.rest().find(is_delimiter).unwrap_or(self.rest().len());
+        let key = &self.rest()[..len];
+
+        if key.is_empty() {
+            return Err(self.error(start, "empty key; quote keys with special characters as [\"...\"]"));
+        }
+        if let Some(offset) = key.find(['"', '\\']) {
+            return Err(self.error(start + offset, "quote keys with special characters as [\"...\"]"));
+        }
+
+        self.pos += len;
+        Ok(if key.contains('*') { Segment::KeyPattern(key.to_string()) } else { Segment::Key(key.to_string()) })
+    }
+
+    /// `[3]`, `[*]`, `[?key=value]`, `[key=value]` or `["quoted key"]`
+    fn bracket(&mut self) -> Result<Segment, JsonDiffError> {
+        let open = self.pos;
+        self.pos += 1;
+
+        let segment = match self.peek() {
+            Some('"') => self.quoted_key(open)?,
+            Some('*') => {
+                self.pos += 1;
+                Segment::AnyIndex
+            }
+            Some('?') => self.filter()?,
+            Some(c) if c.is_ascii_digit() => {
+                let len = self.rest().find(|c: char| !c.is_ascii_digit()).unwrap_or(self.rest().len());
+                let index = self.rest()[..len].parse()
+                    .map_err(|_| self.error(self.pos, "array index is too large"))?;
+                self.pos += len;
+                Segment::Index(index)
+            }
+            None => return Err(self.error(open, "unterminated '['")),
+            Some(']') => return Err(self.error(self.pos, "empty brackets; expected an index, '*' or a quoted key")),
+            Some(_) if self.rest().find(['=', ']']).is_some_and(|len| self.rest()[len..].starts_with('=')) => self.keyed()?,
+            Some(_) => return Err(self.error(self.pos, "expected an index, '*', a filter, key=value or a quoted key inside '[...]'")),
+        };
+
+        match self.peek() {
+            Some(']') => {
+                self.pos += 1;
+                Ok(segment)
+            }
+            None => Err(self.error(open, "unterminated '['")),
+            Some(_) => Err(self.error(self.pos, "expected ']'")),
+        }
+    }
+
+    /// `?key="string"` or `?key=number`, up to the closing bracket
+    fn filter(&mut self) -> Result<Segment, JsonDiffError> {
+        self.pos += 1;
+        let start = self.pos;
+        let Some(len) = self.rest().find('=') else {
+            return Err(self.error(start, "expected '=' in the filter, as in [?id=\"u-42\"]"));
+        };
+        let key = self.filter_key(len, "in the filter")?;
+        let value_start = self.pos;
+        let len = match self.rest().starts_with('"') {
+            true => self.quoted_len("in the filter")?,
+            false => self.rest().find(']').unwrap_or(self.rest().len()),
+        };
+        let value: Value = serde_json::from_str(&self.rest()[..len])
+            .ok()
+            .filter(|value: &Value| value.is_string() || value.is_number())
+            .ok_or_else(|| self.error(value_start, "a filter compares with a string in quotes or a number"))?;
+        self.pos += len;
+
+        Ok(Segment::Filter(Filter { key, value }))
+    }
+
+    /// `key=value` naming an element by key, up to the closing bracket
+    fn keyed(&mut self) -> Result<Segment, JsonDiffError> {
+        let len = self.rest().find('=').unwrap_or(self.rest().len());
+        let key = self.filter_key(len, "of the element")?;
+        let value_start = self.pos;
+        let value = if self.rest().starts_with('"') {
+            let len = self.quoted_len("naming the element")?;
+            let value = serde_json::from_str(&self.rest()[..len])
+                .map_err(|_| self.error(value_start, "invalid escape in the quoted key value"))?;
+            self.pos += len;
+            value
         } else {
-            self.path == path
+            let len = self.rest().find(']').unwrap_or(self.rest().len());
+            let text = &self.rest()[..len];
+            if text.is_empty() || text.contains(['[', '"', '\\']) {
+                return Err(self.error(value_start, "quote a key value with special characters, as in [id=\"a b\"]"));
+            }
+            self.pos += len;
+            serde_json::from_str::<serde_json::Number>(text)
+                .map_or_else(|_| Value::String(text.to_string()), Value::Number)
+        };
+
+        Ok(Segment::Filter(Filter { key, value }))
+    }
+
+    /// The `len` characters before `=`, a key that needs no quotes, and move past the `=`
+    fn filter_key(&mut self, len: usize, context: &str) -> Result<String, JsonDiffError> {
+        let key = &self.rest()[..len];
+        if key.is_empty() || key.contains(|c: char| is_delimiter(c) || matches!(c, '"' | '\\' | '*')) {
+            return Err(self.error(self.pos, format!("expected a plain key before '=' {}", context)));
+        }
+        self.pos += len + 1;
+        Ok(key.to_string())
+    }
+
+    /// Length of the string in quotes at the start of the rest, up to its unescaped closing quote
+    fn quoted_len(&self, context: &str) -> Result<usize, JsonDiffError> {
+        let mut escaped = false;
+        self.rest().char_indices().skip(1)
+            .find(|&(_, c)| {
+                let closes = c == '"' && !escaped;
+                escaped = c == '\\' && !escaped;
+                closes
+            })
+            .map(|(offset, _)| offset + 1)
+            .ok_or_else(|| self.error(self.pos, format!("unterminated string {}", context)))
+    }
+
+    fn quoted_key(&mut self, open: usize) -> Result<Segment, JsonDiffError> {
+        self.pos += 1;
+        let mut key = String::new();
+        let mut chars = self.rest().char_indices();
+
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += offset + 1;
+                    return Ok(Segment::Key(key));
+                }
+                '\\' => match chars.next() {
+                    Some((_, escaped @ ('"' | '\\'))) => key.push(escaped),
+                    _ => return Err(self.error(self.pos + offset, "only '\\\"' and '\\\\' may be escaped in a quoted key")),
+                },
+                c => key.push(c),
+            }
+        }
+
+        Err(self.error(open, "unterminated quoted key"))
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.path[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn error(&self, position: usize, reason: impl Into<String>) -> JsonDiffError {
+        JsonDiffError::InvalidPath {
+            path: self.path.to_string(),
+            position,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Characters that end an unquoted key
+fn is_delimiter(c: char) -> bool {
+    matches!(c, '.' | '[' | ']' | '(')
+}
+
+/// The whole path being matched, and how filters are resolved
+struct FilterContext<'a> {
+    path: &'a str,
+    holds: &'a dyn Fn(&str, &Filter) -> bool,
+}
+
+/// Whether `rest`, the end of `context.path` without its leading `$`, is matched by `segments`
+fn matches_segments(segments: &[Segment], rest: &str, context: &FilterContext<'_>) -> bool {
+    let Some((segment, remaining)) = segments.split_first() else {
+        return rest.is_empty();
+    };
+    let matches_segments = |remaining: &[Segment], rest: &str| matches_segments(remaining, rest, context);
+
+    match segment {
+        Segment::Descendants => {
+            let mut rest = rest;
+            loop {
+                if matches_segments(remaining, rest) {
+                    return true;
+                }
+                match skip_segment(rest) {
+                    Some(next) => rest = next,
+                    None => return false,
+                }
+            }
         }
+        Segment::Key(key) => rest
+            .strip_prefix('.')
+            .and_then(|rest| rest.strip_prefix(key.as_str()))
+            .is_some_and(|rest| at_boundary(rest) && matches_segments(remaining, rest)),
+        Segment::KeyPattern(pattern) => rest.strip_prefix('.').is_some_and(|rest| {
+            let len = rest.find(is_delimiter).unwrap_or(rest.len());
+            len > 0 && glob_matches(pattern, &rest[..len]) && matches_segments(remaining, &rest[len..])
+        }),
+        Segment::Index(index) => rest
+            .strip_prefix(&format!("[{}]", index))
+            .is_some_and(|rest| matches_segments(remaining, rest)),
+        Segment::AnyIndex => element_segment(rest).is_some_and(|(_, rest)| matches_segments(remaining, rest)),
+        Segment::Filter(filter) => element_segment(rest).is_some_and(|(element, after)| {
+            let holds = match element {
+                Segment::Filter(named) => named.key == filter.key && same_value(&named.value, &filter.value),
+                _ => (context.holds)(&context.path[..context.path.len() - after.len()], filter),
+            };
+            holds && matches_segments(remaining, after)
+        }),
+        Segment::Embedded => rest
+            .strip_prefix(EMBEDDED)
+            .is_some_and(|rest| matches_segments(remaining, rest)),
+    }
+}
+
+/// Whether a key ended at the start of `rest`
+fn at_boundary(rest: &str) -> bool {
+    rest.is_empty() || rest.starts_with(['.', '[', '('])
+}
+
+/// `rest` without its first segment, if it has one
+fn skip_segment(rest: &str) -> Option<&str> {
+    if let Some(after) = rest.strip_prefix(EMBEDDED) {
+        return Some(after);
+    }
+    let mut chars = rest.char_indices();
+    match chars.next()? {
+        (_, '.') => Some(&rest[1 + rest[1..].find(['.', '[', '(']).unwrap_or(rest.len() - 1)..]),
+        (_, '[') => element_segment(rest).map(|(_, after)| after).or_else(|| rest.find(']').map(|end| &rest[end + 1..])),
+        _ => None,
+    }
+}
+
+/// The element segment at the start of `rest` in a reported path, an index such as
+/// `[3]` or a key such as `[id="P1"]`, and what follows it
+fn element_segment(rest: &str) -> Option<(Segment, &str)> {
+    if !rest.starts_with('[') || rest[1..].starts_with(['"', '*', '?']) {
+        return None;
+    }
+    let mut parser = Parser { path: rest, pos: 0 };
+    let segment = parser.bracket().ok()?;
+    Some((segment, &rest[parser.pos..]))
+}
+
+/// Match `text` against a pattern where `*` stands for any characters
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if text.len() < first.len() + last.len() || !text.starts_with(first) || !text.ends_with(last) {
+        return false;
+    }
+
+    let mut middle = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match middle.find(part) {
+            Some(found) => middle = &middle[found + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.matcher {
+            Matcher::Regex { regex, .. } => write!(f, "{}", regex.as_str()),
+            Matcher::Segments(_) => write!(f, "{}", self.path),
+        }
+    }
+}
+
+/// How a path is serialized: structured paths as their text, regex paths as a table
+/// with the pattern as compiled, which deserializes without anchoring it again
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PathRepr {
+    Path(String),
+    Regex {
+        path: String,
+        regex: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        case_insensitive: bool,
+    },
+}
+
+impl Serialize for JsonPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match &self.matcher {
+            Matcher::Segments(_) => PathRepr::Path(self.path.clone()),
+            Matcher::Regex { regex, case_insensitive } => PathRepr::Regex {
+                path: self.path.clone(),
+                regex: regex.as_str().to_string(),
+                case_insensitive: *case_insensitive,
+            },
+        };
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonPath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let path = match PathRepr::deserialize(deserializer)? {
+            PathRepr::Path(path) => JsonPath::new(&path),
+            PathRepr::Regex { path, regex, case_insensitive } => {
+                JsonPath::with_regex_opts(&path, &regex, RegexOpts { anchored: false, case_insensitive })
+            }
+        };
+        path.map_err(serde::de::Error::custom)
     }
 }
 
@@ -44,7 +847,7 @@ mod tests {
     #[test]
     fn test_exact_path_match() {
         let path = JsonPath::new("$.user.name").unwrap();
-        
+
         assert!(path.matches("$.user.name"));
         assert!(!path.matches("$.user.age"));
         assert!(!path.matches("$.user"));
@@ -53,7 +856,7 @@ mod tests {
     #[test]
     fn test_path_with_regex() {
         let path = JsonPath::with_regex("$", "^\\$\\.user\\.[a-z]+$").unwrap();
-        
+
         assert!(path.matches("$.user.name"));
         assert!(path.matches("$.user.age"));
         assert!(!path.matches("$.user"));
@@ -63,18 +866,307 @@ mod tests {
     #[test]
     fn test_array_index_path() {
         let path = JsonPath::new("$.users[0].name").unwrap();
-        
+
         assert!(path.matches("$.users[0].name"));
         assert!(!path.matches("$.users[1].name"));
     }
 
+    #[test]
+    fn test_value_paths_and_concrete() {
+        let doc = serde_json::json!({"a.b": true, "items": [{"id": 1}]});
+        assert_eq!(value_paths(&doc), vec!["$", "$[\"a.b\"]", "$.items", "$.items[0]", "$.items[0].id"]);
+
+        assert!(JsonPath::new("$.items[0].id").unwrap().is_concrete());
+        assert!(!JsonPath::new("$.items[*].id").unwrap().is_concrete());
+        assert!(!JsonPath::new("$..id").unwrap().is_concrete());
+        assert!(!JsonPath::with_regex("id", "id").unwrap().is_concrete());
+    }
+
+    #[test]
+    fn test_select_and_rebase() {
+        let doc = serde_json::json!({"data": {"items": [{"a.b": 1}]}});
+        let (path, value) = JsonPath::new("$.data.items[0][\"a.b\"]").unwrap().select(&doc).unwrap();
+        assert_eq!((path.as_str(), value), ("$.data.items[0][\"a.b\"]", &serde_json::json!(1)));
+        assert!(JsonPath::new("$.data.missing").unwrap().select(&doc).is_none());
+        assert!(JsonPath::new("$.data.items[*]").unwrap().select(&doc).is_none());
+
+        assert_eq!(rebase_path("$.data.items[0]", "$.data").as_deref(), Some("$.items[0]"));
+        assert_eq!(rebase_path("$.data", "$.data").as_deref(), Some("$"));
+        assert_eq!(rebase_path("$.database", "$.data"), None);
+    }
+
+    #[test]
+    fn test_index_segments() {
+        assert_eq!(index_segments("$.items[1]"), (true, true));
+        assert_eq!(index_segments("$.items[1].value"), (true, false));
+        assert_eq!(index_segments("$.items[12][0](json)"), (true, false));
+        assert_eq!(index_segments("$.name"), (false, false));
+        assert_eq!(index_segments(r#"$["a[1]"]"#), (false, false));
+        assert_eq!(index_segments(r#"$["say \"[1]\""].x[2]"#), (true, true));
+        assert_eq!(index_segments("$.items[id=P1]"), (true, true));
+        assert_eq!(index_segments(r#"$.items[id="a.b"].x"#), (true, false));
+    }
+
+    #[test]
+    fn test_parent_path() {
+        assert_eq!(parent_path("$.a.b"), "$.a");
+        assert_eq!(parent_path("$.items[3]"), "$.items");
+        assert_eq!(parent_path("$.body(json)"), "$.body");
+        assert_eq!(parent_path(r#"$.a["x.y[1]"]"#), "$.a");
+        assert_eq!(parent_path(r#"$.a[id="x.y"]"#), "$.a");
+        assert_eq!(parent_path("$.a[id=P1].b"), "$.a[id=P1]");
+        assert_eq!(parent_path("$.a"), "$");
+        assert_eq!(parent_path("$"), "");
+    }
+
+    #[test]
+    fn test_ancestor_at_depth() {
+        assert_eq!(ancestor_at_depth("$.items[3].price", 1), "$.items");
+        assert_eq!(ancestor_at_depth("$.items[3].price", 2), "$.items[3]");
+        assert_eq!(ancestor_at_depth(r#"$.a["x.y"].b"#, 2), r#"$.a["x.y"]"#);
+        assert_eq!(ancestor_at_depth("$.a", 3), "$.a");
+        assert_eq!(ancestor_at_depth("$.a.b", 0), "$");
+    }
+
     #[test]
     fn test_wildcard_path() {
         let path = JsonPath::with_regex("$", "^\\$\\.users\\[\\d+\\]\\.name$").unwrap();
-        
+
         assert!(path.matches("$.users[0].name"));
         assert!(path.matches("$.users[1].name"));
         assert!(path.matches("$.users[42].name"));
         assert!(!path.matches("$.users.name"));
     }
+
+    #[test]
+    fn test_regex_anchoring() {
+        let loose = JsonPath::with_regex("$", r"\$\.id").unwrap();
+        assert!(loose.matches("$.id"));
+        assert!(loose.matches("$.id.history"));
+
+        let anchored = JsonPath::with_regex_opts("$", r"\$\.id", RegexOpts::default()).unwrap();
+        assert!(anchored.matches("$.id"));
+        assert!(!anchored.matches("$.id.history"));
+        assert!(!anchored.matches("$.order.$.id"));
+
+        // Alternatives are anchored as a whole
+        let either = JsonPath::with_regex_opts("$", r"\$\.a|\$\.b", RegexOpts::default()).unwrap();
+        assert!(either.matches("$.b"));
+        assert!(!either.matches("$.b.c"));
+
+        let relaxed = RegexOpts { anchored: false, ..RegexOpts::default() };
+        assert!(JsonPath::with_regex_opts("$", r"\.id", relaxed).unwrap().matches("$.order.id.history"));
+    }
+
+    #[test]
+    fn test_regex_case_insensitive() {
+        let opts = RegexOpts { case_insensitive: true, ..RegexOpts::default() };
+        let path = JsonPath::with_regex_opts("$", r"\$\.user\.email", opts).unwrap();
+        assert!(path.matches("$.User.EMAIL"));
+        assert!(path.matches("$.user.email"));
+        assert!(!path.matches("$.user.emails"));
+
+        let exact_case = JsonPath::with_regex_opts("$", r"\$\.user\.email", RegexOpts::default()).unwrap();
+        assert!(!exact_case.matches("$.User.EMAIL"));
+    }
+
+    #[test]
+    fn test_wildcards_in_plain_path() {
+        let path = JsonPath::new("$.orders[*].status").unwrap();
+        assert!(path.matches("$.orders[3].status"));
+        assert!(!path.matches("$.orders.status"));
+        assert!(!path.matches("$.orders[3].status.code"));
+
+        let path = JsonPath::new("$.jobs.*.state").unwrap();
+        assert!(path.matches("$.jobs.build.state"));
+        assert!(!path.matches("$.jobs[0].state"));
+        assert!(!path.matches("$.jobs.a.b.state"));
+    }
+
+    #[test]
+    fn test_matching_is_by_segment() {
+        let path = JsonPath::new("$.user").unwrap();
+        assert!(path.matches("$.user"));
+        assert!(!path.matches("$.username"));
+        assert!(!path.matches("$.user.name"));
+
+        let path = JsonPath::new("$.audit.*_time").unwrap();
+        assert!(path.matches("$.audit.start_time"));
+        assert!(path.matches("$.audit._time"));
+        assert!(!path.matches("$.audit.start_date"));
+
+        let path = JsonPath::new("$.payload(json).id").unwrap();
+        assert!(path.matches("$.payload(json).id"));
+        assert!(!path.matches("$.payload.id"));
+
+        let root = JsonPath::new("$").unwrap();
+        assert!(root.matches("$"));
+        assert!(!root.matches("$.a"));
+    }
+
+    #[test]
+    fn test_quoted_and_unicode_keys() {
+        let path = JsonPath::new(r#"$["first name"]["a.b"]"#).unwrap();
+        assert!(path.matches("$.first name.a.b"));
+        assert!(!path.matches("$.first name.a"));
+
+        let path = JsonPath::new(r#"$["say \"hi\""]["back\\slash"]"#).unwrap();
+        assert!(path.matches(r#"$.say "hi".back\slash"#));
+
+        let path = JsonPath::new("$.名前.ñame[2]").unwrap();
+        assert!(path.matches("$.名前.ñame[2]"));
+        assert!(JsonPath::new(r#"$["*"]"#).unwrap().matches("$.*"));
+        assert!(!JsonPath::new(r#"$["*"]"#).unwrap().matches("$.a"));
+    }
+
+    #[test]
+    fn test_descendants() {
+        let path = JsonPath::new("$..id").unwrap();
+        assert!(path.matches("$.id"));
+        assert!(path.matches("$.users[3].id"));
+        assert!(path.matches("$.a.b(json).id"));
+        assert!(!path.matches("$.users[3].id.value"));
+        assert!(!path.matches("$.uuid"));
+
+        let path = JsonPath::new("$.config.**").unwrap();
+        assert!(path.matches("$.config"));
+        assert!(path.matches("$.config.a[1].b"));
+        assert!(!path.matches("$.other.a"));
+
+        let path = JsonPath::new("$.**.tags[*]").unwrap();
+        assert!(path.matches("$.tags[0]"));
+        assert!(path.matches("$.products[1].tags[0]"));
+
+        assert!(JsonPath::new("$..[0]").unwrap().matches("$.a.b[0]"));
+    }
+
+    #[test]
+    fn test_push_key_round_trips() {
+        for key in ["plain", "with.dot", "a[0]", "star*", "quote\"d", "back\\slash", ""] {
+            let mut path = String::from("$");
+            push_key(&mut path, key);
+            assert!(JsonPath::new(&path).unwrap().matches(&format!("$.{}", key)), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_filter_segments() {
+        let doc = serde_json::json!({"users": [{"id": "u-7"}, {"id": "u-42", "last": 1}, {"id": 3, "tag": "a]\"b"}]});
+        let path = JsonPath::new(r#"$.users[?id="u-42"].last"#).unwrap();
+        assert!(!path.is_concrete());
+        assert_eq!(path.to_string(), r#"$.users[?id="u-42"].last"#);
+
+        // Not resolvable from the path alone
+        assert!(!path.matches("$.users[1].last"));
+        assert!(path.matches_in("$.users[1].last", &doc));
+        assert!(!path.matches_in("$.users[0].last", &doc));
+        assert!(!path.matches_in("$.users[1]", &doc));
+
+        // Numbers equal by value; quoted strings may hold brackets and escaped quotes
+        assert!(JsonPath::new("$.users[?id=3.0]").unwrap().matches_in("$.users[2]", &doc));
+        assert!(JsonPath::new(r#"$.users[?tag="a]\"b"]"#).unwrap().matches_in("$.users[2]", &doc));
+        assert!(!JsonPath::new(r#"$.users[?id="3"]"#).unwrap().matches_in("$.users[2]", &doc));
+        assert!(JsonPath::new(r#"$..[?id="u-7"]"#).unwrap().matches_in("$.users[0]", &doc));
+    }
+
+    #[test]
+    fn test_keyed_segments() {
+        let doc = serde_json::json!({"products": [{"id": "P2"}, {"id": "P1", "price": 3}, {"id": 7}]});
+        let path = JsonPath::new("$.products[id=P1].price").unwrap();
+        assert!(!path.is_concrete());
+
+        // Matches the element named by key in a path, or at its index in the document
+        assert!(path.matches("$.products[id=P1].price"));
+        assert!(!path.matches("$.products[id=P2].price"));
+        assert!(!path.matches("$.products[1].price"));
+        assert!(path.matches_in("$.products[1].price", &doc));
+        assert!(!path.matches_in("$.products[0].price", &doc));
+
+        // The filter syntax and [*] match keyed elements too; unquoted numbers are numbers
+        assert!(JsonPath::new(r#"$.products[?id="P1"].price"#).unwrap().matches("$.products[id=P1].price"));
+        assert!(JsonPath::new("$.products[*].price").unwrap().matches("$.products[id=P1].price"));
+        assert!(JsonPath::new("$..price").unwrap().matches("$.products[id=P1].price"));
+        assert!(JsonPath::new("$.products[id=7.0]").unwrap().matches("$.products[id=7]"));
+        assert!(JsonPath::new("$.products[id=7]").unwrap().matches_in("$.products[2]", &doc));
+        assert!(!JsonPath::new(r#"$.products[id="7"]"#).unwrap().matches("$.products[id=7]"));
+    }
+
+    #[test]
+    fn test_keyed_segment_round_trips() {
+        let values = [
+            serde_json::json!("P1"),
+            serde_json::json!("v1.2-rc:3"),
+            serde_json::json!("a b"),
+            serde_json::json!("x]y[0]"),
+            serde_json::json!("quote\"d \\ and\nnewline"),
+            serde_json::json!("3"),
+            serde_json::json!("1e5"),
+            serde_json::json!(""),
+            serde_json::json!(42),
+            serde_json::json!(-1.5),
+        ];
+        for value in values {
+            let segment = keyed_segment("id", &value).unwrap();
+            let path = format!("$.items{}.name", segment);
+            let doc = serde_json::json!({"items": [{"id": value.clone()}]});
+            assert!(JsonPath::new(&path).unwrap().matches(&path), "{}", path);
+            assert!(JsonPath::new(&path).unwrap().matches_in("$.items[0].name", &doc), "{}", path);
+            assert_eq!(parent_path(&path), format!("$.items{}", segment));
+        }
+        assert_eq!(keyed_segment("id", &serde_json::json!("P1")).unwrap(), "[id=P1]");
+        assert_eq!(keyed_segment("id", &serde_json::json!("3")).unwrap(), r#"[id="3"]"#);
+        assert_eq!(keyed_segment("id", &serde_json::json!("a b")).unwrap(), r#"[id="a b"]"#);
+
+        // Keys that need quotes and values other than strings and numbers keep their index
+        assert_eq!(keyed_segment("user.id", &serde_json::json!(1)), None);
+        assert_eq!(keyed_segment("2nd", &serde_json::json!(1)), None);
+        assert_eq!(keyed_segment("id", &serde_json::json!(true)), None);
+        assert_eq!(keyed_segment("id", &serde_json::json!({"a": 1})), None);
+    }
+
+    #[test]
+    fn test_invalid_paths() {
+        let cases = [
+            ("users.name", 0, "must start with '$'"),
+            ("", 0, "must start with '$'"),
+            ("$users.name", 1, "expected '.' or '[' after '$'"),
+            ("$.a.", 4, "empty key"),
+            ("$.a..", 5, "after '..'"),
+            ("$..", 3, "after '..'"),
+            ("$.items[abc]", 8, "expected an index"),
+            ("$.items[", 7, "unterminated '['"),
+            ("$.items[0", 7, "unterminated '['"),
+            ("$.items[0x]", 9, "expected ']'"),
+            ("$.items[]", 8, "empty brackets"),
+            ("$.a]", 3, "found ']'"),
+            (r#"$["open"#, 1, "unterminated quoted key"),
+            (r#"$["a\n"]"#, 4, "may be escaped"),
+            ("$.a(xml)", 3, "(json)"),
+            ("$.a\"b", 3, "quote keys"),
+            ("$.items[99999999999999999999999]", 8, "too large"),
+            ("$.items[?id]", 9, "expected '='"),
+            ("$.items[?=1]", 9, "plain key"),
+            ("$.items[?id=abc]", 12, "string in quotes or a number"),
+            ("$.items[?id=true]", 12, "string in quotes or a number"),
+            (r#"$.items[?id="u-42]"#, 12, "unterminated string"),
+            (r#"$.items[?id="a"x]"#, 15, "expected ']'"),
+            ("$.items[id=]", 11, "quote a key value"),
+            (r#"$.items[id=a"b]"#, 11, "quote a key value"),
+            (r#"$.items[id="P1]"#, 11, "unterminated string"),
+        ];
+
+        for (path, position, reason) in cases {
+            match JsonPath::new(path) {
+                Err(JsonDiffError::InvalidPath { path: p, position: pos, reason: r }) => {
+                    assert_eq!(p, path);
+                    assert_eq!(pos, position, "{}: {}", path, r);
+                    assert!(r.contains(reason), "{}: {}", path, r);
+                }
+                other => panic!("{} parsed as {:?}", path, other.map(|p| p.to_string())),
+            }
+        }
+
+        let message = JsonPath::new("$users").unwrap_err().to_string();
+        assert_eq!(message, "Invalid JSON path \"$users\" at byte 1: expected '.' or '[' after '$', found 'u'");
+    }
 }
@@ -0,0 +1,128 @@
+//! Named comparison options for common use cases
+//!
+//! A preset is a starting point: a profile and command line flags given with it
+//! override its settings, and their rules are added to its rules.
+
+use crate::compare::CompareOptions;
+use crate::path::{push_key, JsonPath};
+
+/// A named set of comparison options
+#[derive(Debug, Clone, Copy)]
+pub struct Preset {
+    pub name: &'static str,
+    /// What the preset is for, in one line
+    pub description: &'static str,
+    /// Build the preset's options
+    pub options: fn() -> CompareOptions,
+}
+
+/// Every preset, by name
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "api-response",
+        description: "API responses: volatile fields and headers ignored, numbers compared by value",
+        options: api_response,
+    },
+    Preset {
+        name: "config-drift",
+        description: "Configuration files: the structure that changed, large arrays collapsed to their length",
+        options: config_drift,
+    },
+    Preset {
+        name: "strict",
+        description: "Everything exact: tolerated values reported, array changes tagged as such",
+        options: strict,
+    },
+];
+
+/// The preset called `name`
+pub fn preset(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|preset| preset.name == name)
+}
+
+/// Fields that change on every response, wherever they are
+const VOLATILE_FIELDS: &[&str] = &["timestamp", "requestId", "request_id", "traceId", "trace_id"];
+
+/// Headers that change on every response, as `http_capture_rules` ignores them
+const VOLATILE_HEADERS: &[&str] = &["date", "request-id", "x-request-id"];
+
+/// Responses of the same API call: timestamps, request and trace ids anywhere and the
+/// volatile headers of a capture are ignored, and numbers are compared by value
+pub fn api_response() -> CompareOptions {
+    let mut ignore: Vec<String> = VOLATILE_FIELDS.iter().map(|field| format!("$..{}", field)).collect();
+    for header in VOLATILE_HEADERS {
+        let mut path = "$.headers".to_string();
+        push_key(&mut path, header);
+        ignore.push(path);
+    }
+
+    CompareOptions {
+        // The paths above are well-formed
+        ignore_paths: ignore.iter().filter_map(|path| JsonPath::new(path).ok()).collect(),
+        numeric_equivalence: true,
+        ..CompareOptions::default()
+    }
+}
+
+/// Two versions of a configuration: arrays are compared as a whole, and those whose
+/// JSON text exceeds 1 KiB are described by their length instead of shown
+pub fn config_drift() -> CompareOptions {
+    CompareOptions {
+        identify_array_item_changes: false,
+        max_value_size: Some(1024),
+        ..CompareOptions::default()
+    }
+}
+
+/// Nothing is accepted silently: values a tolerance or normalization rule accepts are
+/// reported as tolerated, and changed array elements as array item changes
+pub fn strict() -> CompareOptions {
+    CompareOptions {
+        report_tolerated: true,
+        tag_array_item_changes: true,
+        tag_nested_array_item_changes: true,
+        ..CompareOptions::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::compare::compare_json;
+    use crate::diff::DiffType;
+
+    #[test]
+    fn test_presets_by_name() {
+        let names: Vec<&str> = PRESETS.iter().map(|preset| preset.name).collect();
+        assert_eq!(names, ["api-response", "config-drift", "strict"]);
+        assert!(preset("config-drift").is_some());
+        assert!(preset("lax").is_none());
+    }
+
+    #[test]
+    fn test_api_response() {
+        let options = api_response();
+        assert!(options.numeric_equivalence);
+        let ignored: Vec<String> = options.ignore_paths.iter().map(|path| path.to_string()).collect();
+        assert!(ignored.contains(&"$..request_id".to_string()), "{:?}", ignored);
+        assert!(ignored.contains(&"$.headers.x-request-id".to_string()), "{:?}", ignored);
+
+        let left = json!({"data": {"total": 1, "requestId": "a"}, "headers": {"date": "Mon"}});
+        let right = json!({"data": {"total": 1.0, "requestId": "b"}, "headers": {"date": "Tue"}});
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert!(result.iter().all(|entry| entry.diff_type == DiffType::Ignored), "{:?}", result.entries());
+    }
+
+    #[test]
+    fn test_config_drift_and_strict() {
+        let options = config_drift();
+        assert!(!options.identify_array_item_changes);
+        assert_eq!(options.max_value_size, Some(1024));
+
+        let options = strict();
+        assert!(options.report_tolerated && options.tag_array_item_changes);
+        assert!(!options.numeric_equivalence);
+        assert!(options.ignore_paths.is_empty());
+    }
+}
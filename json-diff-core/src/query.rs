@@ -0,0 +1,372 @@
+//! Queries selecting diff entries, e.g. `type:removed AND path:$.config.* AND value~"secret"`
+//!
+//! A query combines predicates on an entry's fields:
+//!
+//! - `type:removed` - the entry's type, by its readable name in any case
+//! - `path:$.config.*` - a path pattern, written as ignore rules are, matching the
+//!   entry's path or one of its ancestors, so `path:$.config` selects the whole subtree
+//! - `value~"secret"` - a regex found in the JSON text of the old or the new value
+//! - `line>10`, `line<10` - the entry's line: the left one, else the right one
+//! - `delta>100`, `delta<0.5` - how much a number changed, either way; only entries
+//!   compared with [`CompareOptions::numeric_deltas`](crate::CompareOptions::numeric_deltas)
+//!   have a delta
+//! - `near:true`, `near:false` - whether the entry is a near match between two alike
+//!   strings, see [`CompareOptions::string_similarity`](crate::CompareOptions::string_similarity)
+//!
+//! with `NOT`, `AND` and `OR`, binding in that order, and parentheses. Operators are
+//! matched in any case. An operand holding spaces or parentheses is written in double
+//! quotes, with `\"` and `\\` escapes.
+
+use regex::Regex;
+
+use crate::diff::{DiffEntry, DiffType};
+use crate::error::JsonDiffError;
+use crate::lenient::format_value;
+use crate::path::{parent_path, JsonPath};
+
+/// A parsed query, see the [module documentation](self)
+#[derive(Debug, Clone)]
+pub struct Query {
+    expr: Expr,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Type(DiffType),
+    Path(JsonPath),
+    Value(Regex),
+    LineAbove(usize),
+    LineBelow(usize),
+    DeltaAbove(f64),
+    DeltaBelow(f64),
+    Near(bool),
+}
+
+impl Query {
+    /// Parse a query, failing with the position of the problem if it is malformed
+    pub fn parse(query: &str) -> Result<Self, JsonDiffError> {
+        let mut parser = Parser { query, pos: 0 };
+        let expr = parser.or()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(Self { expr }),
+            Some(')') => Err(parser.error("unexpected `)` without a matching `(`")),
+            Some(_) => Err(parser.error("expected AND or OR between predicates")),
+        }
+    }
+
+    /// Whether `entry` is selected by the query
+    pub fn matches(&self, entry: &DiffEntry) -> bool {
+        self.expr.matches(entry)
+    }
+}
+
+impl Expr {
+    fn matches(&self, entry: &DiffEntry) -> bool {
+        let line = entry.left_line.or(entry.right_line);
+        match self {
+            Expr::Not(expr) => !expr.matches(entry),
+            Expr::And(left, right) => left.matches(entry) && right.matches(entry),
+            Expr::Or(left, right) => left.matches(entry) || right.matches(entry),
+            Expr::Type(diff_type) => entry.diff_type == *diff_type,
+            Expr::Path(pattern) => {
+                let mut path = entry.path.as_str();
+                while !path.is_empty() {
+                    if pattern.matches(path) {
+                        return true;
+                    }
+                    path = parent_path(path);
+                }
+                false
+            }
+            Expr::Value(regex) => [&entry.old_value, &entry.new_value]
+                .into_iter()
+                .flatten()
+                .any(|value| regex.is_match(&format_value(value, false))),
+            Expr::LineAbove(limit) => line.is_some_and(|line| line > *limit),
+            Expr::LineBelow(limit) => line.is_some_and(|line| line < *limit),
+            Expr::DeltaAbove(limit) => entry.delta.is_some_and(|delta| delta.abs() > *limit),
+            Expr::DeltaBelow(limit) => entry.delta.is_some_and(|delta| delta.abs() < *limit),
+            Expr::Near(near) => entry.near_match == *near,
+        }
+    }
+}
+
+struct Parser<'a> {
+    query: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn or(&mut self) -> Result<Expr, JsonDiffError> {
+        let mut expr = self.and()?;
+        while self.keyword("OR") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, JsonDiffError> {
+        let mut expr = self.not()?;
+        while self.keyword("AND") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, JsonDiffError> {
+        if self.keyword("NOT") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, JsonDiffError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('(') => {
+                let open = self.pos;
+                self.pos += 1;
+                let expr = self.or()?;
+                self.skip_whitespace();
+                if self.peek() != Some(')') {
+                    return Err(self.error(&format!("expected `)` to close the `(` at byte {}", open)));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(')') | None => Err(self.error("expected a predicate such as `type:added`")),
+            Some(_) => self.predicate(),
+        }
+    }
+
+    fn predicate(&mut self) -> Result<Expr, JsonDiffError> {
+        let start = self.pos;
+        let field_len = self.rest().find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(self.rest().len());
+        let field = self.query[start..start + field_len].to_ascii_lowercase();
+        self.pos += field_len;
+        let op = self.peek();
+        let expected_op = match field.as_str() {
+            "type" | "path" | "near" => ":",
+            "value" => "~",
+            "line" | "delta" => "> or <",
+            _ => {
+                self.pos = start;
+                return Err(self.error("expected a predicate on type, path, value, line, delta or near"));
+            }
+        };
+        if !op.is_some_and(|op| expected_op.contains(op)) {
+            return Err(self.error(&format!("`{}` is followed by {}", field, expected_op)));
+        }
+        self.pos += 1;
+
+        let operand_start = self.pos;
+        let operand = self.operand()?;
+        if operand.is_empty() {
+            self.pos = operand_start;
+            return Err(self.error(&format!("`{}{}` needs a value", field, op.unwrap_or_default())));
+        }
+        let invalid = |reason: String| JsonDiffError::InvalidQuery { query: self.query.to_string(), position: operand_start, reason };
+
+        match (field.as_str(), op) {
+            ("type", _) => DiffType::ALL
+                .into_iter()
+                .find(|diff_type| diff_type.readable_text().eq_ignore_ascii_case(&operand))
+                .map(Expr::Type)
+                .ok_or_else(|| {
+                    let names: Vec<String> = DiffType::ALL.iter().map(|t| t.readable_text().to_ascii_lowercase()).collect();
+                    invalid(format!("unknown type `{}`; expected one of {}", operand, names.join(", ")))
+                }),
+            ("path", _) => JsonPath::new(&operand).map(Expr::Path).map_err(|e| match e {
+                JsonDiffError::InvalidPath { position, reason, .. } => JsonDiffError::InvalidQuery {
+                    query: self.query.to_string(),
+                    // Within a quoted operand, after the opening quote
+                    position: operand_start + usize::from(self.query[operand_start..].starts_with('"')) + position,
+                    reason: format!("invalid path: {}", reason),
+                },
+                e => invalid(e.to_string()),
+            }),
+            ("near", _) => match operand.to_ascii_lowercase().as_str() {
+                "true" => Ok(Expr::Near(true)),
+                "false" => Ok(Expr::Near(false)),
+                _ => Err(invalid(format!("`{}` is not true or false", operand))),
+            },
+            ("value", _) => Regex::new(&operand).map(Expr::Value).map_err(|e| invalid(format!("invalid regex: {}", e))),
+            ("delta", op) => {
+                let limit = operand.parse::<f64>().ok().filter(|limit| limit.is_finite())
+                    .ok_or_else(|| invalid(format!("`{}` is not a number", operand)))?;
+                Ok(if op == Some('>') { Expr::DeltaAbove(limit) } else { Expr::DeltaBelow(limit) })
+            }
+            (_, op) => {
+                let limit = operand.parse().map_err(|_| invalid(format!("`{}` is not a line number", operand)))?;
+                Ok(if op == Some('>') { Expr::LineAbove(limit) } else { Expr::LineBelow(limit) })
+            }
+        }
+    }
+
+    /// A quoted string or the text up to the next space or `)`
+    fn operand(&mut self) -> Result<String, JsonDiffError> {
+        if self.peek() != Some('"') {
+            let len = self.rest().find(|c: char| c.is_whitespace() || c == ')').unwrap_or(self.rest().len());
+            let operand = self.rest()[..len].to_string();
+            self.pos += len;
+            return Ok(operand);
+        }
+
+        let open = self.pos;
+        self.pos += 1;
+        let mut operand = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += offset + 1;
+                    return Ok(operand);
+                }
+                '\\' => match chars.next() {
+                    Some((_, escaped @ ('"' | '\\'))) => operand.push(escaped),
+                    // Other escapes, such as regex ones, are kept as written
+                    Some((_, other)) => {
+                        operand.push('\\');
+                        operand.push(other);
+                    }
+                    None => break,
+                },
+                c => operand.push(c),
+            }
+        }
+        self.pos = open;
+        Err(self.error("unterminated string"))
+    }
+
+    /// Consume `keyword`, in any case, if it comes next as a whole word
+    fn keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let matches = rest.get(..keyword.len()).is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+            && rest[keyword.len()..].chars().next().is_none_or(|c| c.is_whitespace() || c == '(');
+        if matches {
+            self.pos += keyword.len();
+        }
+        matches
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.pos += self.rest().len() - self.rest().trim_start().len();
+    }
+
+    fn rest(&self) -> &str {
+        &self.query[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn error(&self, reason: &str) -> JsonDiffError {
+        JsonDiffError::InvalidQuery { query: self.query.to_string(), position: self.pos, reason: reason.to_string() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entries() -> Vec<DiffEntry> {
+        vec![
+            DiffEntry::removed("$.config.password", json!("secret-1")).with_lines(Some(3), None),
+            DiffEntry::added("$.config.db.host", json!("db.internal")).with_lines(Some(5), Some(6)),
+            DiffEntry::modified("$.name", json!("a"), json!("my secret")).with_lines(Some(12), Some(14)),
+            DiffEntry::ignored("$.id").with_lines(Some(2), Some(2)),
+        ]
+    }
+
+    fn selected(query: &str) -> Vec<String> {
+        let query = Query::parse(query).unwrap_or_else(|e| panic!("{}", e));
+        entries().into_iter().filter(|entry| query.matches(entry)).map(|entry| entry.path).collect()
+    }
+
+    fn error(query: &str) -> String {
+        Query::parse(query).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_predicates() {
+        assert_eq!(selected("type:removed"), ["$.config.password"]);
+        assert_eq!(selected("TYPE:Ignored"), ["$.id"]);
+        assert_eq!(selected("path:$.config.*"), ["$.config.password", "$.config.db.host"]);
+        assert_eq!(selected("path:$.config"), ["$.config.password", "$.config.db.host"]);
+        assert_eq!(selected("path:$..host"), ["$.config.db.host"]);
+        assert_eq!(selected(r#"value~"secret""#), ["$.config.password", "$.name"]);
+        assert_eq!(selected(r#"value~"^\"db\.""#), ["$.config.db.host"]);
+        assert_eq!(selected("line>4"), ["$.config.db.host", "$.name"]);
+        assert_eq!(selected("line<3"), ["$.id"]);
+        assert_eq!(selected(r#"type:removed AND path:$.config.* AND value~"secret""#), ["$.config.password"]);
+    }
+
+    #[test]
+    fn test_delta() {
+        let mut entries = [
+            DiffEntry::modified("$.up", json!(100), json!(250)),
+            DiffEntry::modified("$.down", json!(10.5), json!(-140)),
+            DiffEntry::modified("$.small", json!(1), json!(1.25)),
+            DiffEntry::modified("$.text", json!("a"), json!("b")),
+        ];
+        entries.iter_mut().for_each(DiffEntry::record_delta);
+        let selected = |query: &str| {
+            let query = Query::parse(query).unwrap();
+            entries.iter().filter(|entry| query.matches(entry)).map(|entry| entry.path.as_str()).collect::<Vec<_>>()
+        };
+        assert_eq!(selected("delta>100"), ["$.up", "$.down"]);
+        assert_eq!(selected("delta<0.5"), ["$.small"]);
+        assert_eq!(selected("NOT delta>0"), ["$.text"]);
+    }
+
+    #[test]
+    fn test_near() {
+        let mut entries = [
+            DiffEntry::modified("$.typo", json!("recieve"), json!("receive")),
+            DiffEntry::modified("$.rewrite", json!("yes"), json!("no")),
+        ];
+        entries.iter_mut().for_each(|entry| entry.record_near_match(0.7));
+        let selected = |query: &str| {
+            let query = Query::parse(query).unwrap();
+            entries.iter().filter(|entry| query.matches(entry)).map(|entry| entry.path.as_str()).collect::<Vec<_>>()
+        };
+        assert_eq!(selected("near:true"), ["$.typo"]);
+        assert_eq!(selected("near:FALSE"), ["$.rewrite"]);
+        assert_eq!(error("near:maybe"), "Invalid query \"near:maybe\" at byte 5: `maybe` is not true or false");
+    }
+
+    #[test]
+    fn test_precedence() {
+        // NOT binds tighter than AND, which binds tighter than OR
+        assert_eq!(selected("type:ignored OR type:added AND line>4"), ["$.config.db.host", "$.id"]);
+        assert_eq!(selected("(type:ignored OR type:added) AND line<4"), ["$.id"]);
+        assert_eq!(selected("NOT type:ignored AND NOT path:$.config"), ["$.name"]);
+        assert_eq!(selected("not (type:ignored or path:$.config)"), ["$.name"]);
+        assert_eq!(selected("NOT NOT type:ignored"), ["$.id"]);
+    }
+
+    #[test]
+    fn test_malformed_queries() {
+        assert_eq!(error("colour:red"), "Invalid query \"colour:red\" at byte 0: expected a predicate on type, path, value, line, delta or near");
+        assert_eq!(error("type=added"), "Invalid query \"type=added\" at byte 4: `type` is followed by :");
+        assert_eq!(error("line>"), "Invalid query \"line>\" at byte 5: `line>` needs a value");
+        assert_eq!(error("line>ten"), "Invalid query \"line>ten\" at byte 5: `ten` is not a line number");
+        assert_eq!(error("delta<inf"), "Invalid query \"delta<inf\" at byte 6: `inf` is not a number");
+        assert!(error("type:gone").contains("unknown type `gone`; expected one of added, removed"), "{}", error("type:gone"));
+        assert_eq!(error("path:$.a[x"), "Invalid query \"path:$.a[x\" at byte 9: invalid path: expected an index, '*', a filter, key=value or a quoted key inside '[...]'");
+        assert!(error("value~\"(\"").starts_with("Invalid query \"value~\\\"(\\\"\" at byte 6: invalid regex"));
+        assert_eq!(error(r#"value~"open"#), "Invalid query \"value~\\\"open\" at byte 6: unterminated string");
+        assert_eq!(error("type:added type:removed"), "Invalid query \"type:added type:removed\" at byte 11: expected AND or OR between predicates");
+        assert_eq!(error("(type:added"), "Invalid query \"(type:added\" at byte 11: expected `)` to close the `(` at byte 0");
+        assert_eq!(error("type:added)"), "Invalid query \"type:added)\" at byte 10: unexpected `)` without a matching `(`");
+        assert_eq!(error("type:added AND"), "Invalid query \"type:added AND\" at byte 14: expected a predicate such as `type:added`");
+        assert_eq!(error(""), "Invalid query \"\" at byte 0: expected a predicate such as `type:added`");
+    }
+}
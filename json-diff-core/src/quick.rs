@@ -0,0 +1,142 @@
+//! One call from two documents to the readable text of their differences
+//!
+//! The text is what `json-diff` prints by default, without the lines naming the run: no
+//! file names, checksums, timestamp, generator or hostname, only the format version and
+//! the entries. The same documents and options give the same text byte for byte on every
+//! run and host, so it can be compared against in tests or stored.
+//!
+//! ```
+//! use serde_json::json;
+//!
+//! let text = json_diff_core::quick::diff_to_string(&json!({"a": 1, "b": [1]}), &json!({"a": 2, "b": [1]}));
+//! assert_eq!(text, "DIFF-JSON v1\n\n[MODIFIED] $.a: 1 -> 2\n");
+//! ```
+
+use serde_json::Value;
+
+use crate::compare::{compare_json, compare_strings, CompareOptions};
+use crate::diff::{DiffClassification, DiffResult, OutputStyle};
+use crate::error::JsonDiffError;
+
+/// The differences between two values, compared with the default options
+///
+/// ```
+/// use serde_json::json;
+/// use json_diff_core::quick::diff_to_string;
+///
+/// assert_eq!(diff_to_string(&json!([1, 2]), &json!([1, 2])), "DIFF-JSON v1\n\n");
+/// ```
+pub fn diff_to_string(left: &Value, right: &Value) -> String {
+    // The default options set no limit the comparison could exceed
+    diff_to_string_with_options(left, right, &CompareOptions::default()).expect("comparing with the default options cannot fail")
+}
+
+/// The differences between two values, compared with `options`
+///
+/// Fails where [`compare_json`] fails, such as past a limit the options set.
+pub fn diff_to_string_with_options(left: &Value, right: &Value, options: &CompareOptions) -> Result<String, JsonDiffError> {
+    compare_json(left, right, options).map(format)
+}
+
+/// The differences between two JSON texts, compared with the default options; entries
+/// carry the lines of their values
+///
+/// ```
+/// use json_diff_core::quick::diff_strings_to_string;
+///
+/// let text = diff_strings_to_string("{\n  \"a\": 1\n}", "{\n  \"a\": 1,\n  \"b\": true\n}").unwrap();
+/// assert_eq!(text, "DIFF-JSON v1\n\n[ADDED] $.b (L2:L3): true\n");
+/// assert!(diff_strings_to_string("{", "{}").is_err());
+/// ```
+pub fn diff_strings_to_string(left: &str, right: &str) -> Result<String, JsonDiffError> {
+    diff_strings_to_string_with_options(left, right, &CompareOptions::default())
+}
+
+/// The differences between two JSON texts, compared with `options`
+///
+/// Fails if either text is not JSON, or where [`compare_strings`] fails otherwise.
+pub fn diff_strings_to_string_with_options(left: &str, right: &str, options: &CompareOptions) -> Result<String, JsonDiffError> {
+    compare_strings(left, right, options).map(format)
+}
+
+/// Panic with the readable differences unless `left` and `right` are equivalent under
+/// `options`, for tests: ignored and tolerated entries are allowed, any other is not
+///
+/// ```
+/// use serde_json::json;
+/// use json_diff_core::{quick::assert_equivalent, CompareOptions, JsonPath};
+///
+/// let options = CompareOptions { ignore_paths: vec![JsonPath::new("$.ts")?], ..CompareOptions::default() };
+/// assert_equivalent(&json!({"id": 1, "ts": 5}), &json!({"id": 1, "ts": 6}), &options);
+/// # Ok::<(), json_diff_core::JsonDiffError>(())
+/// ```
+///
+/// # Panics
+///
+/// If the documents differ, with the text of their differences, or if the comparison fails.
+#[track_caller]
+pub fn assert_equivalent(left: &Value, right: &Value, options: &CompareOptions) {
+    let result = compare_json(left, right, options).unwrap_or_else(|e| panic!("comparison failed: {}", e));
+    if result.classification() == DiffClassification::Different {
+        panic!("documents differ:\n{}", format(result));
+    }
+}
+
+/// The readable text of `result` without what differs between runs
+fn format(mut result: DiffResult) -> String {
+    result.strip_metadata();
+    result.format_with(OutputStyle::Readable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_output_is_stable() {
+        let left = r#"{"db": {"host": "x"}, "name": "api", "port": 8080, "tags": ["a", "b"]}"#;
+        let right = r#"{"db": {"host": "y", "pool": 4}, "name": "api", "port": 9090, "tags": ["a"]}"#;
+        let expected = [
+            "DIFF-JSON v1",
+            "",
+            "[MODIFIED] $.db.host (L1:L1): \"x\" -> \"y\"",
+            "[ADDED] $.db.pool (L1:L1): 4",
+            "[MODIFIED] $.port (L1:L1): 8080 -> 9090",
+            "[REMOVED] $.tags[1] (L1:L1): \"b\"",
+            "",
+        ]
+        .join("\n");
+        for _ in 0..3 {
+            assert_eq!(diff_strings_to_string(left, right).unwrap(), expected);
+        }
+
+        // The same entries without lines, from the parsed values
+        let (left, right): (Value, Value) = (serde_json::from_str(left).unwrap(), serde_json::from_str(right).unwrap());
+        let text = diff_to_string(&left, &right);
+        assert_eq!(text, expected.replace(" (L1:L1)", ""));
+        assert!(!text.contains("TIMESTAMP") && !text.contains("GENERATOR"));
+    }
+
+    #[test]
+    fn test_with_options() {
+        let options = CompareOptions { ignore_paths: vec![crate::JsonPath::new("$.id").unwrap()], ..CompareOptions::default() };
+        let text = diff_to_string_with_options(&json!({"id": 1, "v": true}), &json!({"id": 2, "v": false}), &options).unwrap();
+        assert_eq!(text, "DIFF-JSON v1\n\n[IGNORED] $.id: [IGNORED]\n[MODIFIED] $.v: true -> false\n");
+
+        let options = CompareOptions { max_entries: Some(0), ..CompareOptions::default() };
+        assert!(matches!(
+            diff_strings_to_string_with_options("[1]", "[2]", &options),
+            Err(JsonDiffError::LimitExceeded { .. })
+        ));
+        assert!(matches!(diff_strings_to_string("{", "[]"), Err(JsonDiffError::Unparseable { left: Some(_), right: None })));
+    }
+
+    #[test]
+    #[should_panic(expected = "documents differ:\nDIFF-JSON v1\n\n[IGNORED] $.id: [IGNORED]\n[MODIFIED] $.v: true -> false\n")]
+    fn test_assert_equivalent() {
+        let options = CompareOptions { ignore_paths: vec![crate::JsonPath::new("$.id").unwrap()], ..CompareOptions::default() };
+        assert_equivalent(&json!({"id": 1, "v": true}), &json!({"id": 2, "v": true}), &options);
+        assert_equivalent(&json!({"id": 1, "v": true}), &json!({"id": 2, "v": false}), &options);
+    }
+}
@@ -0,0 +1,203 @@
+//! Matching of paths against a list of rules, such as the ignore rules
+//!
+//! Regex rules are compiled into one `RegexSet`, so a path that matches none of them,
+//! as most do, is scanned once however many there are; only the other rules are tried
+//! one by one. A key of both objects is tested before and again while its values are
+//! compared, so the last answer is kept, unless a rule has filters, whose answer for
+//! a path depends on the elements it names. A cache of every path cost more than the
+//! matching it saved in the `ignore_rules` benchmark.
+
+use std::time::Instant;
+use regex::RegexSet;
+use serde::{Deserialize, Serialize};
+use crate::path::{Filter, JsonPath};
+
+/// How a rule fared during a comparison, collected with `CompareOptions::explain_rules`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleStats {
+    /// The rule, as it is displayed
+    pub rule: String,
+    /// Paths tested against the rule, a path tested twice in a row counting once when
+    /// no rule has filters
+    pub tested: usize,
+    /// Paths the rule matched
+    pub matched: usize,
+    /// Time spent matching, in nanoseconds
+    pub nanos: u64,
+}
+
+/// How a rule applied to two documents, see [`CompareOptions::rule_usage`](crate::CompareOptions::rule_usage)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleUsage {
+    /// The kind of rule, `ignore` or `unordered`, as profiles name it
+    pub kind: String,
+    /// The rule, as it is displayed
+    pub rule: String,
+    /// Entries of the result at or beneath a path the rule matches
+    pub entries: usize,
+    /// Paths of the two documents the rule matches, counted in each
+    pub paths: usize,
+}
+
+impl RuleUsage {
+    /// Whether the rule matches no path of either document
+    pub fn is_absent(&self) -> bool {
+        self.paths == 0
+    }
+}
+
+pub(crate) struct RuleSet {
+    rules: Vec<JsonPath>,
+    /// The regex rules in one set, with the index in `rules` of each of its patterns
+    regex_set: Option<(RegexSet, Vec<usize>)>,
+    /// Indices of the rules tried one by one
+    other_rules: Vec<usize>,
+    /// The last path tested and its first matching rule
+    last: Option<(String, Option<usize>)>,
+    /// Whether a rule has filter segments, so the last answer may not hold for the same path
+    filtered: bool,
+    /// Statistics per rule, when collected; every rule is then tried on its own
+    stats: Option<Vec<RuleStats>>,
+}
+
+impl RuleSet {
+    pub(crate) fn new(rules: &[JsonPath], collect_stats: bool) -> Self {
+        let (regex_rules, mut other_rules): (Vec<usize>, Vec<usize>) =
+            (0..rules.len()).partition(|&index| rules[index].set_pattern().is_some());
+
+        // A set too large to compile leaves its rules to be tried one by one
+        let patterns = regex_rules.iter().filter_map(|&index| rules[index].set_pattern());
+        let regex_set = if regex_rules.is_empty() {
+            None
+        } else if let Ok(set) = RegexSet::new(patterns) {
+            Some((set, regex_rules))
+        } else {
+            other_rules.extend(regex_rules);
+            other_rules.sort_unstable();
+            None
+        };
+
+        let stats = collect_stats.then(|| {
+            rules.iter().map(|rule| RuleStats { rule: rule.to_string(), tested: 0, matched: 0, nanos: 0 }).collect()
+        });
+
+        let filtered = rules.iter().any(|rule| rule.filters().next().is_some());
+        Self { rules: rules.to_vec(), regex_set, other_rules, last: None, filtered, stats }
+    }
+
+    /// Whether any rule matches `path`, with filters resolved by `holds` (see [`JsonPath::matches_with`])
+    pub(crate) fn is_match(&mut self, path: &str, holds: &dyn Fn(&str, &Filter) -> bool) -> bool {
+        self.first_match(path, holds).is_some()
+    }
+
+    /// Index of the first rule matching `path`
+    pub(crate) fn first_match(&mut self, path: &str, holds: &dyn Fn(&str, &Filter) -> bool) -> Option<usize> {
+        if self.rules.is_empty() {
+            return None;
+        }
+        if let Some((last, found)) = self.last.as_ref().filter(|_| !self.filtered) {
+            if last == path {
+                return *found;
+            }
+        }
+
+        let found = match &mut self.stats {
+            Some(stats) => {
+                let mut found = None;
+                for (index, (rule, stats)) in self.rules.iter().zip(stats.iter_mut()).enumerate() {
+                    let start = Instant::now();
+                    let matched = rule.matches_with(path, holds);
+                    stats.nanos += u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+                    stats.tested += 1;
+                    if matched {
+                        stats.matched += 1;
+                        found = found.or(Some(index));
+                    }
+                }
+                found
+            }
+            None => {
+                // Most paths match no rule, which one scan of the set tells
+                let in_set = self.regex_set.as_ref()
+                    .filter(|(set, _)| set.is_match(path))
+                    .and_then(|(set, indices)| set.matches(path).iter().next().map(|matched| indices[matched]));
+                self.other_rules.iter()
+                    .copied()
+                    .take_while(|&index| in_set.is_none_or(|in_set| index < in_set))
+                    .find(|&index| self.rules[index].matches_with(path, holds))
+                    .or(in_set)
+            }
+        };
+
+        match &mut self.last {
+            Some((last, last_found)) => {
+                last.clear();
+                last.push_str(path);
+                *last_found = found;
+            }
+            None => self.last = Some((path.to_string(), found)),
+        }
+        found
+    }
+
+    /// The collected statistics, in rule order
+    pub(crate) fn into_stats(self) -> Vec<RuleStats> {
+        self.stats.unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::RegexOpts;
+
+    fn rules() -> Vec<JsonPath> {
+        vec![
+            JsonPath::new("$.a.*").unwrap(),
+            JsonPath::with_regex_opts("$", r"\$\.b\[\d+\]", RegexOpts::default()).unwrap(),
+            JsonPath::new("$..id").unwrap(),
+            JsonPath::with_regex_opts("$", r"\$\.A\..*", RegexOpts { case_insensitive: true, ..RegexOpts::default() }).unwrap(),
+            JsonPath::with_regex("$", r"name").unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_first_match_attributes_like_trying_each_rule() {
+        let rules = rules();
+        let paths = ["$", "$.a.x", "$.A.x", "$.b[3]", "$.b[3].id", "$.c.id", "$.a.name", "$.c.name", "$.c"];
+
+        for collect_stats in [false, true] {
+            let mut set = RuleSet::new(&rules, collect_stats);
+            for path in paths {
+                let expected = rules.iter().position(|rule| rule.matches(path));
+                assert_eq!(set.first_match(path, &|_, _| false), expected, "{}", path);
+                // Answered from the last path the second time
+                assert_eq!(set.first_match(path, &|_, _| false), expected, "{}", path);
+            }
+        }
+
+        let mut set = RuleSet::new(&rules, false);
+        assert_eq!(set.first_match("$.A.x", &|_, _| false), Some(3));
+        assert_eq!(set.first_match("$.c.name", &|_, _| false), Some(4));
+        assert!(!set.is_match("$.c", &|_, _| false));
+    }
+
+    #[test]
+    fn test_stats() {
+        let rules = rules();
+        let mut set = RuleSet::new(&rules, true);
+        for path in ["$.a.x", "$.a.x", "$.b[1]", "$.b[1].id", "$.c"] {
+            set.is_match(path, &|_, _| false);
+        }
+
+        let stats = set.into_stats();
+        assert_eq!(stats.len(), rules.len());
+        assert_eq!(stats[0].rule, "$.a.*");
+        // The repeated path is answered from the last path
+        assert!(stats.iter().all(|stats| stats.tested == 4));
+        let matched: Vec<usize> = stats.iter().map(|stats| stats.matched).collect();
+        assert_eq!(matched, [1, 1, 1, 1, 0]);
+
+        assert!(RuleSet::new(&rules, false).into_stats().is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+//! Comparison rules derived from JSON Schema annotations
+//!
+//! Properties annotated with the `x-diff` vendor extension are turned into rules:
+//!
+//! ```json
+//! {
+//!   "type": "object",
+//!   "properties": {
+//!     "updatedAt": { "type": "string", "x-diff": { "ignore": true } },
+//!     "price": { "type": "number", "x-diff": { "tolerance": 0.01 } },
+//!     "tags": { "type": "array", "x-diff": { "unordered": true } }
+//!   }
+//! }
+//! ```
+//!
+//! Schema locations map onto JSON paths: `properties/<name>` becomes `.<name>`,
+//! `items` becomes `[*]` (any index) and `additionalProperties` becomes `.*` (any key).
+//! `$ref` pointers are resolved within the same document; a recursive `$ref` is
+//! not followed again beneath itself.
+
+use serde_json::Value;
+
+use crate::compare::CompareOptions;
+use crate::error::JsonDiffError;
+use crate::path::{push_key, JsonPath};
+
+/// A segment of a path derived from a schema location
+#[derive(Debug, Clone)]
+enum Segment {
+    Key(String),
+    AnyKey,
+    Index(usize),
+    AnyIndex,
+}
+
+/// Build a `CompareOptions` overlay holding the rules annotated in `schema`
+///
+/// The returned options only carry rules; apply them on top of a base with
+/// [`CompareOptions::extend_rules`].
+pub fn rules_from_schema(schema: &Value) -> Result<CompareOptions, JsonDiffError> {
+    let mut overlay = CompareOptions::default();
+    let mut segments = Vec::new();
+    let mut active_refs = Vec::new();
+    walk(schema, schema, &mut segments, &mut active_refs, &mut overlay)?;
+    Ok(overlay)
+}
+
+fn walk(
+    root: &Value,
+    node: &Value,
+    segments: &mut Vec<Segment>,
+    active_refs: &mut Vec<String>,
+    overlay: &mut CompareOptions,
+) -> Result<(), JsonDiffError> {
+    let Value::Object(schema) = node else {
+        // Boolean schemas carry no annotations
+        return Ok(());
+    };
+
+    if let Some(Value::String(reference)) = schema.get("$ref") {
+        // Recursive schemas describe unbounded paths; stop at the first repetition
+        if !active_refs.contains(reference) {
+            let target = resolve_ref(root, reference)?;
+            active_refs.push(reference.clone());
+            walk(root, target, segments, active_refs, overlay)?;
+            active_refs.pop();
+        }
+    }
+
+    if let Some(annotation) = schema.get("x-diff") {
+        apply_annotation(annotation, segments, overlay)?;
+    }
+
+    if let Some(Value::Object(properties)) = schema.get("properties") {
+        for (name, property) in properties {
+            segments.push(Segment::Key(name.clone()));
+            walk(root, property, segments, active_refs, overlay)?;
+            segments.pop();
+        }
+    }
+
+    if let Some(additional @ Value::Object(_)) = schema.get("additionalProperties") {
+        segments.push(Segment::AnyKey);
+        walk(root, additional, segments, active_refs, overlay)?;
+        segments.pop();
+    }
+
+    match schema.get("items") {
+        Some(Value::Array(tuple)) => {
+            for (index, item) in tuple.iter().enumerate() {
+                segments.push(Segment::Index(index));
+                walk(root, item, segments, active_refs, overlay)?;
+                segments.pop();
+            }
+        }
+        Some(items) => {
+            segments.push(Segment::AnyIndex);
+            walk(root, items, segments, active_refs, overlay)?;
+            segments.pop();
+        }
+        None => {}
+    }
+
+    for keyword in ["allOf", "anyOf", "oneOf"] {
+        if let Some(Value::Array(subschemas)) = schema.get(keyword) {
+            for subschema in subschemas {
+                walk(root, subschema, segments, active_refs, overlay)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve a same-document `$ref` such as `#/definitions/Address`
+fn resolve_ref<'a>(root: &'a Value, reference: &str) -> Result<&'a Value, JsonDiffError> {
+    let pointer = reference.strip_prefix('#').ok_or_else(|| {
+        JsonDiffError::InvalidSchema(format!("only same-document $ref pointers are supported: {}", reference))
+    })?;
+
+    root.pointer(pointer)
+        .ok_or_else(|| JsonDiffError::InvalidSchema(format!("unresolved $ref: {}", reference)))
+}
+
+fn apply_annotation(annotation: &Value, segments: &[Segment], overlay: &mut CompareOptions) -> Result<(), JsonDiffError> {
+    let path = segments_to_path(segments)?;
+
+    if annotation.get("ignore").and_then(Value::as_bool) == Some(true) {
+        overlay.ignore_paths.push(path.clone());
+    }
+
+    if annotation.get("unordered").and_then(Value::as_bool) == Some(true) {
+        overlay.unordered_arrays.push(path.clone());
+    }
+
+    if let Some(tolerance) = annotation.get("tolerance") {
+        let tolerance = tolerance.as_f64().ok_or_else(|| {
+            JsonDiffError::InvalidSchema(format!("x-diff tolerance must be a number at {}", render_path(segments)))
+        })?;
+        overlay.tolerances.push((path, tolerance));
+    }
+
+    Ok(())
+}
+
+fn render_path(segments: &[Segment]) -> String {
+    let mut path = String::from("$");
+    for segment in segments {
+        match segment {
+            Segment::Key(key) => push_key(&mut path, key),
+            Segment::AnyKey => path.push_str(".*"),
+            Segment::Index(index) => path.push_str(&format!("[{}]", index)),
+            Segment::AnyIndex => path.push_str("[*]"),
+        }
+    }
+    path
+}
+
+fn segments_to_path(segments: &[Segment]) -> Result<JsonPath, JsonDiffError> {
+    JsonPath::new(&render_path(segments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::{compare_json, DiffType};
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "definitions": {
+                "Audit": {
+                    "type": "object",
+                    "properties": {
+                        "updatedAt": {"type": "string", "x-diff": {"ignore": true}}
+                    }
+                }
+            },
+            "properties": {
+                "user": {
+                    "type": "object",
+                    "properties": {
+                        "balance": {"type": "number", "x-diff": {"tolerance": 0.01}},
+                        "audit": {"$ref": "#/definitions/Audit"}
+                    }
+                },
+                "orders": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "requestId": {"type": "string", "x-diff": {"ignore": true}}
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_rules_from_nested_schema() {
+        let overlay = rules_from_schema(&schema()).unwrap();
+
+        assert_eq!(overlay.ignore_paths.len(), 2);
+        assert!(overlay.ignore_paths.iter().any(|p| p.matches("$.user.audit.updatedAt")));
+        assert!(overlay.ignore_paths.iter().any(|p| p.matches("$.orders[3].requestId")));
+        assert!(!overlay.ignore_paths.iter().any(|p| p.matches("$.orders.requestId")));
+
+        assert_eq!(overlay.tolerances.len(), 1);
+        assert!(overlay.tolerances[0].0.matches("$.user.balance"));
+        assert_eq!(overlay.tolerances[0].1, 0.01);
+    }
+
+    #[test]
+    fn test_schema_rules_in_comparison() {
+        let left = json!({
+            "user": {"balance": 10.00, "audit": {"updatedAt": "2023-01-01"}},
+            "orders": [{"requestId": "a", "total": 1}, {"requestId": "b", "total": 2}]
+        });
+        let right = json!({
+            "user": {"balance": 10.005, "audit": {"updatedAt": "2023-01-02"}},
+            "orders": [{"requestId": "c", "total": 1}, {"requestId": "d", "total": 3}]
+        });
+
+        let mut options = CompareOptions::default();
+        options.extend_rules(rules_from_schema(&schema()).unwrap());
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        let significant: Vec<_> = result.entries.iter()
+            .filter(|e| e.diff_type != DiffType::Ignored)
+            .collect();
+
+        assert_eq!(significant.len(), 1);
+        assert_eq!(significant[0].path, "$.orders[1].total");
+    }
+
+    #[test]
+    fn test_unresolved_ref() {
+        let schema = json!({"properties": {"a": {"$ref": "#/definitions/Missing"}}});
+        assert!(matches!(rules_from_schema(&schema), Err(JsonDiffError::InvalidSchema(_))));
+    }
+
+    #[test]
+    fn test_recursive_ref() {
+        let schema = json!({
+            "definitions": {
+                "Node": {
+                    "properties": {
+                        "id": {"x-diff": {"ignore": true}},
+                        "next": {"$ref": "#/definitions/Node"}
+                    }
+                }
+            },
+            "$ref": "#/definitions/Node"
+        });
+
+        let overlay = rules_from_schema(&schema).unwrap();
+        assert_eq!(overlay.ignore_paths.len(), 1);
+        assert!(overlay.ignore_paths[0].matches("$.id"));
+    }
+}
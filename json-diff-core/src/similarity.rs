@@ -0,0 +1,83 @@
+//! How alike two strings are, for suggesting file names and for near matches, see
+//! [`CompareOptions::string_similarity`](crate::CompareOptions::string_similarity)
+
+/// Most characters left in either string, once the start and end they share are cut
+/// off, for which [`similarity`] computes the edit distance; it takes time proportional
+/// to the product of both lengths
+pub(crate) const MAX_SIMILARITY_CHARS: usize = 2_000;
+
+/// Levenshtein distance between `a` and `b`, counted in characters
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    char_distance(&a, &b)
+}
+
+fn char_distance(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.iter().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous + usize::from(a != b);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(previous + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// One minus the edit distance between `a` and `b` divided by the length of the longer,
+/// from 0 for strings with nothing in common to 1 for equal ones
+///
+/// The start and end both strings share do not change the distance and are cut off
+/// first. If either of what remains is longer than [`MAX_SIMILARITY_CHARS`], the longer
+/// of the two is taken as the distance instead, which is never less than the real one,
+/// so two long strings are at worst found less alike than they are.
+pub(crate) fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    let prefix = a.iter().zip(&b).take_while(|(a, b)| a == b).count();
+    let (a_rest, b_rest) = (&a[prefix..], &b[prefix..]);
+    let suffix = a_rest.iter().rev().zip(b_rest.iter().rev()).take_while(|(a, b)| a == b).count();
+    let (a_rest, b_rest) = (&a_rest[..a_rest.len() - suffix], &b_rest[..b_rest.len() - suffix]);
+
+    let distance = if a_rest.len().max(b_rest.len()) > MAX_SIMILARITY_CHARS {
+        a_rest.len().max(b_rest.len())
+    } else {
+        char_distance(a_rest, b_rest)
+    };
+    1.0 - distance as f64 / longest as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("config.json", "config.json"), 0);
+        assert_eq!(edit_distance("confg.json", "config.json"), 1);
+        assert_eq!(edit_distance("cofnig.json", "config.json"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(similarity("same", "same"), 1.0);
+        assert_eq!(similarity("", ""), 1.0);
+        assert_eq!(similarity("abc", "xyz"), 0.0);
+        assert_eq!(similarity("Jonh Smith", "John Smith"), 0.8);
+        assert_eq!(similarity("naïve café", "naive café"), 0.9);
+
+        // Past the limit the differing middle counts as rewritten...
+        let (left, right) = ("a".repeat(MAX_SIMILARITY_CHARS + 1), "b".repeat(MAX_SIMILARITY_CHARS + 1));
+        assert_eq!(similarity(&left, &right), 0.0);
+        // ...while a typo in a long text shared around it is still measured exactly
+        let text = "x".repeat(10 * MAX_SIMILARITY_CHARS);
+        assert_eq!(similarity(&format!("{}a{}", text, text), &format!("{}b{}", text, text)), 1.0 - 1.0 / (20 * MAX_SIMILARITY_CHARS + 1) as f64);
+    }
+}
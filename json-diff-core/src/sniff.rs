@@ -0,0 +1,120 @@
+//! Explanations for inputs that are plainly not JSON
+//!
+//! A parse error at line 1 column 1 says little when the file is an HTML error page,
+//! a CSV export or a YAML configuration. Once a document fails to parse, its first
+//! bytes are looked at to tell what it is instead; when they tell nothing, the parse
+//! error is kept.
+
+use serde_json::Value;
+
+/// Characters shown of a document that does not look like JSON
+const PREVIEW_CHARS: usize = 16;
+
+/// Why `text`, which failed to parse with `error`, is not JSON
+pub(crate) fn explain_parse_error(text: &str, error: &serde_json::Error) -> String {
+    let content = text.trim_start_matches(|c: char| c.is_whitespace() || c == '\u{feff}');
+
+    if let Some(count) = concatenated_values(content) {
+        return format!(
+            "holds {} JSON values one after another, as NDJSON does, but only one document can be compared; \
+             wrap them in an array",
+            count
+        );
+    }
+    if looks_like_yaml(content) {
+        return format!(
+            "does not look like JSON but like YAML, which json-diff does not read; convert it to JSON first \
+             (first bytes: `{}`)",
+            preview(content)
+        );
+    }
+    if content.starts_with(|c: char| !c.is_ascii_digit() && !"{[\"-tfn".contains(c)) {
+        let hint = if content.starts_with("NaN") || content.starts_with("Infinity") {
+            "; did you mean --lenient?"
+        } else {
+            ""
+        };
+        return format!("does not look like JSON; first bytes: `{}`{}", preview(content), hint);
+    }
+    error.to_string()
+}
+
+/// Number of values in `content` when it holds several complete JSON values
+fn concatenated_values(content: &str) -> Option<usize> {
+    let mut count = 0;
+    for value in serde_json::Deserializer::from_str(content).into_iter::<Value>() {
+        value.ok()?;
+        count += 1;
+    }
+    (count > 1).then_some(count)
+}
+
+/// Whether the first line that is not blank or a comment reads as YAML: a document
+/// marker, a `key:` mapping entry or a `- ` list item
+fn looks_like_yaml(content: &str) -> bool {
+    let Some(line) = content.lines().map(str::trim_end).find(|line| !line.is_empty() && !line.starts_with('#')) else {
+        return false;
+    };
+    if line == "---" || line.starts_with("--- ") || line.starts_with("- ") {
+        return true;
+    }
+    match line.split_once(':') {
+        Some((key, rest)) => {
+            !key.is_empty()
+                && key.chars().all(|c| c.is_alphanumeric() || "_-. ".contains(c))
+                && (rest.is_empty() || rest.starts_with(' '))
+        }
+        None => false,
+    }
+}
+
+/// The first characters of `content`, escaped to show on one line
+fn preview(content: &str) -> String {
+    let mut chars = content.chars();
+    let shown: String = chars.by_ref().take(PREVIEW_CHARS).collect();
+    let more = if chars.next().is_some() { "…" } else { "" };
+    format!("{}{}", shown.escape_debug(), more)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn explain(text: &str) -> String {
+        let error = serde_json::from_str::<Value>(text).unwrap_err();
+        explain_parse_error(text, &error)
+    }
+
+    #[test]
+    fn test_html_and_csv() {
+        assert_eq!(
+            explain("\n<!DOCTYPE html>\n<html><body>502 Bad Gateway</body></html>"),
+            "does not look like JSON; first bytes: `<!DOCTYPE html>\\n…`"
+        );
+        assert_eq!(explain("id,name\n1,a\n"), "does not look like JSON; first bytes: `id,name\\n1,a\\n`");
+        assert_eq!(explain("NaN"), "does not look like JSON; first bytes: `NaN`; did you mean --lenient?");
+    }
+
+    #[test]
+    fn test_yaml() {
+        let message = explain("# service\nname: api\nreplicas: 3\n");
+        assert!(message.starts_with("does not look like JSON but like YAML"), "{}", message);
+        assert!(explain("---\n- a\n- b\n").contains("like YAML"));
+    }
+
+    #[test]
+    fn test_ndjson() {
+        assert_eq!(
+            explain("{\"id\": 1}\n{\"id\": 2}\n{\"id\": 3}\n"),
+            "holds 3 JSON values one after another, as NDJSON does, but only one document can be compared; \
+             wrap them in an array"
+        );
+    }
+
+    #[test]
+    fn test_plain_syntax_errors_are_kept() {
+        let message = explain("{\"id\": }");
+        assert!(message.contains("line 1 column 8"), "{}", message);
+        assert!(explain("nul").contains("line 1"));
+    }
+}
@@ -0,0 +1,571 @@
+//! Element-by-element comparison of documents that are one huge top-level array
+//!
+//! Only one element per side (or, when matching by key, a bounded number of
+//! unmatched elements) is held in memory at a time. Entries are handed to a
+//! callback as they are found, with paths like `$[10482].field` and the line on
+//! which the element starts.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::ops::ControlFlow;
+use serde_json::Value;
+
+use crate::compare::{compare_values_at, CompareOptions, IndexStyle};
+use crate::path::keyed_segment;
+use crate::rule_set::RuleSet;
+use crate::diff::{DiffEntry, DiffType};
+use crate::error::JsonDiffError;
+
+/// Options for streaming comparison
+#[derive(Debug, Clone)]
+pub struct StreamOptions {
+    /// Match elements by the value of this key instead of by position
+    pub key: Option<String>,
+    /// Most elements held while waiting for their match by key
+    pub max_buffered: usize,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            key: None,
+            max_buffered: 100_000,
+        }
+    }
+}
+
+/// Totals of a streaming comparison
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamSummary {
+    pub left_elements: usize,
+    pub right_elements: usize,
+    pub entries: usize,
+    pub warnings: Vec<String>,
+    /// Whether the callback of [`compare_array_streams_until`] stopped the comparison
+    /// before the end of the inputs
+    pub stopped: bool,
+}
+
+/// Compare two top-level JSON arrays read incrementally, calling `on_entry` for every difference
+///
+/// Elements are compared by position, or by the value of `stream_options.key`. In keyed
+/// mode elements that have not met their counterpart yet are buffered; the comparison
+/// fails once more than `max_buffered` of them are waiting, as the inputs are then too
+/// far out of order to compare in bounded memory. Unordered array rules for `$` are not
+/// supported, and preprocessing transforms are not applied. With `IndexStyle::Keyed`,
+/// entries of keyed streams name their element by key, as in `$[id=42].field`.
+pub fn compare_array_streams<L: Read, R: Read>(
+    left: L,
+    right: R,
+    options: &CompareOptions,
+    stream_options: &StreamOptions,
+    mut on_entry: impl FnMut(DiffEntry),
+) -> Result<StreamSummary, JsonDiffError> {
+    compare_array_streams_until(left, right, options, stream_options, |entry| {
+        on_entry(entry);
+        ControlFlow::Continue(())
+    })
+}
+
+/// [`compare_array_streams`], stopping as soon as `on_entry` returns `ControlFlow::Break`,
+/// e.g. once the first difference is known
+///
+/// The rest of the inputs is not read; the summary counts the elements read so far and
+/// has `stopped` set.
+pub fn compare_array_streams_until<L: Read, R: Read>(
+    left: L,
+    right: R,
+    options: &CompareOptions,
+    stream_options: &StreamOptions,
+    mut on_entry: impl FnMut(DiffEntry) -> ControlFlow<()>,
+) -> Result<StreamSummary, JsonDiffError> {
+    if options.unordered_arrays.iter().any(|p| p.matches("$")) {
+        return Err(JsonDiffError::StreamError(
+            "unordered comparison of the top-level array is not supported; match elements with a stream key instead".to_string()
+        ));
+    }
+
+    let mut summary = StreamSummary::default();
+    if !options.preprocess.is_empty() {
+        summary.warnings.push("Preprocess transforms are not applied when streaming".to_string());
+    }
+
+    let mut ignore = RuleSet::new(&options.ignore_paths, false);
+    let mut left = ArrayElements::new(left, "left");
+    let mut right = ArrayElements::new(right, "right");
+    // Whether to go on
+    let mut emit = |entry: DiffEntry, summary: &mut StreamSummary| {
+        if !options.is_selected(entry.indexed_path()) {
+            return true;
+        }
+        summary.entries += 1;
+        summary.stopped = on_entry(entry).is_break();
+        !summary.stopped
+    };
+
+    match &stream_options.key {
+        None => loop {
+            let (l, r) = (left.next_element()?, right.next_element()?);
+            let index = summary.left_elements.max(summary.right_elements);
+            summary.left_elements += usize::from(l.is_some());
+            summary.right_elements += usize::from(r.is_some());
+
+            let entries = match (l, r) {
+                (Some(l), Some(r)) => compare_elements((&l, index), (&r, index), options, &mut ignore, &mut summary.warnings)?,
+                (Some(l), None) => vec![unmatched(DiffType::Removed, &l, index, &mut ignore)],
+                (None, Some(r)) => vec![unmatched(DiffType::Added, &r, index, &mut ignore)],
+                (None, None) => break,
+            };
+            for entry in entries {
+                if !emit(entry, &mut summary) {
+                    return Ok(summary);
+                }
+            }
+        },
+        Some(key) => {
+            let mut left_pending: HashMap<String, (usize, Element)> = HashMap::new();
+            let mut right_pending: HashMap<String, (usize, Element)> = HashMap::new();
+
+            loop {
+                let (l, r) = (left.next_element()?, right.next_element()?);
+                if l.is_none() && r.is_none() {
+                    break;
+                }
+
+                if let Some(l) = l {
+                    let index = summary.left_elements;
+                    summary.left_elements += 1;
+                    let id = element_key(&l, key, index, "left")?;
+                    match right_pending.remove(&id) {
+                        Some((right_index, r)) => {
+                            for entry in compare_elements((&l, index), (&r, right_index), options, &mut ignore, &mut summary.warnings)? {
+                                if !emit(keyed(entry, &l, index, key, options), &mut summary) {
+                                    return Ok(summary);
+                                }
+                            }
+                        }
+                        None => buffer(&mut left_pending, id, index, l, "left")?,
+                    }
+                }
+
+                if let Some(r) = r {
+                    let index = summary.right_elements;
+                    summary.right_elements += 1;
+                    let id = element_key(&r, key, index, "right")?;
+                    match left_pending.remove(&id) {
+                        Some((left_index, l)) => {
+                            for entry in compare_elements((&l, left_index), (&r, index), options, &mut ignore, &mut summary.warnings)? {
+                                if !emit(keyed(entry, &l, left_index, key, options), &mut summary) {
+                                    return Ok(summary);
+                                }
+                            }
+                        }
+                        None => buffer(&mut right_pending, id, index, r, "right")?,
+                    }
+                }
+
+                if left_pending.len() + right_pending.len() > stream_options.max_buffered {
+                    return Err(JsonDiffError::StreamError(format!(
+                        "more than {} elements are waiting for a match by \"{}\"; the inputs are too far out of order to compare in bounded memory",
+                        stream_options.max_buffered, key
+                    )));
+                }
+            }
+
+            let mut removed: Vec<(usize, Element)> = left_pending.into_values().collect();
+            removed.sort_by_key(|(index, _)| *index);
+            for (index, element) in removed {
+                let entry = unmatched(DiffType::Removed, &element, index, &mut ignore);
+                if !emit(keyed(entry, &element, index, key, options), &mut summary) {
+                    return Ok(summary);
+                }
+            }
+
+            let mut added: Vec<(usize, Element)> = right_pending.into_values().collect();
+            added.sort_by_key(|(index, _)| *index);
+            for (index, element) in added {
+                let entry = unmatched(DiffType::Added, &element, index, &mut ignore);
+                if !emit(keyed(entry, &element, index, key, options), &mut summary) {
+                    return Ok(summary);
+                }
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// An array element and the line it starts on
+struct Element {
+    value: Value,
+    line: usize,
+}
+
+/// Compare two elements, each with its index; entries are reported at the left one,
+/// naming the right one in `right_path` when the indices differ
+fn compare_elements(
+    (left, left_index): (&Element, usize),
+    (right, right_index): (&Element, usize),
+    options: &CompareOptions,
+    ignore: &mut RuleSet,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<DiffEntry>, JsonDiffError> {
+    let path = format!("$[{}]", left_index);
+    let (mut entries, element_warnings) = compare_values_at(&left.value, &right.value, &path, options, ignore)?;
+    warnings.extend(element_warnings);
+
+    for entry in &mut entries {
+        entry.left_line = entry.left_line.or(Some(left.line));
+        entry.right_line = entry.right_line.or(Some(right.line));
+        if left_index != right_index {
+            entry.right_path = entry.path.strip_prefix(&path).map(|rest| format!("$[{}]{}", right_index, rest));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// `entry` of the element at `index`, naming the element by its `key` if the options ask for keyed paths
+fn keyed(mut entry: DiffEntry, element: &Element, index: usize, key: &str, options: &CompareOptions) -> DiffEntry {
+    if options.path_index_style == IndexStyle::Keyed {
+        if let Some(segment) = element.value.get(key).and_then(|id| keyed_segment(key, id)) {
+            entry.name_element(&format!("$[{}]", index), &segment);
+        }
+    }
+    entry
+}
+
+fn unmatched(diff_type: DiffType, element: &Element, index: usize, ignore: &mut RuleSet) -> DiffEntry {
+    let path = format!("$[{}]", index);
+    let is_left = diff_type == DiffType::Removed;
+    let line = Some(element.line);
+    let (left_line, right_line) = if is_left { (line, None) } else { (None, line) };
+
+    if ignore.is_match(&path, &|_, filter| filter.holds(&element.value)) {
+        return DiffEntry::ignored(path).with_lines(left_line, right_line);
+    }
+
+    let entry = match is_left {
+        true => DiffEntry::removed(path, element.value.clone()),
+        false => DiffEntry::added(path, element.value.clone()),
+    };
+    entry.with_lines(left_line, right_line)
+}
+
+fn element_key(element: &Element, key: &str, index: usize, side: &str) -> Result<String, JsonDiffError> {
+    element.value.get(key)
+        .map(|id| id.to_string())
+        .ok_or_else(|| JsonDiffError::StreamError(format!(
+            "element {} of the {} document (line {}) has no \"{}\" key", index, side, element.line, key
+        )))
+}
+
+fn buffer(
+    pending: &mut HashMap<String, (usize, Element)>,
+    id: String,
+    index: usize,
+    element: Element,
+    side: &str,
+) -> Result<(), JsonDiffError> {
+    if pending.contains_key(&id) {
+        return Err(JsonDiffError::StreamError(format!(
+            "duplicate key {} in the {} document (line {})", id, side, element.line
+        )));
+    }
+    pending.insert(id, (index, element));
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Start,
+    First,
+    Next,
+    Done,
+}
+
+/// Reads the elements of a top-level array one at a time
+///
+/// A `serde_json::StreamDeserializer` only handles whitespace-separated values,
+/// so the array punctuation is consumed here and each element's bytes are
+/// collected and parsed on their own.
+struct ArrayElements<R: Read> {
+    reader: BufReader<R>,
+    side: &'static str,
+    line: usize,
+    state: State,
+    buf: Vec<u8>,
+}
+
+impl<R: Read> ArrayElements<R> {
+    fn new(reader: R, side: &'static str) -> Self {
+        Self { reader: BufReader::new(reader), side, line: 1, state: State::Start, buf: Vec::new() }
+    }
+
+    fn next_element(&mut self) -> Result<Option<Element>, JsonDiffError> {
+        if self.state == State::Done {
+            return Ok(None);
+        }
+
+        if self.state == State::Start {
+            self.skip_whitespace()?;
+            if self.peek()? != Some(b'[') {
+                return Err(self.error("expected a top-level array"));
+            }
+            self.bump()?;
+            self.state = State::First;
+        }
+
+        self.skip_whitespace()?;
+        match (self.state, self.peek()?) {
+            (_, Some(b']')) => {
+                self.bump()?;
+                self.skip_whitespace()?;
+                if self.peek()?.is_some() {
+                    return Err(self.error("unexpected content after the top-level array"));
+                }
+                self.state = State::Done;
+                return Ok(None);
+            }
+            (State::Next, Some(b',')) => {
+                self.bump()?;
+                self.skip_whitespace()?;
+            }
+            (State::Next, _) | (_, None) => return Err(self.error("expected ',' or ']'")),
+            _ => {}
+        }
+
+        let line = self.line;
+        self.read_value()?;
+        let value = serde_json::from_slice(&self.buf)?;
+        self.state = State::Next;
+
+        Ok(Some(Element { value, line }))
+    }
+
+    /// Collect the bytes of one value into `buf`
+    fn read_value(&mut self) -> Result<(), JsonDiffError> {
+        self.buf.clear();
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        while let Some(byte) = self.peek()? {
+            if !in_string && depth == 0 && !self.buf.is_empty() {
+                let at_end = match self.buf[0] {
+                    b'{' | b'[' | b'"' => true,
+                    _ => matches!(byte, b',' | b']') || byte.is_ascii_whitespace(),
+                };
+                if at_end {
+                    return Ok(());
+                }
+            }
+
+            self.bump()?;
+            self.buf.push(byte);
+
+            if in_string {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+            } else {
+                match byte {
+                    b'"' => in_string = true,
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+        }
+
+        if self.buf.is_empty() || in_string || depth > 0 {
+            return Err(self.error("unexpected end of input"));
+        }
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), JsonDiffError> {
+        while let Some(byte) = self.peek()? {
+            if !byte.is_ascii_whitespace() {
+                break;
+            }
+            self.bump()?;
+        }
+        Ok(())
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, JsonDiffError> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn bump(&mut self) -> Result<(), JsonDiffError> {
+        if self.peek()? == Some(b'\n') {
+            self.line += 1;
+        }
+        self.reader.consume(1);
+        Ok(())
+    }
+
+    fn error(&self, message: &str) -> JsonDiffError {
+        JsonDiffError::StreamError(format!("{} in the {} document at line {}", message, self.side, self.line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::JsonPath;
+
+    const ELEMENTS: usize = 100_000;
+
+    /// One record per line, with a few planted differences on the right
+    fn generate(right: bool) -> String {
+        let mut out = String::from("[\n");
+        for i in 0..ELEMENTS {
+            let mut record = json!({"id": i, "name": format!("user-{}", i), "score": i % 100});
+            if right && i == 10_482 {
+                record["name"] = json!("changed");
+            }
+            if right && i == 99_999 {
+                record["score"] = json!(-1);
+            }
+            if right && i == 5 {
+                record.as_object_mut().unwrap().remove("score");
+            }
+            out.push_str(&record.to_string());
+            out.push_str(if i + 1 < ELEMENTS { ",\n" } else { "\n" });
+        }
+        out.push(']');
+        out
+    }
+
+    fn collect(left: &str, right: &str, options: &CompareOptions, stream: &StreamOptions) -> Result<(Vec<DiffEntry>, StreamSummary), JsonDiffError> {
+        let mut entries = Vec::new();
+        let summary = compare_array_streams(left.as_bytes(), right.as_bytes(), options, stream, |e| entries.push(e))?;
+        Ok((entries, summary))
+    }
+
+    #[test]
+    fn test_positional_stream() {
+        let (left, right) = (generate(false), generate(true));
+        let (entries, summary) = collect(&left, &right, &CompareOptions::default(), &StreamOptions::default()).unwrap();
+
+        assert_eq!(summary.left_elements, ELEMENTS);
+        assert_eq!(summary.right_elements, ELEMENTS);
+        assert_eq!(summary.entries, 3);
+
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["$[5].score", "$[10482].name", "$[99999].score"]);
+        assert_eq!(entries[0].diff_type, DiffType::Removed);
+        assert_eq!(entries[1].left_line, Some(10_484));
+        assert_eq!(entries[1].new_value, Some(json!("changed")));
+    }
+
+    #[test]
+    fn test_stream_stops_when_asked() {
+        let (left, right) = (generate(false), generate(true));
+        let mut paths = Vec::new();
+        let summary = compare_array_streams_until(left.as_bytes(), right.as_bytes(), &CompareOptions::default(), &StreamOptions::default(), |entry| {
+            paths.push(entry.path);
+            if paths.len() == 2 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        }).unwrap();
+        assert_eq!(paths, ["$[5].score", "$[10482].name"]);
+        assert!(summary.stopped);
+        assert_eq!((summary.entries, summary.left_elements), (2, 10_483));
+
+        let (_, summary) = collect(&left, &right, &CompareOptions::default(), &StreamOptions { key: Some("id".to_string()), ..StreamOptions::default() }).unwrap();
+        assert!(!summary.stopped);
+    }
+
+    #[test]
+    fn test_keyed_stream() {
+        let left = r#"[{"id": 1, "v": "a"}, {"id": 2, "v": "b"}, {"id": 3, "v": "c"}, {"id": 4, "v": 1}]"#;
+        let right = r#"[{"id": 2, "v": "b"}, {"id": 1, "v": "x"}, {"id": 5, "v": "e"}, {"id": 3, "v": "c"}]"#;
+
+        let stream = StreamOptions { key: Some("id".to_string()), ..StreamOptions::default() };
+        let (entries, _) = collect(left, right, &CompareOptions::default(), &stream).unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].path, "$[0].v");
+        assert_eq!(entries[0].diff_type, DiffType::Modified);
+        assert_eq!(entries[0].right_path.as_deref(), Some("$[1].v"));
+        assert_eq!((entries[1].diff_type.clone(), entries[1].path.as_str()), (DiffType::Removed, "$[3]"));
+        assert_eq!((entries[2].diff_type.clone(), entries[2].path.as_str()), (DiffType::Added, "$[2]"));
+    }
+
+    #[test]
+    fn test_keyed_stream_paths() {
+        let left = r#"[{"id": 1, "v": "a"}, {"id": "x y", "v": "b"}, {"id": 3}]"#;
+        let right = r#"[{"id": "x y", "v": "c"}, {"id": 1, "v": "a"}, {"id": 4}]"#;
+
+        let stream = StreamOptions { key: Some("id".to_string()), ..StreamOptions::default() };
+        let mut options = CompareOptions { path_index_style: IndexStyle::Keyed, ..CompareOptions::default() };
+        let (entries, _) = collect(left, right, &options, &stream).unwrap();
+        let paths: Vec<(&str, Option<&str>)> = entries.iter().map(|e| (e.path.as_str(), e.index_path.as_deref())).collect();
+        assert_eq!(paths, vec![
+            (r#"$[id="x y"].v"#, Some("$[1].v")),
+            ("$[id=3]", Some("$[2]")),
+            ("$[id=4]", Some("$[2]")),
+        ]);
+        assert_eq!(entries[0].right_path.as_deref(), Some("$[0].v"));
+
+        // Ignore rules name the elements by key too
+        options.ignore_paths.push(JsonPath::new(r#"$[id="x y"].v"#).unwrap());
+        options.ignore_paths.push(JsonPath::new("$[id=4]").unwrap());
+        let (entries, _) = collect(left, right, &options, &stream).unwrap();
+        let kinds: Vec<(DiffType, &str)> = entries.iter().map(|e| (e.diff_type.clone(), e.path.as_str())).collect();
+        assert_eq!(kinds, vec![
+            (DiffType::Ignored, r#"$[id="x y"].v"#),
+            (DiffType::Removed, "$[id=3]"),
+            (DiffType::Ignored, "$[id=4]"),
+        ]);
+    }
+
+    #[test]
+    fn test_keyed_stream_spill_detection() {
+        let left: Vec<Value> = (0..100).map(|i| json!({"id": i})).collect();
+        let right: Vec<Value> = (0..100).rev().map(|i| json!({"id": i})).collect();
+        let (left, right) = (Value::Array(left).to_string(), Value::Array(right).to_string());
+
+        let stream = StreamOptions { key: Some("id".to_string()), max_buffered: 10 };
+        let err = collect(&left, &right, &CompareOptions::default(), &stream).unwrap_err();
+        assert!(err.to_string().contains("more than 10 elements"));
+
+        let stream = StreamOptions { key: Some("id".to_string()), max_buffered: 100 };
+        let (entries, _) = collect(&left, &right, &CompareOptions::default(), &stream).unwrap();
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn test_stream_rejects_unordered_and_non_arrays() {
+        let mut options = CompareOptions::default();
+        options.unordered_arrays.push(JsonPath::new("$").unwrap());
+        assert!(matches!(collect("[]", "[]", &options, &StreamOptions::default()), Err(JsonDiffError::StreamError(_))));
+
+        let err = collect(r#"{"a": 1}"#, "[]", &CompareOptions::default(), &StreamOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("expected a top-level array in the left document"));
+
+        let err = collect("[1, 2", "[1, 2]", &CompareOptions::default(), &StreamOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("expected ',' or ']'"));
+    }
+
+    #[test]
+    fn test_stream_scalars_and_lengths() {
+        let (entries, summary) = collect(
+            r#"[1, "a,]", [2, [3]], null]"#,
+            r#"[1, "a,]", [2, [4]]]"#,
+            &CompareOptions::default(),
+            &StreamOptions::default(),
+        ).unwrap();
+
+        assert_eq!(summary.left_elements, 4);
+        assert_eq!(summary.right_elements, 3);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].diff_type, DiffType::Removed);
+        assert_eq!(entries[1].path, "$[3]");
+    }
+}
@@ -0,0 +1,335 @@
+//! Comparison of two arrays of records matched by a key field, reported per row
+//!
+//! Both documents are arrays of objects holding a primary key, such as `id`. Rows are
+//! matched by the JSON text of their key, so `1` and `"1"` are different keys, and each
+//! matched pair is compared as a document of its own: the rules of the options are
+//! written against a row (`$.updated_at`), and entries' paths are relative to it. The
+//! entries' lines are those of the whole files.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::compare::{compare_json, CompareOptions};
+use crate::diff::{DiffEntry, DiffType, ValueRenderOptions, ABSENT};
+use crate::error::JsonDiffError;
+use crate::lenient::{format_value, substitute_non_finite};
+use crate::line_map::LineMap;
+use crate::sniff::explain_parse_error;
+
+/// How a row differs between the tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RowStatus {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// A row that was added, removed or changed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RowDiff {
+    /// Value of the key field
+    pub key: Value,
+    pub status: RowStatus,
+    /// Index of the row in each array it is in
+    pub left_index: Option<usize>,
+    pub right_index: Option<usize>,
+    /// Line on which the row starts in each file it is in
+    pub left_line: Option<usize>,
+    pub right_line: Option<usize>,
+    /// The whole row, for added and removed rows
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub row: Option<Value>,
+    /// Differences of a modified row, with paths relative to the row
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub entries: Vec<DiffEntry>,
+}
+
+/// Number of rows per outcome
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TableSummary {
+    pub left_rows: usize,
+    pub right_rows: usize,
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+    pub unchanged: usize,
+}
+
+/// Rows of two tables that differ, in the left table's order followed by the added rows
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TableDiff {
+    /// Field the rows were matched by
+    pub key: String,
+    pub rows: Vec<RowDiff>,
+    pub summary: TableSummary,
+    pub warnings: Vec<String>,
+}
+
+/// Compare two JSON documents given as text as tables keyed by `key`
+///
+/// Fails if a document is not an array, or if a row is not an object holding `key`
+/// or repeats the key of another row.
+pub fn compare_tables(left_content: &str, right_content: &str, key: &str, options: &CompareOptions) -> Result<TableDiff, JsonDiffError> {
+    let (left_text, right_text) = if options.lenient {
+        (substitute_non_finite(left_content), substitute_non_finite(right_content))
+    } else {
+        (Cow::Borrowed(left_content), Cow::Borrowed(right_content))
+    };
+    let (left, right): (Value, Value) = match (serde_json::from_str(&left_text), serde_json::from_str(&right_text)) {
+        (Ok(left), Ok(right)) => (left, right),
+        (left, right) => return Err(JsonDiffError::Unparseable {
+            left: left.err().map(|e| explain_parse_error(left_content, &e)),
+            right: right.err().map(|e| explain_parse_error(right_content, &e)),
+        }),
+    };
+    let left_rows = rows(&left, key, "left")?;
+    let right_rows = rows(&right, key, "right")?;
+    let (left_positions, right_positions) = (LineMap::scan(left_content), LineMap::scan(right_content));
+
+    let mut right_by_key: HashMap<String, usize> = HashMap::new();
+    for (index, (id, _)) in right_rows.iter().enumerate() {
+        if let Some(first) = right_by_key.insert(id.clone(), index) {
+            return Err(duplicate(id, "right", first, index));
+        }
+    }
+
+    let mut diff = TableDiff {
+        key: key.to_string(),
+        rows: Vec::new(),
+        summary: TableSummary { left_rows: left_rows.len(), right_rows: right_rows.len(), ..TableSummary::default() },
+        warnings: Vec::new(),
+    };
+    let row_line = |positions: &LineMap, index: usize| positions.line_of(&format!("$[{}]", index));
+    let mut left_by_key: HashMap<&str, usize> = HashMap::new();
+    let mut matched = vec![false; right_rows.len()];
+
+    for (left_index, (id, left_row)) in left_rows.iter().enumerate() {
+        if let Some(first) = left_by_key.insert(id, left_index) {
+            return Err(duplicate(id, "left", first, left_index));
+        }
+        let Some(&right_index) = right_by_key.get(id) else {
+            diff.summary.removed += 1;
+            diff.rows.push(RowDiff {
+                key: left_row[key].clone(),
+                status: RowStatus::Removed,
+                left_index: Some(left_index),
+                right_index: None,
+                left_line: row_line(&left_positions, left_index),
+                right_line: None,
+                row: Some((*left_row).clone()),
+                entries: Vec::new(),
+            });
+            continue;
+        };
+        matched[right_index] = true;
+
+        let mut result = compare_json(left_row, right_rows[right_index].1, options)?;
+        diff.warnings.append(&mut result.warnings);
+        if !result.iter().any(|entry| entry.diff_type.is_difference()) {
+            diff.summary.unchanged += 1;
+            continue;
+        }
+        // Lines of the cells in the whole files; a cell missing on a side gets its row's line
+        let (left_line, right_line) = (row_line(&left_positions, left_index), row_line(&right_positions, right_index));
+        let mut entries = result.into_entries();
+        for entry in &mut entries {
+            let cell = &entry.path[1..];
+            entry.left_line = left_positions.line_of(&format!("$[{}]{}", left_index, cell)).or(left_line);
+            entry.right_line = right_positions.line_of(&format!("$[{}]{}", right_index, cell)).or(right_line);
+        }
+        diff.summary.modified += 1;
+        diff.rows.push(RowDiff {
+            key: left_row[key].clone(),
+            status: RowStatus::Modified,
+            left_index: Some(left_index),
+            right_index: Some(right_index),
+            left_line,
+            right_line,
+            row: None,
+            entries,
+        });
+    }
+
+    for (right_index, (_, right_row)) in right_rows.iter().enumerate().filter(|(index, _)| !matched[*index]) {
+        diff.summary.added += 1;
+        diff.rows.push(RowDiff {
+            key: right_row[key].clone(),
+            status: RowStatus::Added,
+            left_index: None,
+            right_index: Some(right_index),
+            left_line: None,
+            right_line: row_line(&right_positions, right_index),
+            row: Some((*right_row).clone()),
+            entries: Vec::new(),
+        });
+    }
+
+    Ok(diff)
+}
+
+/// The rows of a table with the JSON text of their key
+fn rows<'a>(document: &'a Value, key: &str, side: &str) -> Result<Vec<(String, &'a Value)>, JsonDiffError> {
+    let Value::Array(rows) = document else {
+        return Err(JsonDiffError::InvalidTable(format!("the {} document is not an array of rows", side)));
+    };
+    rows.iter()
+        .enumerate()
+        .map(|(index, row)| match row.get(key) {
+            Some(id) => Ok((id.to_string(), row)),
+            None => Err(JsonDiffError::InvalidTable(format!("row {} of the {} document has no \"{}\" key", index, side, key))),
+        })
+        .collect()
+}
+
+fn duplicate(id: &str, side: &str, first: usize, second: usize) -> JsonDiffError {
+    JsonDiffError::InvalidTable(format!("key {} is held by rows {} and {} of the {} document", id, first, second, side))
+}
+
+impl RowDiff {
+    /// `key=value`, as rows are named in the text report
+    pub fn label(&self, key: &str) -> String {
+        format!("{}={}", key, format_value(&self.key, false))
+    }
+
+    /// The row as a line of the text report, e.g. `row id=42 (L12:L14): price 10.99 -> 12.99; stock 5 -> 3`
+    pub fn format_with_options(&self, key: &str, options: &ValueRenderOptions) -> String {
+        let lines = match (self.left_line, self.right_line) {
+            (Some(left), Some(right)) => format!(" (L{}:L{})", left, right),
+            (Some(line), None) | (None, Some(line)) => format!(" (L{})", line),
+            (None, None) => String::new(),
+        };
+        let cells = match self.status {
+            RowStatus::Added => "ADDED".to_string(),
+            RowStatus::Removed => "REMOVED".to_string(),
+            RowStatus::Modified => {
+                let cells: Vec<String> = self.entries.iter()
+                    .filter(|entry| entry.diff_type.is_difference())
+                    .map(|entry| cell_change(entry, options))
+                    .collect();
+                cells.join("; ")
+            }
+        };
+        format!("row {}{}: {}", self.label(key), lines, cells)
+    }
+}
+
+/// A changed cell: its path within the row and how it changed
+fn cell_change(entry: &DiffEntry, options: &ValueRenderOptions) -> String {
+    let cell = entry.path.strip_prefix("$.").or_else(|| entry.path.strip_prefix('$')).filter(|cell| !cell.is_empty()).unwrap_or("$");
+    let value = |value: &Option<Value>| value.as_ref().map_or(ABSENT.to_string(), |value| options.render(value));
+    match entry.diff_type {
+        DiffType::ArrayReordered => format!("{} REORDERED", cell),
+        DiffType::Moved => format!("{} MOVED → [{}]", cell, entry.moved_to.map_or(String::new(), |index| index.to_string())),
+        _ => format!("{} {} -> {}", cell, value(&entry.old_value), value(&entry.new_value)),
+    }
+}
+
+impl TableDiff {
+    /// The text report: a line per row that differs, then the row counts
+    pub fn format_with_options(&self, options: &ValueRenderOptions) -> String {
+        let mut text = format!("TABLE KEY: {}\n\n", self.key);
+        for row in &self.rows {
+            text.push_str(&row.format_with_options(&self.key, options));
+            text.push('\n');
+        }
+        if !self.rows.is_empty() {
+            text.push('\n');
+        }
+        let summary = &self.summary;
+        text.push_str(&format!(
+            "SUMMARY\n  Left rows:  {}\n  Right rows: {}\n  Added:      {}\n  Removed:    {}\n  Modified:   {}\n  Unchanged:  {}",
+            summary.left_rows, summary.right_rows, summary.added, summary.removed, summary.modified, summary.unchanged
+        ));
+        text
+    }
+}
+
+impl fmt::Display for TableDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format_with_options(&ValueRenderOptions::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::path::JsonPath;
+
+    /// Ten products, one per line after the opening bracket
+    fn products() -> Vec<Value> {
+        (1..=10).map(|id| json!({"id": id, "name": format!("item {}", id), "price": 10.0 + id as f64, "stock": id})).collect()
+    }
+
+    fn table(rows: &[Value]) -> String {
+        let lines: Vec<String> = rows.iter().map(|row| format!("  {}", row)).collect();
+        format!("[\n{}\n]", lines.join(",\n"))
+    }
+
+    #[test]
+    fn test_compare_tables() {
+        let left = products();
+        let mut right = products();
+        right.remove(6);
+        right[1]["price"] = json!(12.99);
+        right[1]["stock"] = json!(0);
+        right[3]["name"] = json!("renamed");
+        right.push(json!({"id": 11, "name": "item 11", "price": 21.0, "stock": 11}));
+        // Row order does not matter
+        right.swap(0, 8);
+
+        let diff = compare_tables(&table(&left), &table(&right), "id", &CompareOptions::default()).unwrap();
+        assert_eq!(diff.summary, TableSummary { left_rows: 10, right_rows: 10, added: 1, removed: 1, modified: 2, unchanged: 7 });
+
+        let statuses: Vec<(Value, RowStatus)> = diff.rows.iter().map(|row| (row.key.clone(), row.status)).collect();
+        assert_eq!(statuses, [
+            (json!(2), RowStatus::Modified),
+            (json!(4), RowStatus::Modified),
+            (json!(7), RowStatus::Removed),
+            (json!(11), RowStatus::Added),
+        ]);
+        assert_eq!(diff.rows[0].entries.len(), 2);
+        assert_eq!(diff.rows[0].entries[0].path, "$.price");
+        assert_eq!((diff.rows[0].entries[0].left_line, diff.rows[0].entries[0].right_line), (Some(3), Some(3)));
+        assert_eq!(diff.rows[2].row.as_ref().unwrap()["name"], "item 7");
+
+        assert_eq!(diff.to_string(), "TABLE KEY: id\n\n\
+            row id=2 (L3:L3): price 12.0 -> 12.99; stock 2 -> 0\n\
+            row id=4 (L5:L5): name \"item 4\" -> \"renamed\"\n\
+            row id=7 (L8): REMOVED\n\
+            row id=11 (L11): ADDED\n\n\
+            SUMMARY\n  Left rows:  10\n  Right rows: 10\n  Added:      1\n  Removed:    1\n  Modified:   2\n  Unchanged:  7");
+
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(json["rows"][0]["key"], 2);
+        assert_eq!(json["rows"][0]["status"], "modified");
+        assert_eq!(json["rows"][0]["entries"][1]["path"], "$.stock");
+        assert_eq!(json["rows"][3]["row"]["id"], 11);
+        assert!(json["rows"][3].get("entries").is_none());
+    }
+
+    #[test]
+    fn test_table_rules_apply_to_rows() {
+        let left = table(&[json!({"id": "a", "seen": 1, "n": 1}), json!({"id": "b", "seen": 1, "n": 2})]);
+        let right = table(&[json!({"id": "a", "seen": 2, "n": 1}), json!({"id": "b", "seen": 2, "n": 3})]);
+        let options = CompareOptions { ignore_paths: vec![JsonPath::new("$.seen").unwrap()], ..CompareOptions::default() };
+
+        let diff = compare_tables(&left, &right, "id", &options).unwrap();
+        assert_eq!((diff.summary.modified, diff.summary.unchanged), (1, 1));
+        // The ignored cell is kept as an entry but not shown as a change
+        assert_eq!(diff.rows[0].format_with_options("id", &ValueRenderOptions::default()), "row id=\"b\" (L3:L3): n 2 -> 3");
+    }
+
+    #[test]
+    fn test_malformed_tables() {
+        let error = |left: &str, right: &str| compare_tables(left, right, "id", &CompareOptions::default()).unwrap_err().to_string();
+        assert_eq!(error("{}", "[]"), "Invalid table: the left document is not an array of rows");
+        assert_eq!(error("[]", r#"[{"id": 1}, {"name": 2}]"#), "Invalid table: row 1 of the right document has no \"id\" key");
+        assert_eq!(error(r#"[{"id": 1}, {"id": 2}, {"id": 1}]"#, "[]"), "Invalid table: key 1 is held by rows 0 and 2 of the left document");
+        assert!(error("[", "[]").starts_with("Failed to parse JSON: left:"));
+    }
+}
@@ -0,0 +1,177 @@
+//! Line diff of two texts, reported when either is not valid JSON and
+//! [`CompareOptions::text_fallback`](crate::CompareOptions::text_fallback) is set
+//!
+//! Lines are matched by their longest common subsequence, once the lines both texts
+//! start and end with are set aside. In each run of changed lines the removed and added
+//! lines are paired in order as modifications, and those left over are removed or
+//! added. An entry's path is `<line N>`, N being its line on the left, or on the right
+//! for an added line; its values are the lines as strings.
+
+use serde_json::Value;
+use crate::DiffEntry;
+
+/// Most cells the table of common subsequence lengths may have; past it the lines
+/// between the common start and end are reported as one run of changes
+const MAX_TABLE_CELLS: usize = 16_000_000;
+
+/// Path of the entry of line `line`, counted from 1
+pub(crate) fn line_path(line: usize) -> String {
+    format!("<line {}>", line)
+}
+
+/// Whether `path` is one of a line diff's, see [`line_path`]
+pub(crate) fn is_line_path(path: &str) -> bool {
+    path.strip_prefix("<line ")
+        .and_then(|rest| rest.strip_suffix('>'))
+        .is_some_and(|line| line.parse::<usize>().is_ok_and(|line| line > 0))
+}
+
+/// A step of the edit script turning the left lines into the right ones
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    Keep,
+    Remove,
+    Add,
+}
+
+/// The entries of the lines that differ between `left` and `right`, in order
+pub(crate) fn line_entries(left: &str, right: &str) -> Vec<DiffEntry> {
+    let (left, right): (Vec<&str>, Vec<&str>) = (left.lines().collect(), right.lines().collect());
+    let prefix = left.iter().zip(&right).take_while(|(l, r)| l == r).count();
+    let suffix = left[prefix..].iter().rev()
+        .zip(right[prefix..].iter().rev())
+        .take_while(|(l, r)| l == r)
+        .count();
+    let steps = edit_script(&left[prefix..left.len() - suffix], &right[prefix..right.len() - suffix]);
+
+    let mut entries = Vec::new();
+    // Lines before the current step on each side, and the lines of the current run
+    let (mut at_left, mut at_right) = (prefix, prefix);
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    for step in steps.into_iter().chain([Step::Keep]) {
+        match step {
+            Step::Remove => { removed.push(at_left); at_left += 1; }
+            Step::Add => { added.push(at_right); at_right += 1; }
+            Step::Keep => {
+                push_run(&mut entries, (&left, &right), (&removed, &added), (at_left - removed.len(), at_right - added.len()));
+                (removed, added) = (Vec::new(), Vec::new());
+                (at_left, at_right) = (at_left + 1, at_right + 1);
+            }
+        }
+    }
+    entries
+}
+
+/// The entries of one run of changed lines, which starts after `before` lines of each side
+fn push_run(
+    entries: &mut Vec<DiffEntry>,
+    (left, right): (&[&str], &[&str]),
+    (removed, added): (&[usize], &[usize]),
+    before: (usize, usize),
+) {
+    // A line missing on one side is placed next to where the run is on it
+    let near = |before: usize, lines: usize| (lines > 0).then(|| before.clamp(1, lines));
+    let text = |line: &str| Value::String(line.to_string());
+
+    for pair in 0..removed.len().max(added.len()) {
+        let entry = match (removed.get(pair), added.get(pair)) {
+            (Some(&l), Some(&r)) => DiffEntry::modified(line_path(l + 1), text(left[l]), text(right[r]))
+                .with_lines(Some(l + 1), Some(r + 1)),
+            (Some(&l), None) => DiffEntry::removed(line_path(l + 1), text(left[l]))
+                .with_lines(Some(l + 1), near(before.1 + added.len(), right.len())),
+            (None, Some(&r)) => DiffEntry::added(line_path(r + 1), text(right[r]))
+                .with_lines(near(before.0 + removed.len(), left.len()), Some(r + 1)),
+            (None, None) => unreachable!(),
+        };
+        entries.push(entry);
+    }
+}
+
+/// The steps turning `left` into `right` along their longest common subsequence
+fn edit_script(left: &[&str], right: &[&str]) -> Vec<Step> {
+    let (n, m) = (left.len(), right.len());
+    if n.saturating_mul(m) > MAX_TABLE_CELLS {
+        return [Step::Remove].repeat(n).into_iter().chain([Step::Add].repeat(m)).collect();
+    }
+
+    // Length of the common subsequence of left[i..] and right[j..], at i * (m + 1) + j
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i * (m + 1) + j] = if left[i] == right[j] {
+                lengths[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let mut steps = Vec::with_capacity(n + m);
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        let step = if i < n && j < m && left[i] == right[j] {
+            Step::Keep
+        } else if j == m || (i < n && lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1]) {
+            Step::Remove
+        } else {
+            Step::Add
+        };
+        i += usize::from(step != Step::Add);
+        j += usize::from(step != Step::Remove);
+        steps.push(step);
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DiffType;
+    use serde_json::json;
+
+    fn summary(left: &str, right: &str) -> Vec<(DiffType, String, Option<usize>, Option<usize>)> {
+        line_entries(left, right).into_iter()
+            .map(|entry| (entry.diff_type, entry.path, entry.left_line, entry.right_line))
+            .collect()
+    }
+
+    #[test]
+    fn test_line_entries() {
+        let left = "{\n  \"name\": \"api\",\n  \"port\": 80\n  \"debug\": true\n}";
+        let right = "{\n  \"name\": \"api\",\n  \"port\": 8080\n  \"debug\": true,\n  \"tls\": true\n}";
+        let entries = line_entries(left, right);
+        assert_eq!(entries.len(), 3);
+        assert_eq!((entries[0].old_value.as_ref(), entries[0].new_value.as_ref()), (Some(&json!("  \"port\": 80")), Some(&json!("  \"port\": 8080"))));
+        assert_eq!(summary(left, right), [
+            (DiffType::Modified, "<line 3>".to_string(), Some(3), Some(3)),
+            (DiffType::Modified, "<line 4>".to_string(), Some(4), Some(4)),
+            (DiffType::Added, "<line 5>".to_string(), Some(4), Some(5)),
+        ]);
+
+        // Lines found on both sides are kept even when others move around them
+        assert_eq!(summary("a\nb\nc\nd", "a\nc\nd\ne"), [
+            (DiffType::Removed, "<line 2>".to_string(), Some(2), Some(1)),
+            (DiffType::Added, "<line 4>".to_string(), Some(4), Some(4)),
+        ]);
+        assert_eq!(summary("", "x\ny"), [
+            (DiffType::Added, "<line 1>".to_string(), None, Some(1)),
+            (DiffType::Added, "<line 2>".to_string(), None, Some(2)),
+        ]);
+        assert!(line_entries("same\ntext\n", "same\ntext").is_empty());
+        assert!(line_entries("a\nb", "a\nb\nc").iter().all(|entry| entry.validate().is_ok()));
+    }
+
+    #[test]
+    fn test_edit_script_past_the_table_limit() {
+        let (left, right) = (vec!["a"; 5_000], vec!["b"; 5_000]);
+        let steps = edit_script(&left, &right);
+        assert_eq!(steps.iter().filter(|&&step| step == Step::Remove).count(), 5_000);
+        assert_eq!(steps[4_999..5_001], [Step::Remove, Step::Add]);
+    }
+
+    #[test]
+    fn test_is_line_path() {
+        assert!(is_line_path(&line_path(42)));
+        assert!(!is_line_path("<line 0>") && !is_line_path("<line x>") && !is_line_path("$.line"));
+    }
+}
@@ -0,0 +1,357 @@
+use std::cmp::Ordering;
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use serde_json::{Number, Value};
+
+use crate::path::JsonPath;
+
+/// Which document(s) a preprocessing transform is applied to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransformSide {
+    #[default]
+    Both,
+    Left,
+    Right,
+}
+
+/// The operation performed by a preprocessing transform
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransformKind {
+    /// Remove the matching object keys or array elements
+    Remove,
+    /// Sort the matching arrays, by the value of a key for arrays of objects
+    SortArray { by_key: Option<String> },
+    /// Round numbers at (or beneath) the matching paths to the given decimals
+    Round { decimals: u32 },
+    /// Lowercase strings at (or beneath) the matching paths
+    Lowercase,
+    /// Lowercase the keys of objects at (or beneath) the matching paths
+    LowercaseKeys,
+}
+
+/// A transform applied to the documents before they are compared
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transform {
+    pub kind: TransformKind,
+    pub path: JsonPath,
+    pub side: TransformSide,
+}
+
+impl Transform {
+    /// Remove the values at matching paths
+    pub fn remove(path: JsonPath) -> Self {
+        Self::new(TransformKind::Remove, path)
+    }
+
+    /// Sort the arrays at matching paths, optionally by the value of `by_key`
+    pub fn sort_array(path: JsonPath, by_key: Option<&str>) -> Self {
+        Self::new(TransformKind::SortArray { by_key: by_key.map(|k| k.to_string()) }, path)
+    }
+
+    /// Round the numbers at matching paths
+    pub fn round(path: JsonPath, decimals: u32) -> Self {
+        Self::new(TransformKind::Round { decimals }, path)
+    }
+
+    /// Lowercase the strings at matching paths
+    pub fn lowercase(path: JsonPath) -> Self {
+        Self::new(TransformKind::Lowercase, path)
+    }
+
+    /// Lowercase the object keys at matching paths
+    pub fn lowercase_keys(path: JsonPath) -> Self {
+        Self::new(TransformKind::LowercaseKeys, path)
+    }
+
+    /// Restrict the transform to one side of the comparison
+    pub fn on(mut self, side: TransformSide) -> Self {
+        self.side = side;
+        self
+    }
+
+    fn new(kind: TransformKind, path: JsonPath) -> Self {
+        Self { kind, path, side: TransformSide::Both }
+    }
+
+    /// Whether the transform applies to the left or right document
+    pub fn applies_to(&self, is_left: bool) -> bool {
+        match self.side {
+            TransformSide::Both => true,
+            TransformSide::Left => is_left,
+            TransformSide::Right => !is_left,
+        }
+    }
+
+    /// Apply the transform in place and return the number of matched nodes
+    pub fn apply(&self, value: &mut Value) -> usize {
+        if self.kind == TransformKind::Remove {
+            return self.remove_matching(value, "$");
+        }
+        self.apply_at(value, "$")
+    }
+
+    fn apply_at(&self, value: &mut Value, path: &str) -> usize {
+        if self.path.matches(path) {
+            self.apply_to_node(value);
+            return 1;
+        }
+
+        match value {
+            Value::Object(obj) => obj.iter_mut()
+                .map(|(key, val)| self.apply_at(val, &format!("{}.{}", path, key)))
+                .sum(),
+            Value::Array(arr) => arr.iter_mut()
+                .enumerate()
+                .map(|(i, val)| self.apply_at(val, &format!("{}[{}]", path, i)))
+                .sum(),
+            _ => 0,
+        }
+    }
+
+    fn remove_matching(&self, value: &mut Value, path: &str) -> usize {
+        let mut matched = 0;
+
+        match value {
+            Value::Object(obj) => {
+                let doomed: Vec<String> = obj.keys()
+                    .filter(|key| self.path.matches(&format!("{}.{}", path, key)))
+                    .cloned()
+                    .collect();
+                matched += doomed.len();
+                for key in &doomed {
+                    obj.remove(key);
+                }
+
+                for (key, val) in obj.iter_mut() {
+                    matched += self.remove_matching(val, &format!("{}.{}", path, key));
+                }
+            }
+            Value::Array(arr) => {
+                // Indices refer to the original positions, so decide before removing anything
+                let keep: Vec<bool> = (0..arr.len())
+                    .map(|i| !self.path.matches(&format!("{}[{}]", path, i)))
+                    .collect();
+
+                for (i, val) in arr.iter_mut().enumerate() {
+                    if keep[i] {
+                        matched += self.remove_matching(val, &format!("{}[{}]", path, i));
+                    }
+                }
+
+                matched += keep.iter().filter(|k| !**k).count();
+                let mut flags = keep.iter();
+                arr.retain(|_| *flags.next().unwrap_or(&true));
+            }
+            _ => {}
+        }
+
+        matched
+    }
+
+    fn apply_to_node(&self, value: &mut Value) {
+        match &self.kind {
+            TransformKind::Remove => {}
+            TransformKind::SortArray { by_key } => {
+                if let Value::Array(arr) = value {
+                    arr.sort_by(|a, b| match by_key {
+                        Some(key) => compare_values(a.get(key).unwrap_or(&Value::Null), b.get(key).unwrap_or(&Value::Null)),
+                        None => compare_values(a, b),
+                    });
+                }
+            }
+            TransformKind::Round { decimals } => round_numbers(value, *decimals),
+            TransformKind::Lowercase => lowercase_strings(value),
+            TransformKind::LowercaseKeys => lowercase_keys(value),
+        }
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            TransformKind::Remove => write!(f, "remove({})", self.path),
+            TransformKind::SortArray { by_key: Some(key) } => write!(f, "sort_array({}, {})", self.path, key),
+            TransformKind::SortArray { by_key: None } => write!(f, "sort_array({})", self.path),
+            TransformKind::Round { decimals } => write!(f, "round({}, {})", self.path, decimals),
+            TransformKind::Lowercase => write!(f, "lowercase({})", self.path),
+            TransformKind::LowercaseKeys => write!(f, "lowercase_keys({})", self.path),
+        }
+    }
+}
+
+/// Apply all transforms for one side of the comparison, returning warnings for
+/// transforms that matched nothing
+pub(crate) fn apply_transforms(transforms: &[Transform], value: &mut Value, is_left: bool) -> Vec<String> {
+    let side = if is_left { "left" } else { "right" };
+
+    transforms.iter()
+        .filter(|t| t.applies_to(is_left))
+        .filter_map(|t| {
+            if t.apply(value) == 0 {
+                Some(format!("Preprocess transform {} matched nothing in the {} document", t, side))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Total ordering over JSON values used for sorting: null < bool < number < string < array < object
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    fn rank(v: &Value) -> u8 {
+        match v {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Number(_) => 2,
+            Value::String(_) => 3,
+            Value::Array(_) => 4,
+            Value::Object(_) => 5,
+        }
+    }
+
+    match (a, b) {
+        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+        (Value::Number(x), Value::Number(y)) => {
+            let x = x.as_f64().unwrap_or(0.0);
+            let y = y.as_f64().unwrap_or(0.0);
+            x.partial_cmp(&y).unwrap_or(Ordering::Equal)
+        }
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        _ if rank(a) == rank(b) => a.to_string().cmp(&b.to_string()),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn round_numbers(value: &mut Value, decimals: u32) {
+    match value {
+        Value::Number(n) if n.is_f64() => {
+            let factor = 10f64.powi(decimals as i32);
+            let rounded = (n.as_f64().unwrap_or(0.0) * factor).round() / factor;
+            if let Some(number) = Number::from_f64(rounded) {
+                *n = number;
+            }
+        }
+        Value::Object(obj) => obj.values_mut().for_each(|v| round_numbers(v, decimals)),
+        Value::Array(arr) => arr.iter_mut().for_each(|v| round_numbers(v, decimals)),
+        _ => {}
+    }
+}
+
+fn lowercase_strings(value: &mut Value) {
+    match value {
+        Value::String(s) => *s = s.to_lowercase(),
+        Value::Object(obj) => obj.values_mut().for_each(lowercase_strings),
+        Value::Array(arr) => arr.iter_mut().for_each(lowercase_strings),
+        _ => {}
+    }
+}
+
+fn lowercase_keys(value: &mut Value) {
+    match value {
+        Value::Object(obj) => {
+            *obj = std::mem::take(obj).into_iter()
+                .map(|(key, mut val)| {
+                    lowercase_keys(&mut val);
+                    (key.to_lowercase(), val)
+                })
+                .collect();
+        }
+        Value::Array(arr) => arr.iter_mut().for_each(lowercase_keys),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::{compare_json, CompareOptions};
+
+    #[test]
+    fn test_remove_transform() {
+        let mut value = json!({"wrapper": {"id": 1}, "name": "x", "items": [{"tmp": 1, "keep": 2}]});
+        let transform = Transform::remove(JsonPath::with_regex("$", r"^\$(\.wrapper|\.items\[\d+\]\.tmp)$").unwrap());
+
+        assert_eq!(transform.apply(&mut value), 2);
+        assert_eq!(value, json!({"name": "x", "items": [{"keep": 2}]}));
+    }
+
+    #[test]
+    fn test_remove_array_elements() {
+        let mut value = json!({"items": [1, 2, 3]});
+        let transform = Transform::remove(JsonPath::new("$.items[1]").unwrap());
+
+        assert_eq!(transform.apply(&mut value), 1);
+        assert_eq!(value, json!({"items": [1, 3]}));
+    }
+
+    #[test]
+    fn test_sort_array_transform() {
+        let mut value = json!({"users": [{"id": 3}, {"id": 1}, {"id": 2}], "tags": ["b", "c", "a"]});
+
+        Transform::sort_array(JsonPath::new("$.users").unwrap(), Some("id")).apply(&mut value);
+        Transform::sort_array(JsonPath::new("$.tags").unwrap(), None).apply(&mut value);
+
+        assert_eq!(value, json!({"users": [{"id": 1}, {"id": 2}, {"id": 3}], "tags": ["a", "b", "c"]}));
+    }
+
+    #[test]
+    fn test_round_transform() {
+        let mut value = json!({"price": 10.994, "nested": {"a": 1.2345, "b": 7}});
+
+        Transform::round(JsonPath::new("$.price").unwrap(), 2).apply(&mut value);
+        Transform::round(JsonPath::new("$.nested").unwrap(), 1).apply(&mut value);
+
+        assert_eq!(value, json!({"price": 10.99, "nested": {"a": 1.2, "b": 7}}));
+    }
+
+    #[test]
+    fn test_lowercase_transform() {
+        let mut value = json!({"email": "John@Example.COM", "name": "John"});
+
+        Transform::lowercase(JsonPath::new("$.email").unwrap()).apply(&mut value);
+
+        assert_eq!(value, json!({"email": "john@example.com", "name": "John"}));
+    }
+
+    #[test]
+    fn test_lowercase_keys_transform() {
+        let mut value = json!({"Headers": {"Content-Type": "X", "Set-Cookie": [{"Name": 1}]}});
+
+        Transform::lowercase_keys(JsonPath::new("$.Headers").unwrap()).apply(&mut value);
+
+        assert_eq!(value, json!({"Headers": {"content-type": "X", "set-cookie": [{"name": 1}]}}));
+    }
+
+    #[test]
+    fn test_preprocess_in_comparison() {
+        let left = json!({"email": "John@Example.com", "score": 1.2349});
+        let right = json!({"email": "john@example.com", "score": 1.2331});
+
+        let mut options = CompareOptions::default();
+        options.preprocess.push(Transform::lowercase(JsonPath::new("$.email").unwrap()).on(TransformSide::Left));
+        options.preprocess.push(Transform::round(JsonPath::new("$.score").unwrap(), 2));
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert!(result.entries.is_empty());
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_transform_matching_nothing_warns() {
+        let left = json!({"name": "John"});
+        let right = json!({"name": "John"});
+
+        let mut options = CompareOptions::default();
+        options.preprocess.push(Transform::remove(JsonPath::new("$.missing").unwrap()));
+        options.preprocess.push(Transform::lowercase(JsonPath::new("$.name").unwrap()).on(TransformSide::Right));
+
+        let result = compare_json(&left, &right, &options).unwrap();
+        assert_eq!(result.warnings.len(), 2);
+        assert!(result.warnings[0].contains("remove($.missing)"));
+        assert!(result.warnings[0].contains("left"));
+        assert!(result.warnings[1].contains("right"));
+    }
+}
@@ -0,0 +1,151 @@
+//! Entry lines of the text formats wrapped at a width, see [`ValueRenderOptions::width`]
+//!
+//! A line is broken at the last space before it overflows, or at the ` -> ` between an old
+//! and a new value when that is on the line, and the space it is broken at is left out.
+//! A run too long to break at a space is broken where it overflows. Lines are never broken
+//! inside an escape sequence, such as `\"`, `\u00e9` or `\u{1b}`, nor inside a character
+//! and its combining marks. Continuation lines are indented to the entry's value column,
+//! but by at most half the width. Widths are counted in terminal columns.
+//!
+//! [`ValueRenderOptions::width`]: crate::ValueRenderOptions::width
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// `line` wrapped at `width` columns, continuation lines indented by `indent` columns;
+/// `arrow` is the byte offset of the `->` to keep at a line break, if there is one
+pub(crate) fn wrap(line: &str, width: usize, indent: usize, arrow: Option<usize>) -> String {
+    if width == 0 || line.width() <= width {
+        return line.to_string();
+    }
+    let indent = indent.min(width / 2);
+    let units = units(line);
+    let offset = |index: usize| units.get(index).map_or(line.len(), |(offset, _)| *offset);
+
+    let mut wrapped = String::with_capacity(line.len() + 16);
+    let mut start = 0;
+    while start < units.len() {
+        let room = if start == 0 { width } else { width - indent };
+        // As many units as fit, and at least one
+        let mut end = start;
+        let mut used = 0;
+        while end < units.len() && (end == start || used + units[end].1.width() <= room) {
+            used += units[end].1.width();
+            end += 1;
+        }
+        if end < units.len() {
+            // After a space, which may be the unit overflowing
+            let mut breaks = (start + 1..=(end + 1).min(units.len() - 1)).filter(|&index| units[index - 1].1 == " " && units[index].1 != " ");
+            let at_arrow = breaks.clone().find(|&index| Some(units[index].0) == arrow);
+            if let Some(index) = at_arrow.or_else(|| breaks.next_back()) {
+                end = index;
+            }
+        }
+
+        if start > 0 {
+            wrapped.push('\n');
+            wrapped.push_str(&" ".repeat(indent));
+        }
+        let text = &line[offset(start)..offset(end)];
+        wrapped.push_str(if end < units.len() { text.trim_end_matches(' ') } else { text });
+        start = end;
+        // The space a line was broken at does not start the next one
+        while start < units.len() && start > 0 && units[start].1 == " " {
+            start += 1;
+        }
+    }
+    wrapped
+}
+
+/// The pieces of `line` a break cannot fall inside, with their byte offsets: escape
+/// sequences and grapheme clusters
+fn units(line: &str) -> Vec<(usize, &str)> {
+    let mut units = Vec::new();
+    let mut graphemes = line.grapheme_indices(true).peekable();
+    while let Some((offset, grapheme)) = graphemes.next() {
+        if grapheme != "\\" {
+            units.push((offset, grapheme));
+            continue;
+        }
+        let Some((_, escaped)) = graphemes.next() else {
+            units.push((offset, grapheme));
+            break;
+        };
+        let mut end = offset + grapheme.len() + escaped.len();
+        if escaped == "u" {
+            let rest = &line[end..];
+            let braced = rest.strip_prefix('{').and_then(|inner| inner.find('}')).map(|close| close + 2);
+            let hex = (rest.len() >= 4 && rest.as_bytes()[..4].iter().all(u8::is_ascii_hexdigit)).then_some(4);
+            let len = braced.or(hex).unwrap_or(0);
+            while graphemes.peek().is_some_and(|(next, _)| *next < end + len) {
+                graphemes.next();
+            }
+            end += len;
+        }
+        units.push((offset, &line[offset..end]));
+    }
+    units
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_at_spaces_and_the_arrow() {
+        let line = "[MODIFIED] $.title (L3:L3): \"a short title\" -> \"a much longer title than before\"";
+        assert_eq!(wrap(line, 0, 28, None), line);
+        assert_eq!(wrap(line, line.len(), 28, None), line);
+
+        let arrow = line.find("->");
+        assert_eq!(wrap(line, 70, 28, arrow), "[MODIFIED] $.title (L3:L3): \"a short title\"\n                            -> \"a much longer title than before\"");
+        assert_eq!(wrap(line, 60, 28, arrow), "[MODIFIED] $.title (L3:L3): \"a short title\"\n                            -> \"a much longer title than\n                            before\"");
+        // Without an arrow to keep, the line is filled
+        assert_eq!(wrap(line, 60, 28, None), "[MODIFIED] $.title (L3:L3): \"a short title\" -> \"a much\n                            longer title than before\"");
+        // An arrow past the first line is kept at a break on a later one
+        let wrapped = wrap(line, 40, 10, arrow);
+        assert_eq!(wrapped, "[MODIFIED] $.title (L3:L3): \"a short\n          title\"\n          -> \"a much longer title than\n          before\"");
+        assert!(wrapped.lines().all(|line| line.width() <= 40));
+    }
+
+    #[test]
+    fn test_continuation_indent() {
+        let line = "[ADDED] $.tags[0] (L7): \"one two three four five six\"";
+        assert_eq!(wrap(line, 50, 24, None), "[ADDED] $.tags[0] (L7): \"one two three four five\n                        six\"");
+        // A value column past half the width indents by half of it
+        assert_eq!(wrap(line, 40, 24, None), "[ADDED] $.tags[0] (L7): \"one two three\n                    four five six\"");
+        assert_eq!(wrap(line, 30, 24, None), "[ADDED] $.tags[0] (L7): \"one\n               two three four\n               five six\"");
+        assert_eq!(wrap("ab cd ", 3, 0, None), "ab\ncd ");
+    }
+
+    #[test]
+    fn test_wrap_keeps_escapes_and_characters_whole() {
+        // Runs without spaces are broken around escapes, wherever they overflow
+        let line = r#"[ADDED] $.s: "ab\"cd\u00e9\u{1b}\\ef""#;
+        assert_eq!(wrap(line, 12, 0, None), "[ADDED] $.s:\n\"ab\\\"cd\n\\u00e9\\u{1b}\n\\\\ef\"");
+        assert_eq!(wrap(line, 15, 0, None), "[ADDED] $.s:\n\"ab\\\"cd\\u00e9\n\\u{1b}\\\\ef\"");
+        for width in 8..line.len() {
+            let wrapped = wrap(line, width, 0, None);
+            assert_eq!(wrapped.replace(['\n', ' '], ""), line.replace(' ', ""), "{}", width);
+            for escape in [r#"\""#, r"\u00e9", r"\u{1b}", r"\\"] {
+                assert!(wrapped.lines().any(|line| line.contains(escape)), "{:?} split in {:?}", escape, wrapped);
+            }
+        }
+
+        // Wide characters take two columns
+        let line = "[ADDED] $.名前: \"東京都渋谷区神南一丁目\"";
+        let wrapped = wrap(line, 24, 16, None);
+        assert_eq!(wrapped, "[ADDED] $.名前:\n            \"東京都渋谷\n            区神南一丁目\n            \"");
+        assert!(wrapped.lines().all(|line| line.width() <= 24));
+        // Combining marks stay with their letter
+        assert_eq!(wrap("x: \"cafe\u{301}cafe\u{301}\"", 5, 0, None), "x:\n\"cafe\u{301}\ncafe\u{301}\"");
+    }
+
+    #[test]
+    fn test_units() {
+        let line = r#"a\"b\u00e9\u{1b}\"#;
+        let units: Vec<&str> = units(line).into_iter().map(|(_, unit)| unit).collect();
+        assert_eq!(units, ["a", r#"\""#, "b", r"\u00e9", r"\u{1b}", r"\"]);
+        assert_eq!(units.concat(), line);
+    }
+}
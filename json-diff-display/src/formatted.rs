@@ -0,0 +1,159 @@
+//! Panes of the split view showing their file pretty-printed, for minified files whose
+//! few lines are too long to show and would put every entry on line 1
+//!
+//! The formatted text is the document as [`format_canonical`] writes it, indented by two
+//! spaces and numbered on its own. Entries are placed on it by their path on the pane's
+//! side, looked up in the line map of the formatted text; one whose path is not there,
+//! e.g. relative to a root, is placed by the offset of its span in the original, when
+//! spans were recorded. An entry missing on the side is placed on its parent's line.
+
+use std::collections::HashMap;
+use serde_json::Value;
+use json_diff_core::line_map::{self, LineMap};
+use json_diff_core::{format_canonical, parent_path, CanonicalOptions, DiffEntry, DiffResult};
+
+use crate::side_by_side::is_insertion_anchor;
+
+/// Most lines a file can have to be shown formatted
+pub(crate) const MAX_LINES: usize = 5;
+
+/// Length in bytes past which a line is too long to show
+pub(crate) const MAX_LINE_BYTES: usize = 10_000;
+
+/// Whether a file of these lines is shown formatted: a handful of lines, one too long
+pub(crate) fn needs_formatting(lines: &[String]) -> bool {
+    lines.len() <= MAX_LINES && lines.iter().any(|line| line.len() > MAX_LINE_BYTES)
+}
+
+/// A file pretty-printed, with the lines of the result's entries in it
+#[derive(Debug)]
+pub(crate) struct FormattedView {
+    pub(crate) lines: Vec<String>,
+    /// Line of each entry in the formatted text, by index in the result
+    entry_lines: Vec<Option<usize>>,
+    /// First entry whose own line, rather than its insertion point, each line is
+    entries_by_line: HashMap<usize, usize>,
+}
+
+impl FormattedView {
+    /// The formatted view of `original`, the file on the left or right of `result`;
+    /// `None` if it is not JSON
+    pub(crate) fn new(original: &str, result: &DiffResult, is_left: bool) -> Option<Self> {
+        let value: Value = serde_json::from_str(original).ok()?;
+        let text = format_canonical(&value, &CanonicalOptions { sort_keys: false, indent: Some(2) });
+        let formatted = line_map::build(&text).ok()?;
+        // Only needed for entries whose path is not found, so built for the first of them
+        let mut original_map: Option<Option<LineMap>> = None;
+
+        let entry_lines: Vec<Option<usize>> = result.iter()
+            .map(|entry| {
+                let path = side_path(entry, is_left);
+                let path = if is_insertion_anchor(entry, is_left) { parent_path(path) } else { path };
+                formatted.line_of(path).or_else(|| {
+                    let offset = if is_left { entry.left_span } else { entry.right_span }?.byte_start;
+                    let original_map = original_map.get_or_insert_with(|| line_map::build(original).ok()).as_ref()?;
+                    line_at_offset(original_map, &formatted, offset)
+                })
+            })
+            .collect();
+
+        let mut entries_by_line = HashMap::new();
+        for (index, (entry, line)) in result.iter().zip(&entry_lines).enumerate() {
+            if let Some(line) = line.filter(|_| !is_insertion_anchor(entry, is_left)) {
+                entries_by_line.entry(line).or_insert(index);
+            }
+        }
+
+        Some(Self { lines: text.lines().map(str::to_string).collect(), entry_lines, entries_by_line })
+    }
+
+    /// Line in the formatted text of the entry at `index` of the result
+    pub(crate) fn line_of(&self, index: usize) -> Option<usize> {
+        self.entry_lines.get(index).copied().flatten()
+    }
+
+    /// Index of the entry whose own line is `line`, see [`crate::side_by_side::entry_on_line`]
+    pub(crate) fn entry_on_line(&self, line: usize) -> Option<usize> {
+        self.entries_by_line.get(&line).copied()
+    }
+}
+
+/// Path of the entry's value on one side: the right one can be named differently
+pub(crate) fn side_path(entry: &DiffEntry, is_left: bool) -> &str {
+    match is_left {
+        true => &entry.path,
+        false => entry.right_path.as_deref().or(entry.mapped_to.as_deref()).unwrap_or(&entry.path),
+    }
+}
+
+/// Line in the formatted text of the innermost node holding byte `offset` of the original
+pub(crate) fn line_at_offset(original: &LineMap, formatted: &LineMap, offset: usize) -> Option<usize> {
+    original.paths()
+        .into_iter()
+        .filter_map(|path| Some((path, original.span_of(path)?)))
+        .filter(|(_, span)| (span.byte_start..span.byte_end).contains(&offset))
+        .min_by_key(|(_, span)| span.byte_end - span.byte_start)
+        .and_then(|(path, _)| formatted.line_of(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use json_diff_core::{compare_strings, CompareOptions};
+
+    const LEFT: &str = r#"{"name":"api","items":[{"id":1,"price":10},{"id":2,"price":20}],"tags":["a"]}"#;
+    const RIGHT: &str = r#"{"name":"api","items":[{"id":1,"price":11},{"id":2,"price":20}],"tags":["a","b"]}"#;
+
+    #[test]
+    fn test_needs_formatting() {
+        let long = "x".repeat(MAX_LINE_BYTES + 1);
+        assert!(needs_formatting(std::slice::from_ref(&long)));
+        assert!(!needs_formatting(&["{}".to_string()]));
+        assert!(!needs_formatting(&vec![long; MAX_LINES + 1]));
+    }
+
+    #[test]
+    fn test_entries_are_placed_on_formatted_lines() {
+        let options = CompareOptions { record_spans: true, ..CompareOptions::default() };
+        let result = compare_strings(LEFT, RIGHT, &options).unwrap();
+        let (left, right) = (FormattedView::new(LEFT, &result, true).unwrap(), FormattedView::new(RIGHT, &result, false).unwrap());
+        assert_eq!(left.lines[0], "{");
+        assert!(left.lines.len() > 10);
+
+        let index = |path: &str| result.iter().position(|entry| entry.path == path).unwrap();
+        let price = index("$.items[0].price");
+        assert_eq!(left.lines[left.line_of(price).unwrap() - 1].trim(), "\"price\": 10");
+        assert_eq!(right.lines[right.line_of(price).unwrap() - 1].trim(), "\"price\": 11");
+        assert_eq!(left.entry_on_line(left.line_of(price).unwrap()), Some(price));
+
+        // The added tag is on its own line on the right, and at its array's on the left
+        let tag = index("$.tags[1]");
+        assert_eq!(right.lines[right.line_of(tag).unwrap() - 1].trim(), "\"b\"");
+        assert_eq!(left.lines[left.line_of(tag).unwrap() - 1].trim(), "\"tags\": [");
+        assert_eq!(left.entry_on_line(left.line_of(tag).unwrap()), None);
+    }
+
+    #[test]
+    fn test_offsets_translate_to_formatted_lines() {
+        let original = line_map::build(LEFT).unwrap();
+        let value: Value = serde_json::from_str(LEFT).unwrap();
+        let text = format_canonical(&value, &CanonicalOptions { sort_keys: false, indent: Some(2) });
+        let formatted = line_map::build(&text).unwrap();
+        let line = |offset| line_at_offset(&original, &formatted, offset).map(|line| text.lines().nth(line - 1).unwrap().trim());
+
+        // The innermost node holding the offset, whichever of its characters it is
+        assert_eq!(line(LEFT.find("10").unwrap()), Some("\"price\": 10"));
+        assert_eq!(line(LEFT.find("\"price\"").unwrap() + 2), Some("\"price\": 10"));
+        assert_eq!(line(LEFT.find("\"a\"").unwrap()), Some("\"a\""));
+        assert_eq!(line(0), Some("{"));
+        assert_eq!(line(LEFT.len()), None);
+
+        // A path that cannot be found in the formatted text falls back to the span
+        let options = CompareOptions { record_spans: true, ..CompareOptions::default() };
+        let mut result = compare_strings(LEFT, RIGHT, &options).unwrap();
+        let price = result.iter().position(|entry| entry.path == "$.items[0].price").unwrap();
+        result.entries[price].path = "$.renamed".to_string();
+        let view = FormattedView::new(LEFT, &result, true).unwrap();
+        assert_eq!(view.lines[view.line_of(price).unwrap() - 1].trim(), "\"price\": 10");
+    }
+}
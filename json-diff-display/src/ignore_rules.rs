@@ -0,0 +1,164 @@
+//! Ignore rules generated from the entries of the display and written to a profile
+
+use std::path::Path;
+use anyhow::{bail, Context, Result};
+use toml_edit::{Array, Document, Item, Value};
+
+/// Ignore rules covering `path`: the path itself, then, if it holds array indices or
+/// elements named by key such as `[id=P1]`, the path with each of them replaced by `[*]`
+pub fn rule_candidates(path: &str) -> Vec<String> {
+    let generalized = generalize_indices(path);
+    if generalized == path {
+        vec![path.to_string()]
+    } else {
+        vec![path.to_string(), generalized]
+    }
+}
+
+/// Replace the array indices and keyed elements of a path by `[*]`, leaving quoted keys
+/// such as `["a[1]"]` alone
+fn generalize_indices(path: &str) -> String {
+    let mut result = String::with_capacity(path.len());
+    let mut rest = path;
+
+    while let Some(start) = rest.find('[') {
+        result.push_str(&rest[..start]);
+        let bracket = &rest[start..];
+
+        if bracket.starts_with("[\"") {
+            // Copy the quoted key up to its unescaped closing quote
+            let mut end = 2;
+            let bytes = bracket.as_bytes();
+            while end < bytes.len() && bytes[end] != b'"' {
+                end += if bytes[end] == b'\\' { 2 } else { 1 };
+            }
+            let end = (end + 2).min(bracket.len());
+            result.push_str(&bracket[..end]);
+            rest = &bracket[end..];
+            continue;
+        }
+        if let Some(end) = keyed_segment_len(bracket) {
+            result.push_str("[*]");
+            rest = &bracket[end..];
+            continue;
+        }
+
+        match bracket.find(']') {
+            Some(close) if close > 1 && bracket[1..close].bytes().all(|b| b.is_ascii_digit()) => {
+                result.push_str("[*]");
+                rest = &bracket[close + 1..];
+            }
+            _ => {
+                result.push('[');
+                rest = &bracket[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// Length of the segment naming an element by key at the start of `bracket`, such as
+/// `[id=P1]` or `[id="a]b"]`
+fn keyed_segment_len(bracket: &str) -> Option<usize> {
+    let equals = bracket.find('=')?;
+    let key = &bracket[1..equals];
+    if !key.starts_with(|c: char| c.is_alphabetic() || c == '_') || key.contains(['.', '[', ']', '(', '"']) {
+        return None;
+    }
+
+    let value = &bracket[equals + 1..];
+    let close = if value.starts_with('"') {
+        // Up to the unescaped closing quote
+        let bytes = value.as_bytes();
+        let mut end = 1;
+        while end < bytes.len() && bytes[end] != b'"' {
+            end += if bytes[end] == b'\\' { 2 } else { 1 };
+        }
+        end + 1
+    } else {
+        value.find(']')?
+    };
+    value.get(close..)?.starts_with(']').then_some(equals + 1 + close + 1)
+}
+
+/// Add `rules` to the `ignore` list of the profile at `profile`, creating the file if
+/// it does not exist
+///
+/// The rest of the file, comments included, is kept as it is. Rules the list already
+/// holds are not added again. Returns the number of rules added.
+pub fn merge_ignore_rules(profile: &Path, rules: &[String]) -> Result<usize> {
+    let content = match std::fs::read_to_string(profile) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", profile.display())),
+    };
+    let mut document: Document = content.parse()
+        .with_context(|| format!("Failed to parse {}", profile.display()))?;
+
+    let ignore = document.entry("ignore").or_insert(Item::Value(Value::Array(Array::new())));
+    let Some(list) = ignore.as_array_mut() else {
+        bail!("`ignore` in {} is not an array", profile.display());
+    };
+
+    let mut added = 0;
+    for rule in rules {
+        if !list.iter().any(|existing| existing.as_str() == Some(rule.as_str())) {
+            list.push(rule.as_str());
+            added += 1;
+        }
+    }
+
+    std::fs::write(profile, document.to_string())
+        .with_context(|| format!("Failed to write {}", profile.display()))?;
+
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_candidates() {
+        assert_eq!(rule_candidates("$.user.name"), vec!["$.user.name"]);
+        assert_eq!(rule_candidates("$.items[3].tags[10]"), vec!["$.items[3].tags[10]", "$.items[*].tags[*]"]);
+        assert_eq!(rule_candidates("$[0]"), vec!["$[0]", "$[*]"]);
+        // Brackets in quoted keys are part of the key
+        assert_eq!(rule_candidates(r#"$["a[1]"][2]"#), vec![r#"$["a[1]"][2]"#, r#"$["a[1]"][*]"#]);
+        assert_eq!(rule_candidates(r#"$["say \"[1]\""]"#), vec![r#"$["say \"[1]\""]"#]);
+        // Elements named by key, whose quoted values may hold brackets
+        assert_eq!(rule_candidates("$.products[id=P1].price"), vec!["$.products[id=P1].price", "$.products[*].price"]);
+        assert_eq!(rule_candidates(r#"$.a[id="x[2]"].b[0]"#), vec![r#"$.a[id="x[2]"].b[0]"#, "$.a[*].b[*]"]);
+    }
+
+    #[test]
+    fn test_merge_ignore_rules() {
+        let dir = std::env::temp_dir().join(format!("json-diff-ignore-rules-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let profile = dir.join("rules.toml");
+
+        // A missing profile is created
+        assert_eq!(merge_ignore_rules(&profile, &["$.id".to_string()]).unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(&profile).unwrap().trim(), r#"ignore = ["$.id"]"#);
+
+        let existing = "# Rules for the nightly export\nignore = [\n    \"$.id\", # generated\n]\n\nshow_nested_differences = true\n";
+        std::fs::write(&profile, existing).unwrap();
+        let added = merge_ignore_rules(&profile, &["$.id".to_string(), "$.items[*].ts".to_string()]).unwrap();
+        assert_eq!(added, 1);
+
+        let content = std::fs::read_to_string(&profile).unwrap();
+        assert!(content.starts_with("# Rules for the nightly export\n"));
+        assert!(content.contains("# generated"));
+        assert!(content.contains("show_nested_differences = true"));
+        let parsed: toml_edit::Document = content.parse().unwrap();
+        let rules: Vec<_> = parsed["ignore"].as_array().unwrap().iter().filter_map(|v| v.as_str()).collect();
+        assert_eq!(rules, vec!["$.id", "$.items[*].ts"]);
+
+        std::fs::write(&profile, "ignore = \"$.id\"\n").unwrap();
+        assert!(merge_ignore_rules(&profile, &["$.x".to_string()]).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -4,12 +4,21 @@
 //! with vim-like keybindings for navigation using ratatui. The interface is designed
 //! for keyboard-only operation and does not support mouse interactions.
 
-use std::io;
+use std::cell::OnceCell;
+use std::io::{self, IsTerminal, Write};
+use std::panic::PanicHookInfo;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use anyhow::{Result, Context};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 use crossterm::{
+    cursor::Show,
     event::{self, Event, KeyCode},
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, SetTitle},
 };
 use ratatui::{
     backend::{Backend, CrosstermBackend},
@@ -19,7 +28,18 @@ use ratatui::{
     widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap, Clear},
     Frame, Terminal,
 };
-use json_diff_core::{DiffResult, DiffType};
+use json_diff_core::line_map::{self, LineMap};
+use json_diff_core::{escape_control, format_value, group_related, parent_path, read_file_limited, sort_by_delta, Annotation, DiffEntry, DiffResult, DiffType, JsonDiffError, OutputStyle, Query, TriageStatus, ValueRenderOptions};
+
+mod formatted;
+mod ignore_rules;
+mod short_path;
+mod side_by_side;
+
+use formatted::{side_path, FormattedView};
+use short_path::shorten;
+use side_by_side::{entry_on_line, is_insertion_anchor, line_of};
+pub use side_by_side::{format_side_by_side, SideBySideOptions};
 
 /// Display mode for the application
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -30,90 +50,627 @@ pub enum ViewMode {
     SplitScreen,
 }
 
+/// Order in which the entries are listed and navigated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortMode {
+    /// By line number, in the order the entries appear in the files
+    Line,
+    /// Alphabetically by path
+    Path,
+    /// By diff type, then by line number
+    Type,
+    /// By line number, with entries of the same parent kept together: removed, added,
+    /// then modified
+    Related,
+    /// Largest numeric change first, entries without one after in line order
+    Delta,
+}
+
+impl SortMode {
+    /// The mode after this one when cycling with `o`
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Line => SortMode::Path,
+            SortMode::Path => SortMode::Type,
+            SortMode::Type => SortMode::Related,
+            SortMode::Related => SortMode::Delta,
+            SortMode::Delta => SortMode::Line,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Line => "line",
+            SortMode::Path => "path",
+            SortMode::Type => "type",
+            SortMode::Related => "related",
+            SortMode::Delta => "delta",
+        }
+    }
+}
+
+/// Text of the jump-to-path prompt
+#[derive(Debug, Default, PartialEq)]
+struct PathInput {
+    /// What was typed
+    prefix: String,
+    /// Position among the paths starting with `prefix` of the one Tab completed to
+    completion: Option<usize>,
+}
+
+impl PathInput {
+    /// The text shown and jumped to: the completed path, or what was typed
+    fn text<'a>(&'a self, paths: &'a [String]) -> &'a str {
+        self.completion
+            .and_then(|completion| matching_paths(paths, &self.prefix).get(completion))
+            .map_or(&self.prefix, |path| path.as_str())
+    }
+
+    /// Take the shown text as typed, ending the completion
+    fn accept_completion(&mut self, paths: &[String]) {
+        if self.completion.is_some() {
+            self.prefix = self.text(paths).to_string();
+            self.completion = None;
+        }
+    }
+
+    fn push(&mut self, c: char, paths: &[String]) {
+        self.accept_completion(paths);
+        self.prefix.push(c);
+    }
+
+    fn pop(&mut self, paths: &[String]) {
+        self.accept_completion(paths);
+        pop_grapheme(&mut self.prefix);
+    }
+}
+
+/// Remove the last character of `text` as it is shown, with its combining marks
+fn pop_grapheme(text: &mut String) {
+    if let Some((start, _)) = text.grapheme_indices(true).next_back() {
+        text.truncate(start);
+    }
+}
+
+/// The paths of a sorted list that start with `prefix`
+fn matching_paths<'a>(paths: &'a [String], prefix: &str) -> &'a [String] {
+    let start = paths.partition_point(|path| path.as_str() < prefix);
+    let len = paths[start..].iter().take_while(|path| path.starts_with(prefix)).count();
+    &paths[start..start + len]
+}
+
+/// Smallest terminal the UI is drawn in
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 10;
+
+/// Narrowest terminal in which the split view shows the files next to each other
+const SIDE_BY_SIDE_MIN_WIDTH: u16 = 80;
+
+/// Share of the split view's width or height given to the left pane, in percent: the
+/// default, the step `<` and `>` move it by and its bounds
+const DEFAULT_SPLIT_PERCENT: u16 = 50;
+const SPLIT_STEP: u16 = 5;
+const MIN_SPLIT_PERCENT: u16 = 20;
+const MAX_SPLIT_PERCENT: u16 = 80;
+
+/// Ticks the split ratio stays in the status bar after it changes
+const SPLIT_MESSAGE_TICKS: u64 = 8;
+
+/// Characters of a value's preview in a row of the list; the details popup shows it in full
+const LIST_MAX_VALUE_CHARS: usize = 80;
+
+/// Columns of a row of the list kept for the lines and values when its path is shortened
+const LIST_MIN_REST_COLUMNS: usize = 24;
+
+/// Columns ←/→ scroll the list by while paths are shown in full
+const LIST_SCROLL_STEP: usize = 8;
+
+/// Default interval at which the app receives ticks for time-based state
+pub const DEFAULT_TICK_RATE: Duration = Duration::from_millis(250);
+
+/// Ticks a confirmation stays in the status bar, 5 seconds at the default tick rate
+const MESSAGE_TICKS: u64 = 20;
+
+/// A pane of the split view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    Left,
+    Right,
+}
+
+/// How the UI fits in a terminal of a given size
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScreenLayout {
+    /// Too small for any view; only a message is drawn
+    TooSmall,
+    /// The split view's panes are stacked vertically
+    Stacked,
+    /// Everything is drawn as designed
+    Full,
+}
+
+fn screen_layout(width: u16, height: u16) -> ScreenLayout {
+    if width < MIN_WIDTH || height < MIN_HEIGHT {
+        ScreenLayout::TooSmall
+    } else if width < SIDE_BY_SIDE_MIN_WIDTH {
+        ScreenLayout::Stacked
+    } else {
+        ScreenLayout::Full
+    }
+}
+
 /// App holds the state of the application
 pub struct App {
     diff_result: DiffResult,
+    // Position of the list selection in `visible_order`
     current_index: usize,
     quit: bool,
     help_visible: bool,
-    use_readable_format: bool,
+    output_style: OutputStyle,
     view_mode: ViewMode,
     // Split-screen specific state
     left_content: Vec<String>,
     right_content: Vec<String>,
     left_scroll: usize,
     right_scroll: usize,
-    current_diff_index: usize,
-    // Sorted diff indices for proper navigation order
-    sorted_diff_indices: Vec<usize>,
-    // Current position in the sorted diff list
+    // Share of the split given to the left pane, in percent
+    split_percent: u16,
+    // Pane that Tab moved the focus to, and whether it fills the split alone
+    focused_pane: Pane,
+    pane_maximized: bool,
+    // Indices of the entries that pass the type filter and the query, in sort order;
+    // every view navigates through this
+    visible_order: Vec<usize>,
+    sort_mode: SortMode,
+    hidden_types: Vec<DiffType>,
+    // Query the list is narrowed to, with its text
+    query: Option<(String, Query)>,
+    // Position of the focused diff in `visible_order`
     current_sorted_position: usize,
+    // Problem shown in the status bar, e.g. a file too large to load
+    status_message: Option<String>,
+    // Tick at which the status message is cleared; `None` keeps it
+    status_expires_at: Option<u64>,
+    // Ticks received so far
+    ticks: u64,
+    // Whether both files could be loaded into the panes
+    files_loaded: bool,
+    // Panes of minified files shown pretty-printed, see [`formatted`]; made on first use
+    left_formatted: OnceCell<Option<FormattedView>>,
+    right_formatted: OnceCell<Option<FormattedView>>,
+    // Line maps of the files, placing entries the result has no line for; made on first use
+    left_map: OnceCell<Option<LineMap>>,
+    right_map: OnceCell<Option<LineMap>>,
+    // Whether the pane missing the focused entry shows a row where it would be
+    phantom_rows: bool,
+    // Statistics popup state; its lines are assembled on first open
+    stats_visible: bool,
+    stats_scroll: u16,
+    stats_lines: OnceCell<Vec<Line<'static>>>,
+    // Note being typed for the current entry, while the note input is open
+    note_input: Option<String>,
+    // Path prefix being typed, while the jump-to-path prompt is open
+    path_input: Option<PathInput>,
+    // Query being typed, while the query prompt is open
+    query_input: Option<String>,
+    // Paths of all entries, sorted and without duplicates, completed against in the prompt
+    entry_paths: Vec<String>,
+    // Whether the details of the current entry are shown
+    details_visible: bool,
+    // Ignore rules offered for the current entry and the chosen one, while the popup is open
+    rule_choice: Option<(Vec<String>, usize)>,
+    // Ignore rules collected so far, written to the profile with `W`
+    pending_rules: Vec<String>,
+    profile_path: Option<PathBuf>,
+    // Whether the terminal title shows the entry counts while the display is open
+    window_title: bool,
+    // Whether the list shows paths in full rather than shortened to fit, and the columns
+    // its rows are scrolled sideways by then
+    full_paths: bool,
+    list_scroll: usize,
 }
 
 impl App {
     pub fn new(diff_result: DiffResult) -> Self {
-        // Load file contents for split-screen view
-        let left_content = Self::load_file_content(&diff_result.left_file);
-        let right_content = Self::load_file_content(&diff_result.right_file);
-
-        // Create sorted indices for proper diff navigation order
-        let sorted_diff_indices = Self::create_sorted_diff_indices(&diff_result);
+        Self::with_input_limit(diff_result, None)
+    }
 
-        Self {
+    /// Create the app, refusing to load files larger than `max_input_bytes` into the panes
+    pub fn with_input_limit(diff_result: DiffResult, max_input_bytes: Option<u64>) -> Self {
+        // Load file contents for split-screen view
+        let mut problems = Vec::new();
+        let left_content = Self::load_file_content(&diff_result.left_file, max_input_bytes, &mut problems);
+        let right_content = Self::load_file_content(&diff_result.right_file, max_input_bytes, &mut problems);
+        let files_loaded = left_content.is_ok() && right_content.is_ok();
+        let left_content = left_content.unwrap_or_else(|placeholder| vec![placeholder]);
+        let right_content = right_content.unwrap_or_else(|placeholder| vec![placeholder]);
+        let mut entry_paths: Vec<String> = diff_result.entries().iter().map(|entry| entry.path.clone()).collect();
+        entry_paths.sort_unstable();
+        entry_paths.dedup();
+
+        let mut app = Self {
             diff_result,
             current_index: 0,
             quit: false,
             help_visible: false,
-            use_readable_format: false,
+            output_style: OutputStyle::default(),
             view_mode: ViewMode::List,
             left_content,
             right_content,
             left_scroll: 0,
             right_scroll: 0,
-            current_diff_index: 0,
-            sorted_diff_indices,
+            split_percent: DEFAULT_SPLIT_PERCENT,
+            focused_pane: Pane::Left,
+            pane_maximized: false,
+            visible_order: Vec::new(),
+            sort_mode: SortMode::Line,
+            hidden_types: Vec::new(),
+            query: None,
             current_sorted_position: 0,
-        }
+            status_message: if problems.is_empty() { None } else { Some(problems.join("; ")) },
+            status_expires_at: None,
+            ticks: 0,
+            files_loaded,
+            left_formatted: OnceCell::new(),
+            right_formatted: OnceCell::new(),
+            left_map: OnceCell::new(),
+            right_map: OnceCell::new(),
+            phantom_rows: true,
+            stats_visible: false,
+            stats_scroll: 0,
+            stats_lines: OnceCell::new(),
+            note_input: None,
+            path_input: None,
+            query_input: None,
+            entry_paths,
+            details_visible: false,
+            rule_choice: None,
+            pending_rules: Vec::new(),
+            profile_path: None,
+            window_title: true,
+            full_paths: false,
+            list_scroll: 0,
+        };
+        app.update_visible_order();
+        app
     }
 
-    fn create_sorted_diff_indices(diff_result: &DiffResult) -> Vec<usize> {
-        let mut indices_with_lines: Vec<(usize, usize)> = diff_result.entries
-            .iter()
-            .enumerate()
-            .map(|(index, entry)| {
-                // Use the minimum line number (left or right) for sorting
-                // This ensures we navigate in the order they appear in the files
-                let line_num = match (entry.left_line, entry.right_line) {
-                    (Some(left), Some(right)) => left.min(right),
-                    (Some(left), None) => left,
-                    (None, Some(right)) => right,
-                    (None, None) => usize::MAX, // Put entries without line numbers at the end
-                };
-                (index, line_num)
-            })
+    fn create_visible_order(diff_result: &DiffResult, sort_mode: SortMode, hidden_types: &[DiffType], query: Option<&Query>) -> Vec<usize> {
+        let entries = diff_result.entries();
+        // Use the minimum line number (left or right) so entries are visited in the
+        // order they appear in the files; entries without line numbers go last
+        let line_of_entry = |index: usize| {
+            let entry = &entries[index];
+            match (entry.left_line, entry.right_line) {
+                (Some(left), Some(right)) => left.min(right),
+                (Some(left), None) => left,
+                (None, Some(right)) => right,
+                (None, None) => usize::MAX,
+            }
+        };
+        let type_rank = |index: usize| DiffType::ALL.iter().position(|t| *t == entries[index].diff_type);
+
+        let mut order: Vec<usize> = (0..entries.len())
+            .filter(|&index| !hidden_types.contains(&entries[index].diff_type))
+            .filter(|&index| query.is_none_or(|query| query.matches(&entries[index])))
             .collect();
 
-        // Sort by line number
-        indices_with_lines.sort_by_key(|(_, line_num)| *line_num);
+        // Stable sorts keep ties in line order
+        order.sort_by_key(|&index| line_of_entry(index));
+        match sort_mode {
+            SortMode::Line => {}
+            SortMode::Path => order.sort_by(|&a, &b| entries[a].path.cmp(&entries[b].path)),
+            SortMode::Type => order.sort_by_key(|&index| type_rank(index)),
+            SortMode::Related => group_related(&mut order, |&index| &entries[index]),
+            SortMode::Delta => sort_by_delta(&mut order, |&index| &entries[index]),
+        }
+
+        order
+    }
+
+    /// Recompute the visible order after the filter or sort mode changed, keeping
+    /// the selected and focused entries selected where they are still visible
+    fn update_visible_order(&mut self) {
+        let selected = self.selected_entry();
+        let focused = self.focused_diff();
 
-        // Extract just the indices
-        indices_with_lines.into_iter().map(|(index, _)| index).collect()
+        let query = self.query.as_ref().map(|(_, query)| query);
+        self.visible_order = Self::create_visible_order(&self.diff_result, self.sort_mode, &self.hidden_types, query);
+
+        let position_of = |entry: Option<usize>| {
+            entry.and_then(|entry| self.visible_order.iter().position(|&index| index == entry)).unwrap_or(0)
+        };
+        self.current_index = position_of(selected);
+        self.current_sorted_position = position_of(focused);
+    }
+
+    /// Index of the entry focused in the split view, if any entry is visible
+    pub fn focused_diff(&self) -> Option<usize> {
+        self.visible_order.get(self.current_sorted_position).copied()
+    }
+
+    /// Index of the entry selected in the list view, if any entry is visible
+    pub fn selected_entry(&self) -> Option<usize> {
+        self.visible_order.get(self.current_index).copied()
+    }
+
+    /// Index of the entry the annotation keys act on: the selected entry in the list
+    /// view, the focused diff in the split view
+    fn current_entry(&self) -> Option<usize> {
+        match self.view_mode {
+            ViewMode::List => self.selected_entry(),
+            ViewMode::SplitScreen => self.focused_diff(),
+        }
     }
 
-    fn load_file_content(file_path: &Option<std::path::PathBuf>) -> Vec<String> {
+    /// Move the current entry to the next triage status
+    pub fn cycle_triage_status(&mut self) {
+        if let Some(index) = self.current_entry() {
+            let entry = self.diff_result.entries[index].clone();
+            self.diff_result.annotations.cycle_status(&entry);
+        }
+    }
+
+    /// Start editing the note of the current entry
+    pub fn open_note_input(&mut self) {
+        if let Some(index) = self.current_entry() {
+            let note = self.annotation(&self.diff_result.entries[index]).and_then(|a| a.note.clone());
+            self.note_input = Some(note.unwrap_or_default());
+        }
+    }
+
+    fn save_note(&mut self) {
+        if let (Some(note), Some(index)) = (self.note_input.take(), self.current_entry()) {
+            let entry = self.diff_result.entries[index].clone();
+            self.diff_result.annotations.set_note(&entry, &note);
+        }
+    }
+
+    /// Open the prompt for a path to jump to
+    pub fn open_path_input(&mut self) {
+        self.path_input = Some(PathInput::default());
+    }
+
+    /// Complete the typed prefix to the next entry path starting with it
+    fn complete_path(&mut self) {
+        if let Some(input) = &mut self.path_input {
+            let matches = matching_paths(&self.entry_paths, &input.prefix);
+            if !matches.is_empty() {
+                let next = input.completion.map_or(0, |current| (current + 1) % matches.len());
+                input.completion = Some(next);
+            }
+        }
+    }
+
+    /// Select the first visible entry whose path starts with the prompt's text
+    fn jump_to_path(&mut self) {
+        let Some(input) = self.path_input.take() else { return };
+        let text = input.text(&self.entry_paths).to_string();
+        let entries = self.diff_result.entries();
+        match self.visible_order.iter().position(|&index| entries[index].path.starts_with(&text)) {
+            Some(position) => {
+                self.current_index = position;
+                self.current_sorted_position = position;
+                self.jump_to_current_diff();
+            }
+            None => self.show_message(format!("No entry path starts with {}", text), MESSAGE_TICKS),
+        }
+    }
+
+    /// Open the prompt for a query, holding the current one to edit
+    pub fn open_query_input(&mut self) {
+        self.query_input = Some(self.query.as_ref().map(|(text, _)| text.clone()).unwrap_or_default());
+    }
+
+    /// Narrow the list to the entries `query` selects, see [`Query`]; an empty query shows them all again
+    pub fn set_query(&mut self, query: &str) -> Result<(), JsonDiffError> {
+        self.query = if query.trim().is_empty() { None } else { Some((query.to_string(), Query::parse(query)?)) };
+        self.update_visible_order();
+        Ok(())
+    }
+
+    /// Apply the prompt's query, keeping the prompt open with the problem shown if it is malformed
+    fn apply_query(&mut self) {
+        let Some(text) = self.query_input.take() else { return };
+        if let Err(e) = self.set_query(&text) {
+            self.show_message(e.to_string(), MESSAGE_TICKS);
+            self.query_input = Some(text);
+        }
+    }
+
+    fn annotation(&self, entry: &DiffEntry) -> Option<&Annotation> {
+        self.diff_result.annotations.get(entry)
+    }
+
+    /// The diff result, including the annotations made in the display
+    pub fn into_result(self) -> DiffResult {
+        self.diff_result
+    }
+
+    /// The diff result with only the entries shown, in the order they are listed
+    pub fn view_result(&self) -> DiffResult {
+        let mut result = self.diff_result.clone();
+        result.entries = self.visible_order.iter().map(|&index| self.diff_result.entries[index].clone()).collect();
+        result
+    }
+
+    /// Indices of the entries shown, in the order they are listed and navigated
+    pub fn visible_order(&self) -> &[usize] {
+        &self.visible_order
+    }
+
+    pub fn set_sort_mode(&mut self, mode: SortMode) {
+        self.sort_mode = mode;
+        self.update_visible_order();
+    }
+
+    pub fn cycle_sort_mode(&mut self) {
+        self.sort_mode = self.sort_mode.next();
+        self.update_visible_order();
+    }
+
+    /// Hide entries of a type, or show them again if they are hidden
+    pub fn toggle_type_filter(&mut self, diff_type: DiffType) {
+        if let Some(position) = self.hidden_types.iter().position(|t| *t == diff_type) {
+            self.hidden_types.remove(position);
+        } else {
+            self.hidden_types.push(diff_type);
+        }
+        self.update_visible_order();
+    }
+
+    /// Lines of a file, or the placeholder shown instead if it could not be read
+    fn load_file_content(file_path: &Option<std::path::PathBuf>, max_input_bytes: Option<u64>, problems: &mut Vec<String>) -> Result<Vec<String>, String> {
         if let Some(path) = file_path {
-            if let Ok(content) = std::fs::read_to_string(path) {
-                return content.lines().map(|s| s.to_string()).collect();
+            match read_file_limited(path, max_input_bytes) {
+                Ok(content) => return Ok(content.lines().map(|s| s.to_string()).collect()),
+                Err(err @ JsonDiffError::InputTooLarge { .. }) => {
+                    problems.push(err.to_string());
+                    return Err("File is too large to display".to_string());
+                }
+                Err(_) => {}
+            }
+        }
+        Err("File not found or could not be read".to_string())
+    }
+
+    /// Dispatch a key press
+    pub fn handle_key(&mut self, code: KeyCode) {
+        // The note input takes every key while it is open
+        if let Some(note) = &mut self.note_input {
+            match code {
+                KeyCode::Char(c) => note.push(c),
+                KeyCode::Backspace => pop_grapheme(note),
+                KeyCode::Enter => self.save_note(),
+                KeyCode::Esc => self.note_input = None,
+                _ => {}
+            }
+            return;
+        }
+
+        // So does the jump-to-path prompt
+        if let Some(input) = &mut self.path_input {
+            match code {
+                KeyCode::Char(c) => input.push(c, &self.entry_paths),
+                KeyCode::Backspace => input.pop(&self.entry_paths),
+                KeyCode::Tab => self.complete_path(),
+                KeyCode::Enter => self.jump_to_path(),
+                KeyCode::Esc => self.path_input = None,
+                _ => {}
+            }
+            return;
+        }
+
+        // And the query prompt
+        if let Some(query) = &mut self.query_input {
+            match code {
+                KeyCode::Char(c) => query.push(c),
+                KeyCode::Backspace => pop_grapheme(query),
+                KeyCode::Enter => self.apply_query(),
+                KeyCode::Esc => self.query_input = None,
+                _ => {}
+            }
+            return;
+        }
+
+        // The statistics popup takes the navigation keys while it is open
+        if self.stats_visible {
+            match code {
+                KeyCode::Char('s') | KeyCode::Esc => self.toggle_stats(),
+                KeyCode::Char('j') | KeyCode::Down => self.scroll_stats(true),
+                KeyCode::Char('k') | KeyCode::Up => self.scroll_stats(false),
+                KeyCode::Char('q') => self.quit = true,
+                _ => {}
             }
+            return;
+        }
+
+        // The ignore rule popup takes the navigation keys while it is open
+        if self.rule_choice.is_some() {
+            match code {
+                KeyCode::Char('j') | KeyCode::Down => self.move_rule_choice(true),
+                KeyCode::Char('k') | KeyCode::Up => self.move_rule_choice(false),
+                KeyCode::Enter => self.add_chosen_rule(),
+                KeyCode::Char('W') => self.write_pending_rules(),
+                KeyCode::Esc | KeyCode::Char('I') => self.rule_choice = None,
+                KeyCode::Char('q') => self.quit = true,
+                _ => {}
+            }
+            return;
+        }
+
+        if self.details_visible {
+            match code {
+                KeyCode::Enter | KeyCode::Esc => self.toggle_details(),
+                KeyCode::Char('q') => self.quit = true,
+                _ => {}
+            }
+            return;
+        }
+
+        match code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Enter => self.toggle_details(),
+            KeyCode::Char('j') | KeyCode::Down => self.next(),
+            KeyCode::Char('k') | KeyCode::Up => self.previous(),
+            KeyCode::Char('n') => self.next_diff(),
+            KeyCode::Char('N') => self.previous_diff(),
+            KeyCode::Char('v') => self.toggle_view_mode(),
+            KeyCode::Char('h') | KeyCode::Char('?') => self.toggle_help(),
+            KeyCode::Char('r') => self.cycle_output_style(),
+            KeyCode::Char('s') => self.toggle_stats(),
+            KeyCode::Char('o') => self.cycle_sort_mode(),
+            KeyCode::Char('a') => self.cycle_triage_status(),
+            KeyCode::Char('A') => self.open_note_input(),
+            KeyCode::Char(':') | KeyCode::Char('p') => self.open_path_input(),
+            KeyCode::Char('/') => self.open_query_input(),
+            KeyCode::Char('I') => self.open_rule_choice(),
+            KeyCode::Char('W') => self.write_pending_rules(),
+            KeyCode::Char('<') if self.view_mode == ViewMode::SplitScreen => self.resize_split(false),
+            KeyCode::Char('>') if self.view_mode == ViewMode::SplitScreen => self.resize_split(true),
+            KeyCode::Char('=') if self.view_mode == ViewMode::SplitScreen => self.reset_split(),
+            KeyCode::Tab if self.view_mode == ViewMode::SplitScreen => self.switch_pane(),
+            KeyCode::Char('z') if self.view_mode == ViewMode::SplitScreen => self.toggle_maximized(),
+            KeyCode::Char('g') if self.view_mode == ViewMode::SplitScreen => self.toggle_phantom_rows(),
+            KeyCode::Char('f') if self.view_mode == ViewMode::List => self.toggle_full_paths(),
+            KeyCode::Left if self.view_mode == ViewMode::List => self.scroll_list(false),
+            KeyCode::Right if self.view_mode == ViewMode::List => self.scroll_list(true),
+            KeyCode::Char(digit @ '1'..='9') => {
+                let diff_type = DiffType::ALL[digit as usize - '1' as usize].clone();
+                self.toggle_type_filter(diff_type);
+            },
+            KeyCode::Esc => {
+                if self.help_visible {
+                    self.help_visible = false;
+                } else {
+                    self.quit = true;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// Advance time-based state by one tick of the event loop
+    pub fn on_tick(&mut self) {
+        self.ticks += 1;
+
+        if self.status_expires_at.is_some_and(|expiry| self.ticks >= expiry) {
+            self.status_message = None;
+            self.status_expires_at = None;
         }
-        vec!["File not found or could not be read".to_string()]
+    }
+
+    /// Show a message in the status bar for the given number of ticks
+    pub fn show_message(&mut self, message: impl Into<String>, ticks: u64) {
+        self.status_message = Some(message.into());
+        self.status_expires_at = Some(self.ticks + ticks);
     }
 
     pub fn next(&mut self) {
         match self.view_mode {
             ViewMode::List => {
-                if !self.diff_result.entries.is_empty() {
-                    self.current_index = (self.current_index + 1) % self.diff_result.entries.len();
+                if !self.visible_order.is_empty() {
+                    self.current_index = (self.current_index + 1) % self.visible_order.len();
                 }
             }
             ViewMode::SplitScreen => {
@@ -125,11 +682,11 @@ impl App {
     pub fn previous(&mut self) {
         match self.view_mode {
             ViewMode::List => {
-                if !self.diff_result.entries.is_empty() {
+                if !self.visible_order.is_empty() {
                     self.current_index = if self.current_index > 0 {
                         self.current_index - 1
                     } else {
-                        self.diff_result.entries.len() - 1
+                        self.visible_order.len() - 1
                     };
                 }
             }
@@ -143,8 +700,123 @@ impl App {
         self.help_visible = !self.help_visible;
     }
 
-    pub fn toggle_format(&mut self) {
-        self.use_readable_format = !self.use_readable_format;
+    pub fn toggle_stats(&mut self) {
+        self.stats_visible = !self.stats_visible;
+        self.stats_scroll = 0;
+    }
+
+    /// Profile that ignore rules collected in the display are written to
+    pub fn set_profile_path(&mut self, path: Option<PathBuf>) {
+        self.profile_path = path;
+    }
+
+    /// Whether the terminal title is set while the display is open, on by default
+    pub fn set_window_title(&mut self, enabled: bool) {
+        self.window_title = enabled;
+    }
+
+    /// Whether the pane without the focused entry shows a row where it would be, on by default
+    pub fn set_phantom_rows(&mut self, enabled: bool) {
+        self.phantom_rows = enabled;
+    }
+
+    fn toggle_phantom_rows(&mut self) {
+        self.phantom_rows = !self.phantom_rows;
+        let message = if self.phantom_rows { "Showing where missing entries would be" } else { "Hiding where missing entries would be" };
+        self.show_message(message, MESSAGE_TICKS);
+    }
+
+    /// Whether the list shows paths in full, scrolled sideways with ←/→, rather than
+    /// shortened to fit its width; off by default
+    pub fn set_full_paths(&mut self, enabled: bool) {
+        self.full_paths = enabled;
+        self.list_scroll = 0;
+    }
+
+    /// Switch between full and shortened paths in the list
+    pub fn toggle_full_paths(&mut self) {
+        self.set_full_paths(!self.full_paths);
+        let message = if self.full_paths { "Paths in full, ←/→ to scroll" } else { "Paths shortened to fit" };
+        self.show_message(message, MESSAGE_TICKS);
+    }
+
+    /// Scroll the rows of the list sideways, which only paths in full need
+    fn scroll_list(&mut self, right: bool) {
+        if self.full_paths {
+            self.list_scroll = if right { self.list_scroll + LIST_SCROLL_STEP } else { self.list_scroll.saturating_sub(LIST_SCROLL_STEP) };
+        }
+    }
+
+    /// The terminal title for the entries shown, see [`window_title`]
+    pub fn window_title(&self) -> String {
+        window_title(&self.diff_result, &self.visible_order)
+    }
+
+    /// Offer the ignore rules that cover the current entry
+    pub fn open_rule_choice(&mut self) {
+        if let Some(index) = self.current_entry() {
+            let candidates = ignore_rules::rule_candidates(&self.diff_result.entries[index].path);
+            self.rule_choice = Some((candidates, 0));
+        }
+    }
+
+    fn move_rule_choice(&mut self, down: bool) {
+        if let Some((candidates, chosen)) = &mut self.rule_choice {
+            *chosen = if down { (*chosen + 1).min(candidates.len() - 1) } else { chosen.saturating_sub(1) };
+        }
+    }
+
+    /// Add the chosen ignore rule to the pending ones
+    fn add_chosen_rule(&mut self) {
+        if let Some((mut candidates, chosen)) = self.rule_choice.take() {
+            let rule = candidates.swap_remove(chosen);
+            if !self.pending_rules.contains(&rule) {
+                self.pending_rules.push(rule);
+            }
+            let message = format!("{} pending ignore rule(s); W: write to profile", self.pending_rules.len());
+            self.show_message(message, MESSAGE_TICKS);
+        }
+    }
+
+    /// Merge the pending ignore rules into the profile, reporting the outcome in the footer
+    pub fn write_pending_rules(&mut self) {
+        let message = match (&self.profile_path, self.pending_rules.is_empty()) {
+            (_, true) => "No pending ignore rules (I: add one for the current entry)".to_string(),
+            (None, false) => "No profile to write ignore rules to; pass --profile".to_string(),
+            (Some(path), false) => match ignore_rules::merge_ignore_rules(path, &self.pending_rules) {
+                Ok(added) => {
+                    let message = format!("Added {} ignore rule(s) to {}", added, path.display());
+                    self.pending_rules.clear();
+                    message
+                }
+                Err(err) => format!("Failed to write ignore rules: {:#}", err),
+            },
+        };
+        self.show_message(message, MESSAGE_TICKS);
+    }
+
+    /// Show or hide the details of the current entry
+    pub fn toggle_details(&mut self) {
+        self.details_visible = !self.details_visible && self.current_entry().is_some();
+    }
+
+    pub fn scroll_stats(&mut self, down: bool) {
+        let last = self.stats_lines().len().saturating_sub(1) as u16;
+        self.stats_scroll = if down { (self.stats_scroll + 1).min(last) } else { self.stats_scroll.saturating_sub(1) };
+    }
+
+    /// Content of the statistics popup, assembled on first use
+    fn stats_lines(&self) -> &[Line<'static>] {
+        self.stats_lines.get_or_init(|| create_stats_lines(self))
+    }
+
+    /// Switch to the next way of writing diff types
+    pub fn cycle_output_style(&mut self) {
+        self.output_style = self.output_style.next();
+    }
+
+    pub fn set_output_style(&mut self, style: OutputStyle) {
+        self.output_style = style;
     }
 
     pub fn toggle_view_mode(&mut self) {
@@ -154,6 +826,50 @@ impl App {
         };
     }
 
+    /// Share of the split given to the left pane, in percent
+    pub fn split_percent(&self) -> u16 {
+        self.split_percent
+    }
+
+    /// Give the left pane a step more of the split, or a step less, within the bounds
+    pub fn resize_split(&mut self, grow_left: bool) {
+        self.split_percent = if grow_left {
+            (self.split_percent + SPLIT_STEP).min(MAX_SPLIT_PERCENT)
+        } else {
+            self.split_percent.saturating_sub(SPLIT_STEP).max(MIN_SPLIT_PERCENT)
+        };
+        self.show_split_percent();
+    }
+
+    /// Split the view evenly again
+    pub fn reset_split(&mut self) {
+        self.split_percent = DEFAULT_SPLIT_PERCENT;
+        self.show_split_percent();
+    }
+
+    fn show_split_percent(&mut self) {
+        let message = format!("Split {}/{}", self.split_percent, 100 - self.split_percent);
+        self.show_message(message, SPLIT_MESSAGE_TICKS);
+    }
+
+    /// Move the focus to the other pane; a maximized view shows that pane instead
+    pub fn switch_pane(&mut self) {
+        self.focused_pane = match self.focused_pane {
+            Pane::Left => Pane::Right,
+            Pane::Right => Pane::Left,
+        };
+    }
+
+    /// Let the focused pane fill the split, or restore both panes
+    pub fn toggle_maximized(&mut self) {
+        self.pane_maximized = !self.pane_maximized;
+    }
+
+    /// The pane filling the split alone, if one is maximized
+    pub fn maximized_pane(&self) -> Option<Pane> {
+        self.pane_maximized.then_some(self.focused_pane)
+    }
+
     pub fn scroll_up(&mut self) {
         if self.left_scroll > 0 {
             self.left_scroll -= 1;
@@ -164,8 +880,8 @@ impl App {
     }
 
     pub fn scroll_down(&mut self) {
-        let max_left = self.left_content.len().saturating_sub(1);
-        let max_right = self.right_content.len().saturating_sub(1);
+        let max_left = self.pane_lines(true).len().saturating_sub(1);
+        let max_right = self.pane_lines(false).len().saturating_sub(1);
 
         if self.left_scroll < max_left {
             self.left_scroll += 1;
@@ -176,107 +892,395 @@ impl App {
     }
 
     pub fn next_diff(&mut self) {
-        if !self.sorted_diff_indices.is_empty() {
-            self.current_sorted_position = (self.current_sorted_position + 1) % self.sorted_diff_indices.len();
-            self.current_diff_index = self.sorted_diff_indices[self.current_sorted_position];
+        if !self.visible_order.is_empty() {
+            self.current_sorted_position = (self.current_sorted_position + 1) % self.visible_order.len();
             self.jump_to_current_diff();
         }
     }
 
     pub fn previous_diff(&mut self) {
-        if !self.sorted_diff_indices.is_empty() {
+        if !self.visible_order.is_empty() {
             self.current_sorted_position = if self.current_sorted_position > 0 {
                 self.current_sorted_position - 1
             } else {
-                self.sorted_diff_indices.len() - 1
+                self.visible_order.len() - 1
             };
-            self.current_diff_index = self.sorted_diff_indices[self.current_sorted_position];
             self.jump_to_current_diff();
         }
     }
 
     fn jump_to_current_diff(&mut self) {
-        if let Some(entry) = self.diff_result.entries.get(self.current_diff_index) {
-            // Jump to the line number of the current diff
-            if let Some(left_line) = entry.left_line {
-                self.left_scroll = left_line.saturating_sub(1);
+        if let Some(index) = self.focused_diff() {
+            // Jump to the line number of the current diff; on the side missing the
+            // entry, that is the line it would be inserted after
+            let (left, right) = (self.anchor_line(index, true), self.anchor_line(index, false));
+            // The phantom row follows that line, so the side holding the entry starts a line
+            // earlier to show it next to the phantom row
+            let entry = &self.diff_result.entries()[index];
+            let back = |is_left: bool| match self.phantom_rows && left.is_some() && right.is_some() && is_insertion_anchor(entry, !is_left) {
+                true => 2,
+                false => 1,
+            };
+            if let Some(left_line) = left {
+                self.left_scroll = left_line.saturating_sub(back(true));
+            }
+            if let Some(right_line) = right {
+                self.right_scroll = right_line.saturating_sub(back(false));
+            }
+        }
+    }
+
+    /// The pretty-printed view of a pane's file, if it is minified, see [`formatted`]
+    fn formatted(&self, is_left: bool) -> Option<&FormattedView> {
+        let (content, cell) = match is_left {
+            true => (&self.left_content, &self.left_formatted),
+            false => (&self.right_content, &self.right_formatted),
+        };
+        if !self.files_loaded || !formatted::needs_formatting(content) {
+            return None;
+        }
+        cell.get_or_init(|| FormattedView::new(&content.join("\n"), &self.diff_result, is_left)).as_ref()
+    }
+
+    /// Whether a pane shows its file pretty-printed
+    pub fn is_formatted(&self, is_left: bool) -> bool {
+        self.formatted(is_left).is_some()
+    }
+
+    /// Lines a pane shows: its file's, or those of the pretty-printed view
+    fn pane_lines(&self, is_left: bool) -> &[String] {
+        match self.formatted(is_left) {
+            Some(view) => &view.lines,
+            None if is_left => &self.left_content,
+            None => &self.right_content,
+        }
+    }
+
+    /// Line of the entry at `index` in a pane, as [`App::pane_lines`] numbers them
+    fn entry_line(&self, index: usize, is_left: bool) -> Option<usize> {
+        match self.formatted(is_left) {
+            Some(view) => view.line_of(index),
+            None => self.diff_result.entries().get(index).and_then(|entry| line_of(entry, is_left)),
+        }
+    }
+
+    /// Like [`App::entry_line`], but found in the pane's file when the result has no line
+    /// on its side, as results compared without the documents' text or loaded from older
+    /// reports do not: the entry's own line, or on the side missing it the line it would
+    /// follow, after its nearest preceding sibling there or at its parent, as the
+    /// comparison anchors entries, else at its nearest ancestor there
+    fn anchor_line(&self, index: usize, is_left: bool) -> Option<usize> {
+        self.entry_line(index, is_left).or_else(|| {
+            let entry = self.diff_result.entries().get(index)?;
+            // The formatted view places entries on its own lines
+            if self.formatted(is_left).is_some() {
+                return None;
             }
-            if let Some(right_line) = entry.right_line {
-                self.right_scroll = right_line.saturating_sub(1);
+            let here = self.line_map(is_left)?;
+            if !is_insertion_anchor(entry, is_left) {
+                return here.line_of(side_path(entry, is_left));
             }
+            self.line_map(!is_left)
+                .and_then(|other| here.insertion_line(side_path(entry, !is_left), other))
+                .or_else(|| {
+                    std::iter::successors(Some(parent_path(side_path(entry, is_left))), |path| Some(parent_path(path)))
+                        .take_while(|path| !path.is_empty())
+                        .find_map(|path| here.line_of(path))
+                })
+        })
+    }
+
+    /// The line map of a pane's file, if both files are loaded and it is JSON
+    fn line_map(&self, is_left: bool) -> Option<&LineMap> {
+        let (content, cell) = match is_left {
+            true => (&self.left_content, &self.left_map),
+            false => (&self.right_content, &self.right_map),
+        };
+        if !self.files_loaded {
+            return None;
         }
+        cell.get_or_init(|| line_map::build(&content.join("\n")).ok()).as_ref()
+    }
+}
+
+/// Error of a display started without a terminal on stdin and stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoTerminal;
+
+impl std::fmt::Display for NoTerminal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the interactive display needs a terminal on stdin and stdout")
     }
 }
 
+impl std::error::Error for NoTerminal {}
+
+/// Whether stdin and stdout are both terminals, as the display needs
+pub fn terminal_available() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
 /// Runs the terminal UI for displaying diff results
 pub fn run_display(diff_result: DiffResult) -> Result<()> {
-    run_display_with_options(diff_result, false)
+    run_display_with_options(diff_result, OutputStyle::default())
 }
 
 /// Runs the terminal UI for displaying diff results with options
-pub fn run_display_with_options(diff_result: DiffResult, use_readable_format: bool) -> Result<()> {
-    // Setup terminal
-    enable_raw_mode().context("Failed to enable raw mode")?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+pub fn run_display_with_options(diff_result: DiffResult, output_style: OutputStyle) -> Result<()> {
+    run_display_with_limit(diff_result, output_style, None)
+}
 
+/// Runs the terminal UI, refusing to load files larger than `max_input_bytes` into the panes
+pub fn run_display_with_limit(diff_result: DiffResult, output_style: OutputStyle, max_input_bytes: Option<u64>) -> Result<()> {
+    run_display_with_tick_rate(diff_result, output_style, max_input_bytes, DEFAULT_TICK_RATE).map(|_| ())
+}
+
+/// Runs the terminal UI, sending the app a tick every `tick_rate` for time-based state
+///
+/// Returns the result with the annotations made in the display.
+pub fn run_display_with_tick_rate(
+    diff_result: DiffResult,
+    output_style: OutputStyle,
+    max_input_bytes: Option<u64>,
+    tick_rate: Duration,
+) -> Result<DiffResult> {
     // Create app state
-    let mut app = App::new(diff_result);
-    app.use_readable_format = use_readable_format;
+    let mut app = App::with_input_limit(diff_result, max_input_bytes);
+    app.set_output_style(output_style);
+
+    run_app(app, tick_rate)
+}
 
-    // Main loop
-    let result = run_main_loop(&mut terminal, &mut app);
+/// Runs the terminal UI for an app set up by the caller
+///
+/// Returns the result with the annotations made in the display.
+pub fn run_app(mut app: App, tick_rate: Duration) -> Result<DiffResult> {
+    run_app_mut(&mut app, tick_rate)?;
+    Ok(app.into_result())
+}
+
+/// Runs the terminal UI for an app the caller keeps, so its result and view can be
+/// used even if the display fails
+///
+/// Without a terminal the display fails with [`NoTerminal`] before touching it, so it
+/// is not left in raw mode. The terminal is restored however the display ends, a panic
+/// included, whose message is then printed on the normal screen.
+pub fn run_app_mut(app: &mut App, tick_rate: Duration) -> Result<()> {
+    if !terminal_available() {
+        return Err(NoTerminal.into());
+    }
 
-    // Restore terminal (always do this, even if there was an error)
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen
-    )?;
-    terminal.show_cursor()?;
+    let titled = app.window_title && title_supported(std::env::var("TERM").ok().as_deref());
+    let guard = TerminalGuard::enter(titled)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let result = run_main_loop(&mut terminal, app, tick_rate, titled);
 
-    result
+    let restored = guard.restore();
+    result?;
+    restored.context("Failed to restore the terminal")
 }
 
-fn run_main_loop<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()> {
-    while !app.quit {
-        // Draw UI
-        terminal.draw(|f| ui(f, app))?;
+/// Undoes the terminal setup of the display when dropped, and when the display panics
+///
+/// A panic message is printed by the panic hook before the stack unwinds, which would
+/// be on the alternate screen and lost with it, so while the guard lives a hook that
+/// restores the terminal first runs before the one it replaced.
+struct TerminalGuard {
+    teardown: fn() -> io::Result<()>,
+    restored: Arc<AtomicBool>,
+    previous_hook: Arc<PanicHook>,
+}
 
-        // Handle input
-        if let Event::Key(key) = event::read()? {
-            match key.code {
-                KeyCode::Char('q') => app.quit = true,
-                KeyCode::Char('j') | KeyCode::Down => app.next(),
-                KeyCode::Char('k') | KeyCode::Up => app.previous(),
-                KeyCode::Char('n') => app.next_diff(),
-                KeyCode::Char('N') => app.previous_diff(),
-                KeyCode::Char('v') => app.toggle_view_mode(),
-                KeyCode::Char('h') | KeyCode::Char('?') => app.toggle_help(),
-                KeyCode::Char('r') => app.toggle_format(),
-                KeyCode::Esc => {
-                    if app.help_visible {
-                        app.help_visible = false;
-                    } else {
-                        app.quit = true;
-                    }
-                },
-                _ => {}
+type PanicHook = Box<dyn Fn(&PanicHookInfo<'_>) + Sync + Send + 'static>;
+
+impl TerminalGuard {
+    /// Switch the terminal to raw mode and the alternate screen, saving its title first
+    /// if the display is to set it
+    fn enter(titled: bool) -> Result<Self> {
+        enable_raw_mode().context("Failed to enable raw mode")?;
+        // From here on raw mode is undone, even if the alternate screen cannot be entered
+        let guard = Self::with_teardown(if titled { restore_terminal_and_title } else { restore_terminal });
+        if titled {
+            // Best effort: a terminal without a title stack ignores it
+            let _ = write!(io::stdout(), "{}", PUSH_TITLE);
+        }
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(guard)
+    }
+
+    /// A guard running `teardown` once, when dropped or on a panic, whichever comes first
+    fn with_teardown(teardown: fn() -> io::Result<()>) -> Self {
+        let restored = Arc::new(AtomicBool::new(false));
+        let previous_hook: Arc<PanicHook> = Arc::new(std::panic::take_hook());
+        let (hook_restored, hook_previous) = (Arc::clone(&restored), Arc::clone(&previous_hook));
+        std::panic::set_hook(Box::new(move |info| {
+            if !hook_restored.swap(true, Ordering::SeqCst) {
+                let _ = teardown();
+            }
+            hook_previous(info);
+        }));
+
+        Self { teardown, restored, previous_hook }
+    }
+
+    /// Run the teardown now, unless a panic already did, reporting its failure
+    fn restore(self) -> io::Result<()> {
+        if self.restored.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        (self.teardown)()
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if !self.restored.swap(true, Ordering::SeqCst) {
+            let _ = (self.teardown)();
+        }
+        // The hook cannot be replaced while panicking; ours then only passes panics on
+        if !std::thread::panicking() {
+            let previous = Arc::clone(&self.previous_hook);
+            std::panic::set_hook(Box::new(move |info| previous(info)));
+        }
+    }
+}
+
+/// Leave raw mode and the alternate screen and show the cursor, trying each step even
+/// if one before it failed
+fn restore_terminal() -> io::Result<()> {
+    let raw_mode = disable_raw_mode();
+    let screen = execute!(io::stdout(), LeaveAlternateScreen, Show);
+    raw_mode.and(screen)
+}
+
+/// Saves the title on the xterm title stack
+const PUSH_TITLE: &str = "\x1b[22;0t";
+/// Restores the title saved with [`PUSH_TITLE`]
+const POP_TITLE: &str = "\x1b[23;0t";
+
+/// [`restore_terminal`], clearing the title the display set and then restoring the
+/// one before it where the terminal keeps a title stack
+fn restore_terminal_and_title() -> io::Result<()> {
+    let mut stdout = io::stdout();
+    let _ = execute!(stdout, SetTitle("")).and_then(|_| write!(stdout, "{}", POP_TITLE)).and_then(|_| stdout.flush());
+    restore_terminal()
+}
+
+/// Whether the terminal named by `TERM` is expected to show a title set with an escape
+///
+/// Consoles without a window, and an unknown terminal, are left alone.
+fn title_supported(term: Option<&str>) -> bool {
+    match term {
+        None | Some("") | Some("dumb") | Some("linux") | Some("cons25") => false,
+        Some(term) => !term.starts_with("vt"),
+    }
+}
+
+/// The terminal title for the entries of `result` at `visible`, e.g.
+/// `json-diff: 42 diffs (3 removed) — left.json vs right.json`
+///
+/// Ignored and tolerated entries are not counted. Control characters in the labels are
+/// left out, so they cannot end the escape the title is sent in.
+fn window_title(result: &DiffResult, visible: &[usize]) -> String {
+    let entries = visible.iter().map(|&index| &result.entries[index]).filter(|entry| entry.diff_type.is_difference());
+    let (diffs, removed) = entries.fold((0, 0), |(diffs, removed), entry| {
+        (diffs + 1, removed + usize::from(entry.diff_type == DiffType::Removed))
+    });
+
+    let mut title = format!("json-diff: {} diff{}", diffs, if diffs == 1 { "" } else { "s" });
+    if removed > 0 {
+        title.push_str(&format!(" ({} removed)", removed));
+    }
+    let label = |label: &Option<String>, file: &Option<PathBuf>| {
+        let label = label.clone().or_else(|| file.as_ref()?.file_name().map(|name| name.to_string_lossy().into_owned()))?;
+        Some(label.chars().filter(|c| !c.is_control()).collect::<String>())
+    };
+    if let (Some(left), Some(right)) = (label(&result.left_label, &result.left_file), label(&result.right_label, &result.right_file)) {
+        title.push_str(&format!(" \u{2014} {} vs {}", left, right));
+    }
+    title
+}
+
+/// Failures in a row to draw or to read input after which the display gives up
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+
+fn run_main_loop<B: Backend>(terminal: &mut Terminal<B>, app: &mut App, tick_rate: Duration, titled: bool) -> Result<()> {
+    let mut last_tick = Instant::now();
+    let mut failures = 0;
+    let mut shown_title = None;
+
+    while !app.quit {
+        // The title follows the filters; failing to set it never stops the display
+        if titled {
+            let title = app.window_title();
+            if shown_title.as_ref() != Some(&title) {
+                let _ = execute!(io::stdout(), SetTitle(&title));
+                shown_title = Some(title);
+            }
+        }
+        // Sleep until input arrives or the next tick is due; a resize only needs the redraw
+        let step = terminal.draw(|f| ui(f, app))
+            .context("Failed to draw the display")
+            .and_then(|_| next_key(tick_rate.saturating_sub(last_tick.elapsed())).context("Failed to read input"));
+        match step {
+            Ok(key) => {
+                failures = 0;
+                if let Some(key) = key {
+                    app.handle_key(key);
+                }
+            }
+            // A failed frame or read is shown in the status bar and retried
+            Err(error) => {
+                failures += 1;
+                if failures >= MAX_CONSECUTIVE_FAILURES {
+                    return Err(error);
+                }
+                app.status_message = Some(format!("{:#}", error));
             }
         }
+
+        if last_tick.elapsed() >= tick_rate {
+            app.on_tick();
+            last_tick = Instant::now();
+        }
     }
 
     Ok(())
 }
 
+/// The key pressed within `timeout`, if any
+fn next_key(timeout: Duration) -> io::Result<Option<KeyCode>> {
+    if event::poll(timeout)? {
+        if let Event::Key(key) = event::read()? {
+            return Ok(Some(key.code));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Makes drawing panic, to test that the terminal is restored
+    static PANIC_ON_DRAW: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
 fn ui(f: &mut Frame, app: &App) {
+    #[cfg(test)]
+    if PANIC_ON_DRAW.with(std::cell::Cell::get) {
+        panic!("drawing failed on purpose");
+    }
     let size = f.size();
 
+    let layout = screen_layout(size.width, size.height);
+    if layout == ScreenLayout::TooSmall {
+        let message = format!("terminal too small (need at least {}x{})", MIN_WIDTH, MIN_HEIGHT);
+        f.render_widget(Paragraph::new(message).wrap(Wrap { trim: true }), size);
+        return;
+    }
+
     match app.view_mode {
         ViewMode::List => render_list_view(f, app, size),
-        ViewMode::SplitScreen => render_split_screen_view(f, app, size),
+        ViewMode::SplitScreen => render_split_screen_view(f, app, size, layout),
     }
 
     // Help overlay (common to both views)
@@ -286,6 +1290,78 @@ fn ui(f: &mut Frame, app: &App) {
         f.render_widget(Clear, popup_area);
         f.render_widget(help, popup_area);
     }
+
+    if app.stats_visible {
+        let stats = Paragraph::new(app.stats_lines().to_vec())
+            .block(Block::default().borders(Borders::ALL).title("Statistics (j/k: scroll, s/Esc: close)"))
+            .style(Style::default().bg(Color::Black))
+            .scroll((app.stats_scroll, 0));
+        let popup_area = centered_rect(80, 70, size);
+        f.render_widget(Clear, popup_area);
+        f.render_widget(stats, popup_area);
+    }
+
+    if let Some(entry) = app.details_visible.then(|| app.current_entry()).flatten().map(|index| &app.diff_result.entries()[index]) {
+        let details = Paragraph::new(create_details_lines(entry, app.output_style))
+            .block(Block::default().borders(Borders::ALL).title("Details (Enter/Esc: close)"))
+            .style(Style::default().bg(Color::Black))
+            .wrap(Wrap { trim: false });
+        let popup_area = centered_rect(80, 70, size);
+        f.render_widget(Clear, popup_area);
+        f.render_widget(details, popup_area);
+    }
+
+    if let Some((candidates, chosen)) = &app.rule_choice {
+        let mut lines = vec![Line::from(Span::styled("Ignore rule for the current entry:", Style::default().add_modifier(Modifier::BOLD)))];
+        for (index, candidate) in candidates.iter().enumerate() {
+            let style = if index == *chosen { Style::default().bg(Color::Blue) } else { Style::default() };
+            lines.push(Line::from(Span::styled(format!("  {}", candidate), style)));
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled("Pending:", Style::default().add_modifier(Modifier::BOLD))));
+        if app.pending_rules.is_empty() {
+            lines.push(Line::from("  (none)"));
+        }
+        lines.extend(app.pending_rules.iter().map(|rule| Line::from(format!("  {}", rule))));
+
+        let popup = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title("Ignore Rules (j/k: choose, Enter: add, W: write to profile, Esc: close)"))
+            .style(Style::default().bg(Color::Black))
+            .wrap(Wrap { trim: false });
+        let popup_area = centered_rect(70, 40, size);
+        f.render_widget(Clear, popup_area);
+        f.render_widget(popup, popup_area);
+    }
+
+    if let Some(note) = &app.note_input {
+        let input = Paragraph::new(format!("{}_", note))
+            .block(Block::default().borders(Borders::ALL).title("Note (Enter: save, Esc: cancel)"))
+            .style(Style::default().bg(Color::Black))
+            .wrap(Wrap { trim: false });
+        let popup_area = centered_rect(60, 20, size);
+        f.render_widget(Clear, popup_area);
+        f.render_widget(input, popup_area);
+    }
+
+    if let Some(input) = &app.path_input {
+        let prompt = Paragraph::new(format!("{}_", escape_control(input.text(&app.entry_paths))))
+            .block(Block::default().borders(Borders::ALL).title("Jump to path (Tab: complete, Enter: jump, Esc: cancel)"))
+            .style(Style::default().bg(Color::Black))
+            .wrap(Wrap { trim: false });
+        let popup_area = centered_rect(60, 20, size);
+        f.render_widget(Clear, popup_area);
+        f.render_widget(prompt, popup_area);
+    }
+
+    if let Some(query) = &app.query_input {
+        let prompt = Paragraph::new(format!("{}_", query))
+            .block(Block::default().borders(Borders::ALL).title("Query, e.g. type:removed AND path:$.config (Enter: apply, Esc: cancel)"))
+            .style(Style::default().bg(Color::Black))
+            .wrap(Wrap { trim: false });
+        let popup_area = centered_rect(60, 20, size);
+        f.render_widget(Clear, popup_area);
+        f.render_widget(prompt, popup_area);
+    }
 }
 
 fn render_list_view(f: &mut Frame, app: &App, size: Rect) {
@@ -297,7 +1373,7 @@ fn render_list_view(f: &mut Frame, app: &App, size: Rect) {
             [
                 Constraint::Length(3),  // Header
                 Constraint::Min(5),     // Diff content
-                Constraint::Length(3),  // Footer
+                Constraint::Length(footer_height(app)),  // Footer
             ]
             .as_ref(),
         )
@@ -308,7 +1384,8 @@ fn render_list_view(f: &mut Frame, app: &App, size: Rect) {
     f.render_widget(header, chunks[0]);
 
     // Diff content
-    let diff_content = create_diff_content(app);
+    // Inside the borders, so paths are shortened anew whenever the terminal is resized
+    let diff_content = create_diff_content(app, usize::from(chunks[1].width.saturating_sub(2)));
     let mut list_state = ListState::default();
     list_state.select(Some(app.current_index));
     f.render_stateful_widget(diff_content, chunks[1], &mut list_state);
@@ -318,7 +1395,7 @@ fn render_list_view(f: &mut Frame, app: &App, size: Rect) {
     f.render_widget(footer, chunks[2]);
 }
 
-fn render_split_screen_view(f: &mut Frame, app: &App, size: Rect) {
+fn render_split_screen_view(f: &mut Frame, app: &App, size: Rect, layout: ScreenLayout) {
     // Create the layout
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -327,46 +1404,55 @@ fn render_split_screen_view(f: &mut Frame, app: &App, size: Rect) {
             [
                 Constraint::Length(3),  // Header
                 Constraint::Min(5),     // Split content
-                Constraint::Length(3),  // Footer
+                Constraint::Length(footer_height(app)),  // Footer
             ]
             .as_ref(),
         )
         .split(size);
 
     // Header
-    let header = create_split_header(&app.diff_result, app.current_diff_index);
+    let header = create_split_header(app);
     f.render_widget(header, chunks[0]);
 
-    // Split the main area horizontally
+    // Split the main area horizontally, or vertically when the terminal is narrow; a
+    // maximized pane takes all of it
+    let direction = if layout == ScreenLayout::Stacked { Direction::Vertical } else { Direction::Horizontal };
+    let left_percent = match app.maximized_pane() {
+        Some(Pane::Left) => 100,
+        Some(Pane::Right) => 0,
+        None => app.split_percent,
+    };
     let split_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .direction(direction)
+        .constraints([Constraint::Percentage(left_percent), Constraint::Percentage(100 - left_percent)].as_ref())
         .split(chunks[1]);
 
     // Left file content
-    let left_content = create_file_content(&app.left_content, app.left_scroll, "Left File", &app.diff_result, true, app.current_diff_index);
-    f.render_widget(left_content, split_chunks[0]);
+    if app.maximized_pane() != Some(Pane::Right) {
+        let left_content = create_file_content(app, true);
+        f.render_widget(left_content, split_chunks[0]);
+    }
 
     // Right file content
-    let right_content = create_file_content(&app.right_content, app.right_scroll, "Right File", &app.diff_result, false, app.current_diff_index);
-    f.render_widget(right_content, split_chunks[1]);
+    if app.maximized_pane() != Some(Pane::Left) {
+        let right_content = create_file_content(app, false);
+        f.render_widget(right_content, split_chunks[1]);
+    }
 
     // Footer
     let footer = create_split_footer(app);
     f.render_widget(footer, chunks[2]);
 }
 
-fn create_split_header(diff_result: &DiffResult, current_diff_index: usize) -> Paragraph<'static> {
-    let left_file = diff_result.left_file.as_ref()
-        .map(|p| p.display().to_string())
-        .unwrap_or_else(|| "Unknown".to_string());
-
-    let right_file = diff_result.right_file.as_ref()
-        .map(|p| p.display().to_string())
-        .unwrap_or_else(|| "Unknown".to_string());
+fn create_split_header(app: &App) -> Paragraph<'static> {
+    let diff_result = &app.diff_result;
+    let left_file = file_label(&diff_result.left_file, &diff_result.left_sha256, diff_result.left_missing);
+    let right_file = file_label(&diff_result.right_file, &diff_result.right_sha256, diff_result.right_missing);
 
-    let current_diff = if !diff_result.entries.is_empty() {
-        format!("Diff {}/{}", current_diff_index + 1, diff_result.entries.len())
+    let current_diff = if !app.visible_order.is_empty() {
+        format!("Diff {}/{}", app.current_sorted_position + 1, app.visible_order.len())
+    } else if !diff_result.entries().is_empty() {
+        "All differences hidden".to_string()
     } else {
         "No differences".to_string()
     };
@@ -385,7 +1471,19 @@ fn create_split_header(diff_result: &DiffResult, current_diff_index: usize) -> P
         .wrap(Wrap { trim: true })
 }
 
-fn create_file_content(content: &[String], scroll: usize, title: &'static str, diff_result: &DiffResult, is_left: bool, current_diff_index: usize) -> Paragraph<'static> {
+/// A pane of the split view: its file's lines, or those of its pretty-printed view
+fn create_file_content(app: &App, is_left: bool) -> Paragraph<'static> {
+    let diff_result = &app.diff_result;
+    let content = app.pane_lines(is_left);
+    let formatted = app.formatted(is_left);
+    let (scroll, label, default_title, focused) = match is_left {
+        true => (app.left_scroll, &diff_result.left_label, "Left File", app.focused_pane == Pane::Left),
+        false => (app.right_scroll, &diff_result.right_label, "Right File", app.focused_pane == Pane::Right),
+    };
+    let title = label.as_deref().unwrap_or(default_title);
+    let title = if formatted.is_some() { format!("{} (formatted view)", title) } else { title.to_string() };
+    let current_diff_index = app.focused_diff();
+
     let visible_lines = 20; // Adjust based on terminal size
     let start = scroll;
     let end = (start + visible_lines).min(content.len());
@@ -394,12 +1492,24 @@ fn create_file_content(content: &[String], scroll: usize, title: &'static str, d
 
     for (i, line) in content.iter().enumerate().skip(start).take(end - start) {
         let line_number = i + 1;
-        let mut spans = vec![
-            Span::styled(format!("{:4} ", line_number), Style::default().fg(Color::DarkGray)),
-        ];
+        // The focused entry is missing on this side: mark the line it would follow
+        let gutter = match current_diff_index.and_then(|index| Some((index, diff_result.entries().get(index)?))) {
+            Some((index, entry)) if is_insertion_anchor(entry, is_left) && app.anchor_line(index, is_left) == Some(line_number) => {
+                let color = get_semantic_background_color(&Some(entry.diff_type.clone()), true);
+                Span::styled(format!("{:4}▸", line_number), Style::default().fg(color).add_modifier(Modifier::BOLD))
+            }
+            _ => Span::styled(format!("{:4} ", line_number), Style::default().fg(Color::DarkGray)),
+        };
+        let mut spans = vec![gutter];
 
         // Check if this line has a diff and get its type
-        let (has_diff, is_current_diff, diff_type) = check_diff_status_with_type(diff_result, line_number, is_left, current_diff_index);
+        let (has_diff, is_current_diff, diff_type) = match formatted {
+            Some(view) => match view.entry_on_line(line_number) {
+                Some(index) => (true, Some(index) == current_diff_index, Some(diff_result.entries()[index].diff_type.clone())),
+                None => (false, false, None),
+            },
+            None => check_diff_status_with_type(diff_result, line_number, is_left, current_diff_index),
+        };
 
         if has_diff {
             let bg_color = get_semantic_background_color(&diff_type, is_current_diff);
@@ -416,13 +1526,31 @@ fn create_file_content(content: &[String], scroll: usize, title: &'static str, d
         }
 
         lines.push(Line::from(spans));
+        if let Some(row) = phantom_row(app, is_left, line_number) {
+            lines.push(row);
+        }
     }
 
+    let border = if focused { Style::default().fg(Color::Cyan) } else { Style::default() };
     Paragraph::new(lines)
-        .block(Block::default().borders(Borders::ALL).title(title))
+        .block(Block::default().borders(Borders::ALL).border_style(border).title(title))
         .wrap(Wrap { trim: false })
 }
 
+/// The row shown after `line_number` of a pane when the focused entry is missing on its
+/// side and would follow that line, so both panes show the same place
+fn phantom_row(app: &App, is_left: bool, line_number: usize) -> Option<Line<'static>> {
+    let index = app.focused_diff().filter(|_| app.phantom_rows)?;
+    let entry = app.diff_result.entries().get(index).filter(|entry| is_insertion_anchor(entry, is_left))?;
+    if app.anchor_line(index, is_left) != Some(line_number) {
+        return None;
+    }
+    let path = side_path(entry, !is_left);
+    let name = path[parent_path(path).len()..].trim_start_matches('.');
+    let text = format!("     ··· {} would be here ···", escape_control(name));
+    Some(Line::from(Span::styled(text, Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC))))
+}
+
 fn highlight_json_line(line: &str) -> Vec<Span<'static>> {
     let mut spans = Vec::new();
     let mut current = String::new();
@@ -460,11 +1588,11 @@ fn highlight_json_line(line: &str) -> Vec<Span<'static>> {
                 }
                 spans.push(Span::styled(ch.to_string(), Style::default().fg(Color::Yellow)));
             }
-            _ if ch.is_numeric() => {
+            _ if ch.is_ascii_digit() => {
                 current.push(ch);
                 // Continue collecting digits
                 while let Some(&next_ch) = chars.peek() {
-                    if next_ch.is_numeric() || next_ch == '.' {
+                    if next_ch.is_ascii_digit() || next_ch == '.' {
                         current.push(chars.next().unwrap());
                     } else {
                         break;
@@ -488,29 +1616,11 @@ fn highlight_json_line(line: &str) -> Vec<Span<'static>> {
 
 
 
-fn check_diff_status_with_type(diff_result: &DiffResult, line_number: usize, is_left: bool, current_diff_index: usize) -> (bool, bool, Option<DiffType>) {
-    let mut has_diff = false;
-    let mut is_current_diff = false;
-    let mut diff_type = None;
-
-    for (index, entry) in diff_result.entries.iter().enumerate() {
-        let line_matches = if is_left {
-            entry.left_line == Some(line_number)
-        } else {
-            entry.right_line == Some(line_number)
-        };
-
-        if line_matches {
-            has_diff = true;
-            diff_type = Some(entry.diff_type.clone());
-            if index == current_diff_index {
-                is_current_diff = true;
-            }
-            break;
-        }
+fn check_diff_status_with_type(diff_result: &DiffResult, line_number: usize, is_left: bool, current_diff_index: Option<usize>) -> (bool, bool, Option<DiffType>) {
+    match entry_on_line(diff_result, line_number, is_left) {
+        Some((index, entry)) => (true, Some(index) == current_diff_index, Some(entry.diff_type.clone())),
+        None => (false, false, None),
     }
-
-    (has_diff, is_current_diff, diff_type)
 }
 
 fn get_semantic_background_color(diff_type: &Option<DiffType>, is_current_diff: bool) -> Color {
@@ -550,6 +1660,13 @@ fn get_semantic_background_color(diff_type: &Option<DiffType>, is_current_diff:
                 Color::Rgb(100, 0, 100)  // Dark magenta for other array reordering
             }
         }
+        Some(DiffType::Moved) => {
+            if is_current_diff {
+                Color::Blue  // Bright blue for focused moved elements
+            } else {
+                Color::Rgb(0, 0, 110)  // Dark blue for other moved elements
+            }
+        }
         Some(DiffType::Ignored) => {
             if is_current_diff {
                 Color::Gray  // Gray for focused ignored lines
@@ -557,6 +1674,20 @@ fn get_semantic_background_color(diff_type: &Option<DiffType>, is_current_diff:
                 Color::DarkGray  // Dark gray for other ignored lines
             }
         }
+        Some(DiffType::Tolerated) => {
+            if is_current_diff {
+                Color::LightBlue  // Light blue for focused tolerated values
+            } else {
+                Color::Rgb(40, 60, 90)  // Slate for other tolerated values
+            }
+        }
+        Some(DiffType::KeyOrderChanged) => {
+            if is_current_diff {
+                Color::LightMagenta  // Light magenta for focused key order changes
+            } else {
+                Color::Rgb(90, 40, 90)  // Plum for other key order changes
+            }
+        }
         None => {
             // Fallback to the old behavior
             if is_current_diff {
@@ -569,47 +1700,52 @@ fn get_semantic_background_color(diff_type: &Option<DiffType>, is_current_diff:
 }
 
 fn create_split_footer(app: &App) -> Paragraph<'static> {
-    let format_mode = if app.use_readable_format { "Readable (default)" } else { "Symbols" };
+    let text = footer_lines(app, split_nav_info(app));
 
-    let current_diff_info = if !app.diff_result.entries.is_empty() {
-        let entry = &app.diff_result.entries[app.current_diff_index];
-        let diff_type = if app.use_readable_format {
-            entry.diff_type.readable_text()
-        } else {
-            entry.diff_type.symbol()
-        };
-        format!("Current: {} {}", diff_type, entry.path)
+    Paragraph::new(text)
+        .block(Block::default().borders(Borders::ALL).title("Controls"))
+        .wrap(Wrap { trim: true })
+}
+
+/// Navigation line of the split view's footer
+fn split_nav_info(app: &App) -> String {
+    let current_diff_info = if let Some(index) = app.focused_diff() {
+        let entry = &app.diff_result.entries()[index];
+        let diff_type = entry.diff_type.text(app.output_style);
+        match app.annotation(entry).and_then(|a| a.status) {
+            Some(status) => format!("Current: {} {} [{}]", diff_type, escape_control(&entry.path), status),
+            None => format!("Current: {} {}", diff_type, escape_control(&entry.path)),
+        }
     } else {
         "No differences".to_string()
     };
 
-    let nav_info = format!(
-        "Diff {}/{} | {} | Format: {} | j/k: scroll, n/N: next/prev diff, v: view, r: format, h/?: help, q: quit",
-        if app.diff_result.entries.is_empty() { 0 } else { app.current_diff_index + 1 },
-        app.diff_result.entries.len(),
+    format!(
+        "Diff {}/{} | {} | Format: {} | {} | j/k: scroll, n/N: next/prev diff, </>/=: resize, Tab/z: focus/maximize, o: sort, 1-9: filter, /: query, a/A: triage/note, v: view, r: format, h/?: help, q: quit",
+        if app.visible_order.is_empty() { 0 } else { app.current_sorted_position + 1 },
+        app.visible_order.len(),
         current_diff_info,
-        format_mode
-    );
-
-    let text = vec![
-        Line::from(vec![
-            Span::raw(nav_info),
-        ]),
-    ];
-
-    Paragraph::new(text)
-        .block(Block::default().borders(Borders::ALL).title("Controls"))
-        .wrap(Wrap { trim: true })
+        app.output_style.label(),
+        order_info(app)
+    )
 }
 
-fn create_header(diff_result: &DiffResult) -> Paragraph<'static> {
-    let left_file = diff_result.left_file.as_ref()
+/// Label a file for the header: its path followed by an abbreviated checksum when known
+fn file_label(path: &Option<std::path::PathBuf>, sha256: &Option<String>, missing: bool) -> String {
+    let name = path.as_ref()
         .map(|p| p.display().to_string())
         .unwrap_or_else(|| "Unknown".to_string());
 
-    let right_file = diff_result.right_file.as_ref()
-        .map(|p| p.display().to_string())
-        .unwrap_or_else(|| "Unknown".to_string());
+    match sha256 {
+        _ if missing => format!("{} (missing)", name),
+        Some(hash) => format!("{} (sha256 {})", name, &hash[..hash.len().min(12)]),
+        None => name,
+    }
+}
+
+fn create_header(diff_result: &DiffResult) -> Paragraph<'static> {
+    let left_file = file_label(&diff_result.left_file, &diff_result.left_sha256, diff_result.left_missing);
+    let right_file = file_label(&diff_result.right_file, &diff_result.right_sha256, diff_result.right_missing);
 
     let header_text = vec![
         Line::from(vec![
@@ -625,27 +1761,47 @@ fn create_header(diff_result: &DiffResult) -> Paragraph<'static> {
         .wrap(Wrap { trim: true })
 }
 
-fn create_diff_content(app: &App) -> List<'static> {
+/// The list's rows, each fitting its path in `width` columns unless paths are shown in full
+fn create_diff_content(app: &App, width: usize) -> List<'static> {
     let mut list_items = Vec::new();
 
-    for entry in app.diff_result.entries.iter() {
+    for entry in app.visible_order.iter().map(|&index| &app.diff_result.entries()[index]) {
         let color = match entry.diff_type {
             DiffType::Added => Color::Green,
             DiffType::Removed => Color::Red,
+            // Probably a typo, so less prominent than a rewritten value
+            DiffType::Modified if entry.near_match => Color::Rgb(150, 150, 60),
             DiffType::Modified => Color::Yellow,
             DiffType::ArrayItemChanged => Color::Cyan,
             DiffType::ArrayReordered => Color::Magenta,
             DiffType::Ignored => Color::DarkGray,
+            DiffType::Moved => Color::Blue,
+            DiffType::Tolerated => Color::LightBlue,
+            DiffType::KeyOrderChanged => Color::LightMagenta,
         };
 
-        let entry_text = if app.use_readable_format {
-            entry.format_readable()
-        } else {
-            format!("{}", entry)
-        };
-        list_items.push(ListItem::new(Line::from(vec![
-            Span::styled(entry_text, Style::default().fg(color)),
-        ])));
+        let entry_text = entry.format_with_options(app.output_style, &ValueRenderOptions { max_value_chars: LIST_MAX_VALUE_CHARS, preview: true, ..ValueRenderOptions::default() });
+
+        // Merged results show which comparison each entry came from
+        let mut spans = Vec::new();
+        if let Some(source) = &entry.source {
+            spans.push(Span::styled(format!("[{}] ", source), Style::default().fg(Color::Blue)));
+        }
+        if let Some(status) = app.annotation(entry).and_then(|a| a.status) {
+            spans.push(Span::styled(format!("[{}] ", status), Style::default().fg(triage_color(status)).add_modifier(Modifier::BOLD)));
+        }
+        let used = spans.iter().map(Span::width).sum();
+        push_entry_spans(&mut spans, entry_text, &escape_control(&entry.path), Style::default().fg(color), match app.full_paths {
+            true => usize::MAX,
+            false => width.saturating_sub(used),
+        });
+        if let Some(note) = app.annotation(entry).and_then(|a| a.note.as_ref()) {
+            spans.push(Span::styled(format!("  ✎ {}", note), Style::default().fg(Color::Gray).add_modifier(Modifier::ITALIC)));
+        }
+        if app.full_paths {
+            spans = skip_columns(spans, app.list_scroll);
+        }
+        list_items.push(ListItem::new(Line::from(spans)));
     }
 
     List::new(list_items)
@@ -653,29 +1809,208 @@ fn create_diff_content(app: &App) -> List<'static> {
         .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD))
 }
 
+/// Push the text of an entry whose `path` it holds, the path cut to fit `width` columns
+/// with what follows it and its leaf in bold, see [`short_path`]
+fn push_entry_spans(spans: &mut Vec<Span<'static>>, text: String, path: &str, style: Style, width: usize) {
+    let Some(start) = text.find(path) else {
+        spans.push(Span::styled(text, style));
+        return;
+    };
+    let (kind, rest) = (&text[..start], &text[start + path.len()..]);
+    let short = shorten(path, width.saturating_sub(kind.width() + rest.width().min(LIST_MIN_REST_COLUMNS)));
+    spans.push(Span::styled(kind.to_string(), style));
+    spans.push(Span::styled(short.parent, style));
+    spans.push(Span::styled(short.leaf.to_string(), style.add_modifier(Modifier::BOLD)));
+    spans.push(Span::styled(rest.to_string(), style));
+}
+
+/// `spans` without their first `columns` terminal columns; a wide character cut in two
+/// is left out
+fn skip_columns(spans: Vec<Span<'static>>, columns: usize) -> Vec<Span<'static>> {
+    let mut skipped = 0;
+    spans.into_iter()
+        .filter_map(|span| {
+            if skipped >= columns {
+                return Some(span);
+            }
+            let start = span.content.grapheme_indices(true)
+                .find(|(_, grapheme)| {
+                    let done = skipped >= columns;
+                    skipped += if done { 0 } else { grapheme.width() };
+                    done
+                })
+                .map(|(start, _)| start)?;
+            Some(Span::styled(span.content[start..].to_string(), span.style))
+        })
+        .collect()
+}
+
 fn create_footer(app: &App) -> Paragraph<'static> {
-    let format_mode = if app.use_readable_format { "Readable (default)" } else { "Symbols" };
+    let text = footer_lines(app, list_nav_info(app));
+
+    Paragraph::new(text)
+        .block(Block::default().borders(Borders::ALL).title("Controls"))
+        .wrap(Wrap { trim: true })
+}
+
+/// Navigation line of the list view's footer
+fn list_nav_info(app: &App) -> String {
     let view_mode = match app.view_mode {
         ViewMode::List => "List",
         ViewMode::SplitScreen => "Split",
     };
-    let nav_info = format!(
-        "Entry {}/{} | View: {} | Format: {} | j/k: navigate, v: view, r: format, h/?: help, q: quit",
-        if app.diff_result.entries.is_empty() { 0 } else { app.current_index + 1 },
-        app.diff_result.entries.len(),
+    // The path in full, which the row may show shortened
+    let selected = app.selected_entry()
+        .map(|index| format!(": {}", escape_control(&app.diff_result.entries()[index].path)))
+        .unwrap_or_default();
+    format!(
+        "Entry {}/{}{} | View: {} | Format: {} | {} | j/k: navigate, Enter: details, o: sort, 1-9: filter, /: query, a/A: triage/note, I/W: ignore rule, f: full paths, v: view, r: format, s: stats, h/?: help, q: quit",
+        if app.visible_order.is_empty() { 0 } else { app.current_index + 1 },
+        app.visible_order.len(),
+        selected,
         view_mode,
-        format_mode
-    );
+        app.output_style.label(),
+        order_info(app)
+    )
+}
 
-    let text = vec![
-        Line::from(vec![
-            Span::raw(nav_info),
-        ]),
+fn triage_color(status: TriageStatus) -> Color {
+    match status {
+        TriageStatus::Expected => Color::Green,
+        TriageStatus::Bug => Color::Red,
+    }
+}
+
+/// Footer text naming the sort mode, the hidden diff types and the query
+fn order_info(app: &App) -> String {
+    let mut info = format!("Sort: {}", app.sort_mode.label());
+    if !app.hidden_types.is_empty() {
+        let hidden: Vec<&str> = DiffType::ALL
+            .iter()
+            .filter(|t| app.hidden_types.contains(t))
+            .map(|t| t.text(app.output_style))
+            .collect();
+        info.push_str(&format!(" | Hidden: {}", hidden.join(" ")));
+    }
+    if let Some((text, _)) = &app.query {
+        info.push_str(&format!(" | Query: {}", text));
+    }
+    info
+}
+
+/// Height of the footer, including its borders
+fn footer_height(app: &App) -> u16 {
+    if app.status_message.is_some() { 4 } else { 3 }
+}
+
+/// Footer lines: the navigation info, preceded by the status message if there is one
+fn footer_lines(app: &App, nav_info: String) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+
+    if let Some(message) = &app.status_message {
+        lines.push(Line::from(Span::styled(message.clone(), Style::default().fg(Color::Yellow))));
+    }
+    lines.push(Line::from(vec![
+        Span::raw(nav_info),
+    ]));
+
+    lines
+}
+
+/// Content of the details popup: the entry, its values in full and the shape of whole arrays
+fn create_details_lines(entry: &DiffEntry, style: OutputStyle) -> Vec<Line<'static>> {
+    let heading = |text: &str| Line::from(Span::styled(text.to_string(), Style::default().add_modifier(Modifier::BOLD)));
+    let line_text = |line: Option<usize>| line.map(|l| format!("L{}", l)).unwrap_or_else(|| "-".to_string());
+    let mut lines = vec![
+        Line::from(format!("Type: {} ({})", entry.diff_type.text(style), entry.diff_type.description())),
+        Line::from(format!("Path: {}", escape_control(&entry.path))),
+        Line::from(format!("Lines: {} / {}", line_text(entry.left_line), line_text(entry.right_line))),
     ];
+    if let Some(right_path) = entry.mapped_to.as_ref().or(entry.right_path.as_ref()) {
+        lines.insert(2, Line::from(format!("Right path: {}", escape_control(right_path))));
+    }
 
-    Paragraph::new(text)
-        .block(Block::default().borders(Borders::ALL).title("Controls"))
-        .wrap(Wrap { trim: true })
+    if let Some(summary) = &entry.array_summary {
+        lines.push(Line::from(""));
+        lines.push(heading("Arrays"));
+        lines.push(Line::from(format!("  Items: {} -> {}", summary.old_len, summary.new_len)));
+        let indices: Vec<String> = summary.differing_indices.iter().map(|i| format!("[{}]", i)).collect();
+        lines.push(Line::from(format!("  First differing indices: {}", indices.join(", "))));
+    }
+
+    for (title, value) in [("Old value", &entry.old_value), ("New value", &entry.new_value)] {
+        if let Some(value) = value {
+            lines.push(Line::from(""));
+            lines.push(heading(title));
+            let text = format_value(value, true);
+            lines.extend(text.lines().map(|line| Line::from(format!("  {}", line))));
+        }
+    }
+
+    lines
+}
+
+/// Lines of the statistics popup: files, timestamp, counts per type and similarity
+fn create_stats_lines(app: &App) -> Vec<Line<'static>> {
+    let result = &app.diff_result;
+    let heading = |text: &str| Line::from(Span::styled(text.to_string(), Style::default().add_modifier(Modifier::BOLD)));
+    let size = |size: Option<u64>| size.map(|s| format!("{} bytes", s)).unwrap_or_else(|| "unknown size".to_string());
+    let mut lines = vec![heading("Files")];
+
+    for (side, path, file_size, sha256) in [
+        ("Left", &result.left_file, result.left_size, &result.left_sha256),
+        ("Right", &result.right_file, result.right_size, &result.right_sha256),
+    ] {
+        let name = path.as_ref().map(|p| p.display().to_string()).unwrap_or_else(|| "Unknown".to_string());
+        lines.push(Line::from(format!("  {}: {} ({})", side, name, size(file_size))));
+        if let Some(sha256) = sha256 {
+            lines.push(Line::from(format!("    sha256 {}", sha256)));
+        }
+    }
+    let timestamp = result.timestamp_rfc3339().unwrap_or_else(|| "not recorded".to_string());
+    lines.push(Line::from(format!("  Timestamp: {}", timestamp)));
+
+    lines.push(Line::from(""));
+    lines.push(heading("Entries by type"));
+    for diff_type in DiffType::ALL {
+        let badge = Style::default().bg(get_semantic_background_color(&Some(diff_type.clone()), false)).fg(Color::White);
+        lines.push(Line::from(vec![
+            Span::raw("  "),
+            Span::styled(format!(" {:<18} ", diff_type.readable_text()), badge),
+            Span::raw(format!(" {}", result.count(diff_type))),
+        ]));
+    }
+    lines.push(Line::from(format!("  Total: {}", result.entries().len())));
+    lines.push(Line::from(format!("  Ignored by rules (not differences): {}", result.count(DiffType::Ignored))));
+    lines.push(Line::from(format!("  Tolerated by rules (not differences): {}", result.count(DiffType::Tolerated))));
+
+    // Share of lines of both files that no difference points at
+    if app.files_loaded {
+        let similarity = result.line_similarity(app.left_content.len(), app.right_content.len());
+        lines.push(Line::from(format!("  Similarity: {:.1}% of lines unchanged", similarity)));
+    }
+
+    if let Some(timings) = &result.timings {
+        lines.push(Line::from(""));
+        lines.push(heading("Timings"));
+        lines.push(Line::from(format!("  {}", timings)));
+    }
+
+    if !result.sources.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(heading("Sources"));
+        for source in &result.sources {
+            lines.push(Line::from(format!("  {}: {} entries", source.source, source.entry_count)));
+        }
+    }
+
+    if !result.warnings.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(heading(&format!("Warnings ({})", result.warnings.len())));
+        lines.extend(result.warnings.iter().map(|w| Line::from(format!("  {}", w))));
+    }
+
+    lines
 }
 
 fn create_help_popup() -> Paragraph<'static> {
@@ -688,15 +2023,34 @@ fn create_help_popup() -> Paragraph<'static> {
         Line::from(Span::styled("List View Navigation:", Style::default().add_modifier(Modifier::BOLD))),
         Line::from("  j, Down Arrow: Move to next diff entry"),
         Line::from("  k, Up Arrow: Move to previous diff entry"),
+        Line::from("  f: Show paths in full, scrolled with Left/Right, or shortened to fit (the footer shows the selected one in full)"),
         Line::from(""),
         Line::from(Span::styled("Split-Screen View Navigation:", Style::default().add_modifier(Modifier::BOLD))),
         Line::from("  j, Down Arrow: Scroll down both files"),
         Line::from("  k, Up Arrow: Scroll up both files"),
         Line::from("  n: Jump to next diff location (in line number order)"),
         Line::from("  N: Jump to previous diff location (in line number order)"),
+        Line::from("  <, >: Narrow or widen the left pane by 5% (20% to 80%); =: split evenly again"),
+        Line::from("  Tab: Focus the other pane; z: Maximize the focused pane, or restore both"),
+        Line::from("  g: Show or hide the row marking where the focused entry would be in the pane without it"),
+        Line::from(""),
+        Line::from("  Enter: Show the details of the current entry"),
+        Line::from("  :, p: Jump to the first entry whose path starts with the typed text (Tab completes)"),
+        Line::from(""),
+        Line::from(Span::styled("Ordering and Filtering (both views):", Style::default().add_modifier(Modifier::BOLD))),
+        Line::from("  o: Cycle sort order (line, path, type, related, delta)"),
+        Line::from("  1-9: Hide or show ADDED, REMOVED, MODIFIED, ARRAY_ITEM_CHANGED, ARRAY_REORDERED, IGNORED, MOVED, TOLERATED, KEY_ORDER_CHANGED"),
+        Line::from("  /: Show only the entries a query selects, e.g. type:removed AND value~\"secret\" (empty: all)"),
+        Line::from(""),
+        Line::from(Span::styled("Triage (both views):", Style::default().add_modifier(Modifier::BOLD))),
+        Line::from("  a: Mark the current entry expected, bug, or neither"),
+        Line::from("  A: Edit the note of the current entry"),
+        Line::from("  I: Generate an ignore rule from the current entry"),
+        Line::from("  W: Write the generated ignore rules to the profile"),
         Line::from(""),
         Line::from(Span::styled("Display Controls:", Style::default().add_modifier(Modifier::BOLD))),
-        Line::from("  r: Toggle between readable (default) and symbols format"),
+        Line::from("  r: Toggle between readable and symbols format"),
+        Line::from("  s: Toggle statistics (files, checksums, counts per type)"),
         Line::from("  h, ?: Toggle help"),
         Line::from("  q, Esc: Quit"),
         Line::from(""),
@@ -704,9 +2058,12 @@ fn create_help_popup() -> Paragraph<'static> {
         Line::from("  + (ADDED): Property exists in target but not in source"),
         Line::from("  - (REMOVED): Property exists in source but not in target"),
         Line::from("  ~ (MODIFIED): Property exists in both but with different values"),
-        Line::from("  ! (ARRAY_ITEM_CHANGED): Array item has changed"),
+        Line::from("  ! (ARRAY_ITEM_CHANGED): Value of an array element has changed (with tag_array_item_changes)"),
         Line::from("  * (ARRAY_REORDERED): Array elements are reordered"),
         Line::from("  ? (IGNORED): Property was ignored based on configuration"),
+        Line::from("  > (MOVED): Array element is unchanged but at another index"),
+        Line::from("  ≈ (TOLERATED): Values differ but a tolerance or normalization rule accepts them (with report_tolerated)"),
+        Line::from("  ^ (KEY_ORDER_CHANGED): Object has the same keys in another order (with report_key_order)"),
         Line::from(""),
         Line::from(Span::styled("Split-Screen Features:", Style::default().add_modifier(Modifier::BOLD))),
         Line::from("  • JSON syntax highlighting"),
@@ -717,6 +2074,8 @@ fn create_help_popup() -> Paragraph<'static> {
         Line::from("    - Yellow: Modified lines"),
         Line::from("    - Cyan: Array item changes"),
         Line::from("    - Magenta: Array reordering"),
+        Line::from("    - Blue: Moved array elements"),
+        Line::from("    - Light blue: Tolerated values"),
         Line::from("  • Side-by-side comparison"),
         Line::from(""),
         Line::from("Note: Mouse operations are not supported"),
@@ -755,3 +2114,867 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         )
         .split(popup_layout[1])[1]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use json_diff_core::DiffEntry;
+    use json_diff_core::{compare_files, compare_json, CompareOptions, JsonPath};
+
+    fn text(lines: &[Line<'static>]) -> Vec<String> {
+        lines.iter()
+            .map(|line| line.spans.iter().map(|span| span.content.as_ref()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_message_expires_after_ticks() {
+        let mut app = App::new(sample_result());
+        app.on_tick();
+        app.show_message("Reloaded", 3);
+
+        app.on_tick();
+        app.on_tick();
+        assert_eq!(app.status_message.as_deref(), Some("Reloaded"));
+        app.on_tick();
+        assert_eq!(app.status_message, None);
+        assert_eq!(app.ticks, 4);
+    }
+
+    #[test]
+    fn test_persistent_message_survives_ticks() {
+        let mut app = App::new(sample_result());
+        app.status_message = Some("File is too large".to_string());
+        for _ in 0..100 {
+            app.on_tick();
+        }
+        assert_eq!(app.status_message.as_deref(), Some("File is too large"));
+    }
+
+    #[test]
+    fn test_handle_key() {
+        let mut app = App::new(sample_result());
+        app.handle_key(KeyCode::Char('s'));
+        assert!(app.stats_visible);
+        // Esc closes the popup rather than quitting
+        app.handle_key(KeyCode::Esc);
+        assert!(!app.stats_visible);
+        assert!(!app.quit);
+        app.handle_key(KeyCode::Esc);
+        assert!(app.quit);
+    }
+
+    fn entry(diff_type: DiffType, path: &str, line: usize) -> DiffEntry {
+        DiffEntry::new(diff_type, path).with_lines(Some(line), Some(line))
+    }
+
+    fn navigation_app() -> App {
+        let mut result = sample_result();
+        result.entries = vec![
+            entry(DiffType::Modified, "$.a", 3),
+            entry(DiffType::Removed, "$.d", 1),
+            entry(DiffType::Added, "$.c", 2),
+            entry(DiffType::Modified, "$.b", 4),
+        ];
+        App::new(result)
+    }
+
+    #[test]
+    fn test_visible_order_follows_sort_mode() {
+        let mut app = navigation_app();
+        assert_eq!(app.visible_order(), &[1, 2, 0, 3]);
+
+        app.handle_key(KeyCode::Char('o'));
+        assert_eq!(app.sort_mode, SortMode::Path);
+        assert_eq!(app.visible_order(), &[0, 3, 2, 1]);
+
+        app.handle_key(KeyCode::Char('o'));
+        assert_eq!(app.sort_mode, SortMode::Type);
+        // Added, Removed, then the Modified entries in line order
+        assert_eq!(app.visible_order(), &[2, 1, 0, 3]);
+
+        app.handle_key(KeyCode::Char('o'));
+        assert_eq!(app.sort_mode, SortMode::Related);
+        // All entries share the parent `$`
+        assert_eq!(app.visible_order(), &[1, 2, 0, 3]);
+
+        app.handle_key(KeyCode::Char('o'));
+        assert_eq!(app.sort_mode, SortMode::Delta);
+        // No entry has a delta, so the line order stays
+        assert_eq!(app.visible_order(), &[1, 2, 0, 3]);
+
+        app.handle_key(KeyCode::Char('o'));
+        assert_eq!(app.sort_mode, SortMode::Line);
+        assert!(text(&footer_lines(&app, order_info(&app)))[0].contains("Sort: line"));
+    }
+
+    #[test]
+    fn test_related_sort_mode_groups_by_parent() {
+        let mut result = sample_result();
+        result.entries = vec![
+            entry(DiffType::Added, "$.config.new", 2),
+            entry(DiffType::Modified, "$.name", 3),
+            entry(DiffType::Removed, "$.config.old", 4),
+        ];
+        let mut app = App::new(result);
+
+        app.set_sort_mode(SortMode::Related);
+        assert_eq!(app.visible_order(), &[2, 0, 1]);
+    }
+
+    #[test]
+    fn test_matching_paths() {
+        let paths: Vec<String> = ["$.a", "$.items[0].id", "$.items[0].name", "$.items[1].id", "$.name"]
+            .iter().map(|path| path.to_string()).collect();
+
+        assert_eq!(matching_paths(&paths, "$.items[0]"), &paths[1..3]);
+        assert_eq!(matching_paths(&paths, "$.n"), &paths[4..]);
+        assert_eq!(matching_paths(&paths, "$"), &paths[..]);
+        assert!(matching_paths(&paths, "$.z").is_empty());
+    }
+
+    #[test]
+    fn test_path_completion_cycles() {
+        let mut result = sample_result();
+        result.entries = vec![
+            entry(DiffType::Modified, "$.items[1].id", 1),
+            entry(DiffType::Added, "$.items[0].id", 2),
+            entry(DiffType::Modified, "$.name", 3),
+            entry(DiffType::Removed, "$.items[0].id", 4),
+        ];
+        let mut app = App::new(result);
+        assert_eq!(app.entry_paths, ["$.items[0].id", "$.items[1].id", "$.name"]);
+
+        app.handle_key(KeyCode::Char(':'));
+        for c in "$.i".chars() {
+            app.handle_key(KeyCode::Char(c));
+        }
+        let text = |app: &App| app.path_input.as_ref().unwrap().text(&app.entry_paths).to_string();
+        app.handle_key(KeyCode::Tab);
+        assert_eq!(text(&app), "$.items[0].id");
+        app.handle_key(KeyCode::Tab);
+        assert_eq!(text(&app), "$.items[1].id");
+        app.handle_key(KeyCode::Tab);
+        assert_eq!(text(&app), "$.items[0].id");
+
+        // Editing continues from the completed path
+        app.handle_key(KeyCode::Backspace);
+        assert_eq!(text(&app), "$.items[0].i");
+        app.handle_key(KeyCode::Tab);
+        assert_eq!(text(&app), "$.items[0].id");
+
+        // Nothing to complete to leaves the text as typed
+        app.handle_key(KeyCode::Char('x'));
+        app.handle_key(KeyCode::Tab);
+        assert_eq!(text(&app), "$.items[0].idx");
+
+        app.handle_key(KeyCode::Esc);
+        assert_eq!(app.path_input, None);
+        assert!(!app.quit);
+    }
+
+    #[test]
+    fn test_path_prompt_escapes_control_characters() {
+        use ratatui::backend::TestBackend;
+
+        let mut result = sample_result();
+        result.entries = vec![entry(DiffType::Modified, "$.\u{1b}[31mred", 1)];
+        let mut app = App::new(result);
+        app.handle_key(KeyCode::Char(':'));
+        app.handle_key(KeyCode::Tab);
+        let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+        terminal.draw(|f| ui(f, &app)).unwrap();
+        let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol.as_str()).collect();
+        assert!(screen.contains("$.\\u{1b}[31mred_"), "{}", screen);
+        assert!(!screen.contains('\u{1b}'));
+    }
+
+    #[test]
+    fn test_query_prompt() {
+        let mut app = navigation_app();
+        let type_query = |app: &mut App, text: &str| {
+            for c in text.chars() {
+                app.handle_key(KeyCode::Char(c));
+            }
+            app.handle_key(KeyCode::Enter);
+        };
+
+        app.handle_key(KeyCode::Char('/'));
+        type_query(&mut app, "type:modified AND NOT line<4");
+        assert_eq!(app.query_input, None);
+        assert_eq!(app.visible_order(), &[3]);
+        assert!(order_info(&app).ends_with("Query: type:modified AND NOT line<4"), "{}", order_info(&app));
+
+        // The prompt opens on the current query; a malformed one keeps it open
+        app.handle_key(KeyCode::Char('/'));
+        assert_eq!(app.query_input.as_deref(), Some("type:modified AND NOT line<4"));
+        type_query(&mut app, " OR");
+        assert!(app.status_message.as_deref().is_some_and(|m| m.contains("expected a predicate")), "{:?}", app.status_message);
+        assert_eq!(app.query_input.as_deref(), Some("type:modified AND NOT line<4 OR"));
+        assert_eq!(app.visible_order(), &[3]);
+        type_query(&mut app, " path:$.d");
+        assert_eq!(app.visible_order(), &[1, 3]);
+
+        // An empty query shows every entry again
+        app.handle_key(KeyCode::Char('/'));
+        app.query_input = Some(String::new());
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.visible_order(), &[1, 2, 0, 3]);
+        assert!(app.set_query("type:").is_err());
+    }
+
+    #[test]
+    fn test_jump_to_path() {
+        let mut app = navigation_app();
+        app.toggle_view_mode();
+        app.handle_key(KeyCode::Char('p'));
+        for c in "$.b".chars() {
+            app.handle_key(KeyCode::Char(c));
+        }
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.path_input, None);
+        assert_eq!(app.focused_diff(), Some(3));
+        assert_eq!(app.selected_entry(), Some(3));
+        assert_eq!(app.left_scroll, 3);
+
+        // The first match in the visible order is selected
+        app.handle_key(KeyCode::Char(':'));
+        app.handle_key(KeyCode::Char('$'));
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.focused_diff(), Some(1));
+
+        app.handle_key(KeyCode::Char(':'));
+        for c in "$.z".chars() {
+            app.handle_key(KeyCode::Char(c));
+        }
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.focused_diff(), Some(1));
+        assert_eq!(app.status_message.as_deref(), Some("No entry path starts with $.z"));
+    }
+
+    #[test]
+    fn test_split_ratio_is_clamped() {
+        let mut app = navigation_app();
+        // The keys only resize the split view
+        app.handle_key(KeyCode::Char('>'));
+        assert_eq!(app.split_percent(), 50);
+
+        app.toggle_view_mode();
+        app.handle_key(KeyCode::Char('>'));
+        assert_eq!(app.split_percent(), 55);
+        assert_eq!(app.status_message.as_deref(), Some("Split 55/45"));
+        for _ in 0..10 {
+            app.handle_key(KeyCode::Char('>'));
+        }
+        assert_eq!(app.split_percent(), 80);
+        for _ in 0..20 {
+            app.handle_key(KeyCode::Char('<'));
+        }
+        assert_eq!(app.split_percent(), 20);
+        assert_eq!(app.status_message.as_deref(), Some("Split 20/80"));
+
+        app.handle_key(KeyCode::Char('='));
+        assert_eq!(app.split_percent(), 50);
+    }
+
+    #[test]
+    fn test_maximized_pane_toggles() {
+        let mut app = navigation_app();
+        app.toggle_view_mode();
+        assert_eq!(app.maximized_pane(), None);
+
+        app.handle_key(KeyCode::Char('z'));
+        assert_eq!(app.maximized_pane(), Some(Pane::Left));
+        // Moving the focus shows the other pane in full
+        app.handle_key(KeyCode::Tab);
+        assert_eq!(app.maximized_pane(), Some(Pane::Right));
+        app.handle_key(KeyCode::Char('z'));
+        assert_eq!(app.maximized_pane(), None);
+
+        // The ratio is kept while a pane is maximized
+        app.handle_key(KeyCode::Char('<'));
+        app.handle_key(KeyCode::Char('z'));
+        app.handle_key(KeyCode::Char('z'));
+        assert_eq!(app.maximized_pane(), None);
+        assert_eq!(app.split_percent(), 45);
+    }
+
+    #[test]
+    fn test_diff_navigation_follows_visible_order() {
+        let mut app = navigation_app();
+        app.toggle_view_mode();
+        assert_eq!(app.focused_diff(), Some(1));
+        app.cycle_sort_mode();
+        // The focused entry stays focused, now at the end of the path order
+        assert_eq!(app.focused_diff(), Some(1));
+        assert_eq!(app.current_sorted_position, 3);
+
+        let mut visited = Vec::new();
+        for _ in 0..4 {
+            app.next_diff();
+            visited.push(app.focused_diff().unwrap());
+        }
+        assert_eq!(visited, app.visible_order());
+
+        app.previous_diff();
+        assert_eq!(app.focused_diff(), Some(2));
+    }
+
+    #[test]
+    fn test_view_result_keeps_visible_entries() {
+        let mut app = navigation_app();
+        app.toggle_type_filter(DiffType::Modified);
+
+        // Listed by line, without the modifications
+        let view = app.view_result();
+        assert_eq!(view.entries.iter().map(|e| e.path.as_str()).collect::<Vec<_>>(), vec!["$.d", "$.c"]);
+        assert_eq!(app.into_result().entries.len(), 4);
+    }
+
+    #[test]
+    fn test_type_filter_recomputes_visible_order() {
+        let mut app = navigation_app();
+        // Select $.a in the list
+        app.next();
+        app.next();
+        assert_eq!(app.selected_entry(), Some(0));
+
+        app.handle_key(KeyCode::Char('3'));
+        assert_eq!(app.hidden_types, vec![DiffType::Modified]);
+        assert_eq!(app.visible_order(), &[1, 2]);
+        // The selected entry is hidden, so the selection moves to the top
+        assert_eq!(app.selected_entry(), Some(1));
+        assert!(order_info(&app).contains("Hidden: MODIFIED"));
+
+        app.next();
+        assert_eq!(app.selected_entry(), Some(2));
+        app.toggle_type_filter(DiffType::Removed);
+        // $.c stays selected
+        assert_eq!(app.visible_order(), &[2]);
+        assert_eq!(app.selected_entry(), Some(2));
+
+        app.toggle_type_filter(DiffType::Added);
+        assert!(app.visible_order().is_empty());
+        assert_eq!(app.focused_diff(), None);
+        app.next_diff();
+        app.next();
+
+        app.toggle_type_filter(DiffType::Modified);
+        assert_eq!(app.visible_order(), &[0, 3]);
+
+        app.handle_key(KeyCode::Char('8'));
+        assert!(app.hidden_types.contains(&DiffType::Tolerated));
+    }
+
+    #[test]
+    fn test_triage_keys() {
+        let mut app = navigation_app();
+        // Select $.c, then mark it and write a note
+        app.next();
+        app.handle_key(KeyCode::Char('a'));
+        app.handle_key(KeyCode::Char('A'));
+        for c in "known".chars() {
+            app.handle_key(KeyCode::Char(c));
+        }
+        // Keys go to the note input while it is open
+        assert!(!app.quit);
+        app.handle_key(KeyCode::Backspace);
+        app.handle_key(KeyCode::Enter);
+
+        let entry = app.diff_result.entries()[2].clone();
+        let annotation = app.annotation(&entry).unwrap();
+        assert_eq!(annotation.status, Some(TriageStatus::Expected));
+        assert_eq!(annotation.note.as_deref(), Some("know"));
+
+        // The annotation follows the entry when the order changes
+        app.cycle_sort_mode();
+        app.handle_key(KeyCode::Char('a'));
+        assert_eq!(app.annotation(&entry).unwrap().status, Some(TriageStatus::Bug));
+
+        // Esc discards the edit
+        app.handle_key(KeyCode::Char('A'));
+        app.handle_key(KeyCode::Char('x'));
+        app.handle_key(KeyCode::Esc);
+        assert_eq!(app.note_input, None);
+        assert_eq!(app.into_result().annotations.get(&entry).unwrap().note.as_deref(), Some("know"));
+    }
+
+    #[test]
+    fn test_footer_labels_follow_output_style() {
+        let mut app = navigation_app();
+        assert!(list_nav_info(&app).contains("Format: Readable |"));
+
+        app.toggle_view_mode();
+        assert!(split_nav_info(&app).contains("Current: REMOVED $.d"));
+
+        app.handle_key(KeyCode::Char('r'));
+        assert_eq!(app.output_style, OutputStyle::Symbols);
+        assert!(split_nav_info(&app).contains("Format: Symbols |"));
+        assert!(split_nav_info(&app).contains("Current: - $.d"));
+
+        app.handle_key(KeyCode::Char('r'));
+        assert!(list_nav_info(&app).contains("Format: Readable |"));
+        assert!(!list_nav_info(&app).contains("(default)"));
+    }
+
+    #[test]
+    fn test_details_show_array_shape() {
+        let old: Vec<serde_json::Value> = (0..500).map(serde_json::Value::from).collect();
+        let new: Vec<serde_json::Value> = (1..513).map(serde_json::Value::from).collect();
+        let entry = DiffEntry {
+            array_summary: Some(json_diff_core::ArraySummary::new(&old, &new)),
+            ..entry(DiffType::Modified, "$.items", 3)
+        };
+        let lines = text(&create_details_lines(&entry, OutputStyle::Readable));
+        assert!(lines.contains(&"Path: $.items".to_string()));
+        assert!(lines.contains(&"  Items: 500 -> 512".to_string()));
+        assert!(lines.contains(&"  First differing indices: [0], [1], [2], [3], [4], [5], [6], [7], [8], [9]".to_string()));
+        assert!(!lines.iter().any(|line| line.contains("Old value")));
+        assert!(!lines.iter().any(|line| line.starts_with("Right path")));
+
+        let entry = DiffEntry { right_path: Some("$.items[4]".to_string()), ..entry };
+        let lines = text(&create_details_lines(&entry, OutputStyle::Readable));
+        assert_eq!(lines[2], "Right path: $.items[4]");
+
+        let mut app = navigation_app();
+        app.handle_key(KeyCode::Enter);
+        assert!(app.details_visible);
+        // Keys other than closing ones leave the selection alone
+        app.handle_key(KeyCode::Char('j'));
+        assert_eq!(app.current_index, 0);
+        app.handle_key(KeyCode::Esc);
+        assert!(!app.details_visible && !app.quit);
+    }
+
+    #[test]
+    fn test_ignore_rules_from_entries() {
+        let mut app = navigation_app();
+        app.diff_result.entries[1].path = "$.d[2]".to_string();
+        app.handle_key(KeyCode::Char('W'));
+        assert!(app.status_message.as_deref().unwrap().starts_with("No pending ignore rules"));
+
+        // The first entry in line order is $.d[2]; choose its generalized form
+        app.handle_key(KeyCode::Char('I'));
+        assert_eq!(app.rule_choice, Some((vec!["$.d[2]".to_string(), "$.d[*]".to_string()], 0)));
+        app.handle_key(KeyCode::Char('j'));
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.rule_choice, None);
+        assert_eq!(app.pending_rules, vec!["$.d[*]"]);
+
+        app.handle_key(KeyCode::Char('W'));
+        assert!(app.status_message.as_deref().unwrap().contains("pass --profile"));
+
+        let profile = std::env::temp_dir().join(format!("json-diff-display-rules-{}.toml", std::process::id()));
+        std::fs::write(&profile, "# team rules\nignore = [\"$.id\"]\n").unwrap();
+        app.set_profile_path(Some(profile.clone()));
+        app.handle_key(KeyCode::Char('W'));
+        let content = std::fs::read_to_string(&profile).unwrap();
+        std::fs::remove_file(&profile).unwrap();
+
+        assert!(app.status_message.as_deref().unwrap().starts_with("Added 1 ignore rule(s)"));
+        assert!(app.pending_rules.is_empty());
+        assert!(content.starts_with("# team rules\n"));
+        assert!(content.contains(r#""$.id", "$.d[*]""#));
+    }
+
+    #[test]
+    fn test_screen_layout() {
+        assert_eq!(screen_layout(0, 0), ScreenLayout::TooSmall);
+        assert_eq!(screen_layout(19, 40), ScreenLayout::TooSmall);
+        assert_eq!(screen_layout(120, 9), ScreenLayout::TooSmall);
+        assert_eq!(screen_layout(MIN_WIDTH, MIN_HEIGHT), ScreenLayout::Stacked);
+        assert_eq!(screen_layout(79, 24), ScreenLayout::Stacked);
+        assert_eq!(screen_layout(80, 24), ScreenLayout::Full);
+        assert_eq!(screen_layout(300, 100), ScreenLayout::Full);
+    }
+
+    #[test]
+    fn test_window_title() {
+        let mut result = sample_result();
+        result.entries = vec![
+            entry(DiffType::Removed, "$.a", 1),
+            entry(DiffType::Modified, "$.b", 2),
+            entry(DiffType::Ignored, "$.c", 3),
+            entry(DiffType::Removed, "$.d", 4),
+        ];
+        assert_eq!(window_title(&result, &[0, 1, 2, 3]), "json-diff: 3 diffs (2 removed)");
+        assert_eq!(window_title(&result, &[1]), "json-diff: 1 diff");
+        assert_eq!(window_title(&result, &[]), "json-diff: 0 diffs");
+
+        result.left_label = Some("left.json".to_string());
+        result.right_file = Some(PathBuf::from("/tmp/data/right\x07.json"));
+        assert_eq!(window_title(&result, &[0, 3]), "json-diff: 2 diffs (2 removed) \u{2014} left.json vs right.json");
+
+        // The title follows the filters of the app
+        let mut app = App::new(result);
+        assert!(app.window_title().starts_with("json-diff: 3 diffs (2 removed)"));
+        app.toggle_type_filter(DiffType::Removed);
+        assert!(app.window_title().starts_with("json-diff: 1 diff \u{2014}"));
+    }
+
+    #[test]
+    fn test_title_supported() {
+        assert!(title_supported(Some("xterm-256color")));
+        assert!(title_supported(Some("tmux-256color")));
+        assert!(!title_supported(None));
+        assert!(!title_supported(Some("dumb")));
+        assert!(!title_supported(Some("linux")));
+        assert!(!title_supported(Some("vt100")));
+    }
+
+    #[test]
+    fn test_draws_at_every_size() {
+        use ratatui::backend::TestBackend;
+
+        let draw = |app: &App, width: u16, height: u16| -> String {
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+            terminal.draw(|f| ui(f, app)).unwrap();
+            terminal.backend().buffer().content().iter().map(|c| c.symbol.as_str()).collect()
+        };
+
+        let text = draw(&App::new(sample_result()), 19, 5);
+        assert_eq!(text[..19].trim_end(), "terminal too small");
+
+        for (width, height) in [(1, 1), (19, 5), (39, 30), (40, 10), (60, 12), (80, 24)] {
+            for view_mode in [ViewMode::List, ViewMode::SplitScreen] {
+                let mut app = App::new(sample_result());
+                app.view_mode = view_mode;
+                app.help_visible = true;
+                let text = draw(&app, width, height);
+                if screen_layout(width, height) == ScreenLayout::TooSmall {
+                    // Only the message, as much of it as fits
+                    assert!(!text.contains("Help"), "{}x{}", width, height);
+                    assert!(text.starts_with('t'), "{}x{}", width, height);
+                } else {
+                    assert!(!text.contains("too small"), "{}x{}", width, height);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_terminal_restored_on_panic() {
+        use ratatui::backend::TestBackend;
+        use std::sync::atomic::AtomicUsize;
+
+        static TEARDOWNS: AtomicUsize = AtomicUsize::new(0);
+        fn count_teardown() -> io::Result<()> {
+            TEARDOWNS.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        let mut app = App::new(sample_result());
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        PANIC_ON_DRAW.with(|panics| panics.set(true));
+        let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = TerminalGuard::with_teardown(count_teardown);
+            run_main_loop(&mut terminal, &mut app, DEFAULT_TICK_RATE, false)
+        }));
+        PANIC_ON_DRAW.with(|panics| panics.set(false));
+
+        let message = outcome.unwrap_err();
+        assert_eq!(message.downcast_ref::<&str>(), Some(&"drawing failed on purpose"));
+        // Run by the panic hook, and not again as the guard was dropped
+        assert_eq!(TEARDOWNS.load(Ordering::SeqCst), 1);
+
+        // Restoring explicitly tears down once too
+        TerminalGuard::with_teardown(count_teardown).restore().unwrap();
+        assert_eq!(TEARDOWNS.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_list_rows_preview_values() {
+        use ratatui::backend::TestBackend;
+
+        let value = serde_json::json!({"details": {"watts": 40}, "name": "lamp", "tags": ["a", "b"]});
+        let mut result = sample_result();
+        result.entries = vec![DiffEntry::added("$.product", value)];
+        let app = App::new(result);
+        let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+        terminal.draw(|f| ui(f, &app)).unwrap();
+        let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol.as_str()).collect();
+        assert!(screen.contains("[ADDED] $.product: {details{…}, name, tags[2]}"), "{}", screen);
+
+        // The details popup shows the value in full
+        let details = text(&create_details_lines(&app.diff_result.entries[0], OutputStyle::Readable));
+        assert!(details.iter().any(|line| line.contains("\"watts\": 40")), "{:?}", details);
+    }
+
+    #[test]
+    fn test_list_rows_shorten_long_paths() {
+        use ratatui::backend::TestBackend;
+
+        let path = "$.catalog.categories[0].products[3].variants[12].price";
+        let mut result = sample_result();
+        // The first is selected, which makes its whole row bold
+        result.entries = vec![
+            DiffEntry::modified("$.name", serde_json::json!("a"), serde_json::json!("b")).with_lines(Some(2), Some(2)),
+            DiffEntry::modified(path, serde_json::json!(10), serde_json::json!(12)).with_lines(Some(40), Some(40)),
+        ];
+        let mut app = App::new(result);
+        let draw = |app: &App, width: u16| {
+            let mut terminal = Terminal::new(TestBackend::new(width, 24)).unwrap();
+            terminal.draw(|f| ui(f, app)).unwrap();
+            terminal.backend().buffer().clone()
+        };
+        let row = |buffer: &ratatui::buffer::Buffer| -> String {
+            (0..buffer.area.width).map(|x| buffer.get(x, 6).symbol.as_str()).collect::<String>()
+        };
+
+        // Shortened anew at each width, the leaf in bold
+        let buffer = draw(&app, 50);
+        assert!(row(&buffer).contains("[MODIFIED] $…[12].price (L40:L40): 10 -> 12"), "{}", row(&buffer));
+        let leaf = (0..buffer.area.width).find(|&x| (x..x + 6).map(|x| buffer.get(x, 6).symbol.as_str()).collect::<String>() == ".price").unwrap();
+        assert!(buffer.get(leaf, 6).modifier.contains(Modifier::BOLD));
+        assert!(!buffer.get(leaf - 1, 6).modifier.contains(Modifier::BOLD));
+        assert!(row(&draw(&app, 80)).contains("[MODIFIED] $…[0].products[3].variants[12].price (L40:L40)"));
+        assert!(row(&draw(&app, 120)).contains(&format!("[MODIFIED] {} (L40:L40)", path)));
+        // The footer has it in full once selected
+        assert!(list_nav_info(&app).starts_with("Entry 1/2: $.name |"));
+        app.handle_key(KeyCode::Char('j'));
+        assert!(list_nav_info(&app).starts_with(&format!("Entry 2/2: {} |", path)));
+
+        // In full, scrolled sideways
+        app.handle_key(KeyCode::Char('f'));
+        assert!(row(&draw(&app, 50)).contains("[MODIFIED] $.catalog.categories[0].products"));
+        app.handle_key(KeyCode::Right);
+        app.handle_key(KeyCode::Right);
+        assert!(row(&draw(&app, 50)).contains("│alog.categories[0].products"), "{}", row(&draw(&app, 50)));
+        app.handle_key(KeyCode::Left);
+        app.handle_key(KeyCode::Left);
+        app.handle_key(KeyCode::Left);
+        assert_eq!(app.list_scroll, 0);
+        app.handle_key(KeyCode::Char('f'));
+        app.handle_key(KeyCode::Right);
+        assert_eq!((app.full_paths, app.list_scroll), (false, 0));
+    }
+
+    #[test]
+    fn test_skip_columns() {
+        let spans = vec![Span::raw("ab"), Span::styled("名前x", Style::default().fg(Color::Red)), Span::raw("yz")];
+        let shown = |columns| skip_columns(spans.clone(), columns).iter().map(|span| span.content.to_string()).collect::<Vec<_>>();
+        assert_eq!(shown(0), ["ab", "名前x", "yz"]);
+        assert_eq!(shown(2), ["名前x", "yz"]);
+        assert_eq!(shown(4), ["前x", "yz"]);
+        // Half of a wide character is left out with it
+        assert_eq!(shown(3), ["前x", "yz"]);
+        assert_eq!(shown(7), ["yz"]);
+        assert_eq!(shown(20), Vec::<String>::new());
+        assert_eq!(skip_columns(spans.clone(), 4)[0].style.fg, Some(Color::Red));
+    }
+
+    #[test]
+    fn test_unicode_fixture() {
+        use ratatui::backend::TestBackend;
+
+        let fixture = |name: &str| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../tests/fixtures").join(name);
+        let result = compare_files(fixture("unicode_left.json"), fixture("unicode_right.json"), &CompareOptions::default()).unwrap();
+        let mut app = App::new(result);
+
+        // Highlighting splits a line without losing or reordering its characters
+        for line in app.pane_lines(true) {
+            let spans = highlight_json_line(line);
+            assert_eq!(spans.iter().map(|span| span.content.as_ref()).collect::<String>(), *line);
+        }
+        let spans = highlight_json_line("  \"🎉 party\": {");
+        assert_eq!((spans[1].content.as_ref(), spans[1].style.fg), ("\"🎉 party\"", Some(Color::Cyan)));
+
+        // Both views draw at any width, the wide characters taking two cells
+        for view_mode in [ViewMode::List, ViewMode::SplitScreen] {
+            for width in [40, 57, 100] {
+                app.view_mode = view_mode;
+                let mut terminal = Terminal::new(TestBackend::new(width, 24)).unwrap();
+                terminal.draw(|f| ui(f, &app)).unwrap();
+                let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol.as_str()).collect();
+                assert!(screen.contains("名"), "{}", screen);
+            }
+        }
+        let rows = create_diff_content(&app, 40);
+        assert_eq!(rows.len(), app.visible_order().len());
+
+        // Backspace removes a letter with its combining mark
+        app.handle_key(KeyCode::Char('/'));
+        for c in "cafe\u{301}".chars() {
+            app.handle_key(KeyCode::Char(c));
+        }
+        app.handle_key(KeyCode::Backspace);
+        assert_eq!(app.query_input.as_deref(), Some("caf"));
+    }
+
+    fn sample_result() -> DiffResult {
+        DiffResult {
+            left_file: None,
+            right_file: None,
+            timestamp: None,
+            entries: Vec::new(),
+            warnings: Vec::new(),
+            left_sha256: None,
+            right_sha256: None,
+            left_size: None,
+            right_size: None,
+            sources: Vec::new(),
+            annotations: Default::default(),
+            left_label: None,
+            right_label: None,
+            rule_stats: Vec::new(),
+            generator: String::new(),
+            hostname: None,
+            timings: None,
+            left_missing: false,
+            right_missing: false,
+            selections: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_stats_lines() {
+        let dir = std::env::temp_dir().join(format!("json-diff-display-stats-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (left, right) = (dir.join("left.json"), dir.join("right.json"));
+        let left_content = "{\n  \"a\": 1,\n  \"b\": 2,\n  \"c\": 3\n}\n";
+        std::fs::write(&left, left_content).unwrap();
+        std::fs::write(&right, "{\n  \"a\": 1,\n  \"b\": 20,\n  \"c\": 30,\n  \"d\": 4\n}\n").unwrap();
+
+        let mut options = CompareOptions::default();
+        options.ignore_paths.push(JsonPath::new("$.c").unwrap());
+        let result = compare_files(&left, &right, &options).unwrap();
+        let app = App::new(result);
+        let lines = text(app.stats_lines());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(lines.iter().any(|l| l.starts_with("  Left: ") && l.ends_with(&format!("left.json ({} bytes)", left_content.len()))));
+        assert!(lines.iter().any(|l| l.starts_with("    sha256 ") && l.len() == 11 + 64));
+        let count = |name: &str| lines.iter()
+            .find(|l| l.trim_start().starts_with(name))
+            .and_then(|l| l.split_whitespace().last())
+            .map(str::to_string);
+        assert_eq!(count("ADDED").as_deref(), Some("1"));
+        assert_eq!(count("MODIFIED").as_deref(), Some("1"));
+        assert_eq!(count("IGNORED").as_deref(), Some("1"));
+        assert_eq!(count("REMOVED").as_deref(), Some("0"));
+        assert!(lines.contains(&"  Total: 3".to_string()));
+        assert!(lines.contains(&"  Ignored by rules (not differences): 1".to_string()));
+        // 11 lines in all; "b" on both sides and "d" on the right are changed
+        assert!(lines.contains(&"  Similarity: 72.7% of lines unchanged".to_string()));
+        assert!(!lines.iter().any(|l| l == "Sources"));
+        assert!(!lines.iter().any(|l| l == "Timings"));
+    }
+
+    #[test]
+    fn test_stats_lines_without_files() {
+        let result = DiffResult { warnings: vec!["something odd".to_string()], ..sample_result() };
+        let lines = text(App::new(result).stats_lines());
+
+        assert!(lines.contains(&"  Left: Unknown (unknown size)".to_string()));
+        assert!(lines.contains(&"  Timestamp: not recorded".to_string()));
+        assert!(!lines.iter().any(|l| l.contains("Similarity")));
+        assert!(lines.contains(&"  something odd".to_string()));
+    }
+
+    #[test]
+    fn test_missing_side_is_anchored_without_lines() {
+        use ratatui::backend::TestBackend;
+
+        let dir = std::env::temp_dir().join(format!("json-diff-display-anchors-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (left, right) = (dir.join("left.json"), dir.join("right.json"));
+        let (left_text, right_text) = (
+            "{\n  \"a\": 1,\n  \"gone\": {\n    \"x\": 1\n  },\n  \"c\": [\n    1\n  ]\n}\n",
+            "{\n  \"a\": 1,\n  \"b\": 2,\n  \"c\": [\n    1,\n    2\n  ]\n}\n",
+        );
+        std::fs::write(&left, left_text).unwrap();
+        std::fs::write(&right, right_text).unwrap();
+
+        // Compared as values, so no entry has a line
+        let values = |text: &str| serde_json::from_str::<serde_json::Value>(text).unwrap();
+        let mut result = compare_json(&values(left_text), &values(right_text), &CompareOptions::default()).unwrap();
+        (result.left_file, result.right_file) = (Some(left), Some(right));
+        assert!(result.iter().all(|entry| entry.left_line.is_none() && entry.right_line.is_none()));
+        let mut app = App::new(result);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let paths: Vec<String> = app.diff_result.iter().map(|entry| entry.path.clone()).collect();
+        let index = |path: &str| paths.iter().position(|entry| entry == path).unwrap();
+
+        // After the preceding sibling on the side missing the entry, its own line unknown
+        assert_eq!((app.anchor_line(index("$.b"), true), app.anchor_line(index("$.b"), false)), (Some(2), Some(3)));
+        assert_eq!(app.anchor_line(index("$.gone"), false), Some(2));
+        assert_eq!(app.anchor_line(index("$.c[1]"), true), Some(7));
+
+        app.toggle_view_mode();
+        app.previous_diff();
+        while app.focused_diff() != Some(index("$.gone")) {
+            app.next_diff();
+        }
+        // The removed member next to the row where it would be
+        assert_eq!((app.left_scroll, app.right_scroll), (1, 1));
+        let screen = |app: &App| {
+            let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+            terminal.draw(|f| ui(f, app)).unwrap();
+            terminal.backend().buffer().content().iter().map(|c| c.symbol.as_str()).collect::<String>()
+        };
+        assert!(screen(&app).contains("··· gone would be here ···"), "{}", screen(&app));
+        app.handle_key(KeyCode::Char('g'));
+        assert!(!screen(&app).contains("would be here"));
+        app.next_diff();
+        app.previous_diff();
+        assert_eq!((app.left_scroll, app.right_scroll), (2, 1));
+    }
+
+    #[test]
+    fn test_line_diff_of_invalid_json_is_shown() {
+        use ratatui::backend::TestBackend;
+
+        let dir = std::env::temp_dir().join(format!("json-diff-display-text-fallback-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (left, right) = (dir.join("left.json"), dir.join("right.json"));
+        std::fs::write(&left, "{\n  \"a\": 1\n  \"b\": 2\n}\n").unwrap();
+        std::fs::write(&right, "{\n  \"a\": 1,\n  \"b\": 2\n}\n").unwrap();
+
+        let options = CompareOptions { text_fallback: true, ..CompareOptions::default() };
+        let result = compare_files(&left, &right, &options).unwrap();
+        let mut app = App::new(result);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let screen = |app: &App| {
+            let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+            terminal.draw(|f| ui(f, app)).unwrap();
+            terminal.backend().buffer().content().iter().map(|c| c.symbol.as_str()).collect::<String>()
+        };
+
+        assert!(screen(&app).contains(r#"[MODIFIED] <line 2> (L2:L2): "  \"a\": 1" -> "  \"a\": 1,""#), "{}", screen(&app));
+        assert!(list_nav_info(&app).starts_with("Entry 1/1: <line 2> |"));
+        // The split view places the entry by its lines, the left file having no line map
+        app.toggle_view_mode();
+        assert_eq!((app.anchor_line(0, true), app.anchor_line(0, false)), (Some(2), Some(2)));
+        assert!(!app.is_formatted(true) && screen(&app).contains("\"a\": 1,"));
+    }
+
+    #[test]
+    fn test_minified_files_are_formatted() {
+        let dir = std::env::temp_dir().join(format!("json-diff-display-minified-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (left, right) = (dir.join("left.json"), dir.join("right.json"));
+        let items = |changed: u64| (0..2_000).map(|i| format!(r#"{{"id":{},"v":{}}}"#, i, if i == 1_500 { changed } else { 0 })).collect::<Vec<_>>().join(",");
+        std::fs::write(&left, format!(r#"{{"items":[{}]}}"#, items(0))).unwrap();
+        std::fs::write(&right, format!("{{\n  \"items\": [{}]\n}}\n", items(7))).unwrap();
+
+        let result = compare_files(&left, &right, &CompareOptions::default()).unwrap();
+        // Both files put the entry on line 1 or 2
+        assert_eq!((result.entries()[0].left_line, result.entries()[0].right_line), (Some(1), Some(2)));
+        let mut app = App::new(result);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(app.is_formatted(true) && app.is_formatted(false));
+
+        app.toggle_view_mode();
+        app.next_diff();
+        app.previous_diff();
+        // Items take four lines each after the `{` and `"items": [` lines
+        let line = 2 + 1_500 * 4 + 3;
+        assert_eq!((app.left_scroll, app.right_scroll), (line - 1, line - 1));
+        assert_eq!(app.pane_lines(true)[line - 1].trim(), "\"v\": 0");
+        assert_eq!(app.pane_lines(false)[line - 1].trim(), "\"v\": 7");
+
+        // A file with short lines is shown as it is
+        let app = App::with_input_limit(sample_result(), None);
+        assert!(!app.is_formatted(true));
+    }
+}
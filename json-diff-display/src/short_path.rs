@@ -0,0 +1,102 @@
+//! Paths of the list's rows shortened to the width they can take, so the leaf of a deeply
+//! nested path stays in view on a narrow terminal
+//!
+//! The root and the last two segments are always kept. The segments nearest them that fit
+//! come before them, and the others are replaced by one `…`, e.g. `$…variants[12].price`.
+//! Segments are found as [`parent_path`] finds them, so a quoted key stays whole whatever
+//! dots or brackets it holds, and widths are counted in terminal columns.
+
+use unicode_width::UnicodeWidthStr;
+use json_diff_core::parent_path;
+
+/// A path cut to a width: the text before its last segment, and that segment
+#[derive(Debug, PartialEq)]
+pub(crate) struct ShortPath<'a> {
+    pub(crate) parent: String,
+    pub(crate) leaf: &'a str,
+}
+
+/// `path` in at most `width` columns, or with only the root and its last two segments
+/// when they take more
+pub(crate) fn shorten(path: &str, width: usize) -> ShortPath<'_> {
+    let segments = segments(path);
+    let Some((&leaf, rest)) = segments.split_last() else {
+        return ShortPath { parent: String::new(), leaf: path };
+    };
+    // Without a segment to leave out, the whole path is shown
+    if path.width() <= width || segments.len() <= 3 {
+        return ShortPath { parent: path[..path.len() - leaf.len()].to_string(), leaf };
+    }
+
+    let (root, middle, before_leaf) = (rest[0], &rest[1..rest.len() - 1], rest[rest.len() - 1]);
+    let mut budget = width.saturating_sub(root.width() + "…".width() + before_leaf.width() + leaf.width());
+    let kept = middle.iter()
+        .rev()
+        .take_while(|segment| match budget.checked_sub(segment.width()) {
+            Some(left) => { budget = left; true }
+            None => false,
+        })
+        .count();
+    let parent = format!("{}…{}{}", root, middle[middle.len() - kept..].concat(), before_leaf);
+    ShortPath { parent, leaf }
+}
+
+/// The segments of `path` in order, the root first: `$`, `.a`, `[0]`, `["b.c"]`
+fn segments(path: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut rest = path;
+    while !rest.is_empty() {
+        let parent = parent_path(rest);
+        segments.push(&rest[parent.len()..]);
+        rest = parent;
+    }
+    segments.reverse();
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEEP: &str = "$.catalog.categories[0].products[3].variants[12].price";
+
+    fn shown(path: &str, width: usize) -> (String, &str) {
+        let short = shorten(path, width);
+        (short.parent, short.leaf)
+    }
+
+    #[test]
+    fn test_segments() {
+        assert_eq!(segments(DEEP), ["$", ".catalog", ".categories", "[0]", ".products", "[3]", ".variants", "[12]", ".price"]);
+        assert_eq!(segments(r#"$["a.b"][2]["x[y]"]"#), ["$", r#"["a.b"]"#, "[2]", r#"["x[y]"]"#]);
+        assert_eq!(segments("$"), ["$"]);
+    }
+
+    #[test]
+    fn test_shorten_across_widths() {
+        // A path that fits is kept whole
+        assert_eq!(shown(DEEP, DEEP.len()), ("$.catalog.categories[0].products[3].variants[12]".to_string(), ".price"));
+        assert_eq!(shown(DEEP, 40), ("$…[0].products[3].variants[12]".to_string(), ".price"));
+        assert_eq!(shown(DEEP, 30), ("$…[3].variants[12]".to_string(), ".price"));
+        assert_eq!(shown(DEEP, 20), ("$…[12]".to_string(), ".price"));
+        // The root and the last two segments stay even past the width
+        assert_eq!(shown(DEEP, 5), ("$…[12]".to_string(), ".price"));
+        for width in [12, 20, 30, 40, 50] {
+            let short = shorten(DEEP, width);
+            assert!(format!("{}{}", short.parent, short.leaf).width() <= width.max(12), "{}", width);
+        }
+    }
+
+    #[test]
+    fn test_shorten_keeps_segments_whole() {
+        let path = r#"$.config["db.primary"].hosts[10]["max.conn"]"#;
+        assert_eq!(shown(path, 30), ("$….hosts[10]".to_string(), r#"["max.conn"]"#));
+        assert_eq!(shown(path, 40), (r#"$…["db.primary"].hosts[10]"#.to_string(), r#"["max.conn"]"#));
+
+        // Too few segments to leave any out
+        assert_eq!(shown("$.a[0]", 2), ("$.a".to_string(), "[0]"));
+        assert_eq!(shown("$", 0), (String::new(), "$"));
+        // Counted in columns, a CJK character taking two
+        assert_eq!(shown("$.商品.分類.名前.値", 14), ("$….名前".to_string(), ".値"));
+    }
+}
@@ -0,0 +1,319 @@
+//! Non-interactive side-by-side rendering of both files
+//!
+//! The rows are built from the same data as the split-screen view: the file
+//! contents and the line numbers of the entries. Lines holding an entry get a
+//! gutter marker (`|` on both sides, `<` on the left only, `>` on the right only),
+//! and unchanged rows further than [`SideBySideOptions::context`] rows from any
+//! marked row are collapsed into a single `...` row.
+
+use json_diff_core::{DiffEntry, DiffResult, DiffType};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::file_label;
+
+/// Width of a line number column, including the space after it
+const LINE_NUMBER_WIDTH: usize = 5;
+
+/// Narrowest text column, whatever the requested width
+const MIN_COLUMN_WIDTH: usize = 8;
+
+/// Options for the side-by-side rendering
+#[derive(Debug, Clone)]
+pub struct SideBySideOptions {
+    /// Total width of a rendered row
+    pub width: usize,
+    /// Unchanged rows kept around each changed row
+    pub context: usize,
+    /// Whether to colorize changed lines with ANSI escapes
+    pub color: bool,
+}
+
+impl Default for SideBySideOptions {
+    fn default() -> Self {
+        Self { width: 160, context: 0, color: false }
+    }
+}
+
+/// One row of the rendering: a line of each file, or padding on one side
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Row {
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+/// Render both files next to each other
+pub fn format_side_by_side(result: &DiffResult, left: &str, right: &str, options: &SideBySideOptions) -> String {
+    let left_lines: Vec<&str> = left.lines().collect();
+    let right_lines: Vec<&str> = right.lines().collect();
+    let column = (options.width.saturating_sub(2 * LINE_NUMBER_WIDTH + 3) / 2).max(MIN_COLUMN_WIDTH);
+
+    let rows = align_rows(result, left_lines.len(), right_lines.len());
+    let changes: Vec<(Option<&DiffEntry>, Option<&DiffEntry>)> = rows.iter()
+        .map(|row| (
+            row.left.and_then(|line| changed_entry(result, line, true)),
+            row.right.and_then(|line| changed_entry(result, line, false)),
+        ))
+        .collect();
+    let changed: Vec<usize> = changes.iter()
+        .enumerate()
+        .filter(|(_, (left, right))| left.is_some() || right.is_some())
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut output = String::new();
+    let rule = "-".repeat(2 * (LINE_NUMBER_WIDTH + column) + 3);
+    push_row(&mut output, ("", &file_label(&result.left_file, &result.left_sha256, result.left_missing), None), ' ', ("", &file_label(&result.right_file, &result.right_sha256, result.right_missing), None), column, false);
+    output.push_str(&rule);
+    output.push('\n');
+
+    let mut elided = false;
+    for (index, (row, (left_change, right_change))) in rows.iter().zip(&changes).enumerate() {
+        if !changed.iter().any(|&c| c.abs_diff(index) <= options.context) {
+            if !elided {
+                push_row(&mut output, ("", "...", None), ' ', ("", "...", None), column, false);
+                elided = true;
+            }
+            continue;
+        }
+        elided = false;
+
+        let marker = match (left_change, right_change) {
+            (Some(_), Some(_)) => '|',
+            (Some(_), None) => '<',
+            (None, Some(_)) => '>',
+            (None, None) => ' ',
+        };
+        let side = |line: Option<usize>, lines: &[&str], change: &Option<&DiffEntry>| match line {
+            Some(line) => (line.to_string(), lines[line - 1].to_string(), change.map(|e| e.diff_type.clone())),
+            None => (String::new(), String::new(), None),
+        };
+        let (left_number, left_text, left_type) = side(row.left, &left_lines, left_change);
+        let (right_number, right_text, right_type) = side(row.right, &right_lines, right_change);
+
+        push_row(
+            &mut output,
+            (&left_number, &left_text, left_type),
+            marker,
+            (&right_number, &right_text, right_type),
+            column,
+            options.color,
+        );
+    }
+
+    output
+}
+
+/// The entry whose own line, on the given side, is `line`
+///
+/// Insertion points are skipped: the line they name belongs to a neighbouring node.
+pub(crate) fn entry_on_line(result: &DiffResult, line: usize, is_left: bool) -> Option<(usize, &DiffEntry)> {
+    result.iter()
+        .enumerate()
+        .find(|(_, entry)| !is_insertion_anchor(entry, is_left) && line_of(entry, is_left) == Some(line))
+}
+
+/// Whether the entry's line on this side is where it would be inserted, rather than its own line
+pub(crate) fn is_insertion_anchor(entry: &DiffEntry, is_left: bool) -> bool {
+    matches!(
+        (&entry.diff_type, is_left),
+        (DiffType::Added, true) | (DiffType::Removed, false)
+    )
+}
+
+pub(crate) fn line_of(entry: &DiffEntry, is_left: bool) -> Option<usize> {
+    if is_left { entry.left_line } else { entry.right_line }
+}
+
+/// Like [`entry_on_line`], but ignored entries do not count as changes
+fn changed_entry(result: &DiffResult, line: usize, is_left: bool) -> Option<&DiffEntry> {
+    entry_on_line(result, line, is_left)
+        .map(|(_, entry)| entry)
+        .filter(|entry| entry.diff_type.is_difference())
+}
+
+/// Pair the lines of both files, using the entries' line numbers as sync points
+///
+/// Between two sync points, the lines are paired from the end, so the extra lines
+/// of the longer side (typically an added or removed member) come first.
+fn align_rows(result: &DiffResult, left_count: usize, right_count: usize) -> Vec<Row> {
+    let mut syncs: Vec<(usize, usize)> = result.iter()
+        .filter_map(|entry| match (&entry.diff_type, entry.left_line, entry.right_line) {
+            // The anchor follows the line just before the entry on the other side
+            (DiffType::Added, Some(anchor), Some(right)) => Some((anchor, right.checked_sub(1)?)),
+            (DiffType::Removed, Some(left), Some(anchor)) => Some((left.checked_sub(1)?, anchor)),
+            (_, Some(left), Some(right)) => Some((left, right)),
+            _ => None,
+        })
+        .filter(|&(left, right)| (1..=left_count).contains(&left) && (1..=right_count).contains(&right))
+        .collect();
+    syncs.sort_unstable();
+
+    let mut rows = Vec::new();
+    let (mut next_left, mut next_right) = (1, 1);
+    for (left, right) in syncs.into_iter().chain([(left_count + 1, right_count + 1)]) {
+        // Sync points that cross an earlier one cannot be honored
+        if left < next_left || right < next_right {
+            continue;
+        }
+
+        push_gap(&mut rows, next_left..left, next_right..right);
+        if left <= left_count && right <= right_count {
+            rows.push(Row { left: Some(left), right: Some(right) });
+        }
+        next_left = left + 1;
+        next_right = right + 1;
+    }
+
+    rows
+}
+
+fn push_gap(rows: &mut Vec<Row>, left: std::ops::Range<usize>, right: std::ops::Range<usize>) {
+    let (left_len, right_len) = (left.len(), right.len());
+    let extra = left_len.abs_diff(right_len);
+
+    let mut left = left.map(Some);
+    let mut right = right.map(Some);
+    for _ in 0..extra {
+        if left_len > right_len {
+            rows.push(Row { left: left.next().flatten(), right: None });
+        } else {
+            rows.push(Row { left: None, right: right.next().flatten() });
+        }
+    }
+    rows.extend(left.zip(right).map(|(left, right)| Row { left, right }));
+}
+
+fn push_row(
+    output: &mut String,
+    (left_number, left_text, left_type): (&str, &str, Option<DiffType>),
+    marker: char,
+    (right_number, right_text, right_type): (&str, &str, Option<DiffType>),
+    column: usize,
+    color: bool,
+) {
+    let left = paint(&fit(left_text, column), left_type.filter(|_| color));
+    let right = paint(&fit(right_text, column), right_type.filter(|_| color));
+    let row = format!("{:>4} {} {} {:>4} {}", left_number, left, marker, right_number, right);
+    output.push_str(row.trim_end());
+    output.push('\n');
+}
+
+/// Pad or truncate a line to exactly `width` terminal columns
+///
+/// A character taking two columns, such as a CJK one, counts twice, and a line is cut
+/// between grapheme clusters; one cut before a wide character is padded to the width.
+fn fit(text: &str, width: usize) -> String {
+    let text = text.replace('\t', "    ");
+    let mut fitted = String::new();
+    let mut used = 0;
+
+    if text.width() > width {
+        for grapheme in text.graphemes(true) {
+            if used + grapheme.width() > width - 1 {
+                break;
+            }
+            used += grapheme.width();
+            fitted.push_str(grapheme);
+        }
+        fitted.push('…');
+        used += 1;
+    } else {
+        fitted.push_str(&text);
+        used = text.width();
+    }
+    fitted.push_str(&" ".repeat(width - used));
+    fitted
+}
+
+fn paint(text: &str, diff_type: Option<DiffType>) -> String {
+    let code = match diff_type {
+        None => return text.to_string(),
+        Some(DiffType::Added) => "32",
+        Some(DiffType::Removed) => "31",
+        Some(DiffType::Modified) => "33",
+        Some(_) => "36",
+    };
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use json_diff_core::{compare_strings, CompareOptions};
+
+    const LEFT: &str = "{\n  \"a\": 1,\n  \"b\": 2,\n  \"c\": 3,\n  \"d\": 4,\n  \"e\": 5,\n  \"gone\": 6\n}";
+    const RIGHT: &str = "{\n  \"new\": 0,\n  \"a\": 1,\n  \"b\": 2,\n  \"c\": 30,\n  \"d\": 4,\n  \"e\": 5\n}";
+
+    fn render(context: usize, color: bool) -> String {
+        let result = compare_strings(LEFT, RIGHT, &CompareOptions::default()).unwrap();
+        let options = SideBySideOptions { width: 60, context, color };
+        format_side_by_side(&result, LEFT, RIGHT, &options)
+    }
+
+    fn row_of<'a>(output: &'a str, text: &str) -> &'a str {
+        output.lines().find(|line| line.contains(text)).unwrap()
+    }
+
+    #[test]
+    fn test_gutter_markers_and_alignment() {
+        let output = render(8, false);
+        // Width 60 leaves text columns of 23 characters
+        let marker = |row: &str| row.chars().nth(LINE_NUMBER_WIDTH + 23 + 1);
+        let right_number = |row: &str| row.get(LINE_NUMBER_WIDTH + 23 + 3..LINE_NUMBER_WIDTH + 23 + 7).map(str::to_string);
+
+        let modified = row_of(&output, "\"c\": 30");
+        assert!(modified.starts_with("   4   \"c\": 3,"));
+        assert_eq!(marker(modified), Some('|'));
+        assert_eq!(right_number(modified).as_deref(), Some("   5"));
+
+        let added = row_of(&output, "\"new\"");
+        assert!(added.starts_with("     "));
+        assert_eq!(marker(added), Some('>'));
+        assert_eq!(right_number(added).as_deref(), Some("   2"));
+
+        let removed = row_of(&output, "\"gone\"");
+        assert!(removed.starts_with("   7 "));
+        assert_eq!(marker(removed), Some('<'));
+        assert_eq!(removed.len(), LINE_NUMBER_WIDTH + 23 + 2);
+
+        // Unchanged lines are paired with their counterpart
+        let unchanged = row_of(&output, "\"a\": 1");
+        assert!(unchanged.starts_with("   2   \"a\": 1,"));
+        assert_eq!(marker(unchanged), Some(' '));
+        assert_eq!(right_number(unchanged).as_deref(), Some("   3"));
+    }
+
+    #[test]
+    fn test_unchanged_rows_elided() {
+        let output = render(0, false);
+        assert!(!output.contains("\"a\": 1"));
+        assert!(!output.contains("\"d\": 4"));
+        assert!(output.contains("\"c\": 30"));
+        assert!(output.lines().any(|line| line.split_whitespace().eq(["...", "..."])));
+
+        let expanded = render(1, false);
+        assert!(expanded.contains("\"b\": 2"));
+        assert!(expanded.contains("\"d\": 4"));
+        assert!(!expanded.lines().any(|line| line.split_whitespace().eq(["...", "..."])));
+    }
+
+    #[test]
+    fn test_color() {
+        assert!(!render(0, false).contains('\x1b'));
+        let colored = render(0, true);
+        assert!(row_of(&colored, "\"c\": 30").contains("\x1b[33m"));
+        assert!(row_of(&colored, "\"new\"").contains("\x1b[32m"));
+    }
+
+    #[test]
+    fn test_fit() {
+        assert_eq!(fit("abc", 5), "abc  ");
+        assert_eq!(fit("abcdef", 4), "abc…");
+        // Wide characters take two columns, and combining marks stay with their letter
+        assert_eq!(fit("名前", 5), "名前 ");
+        assert_eq!(fit("名前です", 6), "名前… ");
+        assert_eq!(fit("cafe\u{301}s", 5), "cafe\u{301}s");
+        assert_eq!(fit("cafe\u{301}s!", 5), "cafe\u{301}…");
+    }
+}
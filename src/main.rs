@@ -1,13 +1,8 @@
-use json_diff_cli::{Args, run};
+use json_diff_cli::{run_cli, Cli};
 use clap::Parser;
 
 fn main() {
-    let args = Args::parse();
-
-    if let Err(e) = run(args) {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
-    }
+    std::process::exit(run_cli(Cli::parse()));
 }
 
 // Usage examples:
@@ -15,3 +10,12 @@ fn main() {
 // json-diff file1.json file2.json -o diff.txt
 // json-diff file1.json file2.json -p profile.toml
 // json-diff file1.json file2.json -i  # Interactive mode
+// json-diff file1.json file2.json --exit-code extended  # 0 identical, 3 only ignored, 1 differ
+// json-diff -- fmt other.json           # Compare a file named like a subcommand
+// json-diff serve --port 8080           # HTTP server
+// json-diff check-profile profile.toml  # Validate a profile
+// json-diff batch manifest.txt --jobs 4  # Compare many pairs
+// json-diff fmt input.json --sort-keys   # Write a file in canonical form
+// json-diff self-test                    # Check the binary against embedded fixtures
+// json-diff renorm report.json -p new.toml  # Apply new rules to a saved report
+// json-diff --list-presets               # Show what each --preset sets
@@ -3,6 +3,9 @@ use std::process::Command;
 use tempfile::tempdir;
 
 /// Helper function to run the JSON diff CLI
+///
+/// The CLI runs inside the temporary directory with relative file names and
+/// without a timestamp, so the output is reproducible.
 fn run_json_diff(file1_content: &str, file2_content: &str, profile_content: Option<&str>) -> String {
     let dir = tempdir().unwrap();
 
@@ -16,9 +19,11 @@ fn run_json_diff(file1_content: &str, file2_content: &str, profile_content: Opti
 
     // Build command
     let mut cmd = Command::new(env!("CARGO_BIN_EXE_json-diff"));
-    cmd.args(&[
-        file1_path.to_str().unwrap(),
-        file2_path.to_str().unwrap(),
+    cmd.current_dir(dir.path());
+    cmd.args([
+        "file1.json",
+        "file2.json",
+        "--no-timestamp",
         "--output",
         output_path.to_str().unwrap(),
     ]);
@@ -27,7 +32,7 @@ fn run_json_diff(file1_content: &str, file2_content: &str, profile_content: Opti
     if let Some(profile) = profile_content {
         let profile_path = dir.path().join("rules.toml");
         fs::write(&profile_path, profile).unwrap();
-        cmd.args(&["--profile", profile_path.to_str().unwrap()]);
+        cmd.args(["--profile", profile_path.to_str().unwrap()]);
     }
 
     // Run the CLI
@@ -37,32 +42,50 @@ fn run_json_diff(file1_content: &str, file2_content: &str, profile_content: Opti
     // Return the output
     let output = fs::read_to_string(&output_path).unwrap();
     println!("Output for test:\n{}", output);
+
+    // The same comparison must give a result holding the invariants, its lines within the files
+    assert!(cmd.args(["--format", "json"]).status().unwrap().success());
+    let mut result: json_diff_core::DiffResult = serde_json::from_str(&fs::read_to_string(&output_path).unwrap()).unwrap();
+    (result.left_file, result.right_file) = (Some(file1_path), Some(file2_path));
+    result.assert_invariants();
     output
 }
 
+/// The complete report `run_json_diff` is expected to produce for the given inputs and entry lines
+fn expected_report(file1_content: &str, file2_content: &str, entries: &[&str]) -> String {
+    let mut report = format!(
+        "DIFF-JSON v1\nLEFT: file1.json\nRIGHT: file2.json\nLEFT-SHA256: {}\nRIGHT-SHA256: {}\nGENERATOR: json-diff {}\n\n",
+        json_diff_core::sha256_hex(file1_content.as_bytes()),
+        json_diff_core::sha256_hex(file2_content.as_bytes()),
+        env!("CARGO_PKG_VERSION"),
+    );
+    for entry in entries {
+        report.push_str(entry);
+        report.push('\n');
+    }
+    report
+}
+
 #[test]
 fn test_cli_basic_comparison() {
-    let output = run_json_diff(
-        r#"{"name": "John", "age": 30}"#,
-        r#"{"name": "Jane", "age": 30}"#,
-        None
-    );
+    let left = r#"{"name": "John", "age": 30}"#;
+    let right = r#"{"name": "Jane", "age": 30}"#;
+    let output = run_json_diff(left, right, None);
 
-    assert!(output.contains("DIFF-JSON v1"));
-    assert!(output.contains("[MODIFIED] $.name (L1:L1): \"John\" -> \"Jane\""));
+    assert_eq!(output, expected_report(left, right, &[
+        "[MODIFIED] $.name (L1:L1): \"John\" -> \"Jane\"",
+    ]));
 }
 
 #[test]
 fn test_cli_with_profile() {
-    let output = run_json_diff(
-        r#"{"name": "John", "timestamp": "2023-01-01"}"#,
-        r#"{"name": "John", "timestamp": "2023-01-02"}"#,
-        Some(r#"ignore = ["$.timestamp"]"#)
-    );
+    let left = r#"{"name": "John", "timestamp": "2023-01-01"}"#;
+    let right = r#"{"name": "John", "timestamp": "2023-01-02"}"#;
+    let output = run_json_diff(left, right, Some(r#"ignore = ["$.timestamp"]"#));
 
-    assert!(output.contains("DIFF-JSON v1"));
-    assert!(output.contains("[IGNORED] $.timestamp (L1:L1): [IGNORED]"));
-    assert!(!output.contains("~ $.timestamp"));
+    assert_eq!(output, expected_report(left, right, &[
+        "[IGNORED] $.timestamp (L1:L1): [IGNORED]",
+    ]));
 }
 
 #[test]
@@ -135,9 +158,9 @@ fn test_cli_array_objects() {
 
     assert!(output.contains("DIFF-JSON v1"));
     assert!(output.contains("[MODIFIED] $.products[0].price (L3:L3): 10.99 -> 12.99"));
-    assert!(output.contains("[MODIFIED] $.products[2].id (L3:L3): 3 -> 4"));
-    assert!(output.contains("[MODIFIED] $.products[2].name (L3:L3): \"Product C\" -> \"Product D\""));
-    assert!(output.contains("[MODIFIED] $.products[2].price (L3:L3): 5.99 -> 7.99"));
+    assert!(output.contains("[MODIFIED] $.products[2].id (L5:L5): 3 -> 4"));
+    assert!(output.contains("[MODIFIED] $.products[2].name (L5:L5): \"Product C\" -> \"Product D\""));
+    assert!(output.contains("[MODIFIED] $.products[2].price (L5:L5): 5.99 -> 7.99"));
 }
 
 #[test]
@@ -692,8 +715,8 @@ fn test_unordered_arrays_with_nested_differences() {
     // When not marked as unordered, the tool should report all differences
     assert!(output_ordered.contains("[MODIFIED] $.users[0].id (L4:L4): 1 -> 2"));
     assert!(output_ordered.contains("[MODIFIED] $.users[0].name (L5:L5): \"Alice\" -> \"Bob\""));
-    assert!(output_ordered.contains("[MODIFIED] $.users[1].id (L4:L4): 2 -> 1"));
-    assert!(output_ordered.contains("[MODIFIED] $.users[1].name (L5:L5): \"Bob\" -> \"Alice\""));
+    assert!(output_ordered.contains("[MODIFIED] $.users[1].id (L12:L12): 2 -> 1"));
+    assert!(output_ordered.contains("[MODIFIED] $.users[1].name (L13:L13): \"Bob\" -> \"Alice\""));
 
     // Also check if it reports the theme change
     assert!(output_ordered.contains("~ $.users[0].settings.theme: \"dark\" -> \"dark\"") ||
@@ -729,18 +752,18 @@ fn test_partial_array_differences() {
     println!("Partial array differences output:\n{}", output);
 
     // The tool should report specific changes, not mark the whole array as different
-    assert!(output.contains("[MODIFIED] $.items[1].value (L3:L3): \"original\" -> \"modified\""));
+    assert!(output.contains("[MODIFIED] $.items[1].value (L4:L4): \"original\" -> \"modified\""));
 
     // Check if it reports the removed and added items correctly
     let removed_reported = output.contains("- $.items[3].id") ||
                            output.contains("- $.items[3].value") ||
-                           output.contains("[MODIFIED] $.items[3].id (L3:L3): 4 -> 6") ||
-                           output.contains("[MODIFIED] $.items[3].value (L3:L3): \"to be removed\" -> \"newly added\"");
+                           output.contains("[MODIFIED] $.items[3].id (L6:L6): 4 -> 6") ||
+                           output.contains("[MODIFIED] $.items[3].value (L6:L6): \"to be removed\" -> \"newly added\"");
 
     let added_reported = output.contains("+ $.items[3].id") ||
                          output.contains("+ $.items[3].value") ||
-                         output.contains("[MODIFIED] $.items[3].id (L3:L3): 4 -> 6") ||
-                         output.contains("[MODIFIED] $.items[3].value (L3:L3): \"to be removed\" -> \"newly added\"");
+                         output.contains("[MODIFIED] $.items[3].id (L6:L6): 4 -> 6") ||
+                         output.contains("[MODIFIED] $.items[3].value (L6:L6): \"to be removed\" -> \"newly added\"");
 
     assert!(removed_reported);
     assert!(added_reported);
@@ -855,7 +878,7 @@ fn test_complex_localization() {
     assert!(output.contains("[MODIFIED] $.app.config.database.credentials.password (L16:L16): \"secret\" -> \"updated-secret\""));
     assert!(output.contains("[MODIFIED] $.app.config.features.caching (L21:L21): false -> true"));
     assert!(output.contains("[MODIFIED] $.app.modules[0].settings.maxItems (L30:L30): 100 -> 200"));
-    assert!(output.contains("[MODIFIED] $.app.modules[1].enabled (L28:L28): false -> true"));
+    assert!(output.contains("[MODIFIED] $.app.modules[1].enabled (L36:L36): false -> true"));
 
     // Unchanged values should not be reported
     assert!(!output.contains("$.app.name"));
@@ -911,17 +934,1812 @@ fn test_cli_mixed_changes() {
     // Metadata changes
     assert!(output.contains("[MODIFIED] $.metadata.author (L5:L5): \"John Doe\" -> \"Jane Smith\""));
     assert!(output.contains("[MODIFIED] $.metadata.version (L6:L6): 1 -> 2"));
-    assert!(output.contains("[ADDED] $.metadata.modified (L3:L7): \"2023-02-15\""));
+    assert!(output.contains("[ADDED] $.metadata.modified (L6:L7): \"2023-02-15\""));
 
     // Content changes
     assert!(output.contains("[MODIFIED] $.content.title (L9:L10): \"Original Document\" -> \"Revised Document\""));
-    assert!(output.contains("[MODIFIED] $.content.sections[1].text (L11:L12): \"Main Content\" -> \"Updated Content\""));
-    assert!(output.contains("[MODIFIED] $.content.sections[2].id (L2:L2): \"s3\" -> \"s4\""));
-    assert!(output.contains("[MODIFIED] $.content.sections[2].text (L11:L12): \"Conclusion\" -> \"New Section\""));
+    assert!(output.contains("[MODIFIED] $.content.sections[1].text (L12:L13): \"Main Content\" -> \"Updated Content\""));
+    assert!(output.contains("[MODIFIED] $.content.sections[2].id (L13:L14): \"s3\" -> \"s4\""));
+    assert!(output.contains("[MODIFIED] $.content.sections[2].text (L13:L14): \"Conclusion\" -> \"New Section\""));
     assert!(output.contains("[MODIFIED] $.content.tags[0] (L15:L16): \"draft\" -> \"final\""));
     assert!(output.contains("[MODIFIED] $.content.tags[1] (L15:L16): \"review\" -> \"published\""));
-    assert!(output.contains("[ADDED] $.content.summary (L8:L17): \"A brief summary of the document\""));
+    assert!(output.contains("[ADDED] $.content.summary (L15:L17): \"A brief summary of the document\""));
 
     // Status change
     assert!(output.contains("[MODIFIED] $.status (L17:L19): \"draft\" -> \"published\""));
-}
\ No newline at end of file
+}
+#[test]
+fn test_cli_preprocess_transforms() {
+    let output = run_json_diff(
+        r#"{"envelope": {"id": "a"}, "tags": ["b", "a"], "price": 10.004, "email": "John@Example.com"}"#,
+        r#"{"envelope": {"id": "b"}, "tags": ["a", "b"], "price": 10.001, "email": "john@example.com", "extra": true}"#,
+        Some(r#"
+[[preprocess]]
+op = "remove"
+path = "$.envelope"
+
+[[preprocess]]
+op = "sort_array"
+path = "$.tags"
+
+[[preprocess]]
+op = "round"
+path = "$.price"
+decimals = 2
+
+[[preprocess]]
+op = "lowercase"
+path = "$.email"
+side = "left"
+"#)
+    );
+
+    assert!(!output.contains("$.envelope"));
+    assert!(!output.contains("$.tags"));
+    assert!(!output.contains("$.price"));
+    assert!(!output.contains("$.email"));
+    assert!(output.contains("[ADDED] $.extra"));
+}
+
+/// Helper function to run the JSON diff CLI with extra arguments, returning the raw process output
+fn run_json_diff_raw(file1_content: &str, file2_content: &str, extra_args: &[&str]) -> std::process::Output {
+    let dir = tempdir().unwrap();
+
+    let file1_path = dir.path().join("file1.json");
+    let file2_path = dir.path().join("file2.json");
+
+    fs::write(&file1_path, file1_content).unwrap();
+    fs::write(&file2_path, file2_content).unwrap();
+
+    Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .arg(&file1_path)
+        .arg(&file2_path)
+        .args(extra_args)
+        .output()
+        .unwrap()
+}
+
+/// Path to a file in the test fixtures directory
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+const SAMPLE1_SHA256: &str = "43f4eacf87b97591291cdef8d18cc5c060361d129fb8258fdfc21b469c22ffaa";
+const SAMPLE2_SHA256: &str = "cfefc6b758d23af002bf83dfd265b669a7c96be1222b7038e6b65026be70a32f";
+
+#[test]
+fn test_cli_checksum_header() {
+    let output = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .args([fixture("sample1.json"), fixture("sample2.json")])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("LEFT-SHA256: {}", SAMPLE1_SHA256)));
+    assert!(stdout.contains(&format!("RIGHT-SHA256: {}", SAMPLE2_SHA256)));
+}
+
+#[test]
+fn test_cli_checksum_json_output() {
+    let output = run_json_diff_raw(r#"{"a": 1}"#, r#"{"a": 2}"#, &["--format", "json"]);
+    assert!(output.status.success());
+
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["left_sha256"], json_diff_core::sha256_hex(br#"{"a": 1}"#));
+    assert_eq!(result["right_size"], 8);
+    assert_eq!(result["entries"][0]["path"], "$.a");
+}
+
+#[test]
+fn test_cli_verify_hashes() {
+    let output = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .args([fixture("sample1.json"), fixture("sample2.json")])
+        .args(["--verify", SAMPLE1_SHA256, SAMPLE2_SHA256])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .args([fixture("sample1.json"), fixture("sample2.json")])
+        .args(["--verify", SAMPLE1_SHA256, SAMPLE1_SHA256])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Right file"));
+    assert!(stderr.contains(&format!("expected {}, got {}", SAMPLE1_SHA256, SAMPLE2_SHA256)));
+}
+
+#[test]
+fn test_cli_source_date_epoch() {
+    let run = || Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .args([fixture("sample1.json"), fixture("sample2.json")])
+        .env("SOURCE_DATE_EPOCH", "1700000000")
+        .output()
+        .unwrap();
+
+    let first = run();
+    let second = run();
+    assert!(first.status.success());
+    assert_eq!(first.stdout, second.stdout);
+
+    let stdout = String::from_utf8(first.stdout).unwrap();
+    assert!(stdout.contains("TIMESTAMP: 2023-11-14T22:13:20+00:00"));
+
+    let invalid = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .args([fixture("sample1.json"), fixture("sample2.json")])
+        .env("SOURCE_DATE_EPOCH", "yesterday")
+        .output()
+        .unwrap();
+    assert!(!invalid.status.success());
+    assert!(String::from_utf8(invalid.stderr).unwrap().contains("SOURCE_DATE_EPOCH"));
+}
+
+#[test]
+fn test_cli_no_timestamp() {
+    let output = run_json_diff_raw(r#"{"a": 1}"#, r#"{"a": 2}"#, &["--no-timestamp"]);
+    assert!(output.status.success());
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("TIMESTAMP"));
+
+    let output = run_json_diff_raw(r#"{"a": 1}"#, r#"{"a": 2}"#, &["--no-timestamp", "--format", "json"]);
+    assert!(output.status.success());
+
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(result["timestamp"].is_null());
+}
+
+#[test]
+fn test_cli_compare_with_previous_report() {
+    let dir = tempdir().unwrap();
+    let report_path = dir.path().join("previous.json");
+
+    let previous = run_json_diff_raw(
+        r#"{"name": "John", "age": 30}"#,
+        r#"{"name": "Jane", "age": 31}"#,
+        &["--format", "json"],
+    );
+    assert!(previous.status.success());
+    fs::write(&report_path, &previous.stdout).unwrap();
+
+    // The name change persists on another line, the age change is fixed, the email is new
+    let output = run_json_diff_raw(
+        "{\n  \"name\": \"John\",\n  \"age\": 30,\n  \"email\": \"a\"\n}",
+        "{\n  \"name\": \"Jane\",\n  \"age\": 30,\n  \"email\": \"b\"\n}",
+        &["--compare-with", report_path.to_str().unwrap()],
+    );
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, concat!(
+        "NEW (1):\n",
+        "[MODIFIED] $.email (L4:L4): \"a\" -> \"b\"\n",
+        "RESOLVED (1):\n",
+        "[MODIFIED] $.age (L1:L1): 30 -> 31\n",
+        "PERSISTING (1):\n",
+        "[MODIFIED] $.name (L2:L2): \"John\" -> \"Jane\"\n",
+        "\n",
+    ));
+}
+
+#[test]
+fn test_cli_max_input_size() {
+    let output = run_json_diff_raw(r#"{"a": 1}"#, r#"{"a": 2}"#, &["--max-input-size", "4"]);
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("too large (8 bytes, limit is 4 bytes)"));
+    assert!(stderr.contains("--max-input-size"));
+
+    let output = run_json_diff_raw(r#"{"a": 1}"#, r#"{"a": 2}"#, &["--max-input-size", "1KiB"]);
+    assert!(output.status.success());
+
+    let output = run_json_diff_raw(r#"{"a": 1}"#, r#"{"a": 2}"#, &["--max-input-size", "1XB"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_cli_html_report() {
+    let left = "{\n  \"name\": \"John\",\n  \"age\": 30\n}";
+    let right = "{\n  \"name\": \"<Jane>\",\n  \"age\": 30\n}";
+
+    let output = run_json_diff_raw(left, right, &["--format", "html", "--html-embed-sources"]);
+    assert!(output.status.success());
+
+    let html = String::from_utf8(output.stdout).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("&quot;&lt;Jane&gt;&quot;"));
+    assert!(html.contains("href=\"#left-L2\""));
+    assert!(html.contains("id=\"right-L2\" class=\"line diff\""));
+    assert!(html.contains("class=\"source full\""));
+
+    let output = run_json_diff_raw(left, right, &["--format", "html", "--html-embed-sources", "--html-max-embed-bytes", "10"]);
+    assert!(String::from_utf8(output.stdout).unwrap().contains("class=\"source snippet\""));
+}
+
+#[test]
+fn test_cli_cache_reuses_results() {
+    let dir = tempdir().unwrap();
+    let cache_dir = dir.path().join("cache");
+    let cache_arg = cache_dir.to_str().unwrap();
+
+    let left = r#"{"name": "John"}"#;
+    let right = r#"{"name": "Jane"}"#;
+
+    let first = run_json_diff_raw(left, right, &["--cache-dir", cache_arg]);
+    assert!(first.status.success());
+    assert!(String::from_utf8(first.stdout).unwrap().contains("[MODIFIED] $.name"));
+
+    // Tamper with the cached entry; an identical rerun must reuse it
+    let version_dir = cache_dir.join("v1");
+    let entries: Vec<_> = fs::read_dir(&version_dir).unwrap().map(|e| e.unwrap().path()).collect();
+    assert_eq!(entries.len(), 1);
+    let cached = fs::read_to_string(&entries[0]).unwrap();
+    fs::write(&entries[0], cached.replace("$.name", "$.from_cache")).unwrap();
+
+    let second = run_json_diff_raw(left, right, &["--cache-dir", cache_arg]);
+    assert!(String::from_utf8(second.stdout).unwrap().contains("[MODIFIED] $.from_cache"));
+
+    // Different options are a different cache entry
+    let profile_path = dir.path().join("rules.toml");
+    fs::write(&profile_path, r#"ignore = ["$.name"]"#).unwrap();
+    let other = run_json_diff_raw(left, right, &["--cache-dir", cache_arg, "--profile", profile_path.to_str().unwrap()]);
+    assert!(String::from_utf8(other.stdout).unwrap().contains("[IGNORED] $.name"));
+
+    // Clearing the cache recomputes the result
+    let cleared = run_json_diff_raw(left, right, &["--cache-dir", cache_arg, "--cache-clear"]);
+    assert!(String::from_utf8(cleared.stdout).unwrap().contains("[MODIFIED] $.name"));
+
+    // Labelled as without the cache, whether it misses or hits
+    for args in [&["--cache-dir", cache_arg, "--cache-clear", "-f", "json"][..], &["--cache-dir", cache_arg, "-f", "json"]] {
+        let report: serde_json::Value = serde_json::from_slice(&run_json_diff_raw(left, right, args).stdout).unwrap();
+        assert_eq!((&report["left_label"], &report["right_label"]), (&serde_json::json!("file1.json"), &serde_json::json!("file2.json")));
+    }
+}
+
+#[test]
+fn test_cli_stream_array() {
+    let left = "[\n{\"id\": 1, \"v\": 1},\n{\"id\": 2, \"v\": 2},\n{\"id\": 3, \"v\": 3}\n]";
+    let right = "[\n{\"id\": 2, \"v\": 2},\n{\"id\": 1, \"v\": 1},\n{\"id\": 3, \"v\": 4}\n]";
+
+    let output = run_json_diff_raw(left, right, &["--stream-array", "--no-timestamp"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("DIFF-JSON v1\nLEFT: "));
+    assert!(stdout.contains("[MODIFIED] $[0].id (L2:L2): 1 -> 2"));
+    assert!(stdout.contains("[MODIFIED] $[2].v (L4:L4): 3 -> 4"));
+
+    let output = run_json_diff_raw(left, right, &["--stream-array", "--stream-key", "id"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("$[0]"));
+    assert!(stdout.contains("[MODIFIED] $[2].v (L4:L4): 3 -> 4"));
+
+    let output = run_json_diff_raw(left, right, &["--stream-array", "--format", "json"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_cli_side_by_side() {
+    let left = "{\n  \"a\": 1,\n  \"b\": 2,\n  \"c\": 3\n}\n";
+    let right = "{\n  \"a\": 1,\n  \"b\": 20,\n  \"c\": 3\n}\n";
+
+    let output = run_json_diff_raw(left, right, &["--format", "side-by-side", "--width", "60"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // Not a terminal, so no colors by default
+    assert!(!stdout.contains('\x1b'));
+    assert!(stdout.lines().any(|line| line.starts_with("   3   \"b\": 2,") && line.contains(" |    3   \"b\": 20,")));
+    assert!(!stdout.contains("\"a\": 1"));
+
+    let output = run_json_diff_raw(left, right, &["--format", "side-by-side", "--context", "1", "--color", "always"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\"a\": 1"));
+    assert!(stdout.contains("\x1b[33m"));
+}
+
+#[test]
+fn test_cli_allowed_transition() {
+    let output = run_json_diff(
+        r#"{"status": "PENDING", "state": "ok"}"#,
+        r#"{"status": "QUEUED", "state": "failed"}"#,
+        Some(r#"
+[[allowed_transition]]
+path = "$.status"
+from = ["PENDING"]
+to = ["PROCESSING", "QUEUED"]
+
+[[allowed_transition]]
+path = "$.state"
+from = ["failed"]
+to = ["ok"]
+"#)
+    );
+
+    assert!(output.contains(r#"[IGNORED] $.status (L1:L1): [IGNORED] (allowed transition ["PENDING"] -> ["PROCESSING", "QUEUED"])"#));
+    assert!(output.contains(r#"[MODIFIED] $.state (L1:L1): "ok" -> "failed""#));
+}
+
+#[test]
+fn test_cli_json_report_includes_annotations() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("file1.json"), r#"{"a": 1, "b": 2}"#).unwrap();
+    fs::write(dir.path().join("file2.json"), r#"{"a": 5, "b": 3}"#).unwrap();
+
+    let report = |extra: &[&str]| -> serde_json::Value {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_json-diff"));
+        cmd.current_dir(dir.path()).args(["file1.json", "file2.json", "--no-timestamp", "--format", "json"]).args(extra);
+        let output = cmd.output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        serde_json::from_slice(&output.stdout).unwrap()
+    };
+
+    // No annotations section unless an annotations file is given
+    let plain = report(&[]);
+    assert!(plain.get("annotations").is_none());
+
+    let result: json_diff_core::DiffResult = serde_json::from_value(plain).unwrap();
+    let entry = result.iter().find(|e| e.path == "$.b").unwrap();
+    let mut annotations = json_diff_core::Annotations::default();
+    annotations.cycle_status(entry);
+    annotations.set_note(entry, "rounding");
+    annotations.save(&dir.path().join("triage.json")).unwrap();
+
+    let annotated = report(&["--annotations", "triage.json"]);
+    assert_eq!(
+        annotated["annotations"][entry.fingerprint()],
+        serde_json::json!({"status": "expected", "note": "rounding"})
+    );
+}
+
+#[test]
+fn test_cli_output_style_flags() {
+    let style = |args: &[&str]| {
+        let output = run_json_diff_raw(r#"{"a": 1}"#, r#"{"a": 2}"#, args);
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        if stdout.contains("[MODIFIED] $.a") {
+            "readable"
+        } else {
+            assert!(stdout.contains("~ $.a"), "{}", stdout);
+            "symbols"
+        }
+    };
+
+    assert_eq!(style(&[]), "readable");
+    assert_eq!(style(&["--readable"]), "readable");
+    assert_eq!(style(&["--symbols"]), "symbols");
+    assert_eq!(style(&["-S"]), "symbols");
+    // The last of the two flags wins
+    assert_eq!(style(&["--symbols", "--readable"]), "readable");
+    assert_eq!(style(&["--readable", "--symbols"]), "symbols");
+}
+
+#[test]
+fn test_cli_rejects_malformed_profile_path() {
+    let dir = tempdir().unwrap();
+    let profile_path = dir.path().join("rules.toml");
+    fs::write(&profile_path, "ignore = [\"$users.name\"]\n").unwrap();
+
+    let output = run_json_diff_raw(r#"{"a": 1}"#, r#"{"a": 2}"#, &["--profile", profile_path.to_str().unwrap()]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Invalid JSON path \"$users.name\" at byte 1"), "{}", stderr);
+}
+
+#[test]
+fn test_cli_regex_path_rules() {
+    let left = r#"{"id": 1, "order": {"id": {"history": 1}}, "Email": "a"}"#;
+    let right = r#"{"id": 2, "order": {"id": {"history": 2}}, "Email": "b"}"#;
+
+    // Patterns match the whole path unless anchored = false
+    let output = run_json_diff(left, right, Some(r#"ignore = [{ pattern = '\$\.id' }]"#));
+    assert!(output.contains("[IGNORED] $.id "));
+    assert!(output.contains("[MODIFIED] $.order.id.history"));
+
+    let output = run_json_diff(left, right, Some(r#"ignore = [{ pattern = '\.id', anchored = false }]"#));
+    assert!(output.contains("[IGNORED] $.id "));
+    assert!(output.contains("[IGNORED] $.order.id "));
+
+    let output = run_json_diff(left, right, Some(r#"ignore = ["$.nothing", { pattern = '\$\.email', case_insensitive = true }]"#));
+    assert!(output.contains("[IGNORED] $.Email "));
+    assert!(output.contains("[MODIFIED] $.id "));
+}
+
+#[test]
+fn test_cli_detect_array_moves() {
+    let left = "{\n  \"items\": [\n    \"a\",\n    \"b\",\n    \"c\"\n  ]\n}";
+    let right = "{\n  \"items\": [\n    \"b\",\n    \"c\",\n    \"a\"\n  ]\n}";
+
+    let output = run_json_diff(left, right, Some("detect_array_moves = true"));
+    assert!(output.contains("[MOVED] $.items[0] (L3:L5) → [2]"), "{}", output);
+    assert!(!output.contains("[MODIFIED]"));
+
+    let output = run_json_diff(left, right, None);
+    assert!(output.contains("[MODIFIED] $.items[0]"));
+}
+
+#[test]
+fn test_cli_http_capture() {
+    let left = r#"{"status": 200, "headers": {"Content-Type": "application/json", "Date": "Mon"}, "body": "{\"id\": 1}"}"#;
+    let right = r#"{"status": 200, "headers": {"content-type": "application/json", "date": "Tue"}, "body": "{\"id\": 2}"}"#;
+
+    let output = run_json_diff_raw(left, right, &["--http-capture", "--no-timestamp"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[IGNORED] $.headers.date"), "{}", stdout);
+    assert!(stdout.contains("[MODIFIED] $.body(json).id"));
+    assert!(!stdout.contains("content-type"));
+}
+
+#[cfg(feature = "serve")]
+/// Send a raw HTTP request and return the status line and body of the response
+fn http_request(addr: &str, request: &str) -> (String, String) {
+    use std::io::{Read, Write};
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head.lines().next().unwrap().to_string(), body.to_string())
+}
+
+#[cfg(feature = "serve")]
+fn post(addr: &str, path: &str, body: &str) -> (String, String) {
+    http_request(addr, &format!("POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}", path, body.len(), body))
+}
+
+#[cfg(feature = "serve")]
+#[test]
+fn test_cli_serve() {
+    use std::io::BufRead;
+    use std::process::Stdio;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .args(["serve", "--port", "0", "--max-request-size", "1KiB"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    std::io::BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+    let addr = line.trim().strip_prefix("Listening on http://").unwrap().to_string();
+
+    let request = r#"{"left": {"id": 1, "name": "a"}, "right": {"id": 2, "name": "b"}, "options": {"ignore": ["$.id"]}}"#;
+    let (status, body) = post(&addr, "/compare", request);
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    let result: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let paths: Vec<_> = result["entries"].as_array().unwrap().iter().map(|e| e["path"].as_str().unwrap()).collect();
+    assert_eq!(paths, vec!["$.id", "$.name"]);
+
+    let (status, body) = post(&addr, "/compare/text", request);
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert!(body.contains(r#"[MODIFIED] $.name: "a" -> "b""#), "{}", body);
+
+    let (status, body) = post(&addr, "/compare", "{not json");
+    assert_eq!(status, "HTTP/1.1 400 Bad Request");
+    assert!(body.starts_with(r#"{"error":"Invalid request"#));
+
+    let (status, _) = post(&addr, "/compare", &"x".repeat(2048));
+    assert_eq!(status, "HTTP/1.1 413 Payload Too Large");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn test_cli_check_profile() {
+    let dir = tempdir().unwrap();
+    let profile = dir.path().join("rules.toml");
+    let sample = dir.path().join("sample.json");
+    fs::write(&sample, r#"{"id": 1, "tags": ["a"]}"#).unwrap();
+    let check = |content: &str| {
+        fs::write(&profile, content).unwrap();
+        Command::new(env!("CARGO_BIN_EXE_json-diff"))
+            .arg("check-profile")
+            .arg(&profile)
+            .arg("--against")
+            .arg(&sample)
+            .output()
+            .unwrap()
+    };
+
+    let output = check(r#"ignore = ["$.id"]"#);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("  ignore[0] `$.id`: $.id\n"), "{}", stdout);
+
+    let output = check("ignore = [\"$.tags\"]\nunordered = [\"$.tags\"]");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("error: unordered[0] `$.tags`: never applies"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is not a valid profile"));
+}
+
+#[test]
+fn test_cli_lenient_non_finite_numbers() {
+    let left = "{\n  \"a\": NaN,\n  \"b\": Infinity\n}";
+    let right = "{\n  \"a\": NaN,\n  \"b\": 1\n}";
+
+    let output = run_json_diff_raw(left, right, &["--no-timestamp"]);
+    assert!(!output.status.success());
+
+    let output = run_json_diff_raw(left, right, &["--lenient", "--no-timestamp"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[MODIFIED] $.b (L3:L3): Infinity -> 1"), "{}", stdout);
+    assert!(!stdout.contains("$.a"));
+
+    // The JSON report holds the tokens as plain strings
+    let output = run_json_diff_raw(left, right, &["--lenient", "--no-timestamp", "--format", "json"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains('\u{FDD0}') && !stdout.contains("\\ufdd0"), "{}", stdout);
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(report["entries"][0]["old_value"], "Infinity");
+    assert_eq!(report["entries"][0]["new_value"], 1);
+}
+
+#[test]
+fn test_cli_inputs_that_are_not_json() {
+    let json = r#"{"id": 1}"#;
+    let stderr = |left: &str, right: &str| {
+        let output = run_json_diff_raw(left, right, &["--no-timestamp"]);
+        assert!(!output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    let html = "<!DOCTYPE html>\n<html><body>502 Bad Gateway</body></html>\n";
+    let error = stderr(html, json);
+    assert!(error.contains("left: does not look like JSON; first bytes: `<!DOCTYPE html>\\n…`"), "{}", error);
+    assert!(!error.contains("right:"), "{}", error);
+
+    let error = stderr(json, "service:\n  name: api\n  replicas: 3\n");
+    assert!(error.contains("right: does not look like JSON but like YAML"), "{}", error);
+
+    let error = stderr("{\"id\": 1}\n{\"id\": 2}\n", html);
+    assert!(error.contains("left: holds 2 JSON values one after another, as NDJSON does"), "{}", error);
+    assert!(error.contains("right: does not look like JSON"), "{}", error);
+}
+
+#[test]
+fn test_cli_report_facts() {
+    let dir = tempdir().unwrap();
+    let facts_path = dir.path().join("facts.json");
+    let facts = |left: &str, right: &str, format: &str| {
+        let output = run_json_diff_raw(left, right, &["--report-facts", facts_path.to_str().unwrap(), "--format", format]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        serde_json::from_str::<serde_json::Value>(&fs::read_to_string(&facts_path).unwrap()).unwrap()
+    };
+
+    let equal = facts(r#"{"a": 1}"#, r#"{"a": 1}"#, "text");
+    assert_eq!(equal["total"], 0);
+    assert_eq!(equal["counts"]["Added"], 0);
+    assert_eq!(equal["exit_code"], 0);
+    assert_eq!(equal["left_size"], 8);
+    assert_eq!(equal["left_sha256"], equal["right_sha256"]);
+    assert!(equal["duration_ms"].as_f64().unwrap() > 0.0, "{}", equal);
+    assert_eq!(equal["options_fingerprint"].as_str().unwrap().len(), 64);
+
+    let differing = facts(r#"{"a": 1, "b": 2}"#, r#"{"a": 2, "c": 3}"#, "json");
+    assert_eq!(differing["total"], 3);
+    assert_eq!(differing["counts"]["Added"], 1);
+    assert_eq!(differing["counts"]["Removed"], 1);
+    assert_eq!(differing["counts"]["Modified"], 1);
+    assert_ne!(differing["left_sha256"], differing["right_sha256"]);
+    assert!(differing["duration_ms"].as_f64().unwrap() > 0.0, "{}", differing);
+    assert_eq!(differing["options_fingerprint"], equal["options_fingerprint"]);
+    assert_eq!((equal["classification"].as_str(), differing["classification"].as_str()), (Some("identical"), Some("different")));
+}
+
+#[test]
+fn test_cli_include_spans() {
+    let left = "{\n  \"a\": 1,\n  \"b\": [true]\n}";
+    let right = r#"{"a": 2, "b": [true, null]}"#;
+    let entries = |args: &[&str]| {
+        let output = run_json_diff_raw(left, right, args);
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        report["entries"].as_array().unwrap().clone()
+    };
+
+    let entries_with_spans = entries(&["--format", "json", "--include-spans"]);
+    let modified = entries_with_spans.iter().find(|entry| entry["path"] == "$.a").unwrap();
+    assert_eq!(modified["left_span"], serde_json::json!({"line": 2, "col": 3, "byte_start": 4, "byte_end": 10}));
+    assert_eq!(&left[4..10], "\"a\": 1");
+    assert_eq!(&right[1..7], "\"a\": 2");
+    assert_eq!(modified["right_span"]["byte_start"], 1);
+    let added = entries_with_spans.iter().find(|entry| entry["path"] == "$.b[1]").unwrap();
+    assert!(added.get("left_span").is_none(), "{}", added);
+    assert_eq!(added["right_span"]["byte_end"], right.find("null").unwrap() + 4);
+
+    let plain = entries(&["--format", "json"]);
+    assert!(plain.iter().all(|entry| entry.get("left_span").is_none() && entry.get("right_span").is_none()));
+}
+
+#[test]
+fn test_cli_query() {
+    let left = r#"{"config": {"token": "secret-1", "port": 80}, "name": "a"}"#;
+    let right = r#"{"config": {"port": 81}, "name": "secret"}"#;
+
+    let output = run_json_diff_raw(left, right, &["--query", r#"value~"secret" AND NOT type:modified"#]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[REMOVED] $.config.token"), "{}", stdout);
+    assert!(!stdout.contains("$.name") && !stdout.contains("$.config.port"), "{}", stdout);
+
+    let output = run_json_diff_raw(left, right, &["--query", "path:$.config OR"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid query \"path:$.config OR\" at byte 16: expected a predicate"), "{}", stderr);
+}
+
+#[test]
+fn test_cli_preview_values() {
+    let left = r#"{"a": 1}"#;
+    let right = r#"{"a": 1, "items": [{"id": 1, "name": "x"}, {"id": 2, "name": "y"}]}"#;
+
+    let output = run_json_diff_raw(left, right, &["--preview-values"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[ADDED] $.items (L1:L1): [2 items: {id, name}, …]"), "{}", stdout);
+
+    let output = run_json_diff_raw(left, right, &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(r#"[{"id":1,"name":"x"},{"id":2,"name":"y"}]"#), "{}", stdout);
+}
+
+#[test]
+fn test_cli_table() {
+    let left = "[\n  {\"id\": 1, \"price\": 10.99, \"stock\": 5},\n  {\"id\": 2, \"price\": 3},\n  {\"id\": 3, \"price\": 1}\n]";
+    let right = "[\n  {\"id\": 3, \"price\": 1},\n  {\"id\": 1, \"price\": 12.99, \"stock\": 3},\n  {\"id\": 4, \"price\": 2}\n]";
+
+    let output = run_json_diff_raw(left, right, &["--table", "--key", "id"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("TABLE KEY: id\n\n\
+        row id=1 (L2:L3): price 10.99 -> 12.99; stock 5 -> 3\n\
+        row id=2 (L3): REMOVED\n\
+        row id=4 (L4): ADDED\n\n\
+        SUMMARY\n"), "{}", stdout);
+    assert!(stdout.contains("  Unchanged:  1"), "{}", stdout);
+
+    let output = run_json_diff_raw(left, right, &["--table", "--key", "id", "--format", "json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["rows"][0]["entries"][0]["path"], "$.price");
+    assert_eq!(report["summary"]["added"], 1);
+
+    let output = run_json_diff_raw(left, right, &["--table", "--key", "sku"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("row 0 of the left document has no \"sku\" key"));
+    let output = run_json_diff_raw(left, right, &["--table"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_cli_missing_input_suggests_similar_file() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("config.json"), "{}").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .arg(dir.path().join("config.json"))
+        .arg(dir.path().join("confg.json"))
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("Second argument: No such file"), "{}", stderr);
+    assert!(stderr.contains("did you mean") && stderr.contains("config.json?"), "{}", stderr);
+}
+
+#[test]
+fn test_cli_directory_inputs() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("config.json");
+    fs::write(&file, "{}").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .arg(dir.path())
+        .arg(&file)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("First argument:") && stderr.contains("is a directory, not a JSON file"), "{}", stderr);
+    assert!(!stderr.contains("os error"), "{}", stderr);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .arg(dir.path())
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("are directories") && stderr.contains("json-diff batch"), "{}", stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cli_unreadable_input() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("secret.json");
+    fs::write(&file, "{}").unwrap();
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o000)).unwrap();
+    // Permissions do not apply to root
+    if fs::read(&file).is_ok() {
+        return;
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_json-diff")).arg(&file).arg(&file).output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("First argument: Permission denied reading"), "{}", stderr);
+}
+
+#[test]
+fn test_cli_profile_from_stdin() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let dir = tempdir().unwrap();
+    let (left, right) = (dir.path().join("a.json"), dir.path().join("b.json"));
+    fs::write(&left, r#"{"id": 1, "ts": 100}"#).unwrap();
+    fs::write(&right, r#"{"id": 2, "ts": 200}"#).unwrap();
+
+    let piped = |profile: &str, args: &[&str]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+            .arg(&left)
+            .arg(&right)
+            .args(["--profile", "-"])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(profile.as_bytes()).unwrap();
+        String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap()
+    };
+
+    let stdout = piped("ignore = [\"$.ts\"]\n", &[]);
+    assert!(stdout.contains("[MODIFIED] $.id"), "{}", stdout);
+    assert!(stdout.contains("[IGNORED] $.ts"), "{}", stdout);
+
+    let stdout = piped(r#"{"ignore": ["$.id"]}"#, &["--profile-format", "json"]);
+    assert!(stdout.contains("[MODIFIED] $.ts"), "{}", stdout);
+    assert!(stdout.contains("[IGNORED] $.id"), "{}", stdout);
+}
+
+#[test]
+fn test_cli_profile_map() {
+    let profile = "[[map]]\nleft = \"$.total_cents\"\nright = \"$.amount.cents\"\n";
+    let output = run_json_diff_raw(r#"{"total_cents": 1200}"#, r#"{"amount": {"cents": 1250}}"#, &["--profile-inline", profile, "--format", "json"]);
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["entries"].as_array().unwrap().len(), 1, "{}", result);
+    assert_eq!(result["entries"][0]["path"], "$.total_cents");
+    assert_eq!(result["entries"][0]["mapped_to"], "$.amount.cents");
+
+    let profile = "[[map]]\nleft = \"$.items[*].id\"\nright = \"$.id\"\n";
+    let output = run_json_diff_raw("{}", "{}", &["--profile-inline", profile]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid mapping $.items[*].id -> $.id"));
+}
+
+#[test]
+fn test_cli_profile_inline() {
+    let output = run_json_diff_raw(r#"{"id": 1, "ts": 100}"#, r#"{"id": 2, "ts": 200}"#, &["--profile-inline", r#"ignore = ["$.ts"]"#]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[MODIFIED] $.id"), "{}", stdout);
+    assert!(stdout.contains("[IGNORED] $.ts"), "{}", stdout);
+
+    let output = run_json_diff_raw("{}", "{}", &["--profile-inline", "ignore = ["]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Failed to parse profile"));
+}
+
+#[test]
+fn test_cli_invalid_profile_reported_once() {
+    let dir = tempdir().unwrap();
+    let profile = dir.path().join("bad.toml");
+    fs::write(&profile, "[[tolerance]]\npath = \"$.a\"\n").unwrap();
+    let output = run_json_diff_raw("{}", "{}", &["-p", profile.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("Failed to parse profile").count(), 1, "{}", stderr);
+    assert_eq!(stderr.matches("missing field `within`").count(), 1, "{}", stderr);
+    assert!(!stderr.contains("Failed to read file"), "{}", stderr);
+}
+
+#[test]
+fn test_cli_profile_and_input_both_from_stdin() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("a.json");
+    fs::write(&file, "{}").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .arg("-")
+        .arg(&file)
+        .args(["--profile", "-"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Only one input can be read from stdin"));
+}
+
+#[test]
+fn test_cli_fixture_line_numbers() {
+    let output = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .args([fixture("sample1.json"), fixture("sample2.json")])
+        .args(["--format", "json"])
+        .output()
+        .unwrap();
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let lines: Vec<_> = result["entries"].as_array().unwrap().iter()
+        .map(|e| (e["path"].as_str().unwrap(), e["left_line"].as_u64().unwrap(), e["right_line"].as_u64().unwrap()))
+        .collect();
+
+    // Left lines of added entries and right lines of removed ones are insertion points
+    assert_eq!(lines, vec![
+        ("$.user.name", 4, 4),
+        ("$.user.email", 5, 5),
+        ("$.user.profile.age", 7, 7),
+        ("$.user.profile.location", 8, 8),
+        ("$.user.profile.preferences.theme", 10, 10),
+        ("$.user.profile.preferences.timezone", 12, 13),
+        ("$.user.roles[2]", 15, 16),
+        ("$.user.projects[0].id", 18, 19),
+        ("$.user.projects[0].name", 19, 20),
+        ("$.user.projects[0].status", 20, 21),
+        ("$.user.projects[0].team[0]", 21, 22),
+        ("$.user.projects[0].team[1]", 21, 22),
+        ("$.user.projects[0].team[2]", 21, 22),
+        ("$.user.projects[1].id", 24, 25),
+        ("$.user.projects[1].name", 25, 26),
+        ("$.user.projects[1].status", 26, 27),
+        ("$.user.projects[1].team[0]", 27, 28),
+        ("$.user.projects[1].team[1]", 27, 28),
+        ("$.user.projects[1].team[2]", 27, 28),
+        ("$.user.projects[1].team[3]", 27, 28),
+        ("$.user.projects[2]", 28, 30),
+        ("$.user.settings.privacy", 31, 38),
+        ("$.user.settings.twoFactorAuth", 32, 39),
+        ("$.user.settings.lastLogin", 33, 40),
+        ("$.user.settings.sessionTimeout", 33, 41),
+        ("$.metadata.version", 37, 45),
+        ("$.metadata.updated", 39, 47),
+    ]);
+}
+
+#[test]
+fn test_cli_interactive_without_terminal() {
+    let dir = tempdir().unwrap();
+    let output_path = dir.path().join("report.txt");
+
+    // Stdout is a pipe here, so the report is written as without --interactive
+    let output = run_json_diff_raw(r#"{"a": 1, "b": 1}"#, r#"{"a": 2, "b": 2}"#, &["--interactive"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Warning: no terminal for --interactive"));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[MODIFIED] $.a") && stdout.contains("[MODIFIED] $.b"), "{}", stdout);
+
+    let output = run_json_diff_raw(r#"{"a": 1, "b": 1}"#, r#"{"a": 2, "b": 2}"#, &["-i", "--output", output_path.to_str().unwrap(), "--query", "path:$.b"]);
+    assert!(output.status.success());
+    let report = fs::read_to_string(&output_path).unwrap();
+    assert!(report.contains("$.b") && !report.contains("$.a"), "{}", report);
+
+    let output = run_json_diff_raw("{}", "{}", &["--output-view"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_cli_require_tty() {
+    let output = run_json_diff_raw(r#"{"a": 1}"#, r#"{"a": 2}"#, &["--interactive", "--require-tty"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error: the interactive display needs a terminal on stdin and stdout"));
+
+    let output = run_json_diff_raw("{}", "{}", &["--require-tty"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_cli_merge_patch() {
+    let left = r#"{"name": "a", "tags": ["x", "y"], "old": 1, "meta": {"v": 1}}"#;
+    let right = r#"{"name": "b", "tags": ["x"], "meta": {"v": 1, "note": null}}"#;
+
+    let output = run_json_diff_raw(left, right, &["--format", "merge-patch"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let patch: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(patch, serde_json::json!({"name": "b", "tags": ["x"], "old": null, "meta": {"note": null}}));
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Warning: $.tags is replaced as a whole array"), "{}", stderr);
+    assert!(stderr.contains("Warning: $.meta.note becomes null"), "{}", stderr);
+}
+
+#[test]
+fn test_cli_resource_limits() {
+    let nested = |fill: u32| format!("[{}]", vec![format!("[{}]", vec![fill.to_string(); 200].join(",")); 200].join(","));
+    let (left, right) = (nested(0), nested(1));
+
+    let output = run_json_diff_raw(&left, &right, &["--max-nodes", "5000"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Comparison stopped: visited more than 5000 values (max_nodes)"), "{}", stderr);
+
+    let output = run_json_diff_raw(&left, &right, &["--max-entries", "10"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("found more than 10 differences"));
+
+    // Well under the limits, the report is written as usual
+    let output = run_json_diff_raw(r#"{"a": [1, 2]}"#, r#"{"a": [1, 3]}"#, &["--max-nodes", "5000", "--max-entries", "10", "--max-duration", "30s"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("$.a[1]"));
+}
+
+#[test]
+fn test_cli_keyed_paths() {
+    let left = r#"{"products": [{"id": "P1", "price": 10}, {"id": "P2", "price": 20}]}"#;
+    let right = r#"{"products": [{"id": "P2", "price": 20}, {"id": "P1", "price": 12}]}"#;
+    let profile = "unordered = [\"$.products\"]\nshow_nested_differences = true";
+
+    let output = run_json_diff_raw(left, right, &["--profile-inline", profile, "--keyed-paths", "--format", "json", "--no-metadata"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let entry = &report["entries"][1];
+    assert_eq!(entry["path"], "$.products[id=P1].price");
+    assert_eq!(entry["index_path"], "$.products[0].price");
+    assert_eq!(entry["right_path"], "$.products[1].price");
+
+    // Ignore rules may name the element the same way
+    let profile = format!("{}\nignore = [\"$.products[id=P1].price\"]", profile);
+    let output = run_json_diff_raw(left, right, &["--profile-inline", &profile, "--keyed-paths", "--format", "json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["entries"][1]["diff_type"], "Ignored", "{}", report);
+}
+
+#[test]
+fn test_cli_numeric_deltas() {
+    let left = "{\n  \"requests\": 1000,\n  \"errors\": 0,\n  \"p99\": 10.99,\n  \"host\": \"a\"\n}";
+    let right = "{\n  \"requests\": 850,\n  \"errors\": 12,\n  \"p99\": 12.99,\n  \"host\": \"b\"\n}";
+
+    let output = run_json_diff_raw(left, right, &["--numeric-deltas"]);
+    let text = String::from_utf8_lossy(&output.stdout);
+    assert!(text.contains("$.requests (L2:L2): 1000 -> 850 (-150.00, -15.0%)"), "{}", text);
+    assert!(text.contains("$.errors (L3:L3): 0 -> 12 (+12.00)\n"), "{}", text);
+    assert!(text.contains("$.host (L5:L5): \"a\" -> \"b\"\n"), "{}", text);
+
+    // Largest change first, then the query on it
+    let output = run_json_diff_raw(left, right, &["--sort", "delta", "--format", "json", "--no-metadata"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let paths: Vec<&str> = report["entries"].as_array().unwrap().iter().map(|e| e["path"].as_str().unwrap()).collect();
+    assert_eq!(paths, ["$.requests", "$.errors", "$.p99", "$.host"]);
+    assert_eq!(report["entries"][0]["delta"], -150.0);
+    assert_eq!(report["entries"][0]["delta_pct"], -15.0);
+    assert!(report["entries"][1].get("delta_pct").is_none(), "{}", report);
+
+    let output = run_json_diff_raw(left, right, &["--numeric-deltas", "--query", "delta>10", "--format", "json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let paths: Vec<&str> = report["entries"].as_array().unwrap().iter().map(|e| e["path"].as_str().unwrap()).collect();
+    assert_eq!(paths, ["$.requests", "$.errors"]);
+}
+
+#[test]
+fn test_cli_align() {
+    let left = "{\n  \"a\": 1,\n  \"config\": {\"database\": {\"hostname\": \"x\"}}\n}";
+    let right = "{\n  \"a\": 2,\n  \"config\": {\"database\": {\"hostname\": \"y\"}}\n}";
+
+    let output = run_json_diff_raw(left, right, &["--align"]);
+    let text = String::from_utf8_lossy(&output.stdout);
+    assert!(text.contains(&format!("\n[MODIFIED] {:<26} (L2:L2) 1 -> 2\n", "$.a")), "{}", text);
+    assert!(text.contains("\n[MODIFIED] $.config.database.hostname (L3:L3) \"x\" -> \"y\"\n"), "{}", text);
+
+    let output = run_json_diff_raw(left, right, &["--symbols", "--align=12"]);
+    let text = String::from_utf8_lossy(&output.stdout);
+    assert!(text.contains(&format!("\n~ {:<12} (L2:L2) 1 -> 2\n", "$.a")), "{}", text);
+    assert!(text.contains("\n~ $.….hostname (L3:L3) \"x\" -> \"y\"\n"), "{}", text);
+}
+
+#[test]
+fn test_cli_fmt() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("input.json");
+    fs::write(&input, r#"{"b": [1, {"d": 2, "c": 3}], "a": true}"#).unwrap();
+    let fmt = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_json-diff")).arg("fmt").args(args).output().unwrap();
+
+    // Written to a file, which then passes --check
+    let canonical = dir.path().join("canonical.json");
+    let output = fmt(&[input.to_str().unwrap(), "--sort-keys", "--trailing-newline", "-o", canonical.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let expected = "{\n  \"a\": true,\n  \"b\": [\n    1,\n    {\n      \"c\": 3,\n      \"d\": 2\n    }\n  ]\n}\n";
+    assert_eq!(fs::read_to_string(&canonical).unwrap(), expected);
+
+    let output = fmt(&[canonical.to_str().unwrap(), "--check", "--sort-keys", "--trailing-newline"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(output.stdout.is_empty());
+    let output = fmt(&[input.to_str().unwrap(), "--check", "--sort-keys"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("input.json is not in canonical form"));
+    // Another indentation is another form
+    let output = fmt(&[canonical.to_str().unwrap(), "--check", "--indent", "4", "--trailing-newline"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    // From stdin to stdout
+    let mut child = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .args(["fmt", "--indent", "0"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(br#"{ "y": null, "z": [ 1, 2 ] }"#).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), r#"{"y":null,"z":[1,2]}"#);
+
+    let broken = dir.path().join("broken.json");
+    fs::write(&broken, "{").unwrap();
+    let output = fmt(&[broken.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Failed to format"));
+}
+
+#[test]
+fn test_cli_file_named_like_subcommand() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("fmt"), r#"{"a": 1}"#).unwrap();
+    fs::write(dir.path().join("other.json"), r#"{"a": 2}"#).unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_json-diff")).current_dir(dir.path()).args(args).output().unwrap()
+    };
+
+    // After -- or as a path, a file named fmt is compared instead of formatted
+    for args in [&["--format", "json", "--", "fmt", "other.json"][..], &["--format", "json", "./fmt", "other.json"]] {
+        let output = run(args);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(json["left_file"], args[args.len() - 2]);
+        assert_eq!(json["entries"][0]["path"], "$.a");
+    }
+
+    // A subcommand that fails exits 2, like a failed comparison
+    let output = run(&["check-profile", "missing.toml"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Error: "));
+}
+
+#[test]
+fn test_cli_stdout_closed_early() {
+    use std::process::Stdio;
+
+    let dir = tempdir().unwrap();
+    let (left, right) = (dir.path().join("left.json"), dir.path().join("right.json"));
+    let document = |offset: usize| serde_json::Value::Object((0..20_000).map(|i| (i.to_string(), (i + offset).into())).collect());
+    fs::write(&left, document(0).to_string()).unwrap();
+    fs::write(&right, document(1).to_string()).unwrap();
+
+    // As `json-diff left.json right.json | head -1` does once it has its line
+    let mut child = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .arg(&left)
+        .arg(&right)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_cli_timings() {
+    let left = r#"{"a": 1, "b": 2}"#;
+    let right = r#"{"a": 1, "b": 3}"#;
+    let output = run_json_diff_raw(left, right, &["--timings"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let line = stderr.lines().find(|l| l.starts_with("Timings: ")).expect("no timings line");
+    for phase in ["read ", "parse ", "line map ", "compare ", "sort ", "format ", "total "] {
+        assert!(line.contains(phase), "{:?} not in {:?}", phase, line);
+    }
+
+    let output = run_json_diff_raw(left, right, &["--timings", "--format", "json"]);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(json["timings"]["total_ms"].as_f64().unwrap() >= json["timings"]["compare_ms"].as_f64().unwrap());
+
+    // Without the flag nothing is timed
+    let output = run_json_diff_raw(left, right, &["--format", "json"]);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(json.get("timings").is_none());
+}
+
+#[test]
+fn test_cli_near_match() {
+    let left = r#"{"name": "Jonh Smith", "city": "Amsterdam"}"#;
+    let right = r#"{"name": "John Smith", "city": "Rotterdam"}"#;
+    let output = run_json_diff_raw(left, right, &["--near-match", "0.8"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("$.name (L1:L1): \"Jonh Smith\" -> \"John Smith\" (≈)"), "{}", stdout);
+    assert!(stdout.contains("$.city (L1:L1): \"Amsterdam\" -> \"Rotterdam\"\n"), "{}", stdout);
+
+    let output = run_json_diff_raw(left, right, &["--near-match", "0.4", "--near-match-path", "$.city", "--format", "json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let near: Vec<&str> = report["entries"].as_array().unwrap().iter()
+        .filter(|entry| entry["near_match"] == true)
+        .map(|entry| entry["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(near, ["$.city"]);
+
+    let output = run_json_diff_raw(left, right, &["--near-match", "0.8", "--query", "near:false"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("$.city") && !stdout.contains("$.name"), "{}", stdout);
+
+    let output = run_json_diff_raw(left, right, &["--near-match", "1.5"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("threshold must be from 0 to 1"));
+
+    let stdout = run_json_diff(left, right, Some("[near_match]\nthreshold = 0.8\npaths = [\"$.name\"]\n"));
+    assert!(stdout.contains("\"John Smith\" (≈)"), "{}", stdout);
+}
+
+#[test]
+fn test_cli_limit() {
+    let left = serde_json::to_string_pretty(&(0..3000).map(|i| serde_json::json!({"id": i, "v": i})).collect::<Vec<_>>()).unwrap();
+    let right = serde_json::to_string_pretty(&(0..3000).map(|i| serde_json::json!({"id": i, "v": -i, "w": true})).collect::<Vec<_>>()).unwrap();
+
+    let output = run_json_diff_raw(&left, &right, &["--limit", "100"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().filter(|line| line.starts_with('[')).count(), 100);
+    assert!(
+        stdout.trim_end().ends_with("Showing the first 100 of 5999 entries (3000 added, 2999 modified); drop --limit to see everything"),
+        "{}",
+        &stdout[stdout.len().saturating_sub(300)..]
+    );
+
+    // Nothing is cut with --all, or when stdout is not a terminal
+    for args in [&["--all"][..], &[]] {
+        let output = run_json_diff_raw(&left, &right, args);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(stdout.lines().filter(|line| line.starts_with('[')).count(), 5999);
+        assert!(!stdout.contains("Showing the first"));
+    }
+    assert!(!run_json_diff_raw(&left, &right, &["--limit", "1", "--all"]).status.success());
+}
+
+#[test]
+fn test_cli_missing_as_empty() {
+    let dir = tempdir().unwrap();
+    let (present, missing) = (dir.path().join("present.json"), dir.path().join("missing.json"));
+    fs::write(&present, r#"{"a": 1, "b": 2}"#).unwrap();
+    let run = |left: &std::path::Path, right: &std::path::Path, extra: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_json-diff")).arg(left).arg(right).args(extra).output().unwrap()
+    };
+
+    // Without the flag a missing file fails
+    assert!(!run(&missing, &present, &[]).status.success());
+
+    let output = run(&missing, &present, &["--missing-as-empty"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&format!("LEFT: {} (missing)\n", missing.display())), "{}", stdout);
+    assert!(stdout.contains("[ADDED] $.a (L1): 1") && stdout.contains("[ADDED] $.b (L1): 2"), "{}", stdout);
+
+    let output = run(&present, &missing, &["--missing-as-empty", "--format", "json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["right_missing"], true);
+    assert!(report.get("left_missing").is_none());
+    assert!(report["entries"].as_array().unwrap().iter().all(|entry| entry["diff_type"] == "Removed"), "{}", report);
+
+    // Both missing is still an error
+    assert!(!run(&missing, &missing, &["--missing-as-empty"]).status.success());
+}
+
+#[test]
+fn test_cli_escapes_control_characters() {
+    let left = r#"{"a\nb": 1, "\u001b[31mred": true}"#;
+    let right = r#"{"a\nb": 2}"#;
+    let output = run_json_diff_raw(left, right, &["--no-metadata"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains('\u{1b}'), "{:?}", stdout);
+    assert!(stdout.contains("[MODIFIED] $.a\\nb (L1:L1): 1 -> 2\n"), "{:?}", stdout);
+    assert!(stdout.contains("[REMOVED] $.\\u{1b}[31mred (L1:L1): true\n"), "{:?}", stdout);
+}
+
+#[test]
+fn test_cli_summary_only() {
+    let left = "{\n  \"items\": [1, 2, 3],\n  \"config\": {\"a\": 1, \"b\": 2},\n  \"name\": \"x\"\n}";
+    let right = "{\n  \"items\": [1, 5, 6, 7],\n  \"config\": {\"a\": 1, \"b\": 3},\n  \"name\": \"x\"\n}";
+    let output = run_json_diff_raw(left, right, &["--summary-only", "--summary-top", "1"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // Counts and the busiest path, no entries
+    assert!(!stdout.contains("[MODIFIED]") && !stdout.contains("DIFF-JSON"), "{}", stdout);
+    assert!(stdout.contains("  Total: 4, of which differences: 4\n"), "{}", stdout);
+    assert!(stdout.contains("Similarity: 60.0% of lines unchanged"), "{}", stdout);
+    assert!(stdout.ends_with("Top paths at depth 1\n  Path      Count  Mostly\n  $.items       3  MODIFIED\n"), "{}", stdout);
+
+    let output = run_json_diff_raw(left, right, &["--summary-only", "--summary-depth", "2", "--format", "json"]);
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(summary["counts"]["Added"], 1);
+    let paths: Vec<_> = summary["top_paths"].as_array().unwrap().iter().map(|top| top["path"].as_str().unwrap()).collect();
+    assert_eq!(paths, ["$.config.b", "$.items[1]", "$.items[2]", "$.items[3]"]);
+
+    assert!(!run_json_diff_raw(left, right, &["--summary-only", "--format", "html"]).status.success());
+    assert!(!run_json_diff_raw(left, right, &["--summary-depth", "2"]).status.success());
+}
+
+#[test]
+fn test_cli_batch_summary_only() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("a.json"), r#"{"user": {"id": 1, "name": "a"}}"#).unwrap();
+    fs::write(dir.path().join("b.json"), r#"{"user": {"id": 2, "name": "b"}}"#).unwrap();
+    fs::write(dir.path().join("c.json"), r#"{"user": {"id": 3, "name": "a"}, "extra": true}"#).unwrap();
+    let manifest = dir.path().join("manifest.txt");
+    fs::write(&manifest, "a.json\tb.json\na.json\tc.json\na.json\ta.json\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .arg("batch")
+        .arg(&manifest)
+        .args(["--summary-only", "--no-timestamp"])
+        .output()
+        .unwrap();
+    // Differences still give exit code 1
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("=== Line"), "{}", stdout);
+    assert!(stdout.contains("  Total: 4, of which differences: 4\n"), "{}", stdout);
+    assert!(stdout.contains("  $.user        3  MODIFIED\n  $.extra       1  ADDED\n"), "{}", stdout);
+    assert!(stdout.contains("SUMMARY\n  Pairs:       3\n  Equal:       1\n  Differing:   2\n"), "{}", stdout);
+}
+
+#[test]
+fn test_cli_self_test() {
+    let run = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_json-diff")).arg("self-test").args(args).output().unwrap();
+
+    let output = run(&[]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("json-diff "), "{}", stdout);
+    assert!(stdout.contains("PASS nested_objects\n") && stdout.contains("PASS big_numbers\n"), "{}", stdout);
+    assert!(!stdout.contains("FAIL"), "{}", stdout);
+    assert!(stdout.trim_end().ends_with("passed, 0 failed"), "{}", stdout);
+
+    let output = run(&["--case", "unicode"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().skip(1).collect::<Vec<_>>(), ["PASS unicode_keys", "1 cases: 1 passed, 0 failed"]);
+    assert_eq!(run(&["--case", "nothing-like-this"]).status.code(), Some(2));
+}
+
+#[test]
+fn test_cli_renorm() {
+    let dir = tempdir().unwrap();
+    let left = "{\n  \"price\": 10.0,\n  \"name\": \"a\"\n}";
+    let right = "{\n  \"price\": 10.004,\n  \"name\": \"b\",\n  \"tag\": 1\n}";
+    let output = run_json_diff_raw(left, right, &["--format", "json", "--no-timestamp"]);
+    assert!(output.status.success());
+    let report = dir.path().join("report.json");
+    std::fs::write(&report, &output.stdout).unwrap();
+    let profile = dir.path().join("new-rules.toml");
+    std::fs::write(&profile, "report_tolerated = true\n\n[[tolerance]]\npath = \"$.price\"\nwithin = 0.01\n").unwrap();
+
+    let run = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .args(["renorm", report.to_str().unwrap(), "--profile", profile.to_str().unwrap()])
+        .args(args)
+        .output()
+        .unwrap();
+    let output = run(&[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[TOLERATED] $.price (L2:L2): 10.0 -> 10.004 (tolerance 0.01)"), "{}", stdout);
+    assert!(stdout.contains("[MODIFIED] $.name (L3:L3)") && stdout.contains("[ADDED] $.tag"), "{}", stdout);
+
+    let output = run(&["--format", "json"]);
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let types: Vec<_> = result["entries"].as_array().unwrap().iter().map(|entry| entry["diff_type"].as_str().unwrap()).collect();
+    assert_eq!(types.iter().filter(|t| **t == "Tolerated").count(), 1, "{:?}", types);
+
+    std::fs::write(&report, "not a report").unwrap();
+    let output = run(&[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Failed to parse report"));
+}
+
+#[test]
+fn test_cli_select() {
+    let left = r#"{"spec": {"replicas": 2, "containers": [{"name": "app", "image": "app:1.0"}, {"name": "db", "image": "pg:15"}]}}"#;
+    let right = r#"{"spec": {"replicas": 3, "containers": [{"name": "app2", "image": "app:1.1"}, {"name": "db", "image": "pg:16"}]}}"#;
+
+    let output = run_json_diff_raw(left, right, &["--select", "$.spec.containers[*].image", "--no-timestamp"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("SELECT: $.spec.containers[*].image\n"), "{}", stdout);
+    let entries: Vec<_> = stdout.lines().filter(|line| line.starts_with('[')).collect();
+    assert_eq!(entries, [
+        "[MODIFIED] $.spec.containers[0].image (L1:L1): \"app:1.0\" -> \"app:1.1\"",
+        "[MODIFIED] $.spec.containers[1].image (L1:L1): \"pg:15\" -> \"pg:16\"",
+    ]);
+
+    let output = run_json_diff_raw(left, right, &["--select", "$.spec.replicas", "--select", "$.spec.containers[0]", "--format", "json"]);
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let mut paths: Vec<_> = result["entries"].as_array().unwrap().iter().map(|entry| entry["path"].as_str().unwrap()).collect();
+    // In the order of the keys, which is the input's with the key-order feature
+    paths.sort();
+    assert_eq!(paths, ["$.spec.containers[0].image", "$.spec.containers[0].name", "$.spec.replicas"]);
+    assert_eq!(result["selections"], serde_json::json!(["$.spec.replicas", "$.spec.containers[0]"]));
+}
+
+/// Every output format of the sample pair under its profile, compared byte for byte
+/// against `tests/golden/<format>.golden`; run with `UPDATE_GOLDENS=1` to write the
+/// current outputs there instead, then review them with `git diff`
+///
+/// Outputs that follow the order of object keys differ with the key-order feature, so
+/// its builds compare them against `<format>.key-order.golden` where that exists.
+#[test]
+fn test_cli_golden_outputs() {
+    use clap::ValueEnum;
+    let update = std::env::var_os("UPDATE_GOLDENS").is_some_and(|value| value == "1");
+    let golden_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut mismatches = Vec::new();
+
+    for format in json_diff_cli::OutputFormat::value_variants() {
+        let name = format.to_possible_value().unwrap().get_name().to_string();
+        // Relative file names and a fixed time, so nothing depends on the checkout or the clock
+        let output = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+            .current_dir(fixture(""))
+            .args(["sample1.json", "sample2.json", "--profile", "profile.toml", "--format", &name])
+            .env("SOURCE_DATE_EPOCH", "1700000000")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}: {}", name, String::from_utf8_lossy(&output.stderr));
+        let actual = String::from_utf8(output.stdout).unwrap().replace(json_diff_core::GENERATOR, "json-diff <VERSION>");
+
+        let mut path = golden_dir.join(format!("{}.golden", name));
+        let key_order_path = golden_dir.join(format!("{}.key-order.golden", name));
+        if cfg!(feature = "key-order") {
+            if update {
+                // Only kept where the output differs from the default build's
+                match fs::read_to_string(&path).is_ok_and(|expected| expected == actual) {
+                    true => { let _ = fs::remove_file(&key_order_path); }
+                    false => fs::write(&key_order_path, &actual).unwrap(),
+                }
+                continue;
+            }
+            if key_order_path.exists() {
+                path = key_order_path;
+            }
+        }
+        if update {
+            fs::write(&path, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_default();
+        if expected != actual {
+            mismatches.push(format!("{}\n{}", path.display(), unified_diff(&expected, &actual)));
+        }
+    }
+
+    assert!(mismatches.is_empty(), "Outputs differ from their goldens; rerun with UPDATE_GOLDENS=1 to accept them\n\n{}", mismatches.join("\n"));
+}
+
+/// `expected` against `actual` as a unified diff, with three lines of context around changes
+fn unified_diff(expected: &str, actual: &str) -> String {
+    const CONTEXT: usize = 3;
+    let (old, new): (Vec<&str>, Vec<&str>) = (expected.lines().collect(), actual.lines().collect());
+
+    // Length of the longest common subsequence of the lines from each pair of positions on
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] { common[i + 1][j + 1] + 1 } else { common[i + 1][j].max(common[i][j + 1]) };
+        }
+    }
+    // Each line kept, added or removed, with the positions it was found at
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i], i, j));
+            (i, j) = (i + 1, j + 1);
+        } else if j < new.len() && (i == old.len() || common[i][j + 1] >= common[i + 1][j]) {
+            lines.push(('+', new[j], i, j));
+            j += 1;
+        } else {
+            lines.push(('-', old[i], i, j));
+            i += 1;
+        }
+    }
+
+    let changed: Vec<usize> = lines.iter().enumerate().filter(|(_, line)| line.0 != ' ').map(|(index, _)| index).collect();
+    if changed.is_empty() {
+        return "(only the line endings or the final newline differ)\n".to_string();
+    }
+    let mut text = String::from("--- golden\n+++ actual\n");
+    let mut next = 0;
+    while next < changed.len() {
+        // Changes closer than twice the context share a hunk
+        let start = changed[next].saturating_sub(CONTEXT);
+        while next + 1 < changed.len() && changed[next + 1] <= changed[next] + 2 * CONTEXT + 1 {
+            next += 1;
+        }
+        let hunk = &lines[start..(changed[next] + CONTEXT + 1).min(lines.len())];
+        next += 1;
+
+        let count = |skipped: char| hunk.iter().filter(|line| line.0 != skipped).count();
+        text.push_str(&format!("@@ -{},{} +{},{} @@\n", hunk[0].2 + 1, count('+'), hunk[0].3 + 1, count('-')));
+        for (sign, line, ..) in hunk {
+            text.push_str(&format!("{}{}\n", sign, line));
+        }
+    }
+    text
+}
+
+#[test]
+fn test_cli_unicode_fixture() {
+    let dir = tempdir().unwrap();
+    let profile = dir.path().join("rules.toml");
+    fs::write(&profile, "ignore = [\"$.🎉 party.🍕\", \"$.名*\"]\n").unwrap();
+    let (left, right) = (fixture("unicode_left.json"), fixture("unicode_right.json"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .args([&left, &right, "--profile", profile.to_str().unwrap(), "--max-value-chars", "6"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let entries: Vec<_> = stdout.lines().filter(|line| line.starts_with('[')).collect();
+    // Values are cut between characters as they are shown: the decomposed é of Chloé,
+    // the joined family and the flags stay whole
+    assert_eq!(entries, [
+        "[IGNORED] $.名前 (L2:L2): [IGNORED]",
+        "[IGNORED] $.🎉 party.🍕 (L4:L4): [IGNORED]",
+        "[ADDED] $.🎉 party.guests[2] (L5:L5): \"Chloe\u{301}…(+1 chars)",
+        "[MODIFIED] $.cafe\u{301} (L7:L7): \"nai\u{308}ve…(+1 chars) -> \"naïve…(+1 chars)",
+        "[REMOVED] $.ключ (L8:L7): \"значе…(+4 chars)",
+        "[MODIFIED] $.שלום (L9:L8): \"עולם\" -> \"עולם!…(+1 chars)",
+        "[MODIFIED] $.family (L10:L9): \"👨\u{200d}👩\u{200d}👧\u{200d}👦\" -> \"👨\u{200d}👩\u{200d}👧\"",
+        "[MODIFIED] $.flag (L11:L10): \"🇯🇵\" -> \"🇫🇷\"",
+        "[MODIFIED] $.description (L12:L11): \"こんにちは…(+15 chars) -> \"こんにちは…(+17 chars)",
+        "[ADDED] $.新しい (L12:L12): true",
+    ]);
+
+    // Wide characters take two columns of the side-by-side rendering
+    let output = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .args([&left, &right, "--format", "side-by-side", "--width", "50"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    for row in [
+        "   2   \"名前\": \"山田太… |    2   \"名前\": \"山田花…",
+        "   7   \"cafe\u{301}\": \"nai\u{308}ve\", |    7   \"cafe\u{301}\": \"naïve\",",
+        "   9   \"שלום\": \"עולם\",  |    8   \"שלום\": \"עולם!\",",
+        "                        >   12   \"新しい\": true",
+    ] {
+        assert!(stdout.lines().any(|line| line == row), "{:?} not in\n{}", row, stdout);
+    }
+}
+
+#[test]
+fn test_cli_extended_exit_code() {
+    let dir = tempdir().unwrap();
+    let facts_path = dir.path().join("facts.json");
+    let ignore_ts = "ignore = [\"$.ts\"]";
+    let run = |left: &str, right: &str, extra: &[&str]| {
+        let mut args = vec!["--exit-code", "extended", "--profile-inline", ignore_ts, "--report-facts", facts_path.to_str().unwrap()];
+        args.extend_from_slice(extra);
+        let output = run_json_diff_raw(left, right, &args);
+        (output.status.code(), String::from_utf8_lossy(&output.stderr).into_owned())
+    };
+    let facts = || serde_json::from_str::<serde_json::Value>(&fs::read_to_string(&facts_path).unwrap()).unwrap();
+
+    let (code, stderr) = run(r#"{"a": 1}"#, r#"{"a": 1}"#, &[]);
+    assert_eq!(code, Some(0));
+    assert!(stderr.contains("Outcome: identical (exit code 0)"), "{}", stderr);
+    assert_eq!((facts()["classification"].as_str(), facts()["exit_code"].as_i64()), (Some("identical"), Some(0)));
+
+    // An ignored path is reported whether or not its values differ
+    let (code, stderr) = run(r#"{"a": 1, "ts": 5}"#, r#"{"a": 1, "ts": 5}"#, &[]);
+    assert_eq!(code, Some(3));
+    assert!(stderr.contains("Outcome: only ignored or tolerated differences (exit code 3)"), "{}", stderr);
+    assert_eq!((facts()["classification"].as_str(), facts()["exit_code"].as_i64()), (Some("only_ignored"), Some(3)));
+
+    let (code, stderr) = run(r#"{"a": 1, "ts": 5}"#, r#"{"a": 2, "ts": 6}"#, &["--format", "json"]);
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("Outcome: different (exit code 1)"), "{}", stderr);
+    assert_eq!((facts()["classification"].as_str(), facts()["exit_code"].as_i64()), (Some("different"), Some(1)));
+
+    // Every failure is 2, even those with codes of their own otherwise
+    let (code, stderr) = run(r#"{"a": 1}"#, "{not json", &[]);
+    assert_eq!(code, Some(2));
+    assert!(!stderr.contains("Outcome"), "{}", stderr);
+    let output = run_json_diff_raw(r#"{"a": 1}"#, r#"{"a": 1}"#, &["--exit-code", "extended", "--interactive", "--require-tty"]);
+    assert_eq!(output.status.code(), Some(2));
+
+    // Without the flag, differences still exit 0
+    let output = run_json_diff_raw(r#"{"a": 1}"#, r#"{"a": 2}"#, &[]);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_cli_text_fallback() {
+    // A missing comma before "debug" on the left
+    let left = "{\n  \"name\": \"api\",\n  \"port\": 80\n  \"debug\": true\n}\n";
+    let right = "{\n  \"name\": \"api\",\n  \"port\": 8080,\n  \"debug\": true,\n  \"tls\": true\n}\n";
+
+    let output = run_json_diff_raw(left, right, &[]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Failed to parse JSON: left: expected `,` or `}` at line 4"));
+
+    let output = run_json_diff_raw(left, right, &["--text-fallback", "--exit-code", "extended"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(r#"[MODIFIED] <line 3> (L3:L3): "  \"port\": 80" -> "  \"port\": 8080,""#), "{}", stdout);
+    assert!(stdout.contains(r#"[ADDED] <line 5> (L4:L5): "  \"tls\": true""#), "{}", stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Warning: Failed to parse JSON: left: ") && stderr.contains("structural comparison was not possible"), "{}", stderr);
+
+    let output = run_json_diff_raw(left, right, &["--text-fallback", "--format", "json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["entries"][0]["path"], "<line 3>");
+    assert!(report["warnings"][0].as_str().unwrap().ends_with("the texts were compared line by line"));
+    let output = run_json_diff_raw(left, right, &["--profile-inline", "text_fallback = true"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("[MODIFIED] <line 3>"));
+
+    // Batch mode compares such a pair instead of reporting it unparseable
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("left.json"), left).unwrap();
+    fs::write(dir.path().join("right.json"), right).unwrap();
+    let manifest = dir.path().join("manifest.txt");
+    fs::write(&manifest, "left.json\tright.json\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .args(["batch", "--text-fallback", "--no-timestamp"])
+        .arg(&manifest)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("right.json: 3 differences\n  warning: Failed to parse JSON: left: "), "{}", stdout);
+    assert!(stdout.contains("  Differing:   1\n  Unparseable: 0\n"), "{}", stdout);
+}
+
+#[test]
+fn test_cli_width_wraps_entry_lines() {
+    let left = r#"{"note": "x", "title": "the old title"}"#;
+    let right = r#"{"note": "y", "title": "a new, much longer title for the page"}"#;
+
+    let output = run_json_diff_raw(left, right, &["--width", "50"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[MODIFIED] $.note (L1:L1): \"x\" -> \"y\"\n"), "{}", stdout);
+    assert!(stdout.contains(concat!(
+        "[MODIFIED] $.title (L1:L1): \"the old title\"\n",
+        "                         -> \"a new, much longer\n",
+        "                         title for the page\"\n",
+    )), "{}", stdout);
+
+    // Not wrapped by default, nor with 0
+    for args in [&[][..], &["--width", "0"]] {
+        let stdout = String::from_utf8_lossy(&run_json_diff_raw(left, right, args).stdout).into_owned();
+        assert!(stdout.contains("\"the old title\" -> \"a new, much longer title for the page\"\n"), "{}", stdout);
+    }
+}
+
+#[test]
+fn test_cli_strict_rules() {
+    let left = r#"{"id": 1, "tags": ["a", "b"], "meta": {"ts": 1}}"#;
+    let right = r#"{"id": 2, "tags": ["a", "b"], "meta": {"ts": 1}}"#;
+
+    // Rules matching paths whose values are equal are fine
+    let output = run_json_diff_raw(left, right, &["--strict-rules", "--profile-inline", r#"ignore = ["$.meta.ts"]
+unordered = ["$.tags"]"#]);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("[MODIFIED] $.id (L1:L1): 1 -> 2"));
+
+    // Rules matching no path of either file fail the run, each of them named
+    let profile = r#"ignore = ["$.meta.ts", "$.meta.created_at"]
+unordered = ["$.labels"]"#;
+    let output = run_json_diff_raw(left, right, &["--strict-rules", "--profile-inline", profile]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Error: 2 rules match no path of either file:\n  ignore `$.meta.created_at`\n  unordered `$.labels`\n"), "{}", stderr);
+    assert!(!stderr.contains("`$.meta.ts`"), "{}", stderr);
+
+    // Without the flag they do not matter
+    assert_eq!(run_json_diff_raw(left, right, &["--profile-inline", profile]).status.code(), Some(0));
+
+    // The run facts record the code the run exits with
+    let dir = tempdir().unwrap();
+    let facts = dir.path().join("facts.json");
+    let output = run_json_diff_raw(left, right, &["--strict-rules", "--report-facts", facts.to_str().unwrap(), "--profile-inline", r#"ignore = ["$.nope"]"#]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error: 1 rule matches no path of either file:\n  ignore `$.nope`"));
+    let facts: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&facts).unwrap()).unwrap();
+    assert_eq!(facts["exit_code"], 2);
+}
+
+#[test]
+fn test_cli_explain_rules() {
+    let dir = tempdir().unwrap();
+    let profile = dir.path().join("rules.toml");
+    std::fs::write(&profile, "ignore = [\"$.id\", \"$.never\"]\n").unwrap();
+    let left = "{\n  \"id\": 1,\n  \"name\": \"a\"\n}";
+    let right = "{\n  \"id\": 2,\n  \"name\": \"b\"\n}";
+
+    let output = run_json_diff_raw(left, right, &["--explain-rules", "--profile", profile.to_str().unwrap(), "--no-timestamp"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let rows: Vec<Vec<&str>> = stderr.lines().map(|line| line.split_whitespace().collect()).collect();
+    assert_eq!(rows[0], ["Ignore", "rule", "Matched", "Tested", "Time"]);
+    assert!(rows[1..].iter().any(|row| row[..3] == ["$.id", "1", "3"]), "{}", stderr);
+    assert!(rows[1..].iter().any(|row| row[..3] == ["$.never", "0", "3"]), "{}", stderr);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("[MODIFIED] $.name"));
+}
+
+#[test]
+fn test_cli_group_related() {
+    let left = "{\n  \"config\": {\n    \"old\": 1\n  },\n  \"name\": \"a\"\n}";
+    let right = "{\n  \"name\": \"b\",\n  \"config\": {\n    \"new\": 1\n  }\n}";
+
+    let output = run_json_diff_raw(left, right, &["--group-related", "--no-timestamp"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let removed = stdout.find("[REMOVED] $.config.old").expect("removed entry");
+    let added = stdout.find("[ADDED] $.config.new").expect("added entry");
+    let modified = stdout.find("[MODIFIED] $.name").expect("modified entry");
+    assert!(removed < added && added < modified, "{}", stdout);
+}
+
+#[test]
+fn test_cli_max_value_chars() {
+    let long = "a".repeat(2000);
+    let left = format!("{{\"blob\": \"{}\"}}", long);
+    let right = "{\"blob\": \"b\"}";
+
+    let output = run_json_diff_raw(&left, right, &["--no-timestamp"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("…(+1490 chars) -> \"b\""), "{}", stdout);
+
+    let output = run_json_diff_raw(&left, right, &["--no-timestamp", "--max-value-chars", "0"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&format!("\"{}\" -> \"b\"", long)), "{}", stdout);
+
+    // The JSON output is never truncated
+    let output = run_json_diff_raw(&left, right, &["--format", "json", "--max-value-chars", "10"]);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["entries"][0]["old_value"], serde_json::json!(long));
+}
+
+#[test]
+fn test_cli_generator_and_no_metadata() {
+    let left = r#"{"id": 1}"#;
+    let right = r#"{"id": 2}"#;
+    let generator = format!("json-diff {}", env!("CARGO_PKG_VERSION"));
+
+    let output = run_json_diff_raw(left, right, &["--format", "json"]);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["generator"], serde_json::json!(generator));
+    assert!(json.get("hostname").is_none());
+
+    let output = run_json_diff_raw(left, right, &["--format", "json", "--hostname"]);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(json["hostname"].is_string(), "{}", json);
+
+    let output = run_json_diff_raw(left, right, &["--format", "html"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&format!("<tr><th>Generator</th><td>{}</td></tr>", generator)));
+
+    // Only the entries are left
+    let output = run_json_diff_raw(left, right, &["--no-metadata"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "DIFF-JSON v1\n\n[MODIFIED] $.id (L1:L1): 1 -> 2\n\n");
+    let output = run_json_diff_raw(left, right, &["--no-metadata", "--format", "json"]);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let mut keys: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+    keys.sort();
+    assert_eq!(keys, ["entries", "left_file", "right_file", "timestamp"]);
+    assert!(json["left_file"].is_null() && json["timestamp"].is_null());
+}
+
+#[test]
+fn test_cli_presets() {
+    let left = r#"{"total": 1, "requestId": "a", "tags": ["x"]}"#;
+    let right = r#"{"total": 1.0, "requestId": "b", "tags": ["y"]}"#;
+    let stdout = |args: &[&str]| String::from_utf8(run_json_diff_raw(left, right, args).stdout).unwrap();
+
+    let output = stdout(&["--preset", "api-response"]);
+    assert!(output.contains("[IGNORED] $.requestId"), "{}", output);
+    assert!(!output.contains("$.total"), "{}", output);
+
+    // Flags and the profile override the preset
+    let output = stdout(&["--preset", "api-response", "--numeric-equivalence=false"]);
+    assert!(output.contains("[MODIFIED] $.total (L1:L1): 1 -> 1.0"), "{}", output);
+    let dir = tempdir().unwrap();
+    let profile = dir.path().join("rules.toml");
+    fs::write(&profile, "numeric_equivalence = false\nignore = [\"$.tags\"]\n").unwrap();
+    let output = stdout(&["--preset", "api-response", "--profile", profile.to_str().unwrap()]);
+    assert!(output.contains("[MODIFIED] $.total"), "{}", output);
+    // The profile's rules are added to the preset's
+    assert!(output.contains("[IGNORED] $.requestId") && output.contains("[IGNORED] $.tags"), "{}", output);
+
+    let output = stdout(&["--preset", "config-drift"]);
+    assert!(output.contains("[MODIFIED] $.tags (L1:L1): [\"x\"] -> [\"y\"]"), "{}", output);
+    let output = stdout(&["--preset", "config-drift", "--identify-array-item-changes"]);
+    assert!(output.contains("[MODIFIED] $.tags[0]"), "{}", output);
+
+    let output = stdout(&["--preset", "strict"]);
+    assert!(output.contains("[ARRAY_ITEM_CHANGED] $.tags[0]"), "{}", output);
+
+    let output = run_json_diff_raw(left, right, &["--preset", "lax"]);
+    assert!(!output.status.success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_json-diff")).arg("--list-presets").output().unwrap();
+    let listing = String::from_utf8(output.stdout).unwrap();
+    for name in ["api-response: ", "config-drift: ", "strict: "] {
+        assert!(listing.contains(name), "{}", listing);
+    }
+    assert!(listing.contains("  numeric_equivalence = true\n"), "{}", listing);
+    assert!(listing.contains("  max_value_size = 1024\n"), "{}", listing);
+}
+
+#[test]
+fn test_cli_roots() {
+    let left = "{\n  \"data\": {\n    \"result\": {\n      \"id\": 1\n    }\n  }\n}";
+    let right = "{\n  \"payload\": {\n    \"id\": 2\n  }\n}";
+
+    let output = run_json_diff_raw(left, right, &["--left-root", "$.data.result", "--right-root", "$.payload", "--no-timestamp"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[MODIFIED] $.id (L4:L3): 1 -> 2"), "{}", stdout);
+
+    let output = run_json_diff_raw(left, right, &["--left-root", "$.payload"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid left root $.payload: no value at this path"));
+}
+
+#[test]
+fn test_cli_batch() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("a.json"), r#"{"id": 1}"#).unwrap();
+    fs::write(dir.path().join("b.json"), r#"{"id": 2}"#).unwrap();
+    fs::write(dir.path().join("broken.json"), r#"{"id": "#).unwrap();
+    let manifest = dir.path().join("manifest.txt");
+    fs::write(&manifest, "a.json\ta.json\na.json\tb.json\n# a broken pair\na.json\tbroken.json\nb.json\tmissing.json\nb.json\tb.json\n").unwrap();
+
+    let run = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_json-diff"))
+        .arg("batch")
+        .arg(&manifest)
+        .args(args)
+        .args(["--jobs", "2", "--no-timestamp"])
+        .output()
+        .unwrap();
+
+    let output = run(&[]);
+    assert_eq!(output.status.code(), Some(2));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let a = dir.path().join("a.json");
+    assert!(stdout.contains(&format!("=== Line 1: {0} <-> {0}: equal\n", a.display())), "{}", stdout);
+    assert!(stdout.contains("=== Line 2: ") && stdout.contains("b.json: 1 difference\n"));
+    assert!(stdout.contains("[MODIFIED] $.id (L1:L1): 1 -> 2"));
+    // The corrupt file is reported with its parse error, the pairs after it are still compared
+    assert!(stdout.contains("broken.json: unparseable\n  right: EOF while parsing a value at line 1 column 7\n"), "{}", stdout);
+    assert!(stdout.contains("missing.json: error: No such file"), "{}", stdout);
+    assert!(stdout.ends_with("SUMMARY\n  Pairs:       5\n  Equal:       2\n  Differing:   1\n  Unparseable: 1\n  Errored:     1\n"), "{}", stdout);
+
+    let output = run(&["--format", "json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let statuses: Vec<_> = report["pairs"].as_array().unwrap().iter().map(|p| p["status"].as_str().unwrap()).collect();
+    assert_eq!(statuses, vec!["equal", "differing", "unparseable", "error", "equal"]);
+    assert!(report["pairs"][2]["right_error"].as_str().unwrap().starts_with("EOF while parsing"));
+    assert!(report["pairs"][2].get("left_error").is_none());
+    assert_eq!(report["summary"], serde_json::json!({"pairs": 5, "equal": 2, "differing": 1, "unparseable": 1, "errored": 1}));
+
+    // An unparseable pair alone gives exit code 2
+    fs::write(&manifest, "a.json\tbroken.json\n").unwrap();
+    assert_eq!(run(&[]).status.code(), Some(2));
+
+    // Without the broken pair, differences alone give exit code 1
+    fs::write(&manifest, "a.json\tb.json\n").unwrap();
+    assert_eq!(run(&[]).status.code(), Some(1));
+
+    // A file past the size limit fails its pair, with or without a profile
+    fs::write(dir.path().join("rules.toml"), "ignore = []\n").unwrap();
+    fs::write(&manifest, "a.json\tb.json\na.json\tb.json\trules.toml\n").unwrap();
+    let output = run(&["--max-input-size", "4"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches("too large (9 bytes, limit is 4 bytes)").count(), 2, "{}", stdout);
+}